@@ -8,17 +8,37 @@ pub enum Statement<'a> {
     Block(Body<'a>),
     Function {
         name: &'a str,
+        // Type parameters, which appear in the signature as Named types
+        generics: Vec<&'a str>,
+        // T: Interface, the interface a type parameter has to implement
+        bounds: Vec<(&'a str, &'a str)>,
         args: Vec<TypedIdentifier<'a>>,
+        // Where each arg's name is written, empty for functions the compiler
+        // makes itself
+        arg_spans: Vec<Span>,
+        // Default values for the last defaults.len() args, before the
+        // variadic one if there is one
+        defaults: Vec<ExpressionNode<'a>>,
+        // The last arg is a slice collecting the rest of the arguments
+        variadic: bool,
         returns: AzulaType<'a>,
         body: Rc<Statement<'a>>,
+        // pub, visible outside its module and exported from the object file
+        public: bool,
+        // @name hints written before the function, for codegen
+        attributes: Vec<Attribute<'a>>,
+        // The /// comments above the function
+        doc: Option<String>,
         span: Span,
     },
     Return(Option<ExpressionNode<'a>>, Span),
+    // The attributes can only be given to globals
     Assign(
         bool,
         String,
         Option<AzulaType<'a>>,
         ExpressionNode<'a>,
+        Vec<Attribute<'a>>,
         Span,
     ),
     ExpressionStatement(ExpressionNode<'a>, Span),
@@ -26,17 +46,212 @@ pub enum Statement<'a> {
     ExternFunction {
         name: &'a str,
         varargs: bool,
-        args: Vec<AzulaType<'a>>,
+        // Params can be named, `puts(s: string)`, or just typed, `puts(string)`
+        args: Vec<(AzulaType<'a>, Option<&'a str>)>,
         returns: AzulaType<'a>,
         span: Span,
     },
     Reassign(ExpressionNode<'a>, ExpressionNode<'a>, Span),
-    While(ExpressionNode<'a>, Body<'a>, Span),
+    // Loops can be labeled, `outer: while ...`, for a break or continue in a
+    // nested loop to name
+    While(ExpressionNode<'a>, Body<'a>, Option<&'a str>, Span),
+    // do { body } while cond;, the body runs once before cond is checked
+    DoWhile(ExpressionNode<'a>, Body<'a>, Option<&'a str>, Span),
+    // for x in xs { body }, over the elements of an array or slice or the
+    // values of a range, and for i, x in xs with their positions as well
+    For(
+        Option<&'a str>,
+        &'a str,
+        ExpressionNode<'a>,
+        Body<'a>,
+        Option<&'a str>,
+        Span,
+    ),
+    // break and continue, with the label of the loop they're for if it isn't
+    // the innermost
+    Break(Option<&'a str>, Span),
+    Continue(Option<&'a str>, Span),
+    // { body } on its own, whose variables go out of scope at its end
+    Scope(Body<'a>, Span),
     Struct {
         name: &'a str,
+        generics: Vec<&'a str>,
         attributes: Vec<TypedIdentifier<'a>>,
+        // Values for the attributes that have one, used when a literal
+        // leaves them out
+        defaults: Vec<(&'a str, ExpressionNode<'a>)>,
+        // The @name(args) written before it, unlike the attributes above
+        // which are its fields
+        annotations: Vec<Attribute<'a>>,
+        doc: Option<String>,
         span: Span,
     },
+    Match(ExpressionNode<'a>, Vec<MatchArm<'a>>, Span),
+    // var (a, b) = value;
+    Destructure(bool, Vec<&'a str>, ExpressionNode<'a>, Span),
+    // if var x = optional { ... }
+    IfLet(bool, &'a str, ExpressionNode<'a>, Body<'a>, Span),
+    // Runs when the function returns
+    Defer(ExpressionNode<'a>, Span),
+    // The methods a type has to have to implement the interface, with
+    // self typed as Self
+    Interface {
+        name: &'a str,
+        methods: Vec<MethodSignature<'a>>,
+        span: Span,
+    },
+    // impl Interface for Type, the methods are functions taking self first
+    Impl {
+        interface: &'a str,
+        typ: AzulaType<'a>,
+        methods: Vec<Statement<'a>>,
+        span: Span,
+    },
+    // import name; its body is the declarations of name.azl, filled in by
+    // the parser's resolve_imports
+    Import(&'a str, Body<'a>, Span),
+    // @if(key = "value", ...) declaration, replaced by the declaration or
+    // removed once the target is known
+    Conditional(Vec<(&'a str, &'a str)>, Rc<Statement<'a>>, Span),
+}
+
+impl<'a> Statement<'a> {
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Statement::Root(_) | Statement::Block(_) => None,
+            Statement::Function { span, .. }
+            | Statement::ExternFunction { span, .. }
+            | Statement::Struct { span, .. }
+            | Statement::Return(_, span)
+            | Statement::Assign(.., span)
+            | Statement::ExpressionStatement(_, span)
+            | Statement::If(_, _, span)
+            | Statement::Reassign(_, _, span)
+            | Statement::While(.., span)
+            | Statement::DoWhile(.., span)
+            | Statement::For(.., span)
+            | Statement::Match(_, _, span)
+            | Statement::Destructure(.., span)
+            | Statement::IfLet(.., span)
+            | Statement::Defer(_, span)
+            | Statement::Break(_, span)
+            | Statement::Continue(_, span)
+            | Statement::Scope(_, span)
+            | Statement::Interface { span, .. }
+            | Statement::Impl { span, .. }
+            | Statement::Import(_, _, span)
+            | Statement::Conditional(_, _, span) => Some(span.clone()),
+        }
+    }
+
+    /// Moves every span in the statement along by offset, for placing a
+    /// file's tree after the files before it
+    pub fn shift_spans(&mut self, offset: usize) {
+        let shift_body = |body: &mut Body<'a>| {
+            for stmt in body {
+                stmt.shift_spans(offset);
+            }
+        };
+        let shift_attributes = |attributes: &mut Vec<Attribute<'a>>| {
+            for attribute in attributes {
+                for arg in &mut attribute.args {
+                    arg.shift_spans(offset);
+                }
+                attribute.span.shift(offset);
+            }
+        };
+
+        match self {
+            Statement::Root(body) | Statement::Block(body) => shift_body(body),
+            Statement::Function {
+                arg_spans,
+                defaults,
+                body,
+                attributes,
+                span,
+                ..
+            } => {
+                for arg in arg_spans {
+                    arg.shift(offset);
+                }
+                for default in defaults {
+                    default.shift_spans(offset);
+                }
+                shift_attributes(attributes);
+                Rc::make_mut(body).shift_spans(offset);
+                span.shift(offset);
+            }
+            Statement::Return(value, span) => {
+                if let Some(value) = value {
+                    value.shift_spans(offset);
+                }
+                span.shift(offset);
+            }
+            Statement::Assign(_, _, _, value, attributes, span) => {
+                value.shift_spans(offset);
+                shift_attributes(attributes);
+                span.shift(offset);
+            }
+            Statement::ExpressionStatement(value, span)
+            | Statement::Destructure(_, _, value, span)
+            | Statement::Defer(value, span) => {
+                value.shift_spans(offset);
+                span.shift(offset);
+            }
+            Statement::If(cond, body, span)
+            | Statement::While(cond, body, _, span)
+            | Statement::DoWhile(cond, body, _, span)
+            | Statement::For(_, _, cond, body, _, span)
+            | Statement::IfLet(_, _, cond, body, span) => {
+                cond.shift_spans(offset);
+                shift_body(body);
+                span.shift(offset);
+            }
+            Statement::Reassign(target, value, span) => {
+                target.shift_spans(offset);
+                value.shift_spans(offset);
+                span.shift(offset);
+            }
+            Statement::Match(value, arms, span) => {
+                value.shift_spans(offset);
+                for (_, _, body, arm) in arms {
+                    shift_body(body);
+                    arm.shift(offset);
+                }
+                span.shift(offset);
+            }
+            Statement::Impl {
+                methods: body,
+                span,
+                ..
+            }
+            | Statement::Import(_, body, span)
+            | Statement::Scope(body, span) => {
+                shift_body(body);
+                span.shift(offset);
+            }
+            Statement::Conditional(_, declaration, span) => {
+                Rc::make_mut(declaration).shift_spans(offset);
+                span.shift(offset);
+            }
+            Statement::Struct {
+                defaults,
+                annotations,
+                span,
+                ..
+            } => {
+                for (_, default) in defaults {
+                    default.shift_spans(offset);
+                }
+                shift_attributes(annotations);
+                span.shift(offset);
+            }
+            Statement::ExternFunction { span, .. }
+            | Statement::Interface { span, .. }
+            | Statement::Break(_, span)
+            | Statement::Continue(_, span) => span.shift(offset),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -52,11 +267,41 @@ pub enum Expression<'a> {
         args: Vec<ExpressionNode<'a>>,
     },
     Not(Rc<ExpressionNode<'a>>),
+    Negate(Rc<ExpressionNode<'a>>),
     Pointer(Rc<ExpressionNode<'a>>),
+    // *p, the value p points to
+    Deref(Rc<ExpressionNode<'a>>),
     Array(Vec<ExpressionNode<'a>>),
+    // [value; count] with a count that isn't a literal, which the typechecker
+    // evaluates and expands
+    ArrayRepeat(Rc<ExpressionNode<'a>>, Rc<ExpressionNode<'a>>),
     ArrayAccess(Rc<ExpressionNode<'a>>, Rc<ExpressionNode<'a>>),
     StructInitialisation(Rc<ExpressionNode<'a>>, Vec<(&'a str, ExpressionNode<'a>)>),
     StructAccess(Rc<ExpressionNode<'a>>, Rc<ExpressionNode<'a>>),
+    Cast(Rc<ExpressionNode<'a>>, AzulaType<'a>),
+    // sizeof(type), the size of the type in bytes on the target
+    SizeOf(AzulaType<'a>),
+    // new T, a pointer to room for a T on the heap
+    New(AzulaType<'a>),
+    // cond ? then : otherwise
+    Ternary(
+        Rc<ExpressionNode<'a>>,
+        Rc<ExpressionNode<'a>>,
+        Rc<ExpressionNode<'a>>,
+    ),
+    Tuple(Vec<ExpressionNode<'a>>),
+    // start..end, a Range<T> struct of the two bounds
+    Range(Rc<ExpressionNode<'a>>, Rc<ExpressionNode<'a>>),
+    // value? along with the result type of the enclosing function
+    Try(Rc<ExpressionNode<'a>>, AzulaType<'a>),
+    // name: value in a call, the typechecker moves it to the param's place
+    NamedArgument(&'a str, Rc<ExpressionNode<'a>>),
+    // |x: int| body, the variables it captures are filled in by the typechecker
+    Closure {
+        args: Vec<TypedIdentifier<'a>>,
+        body: Rc<ExpressionNode<'a>>,
+        captures: Vec<(AzulaType<'a>, String)>,
+    },
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -66,6 +311,61 @@ pub struct ExpressionNode<'a> {
     pub span: Span,
 }
 
+impl<'a> ExpressionNode<'a> {
+    pub fn shift_spans(&mut self, offset: usize) {
+        let shift = |node: &mut Rc<ExpressionNode<'a>>| Rc::make_mut(node).shift_spans(offset);
+
+        match &mut self.expression {
+            Expression::Infix(left, _, right)
+            | Expression::ArrayAccess(left, right)
+            | Expression::ArrayRepeat(left, right)
+            | Expression::StructAccess(left, right)
+            | Expression::Range(left, right) => {
+                shift(left);
+                shift(right);
+            }
+            Expression::FunctionCall { function, args } => {
+                shift(function);
+                for arg in args {
+                    arg.shift_spans(offset);
+                }
+            }
+            Expression::Not(value)
+            | Expression::Negate(value)
+            | Expression::Pointer(value)
+            | Expression::Deref(value)
+            | Expression::Cast(value, _)
+            | Expression::Try(value, _)
+            | Expression::NamedArgument(_, value)
+            | Expression::Closure { body: value, .. } => shift(value),
+            Expression::Array(values) | Expression::Tuple(values) => {
+                for value in values {
+                    value.shift_spans(offset);
+                }
+            }
+            Expression::StructInitialisation(name, values) => {
+                shift(name);
+                for (_, value) in values {
+                    value.shift_spans(offset);
+                }
+            }
+            Expression::Ternary(cond, then, otherwise) => {
+                shift(cond);
+                shift(then);
+                shift(otherwise);
+            }
+            Expression::Integer(_)
+            | Expression::Float(_)
+            | Expression::Identifier(_)
+            | Expression::Boolean(_)
+            | Expression::String(_)
+            | Expression::SizeOf(_)
+            | Expression::New(_) => {}
+        }
+        self.span.shift(offset);
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Hash, Eq)]
 pub enum Operator {
     Add,
@@ -82,10 +382,35 @@ pub enum Operator {
     Lte,
     Gt,
     Gte,
+    Shl,
+    Shr,
+}
+
+/// `@name` or `@name(args)` before a declaration
+#[derive(Debug, PartialEq, Clone)]
+pub struct Attribute<'a> {
+    pub name: &'a str,
+    pub args: Vec<ExpressionNode<'a>>,
+    pub span: Span,
+}
+
+impl<'a> Attribute<'a> {
+    pub fn new(name: &'a str) -> Self {
+        Attribute {
+            name,
+            args: vec![],
+            span: Span { start: 0, end: 0 },
+        }
+    }
 }
 
 pub type Body<'a> = Vec<Statement<'a>>;
 pub type TypedIdentifier<'a> = (AzulaType<'a>, &'a str);
+// A type to match against (None for the `_` arm), an optional binding and the
+// body, with the span of the type and binding
+pub type MatchArm<'a> = (Option<AzulaType<'a>>, Option<&'a str>, Body<'a>, Span);
+// A method's name, args and return type
+pub type MethodSignature<'a> = (&'a str, Vec<TypedIdentifier<'a>>, AzulaType<'a>);
 
 // #[derive(Debug, PartialEq, Clone)]
 // pub enum Type<'a> {
@@ -101,3 +426,10 @@ pub struct Span {
     pub start: usize,
     pub end: usize,
 }
+
+impl Span {
+    pub fn shift(&mut self, offset: usize) {
+        self.start += offset;
+        self.end += offset;
+    }
+}
@@ -1,5 +1,8 @@
 mod ast;
 
 pub mod prelude {
-    pub use crate::ast::{Expression, ExpressionNode, Operator, Span, Statement, TypedIdentifier};
+    pub use crate::ast::{
+        Attribute, Expression, ExpressionNode, MatchArm, MethodSignature, Operator, Span,
+        Statement, TypedIdentifier,
+    };
 }
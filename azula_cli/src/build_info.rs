@@ -0,0 +1,53 @@
+use std::{
+    env,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use azula_codegen_llvm::prelude::LLVMCodegen;
+
+/// The string returned by `build_info()` when building with
+/// `--embed-build-info`, one `key=value` pair per line
+pub fn build_info(target: Option<&String>) -> String {
+    let target = match target {
+        Some(target) => target.clone(),
+        None => LLVMCodegen::default_target(),
+    };
+
+    format!(
+        "version={}\ngit={}\nbuilt={}\ntarget={}",
+        env!("CARGO_PKG_VERSION"),
+        git_hash().unwrap_or_else(|| "unknown".to_string()),
+        build_time(),
+        target
+    )
+}
+
+/// The commit of the project being built, not of the compiler
+fn git_hash() -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// SOURCE_DATE_EPOCH keeps builds reproducible for verify-repro and packagers
+fn build_time() -> u64 {
+    if let Some(epoch) = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+    {
+        return epoch;
+    }
+
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(0)
+}
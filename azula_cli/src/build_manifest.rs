@@ -0,0 +1,69 @@
+use std::{error::Error, fs};
+
+use azula_codegen::prelude::{OptimizationLevel, TargetOptions};
+use azula_codegen_llvm::prelude::LLVMCodegen;
+
+use crate::{build_plan::json_string, packages::VERSION};
+
+pub const BUILD_MANIFEST: &str = ".build/manifest.json";
+
+/// Writes what a successful build produced to .build/manifest.json, each
+/// artifact with its kind and a hash of its contents, so tools can pick up
+/// the outputs without reading what the build printed
+pub fn write_manifest(
+    artifacts: &[(&str, String)],
+    target: &TargetOptions,
+    opt_level: OptimizationLevel,
+    warnings: usize,
+) -> Result<(), Box<dyn Error>> {
+    let mut entries = vec![];
+    for (kind, path) in artifacts {
+        let contents = fs::read(path)?;
+        entries.push(format!(
+            "    {{ \"kind\": {}, \"path\": {}, \"size\": {}, \"hash\": \"{:016x}\" }}",
+            json_string(kind),
+            json_string(path),
+            contents.len(),
+            fnv1a(&contents)
+        ));
+    }
+
+    let target = match &target.triple {
+        Some(target) => target.clone(),
+        None => LLVMCodegen::default_target(),
+    };
+    let manifest = format!(
+        concat!(
+            "{{\n",
+            "  \"azula\": {},\n",
+            "  \"target\": {},\n",
+            "  \"opt_level\": {},\n",
+            "  \"artifacts\": [\n{}\n  ],\n",
+            // Only a build without errors writes one
+            "  \"diagnostics\": {{ \"errors\": 0, \"warnings\": {} }}\n",
+            "}}\n"
+        ),
+        json_string(VERSION),
+        json_string(&target),
+        json_string(if opt_level == OptimizationLevel::Aggressive {
+            "aggressive"
+        } else {
+            "default"
+        }),
+        entries.join(",\n"),
+        warnings
+    );
+
+    fs::create_dir_all(".build")?;
+    fs::write(BUILD_MANIFEST, manifest)?;
+
+    Ok(())
+}
+
+// 64 bit FNV-1a, which unlike std's hasher stays the same between compiler
+// versions
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
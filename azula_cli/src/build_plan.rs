@@ -0,0 +1,90 @@
+use azula_codegen::prelude::TargetOptions;
+use azula_codegen_llvm::prelude::LLVMCodegen;
+
+/// Everything `azula build` would do for a file, printed as JSON so other
+/// build systems can mirror the build without running it
+pub struct BuildPlan {
+    // The file being built and every module it imports
+    pub files: Vec<String>,
+    pub name: String,
+    pub destination: String,
+    pub target: TargetOptions,
+    pub emit_llvm: bool,
+    pub release: bool,
+}
+
+impl BuildPlan {
+    pub fn to_json(&self) -> String {
+        let object_file = LLVMCodegen::object_file(&self.name);
+        let executable = format!("{}{}", self.destination, self.name);
+
+        let mut compile_outputs = vec![object_file.clone()];
+        if self.emit_llvm {
+            compile_outputs.push(format!("{}.ll", self.name));
+        }
+
+        let link_command = LLVMCodegen::link_command(&self.name, &self.destination, &self.target);
+
+        format!(
+            concat!(
+                "{{\n",
+                "  \"files\": [{}],\n",
+                "  \"target\": {},\n",
+                "  \"opt_level\": {},\n",
+                "  \"steps\": [\n",
+                "    {{\n",
+                "      \"kind\": \"compile\",\n",
+                "      \"inputs\": [{}],\n",
+                "      \"outputs\": [{}]\n",
+                "    }},\n",
+                "    {{\n",
+                "      \"kind\": \"link\",\n",
+                "      \"inputs\": [{}],\n",
+                "      \"outputs\": [{}],\n",
+                "      \"command\": [{}]\n",
+                "    }}\n",
+                "  ]\n",
+                "}}"
+            ),
+            json_list(&self.files),
+            match &self.target.triple {
+                Some(target) => json_string(target),
+                None => "null".to_string(),
+            },
+            json_string(if self.release {
+                "aggressive"
+            } else {
+                "default"
+            }),
+            json_list(&self.files),
+            json_list(&compile_outputs),
+            json_string(&object_file),
+            json_string(&executable),
+            json_list(&link_command),
+        )
+    }
+}
+
+fn json_list(values: &[String]) -> String {
+    values
+        .iter()
+        .map(|value| json_string(value))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub fn json_string(value: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
@@ -0,0 +1,67 @@
+use std::{fs, process::exit};
+
+use azula_ast::prelude::*;
+use azula_parser::prelude::{print_ast, Lexer, Parser};
+
+/// Prints the documented functions and structs of a file as Markdown, each
+/// with its signature above its docs. Methods are listed under their impl
+pub fn print_docs(file: &str) {
+    let input = fs::read_to_string(file).unwrap();
+    let lexer = Lexer::new(&input);
+    let mut parser = Parser::new(&input, lexer);
+    let root = parser.parse();
+    for error in &parser.errors {
+        print!("{}", error.render(&input, file));
+    }
+    if !parser.errors.is_empty() {
+        exit(1);
+    }
+
+    println!("# {}", file.trim_end_matches(".azl"));
+    if let Statement::Root(body) = root {
+        for stmt in &body {
+            match stmt {
+                Statement::Impl {
+                    interface, methods, ..
+                } => {
+                    for method in methods {
+                        print_doc(method, Some(*interface));
+                    }
+                }
+                stmt => print_doc(stmt, None),
+            }
+        }
+    }
+}
+
+fn print_doc(stmt: &Statement, interface: Option<&str>) {
+    let (doc, signature) = match stmt {
+        // The declaration line is the signature, without the body
+        Statement::Function { doc: Some(doc), .. } => (doc, signature(stmt, false)),
+        // Structs are shown whole, their fields are part of the signature
+        Statement::Struct { doc: Some(doc), .. } => (doc, signature(stmt, true)),
+        _ => return,
+    };
+
+    println!();
+    if let Some(interface) = interface {
+        println!("_impl {}_", interface);
+        println!();
+    }
+    println!("```\n{}\n```\n", signature);
+    println!("{}", doc);
+}
+
+fn signature(stmt: &Statement, whole: bool) -> String {
+    let printed = print_ast(stmt);
+    let mut lines = printed.lines().filter(|line| !line.starts_with("///"));
+    if whole {
+        lines.collect::<Vec<_>>().join("\n")
+    } else {
+        lines
+            .next()
+            .unwrap_or("")
+            .trim_end_matches(" {")
+            .to_string()
+    }
+}
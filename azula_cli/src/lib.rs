@@ -1,13 +1,27 @@
+mod build_info;
+mod build_manifest;
+mod build_plan;
+mod doc;
+mod packages;
+mod repro;
+mod run;
+mod serve;
+
 use std::{
     fs,
-    process::{exit, Command},
+    path::{Path, PathBuf},
+    process::exit,
 };
 
-use azula_codegen::prelude::{Backend, Codegen, OptimizationLevel};
+use azula_codegen::prelude::{Backend, Codegen, OptimizationLevel, TargetOptions};
 use azula_codegen_llvm::prelude::LLVMCodegen;
-use azula_parser::prelude::{Lexer, Parser};
-use azula_typecheck::prelude::Typechecker;
+use azula_error::prelude::SourceMap;
+use azula_ir::prelude::Module;
+use azula_parser::prelude::{resolve_imports, wrap_script, Config, Lexer, Parser};
+use azula_typecheck::prelude::{trace_execution, Typechecker};
+use build_plan::BuildPlan;
 use clap::{StructOpt, Subcommand};
+use packages::{check_versions, package_module, project_root, Source};
 
 /// Azula command line
 #[derive(clap::Parser, Debug)]
@@ -27,13 +41,48 @@ enum Commands {
 
         #[clap(long)]
         print_azula_ir: bool,
+
+        /// Run the statements at the top level as an implicit main, the
+        /// default for files starting with a #! line
+        #[clap(long)]
+        script: bool,
+
+        /// Print each statement as it runs, with the values of the variables
+        /// it sets
+        #[clap(long)]
+        trace_exec: bool,
+
+        /// Compile again instead of running the cached module
+        #[clap(long)]
+        force_rebuild: bool,
+
+        /// Passed on to the program
+        args: Vec<String>,
     },
-    Build {
+    /// Run a file's @test functions, exiting with 1 if any fail
+    Test {
         file: String,
 
+        #[clap(long)]
+        release: bool,
+
+        /// Compile again instead of running the cached module
+        #[clap(long)]
+        force_rebuild: bool,
+    },
+    Build {
+        /// The programs to build, each into its own binary
+        #[clap(required = true)]
+        files: Vec<String>,
+
         #[clap(long)]
         target: Option<String>,
 
+        /// Triple, CPU, features and bare metal defaults for an embedded
+        /// target: thumbv7em or riscv32imac. The other flags override it
+        #[clap(long)]
+        target_preset: Option<String>,
+
         #[clap(long)]
         emit_llvm: bool,
 
@@ -42,6 +91,65 @@ enum Commands {
 
         #[clap(long)]
         print_azula_ir: bool,
+
+        /// Print the files, flags and linker steps as JSON instead of building,
+        /// one plan per file
+        #[clap(long)]
+        build_plan: bool,
+
+        /// Print the size of each compiled function after building
+        #[clap(long)]
+        size_report: bool,
+
+        /// Compile version, git hash, build time and target into the binary,
+        /// readable with build_info()
+        #[clap(long)]
+        embed_build_info: bool,
+
+        /// Only build the object file, for linking its pub functions into
+        /// other programs
+        #[clap(long)]
+        object: bool,
+
+        /// LLVM data layout string to use instead of the target's
+        #[clap(long)]
+        data_layout: Option<String>,
+
+        /// Linker script placing code and data, for bare metal targets
+        #[clap(long)]
+        linker_script: Option<String>,
+
+        /// Link without the C runtime and its default entry point, the
+        /// program provides its own
+        #[clap(long)]
+        freestanding: bool,
+    },
+    /// Build twice and check the outputs are byte for byte identical
+    VerifyRepro { file: String },
+    /// Print the /// docs of a file's functions and structs as Markdown
+    Doc { file: String },
+    /// Add a dependency to azula.toml and fetch it into vendor/
+    Add {
+        name: String,
+
+        /// A directory holding the package's modules
+        #[clap(long, required_unless_present = "git", conflicts_with = "git")]
+        path: Option<String>,
+
+        /// A git repository to clone the package from
+        #[clap(long)]
+        git: Option<String>,
+    },
+    /// Fetch the dependencies in azula.toml into vendor/
+    Fetch,
+    /// Compile requests sent over a local socket, see serve.rs for the protocol
+    Serve {
+        /// Keep serving requests instead of exiting after the first
+        #[clap(long)]
+        daemon: bool,
+
+        #[clap(long, default_value = ".build/azula.sock")]
+        socket: String,
     },
 }
 
@@ -53,89 +161,406 @@ pub fn run() {
             file,
             release,
             print_azula_ir,
-        } => {
-            let result = build(file, ".build/", None, false, *release, *print_azula_ir);
-
-            Command::new(format!("./.build/{}", result))
-                .spawn()
-                .unwrap()
-                .wait()
-                .unwrap();
-        }
-        Commands::Build {
+            script,
+            trace_exec,
+            force_rebuild,
+            args,
+        } => exit(run::run_file(
+            file,
+            &run::RunOptions {
+                release: *release,
+                print_azula_ir: *print_azula_ir,
+                script: *script,
+                trace: *trace_exec,
+                test: false,
+            },
+            *force_rebuild,
+            args,
+        )),
+        Commands::Test {
+            file,
+            release,
+            force_rebuild,
+        } => exit(run::run_file(
             file,
+            &run::RunOptions {
+                release: *release,
+                print_azula_ir: false,
+                script: false,
+                trace: false,
+                test: true,
+            },
+            *force_rebuild,
+            &[],
+        )),
+        Commands::Build {
+            files,
             target,
+            target_preset,
             emit_llvm,
             release,
             print_azula_ir,
+            build_plan,
+            size_report,
+            embed_build_info,
+            object,
+            data_layout,
+            linker_script,
+            freestanding,
         } => {
+            let mut target_options = match target_preset {
+                Some(name) => match TargetOptions::preset(name) {
+                    Some(preset) => preset,
+                    None => {
+                        eprintln!(
+                            "Unknown target preset {}, expected one of: {}",
+                            name,
+                            TargetOptions::PRESETS.join(", ")
+                        );
+                        exit(1);
+                    }
+                },
+                None => TargetOptions::default(),
+            };
+            if target.is_some() {
+                target_options.triple = target.clone();
+            }
+            if data_layout.is_some() {
+                target_options.data_layout = data_layout.clone();
+            }
+            if linker_script.is_some() {
+                target_options.linker_script = linker_script.clone();
+            }
+            target_options.freestanding |= *freestanding;
+
+            if *build_plan {
+                for file in files {
+                    let plan = BuildPlan {
+                        files: module_files(file, target_options.triple.as_deref()),
+                        name: file.trim_end_matches(".azl").to_string(),
+                        destination: String::new(),
+                        target: target_options.clone(),
+                        emit_llvm: *emit_llvm,
+                        release: *release,
+                    };
+                    println!("{}", plan.to_json());
+                }
+                return;
+            }
+
+            let info = if *embed_build_info {
+                Some(build_info::build_info(target_options.triple.as_ref()))
+            } else {
+                None
+            };
             build(
-                file,
-                "",
-                target.as_ref(),
-                *emit_llvm,
-                *release,
-                *print_azula_ir,
+                files,
+                &BuildOptions {
+                    destination: "",
+                    target: target_options,
+                    emit_llvm: *emit_llvm,
+                    release: *release,
+                    print_azula_ir: *print_azula_ir,
+                    size_report: *size_report,
+                    object: *object,
+                    build_info: info,
+                },
             );
         }
+        Commands::VerifyRepro { file } => repro::verify_repro(file),
+        Commands::Doc { file } => doc::print_docs(file),
+        Commands::Add { name, path, git } => {
+            let source = match (path, git) {
+                (Some(path), _) => Source::Path(path.clone()),
+                (_, Some(url)) => Source::Git(url.clone()),
+                (None, None) => unreachable!(),
+            };
+            packages::add(name, source)
+        }
+        Commands::Fetch => packages::fetch(),
+        Commands::Serve { daemon, socket } => serve::serve(socket, *daemon),
     }
 }
 
-fn build<'a>(
-    name: &'a str,
+#[derive(Default)]
+struct BuildOptions<'a> {
     destination: &'a str,
-    target: Option<&String>,
+    target: TargetOptions,
     emit_llvm: bool,
     release: bool,
     print_azula_ir: bool,
-) -> &'a str {
-    let input = fs::read_to_string(name).unwrap();
-    let lexer: Lexer = input.as_str().into();
-    let mut parser = Parser::new(input.as_str(), lexer);
-    let parsed = parser.parse();
-    for error in &parser.errors {
-        error.print_stdout(&input, name);
-    }
+    size_report: bool,
+    object: bool,
+    build_info: Option<String>,
+}
 
-    if !parser.errors.is_empty() {
+/// Builds each file into a binary of its own. Every file is checked before
+/// any is compiled, so one with errors stops the build with all of them
+/// reported
+fn build(files: &[String], options: &BuildOptions) {
+    let inputs: Vec<_> = files
+        .iter()
+        .map(|file| fs::read_to_string(file).unwrap())
+        .collect();
+
+    let mut programs = vec![];
+    let mut failed = false;
+    let mut warnings = 0;
+    for (file, input) in files.iter().zip(&inputs) {
+        let mut diagnostics = Diagnostics::default();
+        let module = compile_module(
+            file,
+            input,
+            FrontendOptions {
+                target: options.target.triple.as_deref(),
+                build_info: options.build_info.clone(),
+                ..FrontendOptions::default()
+            },
+            &mut diagnostics,
+        );
+        print!("{}", diagnostics.rendered);
+        warnings += diagnostics.warnings;
+
+        match module {
+            Some(module) => programs.push((file.trim_end_matches(".azl"), module)),
+            None => failed = true,
+        }
+    }
+    if failed {
         exit(1);
     }
 
-    let mut typecheck = Typechecker::new(parsed);
-    let result = typecheck.typecheck();
-    for err in typecheck.errors {
-        err.print_stdout(&input, name);
+    if options.print_azula_ir {
+        for (_, module) in &programs {
+            println!("{}", module);
+        }
     }
 
-    if result.is_err() {
-        exit(1);
+    let functions: Vec<(&str, Vec<String>)> = programs
+        .iter()
+        .map(|(name, module)| {
+            (
+                *name,
+                module.functions.keys().map(|f| f.to_string()).collect(),
+            )
+        })
+        .collect();
+    let opt_level = if options.release {
+        OptimizationLevel::Aggressive
+    } else {
+        OptimizationLevel::Default
+    };
+
+    let names: Vec<_> = programs.iter().map(|(name, _)| *name).collect();
+    let mut artifacts = vec![];
+    if options.object {
+        for (name, module) in programs {
+            let object_file =
+                LLVMCodegen::build_object(name, &options.target, opt_level, module).unwrap();
+            println!("{}", object_file);
+            artifacts.push(("object", object_file));
+        }
+    } else {
+        LLVMCodegen::codegen_programs(
+            programs,
+            options.destination,
+            options.emit_llvm,
+            &options.target,
+            opt_level,
+        )
+        .unwrap();
+        for name in &names {
+            artifacts.push(("executable", format!("{}{}", options.destination, name)));
+            artifacts.push(("object", LLVMCodegen::object_file(name)));
+            if options.emit_llvm {
+                artifacts.push(("llvm_ir", format!("{}.ll", name)));
+            }
+        }
     }
+    build_manifest::write_manifest(&artifacts, &options.target, opt_level, warnings).unwrap();
 
-    let root = result.unwrap();
+    if options.size_report && !options.object {
+        for (name, functions) in &functions {
+            if files.len() > 1 {
+                println!("{}", name);
+            }
+            print_size_report(name, functions);
+        }
+    }
+}
 
-    let name = name.trim_end_matches(".azl");
+fn print_size_report(name: &str, functions: &[String]) {
+    let sizes = LLVMCodegen::symbol_sizes(&LLVMCodegen::object_file(name)).unwrap();
 
-    let mut codegen = Codegen::new(name, root);
-    codegen.codegen();
-    codegen.insert_implicit_return();
+    // Mach-O symbols carry a leading underscore
+    let sizes: Vec<_> = sizes
+        .into_iter()
+        .filter(|(symbol, _)| {
+            functions.contains(symbol) || functions.iter().any(|f| symbol == &format!("_{}", f))
+        })
+        .collect();
+
+    let width = sizes
+        .iter()
+        .map(|(symbol, _)| symbol.len())
+        .max()
+        .unwrap_or(0)
+        .max("Function".len());
 
-    if print_azula_ir {
-        println!("{}", codegen.module);
+    println!("{:<width$}  {:>10}", "Function", "Bytes", width = width);
+    for (symbol, size) in &sizes {
+        println!("{:<width$}  {:>10}", symbol, size, width = width);
     }
+    println!(
+        "{:<width$}  {:>10}",
+        "Total",
+        sizes.iter().map(|(_, size)| size).sum::<u64>(),
+        width = width
+    );
+}
+
+/// The file an import in a module in `dir` reads, the one beside it or else
+/// a package's
+fn import_file(dir: &Path, import: &str) -> PathBuf {
+    let file = dir.join(format!("{}.azl", import));
+    match package_module(dir, import).filter(|_| !file.exists()) {
+        Some(package) => package,
+        None => file,
+    }
+}
+
+/// Every file building `file` reads, it first and then each module it
+/// imports, directly or through another module. One that can't be read is
+/// left out, the build itself reports it
+fn module_files(file: &str, target: Option<&str>) -> Vec<String> {
+    let mut files = vec![file.to_string()];
+    let input = match fs::read_to_string(file) {
+        Ok(input) => input,
+        Err(_) => return files,
+    };
+    let mut parsed = Parser::new(&input, input.as_str().into()).parse();
 
-    LLVMCodegen::codegen(
-        name,
-        destination,
-        emit_llvm,
-        target,
-        if release {
-            OptimizationLevel::Aggressive
-        } else {
-            OptimizationLevel::Default
+    let path = Path::new(file);
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let module = path.file_stem().and_then(|s| s.to_str()).unwrap_or(file);
+    let config = match target {
+        Some(target) => Config::for_target(target),
+        None => Config::for_target(&LLVMCodegen::default_target()),
+    };
+    resolve_imports(
+        &mut parsed,
+        module,
+        &config,
+        &mut SourceMap::new(),
+        &mut |import| {
+            let file = import_file(dir, import);
+            let source = fs::read_to_string(&file).map_err(|e| e.to_string())?;
+            files.push(file.display().to_string());
+            Ok((
+                file.display().to_string(),
+                Box::leak(source.into_boxed_str()),
+            ))
         },
-        codegen.module,
-    )
-    .unwrap();
+    );
+
+    files
+}
+
+/// What compiling a file reported, rendered for reading along with the count
+/// of warnings for the build manifest
+#[derive(Default)]
+struct Diagnostics {
+    rendered: String,
+    warnings: usize,
+}
+
+/// How compile_module treats a file
+#[derive(Default)]
+struct FrontendOptions<'t> {
+    // `@if` declarations are kept for it, the host if there isn't one
+    target: Option<&'t str>,
+    build_info: Option<String>,
+    // Top level statements are wrapped in an implicit main
+    script: bool,
+    // Each statement prints as it runs
+    trace: bool,
+    // main runs the @test functions instead
+    test: bool,
+}
+
+/// Runs the frontend on a source file, rendering any diagnostics into
+/// `diagnostics`. Returns None if there were errors. Imported modules are read
+/// from the file's directory, or are packages, members of its workspace or
+/// fetched into vendor/, which along with the project have to be compatible
+/// with this compiler
+fn compile_module<'a>(
+    name: &'a str,
+    input: &'a str,
+    options: FrontendOptions,
+    diagnostics: &mut Diagnostics,
+) -> Option<Module<'a>> {
+    let lexer: Lexer = input.into();
+    let mut parser = Parser::new(input, lexer);
+    let mut parsed = parser.parse();
+
+    let path = Path::new(name);
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let module = path.file_stem().and_then(|s| s.to_str()).unwrap_or(name);
+    let root = project_root(dir);
+    if let Some(Err(error)) = root.as_deref().map(check_versions) {
+        diagnostics.rendered.push_str(&format!("{}\n", error));
+        return None;
+    }
+    let mut sources = SourceMap::new();
+    sources.add(name.to_string(), input);
+    let config = match options.target {
+        Some(target) => Config::for_target(target),
+        None => Config::for_target(&LLVMCodegen::default_target()),
+    };
+    let (import_errors, import_ignores) =
+        resolve_imports(&mut parsed, module, &config, &mut sources, &mut |import| {
+            let file = import_file(dir, import);
+            let source = fs::read_to_string(&file).map_err(|e| e.to_string())?;
+            let source: &'a str = Box::leak(source.into_boxed_str());
+            Ok((file.display().to_string(), source))
+        });
+
+    if options.script {
+        parsed = wrap_script(parsed);
+    }
+    let errors: Vec<_> = parser.errors.iter().chain(&import_errors).collect();
+    for error in &errors {
+        diagnostics.rendered.push_str(&error.render_in(&sources));
+    }
+
+    if !errors.is_empty() {
+        return None;
+    }
+
+    let mut typecheck = Typechecker::new(parsed);
+    typecheck.ignores = parser.ignores.clone();
+    typecheck.ignores.extend(import_ignores);
+    typecheck.build_info = options.build_info;
+    let result = typecheck.typecheck();
+    for err in &typecheck.errors {
+        diagnostics.rendered.push_str(&err.render_in(&sources));
+    }
+    for warning in &typecheck.warnings {
+        diagnostics.rendered.push_str(&warning.render_in(&sources));
+    }
+    diagnostics.warnings += typecheck.warnings.len();
+
+    let mut root = result.ok()?;
+    if options.trace {
+        root = trace_execution(root, &sources);
+    }
+
+    let mut codegen = Codegen::new(name.trim_end_matches(".azl"), root);
+    codegen.codegen();
+    if options.test {
+        codegen.codegen_test_harness();
+    }
+    codegen.insert_implicit_return();
 
-    return name;
+    Some(codegen.module)
 }
@@ -0,0 +1,565 @@
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    process::{exit, Command},
+};
+
+/// Declares a project's dependencies, which are fetched into vendor/ next to
+/// it, and the compiler and edition it's written for
+pub const MANIFEST: &str = "azula.toml";
+
+/// The language edition, bumped when a change would stop existing programs
+/// compiling
+pub const EDITION: u32 = 1;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug)]
+pub enum Source {
+    Path(String),
+    Git(String),
+}
+
+/// Reads the `[dependencies]` table of a manifest. Each dependency is an
+/// inline table, `strings = { path = "../strings" }` or
+/// `json = { git = "https://example.com/json.git" }`
+pub fn dependencies(manifest: &str) -> Result<Vec<(String, Source)>, String> {
+    let mut dependencies = vec![];
+    for (number, line) in table(manifest, "dependencies") {
+        let error = || {
+            format!(
+                "{}:{}: expected `name = {{ path = \"...\" }}` or `name = {{ git = \"...\" }}`",
+                MANIFEST, number
+            )
+        };
+        let (name, value) = line.split_once('=').ok_or_else(error)?;
+        let (key, location) = value
+            .trim()
+            .strip_prefix('{')
+            .and_then(|value| value.strip_suffix('}'))
+            .and_then(|value| value.split_once('='))
+            .ok_or_else(error)?;
+        let location = location
+            .trim()
+            .strip_prefix('"')
+            .and_then(|location| location.strip_suffix('"'))
+            .ok_or_else(error)?
+            .to_string();
+        let source = match key.trim() {
+            "path" => Source::Path(location),
+            "git" => Source::Git(location),
+            _ => return Err(error()),
+        };
+        let name = name.trim().to_string();
+        check_dependency(&name, &source).map_err(|e| format!("{}:{}: {}", MANIFEST, number, e))?;
+        dependencies.push((name, source));
+    }
+
+    Ok(dependencies)
+}
+
+/// Reads the `[workspace]` table of a manifest, `members = ["core", "app"]`
+/// naming the directories next to it that are packages of their own
+pub fn members(manifest: &str) -> Result<Vec<String>, String> {
+    let mut members = vec![];
+    for (number, line) in table(manifest, "workspace") {
+        let error = || {
+            format!(
+                "{}:{}: expected `members = [\"<directory>\", ...]`",
+                MANIFEST, number
+            )
+        };
+        let list = line
+            .split_once('=')
+            .filter(|(key, _)| key.trim() == "members")
+            .and_then(|(_, value)| value.trim().strip_prefix('['))
+            .and_then(|value| value.strip_suffix(']'))
+            .ok_or_else(error)?;
+        for member in list.split(',').map(str::trim).filter(|m| !m.is_empty()) {
+            let member = member
+                .strip_prefix('"')
+                .and_then(|member| member.strip_suffix('"'))
+                .ok_or_else(error)?;
+            check_name(member).map_err(|e| format!("{}:{}: {}", MANIFEST, number, e))?;
+            members.push(member.to_string());
+        }
+    }
+
+    Ok(members)
+}
+
+// The name is a directory under vendor/ and the url an argument to git, so
+// neither can be allowed to point anywhere else
+fn check_dependency(name: &str, source: &Source) -> Result<(), String> {
+    check_name(name)?;
+    if let Source::Git(url) = source {
+        if url.starts_with('-') {
+            return Err(format!("{:?} isn't a valid git url", url));
+        }
+    }
+
+    Ok(())
+}
+
+// Packages are imported by name, so they have to be named like identifiers
+fn check_name(name: &str) -> Result<(), String> {
+    let mut chars = name.chars();
+    let identifier = chars
+        .next()
+        .map_or(false, |c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !identifier {
+        return Err(format!("{:?} isn't a valid package name", name));
+    }
+
+    Ok(())
+}
+
+/// What the `[package]` table of a manifest asks of the compiler
+#[derive(Default)]
+pub struct Requirements {
+    // The compiler versions it builds with, `azula = "0.2"`, compatible the
+    // way Cargo's are
+    pub azula: Option<String>,
+    // The edition it's written for, `edition = 1`
+    pub edition: Option<u32>,
+}
+
+pub fn requirements(manifest: &str, file: &Path) -> Result<Requirements, String> {
+    let mut requirements = Requirements::default();
+    for (number, line) in table(manifest, "package") {
+        let error = || {
+            format!(
+                "{}:{}: expected `azula = \"<version>\"` or `edition = <number>`",
+                file.display(),
+                number
+            )
+        };
+        let (key, value) = line.split_once('=').ok_or_else(error)?;
+        let value = value.trim();
+        match key.trim() {
+            "azula" => {
+                let version = value
+                    .strip_prefix('"')
+                    .and_then(|value| value.strip_suffix('"'))
+                    .filter(|version| compatible(version, VERSION).is_some())
+                    .ok_or_else(error)?;
+                requirements.azula = Some(version.to_string());
+            }
+            "edition" => requirements.edition = Some(value.parse().map_err(|_| error())?),
+            _ => return Err(error()),
+        }
+    }
+
+    Ok(requirements)
+}
+
+/// Checks that the project at `root`, the workspace it's in and each package
+/// it can import can be built by this compiler
+pub fn check_versions(root: &Path) -> Result<(), String> {
+    let mut manifests = vec![("This project".to_string(), root.join(MANIFEST))];
+    if let Some(workspace) = workspace_root(root) {
+        manifests.push(("The workspace".to_string(), workspace.join(MANIFEST)));
+    }
+    for package in package_dirs(root) {
+        let name = package.file_name().unwrap().to_string_lossy();
+        manifests.push((format!("The package {}", name), package.join(MANIFEST)));
+    }
+
+    for (name, file) in manifests {
+        let manifest = match fs::read_to_string(&file) {
+            Ok(manifest) => manifest,
+            Err(_) => continue,
+        };
+        let requirements = requirements(&manifest, &file)?;
+        if let Some(required) = requirements.azula {
+            if compatible(&required, VERSION) != Some(true) {
+                return Err(format!(
+                    "{} needs azula {}, this is {}",
+                    name, required, VERSION
+                ));
+            }
+        }
+        if let Some(edition) = requirements.edition.filter(|edition| *edition > EDITION) {
+            return Err(format!(
+                "{} is written for edition {}, azula {} only knows up to edition {}",
+                name, edition, VERSION, EDITION
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// Cargo's caret rules, everything up to the first non-zero part has to match
+// and the rest can be newer: 0.2 accepts 0.2.x, 1.3 accepts 1.3.0 up to 2.0.0.
+// None if either isn't a version
+fn compatible(required: &str, version: &str) -> Option<bool> {
+    let parse = |version: &str| -> Option<Vec<u64>> {
+        version.split('.').map(|part| part.parse().ok()).collect()
+    };
+    let required = parse(required).filter(|parts| parts.len() <= 3)?;
+    let version = parse(version)?;
+    let version: Vec<_> = (0..3)
+        .map(|part| version.get(part).copied().unwrap_or(0))
+        .collect();
+
+    let fixed = required
+        .iter()
+        .position(|part| *part != 0)
+        .unwrap_or(required.len() - 1);
+    Some(required[..=fixed] == version[..=fixed] && version[..required.len()] >= required[..])
+}
+
+// The lines of a manifest table with their line numbers, skipping blank lines
+// and comments
+fn table<'m>(manifest: &'m str, name: &'m str) -> impl Iterator<Item = (usize, &'m str)> {
+    let mut inside = false;
+    manifest
+        .lines()
+        .enumerate()
+        .filter_map(move |(number, line)| {
+            let line = line.trim();
+            if line.starts_with('[') {
+                inside = line
+                    .strip_prefix('[')
+                    .and_then(|line| line.strip_suffix(']'))
+                    == Some(name);
+                return None;
+            }
+            if !inside || line.is_empty() || line.starts_with('#') {
+                return None;
+            }
+            Some((number + 1, line))
+        })
+}
+
+/// The nearest directory at or above `dir` with a manifest
+pub fn project_root(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|dir| dir.join(MANIFEST).exists())
+        .map(Path::to_path_buf)
+}
+
+/// The nearest directory at or above `dir` whose manifest has a `[workspace]`
+/// table. Looked for from the absolute path, so a member run from its own
+/// directory still finds the workspace above it
+pub fn workspace_root(dir: &Path) -> Option<PathBuf> {
+    let dir = Path::new(".").join(dir).canonicalize().ok()?;
+    dir.ancestors()
+        .find(|dir| {
+            fs::read_to_string(dir.join(MANIFEST)).map_or(false, |manifest| {
+                manifest.lines().any(|line| line.trim() == "[workspace]")
+            })
+        })
+        .map(Path::to_path_buf)
+}
+
+// A workspace's members share the one vendor/ at its top
+fn vendor_dir(dir: &Path) -> Option<PathBuf> {
+    workspace_root(dir)
+        .or_else(|| project_root(dir))
+        .map(|root| root.join("vendor"))
+}
+
+/// The directories of the packages a file in `dir` can import, the members of
+/// its workspace and then everything fetched into vendor/
+pub fn package_dirs(dir: &Path) -> Vec<PathBuf> {
+    let mut dirs = vec![];
+    if let Some(workspace) = workspace_root(dir) {
+        let manifest = fs::read_to_string(workspace.join(MANIFEST)).unwrap_or_default();
+        let members = members(&manifest).unwrap_or_default();
+        dirs.extend(members.iter().map(|member| workspace.join(member)));
+    }
+    if let Some(Ok(packages)) = vendor_dir(dir).map(fs::read_dir) {
+        let mut packages: Vec<_> = packages.flatten().map(|entry| entry.path()).collect();
+        packages.sort();
+        dirs.extend(packages);
+    }
+
+    dirs
+}
+
+/// A package is imported by its name, from the file of the same name at the
+/// top of its directory
+pub fn package_module(dir: &Path, name: &str) -> Option<PathBuf> {
+    package_dirs(dir)
+        .into_iter()
+        .find(|package| package.file_name() == Some(name.as_ref()))
+        .map(|package| package.join(format!("{}.azl", name)))
+}
+
+/// Adds a dependency to the manifest in the current directory, creating it
+/// for this compiler and edition if there isn't one, and fetches it
+pub fn add(name: &str, source: Source) {
+    let manifest = fs::read_to_string(MANIFEST).unwrap_or_else(|_| {
+        let (series, _) = VERSION.rsplit_once('.').unwrap();
+        format!("[package]\nazula = \"{}\"\nedition = {}\n", series, EDITION)
+    });
+    check_dependency(name, &source).unwrap_or_else(|e| fail(e));
+    let existing = dependencies(&manifest).unwrap_or_else(|e| fail(e));
+    if existing.iter().any(|(dependency, _)| dependency == name) {
+        fail(format!("{} is already a dependency", name));
+    }
+
+    let entry = match &source {
+        Source::Path(path) => format!("{} = {{ path = \"{}\" }}", name, path),
+        Source::Git(url) => format!("{} = {{ git = \"{}\" }}", name, url),
+    };
+    let mut lines: Vec<&str> = manifest.lines().collect();
+    match lines
+        .iter()
+        .position(|line| line.trim() == "[dependencies]")
+    {
+        // At the end of the table, before any blank lines leading to the next
+        Some(header) => {
+            let mut end = lines[header + 1..]
+                .iter()
+                .position(|line| line.trim().starts_with('['))
+                .map_or(lines.len(), |next| header + 1 + next);
+            while end > header + 1 && lines[end - 1].trim().is_empty() {
+                end -= 1;
+            }
+            lines.insert(end, &entry);
+        }
+        None => {
+            if !lines.is_empty() {
+                lines.push("");
+            }
+            lines.push("[dependencies]");
+            lines.push(&entry);
+        }
+    }
+    fs::write(MANIFEST, lines.join("\n") + "\n").unwrap();
+
+    fetch_package(name, &source, &vendor()).unwrap_or_else(|e| fail(e));
+}
+
+/// Fetches every dependency in the current directory's manifest, and in the
+/// manifests of its workspace members
+pub fn fetch() {
+    let manifest = fs::read_to_string(MANIFEST)
+        .unwrap_or_else(|_| fail(format!("No {} in the current directory", MANIFEST)));
+    let mut found = dependencies(&manifest).unwrap_or_else(|e| fail(e));
+    for member in members(&manifest).unwrap_or_else(|e| fail(e)) {
+        let manifest = match fs::read_to_string(Path::new(&member).join(MANIFEST)) {
+            Ok(manifest) => manifest,
+            Err(_) => continue,
+        };
+        let dependencies =
+            dependencies(&manifest).unwrap_or_else(|e| fail(format!("{}/{}", member, e)));
+        // A member's paths are relative to its own directory
+        found.extend(dependencies.into_iter().map(|(name, source)| match source {
+            Source::Path(path) => {
+                let path = Path::new(&member).join(path).display().to_string();
+                (name, Source::Path(path))
+            }
+            git => (name, git),
+        }));
+    }
+
+    let vendor = vendor();
+    for (name, source) in found {
+        fetch_package(&name, &source, &vendor).unwrap_or_else(|e| fail(e));
+    }
+}
+
+// Where packages are fetched to from the current directory
+fn vendor() -> PathBuf {
+    vendor_dir(Path::new("")).unwrap_or_else(|| PathBuf::from("vendor"))
+}
+
+// Path dependencies have their modules and manifest copied, so they're fetched again to
+// pick up changes. Git ones are cloned once and left as they are after that
+fn fetch_package(name: &str, source: &Source, vendor: &Path) -> Result<(), Box<dyn Error>> {
+    let destination = vendor.join(name);
+    match source {
+        Source::Path(path) => {
+            fs::create_dir_all(&destination)?;
+            for entry in fs::read_dir(path)? {
+                let module = entry?.path();
+                let is_manifest = module.file_name().map_or(false, |name| name == MANIFEST);
+                if is_manifest || module.extension().map_or(false, |ext| ext == "azl") {
+                    fs::copy(&module, destination.join(module.file_name().unwrap()))?;
+                }
+            }
+        }
+        Source::Git(url) => {
+            if destination.exists() {
+                return Ok(());
+            }
+            let status = Command::new("git")
+                .args(["clone", "--quiet", "--depth", "1", "--", url])
+                .arg(&destination)
+                .status()?;
+            if !status.success() {
+                return Err(format!("Cloning {} failed", url).into());
+            }
+        }
+    }
+    println!("Fetched {} into {}", name, destination.display());
+
+    Ok(())
+}
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("{}", message);
+    exit(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table() {
+        let manifest = "[package]\nedition = 1\n\n[dependencies]\n# a comment\nstrings = 1\n\n[other]\nx = 2\n";
+        assert_eq!(
+            table(manifest, "dependencies").collect::<Vec<_>>(),
+            vec![(6, "strings = 1")]
+        );
+        assert_eq!(
+            table(manifest, "package").collect::<Vec<_>>(),
+            vec![(2, "edition = 1")]
+        );
+        assert_eq!(table(manifest, "missing").count(), 0);
+    }
+
+    #[test]
+    fn test_dependencies() {
+        let manifest = "[dependencies]\n# fetched from next door\nstrings = { path = \"../strings\" }\njson = { git = \"https://example.com/json.git\" }\n";
+        let found = dependencies(manifest).unwrap();
+        assert_eq!(found.len(), 2);
+        assert!(
+            matches!(&found[0], (name, Source::Path(path)) if name == "strings" && path == "../strings")
+        );
+        assert!(
+            matches!(&found[1], (name, Source::Git(url)) if name == "json" && url == "https://example.com/json.git")
+        );
+
+        let error =
+            "azula.toml:2: expected `name = { path = \"...\" }` or `name = { git = \"...\" }`";
+        for line in [
+            "strings",
+            "strings = \"../strings\"",
+            "strings = { path = ../strings }",
+            "strings = { url = \"../strings\" }",
+        ] {
+            assert_eq!(
+                dependencies(&format!("[dependencies]\n{}\n", line)).unwrap_err(),
+                error
+            );
+        }
+
+        assert_eq!(
+            dependencies("[dependencies]\n../up = { path = \"x\" }\n").unwrap_err(),
+            "azula.toml:2: \"../up\" isn't a valid package name"
+        );
+        assert_eq!(
+            dependencies("[dependencies]\na/b = { path = \"x\" }\n").unwrap_err(),
+            "azula.toml:2: \"a/b\" isn't a valid package name"
+        );
+        assert_eq!(
+            dependencies("[dependencies]\nx = { git = \"--upload-pack=touch\" }\n").unwrap_err(),
+            "azula.toml:2: \"--upload-pack=touch\" isn't a valid git url"
+        );
+    }
+
+    #[test]
+    fn test_requirements() {
+        let file = Path::new(MANIFEST);
+        let (series, _) = VERSION.rsplit_once('.').unwrap();
+        let manifest = format!("[package]\nazula = \"{}\"\nedition = 1\n", series);
+        let found = requirements(&manifest, file).unwrap();
+        assert_eq!(found.azula.as_deref(), Some(series));
+        assert_eq!(found.edition, Some(1));
+
+        let error = "azula.toml:2: expected `azula = \"<version>\"` or `edition = <number>`";
+        for line in [
+            "azula = 0.2",
+            "azula = \"latest\"",
+            "edition = one",
+            "name = \"x\"",
+        ] {
+            assert_eq!(
+                requirements(&format!("[package]\n{}\n", line), file)
+                    .err()
+                    .unwrap(),
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn test_compatible() {
+        assert_eq!(compatible("0.2", "0.2.5"), Some(true));
+        assert_eq!(compatible("0.2", "0.3.0"), Some(false));
+        assert_eq!(compatible("0.2.3", "0.2.1"), Some(false));
+        assert_eq!(compatible("1.3", "1.9.0"), Some(true));
+        assert_eq!(compatible("1.3", "1.2.9"), Some(false));
+        assert_eq!(compatible("1.3", "2.0.0"), Some(false));
+        assert_eq!(compatible("0.0.4", "0.0.5"), Some(false));
+        assert_eq!(compatible("1.x", "1.0.0"), None);
+        assert_eq!(compatible("1.2.3.4", "1.2.3"), None);
+    }
+
+    #[test]
+    fn test_members() {
+        let manifest = "[workspace]\nmembers = [\"core\", \"app\"]\n\n[dependencies]\n";
+        assert_eq!(members(manifest).unwrap(), vec!["core", "app"]);
+        assert!(members("[package]\nedition = 1\n").unwrap().is_empty());
+
+        let error = "azula.toml:2: expected `members = [\"<directory>\", ...]`";
+        for line in [
+            "members = core",
+            "members = [core]",
+            "packages = [\"core\"]",
+        ] {
+            assert_eq!(
+                members(&format!("[workspace]\n{}\n", line)).unwrap_err(),
+                error
+            );
+        }
+        assert_eq!(
+            members("[workspace]\nmembers = [\"../core\"]\n").unwrap_err(),
+            "azula.toml:2: \"../core\" isn't a valid package name"
+        );
+    }
+
+    #[test]
+    fn test_package_dirs() {
+        let workspace =
+            std::env::temp_dir().join(format!("azula-workspace-{}", std::process::id()));
+        let app = workspace.join("app");
+        fs::create_dir_all(&app).unwrap();
+        fs::create_dir_all(workspace.join("vendor").join("json")).unwrap();
+        fs::write(
+            workspace.join(MANIFEST),
+            "[workspace]\nmembers = [\"core\", \"app\"]\n",
+        )
+        .unwrap();
+        fs::write(app.join(MANIFEST), "[package]\nedition = 1\n").unwrap();
+
+        // A member finds the workspace above its own manifest, and imports the
+        // other members and the shared vendor/
+        let workspace = workspace.canonicalize().unwrap();
+        assert_eq!(workspace_root(&app), Some(workspace.clone()));
+        assert_eq!(project_root(&app), Some(app.clone()));
+        assert_eq!(
+            package_dirs(&app),
+            vec![
+                workspace.join("core"),
+                workspace.join("app"),
+                workspace.join("vendor").join("json"),
+            ]
+        );
+        assert_eq!(
+            package_module(&app, "core"),
+            Some(workspace.join("core").join("core.azl"))
+        );
+        assert_eq!(package_module(&app, "missing"), None);
+
+        fs::remove_dir_all(&workspace).unwrap();
+    }
+}
@@ -0,0 +1,135 @@
+use std::{
+    env, fs,
+    path::Path,
+    process::{self, Command},
+};
+
+use azula_codegen::prelude::{OptimizationLevel, TargetOptions};
+use azula_codegen_llvm::prelude::{LLVMCodegen, ObjectCompiler};
+
+use crate::{compile_module, Diagnostics, FrontendOptions};
+
+/// Builds the file twice into fresh temporary directories and compares the
+/// object files and executables byte for byte
+pub fn verify_repro(file: &str) {
+    let input = fs::read_to_string(file).unwrap();
+    let name = Path::new(file)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(file);
+
+    let mut outputs = vec![];
+    for run in 0..2 {
+        let dir = env::temp_dir().join(format!("azula-repro-{}-{}", process::id(), run));
+        outputs.push(build_in(file, &input, name, &dir));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    let (first_object, first_binary) = &outputs[0];
+    let (second_object, second_binary) = &outputs[1];
+
+    let object_same = compare("object file", first_object, second_object);
+    let binary_same = compare("executable", first_binary, second_binary);
+
+    if !(object_same && binary_same) {
+        process::exit(1);
+    }
+}
+
+/// Compiles and links the file with every output under `dir`, so a build
+/// can't pick up what the other one or an earlier build left in .build
+fn build_in(file: &str, input: &str, name: &str, dir: &Path) -> (Vec<u8>, Vec<u8>) {
+    let mut diagnostics = Diagnostics::default();
+    let module = compile_module(file, input, FrontendOptions::default(), &mut diagnostics);
+    print!("{}", diagnostics.rendered);
+    let module = module.unwrap_or_else(|| process::exit(1));
+
+    let target = TargetOptions::default();
+    let compiler = ObjectCompiler::new(&target, OptimizationLevel::Default).unwrap();
+    let object = compiler.compile(&module).unwrap();
+
+    let object_file = dir.join(LLVMCodegen::object_file(name));
+    fs::create_dir_all(object_file.parent().unwrap()).unwrap();
+    fs::write(&object_file, &object).unwrap();
+
+    let link = LLVMCodegen::link_command(name, "", &target);
+    let status = Command::new(&link[0])
+        .args(&link[1..])
+        .current_dir(dir)
+        .status()
+        .unwrap();
+    if !status.success() {
+        eprintln!("Linking {} failed", file);
+        process::exit(1);
+    }
+
+    (object, fs::read(dir.join(name)).unwrap())
+}
+
+fn compare(label: &str, first: &[u8], second: &[u8]) -> bool {
+    let offset = match first.iter().zip(second).position(|(a, b)| a != b) {
+        Some(offset) => offset,
+        None if first.len() == second.len() => {
+            println!("{}: identical ({} bytes)", label, first.len());
+            return true;
+        }
+        None => first.len().min(second.len()),
+    };
+
+    match elf_section_at(first, offset) {
+        Some(section) => println!(
+            "{}: differs at byte {} in section {}",
+            label, offset, section
+        ),
+        None => println!("{}: differs at byte {}", label, offset),
+    }
+
+    false
+}
+
+/// The name of the section containing `offset`, for 64-bit little-endian ELF
+/// files. Returns None for anything else or offsets outside any section
+fn elf_section_at(bytes: &[u8], offset: usize) -> Option<String> {
+    if bytes.get(0..4)? != b"\x7fELF" || *bytes.get(4)? != 2 || *bytes.get(5)? != 1 {
+        return None;
+    }
+
+    let u16_at = |at: usize| Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?));
+    let u32_at = |at: usize| Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+    let u64_at = |at: usize| Some(u64::from_le_bytes(bytes.get(at..at + 8)?.try_into().ok()?));
+
+    let section_headers = u64_at(0x28)? as usize;
+    let header_size = u16_at(0x3a)? as usize;
+    let header_count = u16_at(0x3c)? as usize;
+    let names_index = u16_at(0x3e)? as usize;
+
+    let header = |index: usize| section_headers + index * header_size;
+    let names = u64_at(header(names_index) + 24)? as usize;
+
+    if offset < 64 {
+        return Some("<ELF header>".to_string());
+    }
+    if offset >= section_headers && offset < header(header_count) {
+        return Some("<section headers>".to_string());
+    }
+
+    for index in 0..header_count {
+        // SHT_NOBITS sections like .bss take no space in the file
+        if u32_at(header(index) + 4)? == 8 {
+            continue;
+        }
+
+        let start = u64_at(header(index) + 24)? as usize;
+        let size = u64_at(header(index) + 32)? as usize;
+        if offset < start || offset >= start + size {
+            continue;
+        }
+
+        let name = names + u32_at(header(index))? as usize;
+        let end = name + bytes.get(name..)?.iter().position(|b| *b == 0)?;
+        return Some(String::from_utf8_lossy(&bytes[name..end]).to_string());
+    }
+
+    None
+}
@@ -0,0 +1,131 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    env, fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    process::exit,
+};
+
+use azula_codegen::prelude::{Backend, OptimizationLevel};
+use azula_codegen_llvm::prelude::LLVMCodegen;
+
+use crate::{
+    compile_module,
+    packages::{package_dirs, EDITION, VERSION},
+    Diagnostics, FrontendOptions,
+};
+
+#[derive(Hash)]
+pub struct RunOptions {
+    pub release: bool,
+    pub print_azula_ir: bool,
+    pub script: bool,
+    pub trace: bool,
+    pub test: bool,
+}
+
+/// Runs a file through the JIT, returning main's exit code, or with `test`
+/// set runs its tests and returns whether any failed. The compiled
+/// module is cached by the source and options, so running an unchanged file
+/// again skips straight to executing it unless `force_rebuild` is set
+pub fn run_file(file: &str, options: &RunOptions, force_rebuild: bool, args: &[String]) -> i32 {
+    let input = fs::read_to_string(file).unwrap();
+    // Files with a #! line are run directly, so they're scripts
+    let options = RunOptions {
+        script: options.script || input.starts_with("#!"),
+        ..*options
+    };
+    let opt_level = || {
+        if options.release {
+            OptimizationLevel::Aggressive
+        } else {
+            OptimizationLevel::Default
+        }
+    };
+
+    let cache = cache_path(file, &input, &options);
+    // Each module records the compiler and edition it was built by, and one
+    // from any other is built again
+    let built_by = format!("azula {} edition {}", VERSION, EDITION);
+    let stamp = cache.with_extension("version");
+    let compatible = fs::read_to_string(&stamp).map_or(false, |stamp| stamp == built_by);
+    if cache.exists() && compatible && !force_rebuild && !options.print_azula_ir {
+        return LLVMCodegen::run_cached(&cache, opt_level(), args).unwrap();
+    }
+
+    let mut diagnostics = Diagnostics::default();
+    let module = compile_module(
+        file,
+        &input,
+        FrontendOptions {
+            script: options.script,
+            trace: options.trace,
+            test: options.test,
+            ..FrontendOptions::default()
+        },
+        &mut diagnostics,
+    );
+    print!("{}", diagnostics.rendered);
+
+    let module = match module {
+        Some(module) => module,
+        None => exit(1),
+    };
+
+    if options.print_azula_ir {
+        println!("{}", module);
+    }
+
+    fs::create_dir_all(cache.parent().unwrap()).unwrap();
+    fs::write(&stamp, built_by).unwrap();
+    LLVMCodegen::run(module, opt_level(), &cache, args).unwrap()
+}
+
+// Keyed by the compiler binary too, so rebuilding it without changing its
+// version doesn't pick up modules from the old one. Imports are read from the
+// file's directory, the workspace's members and vendor/, so the modules there
+// are part of the key
+fn cache_path(file: &str, input: &str, options: &RunOptions) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    input.hash(&mut hasher);
+    let dir = Path::new(file).parent().unwrap_or_else(|| Path::new(""));
+    hash_modules(dir, &mut hasher);
+    for package in package_dirs(dir) {
+        hash_modules(&package, &mut hasher);
+    }
+    options.hash(&mut hasher);
+    if let Ok(modified) = env::current_exe()
+        .and_then(fs::metadata)
+        .and_then(|m| m.modified())
+    {
+        modified.hash(&mut hasher);
+    }
+
+    let dir = match (env::var_os("XDG_CACHE_HOME"), env::var_os("HOME")) {
+        (Some(cache), _) => PathBuf::from(cache),
+        (None, Some(home)) => PathBuf::from(home).join(".cache"),
+        (None, None) => env::temp_dir(),
+    };
+
+    dir.join("azula")
+        .join(format!("{:016x}.bc", hasher.finish()))
+}
+
+fn hash_modules(dir: &Path, hasher: &mut DefaultHasher) {
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    if let Ok(entries) = fs::read_dir(dir) {
+        let mut modules: Vec<_> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().map_or(false, |ext| ext == "azl"))
+            .collect();
+        modules.sort();
+        for module in modules {
+            module.hash(hasher);
+            fs::read_to_string(&module).ok().hash(hasher);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    env, fs,
+    io::{Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    process::exit,
+};
+
+use azula_codegen::prelude::{OptimizationLevel, TargetOptions};
+use azula_codegen_llvm::prelude::ObjectCompiler;
+
+use crate::{compile_module, Diagnostics, FrontendOptions};
+
+// Each connection writes the file name on the first line followed by the
+// source, then shuts down its write half. The reply is the rendered
+// diagnostics followed by a final line, `error <message>` or `ok <length>`
+// and that many bytes of object file. A file outside the directory the server
+// runs in is refused. The LLVM context and target machine are made once and
+// kept for every request.
+pub fn serve(socket: &str, daemon: bool) {
+    fs::create_dir_all(".build").unwrap();
+    let _ = fs::remove_file(socket);
+
+    let compiler = ObjectCompiler::new(&TargetOptions::default(), OptimizationLevel::Default)
+        .unwrap_or_else(|e| {
+            eprintln!("azula serve: {}", e);
+            exit(1)
+        });
+    let listener = UnixListener::bind(socket).unwrap();
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(e) = handle(stream, &compiler) {
+                    eprintln!("azula serve: {}", e);
+                }
+            }
+            Err(e) => eprintln!("azula serve: {}", e),
+        }
+
+        if !daemon {
+            break;
+        }
+    }
+
+    let _ = fs::remove_file(socket);
+}
+
+fn handle(mut stream: UnixStream, compiler: &ObjectCompiler) -> std::io::Result<()> {
+    let mut request = String::new();
+    stream.read_to_string(&mut request)?;
+
+    let (file, source) = request.split_once('\n').unwrap_or((request.as_str(), ""));
+    let file = file.trim();
+    // Errors are all replied with, a panic is a bug in the compiler but it
+    // shouldn't take the server down with it
+    let response = match check_path(file) {
+        Ok(()) => panic::catch_unwind(AssertUnwindSafe(|| compile(file, source, compiler)))
+            .unwrap_or_else(|_| b"error the compiler panicked\n".to_vec()),
+        Err(e) => format!("error {}\n", e).into_bytes(),
+    };
+
+    stream.write_all(&response)
+}
+
+// Imports are read from beside the file, so a name reaching out of the
+// working directory would read modules from wherever it pointed
+fn check_path(file: &str) -> Result<(), String> {
+    let path = Path::new(file);
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let cwd = env::current_dir().and_then(|cwd| cwd.canonicalize());
+    let inside = match (dir.canonicalize(), cwd) {
+        (Ok(dir), Ok(cwd)) => dir.starts_with(cwd),
+        _ => false,
+    };
+    if !inside || path.file_name().is_none() {
+        return Err(format!("{} isn't in the working directory", file));
+    }
+
+    Ok(())
+}
+
+fn compile(file: &str, source: &str, compiler: &ObjectCompiler) -> Vec<u8> {
+    let mut diagnostics = Diagnostics::default();
+    let module = compile_module(file, source, FrontendOptions::default(), &mut diagnostics);
+    let mut response = diagnostics.rendered.into_bytes();
+
+    match module.map(|module| compiler.compile(&module)) {
+        Some(Ok(object)) => {
+            response.extend(format!("ok {}\n", object.len()).bytes());
+            response.extend(object);
+        }
+        Some(Err(e)) => response.extend(format!("error {}\n", e).bytes()),
+        None => response.extend(b"error\n"),
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_path() {
+        assert!(check_path("main.azl").is_ok());
+        assert!(check_path("src/main.azl").is_ok());
+        for file in ["../main.azl", "src/../../main.azl", "/etc/passwd", "src/.."] {
+            assert_eq!(
+                check_path(file).unwrap_err(),
+                format!("{} isn't in the working directory", file)
+            );
+        }
+    }
+}
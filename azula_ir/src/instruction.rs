@@ -7,6 +7,7 @@ pub enum Instruction<'a> {
     Load(String, usize, AzulaType<'a>),
     LoadGlobal(String, usize, AzulaType<'a>),
     Store(String, Value, AzulaType<'a>),
+    StoreGlobal(String, Value),
     LoadArg(usize, usize, AzulaType<'a>),
     ConstInt(i64, usize),
     ConstFloat(f64, usize),
@@ -18,6 +19,9 @@ pub enum Instruction<'a> {
     Mul(Value, Value, usize),
     Div(Value, Value, usize),
     Mod(Value, Value, usize),
+    // Division and remainder of unsigned integers
+    UDiv(Value, Value, usize),
+    URem(Value, Value, usize),
     Pow(Value, Value, usize),
     Or(Value, Value, usize),
     And(Value, Value, usize),
@@ -27,18 +31,71 @@ pub enum Instruction<'a> {
     Gte(Value, Value, usize),
     Lt(Value, Value, usize),
     Lte(Value, Value, usize),
+    // Comparisons of unsigned integers
+    UGt(Value, Value, usize),
+    UGte(Value, Value, usize),
+    ULt(Value, Value, usize),
+    ULte(Value, Value, usize),
+    Shl(Value, Value, usize),
+    AShr(Value, Value, usize),
+    LShr(Value, Value, usize),
     Not(Value, usize),
+    Neg(Value, usize),
     Return(Option<Value>),
     FunctionCall(String, Vec<Value>, usize),
     Jcond(Value, String, String),
     Jump(String),
+    // value, cases and the block for anything else
+    Switch(Value, Vec<(i64, String)>, String),
     Pointer(String, usize),
+    // pointer, the type it points to
+    Deref(Value, AzulaType<'a>, usize),
+    // pointer, value
+    StoreDeref(Value, Value),
     CreateArray(AzulaType<'a>, usize, usize),
     StoreElement(Value, Value, Value),
     AccessElement(Value, Value, usize),
+    // The address of an element rather than its value
+    ElementPointer(Value, Value, usize),
     CreateStruct(String, Vec<Value>, usize),
+    CreateTuple(Vec<Value>, usize),
+    FunctionPointer(String, usize),
+    MakeOptional(Option<Value>, AzulaType<'a>, usize),
+    // function, env tuple and its type
+    MakeClosure(String, Value, AzulaType<'a>, usize),
+    // closure, args and the closure's type
+    CallClosure(Value, Vec<Value>, AzulaType<'a>, usize),
+    // Frees the env a closure was made with
+    FreeClosure(Value),
+    // vtable, env tuple and its type
+    MakeInterface(String, Value, AzulaType<'a>, usize),
+    // interface value, method index
+    InterfaceMethod(Value, usize, usize),
+    // value, error message
+    MakeResult(Option<Value>, Option<Value>, AzulaType<'a>, usize),
     StoreStructMember(Value, usize, Value),
     AccessStructMember(Value, usize, usize, bool),
+    MakeAny(Value, i64, usize),
+    AnyTag(Value, usize),
+    AnyPayload(Value, AzulaType<'a>, usize),
+    Cast(Value, AzulaType<'a>, AzulaType<'a>, usize),
+    // The type's size in bytes, known once the target is
+    SizeOf(AzulaType<'a>, usize),
+    // malloc'd room for a value of the type, and handing it back
+    HeapAlloc(AzulaType<'a>, usize),
+    Free(Value),
+    // A string's length, kept alongside its bytes
+    StringLen(Value, usize),
+    // Loads and stores through a pointer that are never merged, reordered
+    // or dropped, for memory-mapped registers
+    VolatileLoad(Value, AzulaType<'a>, usize),
+    VolatileStore(Value, Value),
+    // Loads and stores through a pointer that may not be aligned
+    UnalignedLoad(Value, AzulaType<'a>, usize),
+    UnalignedStore(Value, Value),
+    // Swaps an integer's bytes unless the target already stores them in the
+    // order, big endian if true
+    ByteOrder(Value, bool, usize),
 }
 
 impl<'a> Display for Instruction<'a> {
@@ -51,6 +108,9 @@ impl<'a> Display for Instruction<'a> {
             Instruction::Store(name, value, typ) => {
                 write!(f, "store @{} {} {:?}", name, value, typ)
             }
+            Instruction::StoreGlobal(name, value) => {
+                write!(f, "store_global @{} {}", name, value)
+            }
             Instruction::LoadArg(arg, dest, typ) => {
                 write!(f, "%{}: load_arg %{} {:?}", dest, arg, typ)
             }
@@ -64,6 +124,8 @@ impl<'a> Display for Instruction<'a> {
             Instruction::Mul(val1, val2, dest) => write!(f, "%{}: mul {} {}", dest, val1, val2),
             Instruction::Div(val1, val2, dest) => write!(f, "%{}: div {} {}", dest, val1, val2),
             Instruction::Mod(val1, val2, dest) => write!(f, "%{}: mod {} {}", dest, val1, val2),
+            Instruction::UDiv(val1, val2, dest) => write!(f, "%{}: udiv {} {}", dest, val1, val2),
+            Instruction::URem(val1, val2, dest) => write!(f, "%{}: urem {} {}", dest, val1, val2),
             Instruction::Pow(val1, val2, dest) => write!(f, "%{}: pow {} {}", dest, val1, val2),
             Instruction::Or(val1, val2, dest) => write!(f, "%{}: or {} {}", dest, val1, val2),
             Instruction::And(val1, val2, dest) => write!(f, "%{}: and {} {}", dest, val1, val2),
@@ -73,7 +135,15 @@ impl<'a> Display for Instruction<'a> {
             Instruction::Gte(val1, val2, dest) => write!(f, "%{}: gte {} {}", dest, val1, val2),
             Instruction::Lt(val1, val2, dest) => write!(f, "%{}: lt {} {}", dest, val1, val2),
             Instruction::Lte(val1, val2, dest) => write!(f, "%{}: lte {} {}", dest, val1, val2),
+            Instruction::UGt(val1, val2, dest) => write!(f, "%{}: ugt {} {}", dest, val1, val2),
+            Instruction::UGte(val1, val2, dest) => write!(f, "%{}: ugte {} {}", dest, val1, val2),
+            Instruction::ULt(val1, val2, dest) => write!(f, "%{}: ult {} {}", dest, val1, val2),
+            Instruction::ULte(val1, val2, dest) => write!(f, "%{}: ulte {} {}", dest, val1, val2),
+            Instruction::Shl(val1, val2, dest) => write!(f, "%{}: shl {} {}", dest, val1, val2),
+            Instruction::AShr(val1, val2, dest) => write!(f, "%{}: ashr {} {}", dest, val1, val2),
+            Instruction::LShr(val1, val2, dest) => write!(f, "%{}: lshr {} {}", dest, val1, val2),
             Instruction::Not(val, dest) => write!(f, "%{}: not {}", dest, val),
+            Instruction::Neg(val, dest) => write!(f, "%{}: neg {}", dest, val),
             Instruction::Return(val) => write!(
                 f,
                 "ret {}",
@@ -91,7 +161,12 @@ impl<'a> Display for Instruction<'a> {
             Instruction::Jump(block) => {
                 write!(f, "jump {}", block)
             }
+            Instruction::Switch(val, cases, default) => {
+                write!(f, "switch {} {:?} {}", val, cases, default)
+            }
             Instruction::Pointer(val, dest) => write!(f, "%{}: ptr {}", dest, val),
+            Instruction::Deref(val, typ, dest) => write!(f, "%{}: deref {} {:?}", dest, val, typ),
+            Instruction::StoreDeref(ptr, val) => write!(f, "store_deref {} {}", ptr, val),
             Instruction::CreateArray(typ, size, dest) => {
                 write!(f, "%{}: create_array {:?} {}", dest, typ, size)
             }
@@ -101,9 +176,44 @@ impl<'a> Display for Instruction<'a> {
             Instruction::AccessElement(array, index, dest) => {
                 write!(f, "%{}: access_element %{:?} {}", dest, array, index)
             }
+            Instruction::ElementPointer(array, index, dest) => {
+                write!(f, "%{}: element_pointer %{:?} {}", dest, array, index)
+            }
             Instruction::CreateStruct(name, vals, dest) => {
                 write!(f, "%{}: create_struct {} [{:?}]", dest, name, vals)
             }
+            Instruction::CreateTuple(vals, dest) => {
+                write!(f, "%{}: create_tuple [{:?}]", dest, vals)
+            }
+            Instruction::MakeOptional(val, typ, dest) => match val {
+                Some(val) => write!(f, "%{}: make_optional {:?} {:?}", dest, val, typ),
+                None => write!(f, "%{}: make_optional nil {:?}", dest, typ),
+            },
+            Instruction::MakeResult(val, error, typ, dest) => match (val, error) {
+                (_, Some(error)) => write!(f, "%{}: make_error {:?} {:?}", dest, error, typ),
+                (Some(val), None) => write!(f, "%{}: make_result {:?} {:?}", dest, val, typ),
+                (None, None) => write!(f, "%{}: make_result {:?}", dest, typ),
+            },
+            Instruction::FunctionPointer(name, dest) => {
+                write!(f, "%{}: function_pointer @{}", dest, name)
+            }
+            Instruction::MakeClosure(name, env, typ, dest) => {
+                write!(f, "%{}: make_closure @{} {:?} {:?}", dest, name, env, typ)
+            }
+            Instruction::CallClosure(closure, args, _, dest) => {
+                write!(f, "%{}: call_closure {:?} {:?}", dest, closure, args)
+            }
+            Instruction::FreeClosure(closure) => write!(f, "free_closure {:?}", closure),
+            Instruction::MakeInterface(vtable, env, typ, dest) => {
+                write!(
+                    f,
+                    "%{}: make_interface @{} {:?} {:?}",
+                    dest, vtable, env, typ
+                )
+            }
+            Instruction::InterfaceMethod(value, index, dest) => {
+                write!(f, "%{}: interface_method {:?} {}", dest, value, index)
+            }
             Instruction::StoreStructMember(struc, index, val) => {
                 write!(f, "store_struct_member %{}.{} %{}", struc, index, val)
             }
@@ -114,6 +224,32 @@ impl<'a> Display for Instruction<'a> {
                     dest, struc, index, resolve
                 )
             }
+            Instruction::MakeAny(val, tag, dest) => {
+                write!(f, "%{}: make_any {} tag {}", dest, val, tag)
+            }
+            Instruction::AnyTag(val, dest) => write!(f, "%{}: any_tag {}", dest, val),
+            Instruction::AnyPayload(val, typ, dest) => {
+                write!(f, "%{}: any_payload {} {:?}", dest, val, typ)
+            }
+            Instruction::Cast(val, from, to, dest) => {
+                write!(f, "%{}: cast {} {:?} to {:?}", dest, val, from, to)
+            }
+            Instruction::SizeOf(typ, dest) => write!(f, "%{}: sizeof {:?}", dest, typ),
+            Instruction::HeapAlloc(typ, dest) => write!(f, "%{}: heap_alloc {:?}", dest, typ),
+            Instruction::Free(val) => write!(f, "free {}", val),
+            Instruction::StringLen(val, dest) => write!(f, "%{}: string_len {}", dest, val),
+            Instruction::VolatileLoad(ptr, typ, dest) => {
+                write!(f, "%{}: volatile_load {} {:?}", dest, ptr, typ)
+            }
+            Instruction::VolatileStore(ptr, val) => write!(f, "volatile_store {} {}", ptr, val),
+            Instruction::UnalignedLoad(ptr, typ, dest) => {
+                write!(f, "%{}: unaligned_load {} {:?}", dest, ptr, typ)
+            }
+            Instruction::UnalignedStore(ptr, val) => write!(f, "unaligned_store {} {}", ptr, val),
+            Instruction::ByteOrder(val, big, dest) => {
+                let order = if *big { "big" } else { "little" };
+                write!(f, "%{}: byte_order {} {}", dest, order, val)
+            }
         }
     }
 }
@@ -1,22 +1,29 @@
 use core::fmt;
-use std::{collections::HashMap, fmt::Display, rc::Rc};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Display,
+    rc::Rc,
+};
 
 use azula_type::prelude::AzulaType;
 
 use crate::instruction::{Instruction, Value};
 
+// BTreeMaps so functions, globals and structs are emitted in the same order
+// on every build
 pub struct Module<'a> {
     pub name: &'a str,
-    pub functions: HashMap<&'a str, Function<'a>>,
-    pub extern_functions: HashMap<&'a str, ExternFunction<'a>>,
+    pub functions: BTreeMap<&'a str, Function<'a>>,
+    pub extern_functions: BTreeMap<&'a str, ExternFunction<'a>>,
     pub strings: Vec<String>,
-    pub global_values: HashMap<String, GlobalValue>,
-    pub structs: HashMap<&'a str, Struct<'a>>,
+    pub global_values: BTreeMap<String, GlobalValue>,
+    pub mutable_globals: BTreeSet<String>,
+    pub structs: BTreeMap<&'a str, Struct<'a>>,
 }
 
 impl<'a> Module<'a> {
     pub fn new(name: &'a str) -> Self {
-        let mut extern_functions = HashMap::new();
+        let mut extern_functions = BTreeMap::new();
         extern_functions.insert(
             "printf",
             ExternFunction {
@@ -27,11 +34,12 @@ impl<'a> Module<'a> {
         );
         Module {
             name,
-            functions: HashMap::new(),
+            functions: BTreeMap::new(),
             extern_functions,
             strings: vec![],
-            global_values: HashMap::new(),
-            structs: HashMap::new(),
+            global_values: BTreeMap::new(),
+            mutable_globals: BTreeSet::new(),
+            structs: BTreeMap::new(),
         }
     }
 
@@ -59,7 +67,8 @@ impl<'a> Display for Module<'a> {
         writeln!(f, "Module: {}", self.name).unwrap();
         writeln!(f, "Strings:").unwrap();
         for (index, string) in self.strings.iter().enumerate() {
-            writeln!(f, "\t{}: {}", index, string).unwrap();
+            // Quoted, so a string's line breaks don't break up the listing
+            writeln!(f, "\t{}: {:?}", index, string).unwrap();
         }
 
         writeln!(f, "Structs:").unwrap();
@@ -70,7 +79,8 @@ impl<'a> Display for Module<'a> {
         writeln!(f).unwrap();
 
         for (name, func) in &self.functions {
-            writeln!(f, "func {}:", name).unwrap();
+            let visibility = if func.exported { "pub " } else { "" };
+            writeln!(f, "{}func {}:", visibility, name).unwrap();
             writeln!(f, "\tArguments:").unwrap();
             for (var, typ) in &func.arguments {
                 writeln!(f, "\t\t{} {:?}", var, typ).unwrap();
@@ -98,6 +108,11 @@ pub struct Function<'a> {
     pub variables: HashMap<String, AzulaType<'a>>,
     pub arguments: Vec<(String, AzulaType<'a>)>,
     pub returns: AzulaType<'a>,
+    // pub, and so given external linkage instead of being internal to the
+    // object file
+    pub exported: bool,
+    // @ hints from the source, like jumptable
+    pub attributes: Vec<&'a str>,
 
     tmp_var_index: usize,
     pub if_block_index: usize,
@@ -125,6 +140,8 @@ impl<'a> Function<'a> {
             variables: HashMap::new(),
             arguments: vec![],
             returns: AzulaType::Void,
+            exported: false,
+            attributes: vec![],
             tmp_var_index: 0,
             if_block_index: 0,
             current_block: "entry".to_string(),
@@ -139,6 +156,10 @@ impl<'a> Function<'a> {
         Value::Local(self.tmp_var_index - 1)
     }
 
+    pub fn store_global(&mut self, variable: String, val: Value) {
+        self.add_instruction(Instruction::StoreGlobal(variable, val));
+    }
+
     pub fn load_global(&mut self, variable: String, typ: AzulaType<'a>) -> Value {
         self.add_instruction(Instruction::LoadGlobal(variable, self.tmp_var_index, typ));
 
@@ -239,6 +260,22 @@ impl<'a> Function<'a> {
         Value::Local(self.tmp_var_index - 1)
     }
 
+    pub fn udiv(&mut self, val1: Value, val2: Value) -> Value {
+        self.add_instruction(Instruction::UDiv(val1, val2, self.tmp_var_index));
+
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
+    pub fn urem(&mut self, val1: Value, val2: Value) -> Value {
+        self.add_instruction(Instruction::URem(val1, val2, self.tmp_var_index));
+
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
     pub fn pow(&mut self, val1: Value, val2: Value) -> Value {
         self.add_instruction(Instruction::Pow(val1, val2, self.tmp_var_index));
 
@@ -311,6 +348,62 @@ impl<'a> Function<'a> {
         Value::Local(self.tmp_var_index - 1)
     }
 
+    pub fn ugt(&mut self, val1: Value, val2: Value) -> Value {
+        self.add_instruction(Instruction::UGt(val1, val2, self.tmp_var_index));
+
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
+    pub fn ugte(&mut self, val1: Value, val2: Value) -> Value {
+        self.add_instruction(Instruction::UGte(val1, val2, self.tmp_var_index));
+
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
+    pub fn ult(&mut self, val1: Value, val2: Value) -> Value {
+        self.add_instruction(Instruction::ULt(val1, val2, self.tmp_var_index));
+
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
+    pub fn ulte(&mut self, val1: Value, val2: Value) -> Value {
+        self.add_instruction(Instruction::ULte(val1, val2, self.tmp_var_index));
+
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
+    pub fn shl(&mut self, val1: Value, val2: Value) -> Value {
+        self.add_instruction(Instruction::Shl(val1, val2, self.tmp_var_index));
+
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
+    pub fn ashr(&mut self, val1: Value, val2: Value) -> Value {
+        self.add_instruction(Instruction::AShr(val1, val2, self.tmp_var_index));
+
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
+    pub fn lshr(&mut self, val1: Value, val2: Value) -> Value {
+        self.add_instruction(Instruction::LShr(val1, val2, self.tmp_var_index));
+
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
     pub fn not(&mut self, val: Value) -> Value {
         self.add_instruction(Instruction::Not(val, self.tmp_var_index));
 
@@ -319,6 +412,14 @@ impl<'a> Function<'a> {
         Value::Local(self.tmp_var_index - 1)
     }
 
+    pub fn neg(&mut self, val: Value) -> Value {
+        self.add_instruction(Instruction::Neg(val, self.tmp_var_index));
+
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
     pub fn ptr(&mut self, val: String) -> Value {
         self.add_instruction(Instruction::Pointer(val, self.tmp_var_index));
 
@@ -327,6 +428,18 @@ impl<'a> Function<'a> {
         Value::Local(self.tmp_var_index - 1)
     }
 
+    pub fn deref(&mut self, val: Value, typ: AzulaType<'a>) -> Value {
+        self.add_instruction(Instruction::Deref(val, typ, self.tmp_var_index));
+
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
+    pub fn store_deref(&mut self, ptr: Value, val: Value) {
+        self.add_instruction(Instruction::StoreDeref(ptr, val));
+    }
+
     pub fn ret(&mut self, val: Option<Value>) {
         self.add_instruction(Instruction::Return(val));
     }
@@ -347,6 +460,10 @@ impl<'a> Function<'a> {
         self.add_instruction(Instruction::Jump(block));
     }
 
+    pub fn switch(&mut self, val: Value, cases: Vec<(i64, String)>, default: String) {
+        self.add_instruction(Instruction::Switch(val, cases, default));
+    }
+
     pub fn create_array(&mut self, typ: AzulaType<'a>, size: usize) -> Value {
         self.add_instruction(Instruction::CreateArray(typ, size, self.tmp_var_index));
 
@@ -366,6 +483,17 @@ impl<'a> Function<'a> {
         Value::Local(self.tmp_var_index - 1)
     }
 
+    pub fn element_pointer(&mut self, array: Value, index: Value) -> Value {
+        self.add_instruction(Instruction::ElementPointer(
+            array,
+            index,
+            self.tmp_var_index,
+        ));
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
     pub fn create_struct(&mut self, struc: String, values: Vec<Value>) -> Value {
         self.add_instruction(Instruction::CreateStruct(struc, values, self.tmp_var_index));
         self.tmp_var_index += 1;
@@ -373,6 +501,81 @@ impl<'a> Function<'a> {
         Value::Local(self.tmp_var_index - 1)
     }
 
+    pub fn make_optional(&mut self, val: Option<Value>, typ: AzulaType<'a>) -> Value {
+        self.add_instruction(Instruction::MakeOptional(val, typ, self.tmp_var_index));
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
+    pub fn make_result(
+        &mut self,
+        val: Option<Value>,
+        error: Option<Value>,
+        typ: AzulaType<'a>,
+    ) -> Value {
+        self.add_instruction(Instruction::MakeResult(val, error, typ, self.tmp_var_index));
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
+    pub fn function_pointer(&mut self, name: String) -> Value {
+        self.add_instruction(Instruction::FunctionPointer(name, self.tmp_var_index));
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
+    pub fn make_closure(&mut self, name: String, env: Value, typ: AzulaType<'a>) -> Value {
+        self.add_instruction(Instruction::MakeClosure(name, env, typ, self.tmp_var_index));
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
+    pub fn call_closure(&mut self, closure: Value, args: Vec<Value>, typ: AzulaType<'a>) -> Value {
+        self.add_instruction(Instruction::CallClosure(
+            closure,
+            args,
+            typ,
+            self.tmp_var_index,
+        ));
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
+    pub fn make_interface(&mut self, vtable: String, env: Value, typ: AzulaType<'a>) -> Value {
+        self.add_instruction(Instruction::MakeInterface(
+            vtable,
+            env,
+            typ,
+            self.tmp_var_index,
+        ));
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
+    pub fn interface_method(&mut self, value: Value, index: usize) -> Value {
+        self.add_instruction(Instruction::InterfaceMethod(
+            value,
+            index,
+            self.tmp_var_index,
+        ));
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
+    pub fn create_tuple(&mut self, values: Vec<Value>) -> Value {
+        self.add_instruction(Instruction::CreateTuple(values, self.tmp_var_index));
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
     pub fn access_struct_member(&mut self, struc: Value, index: usize, resolve: bool) -> Value {
         self.add_instruction(Instruction::AccessStructMember(
             struc,
@@ -389,6 +592,92 @@ impl<'a> Function<'a> {
         self.add_instruction(Instruction::StoreStructMember(struc, index, value));
     }
 
+    pub fn cast(&mut self, val: Value, from: AzulaType<'a>, to: AzulaType<'a>) -> Value {
+        self.add_instruction(Instruction::Cast(val, from, to, self.tmp_var_index));
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
+    pub fn size_of(&mut self, typ: AzulaType<'a>) -> Value {
+        self.add_instruction(Instruction::SizeOf(typ, self.tmp_var_index));
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
+    pub fn heap_alloc(&mut self, typ: AzulaType<'a>) -> Value {
+        self.add_instruction(Instruction::HeapAlloc(typ, self.tmp_var_index));
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
+    pub fn free(&mut self, val: Value) {
+        self.add_instruction(Instruction::Free(val));
+    }
+
+    pub fn free_closure(&mut self, closure: Value) {
+        self.add_instruction(Instruction::FreeClosure(closure));
+    }
+
+    pub fn volatile_load(&mut self, ptr: Value, typ: AzulaType<'a>) -> Value {
+        self.add_instruction(Instruction::VolatileLoad(ptr, typ, self.tmp_var_index));
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
+    pub fn volatile_store(&mut self, ptr: Value, val: Value) {
+        self.add_instruction(Instruction::VolatileStore(ptr, val));
+    }
+
+    pub fn unaligned_load(&mut self, ptr: Value, typ: AzulaType<'a>) -> Value {
+        self.add_instruction(Instruction::UnalignedLoad(ptr, typ, self.tmp_var_index));
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
+    pub fn unaligned_store(&mut self, ptr: Value, val: Value) {
+        self.add_instruction(Instruction::UnalignedStore(ptr, val));
+    }
+
+    pub fn byte_order(&mut self, val: Value, big: bool) -> Value {
+        self.add_instruction(Instruction::ByteOrder(val, big, self.tmp_var_index));
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
+    pub fn string_len(&mut self, val: Value) -> Value {
+        self.add_instruction(Instruction::StringLen(val, self.tmp_var_index));
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
+    pub fn make_any(&mut self, val: Value, tag: i64) -> Value {
+        self.add_instruction(Instruction::MakeAny(val, tag, self.tmp_var_index));
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
+    pub fn any_tag(&mut self, val: Value) -> Value {
+        self.add_instruction(Instruction::AnyTag(val, self.tmp_var_index));
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
+    pub fn any_payload(&mut self, val: Value, typ: AzulaType<'a>) -> Value {
+        self.add_instruction(Instruction::AnyPayload(val, typ, self.tmp_var_index));
+        self.tmp_var_index += 1;
+
+        Value::Local(self.tmp_var_index - 1)
+    }
+
     fn add_instruction(&mut self, instruction: Instruction<'a>) {
         self.blocks
             .iter_mut()
@@ -420,6 +709,8 @@ pub enum GlobalValue {
     Bool(bool),
     String(usize),
     Array(Vec<GlobalValue>),
+    // A vtable, the functions for an interface's methods in order
+    Functions(Vec<String>),
 }
 
 #[derive(Debug, Clone)]
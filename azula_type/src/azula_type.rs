@@ -1,4 +1,4 @@
-use std::{ops::Deref, rc::Rc};
+use std::{fmt, ops::Deref, rc::Rc};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum AzulaType<'a> {
@@ -15,6 +15,28 @@ pub enum AzulaType<'a> {
     Named(String),
     UnknownType(&'a str),
     Array(Rc<AzulaType<'a>>, Option<usize>),
+    // [T; N] with the size given by a constant, which the typechecker
+    // evaluates to make it an Array
+    ConstArray(Rc<AzulaType<'a>>, String),
+    // []T, an array of any length, which the typechecker replaces with the
+    // built in struct Slice<T> holding its elements and their count
+    Slice(Rc<AzulaType<'a>>),
+    Any,
+    Tuple(Vec<AzulaType<'a>>),
+    Optional(Rc<AzulaType<'a>>),
+    // !T holds either a T or an error message
+    Result(Rc<AzulaType<'a>>),
+    // fn(params) -> returns, a closure at runtime
+    Function(Vec<AzulaType<'a>>, Rc<AzulaType<'a>>),
+    // Name<A, B>, which the typechecker replaces with the struct instantiated
+    // for those type arguments
+    Generic(String, Vec<AzulaType<'a>>),
+    // A value of any type implementing the interface, which carries the
+    // vtable for its methods
+    Interface(String),
+    // typeof(x), the type of the variable x, which the typechecker looks up
+    // where it's used
+    TypeOf(String),
 }
 
 impl<'a> From<&'a str> for AzulaType<'a> {
@@ -32,20 +54,140 @@ impl<'a> From<&'a str> for AzulaType<'a> {
             "f32" => Self::SizedFloat(32),
             "f64" => Self::SizedFloat(64),
             "str" => Self::Str,
+            // For C functions taking a char *
+            "string" => Self::Pointer(Rc::new(Self::Str)),
             "float" => Self::Float,
             "bool" => Self::Bool,
             "void" => Self::Void,
+            "any" => Self::Any,
             _ => Self::Named(val.to_string()),
         }
     }
 }
 
+// Written the way it is in source
+impl<'a> fmt::Display for AzulaType<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let join = |types: &[AzulaType]| {
+            let types: Vec<_> = types.iter().map(AzulaType::to_string).collect();
+            types.join(", ")
+        };
+
+        match self {
+            AzulaType::Int => write!(f, "int"),
+            AzulaType::SizedSignedInt(size) => write!(f, "i{}", size),
+            AzulaType::SizedUnsignedInt(size) => write!(f, "u{}", size),
+            AzulaType::Str => write!(f, "str"),
+            AzulaType::Float => write!(f, "float"),
+            AzulaType::SizedFloat(size) => write!(f, "f{}", size),
+            AzulaType::Bool => write!(f, "bool"),
+            AzulaType::Void => write!(f, "void"),
+            AzulaType::Any => write!(f, "any"),
+            AzulaType::Pointer(inner) => write!(f, "&{}", inner),
+            AzulaType::Named(name) | AzulaType::Interface(name) => write!(f, "{}", name),
+            AzulaType::UnknownType(name) => write!(f, "{}", name),
+            AzulaType::Array(inner, Some(size)) => write!(f, "[{}; {}]", inner, size),
+            AzulaType::Array(inner, None) => write!(f, "[{}]", inner),
+            AzulaType::ConstArray(inner, size) => write!(f, "[{}; {}]", inner, size),
+            AzulaType::Slice(inner) => write!(f, "[]{}", inner),
+            AzulaType::Tuple(types) => write!(f, "({})", join(types)),
+            AzulaType::Optional(inner) => write!(f, "?{}", inner),
+            AzulaType::Result(inner) => write!(f, "!{}", inner),
+            AzulaType::Function(params, returns) => {
+                write!(f, "fn({}) -> {}", join(params), returns)
+            }
+            AzulaType::Generic(name, args) => write!(f, "{}<{}>", name, join(args)),
+            AzulaType::TypeOf(name) => write!(f, "typeof({})", name),
+            // Only comes out of the typechecker, there is no syntax for it
+            AzulaType::Infer => write!(f, "_"),
+        }
+    }
+}
+
 impl<'a> AzulaType<'a> {
     pub fn is_indexable(&self) -> bool {
         match self {
-            AzulaType::Array(..) => true,
+            AzulaType::Array(..) | AzulaType::ConstArray(..) => true,
+            AzulaType::Slice(..) => true,
             AzulaType::Pointer(..) => true,
             _ => false,
         }
     }
+
+    /// The runtime tag used when a value of this type is stored in an `any`,
+    /// or None if the type can't be stored in one
+    pub fn any_tag(&self) -> Option<i64> {
+        match self {
+            AzulaType::Int => Some(1),
+            AzulaType::Float => Some(2),
+            AzulaType::Bool => Some(3),
+            AzulaType::Pointer(nested) if nested.deref() == &AzulaType::Str => Some(4),
+            _ => None,
+        }
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, AzulaType::Float | AzulaType::SizedFloat(_))
+    }
+
+    /// A name for the type that can go in a symbol, e.g. Square.area for the
+    /// area method implemented for Square
+    pub fn mangled(&self) -> String {
+        let join = |types: &[AzulaType], separator| {
+            let types: Vec<_> = types.iter().map(AzulaType::mangled).collect();
+            types.join(separator)
+        };
+
+        match self {
+            AzulaType::Int => "int".to_string(),
+            AzulaType::Float => "float".to_string(),
+            AzulaType::Bool => "bool".to_string(),
+            AzulaType::Str => "str".to_string(),
+            AzulaType::Void => "void".to_string(),
+            AzulaType::Any => "any".to_string(),
+            AzulaType::Infer => "infer".to_string(),
+            AzulaType::SizedSignedInt(size) => format!("i{}", size),
+            AzulaType::SizedUnsignedInt(size) => format!("u{}", size),
+            AzulaType::SizedFloat(size) => format!("f{}", size),
+            AzulaType::Named(name) => name.clone(),
+            AzulaType::UnknownType(name) => name.to_string(),
+            AzulaType::Pointer(inner) => format!("ptr_{}", inner.mangled()),
+            AzulaType::Array(inner, Some(size)) => format!("arr{}_{}", size, inner.mangled()),
+            AzulaType::Array(inner, None) => format!("arr_{}", inner.mangled()),
+            AzulaType::ConstArray(inner, size) => format!("arr{}_{}", size, inner.mangled()),
+            AzulaType::Slice(inner) => format!("slice_{}", inner.mangled()),
+            AzulaType::Optional(inner) => format!("opt_{}", inner.mangled()),
+            AzulaType::Result(inner) => format!("res_{}", inner.mangled()),
+            AzulaType::Tuple(types) => format!("tup_{}_", join(types, "_")),
+            AzulaType::Function(params, returns) => {
+                format!("fn_{}_{}", join(params, "_"), returns.mangled())
+            }
+            AzulaType::Generic(name, args) => format!("{}.{}", name, join(args, ".")),
+            AzulaType::Interface(name) => format!("dyn_{}", name),
+            AzulaType::TypeOf(name) => format!("typeof_{}", name),
+        }
+    }
+
+    /// What a value of the type is kept as inside an array, and how many.
+    /// Sized arrays are stored inline as their elements, so `[[int; 3]; 2]`
+    /// holds six ints with each row pointing at its first
+    pub fn inline_elements(&self) -> (AzulaType<'a>, usize) {
+        match self {
+            AzulaType::Array(inner, Some(size)) => {
+                let (element, count) = inner.inline_elements();
+                (element, count * size)
+            }
+            _ => (self.clone(), 1),
+        }
+    }
+
+    /// The width in bits of an integer type, or None for non-integers
+    pub fn integer_width(&self) -> Option<usize> {
+        match self {
+            AzulaType::Int => Some(64),
+            AzulaType::SizedSignedInt(size) => Some(*size),
+            AzulaType::SizedUnsignedInt(size) => Some(*size),
+            _ => None,
+        }
+    }
 }
@@ -1,4 +1,4 @@
-use std::error::Error;
+use std::{error::Error, path::Path};
 
 use azula_ir::prelude::Module;
 
@@ -7,15 +7,125 @@ pub trait Backend<'a> {
         name: &'a str,
         destination: &'a str,
         emit: bool,
-        target: Option<&String>,
+        target: &TargetOptions,
         opt_level: OptimizationLevel,
         module: Module<'a>,
     ) -> Result<(), Box<dyn Error>>;
+
+    /// Compiles each program into its own binary, like calling `codegen` for
+    /// each, but backends can share their setup between them
+    fn codegen_programs(
+        programs: Vec<(&'a str, Module<'a>)>,
+        destination: &'a str,
+        emit: bool,
+        target: &TargetOptions,
+        opt_level: OptimizationLevel,
+    ) -> Result<(), Box<dyn Error>> {
+        for (name, module) in programs {
+            Self::codegen(name, destination, emit, target, opt_level, module)?;
+        }
+
+        Ok(())
+    }
+
+    /// Compiles the module to an object file without linking, returning its path
+    fn build_object(
+        name: &'a str,
+        target: &TargetOptions,
+        opt_level: OptimizationLevel,
+        module: Module<'a>,
+    ) -> Result<String, Box<dyn Error>>;
+
+    /// Compiles the module in memory and runs its main with `args`, returning
+    /// the exit code. The compiled module is saved to `cache` for `run_cached`
+    fn run(
+        module: Module<'a>,
+        opt_level: OptimizationLevel,
+        cache: &Path,
+        args: &[String],
+    ) -> Result<i32, Box<dyn Error>>;
+
+    /// Runs a module saved by `run` without compiling it again
+    fn run_cached(
+        cache: &Path,
+        opt_level: OptimizationLevel,
+        args: &[String],
+    ) -> Result<i32, Box<dyn Error>>;
 }
 
-#[derive(PartialEq)]
+/// What to compile for, anything left out is the host's default
+#[derive(Default, Clone, Debug)]
+pub struct TargetOptions {
+    pub triple: Option<String>,
+    pub cpu: Option<String>,
+    // LLVM feature string, like +m,+a,+c
+    pub features: Option<String>,
+    pub reloc_model: RelocModel,
+    // An LLVM data layout string used instead of the target's
+    pub data_layout: Option<String>,
+    // Passed on to the linker, to place code and data at fixed addresses
+    pub linker_script: Option<String>,
+    // Bare metal, linked without the C runtime and its default entry point
+    pub freestanding: bool,
+}
+
+impl TargetOptions {
+    /// Names accepted by `preset`
+    pub const PRESETS: &'static [&'static str] = &["thumbv7em", "riscv32imac"];
+
+    /// Settings for a common embedded target in one go. They're bare metal,
+    /// so freestanding and statically relocated
+    pub fn preset(name: &str) -> Option<TargetOptions> {
+        let (triple, cpu, features) = match name {
+            // Cortex-M4 and M7, without hardware floats
+            "thumbv7em" => ("thumbv7em-none-eabi", "cortex-m4", "+soft-float"),
+            "riscv32imac" => ("riscv32imac-unknown-none-elf", "generic-rv32", "+m,+a,+c"),
+            _ => return None,
+        };
+
+        Some(TargetOptions {
+            triple: Some(triple.to_string()),
+            cpu: Some(cpu.to_string()),
+            features: Some(features.to_string()),
+            reloc_model: RelocModel::Static,
+            freestanding: true,
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RelocModel {
+    Default,
+    Static,
+    Pic,
+}
+
+impl Default for RelocModel {
+    fn default() -> Self {
+        RelocModel::Default
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
 pub enum OptimizationLevel {
     None,
     Default,
     Aggressive,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_presets() {
+        for name in TargetOptions::PRESETS {
+            let preset = TargetOptions::preset(name).unwrap();
+            assert!(preset.triple.unwrap().starts_with(name));
+            assert!(preset.freestanding);
+            assert_eq!(preset.reloc_model, RelocModel::Static);
+        }
+        assert!(TargetOptions::preset("x86").is_none());
+    }
+}
@@ -1,15 +1,28 @@
 use core::panic;
-use std::{collections::HashMap, ops::Deref};
+use std::{collections::HashMap, ops::Deref, rc::Rc};
 
 use azula_ast::prelude::*;
 use azula_ir::prelude::*;
 use azula_type::prelude::AzulaType;
 
+use crate::scopes::rename_shadowed;
+
 pub struct Codegen<'a> {
     root: Statement<'a>,
 
     pub module: Module<'a>,
     pub function_calls: HashMap<String, Vec<AzulaType<'a>>>,
+    // Deferred expressions in scope, in the order they appear. The ones in
+    // a block are dropped when it ends
+    defers: Vec<ExpressionNode<'a>>,
+    // The loops around the current statement, innermost last, by label with
+    // the blocks continue and break jump to and how many defers were in
+    // scope outside them
+    loops: Vec<(Option<&'a str>, String, String, usize)>,
+    // For naming the functions closures are lifted into
+    closures: usize,
+    // Method signatures, in vtable order
+    interfaces: HashMap<&'a str, Vec<MethodSignature<'a>>>,
 }
 
 impl<'a> Codegen<'a> {
@@ -18,14 +31,24 @@ impl<'a> Codegen<'a> {
             root,
             module: Module::new(name),
             function_calls: HashMap::new(),
+            defers: vec![],
+            loops: vec![],
+            closures: 0,
+            interfaces: HashMap::new(),
         }
     }
 
     pub fn codegen(&mut self) {
         if let Statement::Root(stmts) = &self.root {
+            // Everything besides the functions is declared before any of
+            // them are generated, so they can use globals and structs defined
+            // after them
             for stmt in stmts.clone() {
                 match stmt {
-                    Statement::Function { .. } => self.codegen_function(stmt.clone()),
+                    Statement::Interface { name, methods, .. } => {
+                        self.interfaces.insert(name, methods);
+                    }
+                    Statement::Function { .. } => {}
                     Statement::ExternFunction {
                         name,
                         varargs,
@@ -36,11 +59,11 @@ impl<'a> Codegen<'a> {
                         name,
                         ExternFunction {
                             varargs,
-                            arguments: args,
+                            arguments: args.into_iter().map(|(typ, _)| typ).collect(),
                             returns: returns,
                         },
                     ),
-                    Statement::Assign(_, name, _, val, ..) => {
+                    Statement::Assign(mutable, name, _, val, ..) => {
                         let value = match val.expression {
                             Expression::Integer(i) => GlobalValue::Int(i),
                             Expression::Float(f) => GlobalValue::Float(f),
@@ -56,6 +79,9 @@ impl<'a> Codegen<'a> {
                             _ => unreachable!(),
                         };
 
+                        if mutable {
+                            self.module.mutable_globals.insert(name.clone());
+                        }
                         self.module.global_values.insert(name, value);
                     }
                     Statement::Struct {
@@ -66,6 +92,12 @@ impl<'a> Codegen<'a> {
                     _ => unreachable!(),
                 }
             }
+
+            for stmt in stmts.clone() {
+                if let Statement::Function { .. } = stmt {
+                    self.codegen_function(stmt);
+                }
+            }
         }
     }
 
@@ -83,6 +115,7 @@ impl<'a> Codegen<'a> {
                 match block.instructions.last().unwrap() {
                     Instruction::Jcond(..) => continue,
                     Instruction::Jump(..) => continue,
+                    Instruction::Switch(..) => continue,
                     Instruction::Return(..) => continue,
                     _ => {
                         block.instructions.push(Instruction::Return(None));
@@ -94,16 +127,106 @@ impl<'a> Codegen<'a> {
         }
     }
 
+    /// Replaces main with one calling each `@test` function in order and
+    /// printing whether it passed, where failing is returning an error. It
+    /// exits with 1 if any failed
+    pub fn codegen_test_harness(&mut self) {
+        let tests: Vec<(&'a str, bool)> = match &self.root {
+            Statement::Root(stmts) => stmts
+                .iter()
+                .filter_map(|stmt| match stmt {
+                    Statement::Function {
+                        name,
+                        returns,
+                        attributes,
+                        ..
+                    } if attributes.iter().any(|attribute| attribute.name == "test") => {
+                        Some((*name, matches!(returns, AzulaType::Result(_))))
+                    }
+                    _ => None,
+                })
+                .collect(),
+            _ => unreachable!(),
+        };
+
+        let mut main = Function::new();
+        main.returns = AzulaType::Int;
+        let failures = "failures".to_string();
+        let zero = main.const_int(0);
+        main.store(failures.clone(), zero, AzulaType::Int);
+        main.variables.insert(failures.clone(), AzulaType::Int);
+
+        for (index, (name, fallible)) in tests.iter().enumerate() {
+            let running = self.module.add_string(format!("test {} ... ", name));
+            main.function_call("printf".to_string(), vec![running]);
+            let result = main.function_call(name.to_string(), vec![]);
+            let ok = self.module.add_string("ok\n".to_string());
+            if !fallible {
+                main.function_call("printf".to_string(), vec![ok]);
+                continue;
+            }
+
+            let passed_name = format!("passed-{}", index);
+            let failed_name = format!("failed-{}", index);
+            let next_name = format!("next-{}", index);
+            let passed = main.access_struct_member(result.clone(), 0, true);
+            main.jcond(passed, passed_name.clone(), failed_name.clone());
+
+            main.blocks.push((passed_name.clone(), Block::new()));
+            main.current_block = passed_name;
+            main.function_call("printf".to_string(), vec![ok]);
+            main.jump(next_name.clone());
+
+            main.blocks.push((failed_name.clone(), Block::new()));
+            main.current_block = failed_name;
+            let message = main.access_struct_member(result, 1, true);
+            let failed = self.module.add_string("FAILED: %s\n".to_string());
+            main.function_call("printf".to_string(), vec![failed, message]);
+            let count = main.load(failures.clone(), AzulaType::Int);
+            let one = main.const_int(1);
+            let count = main.add(count, one);
+            main.store(failures.clone(), count, AzulaType::Int);
+            main.jump(next_name.clone());
+
+            main.blocks.push((next_name.clone(), Block::new()));
+            main.current_block = next_name;
+        }
+
+        let count = main.load(failures, AzulaType::Int);
+        let summary = self
+            .module
+            .add_string(format!("\n{} tests, %d failed\n", tests.len()));
+        main.function_call("printf".to_string(), vec![summary, count.clone()]);
+        let zero = main.const_int(0);
+        let any_failed = main.gt(count, zero);
+        main.jcond(
+            any_failed,
+            "tests-failed".to_string(),
+            "tests-passed".to_string(),
+        );
+        for (block, code) in [("tests-failed", 1), ("tests-passed", 0)] {
+            main.blocks.push((block.to_string(), Block::new()));
+            main.current_block = block.to_string();
+            let code = main.const_int(code);
+            main.ret(Some(code));
+        }
+
+        self.module.add_function("main", main);
+    }
+
     pub fn codegen_function(&mut self, stmt: Statement<'a>) {
         if let Statement::Function {
             name,
             args,
             returns,
             body,
+            public,
+            attributes,
             ..
         } = stmt
         {
             let mut arguments = vec![];
+            let body = rename_shadowed(&args, body.as_ref().clone());
             for (typ, name) in args {
                 arguments.push((name.to_string(), typ));
             }
@@ -111,13 +234,24 @@ impl<'a> Codegen<'a> {
             let mut function = Function::new();
             function.arguments = arguments;
             function.returns = returns;
+            function.exported = public;
+            function.attributes = attributes.iter().map(|attribute| attribute.name).collect();
 
-            if let Statement::Block(stmts) = body.as_ref().clone() {
+            self.defers.clear();
+            if let Statement::Block(stmts) = body {
                 for stmt in stmts {
                     self.codegen_statement(stmt, &mut function);
                 }
             }
 
+            // Falling off the end of the function is an exit too
+            if !self.defers.is_empty() && !ends_with_return(&function) {
+                self.codegen_defers(0, &mut function);
+                if function.returns == AzulaType::Void {
+                    function.ret(None);
+                }
+            }
+
             self.module.add_function(name, function)
         } else {
             unreachable!()
@@ -133,13 +267,21 @@ impl<'a> Codegen<'a> {
             }
             Statement::If(..) => self.codegen_if(stmt, func),
             Statement::While(..) => self.codegen_while(stmt, func),
+            Statement::DoWhile(..) => self.codegen_do_while(stmt, func),
+            Statement::For(..) => self.codegen_for(stmt, func),
             Statement::Reassign(..) => self.codegen_reassign(stmt, func),
+            Statement::Match(..) => self.codegen_match(stmt, func),
+            Statement::Destructure(..) => self.codegen_destructure(stmt, func),
+            Statement::IfLet(..) => self.codegen_if_let(stmt, func),
+            Statement::Defer(expr, _) => self.defers.push(expr),
+            Statement::Scope(body, _) => self.codegen_body(body, func),
+            Statement::Break(..) | Statement::Continue(..) => self.codegen_loop_control(stmt, func),
             _ => panic!(),
         }
     }
 
     pub fn codegen_assign(&mut self, stmt: Statement<'a>, func: &mut Function<'a>) {
-        if let Statement::Assign(_, name, _, expr, _) = stmt {
+        if let Statement::Assign(_, name, _, expr, _, _) = stmt {
             let value = self.codegen_expr(expr.clone(), func, true);
             func.store(name.clone(), value, expr.typed.clone());
             func.variables.insert(name, expr.typed);
@@ -148,45 +290,90 @@ impl<'a> Codegen<'a> {
         }
     }
 
+    pub fn codegen_destructure(&mut self, stmt: Statement<'a>, func: &mut Function<'a>) {
+        if let Statement::Destructure(_, names, expr, _) = stmt {
+            let types = match &expr.typed {
+                AzulaType::Tuple(types) => types.clone(),
+                _ => unreachable!("{:?}", expr.typed),
+            };
+
+            let value = self.codegen_expr(expr, func, true);
+            for (index, (name, typ)) in names.into_iter().zip(types).enumerate() {
+                let element = func.access_struct_member(value.clone(), index, true);
+                func.store(name.to_string(), element, typ.clone());
+                func.variables.insert(name.to_string(), typ);
+            }
+        } else {
+            unreachable!()
+        }
+    }
+
     pub fn codegen_reassign(&mut self, stmt: Statement<'a>, func: &mut Function<'a>) {
         if let Statement::Reassign(var, val, _) = stmt {
-            let value = self.codegen_expr(val.clone(), func, true);
-            match var.expression {
-                Expression::Identifier(v) => func.store(v.clone(), value, val.typed.clone()),
-                Expression::ArrayAccess(array, index) => {
-                    let array = self.codegen_expr(array.deref().clone(), func, true);
-                    let index = self.codegen_expr(index.deref().clone(), func, true);
+            let value = self.codegen_expr(val, func, true);
+            self.codegen_store(var, value, func);
+        } else {
+            unreachable!()
+        }
+    }
+
+    fn codegen_store(&mut self, var: ExpressionNode<'a>, value: Value, func: &mut Function<'a>) {
+        match var.expression {
+            Expression::Identifier(v)
+                if !func.variables.contains_key(&v)
+                    && self.module.global_values.contains_key(&v) =>
+            {
+                func.store_global(v, value)
+            }
+            Expression::Identifier(v) => func.store(v.clone(), value, var.typed.clone()),
+            Expression::ArrayAccess(array, index) => {
+                let array = self.codegen_expr(array.deref().clone(), func, true);
+                let index = self.codegen_expr(index.deref().clone(), func, true);
+                // A row is copied into place
+                if let AzulaType::Array(_, Some(_)) = var.typed {
+                    let (_, count) = var.typed.inline_elements();
+                    let count_value = func.const_int(count as i64);
+                    let offset = func.mul(index, count_value);
+                    self.copy_elements(value, array, offset, count, func);
+                } else {
                     func.store_element(array.clone(), index, value);
                 }
-                Expression::StructAccess(struc, member) => {
-                    let struc_val = self.codegen_expr(struc.deref().clone(), func, true);
-                    let member_name = match &member.expression {
-                        Expression::Identifier(v) => v,
-                        _ => unreachable!(),
-                    };
-                    let struct_name = match &struc.typed {
+            }
+            Expression::StructAccess(struc, member) => {
+                let struc_val = self.codegen_expr(struc.deref().clone(), func, true);
+                let member_name = match &member.expression {
+                    Expression::Identifier(v) => v,
+                    _ => unreachable!(),
+                };
+                let struct_name = match &struc.typed {
+                    AzulaType::Named(name) => name.clone(),
+                    AzulaType::Pointer(nested) => match nested.deref().clone() {
                         AzulaType::Named(name) => name.clone(),
-                        AzulaType::Pointer(nested) => match nested.deref().clone() {
-                            AzulaType::Named(name) => name.clone(),
-                            _ => unreachable!("{:?}", struc.typed),
-                        },
                         _ => unreachable!("{:?}", struc.typed),
-                    };
+                    },
+                    _ => unreachable!("{:?}", struc.typed),
+                };
 
-                    let struct_def = self.module.structs.get(struct_name.as_str()).unwrap();
-                    let index = struct_def
-                        .attributes
-                        .iter()
-                        .enumerate()
-                        .find(|(_, (_, name))| name.to_string() == member_name.to_string())
-                        .map(|(index, _)| index)
-                        .unwrap();
-                    func.store_struct_member(struc_val.clone(), index, value)
+                let struct_def = self.module.structs.get(struct_name.as_str()).unwrap();
+                let index = struct_def
+                    .attributes
+                    .iter()
+                    .enumerate()
+                    .find(|(_, (_, name))| name.to_string() == member_name.to_string())
+                    .map(|(index, _)| index)
+                    .unwrap();
+                func.store_struct_member(struc_val.clone(), index, value);
+                // A struct held by value is changed in a copy, which goes
+                // back where it was read from
+                if !matches!(struc.typed, AzulaType::Pointer(_)) {
+                    self.codegen_store(struc.deref().clone(), struc_val, func);
                 }
-                _ => todo!(),
             }
-        } else {
-            unreachable!()
+            Expression::Deref(pointer) => {
+                let pointer = self.codegen_expr(pointer.deref().clone(), func, true);
+                func.store_deref(pointer, value);
+            }
+            _ => todo!(),
         }
     }
 
@@ -194,16 +381,54 @@ impl<'a> Codegen<'a> {
         if let Statement::Return(val, _) = stmt {
             match val {
                 Some(expr) => {
+                    // A local returned by name is the caller's now, so the
+                    // drop the typechecker deferred for it is left out. So
+                    // are the drops of the locals a returned closure captures
+                    let defers = self.defers.clone();
+                    match &expr.expression {
+                        Expression::Identifier(name) => {
+                            self.defers.retain(|defer| !is_drop_of(defer, name))
+                        }
+                        Expression::Closure { captures, .. } => self.defers.retain(|defer| {
+                            !captures.iter().any(|(_, name)| is_drop_of(defer, name))
+                        }),
+                        _ => {}
+                    }
                     let value = self.codegen_expr(expr, func, true);
+                    self.codegen_defers(0, func);
+                    self.defers = defers;
                     func.ret(Some(value));
                 }
-                None => func.ret(None),
+                None => {
+                    self.codegen_defers(0, func);
+                    func.ret(None)
+                }
             }
         } else {
             unreachable!()
         }
     }
 
+    // Deferred expressions run last first, after any return value has been
+    // worked out. Leaving a block only runs the ones from `from` on
+    fn codegen_defers(&mut self, from: usize, func: &mut Function<'a>) {
+        for expr in self.defers[from..].to_vec().into_iter().rev() {
+            self.codegen_expr(expr, func, true);
+        }
+    }
+
+    // The defers in a nested block run at its end, if it gets there
+    fn codegen_body(&mut self, body: Vec<Statement<'a>>, func: &mut Function<'a>) {
+        let depth = self.defers.len();
+        for stmt in body {
+            self.codegen_statement(stmt, func);
+        }
+        if !ends_with_return(func) {
+            self.codegen_defers(depth, func);
+        }
+        self.defers.truncate(depth);
+    }
+
     pub fn codegen_if(&mut self, stmt: Statement<'a>, func: &mut Function<'a>) {
         if let Statement::If(cond, body, ..) = stmt {
             let cond = self.codegen_expr(cond, func, true);
@@ -218,9 +443,7 @@ impl<'a> Codegen<'a> {
 
             func.current_block = true_name.clone();
 
-            for stmt in body {
-                self.codegen_statement(stmt, func);
-            }
+            self.codegen_body(body, func);
 
             for (name, block) in &func.blocks.clone() {
                 if name.clone() == func.current_block {
@@ -238,8 +461,45 @@ impl<'a> Codegen<'a> {
         }
     }
 
+    // Optionals are a {present, value} pair, the body only runs when the
+    // first field is set
+    pub fn codegen_if_let(&mut self, stmt: Statement<'a>, func: &mut Function<'a>) {
+        if let Statement::IfLet(_, name, expr, body, ..) = stmt {
+            let inner = match &expr.typed {
+                AzulaType::Optional(inner) => inner.deref().clone(),
+                _ => unreachable!("{:?}", expr.typed),
+            };
+
+            let value = self.codegen_expr(expr, func, true);
+            let present = func.access_struct_member(value.clone(), 0, true);
+
+            let true_name = format!("true-{}", func.if_block_index);
+            let end_name = format!("end-{}", func.if_block_index);
+
+            func.if_block_index += 1;
+
+            func.jcond(present, true_name.clone(), end_name.clone());
+            func.blocks.push((true_name.clone(), Block::new()));
+            func.current_block = true_name;
+
+            let inner_value = func.access_struct_member(value, 1, true);
+            func.store(name.to_string(), inner_value, inner.clone());
+            func.variables.insert(name.to_string(), inner);
+
+            self.codegen_body(body, func);
+            if !ends_with_return(func) {
+                func.jump(end_name.clone());
+            }
+
+            func.blocks.push((end_name.clone(), Block::new()));
+            func.current_block = end_name;
+        } else {
+            unreachable!()
+        }
+    }
+
     pub fn codegen_while(&mut self, stmt: Statement<'a>, func: &mut Function<'a>) {
-        if let Statement::While(cond, body, ..) = stmt {
+        if let Statement::While(cond, body, label, _) = stmt {
             let eval_name = format!("eval-{}", func.if_block_index);
             let true_name = format!("loop-{}", func.if_block_index);
             let end_name = format!("end-{}", func.if_block_index);
@@ -255,9 +515,14 @@ impl<'a> Codegen<'a> {
             func.blocks.push((true_name.clone(), Block::new()));
             func.current_block = true_name.clone();
 
-            for stmt in body {
-                self.codegen_statement(stmt, func);
-            }
+            self.loops.push((
+                label,
+                eval_name.clone(),
+                end_name.clone(),
+                self.defers.len(),
+            ));
+            self.codegen_body(body, func);
+            self.loops.pop();
             func.jump(eval_name.clone());
 
             func.blocks.push((end_name.clone(), Block::new()));
@@ -267,6 +532,378 @@ impl<'a> Codegen<'a> {
         }
     }
 
+    // An index counts up to the number of elements, and before each run of
+    // the body the element it's at is stored in the loop variable, and the
+    // index itself for for i, x. A range's elements are its values from the
+    // start. continue goes on to the next index
+    pub fn codegen_for(&mut self, stmt: Statement<'a>, func: &mut Function<'a>) {
+        if let Statement::For(index_name, name, iterable, body, label, _) = stmt {
+            let eval_name = format!("eval-{}", func.if_block_index);
+            let true_name = format!("loop-{}", func.if_block_index);
+            let step_name = format!("step-{}", func.if_block_index);
+            let end_name = format!("end-{}", func.if_block_index);
+            // Can't be written in source, so it can't clash with a variable
+            let index = format!("for.{}", func.if_block_index);
+
+            func.if_block_index += 1;
+
+            let typ = iterable.typed.clone();
+            let value = self.codegen_expr(iterable, func, true);
+            // The array of elements, or for a range None and its start
+            let (len, elements, start, element) = match &typ {
+                AzulaType::Array(element, Some(size)) => (
+                    func.const_int(*size as i64),
+                    Some(value),
+                    None,
+                    element.deref().clone(),
+                ),
+                AzulaType::Named(name) => {
+                    match self.module.structs[name.as_str()].attributes[0].clone() {
+                        (typ, "start") => {
+                            let start = func.access_struct_member(value.clone(), 0, true);
+                            let end = func.access_struct_member(value, 1, true);
+                            (func.sub(end, start.clone()), None, Some(start), typ)
+                        }
+                        // Slice<T> { data: [T], len: int }
+                        (AzulaType::Array(element, _), _) => (
+                            func.access_struct_member(value.clone(), 1, true),
+                            Some(func.access_struct_member(value, 0, true)),
+                            None,
+                            element.deref().clone(),
+                        ),
+                        _ => unreachable!("{:?}", typ),
+                    }
+                }
+                _ => unreachable!("{:?}", typ),
+            };
+            let zero = func.const_int(0);
+            func.store(index.clone(), zero, AzulaType::Int);
+            func.variables.insert(index.clone(), AzulaType::Int);
+
+            func.jump(eval_name.clone());
+            func.blocks.push((eval_name.clone(), Block::new()));
+            func.current_block = eval_name.clone();
+            let current = func.load(index.clone(), AzulaType::Int);
+            let cond_val = func.lt(current, len);
+            func.jcond(cond_val, true_name.clone(), end_name.clone());
+
+            func.blocks.push((true_name.clone(), Block::new()));
+            func.current_block = true_name.clone();
+            let current = func.load(index.clone(), AzulaType::Int);
+            let value = match (elements, start) {
+                // Rows are stored inline, so one is its first element's address
+                (Some(elements), _) if matches!(element, AzulaType::Array(_, Some(_))) => {
+                    let (_, count) = element.inline_elements();
+                    let count = func.const_int(count as i64);
+                    let offset = func.mul(current.clone(), count);
+                    func.element_pointer(elements, offset)
+                }
+                (Some(elements), _) => func.access_element(elements, current.clone()),
+                (None, Some(start)) => func.add(start, current.clone()),
+                (None, None) => unreachable!(),
+            };
+            let bindings = [
+                (index_name, current, AzulaType::Int),
+                (Some(name), value, element),
+            ];
+            for (name, value, typ) in bindings {
+                if let Some(name) = name.filter(|name| *name != "_") {
+                    func.store(name.to_string(), value, typ.clone());
+                    func.variables.insert(name.to_string(), typ);
+                }
+            }
+
+            self.loops.push((
+                label,
+                step_name.clone(),
+                end_name.clone(),
+                self.defers.len(),
+            ));
+            self.codegen_body(body, func);
+            self.loops.pop();
+            func.jump(step_name.clone());
+
+            func.blocks.push((step_name.clone(), Block::new()));
+            func.current_block = step_name;
+            let current = func.load(index.clone(), AzulaType::Int);
+            let one = func.const_int(1);
+            let next = func.add(current, one);
+            func.store(index, next, AzulaType::Int);
+            func.jump(eval_name);
+
+            func.blocks.push((end_name.clone(), Block::new()));
+            func.current_block = end_name;
+        } else {
+            unreachable!()
+        }
+    }
+
+    pub fn codegen_do_while(&mut self, stmt: Statement<'a>, func: &mut Function<'a>) {
+        if let Statement::DoWhile(cond, body, label, _) = stmt {
+            let loop_name = format!("loop-{}", func.if_block_index);
+            let eval_name = format!("eval-{}", func.if_block_index);
+            let end_name = format!("end-{}", func.if_block_index);
+
+            func.if_block_index += 1;
+
+            // The body comes before the condition, so it's entered unchecked
+            func.jump(loop_name.clone());
+            func.blocks.push((loop_name.clone(), Block::new()));
+            func.current_block = loop_name.clone();
+
+            self.loops.push((
+                label,
+                eval_name.clone(),
+                end_name.clone(),
+                self.defers.len(),
+            ));
+            self.codegen_body(body, func);
+            self.loops.pop();
+            func.jump(eval_name.clone());
+
+            func.blocks.push((eval_name.clone(), Block::new()));
+            func.current_block = eval_name;
+            let cond_val = self.codegen_expr(cond, func, true);
+            func.jcond(cond_val, loop_name, end_name.clone());
+
+            func.blocks.push((end_name.clone(), Block::new()));
+            func.current_block = end_name;
+        } else {
+            unreachable!()
+        }
+    }
+
+    pub fn codegen_loop_control(&mut self, stmt: Statement<'a>, func: &mut Function<'a>) {
+        let (label, breaks) = match stmt {
+            Statement::Break(label, _) => (label, true),
+            Statement::Continue(label, _) => (label, false),
+            _ => unreachable!(),
+        };
+        let (_, next, end, depth) = self
+            .loops
+            .iter()
+            .rev()
+            .find(|(name, ..)| label.is_none() || *name == label)
+            .cloned()
+            .unwrap();
+        self.codegen_defers(depth, func);
+        func.jump(if breaks { end } else { next });
+
+        // Anything after the jump can't run, but still needs a block to go in
+        let after_name = format!("after-{}", func.if_block_index);
+        func.if_block_index += 1;
+        func.blocks.push((after_name.clone(), Block::new()));
+        func.current_block = after_name;
+    }
+
+    pub fn codegen_match(&mut self, stmt: Statement<'a>, func: &mut Function<'a>) {
+        if let Statement::Match(expr, arms, ..) = stmt {
+            let value = self.codegen_expr(expr, func, true);
+            let tag = func.any_tag(value.clone());
+
+            let index = func.if_block_index;
+            let end_name = format!("end-{}", index);
+
+            func.if_block_index += 1;
+
+            // One switch on the tag dispatches to every arm. Only the first arm
+            // for a tag can run, and nothing after `_` can, the typechecker
+            // warns about the others
+            let mut cases = vec![];
+            let mut blocks = vec![];
+            let mut default = None;
+            for (arm, (typ, binding, body, _)) in arms.into_iter().enumerate() {
+                let arm_name = format!("match-{}-{}", index, arm);
+                match typ {
+                    Some(typ) => {
+                        let tag = typ.any_tag().unwrap();
+                        if cases.iter().all(|(case, _)| *case != tag) {
+                            cases.push((tag, arm_name.clone()));
+                            blocks.push((arm_name, Some(typ), binding, body));
+                        }
+                    }
+                    None => {
+                        default = Some(arm_name.clone());
+                        blocks.push((arm_name, None, binding, body));
+                        break;
+                    }
+                }
+            }
+            func.switch(tag, cases, default.unwrap_or_else(|| end_name.clone()));
+
+            for (arm_name, typ, binding, body) in blocks {
+                func.blocks.push((arm_name.clone(), Block::new()));
+                func.current_block = arm_name;
+
+                if let Some(binding) = binding {
+                    let (payload, typ) = match typ {
+                        Some(typ) => (func.any_payload(value.clone(), typ.clone()), typ),
+                        None => (value.clone(), AzulaType::Any),
+                    };
+                    func.store(binding.to_string(), payload, typ.clone());
+                    func.variables.insert(binding.to_string(), typ);
+                }
+                self.codegen_body(body, func);
+                if !ends_with_return(func) {
+                    func.jump(end_name.clone());
+                }
+            }
+
+            func.blocks.push((end_name.clone(), Block::new()));
+            func.current_block = end_name;
+        } else {
+            unreachable!()
+        }
+    }
+
+    pub fn codegen_cast(
+        &mut self,
+        expr: ExpressionNode<'a>,
+        target: AzulaType<'a>,
+        func: &mut Function<'a>,
+    ) -> Value {
+        // Only nil is typed as void
+        if let AzulaType::Optional(inner) = target {
+            if expr.typed == AzulaType::Void {
+                return func.make_optional(None, inner.deref().clone());
+            }
+
+            let value = self.codegen_expr(expr, func, true);
+            return func.make_optional(Some(value), inner.deref().clone());
+        }
+
+        if let AzulaType::Result(inner) = target {
+            if expr.typed == AzulaType::Void {
+                return func.make_result(None, None, inner.deref().clone());
+            }
+
+            // error(msg) is rebuilt with the target's payload type
+            if let Expression::FunctionCall { function, args } = &expr.expression {
+                if function.expression == Expression::Identifier("error".to_string()) {
+                    let message = self.codegen_expr(args[0].clone(), func, true);
+                    return func.make_result(None, Some(message), inner.deref().clone());
+                }
+            }
+
+            let value = self.codegen_expr(expr, func, true);
+            return func.make_result(Some(value), None, inner.deref().clone());
+        }
+
+        let value = self.codegen_expr(expr.clone(), func, true);
+
+        match target {
+            AzulaType::Any => func.make_any(value, expr.typed.any_tag().unwrap()),
+            AzulaType::Interface(interface) => {
+                self.codegen_interface_value(value, expr.typed, interface, func)
+            }
+            _ => func.cast(value, expr.typed, target),
+        }
+    }
+
+    // An interface value pairs a copy of the value with the vtable for its
+    // type. The vtable holds a wrapper per method, which takes the copy as
+    // a closure env and passes it on as self
+    fn codegen_interface_value(
+        &mut self,
+        value: Value,
+        typ: AzulaType<'a>,
+        interface: String,
+        func: &mut Function<'a>,
+    ) -> Value {
+        let type_name = typ.mangled();
+        let vtable = format!("vtable.{}.{}", type_name, interface);
+        let env_type = AzulaType::Tuple(vec![typ]);
+
+        if !self.module.global_values.contains_key(&vtable) {
+            let mut wrappers = vec![];
+            for (method, args, returns) in self.interfaces[interface.as_str()].clone() {
+                let name = format!("{}.{}", type_name, method);
+                let wrapper = format!("{}.dyn", name);
+
+                let mut value = Function::new();
+                value.arguments.push((
+                    "closure.env".to_string(),
+                    AzulaType::Pointer(Rc::new(env_type.clone())),
+                ));
+                for (index, (typ, _)) in args.iter().skip(1).enumerate() {
+                    value
+                        .arguments
+                        .push((format!("arg.{}", index), typ.clone()));
+                }
+                value.returns = returns.clone();
+
+                let env = value.load_arg(0, AzulaType::Pointer(Rc::new(env_type.clone())));
+                let mut params = vec![value.access_struct_member(env, 0, true)];
+                for (index, (typ, _)) in args.into_iter().skip(1).enumerate() {
+                    params.push(value.load_arg(index + 1, typ));
+                }
+                let result = value.function_call(name, params);
+                if returns == AzulaType::Void {
+                    value.ret(None);
+                } else {
+                    value.ret(Some(result));
+                }
+
+                self.module
+                    .add_function(Box::leak(wrapper.clone().into_boxed_str()), value);
+                wrappers.push(wrapper);
+            }
+
+            self.module
+                .global_values
+                .insert(vtable.clone(), GlobalValue::Functions(wrappers));
+        }
+
+        let env = func.create_tuple(vec![value]);
+        func.make_interface(vtable, env, env_type)
+    }
+
+    // Stores the values from offset on, writing the rows of sized arrays in
+    // place instead of as pointers to them
+    fn codegen_array_elements(
+        &mut self,
+        vals: &[ExpressionNode<'a>],
+        array: Value,
+        offset: usize,
+        func: &mut Function<'a>,
+    ) {
+        for (index, val) in vals.iter().enumerate() {
+            let (_, count) = val.typed.inline_elements();
+            let position = offset + index * count;
+            match (&val.typed, &val.expression) {
+                (AzulaType::Array(_, Some(_)), Expression::Array(row)) => {
+                    self.codegen_array_elements(row, array.clone(), position, func)
+                }
+                (AzulaType::Array(_, Some(_)), _) => {
+                    let row = self.codegen_expr(val.clone(), func, true);
+                    let position = func.const_int(position as i64);
+                    self.copy_elements(row, array.clone(), position, count, func);
+                }
+                _ => {
+                    let gened = self.codegen_expr(val.clone(), func, true);
+                    let position = func.const_int(position as i64);
+                    func.store_element(array.clone(), position, gened);
+                }
+            }
+        }
+    }
+
+    fn copy_elements(
+        &mut self,
+        from: Value,
+        to: Value,
+        offset: Value,
+        count: usize,
+        func: &mut Function<'a>,
+    ) {
+        for index in 0..count {
+            let index = func.const_int(index as i64);
+            let element = func.access_element(from.clone(), index.clone());
+            let position = func.add(offset.clone(), index);
+            func.store_element(to.clone(), position, element);
+        }
+    }
+
     pub fn codegen_expr(
         &mut self,
         expr: ExpressionNode<'a>,
@@ -291,11 +928,15 @@ impl<'a> Codegen<'a> {
                     func.load(name, expr.typed)
                 } else if let Some(val) = self.module.global_values.get(&name) {
                     if let GlobalValue::String(v) = val {
-                        return Value::Global(*v);
+                        if !self.module.mutable_globals.contains(&name) {
+                            return Value::Global(*v);
+                        }
                     }
                     func.load_global(name, expr.typed)
                 } else if name == "nil" {
                     func.const_null()
+                } else if let AzulaType::Function(..) = &expr.typed {
+                    self.codegen_function_value(name, expr.typed, func)
                 } else {
                     unreachable!()
                 }
@@ -310,11 +951,79 @@ impl<'a> Codegen<'a> {
                 }
             }
             Expression::FunctionCall { function, args } => {
+                if let AzulaType::Function(..) = &function.typed {
+                    let closure = self.codegen_expr(function.deref().clone(), func, true);
+                    let args = args
+                        .into_iter()
+                        .map(|arg| self.codegen_expr(arg, func, true))
+                        .collect();
+                    return func.call_closure(closure, args, function.typed.clone());
+                }
+
                 let name = match &function.expression {
                     Expression::Identifier(name) => name,
                     _ => todo!(),
                 };
 
+                // C's signal takes an int and the handler's address
+                if name == "on_signal" {
+                    let signal = self.codegen_expr(args[0].clone(), func, true);
+                    let signal = func.cast(signal, AzulaType::Int, AzulaType::SizedSignedInt(32));
+                    let handler = match &args[1].expression {
+                        Expression::Identifier(handler) => func.function_pointer(handler.clone()),
+                        _ => unreachable!(),
+                    };
+
+                    return func.function_call("signal".to_string(), vec![signal, handler]);
+                }
+
+                // free returns nothing, and void values are never read
+                if name == "free" {
+                    let pointer = self.codegen_expr(args[0].clone(), func, true);
+                    func.free(pointer);
+                    return Value::LiteralInteger(0);
+                }
+                // The typechecker's drop of a local closure
+                if name == "closure.drop" {
+                    let closure = self.codegen_expr(args[0].clone(), func, true);
+                    func.free_closure(closure);
+                    return Value::LiteralInteger(0);
+                }
+
+                if name == "volatile_load" || name == "unaligned_load" {
+                    let pointer = self.codegen_expr(args[0].clone(), func, true);
+                    if name == "volatile_load" {
+                        return func.volatile_load(pointer, expr.typed);
+                    }
+                    return func.unaligned_load(pointer, expr.typed);
+                }
+                if name == "volatile_store" || name == "unaligned_store" {
+                    let pointer = self.codegen_expr(args[0].clone(), func, true);
+                    let value = self.codegen_expr(args[1].clone(), func, true);
+                    if name == "volatile_store" {
+                        func.volatile_store(pointer, value);
+                    } else {
+                        func.unaligned_store(pointer, value);
+                    }
+                    return Value::LiteralInteger(0);
+                }
+
+                // Converting to a byte order and back from it are the same swap
+                if let "to_le" | "from_le" | "to_be" | "from_be" = name.as_str() {
+                    let value = self.codegen_expr(args[0].clone(), func, true);
+                    return func.byte_order(value, name.ends_with("be"));
+                }
+
+                if name == "len" {
+                    let string = self.codegen_expr(args[0].clone(), func, true);
+                    return func.string_len(string);
+                }
+
+                if name == "error" {
+                    let message = self.codegen_expr(args[0].clone(), func, true);
+                    return func.make_result(None, Some(message), AzulaType::Void);
+                }
+
                 // if name == "__array_len" {
                 //     match args[0].typed {
                 //         AzulaType::Array(_, size) => return func.const_int(size.unwrap() as i64),
@@ -333,6 +1042,11 @@ impl<'a> Codegen<'a> {
 
                 func.not(val)
             }
+            Expression::Negate(expr) => {
+                let val = self.codegen_expr(expr.as_ref().clone(), func, true);
+
+                func.neg(val)
+            }
             Expression::Pointer(expr) => {
                 //     match &expr.expression {
                 //     Expression::Identifier(ident) => func.ptr(ident.clone()),
@@ -341,14 +1055,23 @@ impl<'a> Codegen<'a> {
 
                 self.codegen_expr(expr.deref().clone(), func, false)
             }
-            Expression::Array(vals) => {
-                let array = func.create_array(vals[0].typed.clone(), vals.len());
-
-                for (index, val) in vals.iter().enumerate() {
-                    let gened = self.codegen_expr(val.clone(), func, true);
-                    let index = func.const_int(index as i64);
-                    func.store_element(array.clone(), index, gened);
+            // Unresolved, *p is just the pointer to write through
+            Expression::Deref(inner) => {
+                let pointer = self.codegen_expr(inner.deref().clone(), func, true);
+                if !resolve_pointer {
+                    return pointer;
                 }
+                func.deref(pointer, expr.typed)
+            }
+            Expression::Array(vals) => {
+                // Variadic arguments can be an empty array, so go by the type
+                let typ = match &expr.typed {
+                    AzulaType::Array(inner, _) => inner.deref().clone(),
+                    _ => vals[0].typed.clone(),
+                };
+                let (element, count) = typ.inline_elements();
+                let array = func.create_array(element, vals.len() * count);
+                self.codegen_array_elements(&vals, array.clone(), 0, func);
 
                 return array;
             }
@@ -356,6 +1079,17 @@ impl<'a> Codegen<'a> {
                 let array = self.codegen_expr(array.deref().clone(), func, true);
                 let index = self.codegen_expr(index.deref().clone(), func, true);
 
+                // Rows are stored inline, so one is its first element's address
+                if let AzulaType::Array(_, Some(_)) = expr.typed {
+                    let (_, count) = expr.typed.inline_elements();
+                    let count = func.const_int(count as i64);
+                    let offset = func.mul(index, count);
+                    return func.element_pointer(array, offset);
+                }
+                // &a[i] is the element's address
+                if !resolve_pointer {
+                    return func.element_pointer(array, index);
+                }
                 func.access_element(array, index)
             }
             Expression::StructInitialisation(struc, vals) => {
@@ -379,6 +1113,15 @@ impl<'a> Codegen<'a> {
                     _ => unreachable!(),
                 };
 
+                // Methods are looked up in the vtable by their position
+                if let AzulaType::Interface(interface) = &struc.typed {
+                    let index = self.interfaces[interface.as_str()]
+                        .iter()
+                        .position(|(name, ..)| *name == member_name)
+                        .unwrap();
+                    return func.interface_method(struct_value, index);
+                }
+
                 let struct_name = match &struc.typed {
                     AzulaType::Named(name) => name.clone(),
                     AzulaType::Pointer(nested) => match nested.deref().clone() {
@@ -399,12 +1142,273 @@ impl<'a> Codegen<'a> {
 
                 func.access_struct_member(struct_value, index, resolve_pointer)
             }
+            Expression::Cast(inner, target) => {
+                self.codegen_cast(inner.deref().clone(), target, func)
+            }
+            Expression::SizeOf(typ) => func.size_of(typ),
+            Expression::New(typ) => func.heap_alloc(typ),
+            Expression::Tuple(values) => {
+                let values = values
+                    .into_iter()
+                    .map(|value| self.codegen_expr(value, func, true))
+                    .collect();
+
+                func.create_tuple(values)
+            }
+            Expression::Try(value, returns) => {
+                self.codegen_try(value.deref().clone(), returns, expr.typed, func)
+            }
+            Expression::Ternary(cond, then, otherwise) => self.codegen_ternary(
+                cond.deref().clone(),
+                then.deref().clone(),
+                otherwise.deref().clone(),
+                expr.typed,
+                func,
+            ),
+            Expression::NamedArgument(..) => {
+                unreachable!("named arguments are put in place by the typechecker")
+            }
+            Expression::Range(..) => unreachable!("ranges are made structs by the typechecker"),
+            Expression::ArrayRepeat(..) => unreachable!("arrays are expanded by the typechecker"),
+            Expression::Closure {
+                args,
+                body,
+                captures,
+            } => self.codegen_closure(args, body.deref().clone(), captures, expr.span, func),
         }
     }
 
-    pub fn codegen_infix(
+    // A closure is lifted into its own function, which takes the captured
+    // variables in an env tuple ahead of its arguments. The closure value
+    // pairs that function with a copy of the captures, which the
+    // typechecker's closure.drop frees
+    fn codegen_closure(
         &mut self,
-        expr: ExpressionNode<'a>,
+        args: Vec<TypedIdentifier<'a>>,
+        body: ExpressionNode<'a>,
+        captures: Vec<(AzulaType<'a>, String)>,
+        span: Span,
+        func: &mut Function<'a>,
+    ) -> Value {
+        // Module functions are named by source slices, lifted ones have to
+        // live as long as the module does
+        let name: &'a str = Box::leak(format!("closure.{}", self.closures).into_boxed_str());
+        self.closures += 1;
+
+        let env_type = AzulaType::Tuple(captures.iter().map(|(typ, _)| typ.clone()).collect());
+        let env_pointer = AzulaType::Pointer(Rc::new(env_type.clone()));
+
+        let mut closure = Function::new();
+        closure
+            .arguments
+            .push(("closure.env".to_string(), env_pointer.clone()));
+        for (typ, name) in args {
+            closure.arguments.push((name.to_string(), typ));
+        }
+        closure.returns = body.typed.clone();
+
+        let env = closure.load_arg(0, env_pointer);
+        for (index, (typ, name)) in captures.iter().enumerate() {
+            let value = closure.access_struct_member(env.clone(), index, true);
+            closure.store(name.clone(), value, typ.clone());
+            closure.variables.insert(name.clone(), typ.clone());
+        }
+
+        // The enclosing function's defers aren't the closure's to run, a ?
+        // returning from it mustn't call them
+        let defers = std::mem::take(&mut self.defers);
+        let value = self.codegen_expr(body, &mut closure, true);
+        self.defers = defers;
+        if closure.returns == AzulaType::Void {
+            closure.ret(None);
+        } else {
+            closure.ret(Some(value));
+        }
+        self.module.add_function(name, closure);
+
+        let values = captures
+            .into_iter()
+            .map(|(typ, name)| {
+                let capture = ExpressionNode {
+                    expression: Expression::Identifier(name),
+                    typed: typ,
+                    span: span.clone(),
+                };
+                self.codegen_expr(capture, func, true)
+            })
+            .collect();
+        let env = func.create_tuple(values);
+
+        func.make_closure(name.to_string(), env, env_type)
+    }
+
+    // Named functions don't take an env, so they're wrapped in one that
+    // ignores it to look like any other closure
+    fn codegen_function_value(
+        &mut self,
+        name: String,
+        typ: AzulaType<'a>,
+        func: &mut Function<'a>,
+    ) -> Value {
+        let (params, returns) = match typ {
+            AzulaType::Function(params, returns) => (params, returns.deref().clone()),
+            _ => unreachable!(),
+        };
+        let env_type = AzulaType::Tuple(vec![]);
+        let wrapper = format!("{}.value", name);
+
+        if !self.module.functions.contains_key(wrapper.as_str()) {
+            let mut value = Function::new();
+            value.arguments.push((
+                "closure.env".to_string(),
+                AzulaType::Pointer(Rc::new(env_type.clone())),
+            ));
+            for (index, typ) in params.iter().enumerate() {
+                value
+                    .arguments
+                    .push((format!("arg.{}", index), typ.clone()));
+            }
+            value.returns = returns.clone();
+
+            let args = params
+                .into_iter()
+                .enumerate()
+                .map(|(index, typ)| value.load_arg(index + 1, typ))
+                .collect();
+            let result = value.function_call(name, args);
+            if returns == AzulaType::Void {
+                value.ret(None);
+            } else {
+                value.ret(Some(result));
+            }
+
+            self.module
+                .add_function(Box::leak(wrapper.clone().into_boxed_str()), value);
+        }
+
+        let env = func.create_tuple(vec![]);
+        func.make_closure(wrapper, env, env_type)
+    }
+
+    // `a && b` only evaluates b when a is true, `a || b` only when a is false.
+    // The result goes through a hidden variable so both paths can set it
+    fn codegen_short_circuit(
+        &mut self,
+        is_and: bool,
+        left: ExpressionNode<'a>,
+        right: ExpressionNode<'a>,
+        func: &mut Function<'a>,
+    ) -> Value {
+        let index = func.if_block_index;
+        let rhs_name = format!("rhs-{}", index);
+        let end_name = format!("end-{}", index);
+        let result = format!("sc-{}", index);
+
+        func.if_block_index += 1;
+
+        let left = self.codegen_expr(left, func, true);
+        func.store(result.clone(), left.clone(), AzulaType::Bool);
+        if is_and {
+            func.jcond(left, rhs_name.clone(), end_name.clone());
+        } else {
+            func.jcond(left, end_name.clone(), rhs_name.clone());
+        }
+
+        func.blocks.push((rhs_name.clone(), Block::new()));
+        func.current_block = rhs_name;
+
+        let right = self.codegen_expr(right, func, true);
+        func.store(result.clone(), right, AzulaType::Bool);
+        func.jump(end_name.clone());
+
+        func.blocks.push((end_name.clone(), Block::new()));
+        func.current_block = end_name;
+
+        func.load(result, AzulaType::Bool)
+    }
+
+    // Results are {ok, error, value}, an error is passed straight on to the
+    // caller by rebuilding it as the enclosing function's result type. The
+    // message is the whole string, its length goes along with its bytes
+    fn codegen_try(
+        &mut self,
+        value: ExpressionNode<'a>,
+        returns: AzulaType<'a>,
+        typ: AzulaType<'a>,
+        func: &mut Function<'a>,
+    ) -> Value {
+        let index = func.if_block_index;
+        let ok_name = format!("ok-{}", index);
+        let err_name = format!("err-{}", index);
+
+        func.if_block_index += 1;
+
+        let value = self.codegen_expr(value, func, true);
+        let ok = func.access_struct_member(value.clone(), 0, true);
+        func.jcond(ok, ok_name.clone(), err_name.clone());
+
+        func.blocks.push((err_name.clone(), Block::new()));
+        func.current_block = err_name;
+        let message = func.access_struct_member(value.clone(), 1, true);
+        let returns = match returns {
+            AzulaType::Result(inner) => inner.deref().clone(),
+            _ => unreachable!("{:?}", returns),
+        };
+        let error = func.make_result(None, Some(message), returns);
+        self.codegen_defers(0, func);
+        func.ret(Some(error));
+
+        func.blocks.push((ok_name.clone(), Block::new()));
+        func.current_block = ok_name;
+
+        if typ == AzulaType::Void {
+            return func.const_null();
+        }
+        func.access_struct_member(value, 2, true)
+    }
+
+    // Like short-circuiting, each branch stores into a hidden variable that
+    // the end block loads
+    fn codegen_ternary(
+        &mut self,
+        cond: ExpressionNode<'a>,
+        then: ExpressionNode<'a>,
+        otherwise: ExpressionNode<'a>,
+        typ: AzulaType<'a>,
+        func: &mut Function<'a>,
+    ) -> Value {
+        let index = func.if_block_index;
+        let then_name = format!("then-{}", index);
+        let else_name = format!("else-{}", index);
+        let end_name = format!("end-{}", index);
+        let result = format!("tern-{}", index);
+
+        func.if_block_index += 1;
+
+        let cond = self.codegen_expr(cond, func, true);
+        func.jcond(cond, then_name.clone(), else_name.clone());
+
+        func.blocks.push((then_name.clone(), Block::new()));
+        func.current_block = then_name;
+        let then = self.codegen_expr(then, func, true);
+        func.store(result.clone(), then, typ.clone());
+        func.jump(end_name.clone());
+
+        func.blocks.push((else_name.clone(), Block::new()));
+        func.current_block = else_name;
+        let otherwise = self.codegen_expr(otherwise, func, true);
+        func.store(result.clone(), otherwise, typ.clone());
+        func.jump(end_name.clone());
+
+        func.blocks.push((end_name.clone(), Block::new()));
+        func.current_block = end_name;
+
+        func.load(result, typ)
+    }
+
+    pub fn codegen_infix(
+        &mut self,
+        expr: ExpressionNode<'a>,
         func: &mut Function<'a>,
         resolve_pointer: bool,
     ) -> Value {
@@ -429,16 +1433,27 @@ impl<'a> Codegen<'a> {
                     func.mul(val1, val2)
                 }
                 Operator::Div => {
+                    let unsigned = matches!(val1.typed, AzulaType::SizedUnsignedInt(_));
                     let val1 = self.codegen_expr(val1.as_ref().clone(), func, true);
                     let val2 = self.codegen_expr(val2.as_ref().clone(), func, true);
 
-                    func.div(val1, val2)
+                    if unsigned {
+                        func.udiv(val1, val2)
+                    } else {
+                        func.div(val1, val2)
+                    }
                 }
                 Operator::Mod => {
+                    // Signed remainders take the sign of the dividend
+                    let unsigned = matches!(val1.typed, AzulaType::SizedUnsignedInt(_));
                     let val1 = self.codegen_expr(val1.as_ref().clone(), func, true);
                     let val2 = self.codegen_expr(val2.as_ref().clone(), func, true);
 
-                    func.modulus(val1, val2)
+                    if unsigned {
+                        func.urem(val1, val2)
+                    } else {
+                        func.modulus(val1, val2)
+                    }
                 }
                 Operator::Power => {
                     let val1 = self.codegen_expr(val1.as_ref().clone(), func, true);
@@ -446,18 +1461,18 @@ impl<'a> Codegen<'a> {
 
                     func.pow(val1, val2)
                 }
-                Operator::Or => {
-                    let val1 = self.codegen_expr(val1.as_ref().clone(), func, true);
-                    let val2 = self.codegen_expr(val2.as_ref().clone(), func, true);
-
-                    func.or(val1, val2)
-                }
-                Operator::And => {
-                    let val1 = self.codegen_expr(val1.as_ref().clone(), func, true);
-                    let val2 = self.codegen_expr(val2.as_ref().clone(), func, true);
-
-                    func.and(val1, val2)
-                }
+                Operator::Or => self.codegen_short_circuit(
+                    false,
+                    val1.as_ref().clone(),
+                    val2.as_ref().clone(),
+                    func,
+                ),
+                Operator::And => self.codegen_short_circuit(
+                    true,
+                    val1.as_ref().clone(),
+                    val2.as_ref().clone(),
+                    func,
+                ),
                 Operator::Eq => {
                     let val1 = self.codegen_expr(val1.as_ref().clone(), func, true);
                     let val2 = self.codegen_expr(val2.as_ref().clone(), func, true);
@@ -470,29 +1485,39 @@ impl<'a> Codegen<'a> {
 
                     func.neq(val1, val2)
                 }
-                Operator::Lt => {
-                    let val1 = self.codegen_expr(val1.as_ref().clone(), func, true);
-                    let val2 = self.codegen_expr(val2.as_ref().clone(), func, true);
-
-                    func.lt(val1, val2)
-                }
-                Operator::Lte => {
+                Operator::Lt | Operator::Lte | Operator::Gt | Operator::Gte => {
+                    let unsigned = matches!(val1.typed, AzulaType::SizedUnsignedInt(_));
                     let val1 = self.codegen_expr(val1.as_ref().clone(), func, true);
                     let val2 = self.codegen_expr(val2.as_ref().clone(), func, true);
 
-                    func.lte(val1, val2)
+                    match (op, unsigned) {
+                        (Operator::Lt, false) => func.lt(val1, val2),
+                        (Operator::Lte, false) => func.lte(val1, val2),
+                        (Operator::Gt, false) => func.gt(val1, val2),
+                        (Operator::Gte, false) => func.gte(val1, val2),
+                        (Operator::Lt, true) => func.ult(val1, val2),
+                        (Operator::Lte, true) => func.ulte(val1, val2),
+                        (Operator::Gt, true) => func.ugt(val1, val2),
+                        _ => func.ugte(val1, val2),
+                    }
                 }
-                Operator::Gt => {
+                Operator::Shl => {
                     let val1 = self.codegen_expr(val1.as_ref().clone(), func, true);
                     let val2 = self.codegen_expr(val2.as_ref().clone(), func, true);
 
-                    func.gt(val1, val2)
+                    func.shl(val1, val2)
                 }
-                Operator::Gte => {
+                Operator::Shr => {
+                    // Unsigned integers shift in zeroes, everything else keeps the sign
+                    let unsigned = matches!(val1.typed, AzulaType::SizedUnsignedInt(_));
                     let val1 = self.codegen_expr(val1.as_ref().clone(), func, true);
                     let val2 = self.codegen_expr(val2.as_ref().clone(), func, true);
 
-                    func.gte(val1, val2)
+                    if unsigned {
+                        func.lshr(val1, val2)
+                    } else {
+                        func.ashr(val1, val2)
+                    }
                 }
             }
         } else {
@@ -501,6 +1526,26 @@ impl<'a> Codegen<'a> {
     }
 }
 
+fn ends_with_return(func: &Function) -> bool {
+    func.blocks
+        .iter()
+        .find(|(name, _)| *name == func.current_block)
+        .map(|(_, block)| matches!(block.instructions.last(), Some(Instruction::Return(_))))
+        .unwrap_or(false)
+}
+
+// The typechecker drops a local with a defer of Type.drop(name), or
+// closure.drop(name) for a closure
+fn is_drop_of(defer: &ExpressionNode, name: &str) -> bool {
+    match &defer.expression {
+        Expression::FunctionCall { function, args } => {
+            matches!(&function.expression, Expression::Identifier(f) if f.ends_with(".drop"))
+                && matches!(&args[..], [arg] if arg.expression == Expression::Identifier(name.to_string()))
+        }
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -510,15 +1555,31 @@ mod tests {
     use azula_type::prelude::*;
     use std::rc::Rc;
 
+    fn node<'a>(expression: Expression<'a>, typed: AzulaType<'a>) -> ExpressionNode<'a> {
+        ExpressionNode {
+            expression,
+            typed,
+            span: Span { start: 0, end: 0 },
+        }
+    }
+
     #[test]
     fn test_codegen_function() {
         let mut codegen = Codegen::new("test", Statement::Root(vec![]));
 
         codegen.codegen_function(Statement::Function {
             name: "test",
+            generics: vec![],
+            bounds: vec![],
             args: vec![(AzulaType::Bool, "x")],
+            arg_spans: vec![],
+            defaults: vec![],
+            variadic: false,
             returns: AzulaType::Int,
             body: Rc::new(Statement::Block(vec![])),
+            public: true,
+            attributes: vec![],
+            doc: None,
             span: Span { start: 0, end: 1 },
         });
 
@@ -528,6 +1589,7 @@ mod tests {
         args.push(("x".to_string(), AzulaType::Bool));
         assert_eq!(function.arguments, args);
         assert_eq!(function.returns, AzulaType::Int);
+        assert!(function.exported);
     }
 
     #[test]
@@ -604,6 +1666,80 @@ mod tests {
         assert_eq!(func.blocks.len(), 3);
     }
 
+    #[test]
+    fn test_codegen_labeled_break() {
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        let mut func = Function::new();
+
+        let span = || Span { start: 0, end: 0 };
+        codegen.codegen_while(
+            Statement::While(
+                node(Expression::Boolean(true), AzulaType::Bool),
+                vec![Statement::While(
+                    node(Expression::Boolean(true), AzulaType::Bool),
+                    vec![
+                        Statement::Break(Some("outer"), span()),
+                        Statement::Continue(None, span()),
+                    ],
+                    None,
+                    span(),
+                )],
+                Some("outer"),
+                span(),
+            ),
+            &mut func,
+        );
+
+        let block = |name: &str| {
+            &func
+                .blocks
+                .iter()
+                .find(|(block, _)| block == name)
+                .unwrap()
+                .1
+                .instructions
+        };
+        assert_eq!(
+            block("loop-1"),
+            &vec![Instruction::Jump("end-0".to_string())]
+        );
+        assert_eq!(
+            block("after-2"),
+            &vec![Instruction::Jump("eval-1".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_codegen_do_while() {
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        let mut func = Function::new();
+
+        codegen.codegen_do_while(
+            Statement::DoWhile(
+                ExpressionNode {
+                    expression: Expression::Boolean(true),
+                    typed: AzulaType::Bool,
+                    span: Span { start: 0, end: 0 },
+                },
+                vec![],
+                None,
+                Span { start: 0, end: 0 },
+            ),
+            &mut func,
+        );
+
+        let names: Vec<_> = func.blocks.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names[1..], ["loop-0", "eval-0", "end-0"]);
+        assert_eq!(
+            func.blocks[0].1.instructions.last(),
+            Some(&Instruction::Jump("loop-0".to_string()))
+        );
+        assert!(matches!(
+            func.blocks[2].1.instructions.last(),
+            Some(Instruction::Jcond(_, body, end)) if body == "loop-0" && end == "end-0"
+        ));
+    }
+
     #[test]
     fn test_codegen_infix() {
         let mut codegen = Codegen::new("test", Statement::Root(vec![]));
@@ -768,4 +1904,1369 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_codegen_shift() {
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+
+        // Unsigned values use a logical shift
+        let mut func = Function::new();
+        codegen.codegen_expr(
+            ExpressionNode {
+                expression: Expression::Infix(
+                    Rc::new(ExpressionNode {
+                        expression: Expression::Integer(10),
+                        typed: AzulaType::SizedUnsignedInt(32),
+                        span: Span { start: 0, end: 1 },
+                    }),
+                    Operator::Shr,
+                    Rc::new(ExpressionNode {
+                        expression: Expression::Integer(2),
+                        typed: AzulaType::SizedUnsignedInt(32),
+                        span: Span { start: 0, end: 1 },
+                    }),
+                ),
+                typed: AzulaType::SizedUnsignedInt(32),
+                span: Span { start: 0, end: 1 },
+            },
+            &mut func,
+            true,
+        );
+        assert_eq!(
+            func.blocks[0].1.instructions,
+            vec![
+                Instruction::ConstInt(10, 0),
+                Instruction::ConstInt(2, 1),
+                Instruction::LShr(Value::Local(0), Value::Local(1), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_codegen_unsigned_div() {
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+
+        let unsigned = |value| {
+            Rc::new(node(
+                Expression::Integer(value),
+                AzulaType::SizedUnsignedInt(8),
+            ))
+        };
+        let mut func = Function::new();
+        for operator in [Operator::Div, Operator::Mod] {
+            codegen.codegen_expr(
+                ExpressionNode {
+                    expression: Expression::Infix(unsigned(200), operator, unsigned(3)),
+                    typed: AzulaType::SizedUnsignedInt(8),
+                    span: Span { start: 0, end: 1 },
+                },
+                &mut func,
+                true,
+            );
+        }
+        assert_eq!(
+            func.blocks[0].1.instructions,
+            vec![
+                Instruction::ConstInt(200, 0),
+                Instruction::ConstInt(3, 1),
+                Instruction::UDiv(Value::Local(0), Value::Local(1), 2),
+                Instruction::ConstInt(200, 3),
+                Instruction::ConstInt(3, 4),
+                Instruction::URem(Value::Local(3), Value::Local(4), 5)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_codegen_unsigned_compare() {
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+
+        let unsigned = |value| {
+            Rc::new(node(
+                Expression::Integer(value),
+                AzulaType::SizedUnsignedInt(8),
+            ))
+        };
+        let mut func = Function::new();
+        for operator in [Operator::Lt, Operator::Lte, Operator::Gt, Operator::Gte] {
+            codegen.codegen_expr(
+                ExpressionNode {
+                    expression: Expression::Infix(unsigned(200), operator, unsigned(3)),
+                    typed: AzulaType::Bool,
+                    span: Span { start: 0, end: 1 },
+                },
+                &mut func,
+                true,
+            );
+        }
+        let compares: Vec<_> = func.blocks[0]
+            .1
+            .instructions
+            .iter()
+            .filter(|instruction| !matches!(instruction, Instruction::ConstInt(..)))
+            .cloned()
+            .collect();
+        assert_eq!(
+            compares,
+            vec![
+                Instruction::ULt(Value::Local(0), Value::Local(1), 2),
+                Instruction::ULte(Value::Local(3), Value::Local(4), 5),
+                Instruction::UGt(Value::Local(6), Value::Local(7), 8),
+                Instruction::UGte(Value::Local(9), Value::Local(10), 11)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_codegen_match() {
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        let mut func = Function::new();
+        func.variables.insert("x".to_string(), AzulaType::Any);
+
+        codegen.codegen_match(
+            Statement::Match(
+                ExpressionNode {
+                    expression: Expression::Identifier("x".to_string()),
+                    typed: AzulaType::Any,
+                    span: Span { start: 0, end: 0 },
+                },
+                vec![
+                    (
+                        Some(AzulaType::Int),
+                        Some("i"),
+                        vec![],
+                        Span { start: 0, end: 0 },
+                    ),
+                    (
+                        Some(AzulaType::Bool),
+                        None,
+                        vec![],
+                        Span { start: 0, end: 0 },
+                    ),
+                    (
+                        Some(AzulaType::Int),
+                        None,
+                        vec![],
+                        Span { start: 0, end: 0 },
+                    ),
+                    (None, None, vec![], Span { start: 0, end: 0 }),
+                ],
+                Span { start: 0, end: 0 },
+            ),
+            &mut func,
+        );
+
+        // entry, the first int arm, the bool arm, `_` and the end block
+        assert_eq!(func.blocks.len(), 5);
+        assert_eq!(
+            func.blocks[0].1.instructions.last(),
+            Some(&Instruction::Switch(
+                Value::Local(1),
+                vec![
+                    (AzulaType::Int.any_tag().unwrap(), "match-0-0".to_string()),
+                    (AzulaType::Bool.any_tag().unwrap(), "match-0-1".to_string()),
+                ],
+                "match-0-3".to_string(),
+            ))
+        );
+        assert_eq!(
+            func.blocks[1].1.instructions,
+            vec![
+                Instruction::AnyPayload(Value::Local(0), AzulaType::Int, 2),
+                Instruction::Store("i".to_string(), Value::Local(2), AzulaType::Int),
+                Instruction::Jump("end-0".to_string()),
+            ]
+        );
+        assert_eq!(func.variables.get("i"), Some(&AzulaType::Int));
+    }
+
+    #[test]
+    fn test_codegen_global_consts() {
+        let constant = |name: &str, expression| {
+            Statement::Assign(
+                false,
+                name.to_string(),
+                None,
+                ExpressionNode {
+                    expression,
+                    typed: AzulaType::Infer,
+                    span: Span { start: 0, end: 1 },
+                },
+                vec![],
+                Span { start: 0, end: 5 },
+            )
+        };
+
+        let mut codegen = Codegen::new(
+            "test",
+            Statement::Root(vec![
+                constant("PI", Expression::Float(3.14159)),
+                constant("DEBUG", Expression::Boolean(true)),
+            ]),
+        );
+        codegen.codegen();
+
+        assert!(matches!(
+            codegen.module.global_values.get("PI"),
+            Some(GlobalValue::Float(_))
+        ));
+        assert!(matches!(
+            codegen.module.global_values.get("DEBUG"),
+            Some(GlobalValue::Bool(true))
+        ));
+    }
+
+    #[test]
+    fn test_codegen_global_var() {
+        let mut codegen = Codegen::new(
+            "test",
+            Statement::Root(vec![Statement::Assign(
+                true,
+                "count".to_string(),
+                None,
+                ExpressionNode {
+                    expression: Expression::Integer(0),
+                    typed: AzulaType::Int,
+                    span: Span { start: 0, end: 1 },
+                },
+                vec![],
+                Span { start: 0, end: 5 },
+            )]),
+        );
+        codegen.codegen();
+        assert!(codegen.module.mutable_globals.contains("count"));
+
+        let mut func = Function::new();
+        codegen.codegen_reassign(
+            Statement::Reassign(
+                ExpressionNode {
+                    expression: Expression::Identifier("count".to_string()),
+                    typed: AzulaType::Int,
+                    span: Span { start: 0, end: 5 },
+                },
+                ExpressionNode {
+                    expression: Expression::Integer(1),
+                    typed: AzulaType::Int,
+                    span: Span { start: 8, end: 9 },
+                },
+                Span { start: 0, end: 10 },
+            ),
+            &mut func,
+        );
+        assert_eq!(
+            func.blocks[0].1.instructions,
+            vec![
+                Instruction::ConstInt(1, 0),
+                Instruction::StoreGlobal("count".to_string(), Value::Local(0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_codegen_deref() {
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        let mut func = Function::new();
+        let pointer = AzulaType::Pointer(Rc::new(AzulaType::Int));
+        func.variables.insert("p".to_string(), pointer.clone());
+
+        let deref = || ExpressionNode {
+            expression: Expression::Deref(Rc::new(ExpressionNode {
+                expression: Expression::Identifier("p".to_string()),
+                typed: pointer.clone(),
+                span: Span { start: 1, end: 2 },
+            })),
+            typed: AzulaType::Int,
+            span: Span { start: 0, end: 2 },
+        };
+        // *p = *p
+        codegen.codegen_reassign(
+            Statement::Reassign(deref(), deref(), Span { start: 0, end: 8 }),
+            &mut func,
+        );
+        assert_eq!(
+            func.blocks[0].1.instructions,
+            vec![
+                Instruction::Load("p".to_string(), 0, pointer.clone()),
+                Instruction::Deref(Value::Local(0), AzulaType::Int, 1),
+                Instruction::Load("p".to_string(), 2, pointer.clone()),
+                Instruction::StoreDeref(Value::Local(2), Value::Local(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_codegen_cast() {
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        let mut func = Function::new();
+
+        codegen.codegen_expr(
+            ExpressionNode {
+                expression: Expression::Cast(
+                    Rc::new(ExpressionNode {
+                        expression: Expression::Integer(5),
+                        typed: AzulaType::Int,
+                        span: Span { start: 0, end: 1 },
+                    }),
+                    AzulaType::Float,
+                ),
+                typed: AzulaType::Float,
+                span: Span { start: 0, end: 10 },
+            },
+            &mut func,
+            true,
+        );
+        assert_eq!(
+            func.blocks[0].1.instructions,
+            vec![
+                Instruction::ConstInt(5, 0),
+                Instruction::Cast(Value::Local(0), AzulaType::Int, AzulaType::Float, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_codegen_short_circuit() {
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        let mut func = Function::new();
+
+        let result = codegen.codegen_infix(
+            node(
+                Expression::Infix(
+                    Rc::new(node(Expression::Boolean(false), AzulaType::Bool)),
+                    Operator::And,
+                    Rc::new(node(
+                        Expression::FunctionCall {
+                            function: Rc::new(node(
+                                Expression::Identifier("check".to_string()),
+                                AzulaType::Bool,
+                            )),
+                            args: vec![],
+                        },
+                        AzulaType::Bool,
+                    )),
+                ),
+                AzulaType::Bool,
+            ),
+            &mut func,
+            true,
+        );
+
+        // The call only happens in the rhs block, which is skipped when the
+        // left side is false
+        assert_eq!(func.blocks.len(), 3);
+        assert_eq!(
+            func.blocks[0].1.instructions.last(),
+            Some(&Instruction::Jcond(
+                Value::Local(0),
+                "rhs-0".to_string(),
+                "end-0".to_string()
+            ))
+        );
+        assert!(func.blocks[1]
+            .1
+            .instructions
+            .iter()
+            .any(|instruction| matches!(instruction, Instruction::FunctionCall(..))));
+        assert_eq!(
+            func.blocks[2].1.instructions,
+            vec![Instruction::Load("sc-0".to_string(), 2, AzulaType::Bool)]
+        );
+        assert_eq!(result, Value::Local(2));
+    }
+
+    #[test]
+    fn test_codegen_destructure() {
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        let mut func = Function::new();
+
+        codegen.codegen_destructure(
+            Statement::Destructure(
+                false,
+                vec!["a", "b"],
+                node(
+                    Expression::Tuple(vec![
+                        node(Expression::Integer(1), AzulaType::Int),
+                        node(Expression::Boolean(true), AzulaType::Bool),
+                    ]),
+                    AzulaType::Tuple(vec![AzulaType::Int, AzulaType::Bool]),
+                ),
+                Span { start: 0, end: 0 },
+            ),
+            &mut func,
+        );
+
+        assert_eq!(
+            func.blocks[0].1.instructions[2..],
+            vec![
+                Instruction::CreateTuple(vec![Value::Local(0), Value::Local(1)], 2),
+                Instruction::AccessStructMember(Value::Local(2), 0, 3, true),
+                Instruction::Store("a".to_string(), Value::Local(3), AzulaType::Int),
+                Instruction::AccessStructMember(Value::Local(2), 1, 4, true),
+                Instruction::Store("b".to_string(), Value::Local(4), AzulaType::Bool),
+            ]
+        );
+        assert_eq!(func.variables.get("b"), Some(&AzulaType::Bool));
+    }
+
+    #[test]
+    fn test_codegen_if_let() {
+        let optional = AzulaType::Optional(Rc::new(AzulaType::Int));
+
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        let mut func = Function::new();
+        func.variables.insert("x".to_string(), optional.clone());
+
+        codegen.codegen_if_let(
+            Statement::IfLet(
+                false,
+                "y",
+                ExpressionNode {
+                    expression: Expression::Identifier("x".to_string()),
+                    typed: optional,
+                    span: Span { start: 0, end: 0 },
+                },
+                vec![],
+                Span { start: 0, end: 0 },
+            ),
+            &mut func,
+        );
+
+        assert_eq!(
+            func.blocks[0].1.instructions[1..],
+            vec![
+                Instruction::AccessStructMember(Value::Local(0), 0, 1, true),
+                Instruction::Jcond(Value::Local(1), "true-0".to_string(), "end-0".to_string()),
+            ]
+        );
+        assert_eq!(
+            func.blocks[1].1.instructions,
+            vec![
+                Instruction::AccessStructMember(Value::Local(0), 1, 2, true),
+                Instruction::Store("y".to_string(), Value::Local(2), AzulaType::Int),
+                Instruction::Jump("end-0".to_string()),
+            ]
+        );
+        assert_eq!(func.variables.get("y"), Some(&AzulaType::Int));
+    }
+
+    #[test]
+    fn test_codegen_try() {
+        let result = |typ| AzulaType::Result(Rc::new(typ));
+
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        let mut func = Function::new();
+        func.variables
+            .insert("x".to_string(), result(AzulaType::Int));
+
+        let value = codegen.codegen_try(
+            ExpressionNode {
+                expression: Expression::Identifier("x".to_string()),
+                typed: result(AzulaType::Int),
+                span: Span { start: 0, end: 0 },
+            },
+            result(AzulaType::Bool),
+            AzulaType::Int,
+            &mut func,
+        );
+
+        assert_eq!(
+            func.blocks[0].1.instructions[1..],
+            vec![
+                Instruction::AccessStructMember(Value::Local(0), 0, 1, true),
+                Instruction::Jcond(Value::Local(1), "ok-0".to_string(), "err-0".to_string()),
+            ]
+        );
+        assert_eq!(
+            func.blocks[1].1.instructions,
+            vec![
+                Instruction::AccessStructMember(Value::Local(0), 1, 2, true),
+                Instruction::MakeResult(None, Some(Value::Local(2)), AzulaType::Bool, 3),
+                Instruction::Return(Some(Value::Local(3))),
+            ]
+        );
+        assert_eq!(
+            func.blocks[2].1.instructions,
+            vec![Instruction::AccessStructMember(Value::Local(0), 2, 4, true)]
+        );
+        assert_eq!(value, Value::Local(4));
+    }
+
+    #[test]
+    fn test_codegen_defer() {
+        let call = |name: &str| {
+            node(
+                Expression::FunctionCall {
+                    function: Rc::new(node(
+                        Expression::Identifier(name.to_string()),
+                        AzulaType::Infer,
+                    )),
+                    args: vec![],
+                },
+                AzulaType::Void,
+            )
+        };
+
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        codegen.codegen_function(Statement::Function {
+            name: "main",
+            generics: vec![],
+            bounds: vec![],
+            args: vec![],
+            arg_spans: vec![],
+            defaults: vec![],
+            variadic: false,
+            returns: AzulaType::Int,
+            body: Rc::new(Statement::Block(vec![
+                Statement::Defer(call("first"), Span { start: 0, end: 0 }),
+                Statement::Defer(call("second"), Span { start: 0, end: 0 }),
+                Statement::Return(
+                    Some(node(Expression::Integer(1), AzulaType::Int)),
+                    Span { start: 0, end: 0 },
+                ),
+            ])),
+            public: false,
+            attributes: vec![],
+            doc: None,
+            span: Span { start: 0, end: 0 },
+        });
+
+        let func = codegen.module.functions.get("main").unwrap();
+        assert_eq!(
+            func.blocks[0].1.instructions,
+            vec![
+                Instruction::ConstInt(1, 0),
+                Instruction::FunctionCall("second".to_string(), vec![], 1),
+                Instruction::FunctionCall("first".to_string(), vec![], 2),
+                Instruction::Return(Some(Value::Local(0))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_codegen_closure() {
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        codegen.codegen_function(Statement::Function {
+            name: "main",
+            generics: vec![],
+            bounds: vec![],
+            args: vec![],
+            arg_spans: vec![],
+            defaults: vec![],
+            variadic: false,
+            returns: AzulaType::Void,
+            body: Rc::new(Statement::Block(vec![
+                Statement::Assign(
+                    true,
+                    "z".to_string(),
+                    Some(AzulaType::Int),
+                    node(Expression::Integer(2), AzulaType::Int),
+                    vec![],
+                    Span { start: 0, end: 0 },
+                ),
+                Statement::ExpressionStatement(
+                    node(
+                        Expression::Closure {
+                            args: vec![(AzulaType::Int, "x")],
+                            body: Rc::new(node(
+                                Expression::Infix(
+                                    Rc::new(node(
+                                        Expression::Identifier("x".to_string()),
+                                        AzulaType::Int,
+                                    )),
+                                    Operator::Add,
+                                    Rc::new(node(
+                                        Expression::Identifier("z".to_string()),
+                                        AzulaType::Int,
+                                    )),
+                                ),
+                                AzulaType::Int,
+                            )),
+                            captures: vec![(AzulaType::Int, "z".to_string())],
+                        },
+                        AzulaType::Function(vec![AzulaType::Int], Rc::new(AzulaType::Int)),
+                    ),
+                    Span { start: 0, end: 0 },
+                ),
+            ])),
+            public: false,
+            attributes: vec![],
+            doc: None,
+            span: Span { start: 0, end: 0 },
+        });
+
+        let closure = codegen.module.functions.get("closure.0").unwrap();
+        assert_eq!(
+            closure.arguments,
+            vec![
+                (
+                    "closure.env".to_string(),
+                    AzulaType::Pointer(Rc::new(AzulaType::Tuple(vec![AzulaType::Int])))
+                ),
+                ("x".to_string(), AzulaType::Int),
+            ]
+        );
+        assert_eq!(closure.returns, AzulaType::Int);
+
+        let main = codegen.module.functions.get("main").unwrap();
+        assert!(main.blocks[0]
+            .1
+            .instructions
+            .iter()
+            .any(|instruction| matches!(
+                instruction,
+                Instruction::MakeClosure(name, _, AzulaType::Tuple(_), _) if name == "closure.0"
+            )));
+    }
+
+    #[test]
+    fn test_codegen_closure_keeps_defers() {
+        let result = AzulaType::Result(Rc::new(AzulaType::Int));
+        let cleanup = node(
+            Expression::FunctionCall {
+                function: Rc::new(node(
+                    Expression::Identifier("cleanup".to_string()),
+                    AzulaType::Infer,
+                )),
+                args: vec![],
+            },
+            AzulaType::Void,
+        );
+
+        // func main(r: Result<int>) { defer cleanup(); func(): Result<int> { r? }; }
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        codegen.codegen_function(Statement::Function {
+            name: "main",
+            generics: vec![],
+            bounds: vec![],
+            args: vec![(result.clone(), "r")],
+            arg_spans: vec![],
+            defaults: vec![],
+            variadic: false,
+            returns: AzulaType::Void,
+            body: Rc::new(Statement::Block(vec![
+                Statement::Defer(cleanup, Span { start: 0, end: 0 }),
+                Statement::ExpressionStatement(
+                    node(
+                        Expression::Closure {
+                            args: vec![],
+                            body: Rc::new(node(
+                                Expression::Try(
+                                    Rc::new(node(
+                                        Expression::Identifier("r".to_string()),
+                                        result.clone(),
+                                    )),
+                                    result.clone(),
+                                ),
+                                AzulaType::Int,
+                            )),
+                            captures: vec![(result.clone(), "r".to_string())],
+                        },
+                        AzulaType::Function(vec![], Rc::new(AzulaType::Int)),
+                    ),
+                    Span { start: 0, end: 0 },
+                ),
+            ])),
+            public: false,
+            attributes: vec![],
+            doc: None,
+            span: Span { start: 0, end: 0 },
+        });
+
+        let calls = |name: &str| {
+            codegen.module.functions[name]
+                .blocks
+                .iter()
+                .flat_map(|(_, block)| &block.instructions)
+                .filter(|instruction| {
+                    matches!(instruction, Instruction::FunctionCall(name, _, _) if name == "cleanup")
+                })
+                .count()
+        };
+        assert_eq!(calls("closure.0"), 0);
+        assert_eq!(calls("main"), 1);
+    }
+
+    #[test]
+    fn test_codegen_closure_drop() {
+        let typ = AzulaType::Function(vec![], Rc::new(AzulaType::Int));
+        let span = || Span { start: 0, end: 0 };
+        let closure = |captures: Vec<(AzulaType<'static>, String)>| {
+            node(
+                Expression::Closure {
+                    args: vec![],
+                    body: Rc::new(node(Expression::Integer(1), AzulaType::Int)),
+                    captures,
+                },
+                typ.clone(),
+            )
+        };
+        let add = || node(Expression::Identifier("add".to_string()), typ.clone());
+        let function = |name, last| Statement::Function {
+            name,
+            generics: vec![],
+            bounds: vec![],
+            args: vec![],
+            arg_spans: vec![],
+            defaults: vec![],
+            variadic: false,
+            returns: typ.clone(),
+            body: Rc::new(Statement::Block(vec![
+                Statement::Assign(
+                    true,
+                    "add".to_string(),
+                    Some(typ.clone()),
+                    closure(vec![]),
+                    vec![],
+                    span(),
+                ),
+                Statement::Defer(
+                    node(
+                        Expression::FunctionCall {
+                            function: Rc::new(node(
+                                Expression::Identifier("closure.drop".to_string()),
+                                AzulaType::Void,
+                            )),
+                            args: vec![add()],
+                        },
+                        AzulaType::Void,
+                    ),
+                    span(),
+                ),
+                Statement::Return(Some(last), span()),
+            ])),
+            public: false,
+            attributes: vec![],
+            doc: None,
+            span: span(),
+        };
+
+        // add is freed when a copy of it isn't what's returned
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        codegen.codegen_function(function("fresh", closure(vec![])));
+        codegen.codegen_function(function("returned", add()));
+        codegen.codegen_function(function(
+            "captured",
+            closure(vec![(typ.clone(), "add".to_string())]),
+        ));
+
+        let frees = |name: &str| {
+            codegen.module.functions[name]
+                .blocks
+                .iter()
+                .flat_map(|(_, block)| &block.instructions)
+                .filter(|instruction| matches!(instruction, Instruction::FreeClosure(_)))
+                .count()
+        };
+        assert_eq!(frees("fresh"), 1);
+        assert_eq!(frees("returned"), 0);
+        assert_eq!(frees("captured"), 0);
+    }
+
+    #[test]
+    fn test_codegen_function_value() {
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        codegen.codegen_function(Statement::Function {
+            name: "main",
+            generics: vec![],
+            bounds: vec![],
+            args: vec![],
+            arg_spans: vec![],
+            defaults: vec![],
+            variadic: false,
+            returns: AzulaType::Void,
+            body: Rc::new(Statement::Block(vec![Statement::Assign(
+                true,
+                "f".to_string(),
+                None,
+                ExpressionNode {
+                    expression: Expression::Identifier("double".to_string()),
+                    typed: AzulaType::Function(vec![AzulaType::Int], Rc::new(AzulaType::Int)),
+                    span: Span { start: 0, end: 0 },
+                },
+                vec![],
+                Span { start: 0, end: 0 },
+            )])),
+            public: false,
+            attributes: vec![],
+            doc: None,
+            span: Span { start: 0, end: 0 },
+        });
+
+        let wrapper = codegen.module.functions.get("double.value").unwrap();
+        assert_eq!(wrapper.arguments[1], ("arg.0".to_string(), AzulaType::Int));
+        assert_eq!(
+            wrapper.blocks[0].1.instructions,
+            vec![
+                Instruction::LoadArg(1, 0, AzulaType::Int),
+                Instruction::FunctionCall("double".to_string(), vec![Value::Local(0)], 1),
+                Instruction::Return(Some(Value::Local(1))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_codegen_interface_value() {
+        let shape = AzulaType::Interface("Shape".to_string());
+
+        let mut codegen = Codegen::new(
+            "test",
+            Statement::Root(vec![
+                Statement::Interface {
+                    name: "Shape",
+                    methods: vec![(
+                        "area",
+                        vec![(AzulaType::Named("Self".to_string()), "self")],
+                        AzulaType::Int,
+                    )],
+                    span: Span { start: 0, end: 0 },
+                },
+                Statement::Function {
+                    name: "main",
+                    generics: vec![],
+                    bounds: vec![],
+                    args: vec![(AzulaType::Named("Square".to_string()), "square")],
+                    arg_spans: vec![],
+                    defaults: vec![],
+                    variadic: false,
+                    returns: AzulaType::Void,
+                    body: Rc::new(Statement::Block(vec![
+                        Statement::Assign(
+                            true,
+                            "shape".to_string(),
+                            Some(shape.clone()),
+                            node(
+                                Expression::Cast(
+                                    Rc::new(node(
+                                        Expression::Identifier("square".to_string()),
+                                        AzulaType::Named("Square".to_string()),
+                                    )),
+                                    shape.clone(),
+                                ),
+                                shape.clone(),
+                            ),
+                            vec![],
+                            Span { start: 0, end: 0 },
+                        ),
+                        Statement::ExpressionStatement(
+                            node(
+                                Expression::FunctionCall {
+                                    function: Rc::new(node(
+                                        Expression::StructAccess(
+                                            Rc::new(node(
+                                                Expression::Identifier("shape".to_string()),
+                                                shape.clone(),
+                                            )),
+                                            Rc::new(node(
+                                                Expression::Identifier("area".to_string()),
+                                                AzulaType::Infer,
+                                            )),
+                                        ),
+                                        AzulaType::Function(vec![], Rc::new(AzulaType::Int)),
+                                    )),
+                                    args: vec![],
+                                },
+                                AzulaType::Int,
+                            ),
+                            Span { start: 0, end: 0 },
+                        ),
+                    ])),
+                    public: false,
+                    attributes: vec![],
+                    doc: None,
+                    span: Span { start: 0, end: 0 },
+                },
+            ]),
+        );
+        codegen.codegen();
+
+        assert!(matches!(
+            codegen.module.global_values.get("vtable.Square.Shape"),
+            Some(GlobalValue::Functions(wrappers)) if wrappers == &vec!["Square.area.dyn".to_string()]
+        ));
+
+        // The wrapper unpacks the value from its env and passes it as self
+        let wrapper = codegen.module.functions.get("Square.area.dyn").unwrap();
+        assert_eq!(
+            wrapper.blocks[0].1.instructions[1..],
+            vec![
+                Instruction::AccessStructMember(Value::Local(0), 0, 1, true),
+                Instruction::FunctionCall("Square.area".to_string(), vec![Value::Local(1)], 2),
+                Instruction::Return(Some(Value::Local(2))),
+            ]
+        );
+
+        let main = codegen.module.functions.get("main").unwrap();
+        let instructions = &main.blocks[0].1.instructions;
+        assert!(instructions.iter().any(|instruction| matches!(
+            instruction,
+            Instruction::MakeInterface(vtable, ..) if vtable == "vtable.Square.Shape"
+        )));
+        assert!(instructions
+            .iter()
+            .any(|instruction| matches!(instruction, Instruction::InterfaceMethod(_, 0, _))));
+    }
+
+    #[test]
+    fn test_codegen_element_pointer() {
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        let mut func = Function::new();
+
+        let array = node(
+            Expression::Array(vec![node(Expression::Integer(5), AzulaType::Int)]),
+            AzulaType::Array(Rc::new(AzulaType::Int), Some(1)),
+        );
+        let element = node(
+            Expression::ArrayAccess(
+                Rc::new(array),
+                Rc::new(node(Expression::Integer(0), AzulaType::Int)),
+            ),
+            AzulaType::Int,
+        );
+        codegen.codegen_expr(
+            node(
+                Expression::Pointer(Rc::new(element)),
+                AzulaType::Pointer(Rc::new(AzulaType::Int)),
+            ),
+            &mut func,
+            true,
+        );
+
+        // &a[0] takes the element's address instead of loading it
+        let instructions = &func.blocks.last().unwrap().1.instructions;
+        assert!(matches!(
+            instructions.last(),
+            Some(Instruction::ElementPointer(..))
+        ));
+        assert!(!instructions
+            .iter()
+            .any(|instruction| matches!(instruction, Instruction::AccessElement(..))));
+    }
+
+    #[test]
+    fn test_codegen_nested_array() {
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        let mut func = Function::new();
+
+        let int = |val| node(Expression::Integer(val), AzulaType::Int);
+        let row_typ = AzulaType::Array(Rc::new(AzulaType::Int), Some(2));
+        let row = |vals| node(Expression::Array(vals), row_typ.clone());
+        let matrix = node(
+            Expression::Array(vec![row(vec![int(1), int(2)]), row(vec![int(3), int(4)])]),
+            AzulaType::Array(Rc::new(row_typ.clone()), Some(2)),
+        );
+        let element = node(
+            Expression::ArrayAccess(
+                Rc::new(node(
+                    Expression::ArrayAccess(Rc::new(matrix), Rc::new(int(1))),
+                    row_typ.clone(),
+                )),
+                Rc::new(int(0)),
+            ),
+            AzulaType::Int,
+        );
+        codegen.codegen_expr(element, &mut func, true);
+
+        // The rows are stored inline, one allocation of four ints
+        let instructions = &func.blocks.last().unwrap().1.instructions;
+        let arrays: Vec<_> = instructions
+            .iter()
+            .filter(|instruction| matches!(instruction, Instruction::CreateArray(..)))
+            .collect();
+        assert!(matches!(
+            arrays.as_slice(),
+            [Instruction::CreateArray(AzulaType::Int, 4, _)]
+        ));
+        // m[1][0] finds the row then the element in it
+        let accesses: Vec<_> = instructions
+            .iter()
+            .filter(|instruction| {
+                matches!(
+                    instruction,
+                    Instruction::ElementPointer(..) | Instruction::AccessElement(..)
+                )
+            })
+            .collect();
+        assert!(matches!(
+            accesses.as_slice(),
+            [
+                Instruction::ElementPointer(..),
+                Instruction::AccessElement(..)
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_codegen_declarations_first() {
+        let span = || Span { start: 0, end: 0 };
+        let limit = || ExpressionNode {
+            expression: Expression::Identifier("limit".to_string()),
+            typed: AzulaType::Int,
+            span: span(),
+        };
+        let mut codegen = Codegen::new(
+            "test",
+            Statement::Root(vec![
+                Statement::Function {
+                    name: "main",
+                    generics: vec![],
+                    bounds: vec![],
+                    args: vec![],
+                    arg_spans: vec![],
+                    defaults: vec![],
+                    variadic: false,
+                    returns: AzulaType::Int,
+                    body: Rc::new(Statement::Block(vec![Statement::Return(
+                        Some(limit()),
+                        span(),
+                    )])),
+                    public: false,
+                    attributes: vec![],
+                    doc: None,
+                    span: span(),
+                },
+                Statement::Assign(
+                    false,
+                    "limit".to_string(),
+                    None,
+                    ExpressionNode {
+                        expression: Expression::Integer(10),
+                        typed: AzulaType::Int,
+                        span: span(),
+                    },
+                    vec![],
+                    span(),
+                ),
+            ]),
+        );
+        codegen.codegen();
+
+        // The global is known by the time main is generated, even though it
+        // comes after it
+        let main = codegen.module.functions.get("main").unwrap();
+        assert_eq!(
+            main.blocks[0].1.instructions[0],
+            Instruction::LoadGlobal("limit".to_string(), 0, AzulaType::Int)
+        );
+    }
+
+    #[test]
+    fn test_codegen_struct_member_reassign() {
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        codegen.module.add_struct(
+            "Point",
+            Struct {
+                name: "Point",
+                attributes: vec![(AzulaType::Int, "x"), (AzulaType::Int, "y")],
+            },
+        );
+        let point = AzulaType::Named("Point".to_string());
+        let mut func = Function::new();
+        func.variables.insert("p".to_string(), point.clone());
+
+        // The struct is changed in a copy that's stored back into p
+        codegen.codegen_reassign(
+            Statement::Reassign(
+                ExpressionNode {
+                    expression: Expression::StructAccess(
+                        Rc::new(ExpressionNode {
+                            expression: Expression::Identifier("p".to_string()),
+                            typed: point.clone(),
+                            span: Span { start: 0, end: 1 },
+                        }),
+                        Rc::new(ExpressionNode {
+                            expression: Expression::Identifier("y".to_string()),
+                            typed: AzulaType::Int,
+                            span: Span { start: 2, end: 3 },
+                        }),
+                    ),
+                    typed: AzulaType::Int,
+                    span: Span { start: 0, end: 3 },
+                },
+                ExpressionNode {
+                    expression: Expression::Integer(5),
+                    typed: AzulaType::Int,
+                    span: Span { start: 6, end: 7 },
+                },
+                Span { start: 0, end: 7 },
+            ),
+            &mut func,
+        );
+        assert_eq!(
+            func.blocks[0].1.instructions,
+            vec![
+                Instruction::ConstInt(5, 0),
+                Instruction::Load("p".to_string(), 1, point.clone()),
+                Instruction::StoreStructMember(Value::Local(1), 1, Value::Local(0)),
+                Instruction::Store("p".to_string(), Value::Local(1), point)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_codegen_scoped_defer() {
+        let call = |name: &str| {
+            node(
+                Expression::FunctionCall {
+                    function: Rc::new(node(
+                        Expression::Identifier(name.to_string()),
+                        AzulaType::Infer,
+                    )),
+                    args: vec![],
+                },
+                AzulaType::Void,
+            )
+        };
+
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        codegen.codegen_function(Statement::Function {
+            name: "main",
+            generics: vec![],
+            bounds: vec![],
+            args: vec![],
+            arg_spans: vec![],
+            defaults: vec![],
+            variadic: false,
+            returns: AzulaType::Void,
+            body: Rc::new(Statement::Block(vec![
+                Statement::Defer(call("outer"), Span { start: 0, end: 0 }),
+                Statement::While(
+                    node(Expression::Boolean(true), AzulaType::Bool),
+                    vec![
+                        Statement::Defer(call("inner"), Span { start: 0, end: 0 }),
+                        Statement::Break(None, Span { start: 0, end: 0 }),
+                    ],
+                    None,
+                    Span { start: 0, end: 0 },
+                ),
+                Statement::Scope(
+                    vec![Statement::Defer(call("block"), Span { start: 0, end: 0 })],
+                    Span { start: 0, end: 0 },
+                ),
+            ])),
+            public: false,
+            attributes: vec![],
+            doc: None,
+            span: Span { start: 0, end: 0 },
+        });
+
+        // Breaking out of the loop only runs the loop body's defer, a block's
+        // runs as it ends and the function's once it ends
+        let func = codegen.module.functions.get("main").unwrap();
+        let block = |name: &str| {
+            func.blocks
+                .iter()
+                .find(|(block, _)| block == name)
+                .map(|(_, block)| block.instructions.clone())
+                .unwrap()
+        };
+        assert_eq!(
+            block("loop-0"),
+            vec![
+                Instruction::FunctionCall("inner".to_string(), vec![], 1),
+                Instruction::Jump("end-0".to_string()),
+            ]
+        );
+        assert_eq!(
+            block("end-0"),
+            vec![
+                Instruction::FunctionCall("block".to_string(), vec![], 3),
+                Instruction::FunctionCall("outer".to_string(), vec![], 4),
+                Instruction::Return(None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_codegen_return_skips_its_drop() {
+        let buffer = AzulaType::Named("Buffer".to_string());
+        let local = |name: &str| node(Expression::Identifier(name.to_string()), buffer.clone());
+        let drop = |name: &str| {
+            Statement::Defer(
+                node(
+                    Expression::FunctionCall {
+                        function: Rc::new(node(
+                            Expression::Identifier("Buffer.drop".to_string()),
+                            AzulaType::Infer,
+                        )),
+                        args: vec![local(name)],
+                    },
+                    AzulaType::Void,
+                ),
+                Span { start: 0, end: 0 },
+            )
+        };
+
+        // if flag { return a; } return b;
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        codegen.codegen_function(Statement::Function {
+            name: "main",
+            generics: vec![],
+            bounds: vec![],
+            args: vec![
+                (buffer.clone(), "a"),
+                (buffer.clone(), "b"),
+                (AzulaType::Bool, "flag"),
+            ],
+            arg_spans: vec![],
+            defaults: vec![],
+            variadic: false,
+            returns: buffer.clone(),
+            body: Rc::new(Statement::Block(vec![
+                drop("a"),
+                drop("b"),
+                Statement::If(
+                    node(Expression::Identifier("flag".to_string()), AzulaType::Bool),
+                    vec![Statement::Return(
+                        Some(local("a")),
+                        Span { start: 0, end: 0 },
+                    )],
+                    Span { start: 0, end: 0 },
+                ),
+                Statement::Return(Some(local("b")), Span { start: 0, end: 0 }),
+            ])),
+            public: false,
+            attributes: vec![],
+            doc: None,
+            span: Span { start: 0, end: 0 },
+        });
+
+        // The args each return's drops are called with
+        let func = codegen.module.functions.get("main").unwrap();
+        let dropped = |name: &str| {
+            let instructions = func
+                .blocks
+                .iter()
+                .find(|(block, _)| block == name)
+                .map(|(_, block)| block.instructions.clone())
+                .unwrap();
+            instructions
+                .iter()
+                .filter_map(|instruction| match instruction {
+                    Instruction::FunctionCall(function, args, _) if function == "Buffer.drop" => {
+                        instructions
+                            .iter()
+                            .find_map(|load| match (load, &args[..]) {
+                                (Instruction::LoadArg(arg, dest, _), [Value::Local(value)])
+                                    if dest == value =>
+                                {
+                                    Some(*arg)
+                                }
+                                _ => None,
+                            })
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(dropped("true-0"), vec![1]);
+        assert_eq!(dropped("end-0"), vec![0]);
+    }
+
+    #[test]
+    fn test_codegen_test_harness() {
+        let function = |name, returns, attributes: Vec<Attribute<'static>>| Statement::Function {
+            name,
+            generics: vec![],
+            bounds: vec![],
+            args: vec![],
+            arg_spans: vec![],
+            defaults: vec![],
+            variadic: false,
+            returns,
+            body: Rc::new(Statement::Block(vec![])),
+            public: false,
+            attributes,
+            doc: None,
+            span: Span { start: 0, end: 0 },
+        };
+
+        let mut codegen = Codegen::new(
+            "test",
+            Statement::Root(vec![
+                function("adds", AzulaType::Void, vec![Attribute::new("test")]),
+                function(
+                    "parses",
+                    AzulaType::Result(Rc::new(AzulaType::Void)),
+                    vec![Attribute::new("test")],
+                ),
+                function("helper", AzulaType::Void, vec![]),
+                function("main", AzulaType::Void, vec![]),
+            ]),
+        );
+        codegen.codegen();
+        codegen.codegen_test_harness();
+
+        // The program's own main is replaced, and only the tests are called
+        let main = codegen.module.functions.get("main").unwrap();
+        assert_eq!(main.returns, AzulaType::Int);
+        let called: Vec<_> = main
+            .blocks
+            .iter()
+            .flat_map(|(_, block)| &block.instructions)
+            .filter_map(|instruction| match instruction {
+                Instruction::FunctionCall(name, ..) if name != "printf" => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(called, vec!["adds", "parses"]);
+        // Only the test returning a result can fail
+        let blocks: Vec<_> = main.blocks.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            blocks,
+            vec![
+                "entry",
+                "passed-1",
+                "failed-1",
+                "next-1",
+                "tests-failed",
+                "tests-passed"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_codegen_for() {
+        let mut codegen = Codegen::new("test", Statement::Root(vec![]));
+        let mut func = Function::new();
+        codegen.codegen_for(
+            Statement::For(
+                Some("i"),
+                "x",
+                node(
+                    Expression::Array(vec![
+                        node(Expression::Integer(1), AzulaType::Int),
+                        node(Expression::Integer(2), AzulaType::Int),
+                    ]),
+                    AzulaType::Array(Rc::new(AzulaType::Int), Some(2)),
+                ),
+                vec![Statement::Continue(None, Span { start: 0, end: 0 })],
+                None,
+                Span { start: 0, end: 0 },
+            ),
+            &mut func,
+        );
+
+        let last = |name: &str| {
+            func.blocks
+                .iter()
+                .find(|(block, _)| block == name)
+                .and_then(|(_, block)| block.instructions.last().cloned())
+        };
+        assert_eq!(func.variables.get("i"), Some(&AzulaType::Int));
+        assert_eq!(func.variables.get("x"), Some(&AzulaType::Int));
+        // continue moves on to the next element rather than checking the same
+        // one again
+        assert_eq!(
+            last("loop-0"),
+            Some(Instruction::Jump("step-0".to_string()))
+        );
+        assert_eq!(
+            last("step-0"),
+            Some(Instruction::Jump("eval-0".to_string()))
+        );
+        assert!(matches!(
+            last("eval-0"),
+            Some(Instruction::Jcond(_, body, end)) if body == "loop-0" && end == "end-0"
+        ));
+    }
 }
@@ -1,7 +1,8 @@
 mod backend;
 mod codegen;
+mod scopes;
 
 pub mod prelude {
-    pub use crate::backend::{Backend, OptimizationLevel};
+    pub use crate::backend::{Backend, OptimizationLevel, RelocModel, TargetOptions};
     pub use crate::codegen::Codegen;
 }
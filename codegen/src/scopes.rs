@@ -0,0 +1,328 @@
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use azula_ast::prelude::*;
+use azula_type::prelude::AzulaType;
+
+// A function's locals are stored by name, so a variable declared again in a
+// nested scope, `var x = 1; { var x = 2; }`, would write over the outer one.
+// Each declaration of a name that's already taken gets a name of its own,
+// x.1, and the uses it's in scope for are renamed to match. A . can't be
+// written in a name, so these don't clash with the source's
+pub fn rename_shadowed<'a>(
+    args: &[(AzulaType<'a>, &'a str)],
+    body: Statement<'a>,
+) -> Statement<'a> {
+    let mut scopes = Scopes {
+        taken: args.iter().map(|(_, name)| name.to_string()).collect(),
+        names: vec![HashMap::new()],
+    };
+    let mut body = body;
+    if let Statement::Block(stmts) = &mut body {
+        scopes.body(stmts);
+    }
+    body
+}
+
+struct Scopes {
+    // Every name given to a local of the function so far
+    taken: HashSet<String>,
+    // What the names in each scope around the statement are stored as,
+    // innermost last
+    names: Vec<HashMap<String, String>>,
+}
+
+impl Scopes {
+    fn declare(&mut self, name: &str) -> String {
+        let mut stored = name.to_string();
+        let mut count = 0;
+        while self.taken.contains(&stored) {
+            count += 1;
+            stored = format!("{}.{}", name, count);
+        }
+        self.taken.insert(stored.clone());
+        self.names
+            .last_mut()
+            .unwrap()
+            .insert(name.to_string(), stored.clone());
+        stored
+    }
+
+    fn declare_str<'a>(&mut self, name: &'a str) -> &'a str {
+        let stored = self.declare(name);
+        if stored == name {
+            name
+        } else {
+            Box::leak(stored.into_boxed_str())
+        }
+    }
+
+    fn resolve(&self, name: &str) -> Option<&String> {
+        self.names.iter().rev().find_map(|names| names.get(name))
+    }
+
+    fn scoped<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.names.push(HashMap::new());
+        let result = f(self);
+        self.names.pop();
+        result
+    }
+
+    fn body(&mut self, body: &mut [Statement]) {
+        for stmt in body {
+            self.statement(stmt);
+        }
+    }
+
+    fn statement(&mut self, stmt: &mut Statement) {
+        match stmt {
+            Statement::Assign(_, name, _, value, _, _) => {
+                self.expression(value);
+                *name = self.declare(name);
+            }
+            Statement::Destructure(_, names, value, _) => {
+                self.expression(value);
+                for name in names {
+                    *name = self.declare_str(*name);
+                }
+            }
+            Statement::Reassign(target, value, _) => {
+                self.expression(target);
+                self.expression(value);
+            }
+            Statement::ExpressionStatement(expr, _) | Statement::Defer(expr, _) => {
+                self.expression(expr)
+            }
+            Statement::Return(value, _) => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            }
+            Statement::If(cond, body, _)
+            | Statement::While(cond, body, ..)
+            | Statement::DoWhile(cond, body, ..) => {
+                self.expression(cond);
+                self.scoped(|scopes| scopes.body(body));
+            }
+            Statement::For(index, name, iterable, body, ..) => {
+                self.expression(iterable);
+                self.scoped(|scopes| {
+                    if let Some(index) = index.as_mut().filter(|index| **index != "_") {
+                        *index = scopes.declare_str(*index);
+                    }
+                    if *name != "_" {
+                        *name = scopes.declare_str(*name);
+                    }
+                    scopes.body(body);
+                });
+            }
+            Statement::IfLet(_, name, value, body, _) => {
+                self.expression(value);
+                self.scoped(|scopes| {
+                    *name = scopes.declare_str(*name);
+                    scopes.body(body);
+                });
+            }
+            Statement::Match(value, arms, _) => {
+                self.expression(value);
+                for (_, binding, body, _) in arms {
+                    self.scoped(|scopes| {
+                        if let Some(binding) = binding {
+                            *binding = scopes.declare_str(*binding);
+                        }
+                        scopes.body(body);
+                    });
+                }
+            }
+            Statement::Block(body) | Statement::Scope(body, _) => {
+                self.scoped(|scopes| scopes.body(body))
+            }
+            _ => {}
+        }
+    }
+
+    fn expression(&mut self, expr: &mut ExpressionNode) {
+        let inner = |scopes: &mut Self, expr: &mut Rc<ExpressionNode>| {
+            scopes.expression(Rc::make_mut(expr))
+        };
+
+        match &mut expr.expression {
+            Expression::Identifier(name) => {
+                if let Some(stored) = self.resolve(name) {
+                    *name = stored.clone();
+                }
+            }
+            Expression::Infix(left, _, right)
+            | Expression::ArrayAccess(left, right)
+            | Expression::ArrayRepeat(left, right)
+            | Expression::Range(left, right) => {
+                inner(self, left);
+                inner(self, right);
+            }
+            Expression::FunctionCall { function, args } => {
+                inner(self, function);
+                for arg in args {
+                    self.expression(arg);
+                }
+            }
+            Expression::Array(values) | Expression::Tuple(values) => {
+                for value in values {
+                    self.expression(value);
+                }
+            }
+            // The struct's name and the member are names of their own
+            Expression::StructInitialisation(_, values) => {
+                for (_, value) in values {
+                    self.expression(value);
+                }
+            }
+            Expression::StructAccess(value, _) => inner(self, value),
+            Expression::Not(value)
+            | Expression::Negate(value)
+            | Expression::Pointer(value)
+            | Expression::Deref(value)
+            | Expression::Cast(value, _)
+            | Expression::Try(value, _)
+            | Expression::NamedArgument(_, value) => inner(self, value),
+            Expression::Ternary(cond, then, otherwise) => {
+                inner(self, cond);
+                inner(self, then);
+                inner(self, otherwise);
+            }
+            // The args are the lifted function's own, the captures are read
+            // from this one
+            Expression::Closure {
+                args,
+                body,
+                captures,
+            } => {
+                for (_, name) in captures {
+                    if let Some(stored) = self.resolve(name) {
+                        *name = stored.clone();
+                    }
+                }
+                self.scoped(|scopes| {
+                    for (_, arg) in args.iter() {
+                        scopes
+                            .names
+                            .last_mut()
+                            .unwrap()
+                            .insert(arg.to_string(), arg.to_string());
+                    }
+                    inner(scopes, body);
+                });
+            }
+            Expression::Integer(_)
+            | Expression::Float(_)
+            | Expression::Boolean(_)
+            | Expression::String(_)
+            | Expression::SizeOf(_)
+            | Expression::New(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(expression: Expression) -> ExpressionNode {
+        ExpressionNode {
+            expression,
+            typed: AzulaType::Int,
+            span: Span { start: 0, end: 0 },
+        }
+    }
+
+    fn assign<'a>(name: &str, value: ExpressionNode<'a>) -> Statement<'a> {
+        Statement::Assign(
+            false,
+            name.to_string(),
+            None,
+            value,
+            vec![],
+            Span { start: 0, end: 0 },
+        )
+    }
+
+    fn read<'a>(name: &str) -> Statement<'a> {
+        Statement::ExpressionStatement(
+            node(Expression::Identifier(name.to_string())),
+            Span { start: 0, end: 0 },
+        )
+    }
+
+    #[test]
+    fn test_rename_shadowed() {
+        // var x = 1; { var x = 2; x; { x; } } x;
+        let body = Statement::Block(vec![
+            assign("x", node(Expression::Integer(1))),
+            Statement::Scope(
+                vec![
+                    assign("x", node(Expression::Integer(2))),
+                    read("x"),
+                    Statement::Scope(vec![read("x")], Span { start: 0, end: 0 }),
+                ],
+                Span { start: 0, end: 0 },
+            ),
+            read("x"),
+        ]);
+
+        assert_eq!(
+            rename_shadowed(&[], body),
+            Statement::Block(vec![
+                assign("x", node(Expression::Integer(1))),
+                Statement::Scope(
+                    vec![
+                        assign("x.1", node(Expression::Integer(2))),
+                        read("x.1"),
+                        Statement::Scope(vec![read("x.1")], Span { start: 0, end: 0 }),
+                    ],
+                    Span { start: 0, end: 0 },
+                ),
+                read("x"),
+            ])
+        );
+
+        // A param is taken too, and the value of the declaration shadowing it
+        // still reads it
+        let body = Statement::Block(vec![Statement::Scope(
+            vec![assign("n", node(Expression::Identifier("n".to_string())))],
+            Span { start: 0, end: 0 },
+        )]);
+        assert_eq!(
+            rename_shadowed(&[(AzulaType::Int, "n")], body),
+            Statement::Block(vec![Statement::Scope(
+                vec![assign("n.1", node(Expression::Identifier("n".to_string())))],
+                Span { start: 0, end: 0 },
+            )])
+        );
+
+        // var i = 0; for i in xs { i; } i;
+        let for_loop = |name, body| {
+            Statement::For(
+                None,
+                name,
+                node(Expression::Identifier("xs".to_string())),
+                body,
+                None,
+                Span { start: 0, end: 0 },
+            )
+        };
+        let body = Statement::Block(vec![
+            assign("i", node(Expression::Integer(0))),
+            for_loop("i", vec![read("i")]),
+            read("i"),
+        ]);
+        assert_eq!(
+            rename_shadowed(&[], body),
+            Statement::Block(vec![
+                assign("i", node(Expression::Integer(0))),
+                for_loop("i.1", vec![read("i.1")]),
+                read("i"),
+            ])
+        );
+    }
+}
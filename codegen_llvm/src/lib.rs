@@ -1,5 +1,5 @@
 pub mod llvm;
 
 pub mod prelude {
-    pub use crate::llvm::LLVMCodegen;
+    pub use crate::llvm::{LLVMCodegen, ObjectCompiler};
 }
@@ -1,22 +1,64 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
+use std::fs;
 use std::ops::Deref;
 use std::path::Path;
-use std::process::Command;
+use std::process::{Child, Command};
+use std::rc::Rc;
 
 use azula_codegen::prelude::Backend;
 use azula_codegen::prelude::OptimizationLevel;
+use azula_codegen::prelude::{RelocModel, TargetOptions};
 use azula_ir::prelude::{GlobalValue, Instruction, Module, Value};
 use azula_type::prelude::AzulaType;
+use inkwell::attributes::{Attribute, AttributeLoc};
 use inkwell::basic_block::BasicBlock;
+use inkwell::memory_buffer::MemoryBuffer;
 use inkwell::module::{Linkage, Module as LLVMModule};
-use inkwell::targets::{FileType, InitializationConfig, Target, TargetMachine, TargetTriple};
+use inkwell::targets::{
+    ByteOrdering, FileType, InitializationConfig, RelocMode, Target, TargetData, TargetMachine,
+    TargetTriple,
+};
 use inkwell::types::StructType;
-use inkwell::types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum, FunctionType};
-use inkwell::values::{BasicMetadataValueEnum, BasicValue, BasicValueEnum, FunctionValue};
+use inkwell::types::{AnyType, BasicMetadataTypeEnum, BasicType, BasicTypeEnum, FunctionType};
+use inkwell::values::{
+    BasicMetadataValueEnum, BasicValue, BasicValueEnum, CallableValue, FunctionValue, IntValue,
+    PointerValue,
+};
 use inkwell::{builder::Builder, context::Context};
 use inkwell::{AddressSpace, FloatPredicate, IntPredicate};
 
+// C functions that only read their arguments, or memory their arguments
+// point to, and always return. Declaring them so lets LLVM merge repeated
+// calls and hoist them out of loops
+const PURE_FUNCTIONS: &[(&str, &str)] = &[
+    ("pow", "readnone"),
+    ("sqrt", "readnone"),
+    ("cbrt", "readnone"),
+    ("exp", "readnone"),
+    ("log", "readnone"),
+    ("log2", "readnone"),
+    ("log10", "readnone"),
+    ("sin", "readnone"),
+    ("cos", "readnone"),
+    ("tan", "readnone"),
+    ("atan2", "readnone"),
+    ("fabs", "readnone"),
+    ("floor", "readnone"),
+    ("ceil", "readnone"),
+    ("round", "readnone"),
+    ("fmod", "readnone"),
+    ("abs", "readnone"),
+    ("strlen", "readonly"),
+    ("strcmp", "readonly"),
+    ("strncmp", "readonly"),
+    ("memcmp", "readonly"),
+];
+
+// LLVM's numbers for the avr_intrcc and msp430_intrcc calling conventions
+const AVR_INTERRUPT_CALL_CONV: u32 = 84;
+const MSP430_INTERRUPT_CALL_CONV: u32 = 69;
+
 pub struct LLVMCodegen<'ctx> {
     context: &'ctx Context,
     module: LLVMModule<'ctx>,
@@ -26,9 +68,30 @@ pub struct LLVMCodegen<'ctx> {
     string_size: HashMap<usize, usize>,
     globals: HashMap<String, BasicValueEnum<'ctx>>,
     structs: HashMap<String, StructType<'ctx>>,
+    // Extern functions returning a string, which comes back from C without
+    // its length
+    c_strings: HashSet<String>,
 
-    target: Option<String>,
+    target: TargetOptions,
     opt_level: OptimizationLevel,
+    // Layout of the target's types, for sizeof
+    target_data: Option<TargetData>,
+    // The parameters of each function that pass a struct in memory
+    memory_params: HashMap<String, Vec<MemoryParam<'ctx>>>,
+}
+
+// A parameter abi_function_type made a pointer to a struct
+#[derive(Clone, Copy)]
+struct MemoryParam<'a> {
+    index: u32,
+    // sret for where the result goes, byval for an argument
+    attribute: &'static str,
+    typ: BasicTypeEnum<'a>,
+}
+
+// The sret pointer comes before the arguments
+fn returns_in_memory(params: &[MemoryParam]) -> bool {
+    params.iter().any(|param| param.attribute == "sret")
 }
 
 struct FunctionLocals<'a> {
@@ -36,6 +99,10 @@ struct FunctionLocals<'a> {
     variables: HashMap<String, BasicValueEnum<'a>>,
 
     blocks: HashMap<String, BasicBlock<'a>>,
+    // Set by @jumptable, dense switches become an indirect branch through a
+    // table of block addresses
+    jump_tables: bool,
+    memory_params: Vec<MemoryParam<'a>>,
 }
 
 impl<'ctx> Backend<'ctx> for LLVMCodegen<'ctx> {
@@ -43,83 +110,255 @@ impl<'ctx> Backend<'ctx> for LLVMCodegen<'ctx> {
         name: &'ctx str,
         destination: &'ctx str,
         emit: bool,
-        target: Option<&String>,
+        target: &TargetOptions,
         opt_level: OptimizationLevel,
         module: Module<'ctx>,
     ) -> Result<(), Box<dyn Error>> {
         let context = Context::create();
+        let codegen = LLVMCodegen::generate(&context, target, opt_level, &module)?;
+
+        codegen.write_program(name, destination, emit)?.wait()?;
+
+        Ok(())
+    }
+
+    // The modules share a context, and each program links while the next
+    // one is compiled
+    fn codegen_programs(
+        programs: Vec<(&'ctx str, Module<'ctx>)>,
+        destination: &'ctx str,
+        emit: bool,
+        target: &TargetOptions,
+        opt_level: OptimizationLevel,
+    ) -> Result<(), Box<dyn Error>> {
+        let context = Context::create();
+        let mut links = vec![];
+        for (name, module) in programs {
+            let codegen = LLVMCodegen::generate(&context, target, opt_level, &module)?;
+            links.push(codegen.write_program(name, destination, emit)?);
+        }
+        for mut link in links {
+            link.wait()?;
+        }
+
+        Ok(())
+    }
+
+    fn build_object(
+        name: &'ctx str,
+        target: &TargetOptions,
+        opt_level: OptimizationLevel,
+        module: Module<'ctx>,
+    ) -> Result<String, Box<dyn Error>> {
+        let context = Context::create();
+        let codegen = LLVMCodegen::generate(&context, target, opt_level, &module)?;
+
+        let object_file = LLVMCodegen::object_file(name);
+        codegen.build_object_file(&object_file)?;
+
+        Ok(object_file)
+    }
+
+    fn run(
+        module: Module<'ctx>,
+        opt_level: OptimizationLevel,
+        cache: &Path,
+        args: &[String],
+    ) -> Result<i32, Box<dyn Error>> {
+        let context = Context::create();
+        let codegen =
+            LLVMCodegen::generate(&context, &TargetOptions::default(), opt_level, &module)?;
+
+        codegen.module.write_bitcode_to_path(cache);
+
+        LLVMCodegen::execute(&codegen.module, &codegen.opt_level, args)
+    }
+
+    fn run_cached(
+        cache: &Path,
+        opt_level: OptimizationLevel,
+        args: &[String],
+    ) -> Result<i32, Box<dyn Error>> {
+        let context = Context::create();
+        let module =
+            LLVMModule::parse_bitcode_from_path(cache, &context).map_err(|e| e.to_string())?;
+
+        LLVMCodegen::execute(&module, &opt_level, args)
+    }
+}
+
+/// Compiles modules to object files in memory, one after another, keeping
+/// the LLVM context and target machine between them. For a server answering
+/// many requests, where setting them up again each time would be most of
+/// the work
+pub struct ObjectCompiler {
+    context: Context,
+    machine: TargetMachine,
+    target: TargetOptions,
+    opt_level: OptimizationLevel,
+}
+
+impl ObjectCompiler {
+    pub fn new(target: &TargetOptions, opt_level: OptimizationLevel) -> Result<Self, String> {
+        Ok(ObjectCompiler {
+            context: Context::create(),
+            machine: LLVMCodegen::create_machine(target, opt_level)?,
+            target: target.clone(),
+            opt_level,
+        })
+    }
+
+    /// The bytes of the module's object file
+    pub fn compile<'a>(&'a self, module: &Module<'a>) -> Result<Vec<u8>, Box<dyn Error>> {
+        let codegen = LLVMCodegen::generate_with(
+            &self.context,
+            &self.machine,
+            &self.target,
+            self.opt_level,
+            module,
+        )?;
+        let object = self
+            .machine
+            .write_to_memory_buffer(&codegen.module, FileType::Object)
+            .map_err(|e| e.to_string())?;
+
+        Ok(object.as_slice().to_vec())
+    }
+}
+
+impl<'a> LLVMCodegen<'a> {
+    fn generate(
+        context: &'a Context,
+        target: &TargetOptions,
+        opt_level: OptimizationLevel,
+        module: &Module<'a>,
+    ) -> Result<Self, String> {
+        let machine = LLVMCodegen::create_machine(target, opt_level)?;
+        LLVMCodegen::generate_with(context, &machine, target, opt_level, module)
+    }
+
+    fn generate_with(
+        context: &'a Context,
+        machine: &TargetMachine,
+        target: &TargetOptions,
+        opt_level: OptimizationLevel,
+        module: &Module<'a>,
+    ) -> Result<Self, String> {
         let llvm_module = context.create_module(module.name);
-        let target = if let Some(val) = target {
-            Some(val.clone())
-        } else {
-            None
-        };
+        let target = target.clone();
         let mut codegen = LLVMCodegen {
-            context: &context,
+            context,
             module: llvm_module,
             builder: context.create_builder(),
             strings: HashMap::new(),
             string_size: HashMap::new(),
             globals: HashMap::new(),
             structs: HashMap::new(),
+            c_strings: HashSet::new(),
             target,
             opt_level,
+            target_data: None,
+            memory_params: HashMap::new(),
+        };
+
+        // Sizes depend on the target, so its layout is fixed before any code
+        codegen.module.set_triple(&machine.get_triple());
+        let target_data = match &codegen.target.data_layout {
+            Some(layout) => TargetData::create(layout),
+            None => machine.get_target_data(),
         };
+        codegen
+            .module
+            .set_data_layout(&target_data.get_data_layout());
+        codegen.target_data = Some(target_data);
 
-        codegen.generate_structs(&module);
+        codegen.generate_structs(module);
 
+        // C takes and returns strings as a pointer to their first byte
+        let string = AzulaType::Pointer(Rc::new(AzulaType::Str));
+        let c_string = codegen.context.i8_type().ptr_type(AddressSpace::Generic);
         for (name, extern_func) in &module.extern_functions {
-            let args: Vec<_> = extern_func
+            let args: Vec<BasicMetadataTypeEnum> = extern_func
                 .arguments
                 .iter()
-                .map(|arg| codegen.azula_type_to_llvm_basic_type(arg.clone()).into())
+                .map(|arg| match arg {
+                    arg if *arg == string => c_string.into(),
+                    arg => codegen.azula_type_to_llvm_basic_type(arg.clone()).into(),
+                })
                 .collect();
-            codegen.module.add_function(
-                name,
-                codegen.azula_type_to_function_llvm_type_with_varargs(
-                    extern_func.returns.clone(),
-                    &args,
-                    extern_func.varargs,
-                ),
-                Some(Linkage::External),
+            if extern_func.returns == string {
+                codegen.c_strings.insert(name.to_string());
+            }
+            let typ = codegen.azula_type_to_function_llvm_type_with_varargs(
+                extern_func.returns.clone(),
+                &args,
+                extern_func.varargs,
             );
+            codegen.add_abi_function(name, typ, Some(Linkage::External));
         }
 
+        // sighandler_t signal(int, sighandler_t), with handlers passed as i8*
+        let i8_ptr = codegen.context.i8_type().ptr_type(AddressSpace::Generic);
         codegen.module.add_function(
-            "pow",
-            codegen.context.f64_type().fn_type(
-                &[
-                    codegen.context.f64_type().as_basic_type_enum().into(),
-                    codegen.context.f64_type().as_basic_type_enum().into(),
-                ],
-                false,
-            ),
+            "signal",
+            i8_ptr.fn_type(&[codegen.context.i32_type().into(), i8_ptr.into()], false),
             Some(Linkage::External),
         );
+        for (name, memory) in PURE_FUNCTIONS {
+            if let Some(function) = codegen.module.get_function(name) {
+                for attribute in [*memory, "nounwind", "willreturn"] {
+                    function
+                        .add_attribute(AttributeLoc::Function, codegen.enum_attribute(attribute));
+                }
+            }
+        }
         let mut i = 0;
 
         for (name, func) in &module.functions {
-            let mut linkage = Some(Linkage::Private);
-            // let mut linkage = None;
-            if *name == "main" {
+            // Internal rather than private so the symbols stay in the object's symbol
+            // table for tools like --size-report. pub functions are exported so
+            // other programs can link against them
+            let mut linkage = Some(Linkage::Internal);
+            // Interrupt handlers are only referred to from the vector table
+            let interrupt = func.attributes.contains(&"interrupt");
+            if *name == "main" || func.exported || interrupt {
                 linkage = None;
             }
-            codegen.module.add_function(
-                name,
-                codegen.azula_type_to_function_llvm_type(
-                    func.returns.clone(),
-                    &func
-                        .arguments
-                        .iter()
-                        .map(|(_, typ)| codegen.azula_type_to_llvm_basic_type(typ.clone()).into())
-                        .collect::<Vec<_>>(),
-                ),
-                linkage,
+            let typ = codegen.azula_type_to_function_llvm_type(
+                func.returns.clone(),
+                &func
+                    .arguments
+                    .iter()
+                    .map(|(_, typ)| codegen.azula_type_to_llvm_basic_type(typ.clone()).into())
+                    .collect::<Vec<_>>(),
             );
+            let function = codegen.add_abi_function(name, typ, linkage);
+
+            // One section per function so the linker can drop unused ones
+            if LLVMCodegen::uses_elf(codegen.target.triple.as_ref()) {
+                function.set_section(&format!(".text.{}", name));
+            }
+            for attribute in &func.attributes {
+                let attribute = match *attribute {
+                    "nojumptable" => codegen
+                        .context
+                        .create_string_attribute("no-jump-tables", "true"),
+                    "inline" => codegen.enum_attribute("alwaysinline"),
+                    "noinline" => codegen.enum_attribute("noinline"),
+                    "cold" => codegen.enum_attribute("cold"),
+                    _ => continue,
+                };
+                function.add_attribute(AttributeLoc::Function, attribute);
+            }
+            if interrupt {
+                codegen.set_interrupt(function)?;
+            }
         }
 
         for (name, func) in &module.functions {
             let mut locals = FunctionLocals::new();
+            locals.jump_tables = func.attributes.contains(&"jumptable");
+            locals.memory_params = codegen.memory_params(name);
             let function = codegen.module.get_function(name).unwrap();
 
             for (name, block) in &func.blocks {
@@ -130,7 +369,7 @@ impl<'ctx> Backend<'ctx> for LLVMCodegen<'ctx> {
                 };
                 codegen.builder.position_at_end(basic);
                 if i == 0 {
-                    codegen.store_globals(&module);
+                    codegen.store_globals(module);
                     i += 1;
                 }
                 for instruction in &block.instructions {
@@ -139,55 +378,169 @@ impl<'ctx> Backend<'ctx> for LLVMCodegen<'ctx> {
             }
         }
 
+        Ok(codegen)
+    }
+
+    // Writes the object file, and the LLVM IR if asked, returning the
+    // running linker
+    fn write_program(
+        &self,
+        name: &str,
+        destination: &str,
+        emit: bool,
+    ) -> Result<Child, Box<dyn Error>> {
         if emit {
-            codegen
-                .module
-                .print_to_file(format!("{}.ll", name))
-                .unwrap();
+            self.module.print_to_file(format!("{}.ll", name))?;
         }
 
-        let object_file = format!(".build/{}.o", name);
-        codegen.build_object_file(object_file.clone());
-
-        if let Some(target) = codegen.target {
-            Command::new("zig")
-                .arg("cc")
-                .arg(format!("-o{}{}", destination, name))
-                .arg(object_file)
-                .arg("-target")
-                .arg(target)
-                .spawn()
-                .unwrap()
-                .wait()
-                .unwrap();
-        } else {
-            Command::new("zig")
-                .arg("cc")
-                .arg(format!("-o{}{}", destination, name))
-                .arg(object_file)
-                .spawn()
-                .unwrap()
-                .wait()
-                .unwrap();
+        let object_file = LLVMCodegen::object_file(name);
+        self.build_object_file(&object_file)?;
+
+        let link = LLVMCodegen::link_command(name, destination, &self.target);
+        Ok(Command::new(&link[0]).args(&link[1..]).spawn()?)
+    }
+
+    /// Sizes of the symbols defined in an object file, largest first
+    pub fn symbol_sizes(object_file: &str) -> Result<Vec<(String, u64)>, Box<dyn Error>> {
+        let buffer = MemoryBuffer::create_from_file(Path::new(object_file))?;
+        let object = buffer
+            .create_object_file()
+            .map_err(|_| format!("{} is not an object file", object_file))?;
+
+        let mut sizes = vec![];
+        for symbol in object.get_symbols() {
+            if let Some(name) = symbol.get_name() {
+                sizes.push((name.to_string_lossy().to_string(), symbol.get_size()));
+            }
         }
+        sizes.sort_by(|(a_name, a_size), (b_name, b_size)| {
+            b_size.cmp(a_size).then(a_name.cmp(b_name))
+        });
 
-        Ok(())
+        Ok(sizes)
+    }
+
+    // JIT compiles the module for the host, skipping the object file and
+    // the linker, and calls its main
+    fn execute(
+        module: &LLVMModule<'a>,
+        opt_level: &OptimizationLevel,
+        args: &[String],
+    ) -> Result<i32, Box<dyn Error>> {
+        Target::initialize_native(&InitializationConfig::default())?;
+
+        let mut jit_level = inkwell::OptimizationLevel::Default;
+        if *opt_level == OptimizationLevel::Aggressive {
+            jit_level = inkwell::OptimizationLevel::Aggressive;
+        }
+        let engine = module
+            .create_jit_execution_engine(jit_level)
+            .map_err(|e| e.to_string())?;
+        let main = module.get_function("main").ok_or("no main function")?;
+
+        let args: Vec<_> = args.iter().map(String::as_str).collect();
+        Ok(unsafe { engine.run_function_as_main(main, &args) })
+    }
+
+    pub fn object_file(name: &str) -> String {
+        format!(".build/{}.o", name)
+    }
+
+    /// The linker invocation for an object built by `codegen`, program first
+    pub fn link_command(name: &str, destination: &str, options: &TargetOptions) -> Vec<String> {
+        let mut command = vec![
+            "zig".to_string(),
+            "cc".to_string(),
+            format!("-o{}{}", destination, name),
+            LLVMCodegen::object_file(name),
+        ];
+
+        let target = options.triple.as_ref();
+        if let Some(target) = target {
+            command.push("-target".to_string());
+            command.push(target.clone());
+        }
+
+        // Without crt0 or libc, the program's own entry point is linked in,
+        // usually placed by the linker script
+        if options.freestanding {
+            command.push("-nostdlib".to_string());
+            command.push("-ffreestanding".to_string());
+        }
+        if let Some(script) = &options.linker_script {
+            command.push(format!("-Wl,-T,{}", script));
+        }
+
+        // Drop functions and data nothing refers to. Mach-O objects are split
+        // per symbol already, ELF relies on the per-symbol sections from codegen
+        if LLVMCodegen::uses_macho(target) {
+            command.push("-Wl,-dead_strip".to_string());
+        } else if LLVMCodegen::uses_elf(target) {
+            command.push("-Wl,--gc-sections".to_string());
+        }
+
+        command
+    }
+
+    /// The triple used when no `--target` is given
+    pub fn default_target() -> String {
+        TargetMachine::get_default_triple()
+            .as_str()
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn uses_macho(target: Option<&String>) -> bool {
+        match target {
+            Some(target) => ["apple", "darwin", "macos", "ios"]
+                .iter()
+                .any(|os| target.contains(os)),
+            None => cfg!(target_vendor = "apple"),
+        }
+    }
+
+    fn uses_elf(target: Option<&String>) -> bool {
+        match target {
+            Some(target) => {
+                !LLVMCodegen::uses_macho(Some(target))
+                    && !target.contains("windows")
+                    && !target.contains("wasm")
+            }
+            None => !cfg!(target_vendor = "apple") && !cfg!(windows),
+        }
     }
-}
 
-impl<'a> LLVMCodegen<'a> {
     fn store_globals(&mut self, module: &Module<'a>) {
+        let c_string = self.context.i8_type().ptr_type(AddressSpace::Generic);
         for (i, str) in module.strings.clone().into_iter().enumerate() {
-            let ptr = self
-                .builder
-                .build_global_string_ptr(str.as_str(), "string")
-                .as_basic_value_enum();
-
-            self.strings.insert(i, ptr);
+            // The bytes are still NUL terminated so they can be passed to C
+            let bytes = self.context.const_string(str.as_bytes(), true);
+            let global = self.module.add_global(bytes.get_type(), None, "string");
+            global.set_initializer(&bytes);
+            global.set_constant(true);
+            global.set_linkage(Linkage::Private);
+            global.set_unnamed_addr(true);
+
+            let string = self.string_type().const_named_struct(&[
+                global.as_pointer_value().const_cast(c_string).into(),
+                self.context
+                    .i64_type()
+                    .const_int(str.len() as u64, false)
+                    .into(),
+            ]);
+
+            self.strings.insert(i, string.as_basic_value_enum());
             self.string_size.insert(i, str.len());
         }
 
+        let split_sections = LLVMCodegen::uses_elf(self.target.triple.as_ref());
         for (name, val) in &module.global_values {
+            let mutable = module.mutable_globals.contains(name);
+            let section = if mutable {
+                format!(".data.{}", name)
+            } else {
+                format!(".rodata.{}", name)
+            };
             let ptr = match val {
                 GlobalValue::Int(i) => {
                     let val = self.module.add_global(
@@ -203,6 +556,10 @@ impl<'a> LLVMCodegen<'a> {
                             .const_int((*i).try_into().unwrap(), false),
                     );
 
+                    val.set_constant(!mutable);
+                    if split_sections {
+                        val.set_section(&section);
+                    }
                     val.as_basic_value_enum()
                 }
                 GlobalValue::Float(f) => {
@@ -219,11 +576,15 @@ impl<'a> LLVMCodegen<'a> {
                             .const_float((*f).try_into().unwrap()),
                     );
 
+                    val.set_constant(!mutable);
+                    if split_sections {
+                        val.set_section(&section);
+                    }
                     val.as_basic_value_enum()
                 }
                 GlobalValue::Bool(b) => {
                     let val = self.module.add_global(
-                        self.context.f64_type(),
+                        self.context.bool_type(),
                         Some(AddressSpace::Global),
                         &name,
                     );
@@ -235,10 +596,56 @@ impl<'a> LLVMCodegen<'a> {
                             .const_int((*b).try_into().unwrap(), false),
                     );
 
+                    val.set_constant(!mutable);
+                    if split_sections {
+                        val.set_section(&section);
+                    }
+                    val.as_basic_value_enum()
+                }
+                GlobalValue::String(s) if mutable => {
+                    let string = self.strings.get(s).unwrap().into_struct_value();
+                    let val = self.module.add_global(
+                        string.get_type(),
+                        Some(AddressSpace::Global),
+                        &name,
+                    );
+
+                    val.set_initializer(&string);
+                    if split_sections {
+                        val.set_section(&section);
+                    }
+
                     val.as_basic_value_enum()
                 }
                 GlobalValue::String(s) => *self.strings.get(&s).unwrap(),
                 GlobalValue::Array(_) => todo!(),
+                // The method wrappers, as i8* in the interface's order
+                GlobalValue::Functions(functions) => {
+                    let pointer = self.context.i8_type().ptr_type(AddressSpace::Generic);
+                    let functions: Vec<_> = functions
+                        .iter()
+                        .map(|function| {
+                            self.module
+                                .get_function(function)
+                                .unwrap()
+                                .as_global_value()
+                                .as_pointer_value()
+                                .const_cast(pointer)
+                        })
+                        .collect();
+                    let val = self.module.add_global(
+                        pointer.array_type(functions.len() as u32),
+                        Some(AddressSpace::Global),
+                        &name,
+                    );
+
+                    val.set_initializer(&pointer.const_array(&functions));
+                    val.set_constant(true);
+                    if split_sections {
+                        val.set_section(&section);
+                    }
+                    val.as_basic_value_enum()
+                }
             };
 
             self.globals.insert(name.clone(), ptr);
@@ -267,6 +674,24 @@ impl<'a> LLVMCodegen<'a> {
         }
     }
 
+    // Variables can be first stored in any block, e.g. one branch of a
+    // ternary, so their allocas go at the top of the entry block where they
+    // dominate every use and aren't repeated by loops
+    fn build_entry_alloca(
+        &self,
+        func: &FunctionValue<'a>,
+        typ: BasicTypeEnum<'a>,
+    ) -> PointerValue<'a> {
+        let entry = func.get_first_basic_block().unwrap();
+        let builder = self.context.create_builder();
+        match entry.get_first_instruction() {
+            Some(first) => builder.position_before(&first),
+            None => builder.position_at_end(entry),
+        }
+
+        builder.build_alloca(typ, "alloca")
+    }
+
     fn codegen_instruction(
         &self,
         instruction: Instruction<'a>,
@@ -287,7 +712,19 @@ impl<'a> LLVMCodegen<'a> {
                 locals.store(dest, value);
             }
             Instruction::LoadArg(arg, dest, _) => {
-                locals.store(dest, func.get_params()[arg]);
+                // Arguments come after the sret pointer, and one in memory is
+                // read from its copy
+                let index = arg + returns_in_memory(&locals.memory_params) as usize;
+                let mut value = func.get_params()[index];
+                if locals
+                    .memory_params
+                    .iter()
+                    .any(|param| param.index as usize == index)
+                {
+                    value = self.builder.build_load(value.into_pointer_value(), "arg");
+                }
+
+                locals.store(dest, value);
             }
             Instruction::Store(name, val, typ) => {
                 let value = match val {
@@ -303,8 +740,7 @@ impl<'a> LLVMCodegen<'a> {
                             locals.variables.get(&name).unwrap().into_pointer_value()
                         } else {
                             let alloca = self
-                                .builder
-                                .build_alloca(self.azula_type_to_llvm_basic_type(typ), "alloca");
+                                .build_entry_alloca(func, self.azula_type_to_llvm_basic_type(typ));
                             locals.variables.insert(name, alloca.as_basic_value_enum());
 
                             alloca
@@ -320,15 +756,23 @@ impl<'a> LLVMCodegen<'a> {
                 let alloca = if locals.variables.contains_key(&name) {
                     locals.variables.get(&name).unwrap().into_pointer_value()
                 } else {
-                    let alloca = self
-                        .builder
-                        .build_alloca(self.azula_type_to_llvm_basic_type(typ), "alloca");
+                    let alloca =
+                        self.build_entry_alloca(func, self.azula_type_to_llvm_basic_type(typ));
                     locals.variables.insert(name, alloca.as_basic_value_enum());
 
                     alloca
                 };
                 self.builder.build_store(alloca, value);
             }
+            Instruction::StoreGlobal(name, val) => {
+                let value = match val {
+                    Value::Global(val) => self.strings.get(&val).unwrap().as_basic_value_enum(),
+                    _ => locals.load(value_to_local(val)),
+                };
+                let global = self.globals.get(&name).unwrap().into_pointer_value();
+
+                self.builder.build_store(global, value);
+            }
             Instruction::ConstInt(val, dest) => {
                 locals.registers.insert(
                     dest,
@@ -378,16 +822,29 @@ impl<'a> LLVMCodegen<'a> {
             Instruction::Add(..) => self.codegen_add(instruction, locals),
             Instruction::Sub(..) => self.codegen_sub(instruction, locals),
             Instruction::Mul(..) => self.codegen_mul(instruction, locals),
-            Instruction::Div(..) => self.codegen_div(instruction, locals),
-            Instruction::Mod(..) => self.codegen_mod(instruction, locals),
-            Instruction::Pow(..) => self.codegen_pow(instruction, locals),
+            Instruction::Div(..) | Instruction::UDiv(..) => self.codegen_div(instruction, locals),
+            Instruction::Mod(..) | Instruction::URem(..) => self.codegen_mod(instruction, locals),
+            Instruction::Pow(..) => self.codegen_pow(instruction, func, locals),
+            Instruction::Shl(..) | Instruction::AShr(..) | Instruction::LShr(..) => {
+                self.codegen_shift(instruction, locals)
+            }
+            Instruction::UGt(..)
+            | Instruction::UGte(..)
+            | Instruction::ULt(..)
+            | Instruction::ULte(..) => self.codegen_unsigned_compare(instruction, locals),
             Instruction::Return(val) => match val {
                 None => {
                     self.builder.build_return(None);
                 }
                 Some(Value::Local(val)) => {
-                    self.builder
-                        .build_return(Some(locals.registers.get(&val).clone().unwrap()));
+                    let value = *locals.registers.get(&val).unwrap();
+                    if returns_in_memory(&locals.memory_params) {
+                        let slot = func.get_first_param().unwrap().into_pointer_value();
+                        self.builder.build_store(slot, value);
+                        self.builder.build_return(None);
+                    } else {
+                        self.builder.build_return(Some(&value));
+                    }
                 }
                 Some(Value::Global(val)) => {
                     self.builder
@@ -396,42 +853,53 @@ impl<'a> LLVMCodegen<'a> {
                 _ => unreachable!(),
             },
             Instruction::FunctionCall(name, args, dest) => {
-                let converted_args: Vec<BasicMetadataValueEnum> = args
+                let function = self.module.get_function(&name).unwrap();
+                let params = function.get_type().get_param_types();
+                let memory_params = self.memory_params(&name);
+                let sret = returns_in_memory(&memory_params);
+                let converted_args: Vec<BasicValueEnum> = args
                     .iter()
-                    .map(|arg| match arg {
-                        Value::Global(i) => {
-                            self.strings.get(i).unwrap().as_basic_value_enum().into()
+                    .enumerate()
+                    .map(|(i, arg)| {
+                        let value = match arg {
+                            Value::Global(pos) => *self.strings.get(pos).unwrap(),
+                            Value::Local(..) => locals.load(value_to_local(arg.clone())),
+                            Value::LiteralInteger(_) => todo!(),
+                            Value::LiteralBoolean(_) => todo!(),
+                        };
+                        // Strings going to C, declared or through varargs like
+                        // printf's, are passed as just their bytes
+                        match params.get(i + sret as usize) {
+                            Some(param) if !param.is_pointer_type() => value,
+                            _ => self.c_argument(value),
                         }
-                        Value::Local(..) => locals.load(value_to_local(arg.clone())).into(),
-                        Value::LiteralInteger(_) => todo!(),
-                        Value::LiteralBoolean(_) => todo!(),
                     })
                     .collect();
 
-                let result = self.builder.build_call(
-                    self.module.get_function(&name).unwrap(),
-                    &converted_args,
-                    "call",
-                );
-
-                let result = result.try_as_basic_value();
+                let result =
+                    self.build_abi_call(function.into(), converted_args, &memory_params, func);
 
-                if result.is_left() {
-                    locals.store(dest, result.unwrap_left().as_basic_value_enum());
+                if let Some(mut value) = result {
+                    if self.c_strings.contains(&name) {
+                        value = self.from_c_string(value.into_pointer_value());
+                    }
+                    locals.store(dest, value);
                 }
             }
             Instruction::Jcond(cond, true_block_name, end_block_name) => {
                 let local = locals.load(value_to_local(cond)).into_int_value();
 
-                let true_block = self
-                    .context
-                    .append_basic_block(*func, true_block_name.clone().as_str());
-                let end_block = self
-                    .context
-                    .append_basic_block(*func, end_block_name.clone().as_str());
-
-                locals.blocks.insert(true_block_name.clone(), true_block);
-                locals.blocks.insert(end_block_name.clone(), end_block);
+                // A do while's condition branches back to a body already seen
+                let mut block = |name: &String| match locals.blocks.get(name) {
+                    Some(block) => *block,
+                    None => {
+                        let block = self.context.append_basic_block(*func, name);
+                        locals.blocks.insert(name.clone(), block);
+                        block
+                    }
+                };
+                let true_block = block(true_block_name);
+                let end_block = block(end_block_name);
 
                 self.builder
                     .build_conditional_branch(local, true_block, end_block);
@@ -520,6 +988,32 @@ impl<'a> LLVMCodegen<'a> {
                     self.builder.position_at_end(jump_block);
                 }
             }
+            Instruction::Switch(val, cases, default) => {
+                let value = locals.load(value_to_local(val)).into_int_value();
+                let mut block = |name: &String| match locals.blocks.get(name) {
+                    Some(block) => *block,
+                    None => {
+                        let block = self.context.append_basic_block(*func, name);
+                        locals.blocks.insert(name.clone(), block);
+                        block
+                    }
+                };
+
+                let default = block(&default);
+                let cases: Vec<_> = cases
+                    .iter()
+                    .map(|(case, name)| (*case, block(name)))
+                    .collect();
+                if locals.jump_tables && LLVMCodegen::is_dense(&cases) {
+                    self.build_jump_table(value, &cases, default, func);
+                } else {
+                    let cases: Vec<_> = cases
+                        .into_iter()
+                        .map(|(case, block)| (value.get_type().const_int(case as u64, true), block))
+                        .collect();
+                    self.builder.build_switch(value, default, &cases);
+                }
+            }
             Instruction::Gt(val1, val2, dest) => {
                 let local1 = locals.load(value_to_local(val1));
                 let local2 = locals.load(value_to_local(val2));
@@ -638,11 +1132,47 @@ impl<'a> LLVMCodegen<'a> {
 
                 locals.store(dest, value.as_basic_value_enum());
             }
+            Instruction::Neg(val, dest) => {
+                let local = locals.load(value_to_local(val));
+
+                let value = match local.get_type() {
+                    BasicTypeEnum::FloatType(_) => self
+                        .builder
+                        .build_float_neg(local.into_float_value(), "neg")
+                        .as_basic_value_enum(),
+                    BasicTypeEnum::IntType(_) => self
+                        .builder
+                        .build_int_neg(local.into_int_value(), "neg")
+                        .as_basic_value_enum(),
+                    _ => unreachable!(),
+                };
+
+                locals.store(dest, value);
+            }
             Instruction::Pointer(val, dest) => {
-                let alloca = locals.variables.get(&val).unwrap().clone();
+                let alloca = match locals.variables.get(&val) {
+                    Some(alloca) => alloca.clone(),
+                    None => self.globals.get(&val).unwrap().clone(),
+                };
 
                 locals.store(dest, alloca.as_basic_value_enum());
             }
+            Instruction::Deref(ptr, _, dest) => {
+                let ptr = self.pointer_of(locals.load(value_to_local(ptr)));
+                let value = self.builder.build_load(ptr, "deref");
+
+                locals.store(dest, value);
+            }
+            Instruction::StoreDeref(ptr, val) => {
+                let ptr = locals.load(value_to_local(ptr)).into_pointer_value();
+                let value = match val {
+                    Value::Local(..) => locals.load(value_to_local(val)),
+                    Value::Global(pos) => self.strings.get(&pos).unwrap().as_basic_value_enum(),
+                    _ => unreachable!(),
+                };
+
+                self.builder.build_store(ptr, value);
+            }
             Instruction::CreateArray(typ, size, dest) => {
                 // let alloca = self.builder.build_alloca(
                 //     self.azula_type_to_llvm_basic_type(typ)
@@ -683,7 +1213,7 @@ impl<'a> LLVMCodegen<'a> {
 
                 let ptr = unsafe {
                     self.builder
-                        .build_in_bounds_gep(array.into_pointer_value(), &[index], "gep")
+                        .build_in_bounds_gep(self.pointer_of(array), &[index], "gep")
                 };
 
                 self.builder.build_store(ptr, val);
@@ -704,13 +1234,24 @@ impl<'a> LLVMCodegen<'a> {
 
                 let ptr = unsafe {
                     self.builder
-                        .build_in_bounds_gep(array.into_pointer_value(), &[index], "gep")
+                        .build_in_bounds_gep(self.pointer_of(array), &[index], "gep")
                 };
 
                 let result = self.builder.build_load(ptr, "access");
 
                 locals.store(dest, result.as_basic_value_enum());
             }
+            Instruction::ElementPointer(array, index, dest) => {
+                let array = locals.load(value_to_local(array));
+                let index = locals.load(value_to_local(index)).into_int_value();
+
+                let ptr = unsafe {
+                    self.builder
+                        .build_in_bounds_gep(self.pointer_of(array), &[index], "gep")
+                };
+
+                locals.store(dest, ptr.as_basic_value_enum());
+            }
             Instruction::StoreStructMember(struc, index, val) => {
                 let val = match val {
                     Value::Local(ptr) => locals.load(ptr),
@@ -772,115 +1313,914 @@ impl<'a> LLVMCodegen<'a> {
 
                 locals.store(dest, agg.as_basic_value_enum());
             }
-            Instruction::AccessStructMember(struc, index, dest, resolve) => {
-                let struc = locals.load(value_to_local(struc));
-
-                if struc.is_struct_value() {
-                    let val = self
-                        .builder
-                        .build_extract_value(struc.into_struct_value(), index as u32, "val")
-                        .unwrap();
+            Instruction::MakeOptional(val, typ, dest) => {
+                let optional = self.optional_type(typ).get_undef();
+                let present = self
+                    .context
+                    .bool_type()
+                    .const_int(val.is_some() as u64, false);
+                let mut optional = self
+                    .builder
+                    .build_insert_value(optional, present, 0, "present")
+                    .unwrap()
+                    .into_struct_value();
 
-                    locals.store(dest, val.as_basic_value_enum());
-                } else if struc.is_pointer_value() {
-                    let ptr = unsafe {
-                        self.builder.build_in_bounds_gep(
-                            struc.into_pointer_value(),
-                            &[
-                                self.context.i32_type().const_int(0, false),
-                                self.context.i32_type().const_int(index as u64, false),
-                            ],
-                            "gep",
-                        )
+                if let Some(val) = val {
+                    let val = match val {
+                        Value::Global(v) => *self.strings.get(&v).unwrap(),
+                        _ => locals.load(value_to_local(val)),
                     };
+                    optional = self
+                        .builder
+                        .build_insert_value(optional, val, 1, "value")
+                        .unwrap()
+                        .into_struct_value();
+                }
 
-                    if resolve {
-                        let val = self.builder.build_load(ptr, "load");
-                        locals.store(dest, val.as_basic_value_enum());
-                    } else {
-                        locals.store(dest, ptr.as_basic_value_enum());
+                locals.store(dest, optional.as_basic_value_enum());
+            }
+            Instruction::MakeResult(val, error, typ, dest) => {
+                let result = self.result_type(typ).get_undef();
+                let ok = self
+                    .context
+                    .bool_type()
+                    .const_int(error.is_none() as u64, false);
+                let mut result = self
+                    .builder
+                    .build_insert_value(result, ok, 0, "ok")
+                    .unwrap()
+                    .into_struct_value();
+
+                for (index, val) in [(1, error), (2, val)] {
+                    if let Some(val) = val {
+                        let val = match val {
+                            Value::Global(v) => *self.strings.get(&v).unwrap(),
+                            _ => locals.load(value_to_local(val)),
+                        };
+                        result = self
+                            .builder
+                            .build_insert_value(result, val, index, "insert")
+                            .unwrap()
+                            .into_struct_value();
                     }
                 }
+
+                locals.store(dest, result.as_basic_value_enum());
             }
-        };
-    }
+            Instruction::FunctionPointer(name, dest) => {
+                let function = self.module.get_function(&name).unwrap();
+                let pointer = self.builder.build_bitcast(
+                    function.as_global_value().as_pointer_value(),
+                    self.context.i8_type().ptr_type(AddressSpace::Generic),
+                    "fnptr",
+                );
 
-    fn codegen_add(&self, instruction: Instruction<'a>, locals: &mut FunctionLocals<'a>) {
-        if let Instruction::Add(val1, val2, dest) = instruction {
-            let local1 = locals.load(value_to_local(val1));
-            let local2 = locals.load(value_to_local(val2));
+                locals.store(dest, pointer);
+            }
+            // The captures are copied to the heap so the closure can outlive
+            // the function that made it
+            Instruction::MakeClosure(name, env, env_type, dest) => {
+                let pointer = self.context.i8_type().ptr_type(AddressSpace::Generic);
 
-            let value = match local1.get_type() {
-                BasicTypeEnum::FloatType(_) => self
-                    .builder
-                    .build_float_add(local1.into_float_value(), local2.into_float_value(), "add")
-                    .as_basic_value_enum(),
-                BasicTypeEnum::IntType(_) => self
+                let env_value = locals.load(value_to_local(env));
+                let env_alloc = self
                     .builder
-                    .build_int_add(local1.into_int_value(), local2.into_int_value(), "add")
+                    .build_malloc(self.azula_type_to_llvm_basic_type(env_type), "env")
+                    .unwrap();
+                self.builder.build_store(env_alloc, env_value);
+
+                let function = self.module.get_function(&name).unwrap();
+                let fields = [
+                    self.builder.build_bitcast(
+                        function.as_global_value().as_pointer_value(),
+                        pointer,
+                        "fnptr",
+                    ),
+                    self.builder.build_bitcast(env_alloc, pointer, "envptr"),
+                ];
+
+                let mut closure = self.closure_type().get_undef();
+                for (index, field) in fields.iter().enumerate() {
+                    closure = self
+                        .builder
+                        .build_insert_value(closure, *field, index as u32, "insert")
+                        .unwrap()
+                        .into_struct_value();
+                }
+
+                locals.store(dest, closure.as_basic_value_enum());
+            }
+            // The value is copied to the heap like a closure's env, so an
+            // interface value is the same size whatever type it holds
+            Instruction::MakeInterface(vtable, env, env_type, dest) => {
+                let pointer = self.context.i8_type().ptr_type(AddressSpace::Generic);
+
+                let env_value = locals.load(value_to_local(env));
+                let env_alloc = self
+                    .builder
+                    .build_malloc(self.azula_type_to_llvm_basic_type(env_type), "env")
+                    .unwrap();
+                self.builder.build_store(env_alloc, env_value);
+
+                let vtable = self.globals.get(&vtable).unwrap().into_pointer_value();
+                let fields = [
+                    self.builder.build_bitcast(env_alloc, pointer, "envptr"),
+                    self.builder.build_bitcast(vtable, pointer, "vtable"),
+                ];
+
+                let mut value = self.interface_type().get_undef();
+                for (index, field) in fields.iter().enumerate() {
+                    value = self
+                        .builder
+                        .build_insert_value(value, *field, index as u32, "insert")
+                        .unwrap()
+                        .into_struct_value();
+                }
+
+                locals.store(dest, value.as_basic_value_enum());
+            }
+            // A method is its wrapper from the vtable, as a closure over the
+            // value's env
+            Instruction::InterfaceMethod(value, index, dest) => {
+                let pointer = self.context.i8_type().ptr_type(AddressSpace::Generic);
+
+                let value = locals.load(value_to_local(value)).into_struct_value();
+                let env = self.builder.build_extract_value(value, 0, "env").unwrap();
+                let vtable = self
+                    .builder
+                    .build_extract_value(value, 1, "vtable")
+                    .unwrap();
+                let vtable = self
+                    .builder
+                    .build_bitcast(vtable, pointer.ptr_type(AddressSpace::Generic), "vtable")
+                    .into_pointer_value();
+
+                let index = self.context.i64_type().const_int(index as u64, false);
+                let function =
+                    unsafe { self.builder.build_in_bounds_gep(vtable, &[index], "method") };
+                let function = self.builder.build_load(function, "fnptr");
+
+                let mut closure = self.closure_type().get_undef();
+                for (index, field) in [function, env].iter().enumerate() {
+                    closure = self
+                        .builder
+                        .build_insert_value(closure, *field, index as u32, "insert")
+                        .unwrap()
+                        .into_struct_value();
+                }
+
+                locals.store(dest, closure.as_basic_value_enum());
+            }
+            Instruction::CallClosure(closure, args, typ, dest) => {
+                let (params, returns) = match typ {
+                    AzulaType::Function(params, returns) => (params, returns.deref().clone()),
+                    _ => unreachable!(),
+                };
+                let pointer = self.context.i8_type().ptr_type(AddressSpace::Generic);
+
+                let closure = locals.load(value_to_local(closure)).into_struct_value();
+                let function = self
+                    .builder
+                    .build_extract_value(closure, 0, "fnptr")
+                    .unwrap();
+                let env = self.builder.build_extract_value(closure, 1, "env").unwrap();
+
+                // The env goes first, typed as i8* rather than the lifted
+                // function's own env tuple
+                let mut param_types: Vec<BasicMetadataTypeEnum> = vec![pointer.into()];
+                param_types.extend(
+                    params
+                        .into_iter()
+                        .map(|typ| self.azula_type_to_llvm_basic_type(typ).into()),
+                );
+                let function_type = self.azula_type_to_function_llvm_type(returns, &param_types);
+                let (function_type, memory_params) = self.abi_function_type(function_type);
+                let function = self
+                    .builder
+                    .build_bitcast(
+                        function,
+                        function_type.ptr_type(AddressSpace::Generic),
+                        "fn",
+                    )
+                    .into_pointer_value();
+
+                let mut converted_args = vec![env];
+                converted_args.extend(args.iter().map(|arg| match arg {
+                    Value::Global(i) => *self.strings.get(i).unwrap(),
+                    _ => locals.load(value_to_local(arg.clone())),
+                }));
+
+                let result = self.build_abi_call(
+                    CallableValue::try_from(function).unwrap(),
+                    converted_args,
+                    &memory_params,
+                    func,
+                );
+
+                if let Some(result) = result {
+                    locals.store(dest, result);
+                }
+            }
+            // Tuples are anonymous structs built up by value
+            Instruction::CreateTuple(values, dest) => {
+                let vals: Vec<_> = values
+                    .iter()
+                    .map(|val| match val {
+                        Value::Global(v) => *self.strings.get(v).unwrap(),
+                        _ => locals.load(value_to_local(val.clone())),
+                    })
+                    .collect();
+
+                let types: Vec<_> = vals.iter().map(|val| val.get_type()).collect();
+                let mut agg = self.context.struct_type(&types, false).get_undef();
+                for (index, val) in vals.iter().enumerate() {
+                    agg = self
+                        .builder
+                        .build_insert_value(agg, *val, index as u32, "insert")
+                        .unwrap()
+                        .into_struct_value();
+                }
+
+                locals.store(dest, agg.as_basic_value_enum());
+            }
+            Instruction::AccessStructMember(struc, index, dest, resolve) => {
+                let struc = locals.load(value_to_local(struc));
+
+                if struc.is_struct_value() {
+                    let val = self
+                        .builder
+                        .build_extract_value(struc.into_struct_value(), index as u32, "val")
+                        .unwrap();
+
+                    locals.store(dest, val.as_basic_value_enum());
+                } else if struc.is_pointer_value() {
+                    let ptr = unsafe {
+                        self.builder.build_in_bounds_gep(
+                            struc.into_pointer_value(),
+                            &[
+                                self.context.i32_type().const_int(0, false),
+                                self.context.i32_type().const_int(index as u64, false),
+                            ],
+                            "gep",
+                        )
+                    };
+
+                    if resolve {
+                        let val = self.builder.build_load(ptr, "load");
+                        locals.store(dest, val.as_basic_value_enum());
+                    } else {
+                        locals.store(dest, ptr.as_basic_value_enum());
+                    }
+                }
+            }
+            Instruction::MakeAny(..) | Instruction::AnyTag(..) | Instruction::AnyPayload(..) => {
+                self.codegen_any(instruction, locals)
+            }
+            Instruction::Cast(..) => self.codegen_cast(instruction, locals),
+            Instruction::HeapAlloc(typ, dest) => {
+                let typ = self.azula_type_to_llvm_basic_type(typ);
+                let pointer = self.builder.build_malloc(typ, "new").unwrap();
+
+                locals.store(dest, pointer.as_basic_value_enum());
+            }
+            Instruction::Free(val) => {
+                let pointer = self.pointer_of(locals.load(value_to_local(val)));
+                self.builder.build_free(pointer);
+            }
+            Instruction::FreeClosure(closure) => {
+                let closure = locals.load(value_to_local(closure)).into_struct_value();
+                let env = self.builder.build_extract_value(closure, 1, "env").unwrap();
+                self.builder.build_free(env.into_pointer_value());
+            }
+            Instruction::VolatileLoad(ptr, _, dest) => {
+                let ptr = self.pointer_of(locals.load(value_to_local(ptr)));
+                let value = self.builder.build_load(ptr, "volatile");
+                value
+                    .as_instruction_value()
+                    .unwrap()
+                    .set_volatile(true)
+                    .unwrap();
+
+                locals.store(dest, value);
+            }
+            Instruction::VolatileStore(ptr, val) => {
+                let ptr = self.pointer_of(locals.load(value_to_local(ptr)));
+                let value = match val {
+                    Value::Local(..) => locals.load(value_to_local(val)),
+                    Value::Global(pos) => *self.strings.get(&pos).unwrap(),
+                    _ => unreachable!(),
+                };
+
+                self.builder
+                    .build_store(ptr, value)
+                    .set_volatile(true)
+                    .unwrap();
+            }
+            Instruction::UnalignedLoad(ptr, _, dest) => {
+                let ptr = self.pointer_of(locals.load(value_to_local(ptr)));
+                let value = self.builder.build_load(ptr, "unaligned");
+                value
+                    .as_instruction_value()
+                    .unwrap()
+                    .set_alignment(1)
+                    .unwrap();
+
+                locals.store(dest, value);
+            }
+            Instruction::UnalignedStore(ptr, val) => {
+                let ptr = self.pointer_of(locals.load(value_to_local(ptr)));
+                let value = match val {
+                    Value::Local(..) => locals.load(value_to_local(val)),
+                    Value::Global(pos) => *self.strings.get(&pos).unwrap(),
+                    _ => unreachable!(),
+                };
+
+                self.builder
+                    .build_store(ptr, value)
+                    .set_alignment(1)
+                    .unwrap();
+            }
+            Instruction::ByteOrder(val, big, dest) => {
+                let int = locals.load(value_to_local(val)).into_int_value();
+                let width = int.get_type().get_bit_width();
+                let target_big = self.target_data.as_ref().unwrap().get_byte_ordering()
+                    == ByteOrdering::BigEndian;
+
+                // A single byte has no order to swap
+                let value = if big == target_big || width == 8 {
+                    int.as_basic_value_enum()
+                } else {
+                    let name = format!("llvm.bswap.i{}", width);
+                    let bswap = self.module.get_function(&name).unwrap_or_else(|| {
+                        self.module.add_function(
+                            &name,
+                            int.get_type().fn_type(&[int.get_type().into()], false),
+                            None,
+                        )
+                    });
+                    self.builder
+                        .build_call(bswap, &[int.into()], "bswap")
+                        .try_as_basic_value()
+                        .unwrap_left()
+                };
+
+                locals.store(dest, value);
+            }
+            Instruction::StringLen(val, dest) => {
+                let string = match val {
+                    Value::Global(name) => *self.strings.get(&name).unwrap(),
+                    _ => locals.load(value_to_local(val)),
+                };
+                let length = self
+                    .builder
+                    .build_extract_value(string.into_struct_value(), 1, "len")
+                    .unwrap();
+
+                locals.store(dest, length);
+            }
+            Instruction::SizeOf(typ, dest) => {
+                let typ = self.azula_type_to_llvm_basic_type(typ);
+                let size = self.target_data.as_ref().unwrap().get_abi_size(&typ);
+
+                locals.store(
+                    dest,
+                    self.context
+                        .i64_type()
+                        .const_int(size, false)
+                        .as_basic_value_enum(),
+                );
+            }
+        };
+    }
+
+    // Handlers save every register they use and return with the target's
+    // interrupt return, which LLVM does for the interrupt attribute on ARM and
+    // RISC-V and for calling conventions of their own on AVR and MSP430
+    fn set_interrupt(&self, function: FunctionValue<'a>) -> Result<(), String> {
+        let triple = self
+            .target
+            .triple
+            .clone()
+            .unwrap_or_else(LLVMCodegen::default_target);
+        let arch = triple.split('-').next().unwrap_or_default();
+
+        if arch.starts_with("arm") || arch.starts_with("thumb") {
+            let attribute = self.context.create_string_attribute("interrupt", "IRQ");
+            function.add_attribute(AttributeLoc::Function, attribute);
+        } else if arch.starts_with("riscv") {
+            let attribute = self.context.create_string_attribute("interrupt", "machine");
+            function.add_attribute(AttributeLoc::Function, attribute);
+        } else if arch == "avr" {
+            function.set_call_conventions(AVR_INTERRUPT_CALL_CONV);
+        } else if arch == "msp430" {
+            function.set_call_conventions(MSP430_INTERRUPT_CALL_CONV);
+        } else {
+            return Err(format!(
+                "@interrupt isn't supported when targeting {}",
+                triple
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn enum_attribute(&self, name: &str) -> Attribute {
+        let kind = Attribute::get_named_enum_kind_id(name);
+        self.context.create_enum_attribute(kind, 0)
+    }
+
+    fn type_attribute(&self, name: &str, typ: BasicTypeEnum<'a>) -> Attribute {
+        let kind = Attribute::get_named_enum_kind_id(name);
+        self.context
+            .create_type_attribute(kind, typ.as_any_type_enum())
+    }
+
+    // Structs bigger than two registers go through memory in the C calling
+    // conventions, as a pointer to a copy for the callee (byval) or to space
+    // the caller sets aside for the result (sret). Smaller ones are passed
+    // as values
+    fn in_memory(&self, typ: BasicTypeEnum<'a>) -> bool {
+        match typ {
+            BasicTypeEnum::StructType(struc) if struc.get_name().is_some() => {
+                self.target_data.as_ref().unwrap().get_abi_size(&struc) > 16
+            }
+            _ => false,
+        }
+    }
+
+    // The type a function is declared and called with, with its structs in
+    // memory turned into pointers
+    fn abi_function_type(&self, typ: FunctionType<'a>) -> (FunctionType<'a>, Vec<MemoryParam<'a>>) {
+        let mut params: Vec<BasicMetadataTypeEnum> = vec![];
+        let mut memory_params = vec![];
+        let returns = typ.get_return_type();
+        let sret = returns.filter(|returns| self.in_memory(*returns));
+        if let Some(returns) = sret {
+            memory_params.push(MemoryParam {
+                index: 0,
+                attribute: "sret",
+                typ: returns,
+            });
+            params.push(returns.ptr_type(AddressSpace::Generic).into());
+        }
+        for param in typ.get_param_types() {
+            if self.in_memory(param) {
+                memory_params.push(MemoryParam {
+                    index: params.len() as u32,
+                    attribute: "byval",
+                    typ: param,
+                });
+                params.push(param.ptr_type(AddressSpace::Generic).into());
+            } else {
+                params.push(param.into());
+            }
+        }
+
+        let typ = match returns {
+            Some(returns) if sret.is_none() => returns.fn_type(&params, typ.is_var_arg()),
+            _ => self.context.void_type().fn_type(&params, typ.is_var_arg()),
+        };
+        (typ, memory_params)
+    }
+
+    fn add_abi_function(
+        &mut self,
+        name: &str,
+        typ: FunctionType<'a>,
+        linkage: Option<Linkage>,
+    ) -> FunctionValue<'a> {
+        let (typ, memory_params) = self.abi_function_type(typ);
+        let function = self.module.add_function(name, typ, linkage);
+        for param in &memory_params {
+            let attribute = self.type_attribute(param.attribute, param.typ);
+            function.add_attribute(AttributeLoc::Param(param.index), attribute);
+        }
+        if !memory_params.is_empty() {
+            self.memory_params.insert(name.to_string(), memory_params);
+        }
+
+        function
+    }
+
+    fn memory_params(&self, function: &str) -> Vec<MemoryParam<'a>> {
+        self.memory_params
+            .get(function)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    // Copies the structs a call passes in memory to the stack, and reads
+    // back a result returned there
+    fn build_abi_call(
+        &self,
+        function: CallableValue<'a>,
+        mut args: Vec<BasicValueEnum<'a>>,
+        memory_params: &[MemoryParam<'a>],
+        func: &FunctionValue<'a>,
+    ) -> Option<BasicValueEnum<'a>> {
+        let mut slot = None;
+        for param in memory_params {
+            let memory = self.build_entry_alloca(func, param.typ);
+            if param.attribute == "sret" {
+                args.insert(0, memory.as_basic_value_enum());
+                slot = Some(memory);
+            } else {
+                let index = param.index as usize;
+                self.builder.build_store(memory, args[index]);
+                args[index] = memory.as_basic_value_enum();
+            }
+        }
+
+        let args: Vec<BasicMetadataValueEnum> = args.into_iter().map(Into::into).collect();
+        let call = self.builder.build_call(function, &args, "call");
+        for param in memory_params {
+            let attribute = self.type_attribute(param.attribute, param.typ);
+            call.add_attribute(AttributeLoc::Param(param.index), attribute);
+        }
+
+        match slot {
+            Some(slot) => Some(self.builder.build_load(slot, "result")),
+            None => call.try_as_basic_value().left(),
+        }
+    }
+
+    // Worth a table when most of the values between the smallest and largest
+    // case have one
+    fn is_dense(cases: &[(i64, BasicBlock)]) -> bool {
+        let min = cases.iter().map(|(case, _)| *case).min();
+        let max = cases.iter().map(|(case, _)| *case).max();
+        match (min, max) {
+            (Some(min), Some(max)) => (max - min) as u128 + 1 <= cases.len() as u128 * 4,
+            _ => false,
+        }
+    }
+
+    // Computed goto: values outside the table go to the default, the rest
+    // load their block's address from a constant array and branch to it
+    fn build_jump_table(
+        &self,
+        value: IntValue<'a>,
+        cases: &[(i64, BasicBlock<'a>)],
+        default: BasicBlock<'a>,
+        func: &FunctionValue<'a>,
+    ) {
+        let min = cases.iter().map(|(case, _)| *case).min().unwrap();
+        let max = cases.iter().map(|(case, _)| *case).max().unwrap();
+        let size = (max - min) as u64 + 1;
+
+        let typ = value.get_type();
+        let index = self
+            .builder
+            .build_int_sub(value, typ.const_int(min as u64, true), "index");
+        let in_range = self.builder.build_int_compare(
+            IntPredicate::ULT,
+            index,
+            typ.const_int(size, false),
+            "in_range",
+        );
+        let table_block = self.context.append_basic_block(*func, "jumptable");
+        self.builder
+            .build_conditional_branch(in_range, table_block, default);
+        self.builder.position_at_end(table_block);
+
+        let target = |case: i64| {
+            cases
+                .iter()
+                .find(|(other, _)| *other == case)
+                .map_or(default, |(_, block)| *block)
+        };
+        let addresses: Vec<_> = (min..=max)
+            .map(|case| unsafe { target(case).get_address() }.unwrap())
+            .collect();
+        let i8_ptr = self.context.i8_type().ptr_type(AddressSpace::Generic);
+        let table = self
+            .module
+            .add_global(i8_ptr.array_type(size as u32), None, "jumptable");
+        table.set_initializer(&i8_ptr.const_array(&addresses));
+        table.set_constant(true);
+        table.set_linkage(Linkage::Private);
+
+        let i64_type = self.context.i64_type();
+        let index = self
+            .builder
+            .build_int_z_extend_or_bit_cast(index, i64_type, "index");
+        let slot = unsafe {
+            self.builder.build_in_bounds_gep(
+                table.as_pointer_value(),
+                &[i64_type.const_zero(), index],
+                "slot",
+            )
+        };
+        let address = self.builder.build_load(slot, "address");
+
+        let mut destinations = vec![default];
+        for (_, block) in cases {
+            if !destinations.contains(block) {
+                destinations.push(*block);
+            }
+        }
+        self.builder.build_indirect_branch(address, &destinations);
+    }
+
+    fn codegen_cast(&self, instruction: Instruction<'a>, locals: &mut FunctionLocals<'a>) {
+        if let Instruction::Cast(val, from, to, dest) = instruction {
+            let value = locals.load(value_to_local(val));
+            let target = self.azula_type_to_llvm_basic_type(to.clone());
+
+            // Bools widen with zero extension like unsigned ints
+            let signed =
+                |typ: &AzulaType| !matches!(typ, AzulaType::SizedUnsignedInt(_) | AzulaType::Bool);
+
+            let result = match (value, target) {
+                (BasicValueEnum::IntValue(int), BasicTypeEnum::IntType(int_type)) => {
+                    if int_type.get_bit_width() > int.get_type().get_bit_width() {
+                        if signed(&from) {
+                            self.builder.build_int_s_extend(int, int_type, "sext")
+                        } else {
+                            self.builder.build_int_z_extend(int, int_type, "zext")
+                        }
+                    } else {
+                        self.builder
+                            .build_int_truncate_or_bit_cast(int, int_type, "trunc")
+                    }
+                    .as_basic_value_enum()
+                }
+                (BasicValueEnum::IntValue(int), BasicTypeEnum::FloatType(float_type)) => {
+                    if signed(&from) {
+                        self.builder
+                            .build_signed_int_to_float(int, float_type, "sitofp")
+                    } else {
+                        self.builder
+                            .build_unsigned_int_to_float(int, float_type, "uitofp")
+                    }
+                    .as_basic_value_enum()
+                }
+                (BasicValueEnum::FloatValue(float), BasicTypeEnum::IntType(int_type)) => {
+                    if signed(&to) {
+                        self.builder
+                            .build_float_to_signed_int(float, int_type, "fptosi")
+                    } else {
+                        self.builder
+                            .build_float_to_unsigned_int(float, int_type, "fptoui")
+                    }
+                    .as_basic_value_enum()
+                }
+                (BasicValueEnum::FloatValue(float), BasicTypeEnum::FloatType(float_type)) => self
+                    .builder
+                    .build_float_cast(float, float_type, "fpcast")
                     .as_basic_value_enum(),
-                _ => unreachable!(),
+                _ => unreachable!("cast from {:?} to {:?}", from, to),
             };
 
-            locals.store(dest, value);
+            locals.store(dest, result);
         }
     }
 
-    fn codegen_sub(&self, instruction: Instruction<'a>, locals: &mut FunctionLocals<'a>) {
-        if let Instruction::Sub(val1, val2, dest) = instruction {
-            let local1 = locals.load(value_to_local(val1));
-            let local2 = locals.load(value_to_local(val2));
+    // An any is a { tag, payload } pair with the payload's bits stored in an i64
+    fn codegen_any(&self, instruction: Instruction<'a>, locals: &mut FunctionLocals<'a>) {
+        let i64_type = self.context.i64_type();
 
-            let value = match local1.get_type() {
-                BasicTypeEnum::FloatType(_) => self
+        match instruction {
+            Instruction::MakeAny(val, tag, dest) => {
+                let val = match val {
+                    Value::Global(name) => self.strings.get(&name).unwrap().as_basic_value_enum(),
+                    _ => locals.load(value_to_local(val)),
+                };
+                // Only a string's bytes fit in the payload, its length is
+                // measured again when it's taken back out
+                let val = self.c_argument(val);
+
+                let payload = match val {
+                    BasicValueEnum::IntValue(int) => self
+                        .builder
+                        .build_int_z_extend_or_bit_cast(int, i64_type, "payload"),
+                    BasicValueEnum::FloatValue(float) => self
+                        .builder
+                        .build_bitcast(float, i64_type, "payload")
+                        .into_int_value(),
+                    BasicValueEnum::PointerValue(ptr) => {
+                        self.builder.build_ptr_to_int(ptr, i64_type, "payload")
+                    }
+                    _ => unreachable!(),
+                };
+
+                let agg = self.any_type().get_undef();
+                let agg = self
                     .builder
-                    .build_float_sub(local1.into_float_value(), local2.into_float_value(), "add")
-                    .as_basic_value_enum(),
-                BasicTypeEnum::IntType(_) => self
+                    .build_insert_value(agg, i64_type.const_int(tag as u64, false), 0, "tag")
+                    .unwrap();
+                let agg = self
                     .builder
-                    .build_int_sub(local1.into_int_value(), local2.into_int_value(), "add")
-                    .as_basic_value_enum(),
-                _ => unreachable!(),
-            };
+                    .build_insert_value(agg, payload, 1, "payload")
+                    .unwrap();
 
-            locals.store(dest, value.as_basic_value_enum());
+                locals.store(dest, agg.as_basic_value_enum());
+            }
+            Instruction::AnyTag(val, dest) => {
+                let any = locals.load(value_to_local(val)).into_struct_value();
+                let tag = self.builder.build_extract_value(any, 0, "tag").unwrap();
+
+                locals.store(dest, tag);
+            }
+            Instruction::AnyPayload(val, typ, dest) => {
+                let any = locals.load(value_to_local(val)).into_struct_value();
+                let payload = self
+                    .builder
+                    .build_extract_value(any, 1, "payload")
+                    .unwrap()
+                    .into_int_value();
+
+                let value = match typ {
+                    AzulaType::Float => {
+                        self.builder
+                            .build_bitcast(payload, self.context.f64_type(), "float")
+                    }
+                    AzulaType::Bool => self
+                        .builder
+                        .build_int_truncate(payload, self.context.bool_type(), "bool")
+                        .as_basic_value_enum(),
+                    AzulaType::Pointer(nested) if nested.deref() == &AzulaType::Str => {
+                        let c_string = self.context.i8_type().ptr_type(AddressSpace::Generic);
+                        let pointer = self.builder.build_int_to_ptr(payload, c_string, "ptr");
+                        self.from_c_string(pointer)
+                    }
+                    AzulaType::Pointer(_) => self
+                        .builder
+                        .build_int_to_ptr(
+                            payload,
+                            self.azula_type_to_llvm_basic_type(typ).into_pointer_type(),
+                            "ptr",
+                        )
+                        .as_basic_value_enum(),
+                    _ => payload.as_basic_value_enum(),
+                };
+
+                locals.store(dest, value);
+            }
+            _ => unreachable!(),
         }
     }
 
-    fn codegen_mul(&self, instruction: Instruction<'a>, locals: &mut FunctionLocals<'a>) {
-        if let Instruction::Mul(val1, val2, dest) = instruction {
+    // A string's bytes and how many there are, so its length is known
+    // without looking for a NUL and it can contain them
+    fn string_type(&self) -> StructType<'a> {
+        let pointer = self.context.i8_type().ptr_type(AddressSpace::Generic);
+
+        self.context
+            .struct_type(&[pointer.into(), self.context.i64_type().into()], false)
+    }
+
+    // What C is given for a value, the pointer to a string's bytes or the
+    // value itself
+    fn c_argument(&self, value: BasicValueEnum<'a>) -> BasicValueEnum<'a> {
+        match value {
+            BasicValueEnum::StructValue(string) if string.get_type() == self.string_type() => self
+                .builder
+                .build_extract_value(string, 0, "bytes")
+                .unwrap(),
+            value => value,
+        }
+    }
+
+    fn pointer_of(&self, value: BasicValueEnum<'a>) -> PointerValue<'a> {
+        self.c_argument(value).into_pointer_value()
+    }
+
+    // Measures a string from C, a null pointer being an empty one
+    fn from_c_string(&self, pointer: PointerValue<'a>) -> BasicValueEnum<'a> {
+        let i64_type = self.context.i64_type();
+        let c_string = self.context.i8_type().ptr_type(AddressSpace::Generic);
+        let strlen = self.module.get_function("strlen").unwrap_or_else(|| {
+            self.module.add_function(
+                "strlen",
+                i64_type.fn_type(&[c_string.into()], false),
+                Some(Linkage::External),
+            )
+        });
+        let empty = match self.module.get_global("empty_string") {
+            Some(empty) => empty,
+            None => self.builder.build_global_string_ptr("", "empty_string"),
+        };
+        let empty = empty.as_pointer_value().const_cast(c_string);
+
+        let is_null = self.builder.build_is_null(pointer, "is_null");
+        let bytes = self
+            .builder
+            .build_select(is_null, empty, pointer, "bytes")
+            .into_pointer_value();
+        let length = self
+            .builder
+            .build_call(strlen, &[bytes.into()], "strlen")
+            .try_as_basic_value()
+            .unwrap_left()
+            .into_int_value();
+        let length = self.builder.build_int_cast(length, i64_type, "len");
+
+        let string = self.string_type().get_undef();
+        let string = self
+            .builder
+            .build_insert_value(string, bytes, 0, "bytes")
+            .unwrap();
+        let string = self
+            .builder
+            .build_insert_value(string, length, 1, "len")
+            .unwrap();
+
+        string.as_basic_value_enum()
+    }
+
+    fn any_type(&self) -> StructType<'a> {
+        let i64_type = self.context.i64_type();
+
+        self.context
+            .struct_type(&[i64_type.into(), i64_type.into()], false)
+    }
+
+    // Functions returning tuples return the struct by value and leave it to
+    // LLVM to lower to registers or an sret pointer for the target
+    fn tuple_type(&self, types: Vec<AzulaType<'a>>) -> StructType<'a> {
+        let types: Vec<_> = types
+            .into_iter()
+            .map(|typ| self.azula_type_to_llvm_basic_type(typ))
+            .collect();
+
+        self.context.struct_type(&types, false)
+    }
+
+    fn optional_type(&self, inner: AzulaType<'a>) -> StructType<'a> {
+        self.context.struct_type(
+            &[
+                self.context.bool_type().as_basic_type_enum(),
+                self.azula_type_to_llvm_basic_type(inner),
+            ],
+            false,
+        )
+    }
+
+    // ok, the error's message as a string and the value, which a !void
+    // result doesn't have
+    fn result_type(&self, inner: AzulaType<'a>) -> StructType<'a> {
+        let mut fields = vec![
+            self.context.bool_type().as_basic_type_enum(),
+            self.string_type().as_basic_type_enum(),
+        ];
+        if inner != AzulaType::Void {
+            fields.push(self.azula_type_to_llvm_basic_type(inner));
+        }
+
+        self.context.struct_type(&fields, false)
+    }
+
+    // The lifted function and its env, both as i8*
+    fn closure_type(&self) -> StructType<'a> {
+        let pointer = self.context.i8_type().ptr_type(AddressSpace::Generic);
+
+        self.context
+            .struct_type(&[pointer.into(), pointer.into()], false)
+    }
+
+    // The value's env and its vtable, both as i8*
+    fn interface_type(&self) -> StructType<'a> {
+        let pointer = self.context.i8_type().ptr_type(AddressSpace::Generic);
+
+        self.context
+            .struct_type(&[pointer.into(), pointer.into()], false)
+    }
+
+    fn codegen_add(&self, instruction: Instruction<'a>, locals: &mut FunctionLocals<'a>) {
+        if let Instruction::Add(val1, val2, dest) = instruction {
             let local1 = locals.load(value_to_local(val1));
             let local2 = locals.load(value_to_local(val2));
 
             let value = match local1.get_type() {
                 BasicTypeEnum::FloatType(_) => self
                     .builder
-                    .build_float_mul(local1.into_float_value(), local2.into_float_value(), "add")
+                    .build_float_add(local1.into_float_value(), local2.into_float_value(), "add")
                     .as_basic_value_enum(),
                 BasicTypeEnum::IntType(_) => self
                     .builder
-                    .build_int_mul(local1.into_int_value(), local2.into_int_value(), "add")
+                    .build_int_add(local1.into_int_value(), local2.into_int_value(), "add")
                     .as_basic_value_enum(),
                 _ => unreachable!(),
             };
 
-            locals.store(dest, value.as_basic_value_enum());
+            locals.store(dest, value);
         }
     }
 
-    fn codegen_div(&self, instruction: Instruction<'a>, locals: &mut FunctionLocals<'a>) {
-        if let Instruction::Div(val1, val2, dest) = instruction {
+    fn codegen_sub(&self, instruction: Instruction<'a>, locals: &mut FunctionLocals<'a>) {
+        if let Instruction::Sub(val1, val2, dest) = instruction {
             let local1 = locals.load(value_to_local(val1));
             let local2 = locals.load(value_to_local(val2));
 
             let value = match local1.get_type() {
                 BasicTypeEnum::FloatType(_) => self
                     .builder
-                    .build_float_div(local1.into_float_value(), local2.into_float_value(), "div")
+                    .build_float_sub(local1.into_float_value(), local2.into_float_value(), "add")
                     .as_basic_value_enum(),
                 BasicTypeEnum::IntType(_) => self
                     .builder
-                    .build_int_signed_div(local1.into_int_value(), local2.into_int_value(), "div")
+                    .build_int_sub(local1.into_int_value(), local2.into_int_value(), "add")
                     .as_basic_value_enum(),
                 _ => unreachable!(),
             };
@@ -889,19 +2229,19 @@ impl<'a> LLVMCodegen<'a> {
         }
     }
 
-    fn codegen_mod(&self, instruction: Instruction<'a>, locals: &mut FunctionLocals<'a>) {
-        if let Instruction::Mod(val1, val2, dest) = instruction {
+    fn codegen_mul(&self, instruction: Instruction<'a>, locals: &mut FunctionLocals<'a>) {
+        if let Instruction::Mul(val1, val2, dest) = instruction {
             let local1 = locals.load(value_to_local(val1));
             let local2 = locals.load(value_to_local(val2));
 
             let value = match local1.get_type() {
                 BasicTypeEnum::FloatType(_) => self
                     .builder
-                    .build_float_rem(local1.into_float_value(), local2.into_float_value(), "mod")
+                    .build_float_mul(local1.into_float_value(), local2.into_float_value(), "add")
                     .as_basic_value_enum(),
                 BasicTypeEnum::IntType(_) => self
                     .builder
-                    .build_int_signed_rem(local1.into_int_value(), local2.into_int_value(), "mod")
+                    .build_int_mul(local1.into_int_value(), local2.into_int_value(), "add")
                     .as_basic_value_enum(),
                 _ => unreachable!(),
             };
@@ -910,69 +2250,298 @@ impl<'a> LLVMCodegen<'a> {
         }
     }
 
-    fn codegen_pow(&self, instruction: Instruction<'a>, locals: &mut FunctionLocals<'a>) {
+    // Integer division truncates towards zero
+    fn codegen_div(&self, instruction: Instruction<'a>, locals: &mut FunctionLocals<'a>) {
+        let (val1, val2, dest, unsigned) = match instruction {
+            Instruction::Div(val1, val2, dest) => (val1, val2, dest, false),
+            Instruction::UDiv(val1, val2, dest) => (val1, val2, dest, true),
+            _ => unreachable!(),
+        };
+        let local1 = locals.load(value_to_local(val1));
+        let local2 = locals.load(value_to_local(val2));
+
+        let value = match local1.get_type() {
+            BasicTypeEnum::FloatType(_) => self
+                .builder
+                .build_float_div(local1.into_float_value(), local2.into_float_value(), "div")
+                .as_basic_value_enum(),
+            BasicTypeEnum::IntType(_) if unsigned => self
+                .builder
+                .build_int_unsigned_div(local1.into_int_value(), local2.into_int_value(), "udiv")
+                .as_basic_value_enum(),
+            BasicTypeEnum::IntType(_) => self
+                .builder
+                .build_int_signed_div(local1.into_int_value(), local2.into_int_value(), "div")
+                .as_basic_value_enum(),
+            _ => unreachable!(),
+        };
+
+        locals.store(dest, value.as_basic_value_enum());
+    }
+
+    fn codegen_mod(&self, instruction: Instruction<'a>, locals: &mut FunctionLocals<'a>) {
+        let (val1, val2, dest, unsigned) = match instruction {
+            Instruction::Mod(val1, val2, dest) => (val1, val2, dest, false),
+            Instruction::URem(val1, val2, dest) => (val1, val2, dest, true),
+            _ => unreachable!(),
+        };
+        let local1 = locals.load(value_to_local(val1));
+        let local2 = locals.load(value_to_local(val2));
+
+        let value = match local1.get_type() {
+            BasicTypeEnum::FloatType(_) => self
+                .builder
+                .build_float_rem(local1.into_float_value(), local2.into_float_value(), "mod")
+                .as_basic_value_enum(),
+            BasicTypeEnum::IntType(_) if unsigned => self
+                .builder
+                .build_int_unsigned_rem(local1.into_int_value(), local2.into_int_value(), "urem")
+                .as_basic_value_enum(),
+            BasicTypeEnum::IntType(_) => self
+                .builder
+                .build_int_signed_rem(local1.into_int_value(), local2.into_int_value(), "mod")
+                .as_basic_value_enum(),
+            _ => unreachable!(),
+        };
+
+        locals.store(dest, value.as_basic_value_enum());
+    }
+
+    fn codegen_unsigned_compare(
+        &self,
+        instruction: Instruction<'a>,
+        locals: &mut FunctionLocals<'a>,
+    ) {
+        let (val1, val2, dest, predicate) = match instruction {
+            Instruction::UGt(val1, val2, dest) => (val1, val2, dest, IntPredicate::UGT),
+            Instruction::UGte(val1, val2, dest) => (val1, val2, dest, IntPredicate::UGE),
+            Instruction::ULt(val1, val2, dest) => (val1, val2, dest, IntPredicate::ULT),
+            Instruction::ULte(val1, val2, dest) => (val1, val2, dest, IntPredicate::ULE),
+            _ => unreachable!(),
+        };
+        let local1 = locals.load(value_to_local(val1)).into_int_value();
+        let local2 = locals.load(value_to_local(val2)).into_int_value();
+
+        let value = self
+            .builder
+            .build_int_compare(predicate, local1, local2, "cmp");
+
+        locals.store(dest, value.as_basic_value_enum());
+    }
+
+    // Floats go through llvm.pow, or llvm.powi for an integer exponent, and
+    // integers are multiplied out
+    fn codegen_pow(
+        &self,
+        instruction: Instruction<'a>,
+        func: &FunctionValue<'a>,
+        locals: &mut FunctionLocals<'a>,
+    ) {
         if let Instruction::Pow(val1, val2, dest) = instruction {
             let local1 = locals.load(value_to_local(val1));
             let local2 = locals.load(value_to_local(val2));
 
-            let result = self.builder.build_call(
-                self.module.get_function("pow").unwrap(),
-                &[local1.into(), local2.into()],
-                "power",
-            );
+            let value = match (local1, local2) {
+                (BasicValueEnum::FloatValue(base), exponent) => {
+                    let typ = base.get_type();
+                    let width = if typ == self.context.f32_type() {
+                        32
+                    } else {
+                        64
+                    };
+                    let (name, exponent) = match exponent {
+                        BasicValueEnum::IntValue(exponent) => {
+                            let i32_type = self.context.i32_type();
+                            let exponent =
+                                self.builder.build_int_cast(exponent, i32_type, "exponent");
+                            (
+                                format!("llvm.powi.f{}.i32", width),
+                                exponent.as_basic_value_enum(),
+                            )
+                        }
+                        exponent => (format!("llvm.pow.f{}", width), exponent),
+                    };
+                    let intrinsic = self.module.get_function(&name).unwrap_or_else(|| {
+                        self.module.add_function(
+                            &name,
+                            typ.fn_type(&[typ.into(), exponent.get_type().into()], false),
+                            None,
+                        )
+                    });
+                    self.builder
+                        .build_call(intrinsic, &[base.into(), exponent.into()], "power")
+                        .try_as_basic_value()
+                        .unwrap_left()
+                }
+                (base, exponent) => self
+                    .int_pow(base.into_int_value(), exponent.into_int_value(), func)
+                    .as_basic_value_enum(),
+            };
 
-            locals.store(dest, result.try_as_basic_value().unwrap_left());
+            locals.store(dest, value);
         }
     }
 
-    fn build_object_file(&self, dest: String) {
-        let target_machine = self.create_machine(self.target.clone()).unwrap();
+    // Squares the base for each bit of the exponent, multiplying the result by
+    // it for the bits that are set. A negative exponent gives 0
+    fn int_pow(
+        &self,
+        base: IntValue<'a>,
+        exponent: IntValue<'a>,
+        func: &FunctionValue<'a>,
+    ) -> IntValue<'a> {
+        let typ = base.get_type();
+        let exponent = self.builder.build_int_cast(exponent, typ, "exponent");
+        let zero = typ.const_zero();
+        let one = typ.const_int(1, false);
+
+        let entry = self.builder.get_insert_block().unwrap();
+        let header = self.context.append_basic_block(*func, "pow");
+        let step = self.context.append_basic_block(*func, "pow_step");
+        let done = self.context.append_basic_block(*func, "pow_done");
+        self.builder.build_unconditional_branch(header);
+
+        self.builder.position_at_end(header);
+        let result = self.builder.build_phi(typ, "result");
+        let square = self.builder.build_phi(typ, "square");
+        let remaining = self.builder.build_phi(typ, "remaining");
+        let more = self.builder.build_int_compare(
+            IntPredicate::SGT,
+            remaining.as_basic_value().into_int_value(),
+            zero,
+            "more",
+        );
+        self.builder.build_conditional_branch(more, step, done);
 
-        target_machine
-            .write_to_file(&self.module, FileType::Object, Path::new(&dest))
-            .unwrap();
+        self.builder.position_at_end(step);
+        let (current, squared, left) = (
+            result.as_basic_value().into_int_value(),
+            square.as_basic_value().into_int_value(),
+            remaining.as_basic_value().into_int_value(),
+        );
+        let bit = self.builder.build_and(left, one, "bit");
+        let odd = self
+            .builder
+            .build_int_compare(IntPredicate::NE, bit, zero, "odd");
+        let multiplied = self.builder.build_int_mul(current, squared, "multiplied");
+        let next_result = self
+            .builder
+            .build_select(odd, multiplied, current, "next_result")
+            .into_int_value();
+        let next_square = self.builder.build_int_mul(squared, squared, "next_square");
+        let next_remaining = self
+            .builder
+            .build_right_shift(left, one, false, "next_remaining");
+        self.builder.build_unconditional_branch(header);
+
+        result.add_incoming(&[(&one, entry), (&next_result, step)]);
+        square.add_incoming(&[(&base, entry), (&next_square, step)]);
+        remaining.add_incoming(&[(&exponent, entry), (&next_remaining, step)]);
+
+        self.builder.position_at_end(done);
+        let negative =
+            self.builder
+                .build_int_compare(IntPredicate::SLT, exponent, zero, "negative");
+        self.builder
+            .build_select(
+                negative,
+                zero,
+                result.as_basic_value().into_int_value(),
+                "power",
+            )
+            .into_int_value()
     }
 
-    fn create_machine(&self, name: Option<String>) -> Option<TargetMachine> {
-        if let Some(target) = name {
-            let triple = TargetTriple::create(&target);
+    fn codegen_shift(&self, instruction: Instruction<'a>, locals: &mut FunctionLocals<'a>) {
+        let (val1, val2, dest) = match instruction.clone() {
+            Instruction::Shl(val1, val2, dest)
+            | Instruction::AShr(val1, val2, dest)
+            | Instruction::LShr(val1, val2, dest) => (val1, val2, dest),
+            _ => unreachable!(),
+        };
 
-            self.module.set_triple(&triple);
-            Target::initialize_all(&InitializationConfig::default());
-            let target = Target::from_triple(&triple).unwrap();
-            let mut opt_level = inkwell::OptimizationLevel::Default;
-            if self.opt_level == OptimizationLevel::Aggressive {
-                opt_level = inkwell::OptimizationLevel::Aggressive;
-            }
-            return target.create_target_machine(
-                &triple,
-                "",
-                "",
-                opt_level,
-                inkwell::targets::RelocMode::Default,
-                inkwell::targets::CodeModel::Default,
-            );
+        let local1 = locals.load(value_to_local(val1)).into_int_value();
+        let local2 = locals.load(value_to_local(val2)).into_int_value();
+
+        // Shifting by the width of the type or more is poison in LLVM, so mask
+        // the amount to keep the behaviour defined for runtime values
+        let int_type = local1.get_type();
+        let mask = int_type.const_int(int_type.get_bit_width() as u64 - 1, false);
+        let amount = self.builder.build_and(local2, mask, "amount");
+
+        let value = match instruction {
+            Instruction::Shl(..) => self.builder.build_left_shift(local1, amount, "shl"),
+            Instruction::AShr(..) => self.builder.build_right_shift(local1, amount, true, "ashr"),
+            _ => self
+                .builder
+                .build_right_shift(local1, amount, false, "lshr"),
+        };
+
+        locals.store(dest, value.as_basic_value_enum());
+    }
+
+    // Objects of modules in nested directories go in the same directories
+    // under .build/
+    fn build_object_file(&self, dest: &str) -> Result<(), Box<dyn Error>> {
+        let target_machine = LLVMCodegen::create_machine(&self.target, self.opt_level)?;
+
+        let dest = Path::new(dest);
+        if let Some(dir) = dest.parent() {
+            fs::create_dir_all(dir)?;
         }
+        target_machine
+            .write_to_file(&self.module, FileType::Object, dest)
+            .map_err(|e| e.to_string())?;
 
-        let triple = TargetMachine::get_default_triple();
-        let cpu = TargetMachine::get_host_cpu_name().to_string();
-        let features = TargetMachine::get_host_cpu_features().to_string();
+        Ok(())
+    }
 
-        self.module.set_triple(&triple);
-        Target::initialize_native(&InitializationConfig::default()).unwrap();
-        let target = Target::from_triple(&triple).unwrap();
-        let mut opt_level = inkwell::OptimizationLevel::Default;
-        if self.opt_level == OptimizationLevel::Aggressive {
-            opt_level = inkwell::OptimizationLevel::Aggressive;
+    fn create_machine(
+        options: &TargetOptions,
+        opt_level: OptimizationLevel,
+    ) -> Result<TargetMachine, String> {
+        let (triple, cpu, features) = match &options.triple {
+            Some(target) => {
+                Target::initialize_all(&InitializationConfig::default());
+                (TargetTriple::create(target), String::new(), String::new())
+            }
+            None => {
+                Target::initialize_native(&InitializationConfig::default())?;
+                (
+                    TargetMachine::get_default_triple(),
+                    TargetMachine::get_host_cpu_name().to_string(),
+                    TargetMachine::get_host_cpu_features().to_string(),
+                )
+            }
+        };
+        let cpu = options.cpu.clone().unwrap_or(cpu);
+        let features = options.features.clone().unwrap_or(features);
+
+        let target = Target::from_triple(&triple).map_err(|e| e.to_string())?;
+        let mut llvm_level = inkwell::OptimizationLevel::Default;
+        if opt_level == OptimizationLevel::Aggressive {
+            llvm_level = inkwell::OptimizationLevel::Aggressive;
         }
-        target.create_target_machine(
-            &triple,
-            &cpu,
-            &features,
-            opt_level,
-            inkwell::targets::RelocMode::Default,
-            inkwell::targets::CodeModel::Default,
-        )
+        let reloc_mode = match options.reloc_model {
+            RelocModel::Default => RelocMode::Default,
+            RelocModel::Static => RelocMode::Static,
+            RelocModel::Pic => RelocMode::PIC,
+        };
+        target
+            .create_target_machine(
+                &triple,
+                &cpu,
+                &features,
+                llvm_level,
+                reloc_mode,
+                inkwell::targets::CodeModel::Default,
+            )
+            .ok_or_else(|| {
+                format!(
+                    "No target machine for {}",
+                    triple.as_str().to_string_lossy()
+                )
+            })
     }
 
     fn azula_type_to_llvm_basic_type(&self, t: AzulaType<'a>) -> BasicTypeEnum<'a> {
@@ -1002,6 +2571,9 @@ impl<'a> LLVMCodegen<'a> {
             },
             AzulaType::Bool => self.context.bool_type().as_basic_type_enum(),
             AzulaType::Void => todo!(),
+            AzulaType::Pointer(nested) if nested.deref() == &AzulaType::Str => {
+                self.string_type().as_basic_type_enum()
+            }
             AzulaType::Pointer(nested) => {
                 let typ = self.azula_type_to_llvm_basic_type(nested.deref().clone());
 
@@ -1014,12 +2586,35 @@ impl<'a> LLVMCodegen<'a> {
                 .unwrap()
                 .as_basic_type_enum(),
             AzulaType::UnknownType(_) => todo!(),
+            // Sized rows are stored inline, so the array points at their
+            // first element the same as a row does
+            AzulaType::Array(row, _) if matches!(row.deref(), AzulaType::Array(_, Some(_))) => {
+                self.azula_type_to_llvm_basic_type(row.deref().clone())
+            }
             AzulaType::Array(typ, _) => {
                 let typ = self.azula_type_to_llvm_basic_type(typ.deref().clone());
 
                 // typ.array_type(size.unwrap() as u32).as_basic_type_enum()
                 typ.ptr_type(AddressSpace::Generic).as_basic_type_enum()
             }
+            AzulaType::Any => self.any_type().as_basic_type_enum(),
+            AzulaType::Tuple(types) => self.tuple_type(types).as_basic_type_enum(),
+            AzulaType::Optional(inner) => self
+                .optional_type(inner.deref().clone())
+                .as_basic_type_enum(),
+            AzulaType::Result(inner) => {
+                self.result_type(inner.deref().clone()).as_basic_type_enum()
+            }
+            AzulaType::Function(..) => self.closure_type().as_basic_type_enum(),
+            AzulaType::Interface(_) => self.interface_type().as_basic_type_enum(),
+            // Generics and slices are instantiated, constant sizes
+            // evaluated and typeofs looked up by the typechecker
+            AzulaType::Generic(..)
+            | AzulaType::Slice(_)
+            | AzulaType::ConstArray(..)
+            | AzulaType::TypeOf(_) => {
+                unreachable!()
+            }
         }
     }
 
@@ -1057,6 +2652,9 @@ impl<'a> LLVMCodegen<'a> {
             .fn_type(args, false),
             AzulaType::Bool => self.context.bool_type().fn_type(args, false),
             AzulaType::Void => self.context.void_type().fn_type(args, false),
+            AzulaType::Pointer(nested) if nested.deref() == &AzulaType::Str => {
+                self.string_type().fn_type(args, false)
+            }
             AzulaType::Pointer(nested) => {
                 let typ = self.azula_type_to_llvm_basic_type(nested.deref().clone());
 
@@ -1069,12 +2667,33 @@ impl<'a> LLVMCodegen<'a> {
                 .unwrap()
                 .fn_type(args, false),
             AzulaType::UnknownType(_) => todo!(),
+            AzulaType::Array(row, _) if matches!(row.deref(), AzulaType::Array(_, Some(_))) => self
+                .azula_type_to_llvm_basic_type(row.deref().clone())
+                .fn_type(args, false),
             AzulaType::Array(typ, _) => {
                 let typ = self.azula_type_to_llvm_basic_type(typ.deref().clone());
 
                 // typ.array_type(size.unwrap() as u32).fn_type(args, false)
                 typ.ptr_type(AddressSpace::Generic).fn_type(args, false)
             }
+            AzulaType::Any => self.any_type().fn_type(args, false),
+            AzulaType::Tuple(types) => self.tuple_type(types).fn_type(args, false),
+            AzulaType::Optional(inner) => self
+                .optional_type(inner.deref().clone())
+                .fn_type(args, false),
+            AzulaType::Result(inner) => {
+                self.result_type(inner.deref().clone()).fn_type(args, false)
+            }
+            AzulaType::Function(..) => self.closure_type().fn_type(args, false),
+            AzulaType::Interface(_) => self.interface_type().fn_type(args, false),
+            // Generics and slices are instantiated, constant sizes
+            // evaluated and typeofs looked up by the typechecker
+            AzulaType::Generic(..)
+            | AzulaType::Slice(_)
+            | AzulaType::ConstArray(..)
+            | AzulaType::TypeOf(_) => {
+                unreachable!()
+            }
         }
     }
 
@@ -1125,12 +2744,33 @@ impl<'a> LLVMCodegen<'a> {
                 .unwrap()
                 .fn_type(args, varargs),
             AzulaType::UnknownType(_) => todo!(),
+            AzulaType::Array(row, _) if matches!(row.deref(), AzulaType::Array(_, Some(_))) => self
+                .azula_type_to_llvm_basic_type(row.deref().clone())
+                .fn_type(args, varargs),
             AzulaType::Array(typ, _) => {
                 let typ = self.azula_type_to_llvm_basic_type(typ.deref().clone());
 
                 // typ.array_type(size.unwrap() as u32).fn_type(args, false)
                 typ.ptr_type(AddressSpace::Generic).fn_type(args, varargs)
             }
+            AzulaType::Any => self.any_type().fn_type(args, varargs),
+            AzulaType::Tuple(types) => self.tuple_type(types).fn_type(args, varargs),
+            AzulaType::Optional(inner) => self
+                .optional_type(inner.deref().clone())
+                .fn_type(args, varargs),
+            AzulaType::Result(inner) => self
+                .result_type(inner.deref().clone())
+                .fn_type(args, varargs),
+            AzulaType::Function(..) => self.closure_type().fn_type(args, varargs),
+            AzulaType::Interface(_) => self.interface_type().fn_type(args, varargs),
+            // Generics and slices are instantiated, constant sizes
+            // evaluated and typeofs looked up by the typechecker
+            AzulaType::Generic(..)
+            | AzulaType::Slice(_)
+            | AzulaType::ConstArray(..)
+            | AzulaType::TypeOf(_) => {
+                unreachable!()
+            }
         }
     }
 }
@@ -1141,6 +2781,8 @@ impl<'ctx> FunctionLocals<'ctx> {
             registers: HashMap::new(),
             variables: HashMap::new(),
             blocks: HashMap::new(),
+            jump_tables: false,
+            memory_params: vec![],
         }
     }
 
@@ -1,6 +1,8 @@
-use ansi_term::Colour::*;
+use ansi_term::Colour::{self, *};
 use line_col::LineColLookup;
 
+use crate::source_map::SourceMap;
+
 #[derive(Debug)]
 pub enum ErrorType {
     // Lex & Parse
@@ -10,23 +12,85 @@ pub enum ErrorType {
     ExpectedStatement(String),
     UnexpectedEOF,
     InvalidEscape,
-    ArrayInitialiserSizeNonConstant,
+    NewWithLiteral,
 
     // Typecheck
     UnknownVariable(String),
     MismatchedTypes(String, String),
     NonOperatorType(String, String),
     IncorrectFunctionReturn(String, String),
-    NonBoolCondition(String),
+    // The comparison that would make it a bool, if there is one
+    NonBoolCondition(String, Option<String>),
     FunctionNotFound(String),
     MismatchedAssignTypes(String, String),
-    NonGlobalConstant,
+    CannotInferType(String, String),
+    NonLiteralConstant,
     NonIntIndex(String),
     NonArrayInIndex(String),
+    NotIterable(String),
     ConstantAssign,
     AccessNonStruct,
     UnknownStruct(String),
     UnknownStructMember(String, String),
+    InvalidNameof,
+    InvalidTypeName,
+    ShiftOutOfRange(i64, usize),
+    NotConvertibleToAny(String),
+    MatchNonAny(String),
+    InvalidCast(String, String),
+    NoNumericPromotion(String, String),
+    BuildInfoNotEmbedded,
+    UnexpectedArguments(String),
+    DestructureMismatch(usize, String),
+    InvalidSignalHandler(String),
+    WrongArgumentCount(String, usize, usize),
+    NonOptional(String),
+    TryOnNonResult(String),
+    TryOutsideResultFunction,
+    NestedDefer,
+    MissingDefault(String),
+    NotEnoughArguments(String, usize, usize),
+    ParamAfterVariadic(String),
+    UnknownArgument(String, String),
+    DuplicateArgument(String),
+    UnexpectedNamedArgument(String),
+    PositionalAfterNamed,
+    MissingArgument(String, String),
+    NotCallable(String),
+    ConflictingTypeArguments(String, String, String),
+    UninferredTypeParameter(String, String),
+    WrongTypeArgumentCount(String, usize, usize),
+    StatementOutsideFunction(String),
+    UnknownInterface(String),
+    UnknownMethod(String, String),
+    MissingMethod(String, String, String),
+    MismatchedMethodSignature(String, String),
+    UnsatisfiedBound(String, String),
+    NotDynamicMethod(String, String),
+    ModuleNotFound(String, String),
+    UnknownModuleMember(String, String),
+    DuplicateDeclaration(String, Option<String>),
+    InvalidStructLiteral,
+    PrivateFunction(String, String),
+    UnknownAttribute(String),
+    ConflictingAttributes(String, String),
+    UnsizedType(String),
+    DerefNonPointer(String),
+    InvalidInterruptHandler(String),
+    InvalidTest(String),
+    MissingReturn(String),
+    LoopControlOutsideLoop(String),
+    UnknownLoopLabel(String),
+    InvalidRange(String, String),
+    NonConstantArraySize(String),
+    ConstantCycle(String),
+    DivisionByZero,
+    ConstantOverflow,
+    MissingField(String, String),
+    DuplicateField(String),
+    MismatchedFieldType(String, String, String),
+    UnknownConfigKey(String),
+    ExpectedDeclaration(String),
 }
 
 impl<'a> ErrorType {
@@ -41,8 +105,8 @@ impl<'a> ErrorType {
             ErrorType::ExpectedStatement(got) => format!("Expected a statement, got {:?}", got),
             ErrorType::UnexpectedEOF => "Unexpected EOF".to_string(),
             ErrorType::InvalidEscape => "Invalid use of escape".to_string(),
-            ErrorType::ArrayInitialiserSizeNonConstant => {
-                "Array initialiser size must be a constant int".to_string()
+            ErrorType::NewWithLiteral => {
+                "new only allocates, set the fields through the pointer it gives".to_string()
             }
             ErrorType::UnknownVariable(name) => format!("Unknown variable {:?}", name),
             ErrorType::MismatchedTypes(left, right) => {
@@ -57,8 +121,8 @@ impl<'a> ErrorType {
                     wanted, got
                 )
             }
-            ErrorType::NonBoolCondition(got) => {
-                format!("If condition must be a boolean, got {}", got)
+            ErrorType::NonBoolCondition(got, _) => {
+                format!("Condition must be a boolean, got {}", got)
             }
             ErrorType::FunctionNotFound(func) => {
                 format!("Function {} not found", func)
@@ -69,8 +133,15 @@ impl<'a> ErrorType {
                     wanted, got
                 )
             }
-            ErrorType::NonGlobalConstant => {
-                "Only constant assigns are allowed at top-level".to_string()
+            ErrorType::NonLiteralConstant => {
+                "Only literals, constants and operators on them can be worked out at compile time"
+                    .to_string()
+            }
+            ErrorType::CannotInferType(name, got) => {
+                format!(
+                    "Cannot infer a type for {} from {}, add a type annotation",
+                    name, got
+                )
             }
             ErrorType::NonIntIndex(got) => {
                 format!("Index must be integer, got {}", got)
@@ -78,6 +149,10 @@ impl<'a> ErrorType {
             ErrorType::NonArrayInIndex(got) => {
                 format!("Cannot index non-array, got {}", got)
             }
+            ErrorType::NotIterable(got) => format!(
+                "Cannot loop over {}, expected a sized array, a slice or a range of ints",
+                got
+            ),
             ErrorType::ConstantAssign => "Cannot assign to constant".to_string(),
             ErrorType::AccessNonStruct => "Cannot access non-struct".to_string(),
             ErrorType::UnknownStruct(struc) => {
@@ -86,6 +161,187 @@ impl<'a> ErrorType {
             ErrorType::UnknownStructMember(struc, member) => {
                 format!("Struct {} attribute {} not found", struc, member)
             }
+            ErrorType::InvalidNameof => "nameof expects a single identifier".to_string(),
+            ErrorType::InvalidTypeName => "type_name expects a single expression".to_string(),
+            ErrorType::ShiftOutOfRange(amount, width) => {
+                format!("Cannot shift a {}-bit integer by {}", width, amount)
+            }
+            ErrorType::NotConvertibleToAny(typ) => {
+                format!("Type {} cannot be stored in an any", typ)
+            }
+            ErrorType::MatchNonAny(typ) => format!("Can only match on any, got {}", typ),
+            ErrorType::InvalidCast(from, to) => format!("Cannot cast {} to {}", from, to),
+            ErrorType::NoNumericPromotion(left, right) => format!(
+                "Cannot mix {} and {} without an explicit 'as' conversion",
+                left, right
+            ),
+            ErrorType::BuildInfoNotEmbedded => {
+                "build_info() needs the --embed-build-info flag".to_string()
+            }
+            ErrorType::UnexpectedArguments(function) => {
+                format!("{} does not take any arguments", function)
+            }
+            ErrorType::NonOptional(typ) => format!("Expected an optional, got {}", typ),
+            ErrorType::TryOnNonResult(typ) => {
+                format!("? can only be used on a result, got {}", typ)
+            }
+            ErrorType::MissingDefault(param) => format!(
+                "Parameter {} needs a default value as it follows one with a default",
+                param
+            ),
+            ErrorType::NotEnoughArguments(function, expected, got) => format!(
+                "{} expects at least {} arguments, got {}",
+                function, expected, got
+            ),
+            ErrorType::UnknownArgument(function, name) => {
+                format!("{} has no parameter named {}", function, name)
+            }
+            ErrorType::DuplicateArgument(name) => {
+                format!("Argument {} is given more than once", name)
+            }
+            ErrorType::UnexpectedNamedArgument(name) => {
+                format!("Argument {} can't be given by name here", name)
+            }
+            ErrorType::PositionalAfterNamed => {
+                "Positional arguments can't come after named ones".to_string()
+            }
+            ErrorType::MissingArgument(function, name) => {
+                format!("{} is missing an argument for {}", function, name)
+            }
+            ErrorType::NotCallable(typ) => format!("Can't call a value of type {:?}", typ),
+            ErrorType::ConflictingTypeArguments(generic, first, second) => {
+                format!("{} is inferred as both {} and {}", generic, first, second)
+            }
+            ErrorType::WrongTypeArgumentCount(name, expected, got) => {
+                format!("{} takes {} type arguments, got {}", name, expected, got)
+            }
+            ErrorType::UninferredTypeParameter(generic, function) => format!(
+                "Can't infer {} for {}, it has to appear in a parameter's type",
+                generic, function
+            ),
+            ErrorType::ParamAfterVariadic(param) => format!(
+                "Parameter {} comes after a variadic parameter, which has to be last",
+                param
+            ),
+            ErrorType::NestedDefer => {
+                "defer can only be used at the top level of a function body".to_string()
+            }
+            ErrorType::TryOutsideResultFunction => {
+                "? can only be used in a function that returns a result".to_string()
+            }
+            ErrorType::WrongArgumentCount(function, expected, got) => {
+                format!("{} expects {} arguments, got {}", function, expected, got)
+            }
+            ErrorType::InvalidSignalHandler(name) => format!(
+                "Signal handler {} must be a function taking a single i32 and returning void",
+                name
+            ),
+            ErrorType::DestructureMismatch(count, typ) => {
+                format!("Cannot destructure {} into {} variables", typ, count)
+            }
+            ErrorType::StatementOutsideFunction(kind) => {
+                format!("{} must be inside a function", kind)
+            }
+            ErrorType::UnknownInterface(name) => format!("Unknown interface {}", name),
+            ErrorType::UnknownMethod(method, interface) => {
+                format!("{} is not a method of {}", method, interface)
+            }
+            ErrorType::MissingMethod(interface, typ, method) => {
+                format!("impl {} for {} is missing {}", interface, typ, method)
+            }
+            ErrorType::MismatchedMethodSignature(method, interface) => {
+                format!("{} does not match its signature in {}", method, interface)
+            }
+            ErrorType::UnsatisfiedBound(typ, interface) => {
+                format!("{} does not implement {}", typ, interface)
+            }
+            ErrorType::ModuleNotFound(module, reason) => {
+                format!("Couldn't import {}: {}", module, reason)
+            }
+            ErrorType::UnknownModuleMember(module, name) => {
+                format!("Module {} doesn't declare {}", module, name)
+            }
+            ErrorType::DuplicateDeclaration(name, Some(module)) => {
+                format!("{} is already declared by module {}", name, module)
+            }
+            ErrorType::DuplicateDeclaration(name, None) => {
+                format!("{} is already declared in the main file", name)
+            }
+            ErrorType::InvalidStructLiteral => {
+                "A struct literal has to start with the name of a struct".to_string()
+            }
+            ErrorType::PrivateFunction(function, module) => format!(
+                "{} is private to module {}, mark it pub to use it here",
+                function, module
+            ),
+            ErrorType::UnknownAttribute(attribute) => format!("Unknown attribute @{}", attribute),
+            ErrorType::UnsizedType(typ) => format!("{} has no size", typ),
+            ErrorType::DerefNonPointer(typ) => {
+                format!("Can't dereference {}, it isn't a pointer", typ)
+            }
+            ErrorType::MissingReturn(function) => {
+                format!("{} can reach its end without returning a value", function)
+            }
+            ErrorType::LoopControlOutsideLoop(keyword) => {
+                format!("{} can only be used inside a loop", keyword)
+            }
+            ErrorType::UnknownLoopLabel(label) => {
+                format!("There's no loop labeled {} around this", label)
+            }
+            ErrorType::InvalidRange(start, end) => format!(
+                "Range bounds must be integers of the same type, got {} and {}",
+                start, end
+            ),
+            ErrorType::InvalidInterruptHandler(function) => format!(
+                "Interrupt handler {} can't take arguments or return a value",
+                function
+            ),
+            ErrorType::InvalidTest(function) => format!(
+                "Test {} can't take arguments and has to return nothing or !void",
+                function
+            ),
+            ErrorType::ConflictingAttributes(first, second) => {
+                format!("@{} and @{} can't be used together", first, second)
+            }
+            ErrorType::NotDynamicMethod(method, interface) => format!(
+                "{} can't be called on a {} value since it uses Self",
+                method, interface
+            ),
+            ErrorType::NonConstantArraySize(typ) => {
+                format!("Array sizes must be a constant int, got {}", typ)
+            }
+            ErrorType::ConstantCycle(name) => format!("The constant {} depends on itself", name),
+            ErrorType::DivisionByZero => "Division by zero".to_string(),
+            ErrorType::ConstantOverflow => "Constant arithmetic overflows".to_string(),
+            ErrorType::MissingField(struc, field) => {
+                format!("{} is missing a value for {}", struc, field)
+            }
+            ErrorType::DuplicateField(field) => format!("Field {} is given more than once", field),
+            ErrorType::MismatchedFieldType(field, expected, got) => {
+                format!("Field {} expects {}, got {}", field, expected, got)
+            }
+            ErrorType::UnknownConfigKey(key) => format!(
+                "Unknown configuration key {}, expected target_os or target_arch",
+                key
+            ),
+            ErrorType::ExpectedDeclaration(got) => {
+                format!("@if has to come before a declaration, not {}", got)
+            }
+        }
+    }
+}
+
+impl ErrorType {
+    // A suggested fix, given the source the error points at
+    fn hint(&self, spanned: &str) -> Option<String> {
+        match self {
+            ErrorType::NonBoolCondition(_, Some(comparison)) => {
+                Some(format!("try `{} {}`", spanned, comparison))
+            }
+            ErrorType::StatementOutsideFunction(_) => {
+                Some("wrap it in `func main() { ... }`".to_string())
+            }
+            _ => None,
         }
     }
 }
@@ -107,54 +363,104 @@ impl AzulaError {
     }
 
     pub fn print_stdout(&self, source: &str, filename: &str) {
-        let lookup = LineColLookup::new(source);
-        println!(
-            "{}: {}",
+        print!("{}", self.render(source, filename));
+    }
+
+    pub fn render(&self, source: &str, filename: &str) -> String {
+        self.render_span(source, filename, self.start, self.end)
+    }
+
+    /// Renders against whichever of the files the error is in
+    pub fn render_in(&self, sources: &SourceMap) -> String {
+        let file = sources.file(self.start);
+        self.render_span(
+            file.source,
+            &file.name,
+            self.start - file.base,
+            self.end - file.base,
+        )
+    }
+
+    fn render_span(&self, source: &str, filename: &str, start: usize, end: usize) -> String {
+        let mut rendered = format!(
+            "{}: {}\n{}",
             Red.paint("ERROR"),
-            self.error_type.error_message()
-        );
-        let show_start = self.read_back_until_new_line(source, self.start - 1);
-        let show_end = self.read_forward_until_new_line(source, self.end - 1);
-        let (line_number, col) = lookup.get(self.start);
-        // println!(
-        //     "{}",
-        //     Red.paint(format!("Line: {} Column: {}", line_number, col))
-        // );
-        println!(
-            "{}",
-            Red.paint(format!("-> {}:{}:{}", filename, line_number, col))
-        );
-        print!("{}", &source[show_start + 1..self.start]);
-        print!("{}", White.paint(&source[self.start..self.end]));
-        print!("{}\n", &source[self.end..show_end]);
-        println!(
-            "{}{}",
-            " ".repeat(col - 1),
-            Red.paint("^".repeat(self.end - self.start))
+            self.error_type.error_message(),
+            format_source_span(source, filename, start, end, Red)
         );
+        if let Some(hint) = self.error_type.hint(&source[start..end]) {
+            rendered.push_str(&format!("{}: {}\n", Cyan.paint("HELP"), hint));
+        }
+
+        rendered
     }
+}
 
-    fn read_back_until_new_line(&self, source: &str, mut point: usize) -> usize {
-        let mut char = source.as_bytes()[point] as char;
-        while char != '\n' {
-            point -= 1;
-            if point == 0 {
-                return point;
-            }
-            char = source.as_bytes()[point] as char;
+pub(crate) fn format_source_span(
+    source: &str,
+    filename: &str,
+    start: usize,
+    end: usize,
+    colour: Colour,
+) -> String {
+    let lookup = LineColLookup::new(source);
+    let show_start = source[..start].rfind('\n').map_or(0, |newline| newline + 1);
+    let show_end = read_forward_until_new_line(source, end - 1);
+    let (line_number, col) = lookup.get(start);
+    // println!(
+    //     "{}",
+    //     Red.paint(format!("Line: {} Column: {}", line_number, col))
+    // );
+    format!(
+        "{}\n{}{}{}\n{}{}\n",
+        colour.paint(format!("-> {}:{}:{}", filename, line_number, col)),
+        &source[show_start..start],
+        White.paint(&source[start..end]),
+        &source[end..show_end],
+        " ".repeat(col - 1),
+        colour.paint("^".repeat(end - start))
+    )
+}
+
+fn read_forward_until_new_line(source: &str, mut point: usize) -> usize {
+    let mut char = source.as_bytes()[point] as char;
+    while char != '\n' {
+        point += 1;
+        if point >= source.len() {
+            return point;
         }
-        return point;
+        char = source.as_bytes()[point] as char;
     }
+    return point;
+}
 
-    fn read_forward_until_new_line(&self, source: &str, mut point: usize) -> usize {
-        let mut char = source.as_bytes()[point] as char;
-        while char != '\n' {
-            point += 1;
-            if point >= source.len() {
-                return point;
-            }
-            char = source.as_bytes()[point] as char;
-        }
-        return point;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_non_bool_condition_hint() {
+        let error_type = ErrorType::NonBoolCondition("Int".to_string(), Some("!= 0".to_string()));
+        assert_eq!(
+            error_type.hint("count"),
+            Some("try `count != 0`".to_string())
+        );
+        assert_eq!(
+            ErrorType::NonBoolCondition("Str".to_string(), None).hint("name"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_statement_outside_function_hint() {
+        let source = "return 1;\n";
+        let error = AzulaError::new(
+            ErrorType::StatementOutsideFunction("A return statement".to_string()),
+            0,
+            9,
+        );
+        let rendered = error.render(source, "test.az");
+        assert!(rendered.contains("A return statement must be inside a function"));
+        assert!(rendered.contains("wrap it in `func main() { ... }`"));
     }
 }
@@ -1,5 +1,9 @@
 mod error;
+mod source_map;
+mod warning;
 
 pub mod prelude {
     pub use crate::error::{AzulaError, ErrorType};
+    pub use crate::source_map::{SourceFile, SourceMap};
+    pub use crate::warning::{AzulaWarning, IgnoreDirective, Lint, WarningType};
 }
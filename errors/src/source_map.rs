@@ -0,0 +1,55 @@
+/// The files making up a program. Each file's spans start at its base, past
+/// the end of the file before it, so spans from all of them fit in one tree
+#[derive(Default)]
+pub struct SourceMap<'a> {
+    files: Vec<SourceFile<'a>>,
+}
+
+pub struct SourceFile<'a> {
+    pub name: String,
+    pub source: &'a str,
+    pub base: usize,
+}
+
+impl<'a> SourceMap<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file, returning the base to shift its spans by
+    pub fn add(&mut self, name: String, source: &'a str) -> usize {
+        // One past the end, so an error at the end of a file stays in it
+        let base = self
+            .files
+            .last()
+            .map_or(0, |file| file.base + file.source.len() + 1);
+        self.files.push(SourceFile { name, source, base });
+
+        base
+    }
+
+    /// The file an offset falls in
+    pub fn file(&self, offset: usize) -> &SourceFile<'a> {
+        self.files
+            .iter()
+            .rev()
+            .find(|file| file.base <= offset)
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_map() {
+        let mut sources = SourceMap::new();
+        assert_eq!(sources.add("main.azl".to_string(), "import b;"), 0);
+        assert_eq!(sources.add("b.azl".to_string(), "func f() {}"), 10);
+
+        assert_eq!(sources.file(9).name, "main.azl");
+        assert_eq!(sources.file(10).name, "b.azl");
+        assert_eq!(sources.file(21).name, "b.azl");
+    }
+}
@@ -0,0 +1,174 @@
+use ansi_term::Colour::*;
+
+use crate::{error::format_source_span, source_map::SourceMap};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lint {
+    UnusedVariable,
+    UnusedParameter,
+    UnreachableArm,
+}
+
+impl Lint {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::UnusedVariable => "unused_variable",
+            Lint::UnusedParameter => "unused_parameter",
+            Lint::UnreachableArm => "unreachable_arm",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        match name {
+            "unused_variable" => Some(Lint::UnusedVariable),
+            "unused_parameter" => Some(Lint::UnusedParameter),
+            "unreachable_arm" => Some(Lint::UnreachableArm),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum WarningType {
+    UnusedVariable(String),
+    // parameter, function
+    UnusedParameter(String, String),
+    // A match arm after `_` or another arm for its type
+    UnreachableArm,
+}
+
+impl WarningType {
+    fn lint(&self) -> Lint {
+        match self {
+            WarningType::UnusedVariable(_) => Lint::UnusedVariable,
+            WarningType::UnusedParameter(..) => Lint::UnusedParameter,
+            WarningType::UnreachableArm => Lint::UnreachableArm,
+        }
+    }
+
+    fn warning_message(&self) -> String {
+        match self {
+            WarningType::UnusedVariable(name) => format!("Unused variable {:?}", name),
+            WarningType::UnusedParameter(name, function) => format!(
+                "Unused parameter {:?} in {}, name it _{} if that's intended",
+                name, function, name
+            ),
+            WarningType::UnreachableArm => {
+                "Unreachable arm, an earlier one already matches".to_string()
+            }
+        }
+    }
+}
+
+/// Parsed from a `// azula:ignore <lint>...` comment, covers the span of the
+/// statement that follows it
+#[derive(Debug, Clone, PartialEq)]
+pub struct IgnoreDirective {
+    pub lints: Vec<Lint>,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl IgnoreDirective {
+    pub fn parse(comment: &str) -> Option<Vec<Lint>> {
+        let rest = comment
+            .trim_start_matches('/')
+            .trim()
+            .strip_prefix("azula:ignore")?;
+
+        Some(
+            rest.split(|c: char| c == ',' || c.is_whitespace())
+                .filter_map(Lint::from_name)
+                .collect(),
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct AzulaWarning {
+    pub warning_type: WarningType,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl AzulaWarning {
+    pub fn new(warning_type: WarningType, start: usize, end: usize) -> Self {
+        Self {
+            warning_type,
+            start,
+            end,
+        }
+    }
+
+    pub fn lint(&self) -> Lint {
+        self.warning_type.lint()
+    }
+
+    pub fn is_ignored(&self, directives: &[IgnoreDirective]) -> bool {
+        directives.iter().any(|directive| {
+            directive.start <= self.start
+                && self.end <= directive.end
+                && directive.lints.contains(&self.lint())
+        })
+    }
+
+    pub fn print_stdout(&self, source: &str, filename: &str) {
+        print!("{}", self.render(source, filename));
+    }
+
+    pub fn render(&self, source: &str, filename: &str) -> String {
+        self.render_span(source, filename, self.start, self.end)
+    }
+
+    /// Renders against whichever of the files the warning is in
+    pub fn render_in(&self, sources: &SourceMap) -> String {
+        let file = sources.file(self.start);
+        self.render_span(
+            file.source,
+            &file.name,
+            self.start - file.base,
+            self.end - file.base,
+        )
+    }
+
+    fn render_span(&self, source: &str, filename: &str, start: usize, end: usize) -> String {
+        format!(
+            "{}: {} [{}]\n{}",
+            Yellow.paint("WARNING"),
+            self.warning_type.warning_message(),
+            self.lint().name(),
+            format_source_span(source, filename, start, end, Yellow)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ignore_directive() {
+        assert_eq!(
+            IgnoreDirective::parse("// azula:ignore unused_variable"),
+            Some(vec![Lint::UnusedVariable])
+        );
+        assert_eq!(IgnoreDirective::parse("// azula:ignore"), Some(vec![]));
+        assert_eq!(IgnoreDirective::parse("// just a comment"), None);
+    }
+
+    #[test]
+    fn test_warning_ignored() {
+        let warning = AzulaWarning::new(WarningType::UnusedVariable("x".to_string()), 10, 20);
+        let directive = IgnoreDirective {
+            lints: vec![Lint::UnusedVariable],
+            start: 5,
+            end: 25,
+        };
+
+        assert!(warning.is_ignored(&[directive.clone()]));
+        assert!(!warning.is_ignored(&[IgnoreDirective {
+            start: 21,
+            ..directive
+        }]));
+    }
+}
@@ -0,0 +1,152 @@
+use std::rc::Rc;
+
+use azula_ast::prelude::*;
+use azula_error::prelude::*;
+
+/// What `@if` conditions are checked against, worked out from the target
+/// being built for
+#[derive(Debug, PartialEq, Clone)]
+pub struct Config {
+    pub target_os: String,
+    pub target_arch: String,
+}
+
+impl Config {
+    /// The config for a target triple like `x86_64-unknown-linux-gnu`. The
+    /// os is named the way Rust's target_os is, so darwin triples are macos
+    /// and ones without an os, like `thumbv7em-none-eabi`, are none
+    pub fn for_target(triple: &str) -> Config {
+        let parts: Vec<&str> = triple.split('-').collect();
+        let os = if parts.contains(&"android") {
+            "android"
+        } else {
+            parts
+                .iter()
+                .skip(1)
+                .find_map(|part| match *part {
+                    "darwin" | "macos" => Some("macos"),
+                    "linux" | "windows" | "ios" | "freebsd" | "netbsd" | "openbsd" | "wasi" => {
+                        Some(part)
+                    }
+                    _ => None,
+                })
+                .unwrap_or("none")
+        };
+
+        Config {
+            target_os: os.to_string(),
+            target_arch: parts[0].to_string(),
+        }
+    }
+
+    fn value(&self, key: &str) -> Option<&str> {
+        match key {
+            "target_os" => Some(&self.target_os),
+            "target_arch" => Some(&self.target_arch),
+            _ => None,
+        }
+    }
+}
+
+// Replaces each `@if` declaration in a file with its declaration when every
+// key in the condition has its value for the config, and removes it when not.
+// Keys that aren't known are reported and the declaration is left out
+pub(crate) fn configure(body: &mut Vec<Statement>, config: &Config, errors: &mut Vec<AzulaError>) {
+    *body = std::mem::take(body)
+        .into_iter()
+        .filter_map(|stmt| configured(stmt, config, errors))
+        .collect();
+}
+
+fn configured<'a>(
+    stmt: Statement<'a>,
+    config: &Config,
+    errors: &mut Vec<AzulaError>,
+) -> Option<Statement<'a>> {
+    let (condition, declaration, span) = match stmt {
+        Statement::Conditional(condition, declaration, span) => (condition, declaration, span),
+        stmt => return Some(stmt),
+    };
+
+    let mut holds = true;
+    for (key, value) in condition {
+        match config.value(key) {
+            Some(expected) => holds &= expected == value,
+            None => {
+                errors.push(AzulaError::new(
+                    ErrorType::UnknownConfigKey(key.to_string()),
+                    span.start,
+                    span.end,
+                ));
+                holds = false;
+            }
+        }
+    }
+    if !holds {
+        return None;
+    }
+
+    let declaration = Rc::try_unwrap(declaration).unwrap_or_else(|rc| rc.as_ref().clone());
+    configured(declaration, config, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{lexer::Lexer, parser::Parser};
+
+    #[test]
+    fn test_for_target() {
+        let tests = vec![
+            ("x86_64-unknown-linux-gnu", "linux", "x86_64"),
+            ("aarch64-apple-darwin", "macos", "aarch64"),
+            ("x86_64-pc-windows-msvc", "windows", "x86_64"),
+            ("aarch64-linux-android", "android", "aarch64"),
+            ("thumbv7em-none-eabi", "none", "thumbv7em"),
+        ];
+
+        for (triple, os, arch) in tests {
+            let config = Config::for_target(triple);
+            assert_eq!(config.target_os, os, "{}", triple);
+            assert_eq!(config.target_arch, arch, "{}", triple);
+        }
+    }
+
+    #[test]
+    fn test_configure() {
+        let source = "@if(target_os = \"linux\")
+extern func getpid(): int;
+@if(target_os = \"windows\")
+extern func GetCurrentProcessId(): int;
+@if(target_os = \"linux\", target_arch = \"aarch64\")
+struct Registers { x0: int }
+@if(target_family = \"unix\")
+const UNIX = true;
+func main() {}";
+        let lexer = Lexer::new(source);
+        let mut parser = Parser::new(source, lexer);
+        let mut body = match parser.parse() {
+            Statement::Root(body) => body,
+            _ => unreachable!(),
+        };
+        assert!(parser.errors.is_empty());
+
+        let mut errors = vec![];
+        configure(
+            &mut body,
+            &Config::for_target("x86_64-unknown-linux-gnu"),
+            &mut errors,
+        );
+        assert!(matches!(
+            errors.as_slice(),
+            [AzulaError { error_type: ErrorType::UnknownConfigKey(key), .. }] if key == "target_family"
+        ));
+        assert_eq!(body.len(), 2);
+        assert!(matches!(
+            body[0],
+            Statement::ExternFunction { name: "getpid", .. }
+        ));
+        assert!(matches!(body[1], Statement::Function { name: "main", .. }));
+    }
+}
@@ -0,0 +1,448 @@
+use std::rc::Rc;
+
+use azula_ast::prelude::*;
+use azula_type::prelude::AzulaType;
+
+use crate::printer::print_ast;
+
+const NAMES: &[&str] = &["a", "b", "count", "value", "x1", "next_item"];
+const TYPES: &[&str] = &["Point", "Node"];
+const INTERFACES: &[&str] = &["Printable", "Shape"];
+const ATTRIBUTES: &[&str] = &[
+    "jumptable",
+    "nojumptable",
+    "inline",
+    "noinline",
+    "cold",
+    "interrupt",
+];
+const OPERATORS: &[Operator] = &[
+    Operator::Add,
+    Operator::Sub,
+    Operator::Mul,
+    Operator::Div,
+    Operator::Mod,
+    Operator::Power,
+    Operator::Or,
+    Operator::And,
+    Operator::Eq,
+    Operator::Neq,
+    Operator::Lt,
+    Operator::Lte,
+    Operator::Gt,
+    Operator::Gte,
+    Operator::Shl,
+    Operator::Shr,
+];
+
+/// Generates a random program from the seed and returns its source, for
+/// checking the grammar with round trips through the parser. The program
+/// only has to parse, it won't typecheck
+pub fn generate_ast(seed: u64) -> String {
+    let mut generator = Generator { state: seed };
+
+    let mut body = vec![];
+    for _ in 0..generator.below(4) + 1 {
+        let stmt = generator.top_level();
+        body.push(stmt);
+    }
+
+    print_ast(&Statement::Root(body))
+}
+
+struct Generator {
+    state: u64,
+}
+
+impl Generator {
+    // xorshift, good enough to wander around the grammar
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        self.state
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn pick<T: Clone>(&mut self, values: &[T]) -> T {
+        values[self.below(values.len() as u64) as usize].clone()
+    }
+
+    fn top_level(&mut self) -> Statement<'static> {
+        match self.below(6) {
+            0 => Statement::Struct {
+                name: self.pick(TYPES),
+                generics: (0..self.below(3)).map(|_| self.pick(TYPES)).collect(),
+                attributes: (0..self.below(3))
+                    .map(|_| (self.typ(2), self.pick(NAMES)))
+                    .collect(),
+                defaults: vec![],
+                annotations: vec![],
+                doc: None,
+                span: span(),
+            },
+            1 => Statement::ExternFunction {
+                name: self.pick(NAMES),
+                varargs: self.below(2) == 0,
+                args: (0..self.below(3))
+                    .map(|_| {
+                        let name = (self.below(2) == 0).then(|| self.pick(NAMES));
+                        (self.typ(2), name)
+                    })
+                    .collect(),
+                returns: self.return_type(),
+                span: span(),
+            },
+            2 => Statement::Interface {
+                name: self.pick(INTERFACES),
+                methods: (0..self.below(3))
+                    .map(|_| {
+                        let mut args = vec![(AzulaType::Named("Self".to_string()), "self")];
+                        args.extend((0..self.below(2)).map(|_| (self.typ(2), self.pick(NAMES))));
+                        (self.pick(NAMES), args, self.return_type())
+                    })
+                    .collect(),
+                span: span(),
+            },
+            3 => Statement::Impl {
+                interface: self.pick(INTERFACES),
+                typ: self.typ(2),
+                methods: (0..self.below(3)).map(|_| self.function(false)).collect(),
+                span: span(),
+            },
+            _ => self.function(true),
+        }
+    }
+
+    // Methods can't be pub or have attributes, only top level functions
+    fn function(&mut self, top_level: bool) -> Statement<'static> {
+        let public = top_level && self.below(2) == 0;
+        let attributes = match top_level {
+            true => (0..self.below(2))
+                .map(|_| Attribute::new(self.pick(ATTRIBUTES)))
+                .collect(),
+            false => vec![],
+        };
+        let mut args: Vec<_> = (0..self.below(3))
+            .map(|_| (self.typ(2), self.pick(NAMES)))
+            .collect();
+        let variadic = !args.is_empty() && self.below(3) == 0;
+        if variadic {
+            let (typ, _) = args.last_mut().unwrap();
+            *typ = AzulaType::Slice(Rc::new(typ.clone()));
+        }
+        let defaults = (0..self.below((args.len() - variadic as usize) as u64 + 1))
+            .map(|_| self.expression(2))
+            .collect();
+        // Bounds are printed by name, so the names can't repeat
+        let mut generics = vec![];
+        let mut bounds = vec![];
+        for _ in 0..self.below(3) {
+            let generic = self.pick(TYPES);
+            if generics.contains(&generic) {
+                continue;
+            }
+            generics.push(generic);
+            if self.below(2) == 0 {
+                bounds.push((generic, self.pick(INTERFACES)));
+            }
+        }
+
+        Statement::Function {
+            name: self.pick(NAMES),
+            generics,
+            bounds,
+            args,
+            arg_spans: vec![],
+            defaults,
+            variadic,
+            returns: self.return_type(),
+            body: Rc::new(Statement::Block(self.body(3))),
+            public,
+            attributes,
+            doc: None,
+            span: span(),
+        }
+    }
+
+    fn body(&mut self, depth: usize) -> Vec<Statement<'static>> {
+        (0..self.below(4)).map(|_| self.statement(depth)).collect()
+    }
+
+    fn statement(&mut self, depth: usize) -> Statement<'static> {
+        // Only statements without bodies once the depth runs out
+        let choices = if depth == 0 { 8 } else { 15 };
+        match self.below(choices) {
+            0 => Statement::Return(
+                if self.below(2) == 0 {
+                    Some(self.expression(3))
+                } else {
+                    None
+                },
+                span(),
+            ),
+            1 => Statement::Assign(
+                self.below(2) == 0,
+                self.pick(NAMES).to_string(),
+                if self.below(2) == 0 {
+                    Some(self.typ(2))
+                } else {
+                    None
+                },
+                self.expression(3),
+                vec![],
+                span(),
+            ),
+            2 => Statement::ExpressionStatement(self.expression(3), span()),
+            3 => Statement::Reassign(self.target(), self.expression(3), span()),
+            4 => Statement::Destructure(
+                self.below(2) == 0,
+                (0..self.below(3) + 1).map(|_| self.pick(NAMES)).collect(),
+                self.expression(3),
+                span(),
+            ),
+            5 => Statement::Defer(self.expression(3), span()),
+            6 => Statement::Break(self.optional_name(), span()),
+            7 => Statement::Continue(self.optional_name(), span()),
+            8 => Statement::If(self.expression(2), self.body(depth - 1), span()),
+            9 => {
+                let cond = self.expression(2);
+                let body = self.body(depth - 1);
+                Statement::While(cond, body, self.optional_name(), span())
+            }
+            10 => Statement::IfLet(
+                self.below(2) == 0,
+                self.pick(NAMES),
+                self.expression(2),
+                self.body(depth - 1),
+                span(),
+            ),
+            11 => {
+                let cond = self.expression(2);
+                let body = self.body(depth - 1);
+                Statement::DoWhile(cond, body, self.optional_name(), span())
+            }
+            12 => {
+                let index = self.optional_name();
+                let name = self.pick(NAMES);
+                let iterable = self.expression(2);
+                let body = self.body(depth - 1);
+                Statement::For(index, name, iterable, body, self.optional_name(), span())
+            }
+            13 => Statement::Scope(self.body(depth - 1), span()),
+            _ => {
+                let mut arms: Vec<MatchArm> = (0..self.below(3))
+                    .map(|_| {
+                        let binding = self.optional_name();
+                        (Some(self.typ(1)), binding, self.body(depth - 1), span())
+                    })
+                    .collect();
+                if self.below(2) == 0 {
+                    let binding = self.optional_name();
+                    arms.push((None, binding, self.body(depth - 1), span()));
+                }
+                Statement::Match(self.expression(2), arms, span())
+            }
+        }
+    }
+
+    fn optional_name(&mut self) -> Option<&'static str> {
+        if self.below(2) == 0 {
+            Some(self.pick(NAMES))
+        } else {
+            None
+        }
+    }
+
+    fn target(&mut self) -> ExpressionNode<'static> {
+        let name = node(Expression::Identifier(self.pick(NAMES).to_string()));
+        match self.below(3) {
+            0 => node(Expression::ArrayAccess(
+                Rc::new(name),
+                Rc::new(self.expression(2)),
+            )),
+            1 => node(Expression::StructAccess(
+                Rc::new(name),
+                Rc::new(node(Expression::Identifier(self.pick(NAMES).to_string()))),
+            )),
+            _ => name,
+        }
+    }
+
+    fn expression(&mut self, depth: usize) -> ExpressionNode<'static> {
+        let choices = if depth == 0 { 5 } else { 18 };
+        let expression = match self.below(choices) {
+            0 => Expression::Integer(self.below(1000) as i64),
+            // The parser reads 1.05 as 1.5 and 1.0 as 1, so only generate
+            // fractions that print back the same way
+            1 => Expression::Float(
+                format!("{}.{}", self.below(100), self.below(9) + 1)
+                    .parse()
+                    .unwrap(),
+            ),
+            2 => Expression::Identifier(self.pick(NAMES).to_string()),
+            3 => Expression::Boolean(self.below(2) == 0),
+            4 => Expression::String(
+                self.pick(&["", "hello", "a \"quote\"", "tab\there\n"])
+                    .to_string(),
+            ),
+            5 | 6 => Expression::Infix(
+                Rc::new(self.expression(depth - 1)),
+                self.pick(OPERATORS),
+                Rc::new(self.expression(depth - 1)),
+            ),
+            7 => Expression::FunctionCall {
+                function: Rc::new(node(Expression::Identifier(self.pick(NAMES).to_string()))),
+                args: self.arguments(depth - 1),
+            },
+            8 => Expression::Not(Rc::new(self.expression(depth - 1))),
+            9 => Expression::Pointer(Rc::new(self.expression(depth - 1))),
+            10 => Expression::Array(self.expressions(depth - 1)),
+            11 => Expression::ArrayAccess(
+                Rc::new(self.expression(depth - 1)),
+                Rc::new(self.expression(depth - 1)),
+            ),
+            12 => Expression::StructInitialisation(
+                Rc::new(node(Expression::Identifier(self.pick(TYPES).to_string()))),
+                (0..self.below(3))
+                    .map(|_| (self.pick(NAMES), self.expression(depth - 1)))
+                    .collect(),
+            ),
+            13 => Expression::StructAccess(
+                Rc::new(self.expression(depth - 1)),
+                Rc::new(node(Expression::Identifier(self.pick(NAMES).to_string()))),
+            ),
+            14 => Expression::Cast(Rc::new(self.expression(depth - 1)), self.typ(1)),
+            15 => Expression::Ternary(
+                Rc::new(self.expression(depth - 1)),
+                Rc::new(self.expression(depth - 1)),
+                Rc::new(self.expression(depth - 1)),
+            ),
+            16 => Expression::Negate(Rc::new(self.expression(depth - 1))),
+            _ => match self.below(6) {
+                // A single value in brackets is just that value
+                0 => Expression::Tuple(
+                    (0..self.below(2) + 2)
+                        .map(|_| self.expression(depth - 1))
+                        .collect(),
+                ),
+                1 => Expression::Try(Rc::new(self.expression(depth - 1)), AzulaType::Infer),
+                2 => Expression::SizeOf(self.typ(1)),
+                3 => Expression::Deref(Rc::new(self.expression(depth - 1))),
+                4 => Expression::Range(
+                    Rc::new(self.expression(depth - 1)),
+                    Rc::new(self.expression(depth - 1)),
+                ),
+                _ => Expression::Closure {
+                    args: (0..self.below(3))
+                        .map(|_| (self.typ(1), self.pick(NAMES)))
+                        .collect(),
+                    body: Rc::new(self.expression(depth - 1)),
+                    captures: vec![],
+                },
+            },
+        };
+
+        node(expression)
+    }
+
+    fn expressions(&mut self, depth: usize) -> Vec<ExpressionNode<'static>> {
+        (0..self.below(3)).map(|_| self.expression(depth)).collect()
+    }
+
+    // Any named arguments come after the positional ones
+    fn arguments(&mut self, depth: usize) -> Vec<ExpressionNode<'static>> {
+        let mut args = self.expressions(depth);
+        for _ in 0..self.below(3) {
+            let value = self.expression(depth);
+            args.push(node(Expression::NamedArgument(
+                self.pick(NAMES),
+                Rc::new(value),
+            )));
+        }
+        args
+    }
+
+    fn return_type(&mut self) -> AzulaType<'static> {
+        if self.below(3) == 0 {
+            AzulaType::Void
+        } else {
+            self.typ(2)
+        }
+    }
+
+    fn typ(&mut self, depth: usize) -> AzulaType<'static> {
+        let choices = if depth == 0 { 9 } else { 17 };
+        match self.below(choices) {
+            0 => AzulaType::Int,
+            1 => AzulaType::SizedSignedInt(self.pick(&[8, 16, 32, 64])),
+            2 => AzulaType::SizedUnsignedInt(self.pick(&[8, 16, 32, 64])),
+            3 => AzulaType::SizedFloat(self.pick(&[32, 64])),
+            4 => AzulaType::Str,
+            5 => AzulaType::Float,
+            6 => AzulaType::Bool,
+            7 => AzulaType::Any,
+            8 => AzulaType::Named(self.pick(TYPES).to_string()),
+            9 => AzulaType::Pointer(Rc::new(self.typ(depth - 1))),
+            10 => AzulaType::Array(Rc::new(self.typ(depth - 1)), None),
+            11 => AzulaType::Array(Rc::new(self.typ(depth - 1)), Some(self.below(10) as usize)),
+            12 => AzulaType::Optional(Rc::new(self.typ(depth - 1))),
+            13 => AzulaType::Result(Rc::new(self.typ(depth - 1))),
+            14 => AzulaType::Generic(
+                self.pick(TYPES).to_string(),
+                (0..self.below(2) + 1)
+                    .map(|_| self.typ(depth - 1))
+                    .collect(),
+            ),
+            15 => AzulaType::Slice(Rc::new(self.typ(depth - 1))),
+            _ => AzulaType::Tuple(vec![self.typ(depth - 1), self.typ(depth - 1)]),
+        }
+    }
+}
+
+fn node(expression: Expression<'static>) -> ExpressionNode<'static> {
+    ExpressionNode {
+        expression,
+        typed: AzulaType::Infer,
+        span: span(),
+    }
+}
+
+fn span() -> Span {
+    Span { start: 0, end: 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_round_trip() {
+        for seed in 0..500 {
+            let source = generate_ast(seed);
+            let lexer: Lexer = source.as_str().into();
+            let mut parser = Parser::new(&source, lexer);
+            let ast = parser.parse();
+
+            assert!(
+                parser.errors.is_empty(),
+                "seed {} failed to parse {:?}:\n{}",
+                seed,
+                parser.errors,
+                source
+            );
+            assert_eq!(print_ast(&ast), source, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn test_generate_is_deterministic() {
+        assert_eq!(generate_ast(42), generate_ast(42));
+        assert_ne!(generate_ast(1), generate_ast(2));
+    }
+}
@@ -3,6 +3,7 @@ use std::{iter::Peekable, str::Chars};
 use crate::token::*;
 
 /// Lexer transforms a &str into Tokens we can parse
+#[derive(Clone)]
 pub struct Lexer<'a> {
     pub input: &'a str,
     pub peekable: Peekable<Chars<'a>>,
@@ -13,11 +14,21 @@ pub struct Lexer<'a> {
 impl<'a> Lexer<'a> {
     /// Create a new Lexer from a given input str
     pub fn new(input: &'a str) -> Self {
-        Self {
+        let mut lexer = Self {
             input,
             peekable: input.chars().peekable(),
             index: 0,
+        };
+
+        // A #! line lets the file be run directly, the newline is left so
+        // the positions after it don't change
+        if input.starts_with("#!") {
+            while !matches!(lexer.peekable.peek(), None | Some('\n')) {
+                lexer.next();
+            }
         }
+
+        lexer
     }
 
     fn next(&mut self) -> Option<char> {
@@ -36,23 +47,87 @@ impl<'a> Lexer<'a> {
                 ']' => Token::new(TokenKind::SquareClose, start, self.index),
                 '{' => Token::new(TokenKind::BraceOpen, start, self.index),
                 '}' => Token::new(TokenKind::BraceClose, start, self.index),
-                '.' => Token::new(TokenKind::Dot, start, self.index),
+                '.' => {
+                    let mut ahead = self.peekable.clone();
+                    if ahead.next() == Some('.') && ahead.next() == Some('.') {
+                        self.next();
+                        self.next();
+                        Token::new(TokenKind::Ellipsis, start, self.index)
+                    } else if self.peekable.peek() == Some(&'.') {
+                        self.next();
+                        Token::new(TokenKind::DotDot, start, self.index)
+                    } else {
+                        Token::new(TokenKind::Dot, start, self.index)
+                    }
+                }
                 ',' => Token::new(TokenKind::Comma, start, self.index),
                 ';' => Token::new(TokenKind::SemiColon, start, self.index),
                 ':' => Token::new(TokenKind::Colon, start, self.index),
-                '+' => Token::new(TokenKind::Plus, start, self.index),
-                '-' => Token::new(TokenKind::Minus, start, self.index),
+                '@' => Token::new(TokenKind::At, start, self.index),
+                // Whether this is a ternary or the try operator is left to the parser
+                '?' => Token::new(TokenKind::Question, start, self.index),
+                '+' => match self.peekable.peek() {
+                    Some('+') => {
+                        self.next();
+                        Token::new(TokenKind::Increment, start, self.index)
+                    }
+                    _ => Token::new(TokenKind::Plus, start, self.index),
+                },
+                '-' => match self.peekable.peek() {
+                    Some('>') => {
+                        self.next();
+                        Token::new(TokenKind::Arrow, start, self.index)
+                    }
+                    Some('-') => {
+                        self.next();
+                        Token::new(TokenKind::Decrement, start, self.index)
+                    }
+                    _ => Token::new(TokenKind::Minus, start, self.index),
+                },
                 '/' => match self.peekable.peek() {
                     Some('/') => {
-                        // We found a comment - skip for now (maybe use for docs later)
+                        let mut slashes = 1;
                         while let Some(val) = self.peekable.peek() {
                             match val {
                                 '\n' => break,
-                                _ => self.next(),
+                                '/' if slashes == self.index - start => {
+                                    slashes += 1;
+                                    self.next();
+                                }
+                                _ => {
+                                    self.next();
+                                }
                             };
                         }
 
-                        Token::new(TokenKind::Comment, start, self.index)
+                        // Exactly three slashes document the next declaration
+                        if slashes == 3 {
+                            Token::new(TokenKind::DocComment, start, self.index)
+                        } else {
+                            Token::new(TokenKind::Comment, start, self.index)
+                        }
+                    }
+                    Some('*') => {
+                        // Block comments are dropped entirely so they can sit
+                        // anywhere whitespace can, an unterminated one runs to
+                        // the end of the input
+                        self.next();
+                        let mut previous = None;
+                        loop {
+                            match self.next() {
+                                Some('/') if previous == Some('*') => break,
+                                Some(c) => previous = Some(c),
+                                None => {
+                                    return Some(Token::new(
+                                        TokenKind::UnknownToken,
+                                        start,
+                                        self.index - 1,
+                                    ))
+                                }
+                            }
+                        }
+
+                        return self.next_token();
                     }
                     _ => Token::new(TokenKind::Slash, start, self.index),
                 },
@@ -68,6 +143,10 @@ impl<'a> Lexer<'a> {
                         self.next();
                         Token::new(TokenKind::Equal, start, self.index)
                     }
+                    Some('>') => {
+                        self.next();
+                        Token::new(TokenKind::FatArrow, start, self.index)
+                    }
                     _ => Token::new(TokenKind::Assign, start, self.index),
                 },
                 '!' => match self.peekable.peek() {
@@ -96,6 +175,10 @@ impl<'a> Lexer<'a> {
                         self.next();
                         Token::new(TokenKind::LessEqual, start, self.index)
                     }
+                    Some('<') => {
+                        self.next();
+                        Token::new(TokenKind::ShiftLeft, start, self.index)
+                    }
                     _ => Token::new(TokenKind::Less, start, self.index),
                 },
                 '>' => match self.peekable.peek() {
@@ -103,9 +186,35 @@ impl<'a> Lexer<'a> {
                         self.next();
                         Token::new(TokenKind::GreaterEqual, start, self.index)
                     }
+                    Some('>') => {
+                        self.next();
+                        Token::new(TokenKind::ShiftRight, start, self.index)
+                    }
                     _ => Token::new(TokenKind::Greater, start, self.index),
                 },
                 '%' => Token::new(TokenKind::Modulo, start, self.index),
+                '"' if self.input[self.index..].starts_with("\"\"") => {
+                    self.next();
+                    self.next();
+                    // It ends at the last three of a run of quotes, so it can
+                    // end with one
+                    let contents = self.index;
+                    while self.peekable.peek().is_some()
+                        && !(self.input[self.index..].starts_with("\"\"\"")
+                            && !self.input[self.index..].starts_with("\"\"\"\""))
+                    {
+                        self.next();
+                    }
+
+                    let str = &self.input[contents..self.index];
+                    // An unterminated one runs to the end of the file
+                    for _ in 0..3 {
+                        if self.peekable.peek().is_some() {
+                            self.next();
+                        }
+                    }
+                    Token::new(TokenKind::MultilineString(str), start, self.index)
+                }
                 '"' => {
                     while let Some(val) = self.peekable.peek() {
                         match val {
@@ -147,6 +256,7 @@ impl<'a> Lexer<'a> {
                         self.index,
                     )
                 }
+                'r' if self.raw_string_hashes().is_some() => self.raw_string(start),
                 'a'..='z' | 'A'..='Z' | '_' => {
                     while let Some(val) = self.peekable.peek() {
                         match val {
@@ -165,6 +275,51 @@ impl<'a> Lexer<'a> {
         None
     }
 
+    // The number of #s between the r and the opening quote of a raw string,
+    // None if it isn't one
+    fn raw_string_hashes(&self) -> Option<usize> {
+        let mut ahead = self.peekable.clone();
+        let mut hashes = 0;
+        loop {
+            match ahead.next() {
+                Some('#') => hashes += 1,
+                Some('"') => return Some(hashes),
+                _ => return None,
+            }
+        }
+    }
+
+    // r"..." ends at the first quote, r#"..."# at the first quote followed by
+    // as many #s as it started with, so it can hold quotes
+    fn raw_string(&mut self, start: usize) -> Token<'a> {
+        let hashes = self.raw_string_hashes().unwrap();
+        for _ in 0..=hashes {
+            self.next();
+        }
+
+        let contents = self.index;
+        let mut end = None;
+        while let Some(val) = self.next() {
+            let closed = self
+                .peekable
+                .clone()
+                .take(hashes)
+                .filter(|c| *c == '#')
+                .count()
+                == hashes;
+            if val == '"' && closed {
+                end = Some(self.index - 1);
+                for _ in 0..hashes {
+                    self.next();
+                }
+                break;
+            }
+        }
+
+        let str = &self.input[contents..end.unwrap_or(self.index)];
+        Token::new(TokenKind::RawString(str), start, self.index)
+    }
+
     fn skip_whitespace(&mut self) {
         while let Some(val) = self.peekable.peek() {
             match val {
@@ -186,7 +341,19 @@ impl<'a> Lexer<'a> {
             "extern" => Token::new(TokenKind::Extern, start, self.index),
             "varargs" => Token::new(TokenKind::VarArgs, start, self.index),
             "while" => Token::new(TokenKind::While, start, self.index),
+            "do" => Token::new(TokenKind::Do, start, self.index),
+            "break" => Token::new(TokenKind::Break, start, self.index),
+            "continue" => Token::new(TokenKind::Continue, start, self.index),
             "struct" => Token::new(TokenKind::Struct, start, self.index),
+            "match" => Token::new(TokenKind::Match, start, self.index),
+            "as" => Token::new(TokenKind::As, start, self.index),
+            "defer" => Token::new(TokenKind::Defer, start, self.index),
+            "interface" => Token::new(TokenKind::Interface, start, self.index),
+            "impl" => Token::new(TokenKind::Impl, start, self.index),
+            "for" => Token::new(TokenKind::For, start, self.index),
+            "in" => Token::new(TokenKind::In, start, self.index),
+            "import" => Token::new(TokenKind::Import, start, self.index),
+            "pub" => Token::new(TokenKind::Pub, start, self.index),
             _ => Token::new(TokenKind::Identifier(value), start, self.index),
         }
     }
@@ -195,11 +362,7 @@ impl<'a> Lexer<'a> {
 // Allows us to call .into() on a &str
 impl<'a> From<&'a str> for Lexer<'a> {
     fn from(source: &'a str) -> Self {
-        Self {
-            input: source,
-            peekable: source.chars().peekable(),
-            index: 0,
-        }
+        Self::new(source)
     }
 }
 
@@ -261,6 +424,37 @@ mod tests {
             ":",
             vec![Token::new(TokenKind::Colon, 0, 1)],
         ),
+        question: (
+            "? a",
+            vec![
+                Token::new(TokenKind::Question, 0, 1),
+                Token::new(TokenKind::Identifier("a"), 2, 3),
+            ],
+        ),
+        try_operator: (
+            "a? )",
+            vec![
+                Token::new(TokenKind::Identifier("a"), 0, 1),
+                Token::new(TokenKind::Question, 1, 2),
+                Token::new(TokenKind::BracketClose, 3, 4),
+            ],
+        ),
+        ellipsis: (
+            "int....",
+            vec![
+                Token::new(TokenKind::Identifier("int"), 0, 3),
+                Token::new(TokenKind::Ellipsis, 3, 6),
+                Token::new(TokenKind::Dot, 6, 7),
+            ],
+        ),
+        range: (
+            "0..n",
+            vec![
+                Token::new(TokenKind::Integer(0), 0, 1),
+                Token::new(TokenKind::DotDot, 1, 3),
+                Token::new(TokenKind::Identifier("n"), 3, 4),
+            ],
+        ),
         plus: (
             "+",
             vec![Token::new(TokenKind::Plus, 0, 1)],
@@ -269,6 +463,10 @@ mod tests {
             "-",
             vec![Token::new(TokenKind::Minus, 0, 1)],
         ),
+        arrow: (
+            "->",
+            vec![Token::new(TokenKind::Arrow, 0, 2)],
+        ),
         slash: (
             "/",
             vec![Token::new(TokenKind::Slash, 0, 1)],
@@ -285,6 +483,10 @@ mod tests {
             "=",
             vec![Token::new(TokenKind::Assign, 0, 1)],
         ),
+        fat_arrow: (
+            "=>",
+            vec![Token::new(TokenKind::FatArrow, 0, 2)],
+        ),
         equal: (
             "==",
             vec![Token::new(TokenKind::Equal, 0, 2)],
@@ -325,10 +527,28 @@ mod tests {
             ">=",
             vec![Token::new(TokenKind::GreaterEqual, 0, 2)],
         ),
+        shift_left: (
+            "<<",
+            vec![Token::new(TokenKind::ShiftLeft, 0, 2)],
+        ),
+        shift_right: (
+            ">>",
+            vec![Token::new(TokenKind::ShiftRight, 0, 2)],
+        ),
         and: (
             "&&",
             vec![Token::new(TokenKind::And, 0, 2)],
         ),
+        increment: (
+            "++ -- + - ->",
+            vec![
+                Token::new(TokenKind::Increment, 0, 2),
+                Token::new(TokenKind::Decrement, 3, 5),
+                Token::new(TokenKind::Plus, 6, 7),
+                Token::new(TokenKind::Minus, 8, 9),
+                Token::new(TokenKind::Arrow, 10, 12),
+            ],
+        ),
         modulo: (
             "%",
             vec![Token::new(TokenKind::Modulo, 0, 1)],
@@ -337,6 +557,21 @@ mod tests {
             "\"test\" \"another_test$$%\"",
             vec![Token::new(TokenKind::String("test"), 0, 6), Token::new(TokenKind::String("another_test$$%"), 7, 24)],
         ),
+        raw_string: (
+            "r\"C:\\dir\" r#\"say \"hi\"\"# r",
+            vec![
+                Token::new(TokenKind::RawString("C:\\dir"), 0, 9),
+                Token::new(TokenKind::RawString("say \"hi\""), 10, 23),
+                Token::new(TokenKind::Identifier("r"), 24, 25),
+            ],
+        ),
+        multiline_string: (
+            "\"\"\"\n  a \"b\"\n  \"\"\" \"\"",
+            vec![
+                Token::new(TokenKind::MultilineString("\n  a \"b\"\n  "), 0, 17),
+                Token::new(TokenKind::String(""), 18, 20),
+            ],
+        ),
         char: (
             "'a' '$'",
             vec![Token::new(TokenKind::Char("a"), 0, 3), Token::new(TokenKind::Char("$"), 4, 7)],
@@ -352,6 +587,18 @@ mod tests {
             ",
             vec![Token::new(TokenKind::Comment, 13, 25), Token::new(TokenKind::Identifier("test"), 38, 42)],
         ),
+        block_comment: (
+            "a /* one\ntwo */ b /**/ c",
+            vec![
+                Token::new(TokenKind::Identifier("a"), 0, 1),
+                Token::new(TokenKind::Identifier("b"), 16, 17),
+                Token::new(TokenKind::Identifier("c"), 23, 24),
+            ],
+        ),
+        unterminated_block_comment: (
+            "a /* b",
+            vec![Token::new(TokenKind::Identifier("a"), 0, 1), Token::new(TokenKind::UnknownToken, 2, 6)],
+        ),
         identifier: (
             "identifier_test",
             vec![Token::new(TokenKind::Identifier("identifier_test"), 0, 15)],
@@ -388,9 +635,65 @@ mod tests {
             "while",
             vec![Token::new(TokenKind::While, 0, 5)],
         ),
+        do_stmt: (
+            "do",
+            vec![Token::new(TokenKind::Do, 0, 2)],
+        ),
         struct_stmt: (
             "struct",
             vec![Token::new(TokenKind::Struct, 0, 6)],
         ),
+        match_stmt: (
+            "match",
+            vec![Token::new(TokenKind::Match, 0, 5)],
+        ),
+        as_stmt: (
+            "as",
+            vec![Token::new(TokenKind::As, 0, 2)],
+        ),
+        defer_stmt: (
+            "defer",
+            vec![Token::new(TokenKind::Defer, 0, 5)],
+        ),
+        interface_stmt: (
+            "interface",
+            vec![Token::new(TokenKind::Interface, 0, 9)],
+        ),
+        impl_stmt: (
+            "impl for",
+            vec![Token::new(TokenKind::Impl, 0, 4), Token::new(TokenKind::For, 5, 8)],
+        ),
+        for_in: (
+            "for x in",
+            vec![
+                Token::new(TokenKind::For, 0, 3),
+                Token::new(TokenKind::Identifier("x"), 4, 5),
+                Token::new(TokenKind::In, 6, 8),
+            ],
+        ),
+        import_stmt: (
+            "import",
+            vec![Token::new(TokenKind::Import, 0, 6)],
+        ),
+        attribute: (
+            "@inline",
+            vec![Token::new(TokenKind::At, 0, 1), Token::new(TokenKind::Identifier("inline"), 1, 7)],
+        ),
+        pub_keyword: (
+            "pub",
+            vec![Token::new(TokenKind::Pub, 0, 3)],
+        ),
+        shebang: (
+            "#!/usr/bin/env azula run\n(",
+            vec![Token::new(TokenKind::BracketOpen, 25, 26)],
+        ),
+        doc_comment: (
+            "/// docs\n//// not docs\n// a/// b",
+            vec![
+                Token::new(TokenKind::DocComment, 0, 8),
+                Token::new(TokenKind::Comment, 9, 22),
+                Token::new(TokenKind::Comment, 23, 32),
+            ],
+        ),
     }
 }
@@ -1,9 +1,19 @@
+mod config;
+mod generator;
 mod lexer;
+mod modules;
 mod parser;
+mod printer;
+mod script;
 mod token;
 
 pub mod prelude {
+    pub use crate::config::Config;
+    pub use crate::generator::generate_ast;
     pub use crate::lexer::Lexer;
+    pub use crate::modules::{resolve_imports, ModuleLoader};
     pub use crate::parser::Parser;
+    pub use crate::printer::{print_ast, print_expression, print_type};
+    pub use crate::script::wrap_script;
     pub use crate::token::{Span, Token, TokenKind};
 }
@@ -0,0 +1,169 @@
+use std::collections::HashSet;
+
+use azula_ast::prelude::*;
+use azula_error::prelude::*;
+
+use crate::{
+    config::{configure, Config},
+    lexer::Lexer,
+    parser::Parser,
+};
+
+/// Loads a module by name, returning its file name and source
+pub type ModuleLoader<'a, 'b> = dyn FnMut(&str) -> Result<(String, &'a str), String> + 'b;
+
+/// Parses the files a tree imports, and the files they import, filling in
+/// each import with the declarations of its file. The `@if` declarations of
+/// each file are kept or removed for `config` first, so imports can depend
+/// on the target. Each file is added to
+/// `sources` with its spans shifted to fit, and so are its parse errors and
+/// ignore directives, which are returned. A module imported a second time is
+/// left empty, as its declarations are already in the tree, and an imported
+/// file's main is dropped since the program's is in the first file
+pub fn resolve_imports<'a>(
+    root: &mut Statement<'a>,
+    module: &str,
+    config: &Config,
+    sources: &mut SourceMap<'a>,
+    load: &mut ModuleLoader<'a, '_>,
+) -> (Vec<AzulaError>, Vec<IgnoreDirective>) {
+    let mut resolver = Resolver {
+        config,
+        sources,
+        load,
+        imported: HashSet::from([module.to_string()]),
+        errors: vec![],
+        ignores: vec![],
+    };
+    if let Statement::Root(body) = root {
+        configure(body, config, &mut resolver.errors);
+        resolver.resolve(body);
+    }
+
+    (resolver.errors, resolver.ignores)
+}
+
+struct Resolver<'a, 'b, 'c> {
+    config: &'b Config,
+    sources: &'b mut SourceMap<'a>,
+    load: &'b mut ModuleLoader<'a, 'c>,
+    imported: HashSet<String>,
+    errors: Vec<AzulaError>,
+    ignores: Vec<IgnoreDirective>,
+}
+
+impl<'a, 'b, 'c> Resolver<'a, 'b, 'c> {
+    fn resolve(&mut self, body: &mut [Statement<'a>]) {
+        for stmt in body {
+            if let Statement::Import(name, declarations, span) = stmt {
+                if self.imported.insert(name.to_string()) {
+                    *declarations = self.import(name, span);
+                }
+            }
+        }
+    }
+
+    fn import(&mut self, name: &str, span: &Span) -> Vec<Statement<'a>> {
+        let (file, source) = match (self.load)(name) {
+            Ok(file) => file,
+            Err(reason) => {
+                self.errors.push(AzulaError::new(
+                    ErrorType::ModuleNotFound(name.to_string(), reason),
+                    span.start,
+                    span.end,
+                ));
+                return vec![];
+            }
+        };
+        let base = self.sources.add(file, source);
+
+        let lexer = Lexer::new(source);
+        let mut parser = Parser::new(source, lexer);
+        let mut root = parser.parse();
+        root.shift_spans(base);
+        for mut error in parser.errors {
+            error.start += base;
+            error.end += base;
+            self.errors.push(error);
+        }
+        for mut ignore in parser.ignores {
+            ignore.start += base;
+            ignore.end += base;
+            self.ignores.push(ignore);
+        }
+
+        let mut body = match root {
+            Statement::Root(body) => body,
+            _ => unreachable!(),
+        };
+        configure(&mut body, self.config, &mut self.errors);
+        body.retain(|stmt| !matches!(stmt, Statement::Function { name: "main", .. }));
+        self.resolve(&mut body);
+
+        body
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn test_resolve_imports() {
+        let main = "import b;\nfunc main() { f(); }";
+        let files = HashMap::from([
+            ("b", "import c;\nfunc f() {}\nfunc main() {}"),
+            ("c", "import b;\nimport missing;\nfunc g() {}"),
+        ]);
+
+        let mut sources = SourceMap::new();
+        sources.add("main.azl".to_string(), main);
+        let mut root = Parser::new(main, Lexer::new(main)).parse();
+        let (errors, _) = resolve_imports(
+            &mut root,
+            "main",
+            &Config::for_target("x86_64-unknown-linux-gnu"),
+            &mut sources,
+            &mut |name| {
+                files
+                    .get(name)
+                    .map(|source| (format!("{}.azl", name), *source))
+                    .ok_or_else(|| "no such file".to_string())
+            },
+        );
+
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            &errors[0].error_type,
+            ErrorType::ModuleNotFound(module, _) if module == "missing"
+        ));
+        assert_eq!(sources.file(errors[0].start).name, "c.azl");
+
+        let body = match &root {
+            Statement::Root(body) => body,
+            stmt => panic!("expected root, got {:?}", stmt),
+        };
+        let b = match &body[0] {
+            Statement::Import("b", b, _) => b,
+            stmt => panic!("expected import, got {:?}", stmt),
+        };
+        // b's main is dropped, c's import of b is left empty
+        assert_eq!(b.len(), 2);
+        match &b[1] {
+            Statement::Function { name, span, .. } => {
+                assert_eq!(*name, "f");
+                assert_eq!(sources.file(span.start).name, "b.azl");
+            }
+            stmt => panic!("expected function, got {:?}", stmt),
+        }
+        match &b[0] {
+            Statement::Import("c", c, _) => {
+                assert_eq!(c.len(), 3);
+                assert!(matches!(&c[0], Statement::Import("b", b, _) if b.is_empty()));
+            }
+            stmt => panic!("expected import, got {:?}", stmt),
+        }
+    }
+}
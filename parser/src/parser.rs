@@ -12,21 +12,45 @@ use crate::{
 type OperatorPrecedence = u8;
 
 const LOWEST: OperatorPrecedence = 0;
-const COMPARISON: OperatorPrecedence = 1;
-const EQUALS: OperatorPrecedence = 2;
-const LESS_GREATER: OperatorPrecedence = 3;
-const SUM: OperatorPrecedence = 4;
-const PRODUCT: OperatorPrecedence = 5;
-const PREFIX: OperatorPrecedence = 6;
-const STRUCT_INIT: OperatorPrecedence = 7;
-const CALL: OperatorPrecedence = 8;
-const ACCESS: OperatorPrecedence = 9;
+const TERNARY: OperatorPrecedence = 1;
+const RANGE: OperatorPrecedence = 2;
+const COMPARISON: OperatorPrecedence = 3;
+const EQUALS: OperatorPrecedence = 4;
+const LESS_GREATER: OperatorPrecedence = 5;
+const SHIFT: OperatorPrecedence = 6;
+const SUM: OperatorPrecedence = 7;
+const PRODUCT: OperatorPrecedence = 8;
+const CAST: OperatorPrecedence = 9;
+const PREFIX: OperatorPrecedence = 10;
+const STRUCT_INIT: OperatorPrecedence = 11;
+const CALL: OperatorPrecedence = 12;
+const ACCESS: OperatorPrecedence = 13;
+
+// Extra parts that function parameters can have over struct attributes
+#[derive(Default)]
+struct FunctionParams<'a> {
+    spans: Vec<Span>,
+    defaults: Vec<ExpressionNode<'a>>,
+    variadic: bool,
+}
+
+// Where the parts after each identifier in a typed identifier list go
+enum ListExtras<'p, 'a> {
+    None,
+    Params(&'p mut FunctionParams<'a>),
+    // Any struct attribute can have a default, they're filled in by name
+    Defaults(&'p mut Vec<(&'a str, ExpressionNode<'a>)>),
+}
 
 pub struct Parser<'a> {
     source: &'a str,
     lexer: Peekable<Lexer<'a>>,
+    pending_ignores: Vec<Lint>,
+    // /// lines waiting for the declaration they document
+    pending_docs: Vec<&'a str>,
 
     pub errors: Vec<AzulaError>,
+    pub ignores: Vec<IgnoreDirective>,
 }
 
 impl<'a> Parser<'a> {
@@ -34,7 +58,10 @@ impl<'a> Parser<'a> {
         Self {
             source,
             lexer: lexer.peekable(),
+            pending_ignores: vec![],
+            pending_docs: vec![],
             errors: vec![],
+            ignores: vec![],
         }
     }
 
@@ -43,6 +70,10 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_statement(&mut self) -> Option<Statement<'a>> {
+        if self.is_label() {
+            return self.parse_labeled();
+        }
+
         let token = self.lexer.peek();
         if token.is_none() {
             return None;
@@ -58,12 +89,33 @@ impl<'a> Parser<'a> {
             TokenKind::Const => self.parse_assign(false),
             TokenKind::If => self.parse_if(),
             TokenKind::While => self.parse_while(),
+            TokenKind::For => self.parse_for(),
+            TokenKind::Do => self.parse_do_while(),
+            TokenKind::BraceOpen => self.parse_scope(),
+            TokenKind::Break | TokenKind::Continue => self.parse_loop_control(),
+            TokenKind::Match => self.parse_match(),
+            TokenKind::Defer => self.parse_defer(),
+            TokenKind::Interface => self.parse_interface(),
+            TokenKind::Impl => self.parse_impl(),
+            TokenKind::Import => self.parse_import(),
+            TokenKind::Pub => self.parse_pub(),
+            TokenKind::At => self.parse_attributes(),
             TokenKind::SemiColon => {
                 self.lexer.next();
                 None
             }
             TokenKind::Comment => {
-                self.lexer.next();
+                let comment = self.lexer.next().unwrap();
+                if let Some(lints) =
+                    IgnoreDirective::parse(&self.source[comment.span.start..comment.span.end])
+                {
+                    self.pending_ignores.extend(lints);
+                }
+                None
+            }
+            TokenKind::DocComment => {
+                let comment = self.lexer.next().unwrap();
+                self.push_doc(comment);
                 None
             }
             _ => {
@@ -72,11 +124,18 @@ impl<'a> Parser<'a> {
                     None => return None,
                 };
 
-                if self.lexer.peek().unwrap().kind == TokenKind::Assign {
-                    return self.parse_reassign(expr.clone());
+                match self.lexer.peek().unwrap().kind {
+                    TokenKind::Assign => return self.parse_reassign(expr.clone()),
+                    TokenKind::Increment => return self.parse_increment(expr, Operator::Add),
+                    TokenKind::Decrement => return self.parse_increment(expr, Operator::Sub),
+                    _ => {}
                 }
 
-                if !self.expect_peek(TokenKind::SemiColon) {
+                // The last expression in a block can leave out its semicolon,
+                // as when it's the value a function returns
+                if self.lexer.peek().unwrap().kind != TokenKind::BraceClose
+                    && !self.expect_peek(TokenKind::SemiColon)
+                {
                     return None;
                 }
 
@@ -92,20 +151,56 @@ impl<'a> Parser<'a> {
             if self.lexer.peek().unwrap().kind == ending {
                 break;
             }
+            // Directives apply to the next statement in this block, so the inner
+            // blocks of that statement must not pick them up
+            let mut ignores = std::mem::take(&mut self.pending_ignores);
             let stmt = self.parse_statement();
             tok = self.lexer.peek().is_some();
             if stmt.is_none() {
                 // This could cause issues - not sure how it would occur
+                ignores.append(&mut self.pending_ignores);
+                self.pending_ignores = ignores;
                 continue;
             }
-            statements.push(stmt.unwrap());
+            let stmt = stmt.unwrap();
+            // Docs only belong to the declaration straight after them
+            self.pending_docs.clear();
+            if !ignores.is_empty() {
+                if let Some(span) = stmt.span() {
+                    self.ignores.push(IgnoreDirective {
+                        lints: ignores,
+                        start: span.start,
+                        end: span.end,
+                    });
+                }
+            }
+            statements.push(stmt);
         }
+        self.pending_ignores.clear();
+        self.pending_docs.clear();
         statements
     }
 
+    fn push_doc(&mut self, comment: Token<'a>) {
+        let line = &self.source[comment.span.start + 3..comment.span.end];
+        self.pending_docs
+            .push(line.strip_prefix(' ').unwrap_or(line));
+    }
+
+    // The docs written above the declaration being parsed
+    fn take_doc(&mut self) -> Option<String> {
+        if self.pending_docs.is_empty() {
+            return None;
+        }
+        let doc = self.pending_docs.join("\n");
+        self.pending_docs.clear();
+        Some(doc)
+    }
+
     fn parse_function(&mut self) -> Option<Statement<'a>> {
         // func
         let start_token = self.lexer.next().unwrap();
+        let doc = self.take_doc();
 
         // Parse name of the function
         let tok = self.lexer.next();
@@ -120,11 +215,21 @@ impl<'a> Parser<'a> {
             _ => return None,
         };
 
+        let mut generics = vec![];
+        let mut bounds = vec![];
+        if self.lexer.peek().map(|tok| &tok.kind) == Some(&TokenKind::Less) {
+            generics = self.parse_type_params(Some(&mut bounds))?;
+        }
+
         // Parse function arguments
         let mut args = vec![];
+        let mut params = FunctionParams::default();
         if let Some(tok) = self.lexer.peek() {
             if tok.kind == TokenKind::BracketOpen {
-                args = self.parse_typed_identifier_list(TokenKind::BracketOpen);
+                args = self.parse_typed_identifier_list(
+                    TokenKind::BracketOpen,
+                    ListExtras::Params(&mut params),
+                );
             }
         }
 
@@ -154,9 +259,17 @@ impl<'a> Parser<'a> {
 
         Some(Statement::Function {
             name: ident,
+            generics,
+            bounds,
             args,
+            arg_spans: params.spans,
+            defaults: params.defaults,
+            variadic: params.variadic,
             returns,
             body: Rc::new(Statement::Block(body)),
+            public: false,
+            attributes: vec![],
+            doc,
             span: Span {
                 start: start_token.span.start,
                 end: end_token.span.end,
@@ -164,6 +277,38 @@ impl<'a> Parser<'a> {
         })
     }
 
+    // <T, U>, or <T: Interface> where bounds are allowed
+    fn parse_type_params(
+        &mut self,
+        mut bounds: Option<&mut Vec<(&'a str, &'a str)>>,
+    ) -> Option<Vec<&'a str>> {
+        self.lexer.next();
+
+        let mut generics = vec![];
+        loop {
+            let name = self.parse_identifier()?;
+            generics.push(name);
+            if let Some(bounds) = bounds.as_deref_mut() {
+                if self.lexer.peek().map(|tok| &tok.kind) == Some(&TokenKind::Colon) {
+                    self.lexer.next();
+                    bounds.push((name, self.parse_identifier()?));
+                }
+            }
+
+            if self.lexer.peek().map(|tok| &tok.kind) != Some(&TokenKind::Comma) {
+                break;
+            }
+            self.lexer.next();
+        }
+
+        if !self.expect_peek(TokenKind::Greater) {
+            return None;
+        }
+        self.lexer.next();
+
+        Some(generics)
+    }
+
     fn parse_extern_function(&mut self) -> Option<Statement<'a>> {
         // extern
         let start_token = self.lexer.next().unwrap();
@@ -195,7 +340,7 @@ impl<'a> Parser<'a> {
         let mut args = vec![];
         if let Some(tok) = self.lexer.peek() {
             if tok.kind == TokenKind::BracketOpen {
-                args = self.parse_type_list(TokenKind::BracketOpen);
+                args = self.parse_extern_args()?;
             }
         }
 
@@ -227,9 +372,56 @@ impl<'a> Parser<'a> {
         })
     }
 
+    // (name: type, type), the names are optional
+    fn parse_extern_args(&mut self) -> Option<Vec<(AzulaType<'a>, Option<&'a str>)>> {
+        // (
+        self.lexer.next();
+
+        let mut args = vec![];
+        while self.lexer.peek().map(|tok| &tok.kind) != Some(&TokenKind::BracketClose) {
+            if !args.is_empty() {
+                if !self.expect_peek(TokenKind::Comma) {
+                    return None;
+                }
+                self.lexer.next();
+            }
+
+            let name = match self.lexer.peek().map(|tok| &tok.kind) {
+                Some(TokenKind::Identifier(name)) => Some(*name),
+                Some(_) => None,
+                None => {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::UnexpectedEOF,
+                        self.source.len() - 2,
+                        self.source.len() - 1,
+                    ));
+                    return None;
+                }
+            };
+            // A lone identifier is a named type unless a colon follows it
+            let typ = self.parse_type();
+            match name {
+                Some(name)
+                    if typ == AzulaType::Named(name.to_string())
+                        && self.lexer.peek().map(|tok| &tok.kind) == Some(&TokenKind::Colon) =>
+                {
+                    self.lexer.next();
+                    args.push((self.parse_type(), Some(name)));
+                }
+                _ => args.push((typ, None)),
+            }
+        }
+
+        // )
+        self.lexer.next();
+
+        Some(args)
+    }
+
     fn parse_struct(&mut self) -> Option<Statement<'a>> {
         // struct
         let start_token = self.lexer.next().unwrap();
+        let doc = self.take_doc();
 
         // Parse name of the struct
         let tok = self.lexer.next();
@@ -244,17 +436,30 @@ impl<'a> Parser<'a> {
             _ => return None,
         };
 
+        let mut generics = vec![];
+        if self.lexer.peek().map(|tok| &tok.kind) == Some(&TokenKind::Less) {
+            generics = self.parse_type_params(None)?;
+        }
+
         // Parse struct arguments
         let mut args = vec![];
+        let mut defaults = vec![];
         if let Some(tok) = self.lexer.peek() {
             if tok.kind == TokenKind::BraceOpen {
-                args = self.parse_typed_identifier_list(TokenKind::BraceOpen);
+                args = self.parse_typed_identifier_list(
+                    TokenKind::BraceOpen,
+                    ListExtras::Defaults(&mut defaults),
+                );
             }
         }
 
         Some(Statement::Struct {
             name: ident,
+            generics,
             attributes: args,
+            defaults,
+            annotations: vec![],
+            doc,
             span: Span {
                 start: start_token.span.start,
                 end: start_token.span.end,
@@ -262,6 +467,143 @@ impl<'a> Parser<'a> {
         })
     }
 
+    fn parse_interface(&mut self) -> Option<Statement<'a>> {
+        // interface
+        let start_token = self.lexer.next().unwrap();
+
+        let name = self.parse_identifier()?;
+
+        if !self.expect_peek(TokenKind::BraceOpen) {
+            return None;
+        }
+        self.lexer.next();
+
+        // func name(self, ..): type;
+        let mut methods = vec![];
+        while self.skip_comments() != Some(TokenKind::BraceClose) {
+            if !self.expect_peek(TokenKind::Function) {
+                return None;
+            }
+            self.lexer.next();
+
+            let method = self.parse_identifier()?;
+
+            if !self.expect_peek(TokenKind::BracketOpen) {
+                return None;
+            }
+            let args = self.parse_typed_identifier_list(TokenKind::BracketOpen, ListExtras::None);
+
+            let mut returns = AzulaType::Void;
+            if self.lexer.peek().map(|tok| &tok.kind) == Some(&TokenKind::Colon) {
+                self.lexer.next();
+                returns = self.parse_type();
+            }
+
+            if !self.expect_peek(TokenKind::SemiColon) {
+                return None;
+            }
+            self.lexer.next();
+
+            methods.push((method, args, returns));
+        }
+
+        let end_token = self.lexer.next().unwrap();
+
+        Some(Statement::Interface {
+            name,
+            methods,
+            span: Span {
+                start: start_token.span.start,
+                end: end_token.span.end,
+            },
+        })
+    }
+
+    fn parse_impl(&mut self) -> Option<Statement<'a>> {
+        // impl
+        let start_token = self.lexer.next().unwrap();
+
+        let interface = self.parse_identifier()?;
+
+        if !self.expect_peek(TokenKind::For) {
+            return None;
+        }
+        self.lexer.next();
+
+        let typ = self.parse_type();
+
+        if !self.expect_peek(TokenKind::BraceOpen) {
+            return None;
+        }
+        self.lexer.next();
+
+        let mut methods = vec![];
+        while self.skip_comments() != Some(TokenKind::BraceClose) {
+            if !self.expect_peek(TokenKind::Function) {
+                return None;
+            }
+            methods.push(self.parse_function()?);
+        }
+
+        let end_token = self.lexer.next().unwrap();
+
+        Some(Statement::Impl {
+            interface,
+            typ,
+            methods,
+            span: Span {
+                start: start_token.span.start,
+                end: end_token.span.end,
+            },
+        })
+    }
+
+    // Passes over comments and returns the kind of the next token, docs are
+    // kept for the declaration after them
+    fn skip_comments(&mut self) -> Option<TokenKind<'a>> {
+        loop {
+            match self.lexer.peek().map(|tok| &tok.kind) {
+                Some(TokenKind::Comment) => {
+                    self.lexer.next();
+                }
+                Some(TokenKind::DocComment) => {
+                    let comment = self.lexer.next().unwrap();
+                    self.push_doc(comment);
+                }
+                _ => break,
+            }
+        }
+        self.lexer.peek().map(|tok| tok.kind.clone())
+    }
+
+    fn parse_identifier(&mut self) -> Option<&'a str> {
+        match self.lexer.next() {
+            Some(Token {
+                kind: TokenKind::Identifier(name),
+                ..
+            }) => Some(name),
+            Some(tok) => {
+                self.errors.push(AzulaError::new(
+                    ErrorType::ExpectedToken(
+                        format!("{:?}", TokenKind::Identifier("")),
+                        Some(format!("{:?}", tok.kind)),
+                    ),
+                    tok.span.start,
+                    tok.span.end,
+                ));
+                None
+            }
+            None => {
+                self.errors.push(AzulaError::new(
+                    ErrorType::UnexpectedEOF,
+                    self.source.len() - 1,
+                    self.source.len(),
+                ));
+                None
+            }
+        }
+    }
+
     fn parse_return(&mut self) -> Option<Statement<'a>> {
         // return
         let start_token = self.lexer.next().unwrap();
@@ -310,6 +652,10 @@ impl<'a> Parser<'a> {
         // var
         let start_token = self.lexer.next().unwrap();
 
+        if self.lexer.peek().map(|tok| &tok.kind) == Some(&TokenKind::BracketOpen) {
+            return self.parse_destructure(mutable, start_token);
+        }
+
         let tok = self.lexer.next();
         let ident = match tok {
             Some(v) if matches!(v.kind, TokenKind::Identifier(_)) => {
@@ -362,6 +708,7 @@ impl<'a> Parser<'a> {
             ident.to_string(),
             type_annotation,
             expr,
+            vec![],
             Span {
                 start: start_token.span.start,
                 end: end_token.span.end,
@@ -369,50 +716,80 @@ impl<'a> Parser<'a> {
         ))
     }
 
-    fn parse_reassign(&mut self, ident: ExpressionNode<'a>) -> Option<Statement<'a>> {
+    // var (a, b) = value;
+    fn parse_destructure(&mut self, mutable: bool, start_token: Token) -> Option<Statement<'a>> {
+        // (
         self.lexer.next();
 
-        let expr = if let Some(expr) = self.parse_expression(LOWEST, true) {
-            expr
-        } else {
+        let mut names = vec![];
+        loop {
+            match self.lexer.next() {
+                Some(Token {
+                    kind: TokenKind::Identifier(name),
+                    ..
+                }) => names.push(name),
+                Some(tok) => {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::ExpectedToken(
+                            format!("{:?}", TokenKind::Identifier("")),
+                            Some(format!("{:?}", tok.kind)),
+                        ),
+                        tok.span.start,
+                        tok.span.end,
+                    ));
+                    return None;
+                }
+                None => return None,
+            }
+
+            if self.lexer.peek().map(|tok| &tok.kind) != Some(&TokenKind::Comma) {
+                break;
+            }
+            self.lexer.next();
+        }
+
+        if !self.expect_peek(TokenKind::BracketClose) {
             return None;
-        };
+        }
+        self.lexer.next();
 
-        if !self.expect_peek(TokenKind::SemiColon) {
+        if !self.expect_peek(TokenKind::Assign) {
             return None;
         }
+        self.lexer.next();
 
+        let expr = self.parse_expression(LOWEST, true)?;
+
+        if !self.expect_peek(TokenKind::SemiColon) {
+            return None;
+        }
         let end_token = self.lexer.next().unwrap();
 
-        Some(Statement::Reassign(
-            ident.clone(),
+        Some(Statement::Destructure(
+            mutable,
+            names,
             expr,
             Span {
-                start: ident.span.start,
+                start: start_token.span.start,
                 end: end_token.span.end,
             },
         ))
     }
 
-    fn parse_if(&mut self) -> Option<Statement<'a>> {
-        // if
+    fn parse_import(&mut self) -> Option<Statement<'a>> {
+        // import
         let start_token = self.lexer.next().unwrap();
 
-        let expr = self.parse_expression(LOWEST, false);
+        let name = self.parse_identifier()?;
 
-        if !self.expect_peek(TokenKind::BraceOpen) {
+        if !self.expect_peek(TokenKind::SemiColon) {
             return None;
         }
-
-        self.lexer.next();
-
-        let body = self.parse_block(TokenKind::BraceClose);
-
         let end_token = self.lexer.next().unwrap();
 
-        Some(Statement::If(
-            expr.unwrap(),
-            body,
+        Some(Statement::Import(
+            name,
+            vec![],
             Span {
                 start: start_token.span.start,
                 end: end_token.span.end,
@@ -420,1797 +797,4065 @@ impl<'a> Parser<'a> {
         ))
     }
 
-    fn parse_while(&mut self) -> Option<Statement<'a>> {
-        // while
-        let start_token = self.lexer.next().unwrap();
-
-        let expr = self.parse_expression(LOWEST, false);
+    // pub func, exported from the module and the object file
+    fn parse_pub(&mut self) -> Option<Statement<'a>> {
+        // pub
+        self.lexer.next();
 
-        if !self.expect_peek(TokenKind::BraceOpen) {
+        if !self.expect_peek(TokenKind::Function) {
             return None;
         }
+        let mut function = self.parse_function()?;
+        if let Statement::Function { public, .. } = &mut function {
+            *public = true;
+        }
 
-        self.lexer.next();
+        Some(function)
+    }
 
-        let body = self.parse_block(TokenKind::BraceClose);
+    // @name @name(args) before a function, struct or global
+    fn parse_attributes(&mut self) -> Option<Statement<'a>> {
+        let mut ahead = self.lexer.clone();
+        ahead.next();
+        if matches!(ahead.next(), Some(tok) if tok.kind == TokenKind::If) {
+            return self.parse_conditional();
+        }
 
-        let end_token = self.lexer.next().unwrap();
+        let mut attributes = vec![];
+        while self.lexer.peek().map(|tok| &tok.kind) == Some(&TokenKind::At) {
+            let start_token = self.lexer.next().unwrap();
+            let mut end = self
+                .lexer
+                .peek()
+                .map_or(start_token.span.end, |tok| tok.span.end);
+            let name = self.parse_identifier()?;
+
+            let mut args = vec![];
+            if self.lexer.peek().map(|tok| &tok.kind) == Some(&TokenKind::BracketOpen) {
+                args = self.parse_expression_list(TokenKind::BracketOpen);
+                end = self.lexer.next()?.span.end;
+            }
 
-        Some(Statement::While(
-            expr.unwrap(),
-            body,
-            Span {
-                start: start_token.span.start,
-                end: end_token.span.end,
-            },
-        ))
-    }
+            attributes.push(Attribute {
+                name,
+                args,
+                span: Span {
+                    start: start_token.span.start,
+                    end,
+                },
+            });
+        }
 
-    fn parse_type(&mut self) -> AzulaType<'a> {
-        if let Some(tok) = self.lexer.next() {
-            if let TokenKind::Identifier(ident) = tok.kind {
-                return ident.into();
+        let mut declaration = match self.lexer.peek().map(|tok| &tok.kind) {
+            Some(TokenKind::Pub) => self.parse_pub()?,
+            Some(TokenKind::Struct) => self.parse_struct()?,
+            Some(TokenKind::Var) => self.parse_assign(true)?,
+            Some(TokenKind::Const) => self.parse_assign(false)?,
+            _ => {
+                if !self.expect_peek(TokenKind::Function) {
+                    return None;
+                }
+                self.parse_function()?
             }
-
-            if let TokenKind::Ampersand = tok.kind {
-                return AzulaType::Pointer(Rc::new(self.parse_type()));
+        };
+        match &mut declaration {
+            Statement::Function {
+                attributes: declared,
+                ..
             }
+            | Statement::Struct {
+                annotations: declared,
+                ..
+            }
+            | Statement::Assign(_, _, _, _, declared, _) => *declared = attributes,
+            _ => {}
+        }
 
-            if let TokenKind::SquareOpen = tok.kind {
-                let internal_type = self.parse_type();
-                let mut size = None;
-                if self.lexer.peek().unwrap().kind == TokenKind::SemiColon {
-                    self.lexer.next();
+        Some(declaration)
+    }
 
-                    if let TokenKind::Integer(i) = self.lexer.next().unwrap().kind {
-                        size = Some(i as usize);
-                    }
-                }
-                if !self.expect_peek(TokenKind::SquareClose) {
-                    return AzulaType::Void;
-                }
+    // @if(key = "value", ...) declaration
+    fn parse_conditional(&mut self) -> Option<Statement<'a>> {
+        // @if
+        let start_token = self.lexer.next().unwrap();
+        self.lexer.next();
 
-                self.lexer.next();
+        if !self.expect_peek(TokenKind::BracketOpen) {
+            return None;
+        }
+        self.lexer.next();
 
-                return AzulaType::Array(Rc::new(internal_type), size);
+        let mut condition = vec![];
+        loop {
+            let key = self.parse_identifier()?;
+            if !self.expect_peek(TokenKind::Assign) {
+                return None;
             }
-
-            // Bit of a hack to allow for double &&
-            if let TokenKind::And = tok.kind {
-                return AzulaType::Pointer(Rc::new(AzulaType::Pointer(Rc::new(self.parse_type()))));
+            self.lexer.next();
+            match self.lexer.next() {
+                Some(Token {
+                    kind: TokenKind::String(value),
+                    ..
+                }) => condition.push((key, value)),
+                Some(tok) => {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::ExpectedToken(
+                            format!("{:?}", TokenKind::String("")),
+                            Some(format!("{:?}", tok.kind)),
+                        ),
+                        tok.span.start,
+                        tok.span.end,
+                    ));
+                    return None;
+                }
+                None => return None,
             }
-        }
-
-        AzulaType::Void
-    }
 
-    fn parse_typed_identifier(&mut self) -> Option<TypedIdentifier<'a>> {
-        let name = if let Some(tok) = self.lexer.next() {
-            if let TokenKind::Identifier(ident) = tok.kind {
-                ident
-            } else {
-                self.errors.push(AzulaError::new(
-                    ErrorType::ExpectedToken(
-                        format!("{:?}", TokenKind::Identifier("")),
-                        Some(format!("{:?}", tok.kind)),
-                    ),
-                    tok.span.start,
-                    tok.span.end,
-                ));
+            if self.lexer.peek().map(|tok| &tok.kind) == Some(&TokenKind::Comma) {
+                self.lexer.next();
+                continue;
+            }
+            if !self.expect_peek(TokenKind::BracketClose) {
                 return None;
             }
-        } else {
+            self.lexer.next();
+            break;
+        }
+
+        let tok = match self.lexer.peek() {
+            Some(tok) => tok.clone(),
+            None => return None,
+        };
+        if !matches!(
+            tok.kind,
+            TokenKind::Function
+                | TokenKind::Extern
+                | TokenKind::Struct
+                | TokenKind::Var
+                | TokenKind::Const
+                | TokenKind::Interface
+                | TokenKind::Impl
+                | TokenKind::Import
+                | TokenKind::Pub
+                | TokenKind::At
+        ) {
             self.errors.push(AzulaError::new(
-                ErrorType::UnexpectedEOF,
-                self.source.len() - 2,
-                self.source.len() - 1,
+                ErrorType::ExpectedDeclaration(format!("{:?}", tok.kind)),
+                tok.span.start,
+                tok.span.end,
             ));
             return None;
-        };
+        }
+        let declaration = self.parse_statement()?;
+
+        let end = declaration
+            .span()
+            .map_or(start_token.span.end, |span| span.end);
+        Some(Statement::Conditional(
+            condition,
+            Rc::new(declaration),
+            Span {
+                start: start_token.span.start,
+                end,
+            },
+        ))
+    }
 
-        if !self.expect_peek(TokenKind::Colon) {
+    fn parse_defer(&mut self) -> Option<Statement<'a>> {
+        // defer
+        let start_token = self.lexer.next().unwrap();
+
+        let expr = self.parse_expression(LOWEST, true)?;
+
+        if !self.expect_peek(TokenKind::SemiColon) {
             return None;
         }
+        let end_token = self.lexer.next().unwrap();
+
+        Some(Statement::Defer(
+            expr,
+            Span {
+                start: start_token.span.start,
+                end: end_token.span.end,
+            },
+        ))
+    }
 
+    fn parse_reassign(&mut self, ident: ExpressionNode<'a>) -> Option<Statement<'a>> {
         self.lexer.next();
 
-        let ident_type = self.parse_type();
+        let expr = if let Some(expr) = self.parse_expression(LOWEST, true) {
+            expr
+        } else {
+            return None;
+        };
 
-        Some((ident_type, name))
+        if !self.expect_peek(TokenKind::SemiColon) {
+            return None;
+        }
+
+        let end_token = self.lexer.next().unwrap();
+
+        Some(Statement::Reassign(
+            ident.clone(),
+            expr,
+            Span {
+                start: ident.span.start,
+                end: end_token.span.end,
+            },
+        ))
     }
 
-    fn parse_typed_identifier_list(
+    // x++ is x = x + 1, and x-- is x = x - 1
+    fn parse_increment(
         &mut self,
-        opening_delimiter: TokenKind,
-    ) -> Vec<TypedIdentifier<'a>> {
-        let closing_delimiter = opening_delimiter.get_closing_delimiter().unwrap();
+        target: ExpressionNode<'a>,
+        operator: Operator,
+    ) -> Option<Statement<'a>> {
+        let tok = self.lexer.next().unwrap();
 
-        self.lexer.next();
+        if !self.expect_peek(TokenKind::SemiColon) {
+            return None;
+        }
+        let end_token = self.lexer.next().unwrap();
 
-        if let Some(peek) = self.lexer.peek() {
-            if peek.kind == closing_delimiter {
-                self.lexer.next();
-                return vec![];
-            }
-        } else {
-            self.errors.push(AzulaError::new(
-                ErrorType::UnexpectedEOF,
-                self.source.len() - 2,
-                self.source.len() - 1,
-            ));
-            return vec![];
+        let one = ExpressionNode {
+            expression: Expression::Integer(1),
+            typed: AzulaType::Int,
+            span: Span {
+                start: tok.span.start,
+                end: tok.span.end,
+            },
+        };
+        let value = ExpressionNode {
+            expression: Expression::Infix(Rc::new(target.clone()), operator, Rc::new(one)),
+            typed: AzulaType::Infer,
+            span: Span {
+                start: target.span.start,
+                end: tok.span.end,
+            },
+        };
+
+        Some(Statement::Reassign(
+            target.clone(),
+            value,
+            Span {
+                start: target.span.start,
+                end: end_token.span.end,
+            },
+        ))
+    }
+
+    fn parse_if(&mut self) -> Option<Statement<'a>> {
+        // if
+        let start_token = self.lexer.next().unwrap();
+
+        match self.lexer.peek().map(|tok| &tok.kind) {
+            Some(TokenKind::Var) => return self.parse_if_let(true, start_token),
+            Some(TokenKind::Const) => return self.parse_if_let(false, start_token),
+            _ => {}
         }
 
-        let mut identifiers = vec![];
+        let expr = self.parse_expression(LOWEST, false);
 
-        if let Some((typ, name)) = self.parse_typed_identifier() {
-            identifiers.push((typ, name));
+        if !self.expect_peek(TokenKind::BraceOpen) {
+            return None;
         }
 
-        let mut peek = self.lexer.peek().unwrap().kind.clone();
-        while peek == TokenKind::Comma {
-            self.lexer.next();
-            if self.lexer.peek().unwrap().kind == closing_delimiter {
-                break;
-            }
-            if let Some((typ, name)) = self.parse_typed_identifier() {
-                identifiers.push((typ, name));
+        self.lexer.next();
+
+        let body = self.parse_block(TokenKind::BraceClose);
+
+        let end_token = self.lexer.next().unwrap();
+
+        Some(Statement::If(
+            expr.unwrap(),
+            body,
+            Span {
+                start: start_token.span.start,
+                end: end_token.span.end,
+            },
+        ))
+    }
+
+    // if var x = optional { ... }, runs the body with x bound to the value if
+    // there is one
+    fn parse_if_let(&mut self, mutable: bool, start_token: Token) -> Option<Statement<'a>> {
+        // var
+        self.lexer.next();
+
+        let name = match self.lexer.next() {
+            Some(Token {
+                kind: TokenKind::Identifier(name),
+                ..
+            }) => name,
+            Some(tok) => {
+                self.errors.push(AzulaError::new(
+                    ErrorType::ExpectedToken(
+                        format!("{:?}", TokenKind::Identifier("")),
+                        Some(format!("{:?}", tok.kind)),
+                    ),
+                    tok.span.start,
+                    tok.span.end,
+                ));
+                return None;
             }
-            peek = self.lexer.peek().unwrap().kind.clone();
+            None => return None,
+        };
+
+        if !self.expect_peek(TokenKind::Assign) {
+            return None;
         }
+        self.lexer.next();
 
-        self.expect_peek(closing_delimiter);
+        let expr = self.parse_expression(LOWEST, false)?;
 
+        if !self.expect_peek(TokenKind::BraceOpen) {
+            return None;
+        }
         self.lexer.next();
 
-        identifiers
+        let body = self.parse_block(TokenKind::BraceClose);
+
+        let end_token = self.lexer.next().unwrap();
+
+        Some(Statement::IfLet(
+            mutable,
+            name,
+            expr,
+            body,
+            Span {
+                start: start_token.span.start,
+                end: end_token.span.end,
+            },
+        ))
     }
 
-    fn parse_type_list(&mut self, opening_delimiter: TokenKind) -> Vec<AzulaType<'a>> {
-        let closing_delimiter = opening_delimiter.get_closing_delimiter().unwrap();
+    fn parse_while(&mut self) -> Option<Statement<'a>> {
+        // while
+        let start_token = self.lexer.next().unwrap();
 
-        if let Some(peek) = self.lexer.peek() {
-            if peek.kind == closing_delimiter {
-                return vec![];
-            }
-        } else {
-            self.errors.push(AzulaError::new(
-                ErrorType::UnexpectedEOF,
-                self.source.len() - 2,
-                self.source.len() - 1,
-            ));
-            return vec![];
+        let expr = self.parse_expression(LOWEST, false);
+
+        if !self.expect_peek(TokenKind::BraceOpen) {
+            return None;
         }
 
         self.lexer.next();
 
-        let mut types = vec![];
+        let body = self.parse_block(TokenKind::BraceClose);
 
-        types.push(self.parse_type());
+        let end_token = self.lexer.next().unwrap();
 
-        let mut peek = self.lexer.peek().unwrap().kind.clone();
-        while peek == TokenKind::Comma {
+        Some(Statement::While(
+            expr.unwrap(),
+            body,
+            None,
+            Span {
+                start: start_token.span.start,
+                end: end_token.span.end,
+            },
+        ))
+    }
+
+    fn parse_for(&mut self) -> Option<Statement<'a>> {
+        // for
+        let start_token = self.lexer.next().unwrap();
+
+        // for i, x also binds the position
+        let mut index = None;
+        let mut name = self.parse_identifier()?;
+        if self.lexer.peek().map(|tok| &tok.kind) == Some(&TokenKind::Comma) {
             self.lexer.next();
-            types.push(self.parse_type());
-            peek = self.lexer.peek().unwrap().kind.clone();
+            index = Some(name);
+            name = self.parse_identifier()?;
+        }
+        if !self.expect_peek(TokenKind::In) {
+            return None;
         }
+        self.lexer.next();
 
-        self.expect_peek(closing_delimiter);
+        let iterable = self.parse_expression(LOWEST, false)?;
 
+        if !self.expect_peek(TokenKind::BraceOpen) {
+            return None;
+        }
         self.lexer.next();
 
-        types
-    }
+        let body = self.parse_block(TokenKind::BraceClose);
+        let end_token = self.lexer.next().unwrap();
 
-    fn expect_peek(&mut self, token_kind: TokenKind) -> bool {
-        if let Some(tok) = self.lexer.peek() {
-            return match tok.kind {
-                _ if tok.kind == token_kind => true,
-                _ => {
-                    let span = tok.span.clone();
-                    self.errors.push(AzulaError::new(
-                        ErrorType::ExpectedToken(
-                            format!("{:?}", token_kind),
-                            Some(format!("{:?}", tok.kind)),
-                        ),
-                        span.start,
-                        span.end,
-                    ));
-                    false
-                }
-            };
-        }
-
-        self.errors.push(AzulaError::new(
-            ErrorType::UnexpectedEOF,
-            self.source.len() - 1,
-            self.source.len(),
-        ));
-        false
+        Some(Statement::For(
+            index,
+            name,
+            iterable,
+            body,
+            None,
+            Span {
+                start: start_token.span.start,
+                end: end_token.span.end,
+            },
+        ))
     }
 
-    fn parse_expression(
-        &mut self,
-        precedence: OperatorPrecedence,
-        allow_struct_init: bool,
-    ) -> Option<ExpressionNode<'a>> {
-        let tok = if let Some(tok) = self.lexer.next() {
-            tok
-        } else {
-            self.errors.push(AzulaError::new(
-                ErrorType::UnexpectedEOF,
-                self.source.len() - 2,
-                self.source.len() - 1,
-            ));
+    fn parse_do_while(&mut self) -> Option<Statement<'a>> {
+        // do
+        let start_token = self.lexer.next().unwrap();
+
+        if !self.expect_peek(TokenKind::BraceOpen) {
             return None;
-        };
+        }
+        self.lexer.next();
 
-        let mut left = match tok.kind {
-            TokenKind::Integer(i) => {
-                if let Some(peek) = self.lexer.peek() {
-                    if peek.kind == TokenKind::Dot {
-                        self.lexer.next();
-                        let second_number = self.parse_expression(CALL, false).unwrap();
-                        match second_number.expression {
-                            Expression::Integer(y) => Some(ExpressionNode {
-                                expression: Expression::Float(
-                                    format!("{}.{}", i, y).parse().unwrap(),
-                                ),
-                                typed: AzulaType::Float,
-                                span: Span {
-                                    start: tok.span.start,
-                                    end: second_number.span.end,
-                                },
-                            }),
-                            _ => panic!(),
-                        }
-                    } else {
-                        Some(ExpressionNode {
-                            expression: Expression::Integer(i),
-                            typed: AzulaType::Int,
-                            span: Span {
-                                start: tok.span.start,
-                                end: tok.span.end,
-                            },
-                        })
-                    }
-                } else {
-                    Some(ExpressionNode {
-                        expression: Expression::Integer(i),
-                        typed: AzulaType::Int,
-                        span: Span {
-                            start: tok.span.start,
-                            end: tok.span.end,
-                        },
-                    })
-                }
-            }
-            TokenKind::True => Some(ExpressionNode {
-                expression: Expression::Boolean(true),
-                typed: AzulaType::Bool,
-                span: Span {
-                    start: tok.span.start,
-                    end: tok.span.end,
-                },
-            }),
-            TokenKind::False => Some(ExpressionNode {
-                expression: Expression::Boolean(false),
-                typed: AzulaType::Bool,
-                span: Span {
-                    start: tok.span.start,
-                    end: tok.span.end,
-                },
-            }),
-            TokenKind::String(val) => {
-                let transformed = match string_transform(val) {
-                    Ok(str) => str,
-                    Err(index) => {
-                        self.errors.push(AzulaError::new(
-                            ErrorType::InvalidEscape,
-                            tok.span.start + index,
-                            tok.span.start + index + 1,
-                        ));
-                        "".to_string()
-                    }
-                };
-                Some(ExpressionNode {
-                    expression: Expression::String(transformed),
-                    typed: AzulaType::Pointer(Rc::new(AzulaType::Str)),
-                    span: Span {
-                        start: tok.span.start,
-                        end: tok.span.end,
-                    },
-                })
-            }
-            TokenKind::Identifier(x) => Some(ExpressionNode {
-                expression: Expression::Identifier(x.to_string()),
-                typed: AzulaType::Infer,
-                span: Span {
-                    start: tok.span.start,
-                    end: tok.span.end,
-                },
-            }),
-            TokenKind::BracketOpen => {
-                let expr = self.parse_expression(LOWEST, true).unwrap();
+        let body = self.parse_block(TokenKind::BraceClose);
+        self.lexer.next();
 
-                self.expect_peek(TokenKind::BracketClose);
+        if !self.expect_peek(TokenKind::While) {
+            return None;
+        }
+        self.lexer.next();
 
-                self.lexer.next();
+        let expr = self.parse_expression(LOWEST, true)?;
 
-                Some(expr)
-            }
-            TokenKind::Bang => {
-                let expr = self.parse_expression(PREFIX, allow_struct_init).unwrap();
+        if !self.expect_peek(TokenKind::SemiColon) {
+            return None;
+        }
+        let end_token = self.lexer.next().unwrap();
 
-                Some(ExpressionNode {
-                    expression: Expression::Not(Rc::new(expr.clone())),
-                    typed: AzulaType::Bool,
-                    span: Span {
-                        start: tok.span.start,
-                        end: expr.span.end,
-                    },
-                })
-            }
-            TokenKind::Ampersand => {
-                let expr = self.parse_expression(PREFIX, true).unwrap();
+        Some(Statement::DoWhile(
+            expr,
+            body,
+            None,
+            Span {
+                start: start_token.span.start,
+                end: end_token.span.end,
+            },
+        ))
+    }
 
-                Some(ExpressionNode {
-                    expression: Expression::Pointer(Rc::new(expr.clone())),
-                    typed: AzulaType::Infer,
-                    span: Span {
-                        start: tok.span.start,
-                        end: expr.span.end,
-                    },
-                })
-            }
-            TokenKind::SquareOpen => self.parse_array(tok),
-            _ => {
-                self.errors.push(AzulaError::new(
-                    ErrorType::ExpectedExpression(format!("{:?}", tok.kind)),
-                    tok.span.start,
-                    tok.span.end,
-                ));
-                None
-            }
-        };
+    fn parse_scope(&mut self) -> Option<Statement<'a>> {
+        // {
+        let start_token = self.lexer.next().unwrap();
 
-        let mut peek_token = &if let Some(tok) = self.lexer.peek() {
-            tok
-        } else {
-            return left;
-        }
-        .kind;
+        let body = self.parse_block(TokenKind::BraceClose);
 
-        // Keep creating infix expressions until precedence is higher or semicolon found
-        while peek_token.clone() != TokenKind::SemiColon
-            && precedence < operator_precedence(peek_token.clone(), allow_struct_init)
-        {
-            if left.is_none() {
-                return None;
-            }
-            left = self.parse_infix(left.unwrap(), allow_struct_init);
-            peek_token = if let Some(v) = self.lexer.peek() {
-                &v.kind
-            } else {
-                return left;
-            };
+        if !self.expect_peek(TokenKind::BraceClose) {
+            return None;
         }
+        let end_token = self.lexer.next().unwrap();
 
-        left
+        Some(Statement::Scope(
+            body,
+            Span {
+                start: start_token.span.start,
+                end: end_token.span.end,
+            },
+        ))
     }
 
-    fn parse_expression_list(&mut self, opening_delimiter: TokenKind) -> Vec<ExpressionNode<'a>> {
-        let closing_delimiter = opening_delimiter.get_closing_delimiter().unwrap();
+    // `name:` at the start of a statement labels the loop after it
+    fn is_label(&mut self) -> bool {
+        if !matches!(self.lexer.peek(), Some(tok) if matches!(tok.kind, TokenKind::Identifier(_))) {
+            return false;
+        }
+        let mut ahead = self.lexer.clone();
+        ahead.next();
+        matches!(ahead.next(), Some(tok) if tok.kind == TokenKind::Colon)
+    }
 
+    fn parse_labeled(&mut self) -> Option<Statement<'a>> {
+        // label:
+        let label = match self.lexer.next().unwrap().kind {
+            TokenKind::Identifier(label) => label,
+            _ => unreachable!(),
+        };
         self.lexer.next();
 
-        if let Some(peek) = self.lexer.peek() {
-            if peek.kind == closing_delimiter {
-                return vec![];
-            }
+        let is_do = matches!(self.lexer.peek(), Some(tok) if tok.kind == TokenKind::Do);
+        let is_for = matches!(self.lexer.peek(), Some(tok) if tok.kind == TokenKind::For);
+        let stmt = if is_do {
+            self.parse_do_while()
+        } else if is_for {
+            self.parse_for()
+        } else if self.expect_peek(TokenKind::While) {
+            self.parse_while()
         } else {
-            self.errors.push(AzulaError::new(
-                ErrorType::UnexpectedEOF,
-                self.source.len() - 2,
-                self.source.len() - 1,
-            ));
-            return vec![];
+            return None;
+        };
+        match stmt? {
+            Statement::While(cond, body, _, span) => {
+                Some(Statement::While(cond, body, Some(label), span))
+            }
+            Statement::DoWhile(cond, body, _, span) => {
+                Some(Statement::DoWhile(cond, body, Some(label), span))
+            }
+            Statement::For(index, name, iterable, body, _, span) => Some(Statement::For(
+                index,
+                name,
+                iterable,
+                body,
+                Some(label),
+                span,
+            )),
+            _ => unreachable!(),
         }
+    }
 
-        let mut expressions = vec![];
+    fn parse_loop_control(&mut self) -> Option<Statement<'a>> {
+        // break or continue, and the label of the loop
+        let start_token = self.lexer.next().unwrap();
 
-        if let Some(expr) = self.parse_expression(LOWEST, true) {
-            expressions.push(expr);
+        let label = match self.lexer.peek().map(|tok| &tok.kind) {
+            Some(TokenKind::Identifier(label)) => {
+                let label = *label;
+                self.lexer.next();
+                Some(label)
+            }
+            _ => None,
+        };
+
+        if !self.expect_peek(TokenKind::SemiColon) {
+            return None;
         }
+        let end_token = self.lexer.next().unwrap();
 
-        let mut peek = self.lexer.peek().unwrap().kind.clone();
-        while peek == TokenKind::Comma {
-            self.lexer.next();
-            if let Some(expr) = self.parse_expression(LOWEST, true) {
-                expressions.push(expr);
-            }
-            peek = self.lexer.peek().unwrap().kind.clone();
+        let span = Span {
+            start: start_token.span.start,
+            end: end_token.span.end,
+        };
+        if start_token.kind == TokenKind::Break {
+            Some(Statement::Break(label, span))
+        } else {
+            Some(Statement::Continue(label, span))
         }
+    }
 
-        self.expect_peek(closing_delimiter);
+    fn parse_match(&mut self) -> Option<Statement<'a>> {
+        // match
+        let start_token = self.lexer.next().unwrap();
 
-        expressions
-    }
+        let expr = self.parse_expression(LOWEST, false)?;
 
-    fn parse_infix(
-        &mut self,
-        left: ExpressionNode<'a>,
-        allow_struct_init: bool,
-    ) -> Option<ExpressionNode<'a>> {
-        let operator = self.lexer.peek().unwrap();
-        let kind = operator.kind.clone();
+        if !self.expect_peek(TokenKind::BraceOpen) {
+            return None;
+        }
+        self.lexer.next();
 
-        match kind {
-            TokenKind::Dot => self.parse_struct_access(left),
-            TokenKind::BracketOpen => self.parse_function_call(left),
-            TokenKind::SquareOpen => self.parse_array_access(left),
-            TokenKind::BraceOpen if allow_struct_init => self.parse_struct_init(left),
-            TokenKind::Plus
-            | TokenKind::Minus
-            | TokenKind::Asterisk
-            | TokenKind::Slash
-            | TokenKind::Modulo
-            | TokenKind::Power
-            | TokenKind::Or
-            | TokenKind::And
-            | TokenKind::Equal
-            | TokenKind::NotEqual
-            | TokenKind::Less
-            | TokenKind::LessEqual
-            | TokenKind::Greater
-            | TokenKind::GreaterEqual => {
-                let precedence = operator_precedence(operator.kind.clone(), allow_struct_init);
+        let mut arms = vec![];
+        loop {
+            match self.lexer.peek() {
+                Some(tok) if tok.kind == TokenKind::BraceClose => break,
+                Some(tok)
+                    if matches!(
+                        tok.kind,
+                        TokenKind::Comma | TokenKind::Comment | TokenKind::DocComment
+                    ) =>
+                {
+                    self.lexer.next();
+                    continue;
+                }
+                Some(_) => {}
+                None => {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::UnexpectedEOF,
+                        self.source.len() - 2,
+                        self.source.len() - 1,
+                    ));
+                    return None;
+                }
+            }
+
+            // `_ => { ... }` matches anything that the other arms didn't
+            let arm_start = self.lexer.peek().unwrap().span.start;
+            let typ = match self.lexer.peek().unwrap().kind {
+                TokenKind::Identifier("_") => {
+                    self.lexer.next();
+                    None
+                }
+                _ => Some(self.parse_type()),
+            };
 
+            let mut binding = None;
+            if let TokenKind::Identifier(name) = self.lexer.peek().unwrap().kind {
                 self.lexer.next();
+                binding = Some(name);
+            }
+            let arm_span = Span {
+                start: arm_start,
+                end: self.previous_end(),
+            };
 
-                let right = if let Some(expr) = self.parse_expression(precedence, allow_struct_init)
-                {
-                    expr
-                } else {
-                    return None;
-                };
-                let operator = operator_from_token(kind).unwrap();
-                return Some(ExpressionNode {
-                    expression: Expression::Infix(
-                        Rc::new(left.clone()),
-                        operator,
-                        Rc::new(right.clone()),
-                    ),
-                    typed: AzulaType::Infer,
-                    span: Span {
-                        start: left.span.start,
-                        end: right.span.end,
-                    },
-                });
+            if !self.expect_peek(TokenKind::FatArrow) {
+                return None;
             }
-            _ => {
-                println!("unknown operator");
+            self.lexer.next();
+
+            if !self.expect_peek(TokenKind::BraceOpen) {
                 return None;
             }
-        }
-    }
+            self.lexer.next();
 
-    fn parse_function_call(&mut self, left: ExpressionNode<'a>) -> Option<ExpressionNode<'a>> {
-        let exprs = self.parse_expression_list(TokenKind::BracketOpen);
+            let body = self.parse_block(TokenKind::BraceClose);
+
+            if !self.expect_peek(TokenKind::BraceClose) {
+                return None;
+            }
+            self.lexer.next();
+
+            arms.push((typ, binding, body, arm_span));
+        }
 
         let end_token = self.lexer.next().unwrap();
 
-        Some(ExpressionNode {
-            expression: Expression::FunctionCall {
-                function: Rc::new(left.clone()),
-                args: exprs,
-            },
-            typed: AzulaType::Infer,
-            span: Span {
-                start: left.span.start,
+        Some(Statement::Match(
+            expr,
+            arms,
+            Span {
+                start: start_token.span.start,
                 end: end_token.span.end,
             },
-        })
+        ))
     }
 
-    fn parse_array(&mut self, tok: Token) -> Option<ExpressionNode<'a>> {
-        if let Some(peek) = self.lexer.peek() {
-            if peek.kind == TokenKind::SquareClose {
-                let close = self.lexer.next().unwrap();
-                return Some(ExpressionNode {
-                    expression: Expression::Array(vec![]),
-                    typed: AzulaType::Array(Rc::new(AzulaType::Infer), Some(0)),
-                    span: Span {
-                        start: tok.span.start,
-                        end: close.span.end,
-                    },
-                });
-            }
-        } else {
-            self.errors.push(AzulaError::new(
-                ErrorType::UnexpectedEOF,
-                self.source.len() - 2,
-                self.source.len() - 1,
-            ));
-            return Some(ExpressionNode {
-                expression: Expression::Array(vec![]),
-                typed: AzulaType::Array(Rc::new(AzulaType::Infer), Some(0)),
-                span: Span {
-                    start: tok.span.start,
-                    end: 0,
-                },
-            });
-        }
+    fn parse_type(&mut self) -> AzulaType<'a> {
+        if let Some(tok) = self.lexer.next() {
+            // fn(a, b) -> c
+            if tok.kind == TokenKind::Identifier("fn")
+                && self.lexer.peek().map(|tok| &tok.kind) == Some(&TokenKind::BracketOpen)
+            {
+                self.lexer.next();
 
-        let mut expressions = vec![];
+                let mut params = vec![];
+                if self.lexer.peek().map(|tok| &tok.kind) != Some(&TokenKind::BracketClose) {
+                    params.push(self.parse_type());
+                    while self.lexer.peek().map(|tok| &tok.kind) == Some(&TokenKind::Comma) {
+                        self.lexer.next();
+                        params.push(self.parse_type());
+                    }
+                }
 
-        if let Some(expr) = self.parse_expression(LOWEST, true) {
-            expressions.push(expr);
-        }
+                if !self.expect_peek(TokenKind::BracketClose) {
+                    return AzulaType::Void;
+                }
+                self.lexer.next();
+                if !self.expect_peek(TokenKind::Arrow) {
+                    return AzulaType::Void;
+                }
+                self.lexer.next();
 
-        let mut peek = self.lexer.peek().unwrap().kind.clone();
-        // Parse initialiser like [0; 20]
-        if peek == TokenKind::SemiColon {
-            self.lexer.next();
+                return AzulaType::Function(params, Rc::new(self.parse_type()));
+            }
 
-            if let TokenKind::Integer(v) = self.lexer.next().unwrap().kind {
-                for _ in 0..v - 1 {
-                    expressions.push(expressions[0].clone());
+            // typeof(x)
+            if tok.kind == TokenKind::Identifier("typeof")
+                && self.lexer.peek().map(|tok| &tok.kind) == Some(&TokenKind::BracketOpen)
+            {
+                self.lexer.next();
+                let name = match self.parse_identifier() {
+                    Some(name) => name,
+                    None => return AzulaType::Void,
+                };
+                if !self.expect_peek(TokenKind::BracketClose) {
+                    return AzulaType::Void;
                 }
-                self.expect_peek(TokenKind::SquareClose);
+                self.lexer.next();
 
-                let close = self.lexer.next().unwrap();
-                return Some(ExpressionNode {
-                    expression: Expression::Array(expressions.clone()),
-                    typed: AzulaType::Array(Rc::new(AzulaType::Infer), Some(expressions.len())),
-                    span: Span {
-                        start: tok.span.start,
-                        end: close.span.end,
-                    },
-                });
-            } else {
-                self.expect_peek(TokenKind::SquareClose);
+                return AzulaType::TypeOf(name.to_string());
+            }
 
-                let close = self.lexer.next().unwrap();
-                self.errors.push(AzulaError::new(
-                    ErrorType::ArrayInitialiserSizeNonConstant,
-                    tok.span.start,
-                    close.span.end,
-                ));
-                return None;
+            if let TokenKind::Identifier(ident) = tok.kind {
+                return match ident.into() {
+                    AzulaType::Named(name)
+                        if self.lexer.peek().map(|tok| &tok.kind) == Some(&TokenKind::Less) =>
+                    {
+                        self.parse_type_args(name)
+                    }
+                    typ => typ,
+                };
             }
-        }
-        while peek == TokenKind::Comma {
-            // TODO allow trailing comma
-            self.lexer.next();
-            if let Some(expr) = self.parse_expression(LOWEST, true) {
-                expressions.push(expr);
+
+            if let TokenKind::Ampersand | TokenKind::Asterisk = tok.kind {
+                return AzulaType::Pointer(Rc::new(self.parse_type()));
             }
-            peek = self.lexer.peek().unwrap().kind.clone();
-        }
 
-        self.expect_peek(TokenKind::SquareClose);
+            if let TokenKind::SquareOpen = tok.kind {
+                if self.lexer.peek().map(|tok| &tok.kind) == Some(&TokenKind::SquareClose) {
+                    self.lexer.next();
+                    return AzulaType::Slice(Rc::new(self.parse_type()));
+                }
 
-        let close = self.lexer.next().unwrap();
+                let internal_type = self.parse_type();
+                let mut size = None;
+                let mut constant = None;
+                if self.lexer.peek().unwrap().kind == TokenKind::SemiColon {
+                    self.lexer.next();
 
-        Some(ExpressionNode {
-            expression: Expression::Array(expressions.clone()),
-            typed: AzulaType::Array(Rc::new(AzulaType::Infer), Some(expressions.len())),
-            span: Span {
-                start: tok.span.start,
-                end: close.span.end,
-            },
-        })
-    }
+                    match self.lexer.next().unwrap().kind {
+                        TokenKind::Integer(i) => size = Some(i as usize),
+                        TokenKind::Identifier(name) => constant = Some(name.to_string()),
+                        _ => {}
+                    }
+                }
+                if !self.expect_peek(TokenKind::SquareClose) {
+                    return AzulaType::Void;
+                }
 
-    fn parse_array_access(&mut self, left: ExpressionNode<'a>) -> Option<ExpressionNode<'a>> {
-        self.lexer.next();
-        let index = match self.parse_expression(LOWEST, true) {
-            Some(expr) => expr,
-            None => return None,
-        };
+                self.lexer.next();
 
-        if !self.expect_peek(TokenKind::SquareClose) {
-            return None;
-        }
+                if let Some(constant) = constant {
+                    return AzulaType::ConstArray(Rc::new(internal_type), constant);
+                }
+                return AzulaType::Array(Rc::new(internal_type), size);
+            }
 
-        let end_token = self.lexer.next().unwrap();
+            if let TokenKind::Question = tok.kind {
+                return AzulaType::Optional(Rc::new(self.parse_type()));
+            }
 
-        Some(ExpressionNode {
-            expression: Expression::ArrayAccess(Rc::new(left.clone()), Rc::new(index)),
-            typed: AzulaType::Infer,
-            span: Span {
-                start: left.span.start,
-                end: end_token.span.end,
-            },
-        })
-    }
+            if let TokenKind::Bang = tok.kind {
+                return AzulaType::Result(Rc::new(self.parse_type()));
+            }
 
-    fn parse_struct_access(&mut self, left: ExpressionNode<'a>) -> Option<ExpressionNode<'a>> {
-        self.lexer.next();
-        let index = match self.parse_expression(ACCESS, false) {
-            Some(expr) => expr,
-            None => return None,
-        };
+            // (a, b) is a tuple, a single parenthesised type is just that type
+            if let TokenKind::BracketOpen = tok.kind {
+                let mut types = vec![self.parse_type()];
+                while self.lexer.peek().map(|tok| &tok.kind) == Some(&TokenKind::Comma) {
+                    self.lexer.next();
+                    types.push(self.parse_type());
+                }
 
-        Some(ExpressionNode {
-            expression: Expression::StructAccess(Rc::new(left.clone()), Rc::new(index.clone())),
-            typed: AzulaType::Infer,
-            span: Span {
-                start: left.span.start,
-                end: index.span.end,
-            },
-        })
+                if !self.expect_peek(TokenKind::BracketClose) {
+                    return AzulaType::Void;
+                }
+                self.lexer.next();
+
+                if types.len() == 1 {
+                    return types.remove(0);
+                }
+                return AzulaType::Tuple(types);
+            }
+
+            // Bit of a hack to allow for double && and **
+            if let TokenKind::And | TokenKind::Power = tok.kind {
+                return AzulaType::Pointer(Rc::new(AzulaType::Pointer(Rc::new(self.parse_type()))));
+            }
+        }
+
+        AzulaType::Void
     }
 
-    fn parse_struct_init(&mut self, left: ExpressionNode<'a>) -> Option<ExpressionNode<'a>> {
-        // {
+    // Name<A, B>
+    fn parse_type_args(&mut self, name: String) -> AzulaType<'a> {
         self.lexer.next();
 
-        let mut attrs = vec![];
+        let mut args = vec![self.parse_type()];
+        while self.lexer.peek().map(|tok| &tok.kind) == Some(&TokenKind::Comma) {
+            self.lexer.next();
+            args.push(self.parse_type());
+        }
 
-        let mut peek = match self.lexer.peek() {
-            Some(peek) => peek.kind.clone(),
-            None => {
+        // The >> closing two lists at once is split, leaving a > for the
+        // outer one
+        if let Some(tok) = self.lexer.peek_mut() {
+            if tok.kind == TokenKind::ShiftRight {
+                tok.kind = TokenKind::Greater;
+                tok.span.start += 1;
+                return AzulaType::Generic(name, args);
+            }
+        }
+
+        if !self.expect_peek(TokenKind::Greater) {
+            return AzulaType::Void;
+        }
+        self.lexer.next();
+
+        AzulaType::Generic(name, args)
+    }
+
+    fn parse_typed_identifier(&mut self) -> Option<TypedIdentifier<'a>> {
+        let name = if let Some(tok) = self.lexer.next() {
+            if let TokenKind::Identifier(ident) = tok.kind {
+                ident
+            } else {
                 self.errors.push(AzulaError::new(
-                    ErrorType::UnexpectedEOF,
-                    self.source.len() - 2,
-                    self.source.len() - 1,
+                    ErrorType::ExpectedToken(
+                        format!("{:?}", TokenKind::Identifier("")),
+                        Some(format!("{:?}", tok.kind)),
+                    ),
+                    tok.span.start,
+                    tok.span.end,
                 ));
                 return None;
             }
+        } else {
+            self.errors.push(AzulaError::new(
+                ErrorType::UnexpectedEOF,
+                self.source.len() - 2,
+                self.source.len() - 1,
+            ));
+            return None;
         };
 
-        while peek != TokenKind::BraceClose {
-            if let Some(tok) = self.lexer.peek() {
-                if tok.kind == TokenKind::Comma {
-                    self.lexer.next();
-                    if let Some(tok) = self.lexer.peek() {
-                        if tok.kind == TokenKind::BraceClose {
-                            break;
-                        }
-                    }
-                }
-            }
-            let tok = self.lexer.next();
-            let name = match tok {
-                Some(Token {
-                    kind: TokenKind::Identifier(val),
-                    span: _,
-                }) => val,
-                _ => todo!("{:?}", tok),
-            };
+        // A method's self param can leave out its type
+        if name == "self" && self.lexer.peek().map(|tok| &tok.kind) != Some(&TokenKind::Colon) {
+            return Some((AzulaType::Named("Self".to_string()), name));
+        }
 
-            if !self.expect_peek(TokenKind::Colon) {
-                return None;
-            }
+        if !self.expect_peek(TokenKind::Colon) {
+            return None;
+        }
 
-            self.lexer.next();
+        self.lexer.next();
 
-            let expr = self.parse_expression(LOWEST, true).unwrap();
+        let ident_type = self.parse_type();
 
-            attrs.push((name, expr));
+        Some((ident_type, name))
+    }
 
-            peek = match self.lexer.peek() {
-                Some(peek) => peek.kind.clone(),
-                None => {
-                    self.errors.push(AzulaError::new(
-                        ErrorType::UnexpectedEOF,
-                        self.source.len() - 2,
-                        self.source.len() - 1,
-                    ));
-                    return None;
-                }
-            };
+    // Function parameters pass in somewhere to put defaults and varargs
+    fn parse_typed_identifier_list(
+        &mut self,
+        opening_delimiter: TokenKind,
+        mut extras: ListExtras<'_, 'a>,
+    ) -> Vec<TypedIdentifier<'a>> {
+        let closing_delimiter = opening_delimiter.get_closing_delimiter().unwrap();
+
+        self.lexer.next();
+
+        if let Some(peek) = self.lexer.peek() {
+            if peek.kind == closing_delimiter {
+                self.lexer.next();
+                return vec![];
+            }
+        } else {
+            self.errors.push(AzulaError::new(
+                ErrorType::UnexpectedEOF,
+                self.source.len() - 2,
+                self.source.len() - 1,
+            ));
+            return vec![];
         }
 
-        if !self.expect_peek(TokenKind::BraceClose) {
-            return None;
+        let mut identifiers = vec![];
+
+        let start = self.lexer.peek().unwrap().span.start;
+        if let Some((typ, name)) = self.parse_typed_identifier() {
+            identifiers.push((typ, name));
         }
+        self.parse_list_extras(start, &mut identifiers, &mut extras);
 
-        let end_token = self.lexer.next().unwrap();
+        let mut peek = self.lexer.peek().unwrap().kind.clone();
+        while peek == TokenKind::Comma {
+            self.lexer.next();
+            if self.lexer.peek().unwrap().kind == closing_delimiter {
+                break;
+            }
+            let start = self.lexer.peek().unwrap().span.start;
+            if let Some((typ, name)) = self.parse_typed_identifier() {
+                identifiers.push((typ, name));
+            }
+            self.parse_list_extras(start, &mut identifiers, &mut extras);
+            peek = self.lexer.peek().unwrap().kind.clone();
+        }
 
-        Some(ExpressionNode {
-            expression: Expression::StructInitialisation(Rc::new(left.clone()), attrs),
-            typed: AzulaType::Infer,
-            span: Span {
-                start: left.span.start,
-                end: end_token.span.end,
-            },
-        })
-    }
-}
+        self.expect_peek(closing_delimiter);
 
-fn string_transform(str: &str) -> Result<String, usize> {
-    let mut result = String::new();
+        self.lexer.next();
 
-    let mut chars = str.chars().enumerate().peekable();
+        identifiers
+    }
 
-    loop {
-        let char = chars.next();
-        if char.is_none() {
-            break;
-        }
-        let (index, char) = char.unwrap();
-        if char == '\\' {
-            match chars.peek() {
-                Some((_, '\\')) => {
-                    chars.next();
-                    result.push('\\');
-                }
-                Some((_, 'n')) => {
-                    chars.next();
-                    result.push('\n');
-                }
-                Some((_, 't')) => {
-                    chars.next();
-                    result.push('\t');
-                }
-                Some((_, 'r')) => {
-                    chars.next();
-                    result.push('\r');
-                }
-                Some((_, '0')) => {
-                    chars.next();
-                    result.push('\0');
+    fn parse_list_extras(
+        &mut self,
+        start: usize,
+        identifiers: &mut [TypedIdentifier<'a>],
+        extras: &mut ListExtras<'_, 'a>,
+    ) {
+        match extras {
+            ListExtras::None => {}
+            ListExtras::Params(params) => self.parse_param_suffix(start, identifiers, params),
+            ListExtras::Defaults(defaults) => {
+                if self.lexer.peek().map(|tok| &tok.kind) != Some(&TokenKind::Assign) {
+                    return;
                 }
-                Some((_, 'x')) => {
-                    chars.next();
-                    let (_, char1) = chars.next().unwrap();
-                    let (_, char2) = chars.next().unwrap();
-                    result.push(
-                        char::from_u32(
-                            i64::from_str_radix(&format!("{}{}", char1, char2), 16).unwrap() as u32,
-                        )
-                        .unwrap(),
-                    );
+                self.lexer.next();
+
+                if let (Some((_, name)), Some(expr)) =
+                    (identifiers.last(), self.parse_expression(LOWEST, true))
+                {
+                    defaults.push((*name, expr));
                 }
-                _ => return Err(index + 1),
             }
-        } else {
-            result.push(char);
         }
     }
 
-    Ok(result)
-}
+    // A parameter followed by ... collects the rest of the arguments into an
+    // array, so it has to be the last one and can't have a default
+    fn parse_param_suffix(
+        &mut self,
+        start: usize,
+        identifiers: &mut [TypedIdentifier<'a>],
+        params: &mut FunctionParams<'a>,
+    ) {
+        // The param starts with its name
+        if let Some((_, name)) = identifiers.get(params.spans.len()) {
+            params.spans.push(Span {
+                start,
+                end: start + name.len(),
+            });
+        }
 
-fn operator_from_token(tok: TokenKind) -> Option<Operator> {
-    match tok {
-        TokenKind::Plus => Some(Operator::Add),
-        TokenKind::Minus => Some(Operator::Sub),
-        TokenKind::Asterisk => Some(Operator::Mul),
-        TokenKind::Slash => Some(Operator::Div),
-        TokenKind::Modulo => Some(Operator::Mod),
-        TokenKind::Power => Some(Operator::Power),
-        TokenKind::Or => Some(Operator::Or),
-        TokenKind::And => Some(Operator::And),
-        TokenKind::Equal => Some(Operator::Eq),
-        TokenKind::NotEqual => Some(Operator::Neq),
-        TokenKind::Less => Some(Operator::Lt),
-        TokenKind::LessEqual => Some(Operator::Lte),
-        TokenKind::Greater => Some(Operator::Gt),
-        TokenKind::GreaterEqual => Some(Operator::Gte),
-        _ => None,
-    }
-}
+        if params.variadic {
+            let end = match self.lexer.peek() {
+                Some(tok) => tok.span.start,
+                None => self.source.len(),
+            };
+            self.errors.push(AzulaError::new(
+                ErrorType::ParamAfterVariadic(self.source[start..end].trim_end().to_string()),
+                start,
+                end,
+            ));
+            return;
+        }
 
-fn operator_precedence(tok: TokenKind, allow_struct_init: bool) -> OperatorPrecedence {
-    match tok {
-        TokenKind::Or | TokenKind::And => COMPARISON,
-        TokenKind::Equal | TokenKind::NotEqual => EQUALS,
-        TokenKind::Less | TokenKind::LessEqual | TokenKind::Greater | TokenKind::GreaterEqual => {
-            LESS_GREATER
+        if self.lexer.peek().map(|tok| &tok.kind) == Some(&TokenKind::Ellipsis) {
+            self.lexer.next();
+            if let Some((typ, _)) = identifiers.last_mut() {
+                *typ = AzulaType::Slice(Rc::new(typ.clone()));
+            }
+            params.variadic = true;
+            return;
         }
-        TokenKind::Plus | TokenKind::Minus => SUM,
-        TokenKind::Slash | TokenKind::Asterisk | TokenKind::Power | TokenKind::Modulo => PRODUCT,
-        TokenKind::BraceOpen if allow_struct_init => STRUCT_INIT,
-        TokenKind::BracketOpen | TokenKind::SquareOpen => CALL,
-        TokenKind::Dot => ACCESS,
-        _ => LOWEST,
+
+        self.parse_default(start, &mut params.defaults);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    // Defaults are filled in for missing trailing arguments, so once one
+    // parameter has a default the rest need one too
+    fn parse_default(&mut self, start: usize, defaults: &mut Vec<ExpressionNode<'a>>) {
+        if self.lexer.peek().map(|tok| &tok.kind) == Some(&TokenKind::Assign) {
+            self.lexer.next();
 
-    macro_rules! parser_tests {
-        ($($name:ident: $value:expr,)*) => {
-            $(
-                #[test]
-                fn $name() {
-                    let (input, expected) = $value;
-                    let lexer: Lexer = input.into();
-                    let mut parser = Parser::new(input, lexer);
-                    if let Statement::Root(body) = parser.parse() {
-                        for (i, statement) in body.into_iter().enumerate() {
-                            assert_eq!(statement, expected[i])
-                        }
-                    };
-                }
-            )*
+            if let Some(expr) = self.parse_expression(LOWEST, true) {
+                defaults.push(expr);
+            }
+        } else if !defaults.is_empty() {
+            let end = match self.lexer.peek() {
+                Some(tok) => tok.span.start,
+                None => self.source.len(),
+            };
+            self.errors.push(AzulaError::new(
+                ErrorType::MissingDefault(self.source[start..end].trim_end().to_string()),
+                start,
+                end,
+            ));
         }
     }
 
-    parser_tests!(
-        function: (
-            "func test(x: bool): int { return 5; } func test2(x: int): int { return x; }",
-            vec![Statement::Function {
-                name: "test",
-                args: vec![(AzulaType::Bool, "x")],
-                returns: AzulaType::Int,
-                body: Rc::new(Statement::Block(vec![Statement::Return(Some(ExpressionNode {
-                    expression: Expression::Integer(5),
-                    typed: AzulaType::Int,
-                    span: Span { start: 33, end: 34},
+    fn expect_peek(&mut self, token_kind: TokenKind) -> bool {
+        if let Some(tok) = self.lexer.peek() {
+            return match tok.kind {
+                _ if tok.kind == token_kind => true,
+                _ => {
+                    let span = tok.span.clone();
+                    self.errors.push(AzulaError::new(
+                        ErrorType::ExpectedToken(
+                            format!("{:?}", token_kind),
+                            Some(format!("{:?}", tok.kind)),
+                        ),
+                        span.start,
+                        span.end,
+                    ));
+                    false
+                }
+            };
+        }
+
+        self.errors.push(AzulaError::new(
+            ErrorType::UnexpectedEOF,
+            self.source.len() - 1,
+            self.source.len(),
+        ));
+        false
+    }
+
+    fn parse_expression(
+        &mut self,
+        precedence: OperatorPrecedence,
+        allow_struct_init: bool,
+    ) -> Option<ExpressionNode<'a>> {
+        let tok = if let Some(tok) = self.lexer.next() {
+            tok
+        } else {
+            self.errors.push(AzulaError::new(
+                ErrorType::UnexpectedEOF,
+                self.source.len() - 2,
+                self.source.len() - 1,
+            ));
+            return None;
+        };
+
+        let mut left = match tok.kind {
+            TokenKind::Integer(i) => {
+                if let Some(peek) = self.lexer.peek() {
+                    if peek.kind == TokenKind::Dot {
+                        self.lexer.next();
+                        let second_number = self.parse_expression(CALL, false).unwrap();
+                        match second_number.expression {
+                            Expression::Integer(y) => Some(ExpressionNode {
+                                expression: Expression::Float(
+                                    format!("{}.{}", i, y).parse().unwrap(),
+                                ),
+                                typed: AzulaType::Float,
+                                span: Span {
+                                    start: tok.span.start,
+                                    end: second_number.span.end,
+                                },
+                            }),
+                            _ => panic!(),
+                        }
+                    } else {
+                        Some(ExpressionNode {
+                            expression: Expression::Integer(i),
+                            typed: AzulaType::Int,
+                            span: Span {
+                                start: tok.span.start,
+                                end: tok.span.end,
+                            },
+                        })
+                    }
+                } else {
+                    Some(ExpressionNode {
+                        expression: Expression::Integer(i),
+                        typed: AzulaType::Int,
+                        span: Span {
+                            start: tok.span.start,
+                            end: tok.span.end,
+                        },
+                    })
+                }
+            }
+            TokenKind::True => Some(ExpressionNode {
+                expression: Expression::Boolean(true),
+                typed: AzulaType::Bool,
+                span: Span {
+                    start: tok.span.start,
+                    end: tok.span.end,
+                },
+            }),
+            TokenKind::False => Some(ExpressionNode {
+                expression: Expression::Boolean(false),
+                typed: AzulaType::Bool,
+                span: Span {
+                    start: tok.span.start,
+                    end: tok.span.end,
+                },
+            }),
+            TokenKind::String(val) => {
+                let transformed = match string_transform(val) {
+                    Ok(str) => str,
+                    Err(index) => {
+                        self.errors.push(AzulaError::new(
+                            ErrorType::InvalidEscape,
+                            tok.span.start + index,
+                            tok.span.start + index + 1,
+                        ));
+                        "".to_string()
+                    }
+                };
+                Some(ExpressionNode {
+                    expression: Expression::String(transformed),
+                    typed: AzulaType::Pointer(Rc::new(AzulaType::Str)),
+                    span: Span {
+                        start: tok.span.start,
+                        end: tok.span.end,
+                    },
+                })
+            }
+            // Escapes in one are reported at the whole string, since taking
+            // off its indentation moves them
+            TokenKind::MultilineString(val) => {
+                let transformed = match string_transform(&dedent(val)) {
+                    Ok(str) => str,
+                    Err(_) => {
+                        self.errors.push(AzulaError::new(
+                            ErrorType::InvalidEscape,
+                            tok.span.start,
+                            tok.span.end,
+                        ));
+                        "".to_string()
+                    }
+                };
+                Some(ExpressionNode {
+                    expression: Expression::String(transformed),
+                    typed: AzulaType::Pointer(Rc::new(AzulaType::Str)),
+                    span: Span {
+                        start: tok.span.start,
+                        end: tok.span.end,
+                    },
+                })
+            }
+            TokenKind::RawString(val) => Some(ExpressionNode {
+                expression: Expression::String(val.to_string()),
+                typed: AzulaType::Pointer(Rc::new(AzulaType::Str)),
+                span: Span {
+                    start: tok.span.start,
+                    end: tok.span.end,
+                },
+            }),
+            TokenKind::Identifier("sizeof")
+                if self.lexer.peek().map(|tok| &tok.kind) == Some(&TokenKind::BracketOpen) =>
+            {
+                self.parse_sizeof(tok)
+            }
+            // new followed by a type, anything else is a plain identifier
+            TokenKind::Identifier("new")
+                if matches!(
+                    self.lexer.peek().map(|tok| &tok.kind),
+                    Some(TokenKind::Identifier(_) | TokenKind::SquareOpen | TokenKind::Ampersand)
+                ) =>
+            {
+                let typ = self.parse_type();
+
+                // Otherwise `new P { x: 1 }` would be taken as a literal of
+                // the pointer new gives
+                if let Some(brace) = self
+                    .lexer
+                    .peek()
+                    .filter(|tok| allow_struct_init && tok.kind == TokenKind::BraceOpen)
+                {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::NewWithLiteral,
+                        tok.span.start,
+                        brace.span.end,
+                    ));
+                    return None;
+                }
+
+                Some(ExpressionNode {
+                    expression: Expression::New(typ),
+                    typed: AzulaType::Infer,
+                    span: Span {
+                        start: tok.span.start,
+                        end: self.previous_end(),
+                    },
+                })
+            }
+            TokenKind::Identifier(x) => Some(ExpressionNode {
+                expression: Expression::Identifier(x.to_string()),
+                typed: AzulaType::Infer,
+                span: Span {
+                    start: tok.span.start,
+                    end: tok.span.end,
+                },
+            }),
+            TokenKind::BracketOpen => self.parse_bracketed(tok),
+            TokenKind::Bang => {
+                let expr = self.parse_expression(PREFIX, allow_struct_init).unwrap();
+
+                Some(ExpressionNode {
+                    expression: Expression::Not(Rc::new(expr.clone())),
+                    typed: AzulaType::Bool,
+                    span: Span {
+                        start: tok.span.start,
+                        end: expr.span.end,
+                    },
+                })
+            }
+            TokenKind::Minus => {
+                let expr = self.parse_expression(PREFIX, allow_struct_init).unwrap();
+
+                Some(ExpressionNode {
+                    expression: Expression::Negate(Rc::new(expr.clone())),
+                    typed: AzulaType::Infer,
+                    span: Span {
+                        start: tok.span.start,
+                        end: expr.span.end,
+                    },
+                })
+            }
+            TokenKind::Ampersand => {
+                let expr = self.parse_expression(PREFIX, allow_struct_init).unwrap();
+
+                Some(ExpressionNode {
+                    expression: Expression::Pointer(Rc::new(expr.clone())),
+                    typed: AzulaType::Infer,
+                    span: Span {
+                        start: tok.span.start,
+                        end: expr.span.end,
+                    },
+                })
+            }
+            TokenKind::Asterisk => self.parse_deref(tok, allow_struct_init, false),
+            // ** lexes as one token, so it's two derefs here
+            TokenKind::Power => self.parse_deref(tok, allow_struct_init, true),
+            TokenKind::SquareOpen => self.parse_array(tok),
+            TokenKind::Bar | TokenKind::Or => self.parse_closure(tok, allow_struct_init),
+            _ => {
+                self.errors.push(AzulaError::new(
+                    ErrorType::ExpectedExpression(format!("{:?}", tok.kind)),
+                    tok.span.start,
+                    tok.span.end,
+                ));
+                None
+            }
+        };
+
+        self.mark_try(allow_struct_init);
+        let mut peek_token = &if let Some(tok) = self.lexer.peek() {
+            tok
+        } else {
+            return left;
+        }
+        .kind;
+
+        // Keep creating infix expressions until precedence is higher or semicolon found
+        while peek_token.clone() != TokenKind::SemiColon
+            && precedence < operator_precedence(peek_token.clone(), allow_struct_init)
+        {
+            if left.is_none() {
+                return None;
+            }
+            left = self.parse_infix(left.unwrap(), allow_struct_init);
+            self.mark_try(allow_struct_init);
+            peek_token = if let Some(v) = self.lexer.peek() {
+                &v.kind
+            } else {
+                return left;
+            };
+        }
+
+        left
+    }
+
+    // A ? is the try operator unless an expression follows it and then a : before the
+    // expression ends, which makes it a ternary
+    fn mark_try(&mut self, allow_struct_init: bool) {
+        let mut tokens = self.lexer.clone();
+        if tokens.next().map(|tok| tok.kind) != Some(TokenKind::Question) {
+            return;
+        }
+
+        let starts_expression = tokens
+            .peek()
+            .map_or(false, |tok| starts_expression(&tok.kind));
+        if starts_expression && has_colon(tokens, allow_struct_init) {
+            return;
+        }
+
+        if let Some(tok) = self.lexer.peek_mut() {
+            tok.kind = TokenKind::Try;
+        }
+    }
+
+    fn parse_expression_list(&mut self, opening_delimiter: TokenKind) -> Vec<ExpressionNode<'a>> {
+        let closing_delimiter = opening_delimiter.get_closing_delimiter().unwrap();
+
+        self.lexer.next();
+
+        if let Some(peek) = self.lexer.peek() {
+            if peek.kind == closing_delimiter {
+                return vec![];
+            }
+        } else {
+            self.errors.push(AzulaError::new(
+                ErrorType::UnexpectedEOF,
+                self.source.len() - 2,
+                self.source.len() - 1,
+            ));
+            return vec![];
+        }
+
+        let mut expressions = vec![];
+
+        if let Some(expr) = self.parse_argument() {
+            expressions.push(expr);
+        }
+
+        let mut peek = self.lexer.peek().unwrap().kind.clone();
+        while peek == TokenKind::Comma {
+            self.lexer.next();
+            if let Some(expr) = self.parse_argument() {
+                expressions.push(expr);
+            }
+            peek = self.lexer.peek().unwrap().kind.clone();
+        }
+
+        self.expect_peek(closing_delimiter);
+
+        expressions
+    }
+
+    // An argument can be named, `width: 3`
+    fn parse_argument(&mut self) -> Option<ExpressionNode<'a>> {
+        let expr = self.parse_expression(LOWEST, true)?;
+
+        match (&expr.expression, self.lexer.peek()) {
+            (Expression::Identifier(_), Some(tok)) if tok.kind == TokenKind::Colon => {
+                self.lexer.next();
+                let value = self.parse_expression(LOWEST, true)?;
+
+                Some(ExpressionNode {
+                    span: Span {
+                        start: expr.span.start,
+                        end: value.span.end,
+                    },
+                    expression: Expression::NamedArgument(
+                        &self.source[expr.span.start..expr.span.end],
+                        Rc::new(value),
+                    ),
+                    typed: AzulaType::Infer,
+                })
+            }
+            _ => Some(expr),
+        }
+    }
+
+    fn parse_infix(
+        &mut self,
+        left: ExpressionNode<'a>,
+        allow_struct_init: bool,
+    ) -> Option<ExpressionNode<'a>> {
+        let operator = self.lexer.peek().unwrap();
+        let kind = operator.kind.clone();
+
+        match kind {
+            TokenKind::Dot => self.parse_struct_access(left),
+            TokenKind::BracketOpen => self.parse_function_call(left),
+            TokenKind::SquareOpen => self.parse_array_access(left),
+            TokenKind::BraceOpen if allow_struct_init => self.parse_struct_init(left),
+            TokenKind::As => self.parse_cast(left),
+            TokenKind::Question => self.parse_ternary(left, allow_struct_init),
+            TokenKind::DotDot => {
+                self.lexer.next();
+
+                let end = self.parse_expression(RANGE, allow_struct_init)?;
+                Some(ExpressionNode {
+                    span: Span {
+                        start: left.span.start,
+                        end: end.span.end,
+                    },
+                    expression: Expression::Range(Rc::new(left), Rc::new(end)),
+                    typed: AzulaType::Infer,
+                })
+            }
+            TokenKind::Try => {
+                let tok = self.lexer.next().unwrap();
+
+                Some(ExpressionNode {
+                    span: Span {
+                        start: left.span.start,
+                        end: tok.span.end,
+                    },
+                    expression: Expression::Try(Rc::new(left), AzulaType::Infer),
+                    typed: AzulaType::Infer,
+                })
+            }
+            TokenKind::Plus
+            | TokenKind::Minus
+            | TokenKind::Asterisk
+            | TokenKind::Slash
+            | TokenKind::Modulo
+            | TokenKind::Power
+            | TokenKind::Or
+            | TokenKind::And
+            | TokenKind::Equal
+            | TokenKind::NotEqual
+            | TokenKind::Less
+            | TokenKind::LessEqual
+            | TokenKind::Greater
+            | TokenKind::GreaterEqual
+            | TokenKind::ShiftLeft
+            | TokenKind::ShiftRight => {
+                let precedence = operator_precedence(operator.kind.clone(), allow_struct_init);
+
+                self.lexer.next();
+
+                let right = if let Some(expr) = self.parse_expression(precedence, allow_struct_init)
+                {
+                    expr
+                } else {
+                    return None;
+                };
+                let operator = operator_from_token(kind).unwrap();
+                return Some(ExpressionNode {
+                    expression: Expression::Infix(
+                        Rc::new(left.clone()),
+                        operator,
+                        Rc::new(right.clone()),
+                    ),
+                    typed: AzulaType::Infer,
+                    span: Span {
+                        start: left.span.start,
+                        end: right.span.end,
+                    },
+                });
+            }
+            _ => {
+                println!("unknown operator");
+                return None;
+            }
+        }
+    }
+
+    fn parse_cast(&mut self, left: ExpressionNode<'a>) -> Option<ExpressionNode<'a>> {
+        // as
+        self.lexer.next();
+
+        let target = self.parse_type();
+
+        // Types don't carry a span, so end the cast at the last character
+        // before whatever follows the type
+        let next_start = match self.lexer.peek() {
+            Some(tok) => tok.span.start,
+            None => self.source.len(),
+        };
+        let end = self.source[..next_start].trim_end().len();
+
+        Some(ExpressionNode {
+            expression: Expression::Cast(Rc::new(left.clone()), target),
+            typed: AzulaType::Infer,
+            span: Span {
+                start: left.span.start,
+                end,
+            },
+        })
+    }
+
+    // (a) is just a, (a, b) is a tuple
+    fn parse_bracketed(&mut self, tok: Token) -> Option<ExpressionNode<'a>> {
+        let expr = self.parse_expression(LOWEST, true)?;
+
+        if self.lexer.peek().map(|tok| &tok.kind) != Some(&TokenKind::Comma) {
+            self.expect_peek(TokenKind::BracketClose);
+
+            self.lexer.next();
+
+            return Some(expr);
+        }
+
+        let mut exprs = vec![expr];
+        while self.lexer.peek().map(|tok| &tok.kind) == Some(&TokenKind::Comma) {
+            self.lexer.next();
+            exprs.push(self.parse_expression(LOWEST, true)?);
+        }
+
+        if !self.expect_peek(TokenKind::BracketClose) {
+            return None;
+        }
+        let end_token = self.lexer.next().unwrap();
+
+        Some(ExpressionNode {
+            expression: Expression::Tuple(exprs),
+            typed: AzulaType::Infer,
+            span: Span {
+                start: tok.span.start,
+                end: end_token.span.end,
+            },
+        })
+    }
+
+    fn parse_ternary(
+        &mut self,
+        cond: ExpressionNode<'a>,
+        allow_struct_init: bool,
+    ) -> Option<ExpressionNode<'a>> {
+        // ?
+        self.lexer.next();
+
+        let then = self.parse_expression(LOWEST, allow_struct_init)?;
+
+        if !self.expect_peek(TokenKind::Colon) {
+            return None;
+        }
+        self.lexer.next();
+
+        // Parsing the else branch at LOWEST makes `a ? b : c ? d : e` group
+        // from the right
+        let otherwise = self.parse_expression(LOWEST, allow_struct_init)?;
+
+        let span = Span {
+            start: cond.span.start,
+            end: otherwise.span.end,
+        };
+
+        Some(ExpressionNode {
+            expression: Expression::Ternary(Rc::new(cond), Rc::new(then), Rc::new(otherwise)),
+            typed: AzulaType::Infer,
+            span,
+        })
+    }
+
+    fn parse_function_call(&mut self, left: ExpressionNode<'a>) -> Option<ExpressionNode<'a>> {
+        let exprs = self.parse_expression_list(TokenKind::BracketOpen);
+
+        let end_token = self.lexer.next().unwrap();
+
+        Some(ExpressionNode {
+            expression: Expression::FunctionCall {
+                function: Rc::new(left.clone()),
+                args: exprs,
+            },
+            typed: AzulaType::Infer,
+            span: Span {
+                start: left.span.start,
+                end: end_token.span.end,
+            },
+        })
+    }
+
+    // |x: int, y: int| body, or || body without any params
+    fn parse_closure(&mut self, tok: Token, allow_struct_init: bool) -> Option<ExpressionNode<'a>> {
+        let mut args = vec![];
+        if tok.kind == TokenKind::Bar {
+            loop {
+                args.push(self.parse_typed_identifier()?);
+                match self.lexer.next() {
+                    Some(next) if next.kind == TokenKind::Comma => continue,
+                    Some(next) if next.kind == TokenKind::Bar => break,
+                    Some(next) => {
+                        self.errors.push(AzulaError::new(
+                            ErrorType::ExpectedToken(
+                                format!("{:?}", TokenKind::Bar),
+                                Some(format!("{:?}", next.kind)),
+                            ),
+                            next.span.start,
+                            next.span.end,
+                        ));
+                        return None;
+                    }
+                    None => {
+                        self.errors.push(AzulaError::new(
+                            ErrorType::UnexpectedEOF,
+                            self.source.len() - 1,
+                            self.source.len(),
+                        ));
+                        return None;
+                    }
+                }
+            }
+        }
+
+        let body = self.parse_expression(LOWEST, allow_struct_init)?;
+
+        Some(ExpressionNode {
+            span: Span {
+                start: tok.span.start,
+                end: body.span.end,
+            },
+            expression: Expression::Closure {
+                args,
+                body: Rc::new(body),
+                captures: vec![],
+            },
+            typed: AzulaType::Infer,
+        })
+    }
+
+    fn parse_deref(
+        &mut self,
+        tok: Token,
+        allow_struct_init: bool,
+        twice: bool,
+    ) -> Option<ExpressionNode<'a>> {
+        let expr = self.parse_expression(PREFIX, allow_struct_init)?;
+        let span = Span {
+            start: tok.span.start,
+            end: expr.span.end,
+        };
+
+        let mut expr = ExpressionNode {
+            expression: Expression::Deref(Rc::new(expr)),
+            typed: AzulaType::Infer,
+            span: span.clone(),
+        };
+        if twice {
+            expr = ExpressionNode {
+                expression: Expression::Deref(Rc::new(expr)),
+                typed: AzulaType::Infer,
+                span,
+            };
+        }
+        Some(expr)
+    }
+
+    // Where the last token ended, for types which don't hand back their
+    // final token
+    fn previous_end(&mut self) -> usize {
+        let next = self
+            .lexer
+            .peek()
+            .map_or(self.source.len(), |tok| tok.span.start);
+        self.source[..next].trim_end().len()
+    }
+
+    // sizeof(type) takes a type rather than an expression
+    fn parse_sizeof(&mut self, tok: Token) -> Option<ExpressionNode<'a>> {
+        self.lexer.next();
+        let typ = self.parse_type();
+        if !self.expect_peek(TokenKind::BracketClose) {
+            return None;
+        }
+        let close = self.lexer.next().unwrap();
+
+        Some(ExpressionNode {
+            expression: Expression::SizeOf(typ),
+            typed: AzulaType::Int,
+            span: Span {
+                start: tok.span.start,
+                end: close.span.end,
+            },
+        })
+    }
+
+    fn parse_array(&mut self, tok: Token) -> Option<ExpressionNode<'a>> {
+        if let Some(peek) = self.lexer.peek() {
+            if peek.kind == TokenKind::SquareClose {
+                let close = self.lexer.next().unwrap();
+                return Some(ExpressionNode {
+                    expression: Expression::Array(vec![]),
+                    typed: AzulaType::Array(Rc::new(AzulaType::Infer), Some(0)),
+                    span: Span {
+                        start: tok.span.start,
+                        end: close.span.end,
+                    },
+                });
+            }
+        } else {
+            self.errors.push(AzulaError::new(
+                ErrorType::UnexpectedEOF,
+                self.source.len() - 2,
+                self.source.len() - 1,
+            ));
+            return Some(ExpressionNode {
+                expression: Expression::Array(vec![]),
+                typed: AzulaType::Array(Rc::new(AzulaType::Infer), Some(0)),
+                span: Span {
+                    start: tok.span.start,
+                    end: 0,
+                },
+            });
+        }
+
+        let mut expressions = vec![];
+
+        if let Some(expr) = self.parse_expression(LOWEST, true) {
+            expressions.push(expr);
+        }
+
+        let mut peek = self.lexer.peek().unwrap().kind.clone();
+        // Parse initialiser like [0; 20]
+        if peek == TokenKind::SemiColon {
+            self.lexer.next();
+
+            let count = self.parse_expression(LOWEST, true)?;
+            self.expect_peek(TokenKind::SquareClose);
+            let close = self.lexer.next().unwrap();
+            let span = Span {
+                start: tok.span.start,
+                end: close.span.end,
+            };
+
+            // Other counts are left to the typechecker to evaluate
+            let v = match count.expression {
+                Expression::Integer(v) => v,
+                _ => {
+                    return Some(ExpressionNode {
+                        expression: Expression::ArrayRepeat(
+                            Rc::new(expressions.pop()?),
+                            Rc::new(count),
+                        ),
+                        typed: AzulaType::Infer,
+                        span,
+                    })
+                }
+            };
+            for _ in 0..v - 1 {
+                expressions.push(expressions[0].clone());
+            }
+            return Some(ExpressionNode {
+                expression: Expression::Array(expressions.clone()),
+                typed: AzulaType::Array(Rc::new(AzulaType::Infer), Some(expressions.len())),
+                span,
+            });
+        }
+        while peek == TokenKind::Comma {
+            // TODO allow trailing comma
+            self.lexer.next();
+            if let Some(expr) = self.parse_expression(LOWEST, true) {
+                expressions.push(expr);
+            }
+            peek = self.lexer.peek().unwrap().kind.clone();
+        }
+
+        self.expect_peek(TokenKind::SquareClose);
+
+        let close = self.lexer.next().unwrap();
+
+        Some(ExpressionNode {
+            expression: Expression::Array(expressions.clone()),
+            typed: AzulaType::Array(Rc::new(AzulaType::Infer), Some(expressions.len())),
+            span: Span {
+                start: tok.span.start,
+                end: close.span.end,
+            },
+        })
+    }
+
+    fn parse_array_access(&mut self, left: ExpressionNode<'a>) -> Option<ExpressionNode<'a>> {
+        self.lexer.next();
+        let index = match self.parse_expression(LOWEST, true) {
+            Some(expr) => expr,
+            None => return None,
+        };
+
+        if !self.expect_peek(TokenKind::SquareClose) {
+            return None;
+        }
+
+        let end_token = self.lexer.next().unwrap();
+
+        Some(ExpressionNode {
+            expression: Expression::ArrayAccess(Rc::new(left.clone()), Rc::new(index)),
+            typed: AzulaType::Infer,
+            span: Span {
+                start: left.span.start,
+                end: end_token.span.end,
+            },
+        })
+    }
+
+    fn parse_struct_access(&mut self, left: ExpressionNode<'a>) -> Option<ExpressionNode<'a>> {
+        self.lexer.next();
+        let index = match self.parse_expression(ACCESS, false) {
+            Some(expr) => expr,
+            None => return None,
+        };
+
+        Some(ExpressionNode {
+            expression: Expression::StructAccess(Rc::new(left.clone()), Rc::new(index.clone())),
+            typed: AzulaType::Infer,
+            span: Span {
+                start: left.span.start,
+                end: index.span.end,
+            },
+        })
+    }
+
+    fn parse_struct_init(&mut self, left: ExpressionNode<'a>) -> Option<ExpressionNode<'a>> {
+        // {
+        self.lexer.next();
+
+        let mut attrs = vec![];
+
+        let mut peek = match self.lexer.peek() {
+            Some(peek) => peek.kind.clone(),
+            None => {
+                self.errors.push(AzulaError::new(
+                    ErrorType::UnexpectedEOF,
+                    self.source.len() - 2,
+                    self.source.len() - 1,
+                ));
+                return None;
+            }
+        };
+
+        while peek != TokenKind::BraceClose {
+            if let Some(tok) = self.lexer.peek() {
+                if tok.kind == TokenKind::Comma {
+                    self.lexer.next();
+                    if let Some(tok) = self.lexer.peek() {
+                        if tok.kind == TokenKind::BraceClose {
+                            break;
+                        }
+                    }
+                }
+            }
+            let tok = self.lexer.next();
+            let name = match tok {
+                Some(Token {
+                    kind: TokenKind::Identifier(val),
+                    span: _,
+                }) => val,
+                _ => todo!("{:?}", tok),
+            };
+
+            if !self.expect_peek(TokenKind::Colon) {
+                return None;
+            }
+
+            self.lexer.next();
+
+            let expr = self.parse_expression(LOWEST, true).unwrap();
+
+            attrs.push((name, expr));
+
+            peek = match self.lexer.peek() {
+                Some(peek) => peek.kind.clone(),
+                None => {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::UnexpectedEOF,
+                        self.source.len() - 2,
+                        self.source.len() - 1,
+                    ));
+                    return None;
+                }
+            };
+        }
+
+        if !self.expect_peek(TokenKind::BraceClose) {
+            return None;
+        }
+
+        let end_token = self.lexer.next().unwrap();
+
+        Some(ExpressionNode {
+            expression: Expression::StructInitialisation(Rc::new(left.clone()), attrs),
+            typed: AzulaType::Infer,
+            span: Span {
+                start: left.span.start,
+                end: end_token.span.end,
+            },
+        })
+    }
+}
+
+// A """ string starts on the line after its opening quotes and ends on the
+// line before its closing ones if they're on a line of their own. The
+// indentation every line shares, the closing quotes' included, is taken off
+fn dedent(str: &str) -> String {
+    let str = str.strip_prefix('\n').unwrap_or(str);
+    let mut lines: Vec<&str> = str.split('\n').collect();
+    let indentation = |line: &str| line.len() - line.trim_start_matches([' ', '\t']).len();
+
+    let mut indent = usize::MAX;
+    if lines.len() > 1 && lines.last().unwrap().trim().is_empty() {
+        indent = indentation(lines.pop().unwrap());
+    }
+    for line in lines.iter().filter(|line| !line.trim().is_empty()) {
+        indent = indent.min(indentation(line));
+    }
+
+    let lines: Vec<_> = lines
+        .iter()
+        .map(|line| line.get(indent..).unwrap_or(""))
+        .collect();
+    lines.join("\n")
+}
+
+fn string_transform(str: &str) -> Result<String, usize> {
+    let mut result = String::new();
+
+    let mut chars = str.chars().enumerate().peekable();
+
+    loop {
+        let char = chars.next();
+        if char.is_none() {
+            break;
+        }
+        let (index, char) = char.unwrap();
+        if char == '\\' {
+            match chars.peek() {
+                Some((_, '\\')) => {
+                    chars.next();
+                    result.push('\\');
+                }
+                Some((_, 'n')) => {
+                    chars.next();
+                    result.push('\n');
+                }
+                Some((_, 't')) => {
+                    chars.next();
+                    result.push('\t');
+                }
+                Some((_, 'r')) => {
+                    chars.next();
+                    result.push('\r');
+                }
+                Some((_, '0')) => {
+                    chars.next();
+                    result.push('\0');
+                }
+                Some((_, 'x')) => {
+                    chars.next();
+                    let (_, char1) = chars.next().unwrap();
+                    let (_, char2) = chars.next().unwrap();
+                    result.push(
+                        char::from_u32(
+                            i64::from_str_radix(&format!("{}{}", char1, char2), 16).unwrap() as u32,
+                        )
+                        .unwrap(),
+                    );
+                }
+                _ => return Err(index + 1),
+            }
+        } else {
+            result.push(char);
+        }
+    }
+
+    Ok(result)
+}
+
+fn operator_from_token(tok: TokenKind) -> Option<Operator> {
+    match tok {
+        TokenKind::Plus => Some(Operator::Add),
+        TokenKind::Minus => Some(Operator::Sub),
+        TokenKind::Asterisk => Some(Operator::Mul),
+        TokenKind::Slash => Some(Operator::Div),
+        TokenKind::Modulo => Some(Operator::Mod),
+        TokenKind::Power => Some(Operator::Power),
+        TokenKind::Or => Some(Operator::Or),
+        TokenKind::And => Some(Operator::And),
+        TokenKind::Equal => Some(Operator::Eq),
+        TokenKind::NotEqual => Some(Operator::Neq),
+        TokenKind::Less => Some(Operator::Lt),
+        TokenKind::LessEqual => Some(Operator::Lte),
+        TokenKind::Greater => Some(Operator::Gt),
+        TokenKind::GreaterEqual => Some(Operator::Gte),
+        TokenKind::ShiftLeft => Some(Operator::Shl),
+        TokenKind::ShiftRight => Some(Operator::Shr),
+        _ => None,
+    }
+}
+
+fn starts_expression(tok: &TokenKind) -> bool {
+    matches!(
+        tok,
+        TokenKind::Integer(_)
+            | TokenKind::True
+            | TokenKind::False
+            | TokenKind::String(_)
+            | TokenKind::MultilineString(_)
+            | TokenKind::RawString(_)
+            | TokenKind::Identifier(_)
+            | TokenKind::BracketOpen
+            | TokenKind::Bang
+            | TokenKind::Minus
+            | TokenKind::Ampersand
+            | TokenKind::Asterisk
+            | TokenKind::Power
+            | TokenKind::SquareOpen
+            | TokenKind::Bar
+            | TokenKind::Or
+    )
+}
+
+// Looks for the : of a ternary, stopping where the expression has to end. Without
+// struct literals a { at the top is the body of an if or a loop
+fn has_colon<'a>(tokens: impl Iterator<Item = Token<'a>>, allow_struct_init: bool) -> bool {
+    let mut depth = 0;
+    for tok in tokens {
+        match tok.kind {
+            TokenKind::BraceOpen if depth == 0 && !allow_struct_init => return false,
+            TokenKind::BracketOpen | TokenKind::SquareOpen | TokenKind::BraceOpen => depth += 1,
+            TokenKind::BracketClose | TokenKind::SquareClose | TokenKind::BraceClose => {
+                if depth == 0 {
+                    return false;
+                }
+                depth -= 1;
+            }
+            TokenKind::Colon if depth == 0 => return true,
+            TokenKind::SemiColon | TokenKind::Comma if depth == 0 => return false,
+            _ => {}
+        }
+    }
+    false
+}
+
+fn operator_precedence(tok: TokenKind, allow_struct_init: bool) -> OperatorPrecedence {
+    match tok {
+        TokenKind::Question => TERNARY,
+        TokenKind::DotDot => RANGE,
+        TokenKind::Or | TokenKind::And => COMPARISON,
+        TokenKind::Equal | TokenKind::NotEqual => EQUALS,
+        TokenKind::Less | TokenKind::LessEqual | TokenKind::Greater | TokenKind::GreaterEqual => {
+            LESS_GREATER
+        }
+        TokenKind::ShiftLeft | TokenKind::ShiftRight => SHIFT,
+        TokenKind::Plus | TokenKind::Minus => SUM,
+        TokenKind::Slash | TokenKind::Asterisk | TokenKind::Power | TokenKind::Modulo => PRODUCT,
+        TokenKind::As => CAST,
+        TokenKind::BraceOpen if allow_struct_init => STRUCT_INIT,
+        TokenKind::BracketOpen | TokenKind::SquareOpen | TokenKind::Try => CALL,
+        TokenKind::Dot => ACCESS,
+        _ => LOWEST,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! parser_tests {
+        ($($name:ident: $value:expr,)*) => {
+            $(
+                #[test]
+                fn $name() {
+                    let (input, expected) = $value;
+                    let lexer: Lexer = input.into();
+                    let mut parser = Parser::new(input, lexer);
+                    if let Statement::Root(body) = parser.parse() {
+                        for (i, statement) in body.into_iter().enumerate() {
+                            assert_eq!(statement, expected[i])
+                        }
+                    };
+                }
+            )*
+        }
+    }
+
+    parser_tests!(
+        function: (
+            "func test(x: bool): int { return 5; } func test2(x: int): int { return x; }",
+            vec![Statement::Function {
+                name: "test",
+                generics: vec![],
+                bounds: vec![],
+                args: vec![(AzulaType::Bool, "x")],
+                arg_spans: vec![Span { start: 10, end: 11 }],
+                defaults: vec![],
+                variadic: false,
+                returns: AzulaType::Int,
+                body: Rc::new(Statement::Block(vec![Statement::Return(Some(ExpressionNode {
+                    expression: Expression::Integer(5),
+                    typed: AzulaType::Int,
+                    span: Span { start: 33, end: 34},
                 }), Span { start: 26, end: 35})])),
+                public: false,
+                attributes: vec![],
+                doc: None,
                 span: Span { start: 0, end: 37},
             }, Statement::Function {
                 name: "test2",
+                generics: vec![],
+                bounds: vec![],
                 args: vec![(AzulaType::Int, "x")],
+                arg_spans: vec![Span { start: 49, end: 50 }],
+                defaults: vec![],
+                variadic: false,
                 returns: AzulaType::Int,
                 body: Rc::new(Statement::Block(vec![Statement::Return(Some(ExpressionNode {
                     expression: Expression::Identifier("x".to_string()),
                     typed: AzulaType::Infer,
-                    span: Span { start: 71, end: 72},
-                }), Span { start: 64, end: 73},)])),
-                span: Span { start: 38, end: 75},
-            }],
-        ),
-        return_int: (
-            "return 5",
-            vec![Statement::Return(Some(ExpressionNode {
-                expression: Expression::Integer(5),
-                typed: AzulaType::Int,
-                span: Span { start: 7, end: 8},
-            }), Span{start: 0, end: 8})],
-        ),
-        return_none: (
-            "return;",
-            vec![Statement::Return(None, Span{start: 0, end: 6})],
-        ),
-    );
+                    span: Span { start: 71, end: 72},
+                }), Span { start: 64, end: 73},)])),
+                public: false,
+                attributes: vec![],
+                doc: None,
+                span: Span { start: 38, end: 75},
+            }],
+        ),
+        return_int: (
+            "return 5",
+            vec![Statement::Return(Some(ExpressionNode {
+                expression: Expression::Integer(5),
+                typed: AzulaType::Int,
+                span: Span { start: 7, end: 8},
+            }), Span{start: 0, end: 8})],
+        ),
+        return_none: (
+            "return;",
+            vec![Statement::Return(None, Span{start: 0, end: 6})],
+        ),
+    );
+
+    #[test]
+    fn test_parse_function() {
+        // No args, no return
+        let input = "func main { }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let func = parser.parse_function().unwrap();
+        assert!(matches!(func, Statement::Function { .. }));
+        if let Statement::Function {
+            name,
+            args,
+            returns,
+            body,
+            ..
+        } = func
+        {
+            assert_eq!(name, "main");
+            assert_eq!(args, vec![]);
+            assert_eq!(returns, AzulaType::Void);
+            assert_eq!(body, Rc::new(Statement::Block(vec![])));
+        }
+
+        // No args, no return
+        let input = "func main() { }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let func = parser.parse_function();
+        println!("{:?}", parser.errors);
+
+        let func = func.unwrap();
+        assert!(matches!(func, Statement::Function { .. }));
+        if let Statement::Function {
+            name,
+            args,
+            returns,
+            body,
+            ..
+        } = func
+        {
+            assert_eq!(name, "main");
+            assert_eq!(args, vec![]);
+            assert_eq!(returns, AzulaType::Void);
+            assert_eq!(body, Rc::new(Statement::Block(vec![])));
+        }
+
+        // Return type
+        let input = "func test: bool { }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let func = parser.parse_function().unwrap();
+        assert!(matches!(func, Statement::Function { .. }));
+        if let Statement::Function {
+            name,
+            args,
+            returns,
+            body,
+            ..
+        } = func
+        {
+            assert_eq!(name, "test");
+            assert_eq!(args, vec![]);
+            assert_eq!(returns, AzulaType::Bool);
+            assert_eq!(body, Rc::new(Statement::Block(vec![])));
+        }
+
+        // Args & return type
+        let input = "func test(x: int, y: bool): bool { }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let func = parser.parse_function().unwrap();
+        assert!(matches!(func, Statement::Function { .. }));
+        if let Statement::Function {
+            name,
+            args,
+            returns,
+            body,
+            ..
+        } = func
+        {
+            assert_eq!(name, "test");
+            assert_eq!(args, vec![(AzulaType::Int, "x"), (AzulaType::Bool, "y")]);
+            assert_eq!(returns, AzulaType::Bool);
+            assert_eq!(body, Rc::new(Statement::Block(vec![])));
+        }
+    }
+
+    #[test]
+    fn test_parse_extern_function() {
+        let input = "extern varargs func test(int): bool;";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let func = parser.parse_statement().unwrap();
+        println!("{:?}", func);
+        assert!(matches!(func, Statement::ExternFunction { .. }));
+        if let Statement::ExternFunction {
+            name,
+            varargs,
+            args,
+            returns,
+            ..
+        } = func
+        {
+            assert_eq!(name, "test");
+            assert_eq!(varargs, true);
+            assert_eq!(args, vec![(AzulaType::Int, None)]);
+            assert_eq!(returns, AzulaType::Bool);
+        }
+    }
+
+    #[test]
+    fn test_parse_extern_arg_names() {
+        let input = "extern func puts(s: string, int, point: Point): int;";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        match parser.parse_statement() {
+            Some(Statement::ExternFunction { args, .. }) => assert_eq!(
+                args,
+                vec![
+                    (AzulaType::Pointer(Rc::new(AzulaType::Str)), Some("s")),
+                    (AzulaType::Int, None),
+                    (AzulaType::Named("Point".to_string()), Some("point")),
+                ]
+            ),
+            stmt => panic!("expected extern function, got {:?}", stmt),
+        }
+        assert!(parser.errors.is_empty());
+    }
+
+    #[test]
+    fn test_parse_assign() {
+        let input = "var test = 5;";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let func = parser.parse_statement().unwrap();
+        assert!(matches!(func, Statement::Assign(..)));
+
+        let input = "const test = 5;";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let func = parser.parse_statement().unwrap();
+        assert!(matches!(func, Statement::Assign(..)));
+
+        // Type annotation
+        let input = "var test: int = 5;";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let assign = parser.parse_assign(true).unwrap();
+        assert!(matches!(func, Statement::Assign(..)));
+        if let Statement::Assign(mutable, name, type_annotation, value, ..) = assign {
+            assert_eq!(mutable, true);
+            assert_eq!(name, "test".to_string());
+            assert_eq!(type_annotation, Some(AzulaType::Int));
+            assert_eq!(
+                value,
+                ExpressionNode {
+                    expression: Expression::Integer(5),
+                    typed: AzulaType::Int,
+                    span: Span { start: 16, end: 17 }
+                }
+            )
+        }
+    }
+
+    #[test]
+    fn test_parse_struct() {
+        // No attributes
+        let input = "struct Test { }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let struc = parser.parse_statement().unwrap();
+        assert!(matches!(struc, Statement::Struct { .. }));
+
+        // Single attribute
+        let input = "struct Test { test: int }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let struc = parser.parse_statement().unwrap();
+        assert!(matches!(struc, Statement::Struct { .. }));
+        if let Statement::Struct {
+            name, attributes, ..
+        } = struc
+        {
+            assert_eq!(name, "Test");
+            assert_eq!(attributes, vec![(AzulaType::Int, "test")]);
+        }
+
+        // Multiple attribute
+        let input = "struct Test { test: int, test1: &str, }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let struc = parser.parse_statement().unwrap();
+        assert!(matches!(struc, Statement::Struct { .. }));
+        if let Statement::Struct {
+            name, attributes, ..
+        } = struc
+        {
+            assert_eq!(name, "Test");
+            assert_eq!(
+                attributes,
+                vec![
+                    (AzulaType::Int, "test"),
+                    (AzulaType::Pointer(Rc::new(AzulaType::Str)), "test1")
+                ]
+            );
+        }
+
+        // Defaults
+        let input = "struct Config { name: &str, retries: int = 3 }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let struc = parser.parse_statement().unwrap();
+        if let Statement::Struct {
+            attributes,
+            defaults,
+            ..
+        } = struc
+        {
+            assert_eq!(attributes.len(), 2);
+            assert_eq!(
+                defaults
+                    .into_iter()
+                    .map(|(name, default)| (name, default.expression))
+                    .collect::<Vec<_>>(),
+                vec![("retries", Expression::Integer(3))]
+            );
+        } else {
+            panic!("expected struct, got {:?}", struc);
+        }
+        assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+    }
+
+    #[test]
+    fn test_parse_generic_struct() {
+        let input = "struct Pair<A, B> { a: A, b: B }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let struc = parser.parse_statement().unwrap();
+        if let Statement::Struct {
+            name,
+            generics,
+            attributes,
+            ..
+        } = struc
+        {
+            assert_eq!(name, "Pair");
+            assert_eq!(generics, vec!["A", "B"]);
+            assert_eq!(
+                attributes,
+                vec![
+                    (AzulaType::Named("A".to_string()), "a"),
+                    (AzulaType::Named("B".to_string()), "b")
+                ]
+            );
+        } else {
+            panic!("expected a struct");
+        }
+
+        // The closing >> is split in two
+        let input = "var x: List<Pair<int, float>> = y;";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let stmt = parser.parse_statement().unwrap();
+        if let Statement::Assign(_, _, typ, _, _, _) = stmt {
+            assert_eq!(
+                typ,
+                Some(AzulaType::Generic(
+                    "List".to_string(),
+                    vec![AzulaType::Generic(
+                        "Pair".to_string(),
+                        vec![AzulaType::Int, AzulaType::Float]
+                    )]
+                ))
+            );
+        } else {
+            panic!("expected an assignment");
+        }
+    }
+
+    #[test]
+    fn test_parse_if() {
+        let input = "if x { return 5; }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let func = parser.parse_if().unwrap();
+        assert!(matches!(func, Statement::If(..)));
+        if let Statement::If(expr, body, ..) = func {
+            assert_eq!(expr.expression, Expression::Identifier("x".to_string()));
+            assert_eq!(
+                body[0],
+                Statement::Return(
+                    Some(ExpressionNode {
+                        expression: Expression::Integer(5),
+                        typed: AzulaType::Int,
+                        span: Span { start: 14, end: 15 }
+                    }),
+                    Span { start: 7, end: 16 }
+                )
+            );
+        }
+
+        let input = "if x || y { return 5; }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let func = parser.parse_if();
+        println!("{:?}", parser.errors);
+        let func = func.unwrap();
+        assert!(matches!(func, Statement::If(..)));
+        if let Statement::If(expr, body, ..) = func {
+            assert_eq!(
+                expr.expression,
+                Expression::Infix(
+                    Rc::new(ExpressionNode {
+                        expression: Expression::Identifier("x".to_string()),
+                        typed: AzulaType::Infer,
+                        span: Span { start: 3, end: 4 },
+                    }),
+                    Operator::Or,
+                    Rc::new(ExpressionNode {
+                        expression: Expression::Identifier("y".to_string()),
+                        typed: AzulaType::Infer,
+                        span: Span { start: 8, end: 9 },
+                    })
+                )
+            );
+            assert_eq!(
+                body[0],
+                Statement::Return(
+                    Some(ExpressionNode {
+                        expression: Expression::Integer(5),
+                        typed: AzulaType::Int,
+                        span: Span { start: 19, end: 20 }
+                    }),
+                    Span { start: 12, end: 21 }
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_type() {
+        // Basic type
+        let input = "int";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let typ = parser.parse_type();
+        assert_eq!(typ, AzulaType::Int);
+
+        // Pointer
+        let input = "&str";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let typ = parser.parse_type();
+        assert_eq!(typ, AzulaType::Pointer(Rc::new(AzulaType::Str)));
+
+        // Nested Pointers
+        let input = "&&&str";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let typ = parser.parse_type();
+        assert_eq!(
+            typ,
+            AzulaType::Pointer(Rc::new(AzulaType::Pointer(Rc::new(AzulaType::Pointer(
+                Rc::new(AzulaType::Str)
+            )))))
+        );
+
+        // The type of a variable
+        let input = "&typeof(x)";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let typ = parser.parse_type();
+        assert_eq!(
+            typ,
+            AzulaType::Pointer(Rc::new(AzulaType::TypeOf("x".to_string())))
+        );
+
+        // Array
+        let input = "[int]";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let typ = parser.parse_type();
+        assert_eq!(typ, AzulaType::Array(Rc::new(AzulaType::Int), None));
+
+        // Nested Array
+        let input = "[[int]]";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let typ = parser.parse_type();
+        assert_eq!(
+            typ,
+            AzulaType::Array(
+                Rc::new(AzulaType::Array(Rc::new(AzulaType::Int), None)),
+                None
+            )
+        );
+
+        // Sized Array
+        let input = "[int; 20]";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let typ = parser.parse_type();
+        assert_eq!(typ, AzulaType::Array(Rc::new(AzulaType::Int), Some(20)));
+
+        // Slice
+        let input = "[]int";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let typ = parser.parse_type();
+        assert_eq!(typ, AzulaType::Slice(Rc::new(AzulaType::Int)));
+
+        // Sized by a constant
+        let input = "[int; SIZE]";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let typ = parser.parse_type();
+        assert_eq!(
+            typ,
+            AzulaType::ConstArray(Rc::new(AzulaType::Int), "SIZE".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_reassign() {
+        // Basic int
+        let input = "x = 5;";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let stmt = parser.parse_statement().unwrap();
+        assert_eq!(
+            stmt,
+            Statement::Reassign(
+                ExpressionNode {
+                    expression: Expression::Identifier("x".to_string()),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 0, end: 1 }
+                },
+                ExpressionNode {
+                    expression: Expression::Integer(5),
+                    typed: AzulaType::Int,
+                    span: Span { start: 4, end: 5 }
+                },
+                Span { start: 0, end: 6 },
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_while() {
+        // Basic int
+        let input = "while x { i = 1; }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let stmt = parser.parse_statement().unwrap();
+        assert_eq!(
+            stmt,
+            Statement::While(
+                ExpressionNode {
+                    expression: Expression::Identifier("x".to_string()),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 6, end: 7 }
+                },
+                vec![Statement::Reassign(
+                    ExpressionNode {
+                        expression: Expression::Identifier("i".to_string()),
+                        typed: AzulaType::Infer,
+                        span: Span { start: 10, end: 11 },
+                    },
+                    ExpressionNode {
+                        expression: Expression::Integer(1),
+                        typed: AzulaType::Int,
+                        span: Span { start: 14, end: 15 },
+                    },
+                    Span { start: 10, end: 16 }
+                )],
+                None,
+                Span { start: 0, end: 18 },
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_array_access() {
+        // Basic int
+        let input = "x[10]";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let expr = parser.parse_expression(LOWEST, true).unwrap();
+        assert_eq!(
+            expr,
+            ExpressionNode {
+                expression: Expression::ArrayAccess(
+                    Rc::new(ExpressionNode {
+                        expression: Expression::Identifier("x".to_string()),
+                        typed: AzulaType::Infer,
+                        span: Span { start: 0, end: 1 }
+                    }),
+                    Rc::new(ExpressionNode {
+                        expression: Expression::Integer(10),
+                        typed: AzulaType::Int,
+                        span: Span { start: 2, end: 4 }
+                    })
+                ),
+                typed: AzulaType::Infer,
+                span: Span { start: 0, end: 5 }
+            }
+        );
+    }
 
     #[test]
-    fn test_parse_function() {
-        // No args, no return
-        let input = "func main { }";
+    fn test_parse_struct_access() {
+        // Basic int
+        let input = "test.test";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let func = parser.parse_function().unwrap();
-        assert!(matches!(func, Statement::Function { .. }));
-        if let Statement::Function {
-            name,
-            args,
-            returns,
-            body,
-            ..
-        } = func
-        {
-            assert_eq!(name, "main");
-            assert_eq!(args, vec![]);
-            assert_eq!(returns, AzulaType::Void);
-            assert_eq!(body, Rc::new(Statement::Block(vec![])));
+        let expr = parser.parse_expression(LOWEST, true).unwrap();
+        assert_eq!(
+            expr,
+            ExpressionNode {
+                expression: Expression::StructAccess(
+                    Rc::new(ExpressionNode {
+                        expression: Expression::Identifier("test".to_string()),
+                        typed: AzulaType::Infer,
+                        span: Span { start: 0, end: 4 }
+                    }),
+                    Rc::new(ExpressionNode {
+                        expression: Expression::Identifier("test".to_string()),
+                        typed: AzulaType::Infer,
+                        span: Span { start: 5, end: 9 }
+                    })
+                ),
+                typed: AzulaType::Infer,
+                span: Span { start: 0, end: 9 }
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_struct_init() {
+        // Basic struct init
+        let input = "Test { test: 5 }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let expr = parser.parse_expression(LOWEST, true).unwrap();
+        assert_eq!(
+            expr,
+            ExpressionNode {
+                expression: Expression::StructInitialisation(
+                    Rc::new(ExpressionNode {
+                        expression: Expression::Identifier("Test".to_string()),
+                        typed: AzulaType::Infer,
+                        span: Span { start: 0, end: 4 }
+                    }),
+                    vec![(
+                        "test",
+                        ExpressionNode {
+                            expression: Expression::Integer(5),
+                            typed: AzulaType::Int,
+                            span: Span { start: 13, end: 14 },
+                        }
+                    )]
+                ),
+                typed: AzulaType::Infer,
+                span: Span { start: 0, end: 16 }
+            }
+        );
+
+        // Multiple args
+        let input = "Test { test: 5, test1: true }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let expr = parser.parse_expression(LOWEST, true).unwrap();
+        assert_eq!(
+            expr,
+            ExpressionNode {
+                expression: Expression::StructInitialisation(
+                    Rc::new(ExpressionNode {
+                        expression: Expression::Identifier("Test".to_string()),
+                        typed: AzulaType::Infer,
+                        span: Span { start: 0, end: 4 }
+                    }),
+                    vec![
+                        (
+                            "test",
+                            ExpressionNode {
+                                expression: Expression::Integer(5),
+                                typed: AzulaType::Int,
+                                span: Span { start: 13, end: 14 },
+                            }
+                        ),
+                        (
+                            "test1",
+                            ExpressionNode {
+                                expression: Expression::Boolean(true),
+                                typed: AzulaType::Bool,
+                                span: Span { start: 23, end: 27 },
+                            }
+                        )
+                    ]
+                ),
+                typed: AzulaType::Infer,
+                span: Span { start: 0, end: 29 }
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_typed_identifier() {
+        // Basic int
+        let input = "x: int";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let (typ, name) = parser.parse_typed_identifier().unwrap();
+        assert_eq!(typ, AzulaType::Int);
+        assert_eq!(name, "x");
+
+        // Custom struct type
+        let input = "my_typed_identifier: CustomStruct";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let (typ, name) = parser.parse_typed_identifier().unwrap();
+        assert_eq!(typ, AzulaType::Named("CustomStruct".to_string()));
+        assert_eq!(name, "my_typed_identifier");
+
+        // Non identifier for name
+        let input = "4: CustomStruct";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        parser.parse_typed_identifier();
+        assert_eq!(parser.errors.len(), 1);
+        assert!(matches!(
+            parser.errors[0].error_type,
+            ErrorType::ExpectedToken(..)
+        ));
+        if let ErrorType::ExpectedToken(expected, ..) = &parser.errors[0].error_type {
+            assert_eq!(expected.clone(), "Identifier(\"\")".to_string());
         }
 
-        // No args, no return
-        let input = "func main() { }";
+        // No colon - EOF
+        let input = "x";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        parser.parse_typed_identifier();
+        assert_eq!(parser.errors.len(), 1);
+        assert!(matches!(
+            parser.errors[0].error_type,
+            ErrorType::UnexpectedEOF,
+        ));
+
+        // Pointer
+        let input = "x: &str";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let (typ, name) = parser.parse_typed_identifier().unwrap();
+        assert_eq!(parser.errors.len(), 0);
+        assert_eq!(typ, AzulaType::Pointer(Rc::new(AzulaType::Str)));
+        assert_eq!(name, "x");
+    }
+
+    #[test]
+    fn test_parse_typed_identifier_list() {
+        let input = "(x: int, y: int, z: bool)";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let identifiers =
+            parser.parse_typed_identifier_list(TokenKind::BracketOpen, ListExtras::None);
+
+        let (typ, name) = &identifiers[0];
+        assert_eq!(typ.clone(), AzulaType::Int);
+        assert_eq!(name.clone(), "x");
+
+        let (typ, name) = &identifiers[1];
+        assert_eq!(typ.clone(), AzulaType::Int);
+        assert_eq!(name.clone(), "y");
+
+        let (typ, name) = &identifiers[2];
+        assert_eq!(typ.clone(), AzulaType::Bool);
+        assert_eq!(name.clone(), "z");
+    }
+
+    #[test]
+    fn test_parse_expression_list() {
+        let input = "(5, \"test\", x, true)";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let identifiers = parser.parse_expression_list(TokenKind::BracketOpen);
+
+        println!("{:?}", parser.errors);
+
+        let num = &identifiers[0];
+        assert_eq!(num.typed.clone(), AzulaType::Int);
+        matches!(num.expression, Expression::Integer(5));
+
+        let num = &identifiers[1];
+        assert_eq!(
+            num.typed.clone(),
+            AzulaType::Pointer(Rc::new(AzulaType::Str))
+        );
+        assert_eq!(num.expression, Expression::String("test".to_string()));
+
+        let num = &identifiers[2];
+        assert_eq!(num.typed.clone(), AzulaType::Infer);
+        assert_eq!(num.expression, Expression::Identifier("x".to_string()));
+
+        let num = &identifiers[3];
+        assert_eq!(num.typed.clone(), AzulaType::Bool);
+        matches!(num.expression, Expression::Boolean(true));
+    }
+
+    #[test]
+    fn test_parse_integer_expression() {
+        let input = "123";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let expression = parser.parse_expression(LOWEST, true).unwrap();
+        assert!(matches!(expression.expression, Expression::Integer(123)));
+    }
+
+    #[test]
+    fn test_parse_boolean_expression() {
+        let input = "true";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let expression = parser.parse_expression(LOWEST, true).unwrap();
+        assert!(matches!(expression.expression, Expression::Boolean(true)));
+
+        let input = "false";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let func = parser.parse_function();
-        println!("{:?}", parser.errors);
+        let expression = parser.parse_expression(LOWEST, true).unwrap();
+        assert!(matches!(expression.expression, Expression::Boolean(false)));
+    }
 
-        let func = func.unwrap();
-        assert!(matches!(func, Statement::Function { .. }));
-        if let Statement::Function {
-            name,
-            args,
-            returns,
-            body,
-            ..
-        } = func
-        {
-            assert_eq!(name, "main");
-            assert_eq!(args, vec![]);
-            assert_eq!(returns, AzulaType::Void);
-            assert_eq!(body, Rc::new(Statement::Block(vec![])));
-        }
+    #[test]
+    fn test_parse_infix_expression() {
+        // Addition
+        let input = "123 + 10";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
 
-        // Return type
-        let input = "func test: bool { }";
+        let expression = parser.parse_expression(LOWEST, true).unwrap().expression;
+        assert_eq!(
+            expression,
+            Expression::Infix(
+                Rc::new(ExpressionNode {
+                    expression: Expression::Integer(123),
+                    typed: AzulaType::Int,
+                    span: Span { start: 0, end: 3 },
+                }),
+                Operator::Add,
+                Rc::new(ExpressionNode {
+                    expression: Expression::Integer(10),
+                    typed: AzulaType::Int,
+                    span: Span { start: 6, end: 8 },
+                })
+            )
+        );
+
+        // Precedence
+        let input = "123 * 3 + 10 * 5";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let func = parser.parse_function().unwrap();
-        assert!(matches!(func, Statement::Function { .. }));
-        if let Statement::Function {
-            name,
-            args,
-            returns,
-            body,
-            ..
-        } = func
-        {
-            assert_eq!(name, "test");
-            assert_eq!(args, vec![]);
-            assert_eq!(returns, AzulaType::Bool);
-            assert_eq!(body, Rc::new(Statement::Block(vec![])));
-        }
+        let expression = parser.parse_expression(LOWEST, true).unwrap().expression;
+        assert_eq!(
+            expression,
+            Expression::Infix(
+                Rc::new(ExpressionNode {
+                    expression: Expression::Infix(
+                        Rc::new(ExpressionNode {
+                            expression: Expression::Integer(123),
+                            typed: AzulaType::Int,
+                            span: Span { start: 0, end: 3 },
+                        }),
+                        Operator::Mul,
+                        Rc::new(ExpressionNode {
+                            expression: Expression::Integer(3),
+                            typed: AzulaType::Int,
+                            span: Span { start: 6, end: 7 },
+                        })
+                    ),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 0, end: 7 },
+                }),
+                Operator::Add,
+                Rc::new(ExpressionNode {
+                    expression: Expression::Infix(
+                        Rc::new(ExpressionNode {
+                            expression: Expression::Integer(10),
+                            typed: AzulaType::Int,
+                            span: Span { start: 10, end: 12 },
+                        }),
+                        Operator::Mul,
+                        Rc::new(ExpressionNode {
+                            expression: Expression::Integer(5),
+                            typed: AzulaType::Int,
+                            span: Span { start: 15, end: 16 },
+                        })
+                    ),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 10, end: 16 },
+                })
+            )
+        );
 
-        // Args & return type
-        let input = "func test(x: int, y: bool): bool { }";
+        // Brackets
+        let input = "(123 + 3) * 10";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let func = parser.parse_function().unwrap();
-        assert!(matches!(func, Statement::Function { .. }));
-        if let Statement::Function {
-            name,
-            args,
-            returns,
-            body,
-            ..
-        } = func
-        {
-            assert_eq!(name, "test");
-            assert_eq!(args, vec![(AzulaType::Int, "x"), (AzulaType::Bool, "y")]);
-            assert_eq!(returns, AzulaType::Bool);
-            assert_eq!(body, Rc::new(Statement::Block(vec![])));
-        }
-    }
+        let expression = parser.parse_expression(LOWEST, true).unwrap().expression;
+        assert_eq!(
+            expression,
+            Expression::Infix(
+                Rc::new(ExpressionNode {
+                    expression: Expression::Infix(
+                        Rc::new(ExpressionNode {
+                            expression: Expression::Integer(123),
+                            typed: AzulaType::Int,
+                            span: Span { start: 1, end: 4 },
+                        }),
+                        Operator::Add,
+                        Rc::new(ExpressionNode {
+                            expression: Expression::Integer(3),
+                            typed: AzulaType::Int,
+                            span: Span { start: 7, end: 8 },
+                        })
+                    ),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 1, end: 8 },
+                }),
+                Operator::Mul,
+                Rc::new(ExpressionNode {
+                    expression: Expression::Integer(10),
+                    typed: AzulaType::Int,
+                    span: Span { start: 12, end: 14 },
+                })
+            )
+        );
 
-    #[test]
-    fn test_parse_extern_function() {
-        let input = "extern varargs func test(int): bool;";
+        // Comparison
+        let input = "x || y";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let func = parser.parse_statement().unwrap();
-        println!("{:?}", func);
-        assert!(matches!(func, Statement::ExternFunction { .. }));
-        if let Statement::ExternFunction {
-            name,
-            varargs,
-            args,
-            returns,
-            ..
-        } = func
-        {
-            assert_eq!(name, "test");
-            assert_eq!(varargs, true);
-            assert_eq!(args, vec![AzulaType::Int]);
-            assert_eq!(returns, AzulaType::Bool);
-        }
+        let expression = parser.parse_expression(LOWEST, true).unwrap().expression;
+        assert_eq!(
+            expression,
+            Expression::Infix(
+                Rc::new(ExpressionNode {
+                    expression: Expression::Identifier("x".to_string()),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 0, end: 1 },
+                }),
+                Operator::Or,
+                Rc::new(ExpressionNode {
+                    expression: Expression::Identifier("y".to_string()),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 5, end: 6 },
+                })
+            )
+        );
     }
 
     #[test]
-    fn test_parse_assign() {
-        let input = "var test = 5;";
+    fn test_parse_array() {
+        let input = "[1, 2, 3]";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let func = parser.parse_statement().unwrap();
-        assert!(matches!(func, Statement::Assign(..)));
+        let expression = parser.parse_expression(LOWEST, true).unwrap();
+        assert_eq!(
+            expression.expression,
+            Expression::Array(vec![
+                ExpressionNode {
+                    expression: Expression::Integer(1),
+                    typed: AzulaType::Int,
+                    span: Span { start: 1, end: 2 }
+                },
+                ExpressionNode {
+                    expression: Expression::Integer(2),
+                    typed: AzulaType::Int,
+                    span: Span { start: 4, end: 5 }
+                },
+                ExpressionNode {
+                    expression: Expression::Integer(3),
+                    typed: AzulaType::Int,
+                    span: Span { start: 7, end: 8 }
+                }
+            ])
+        );
 
-        let input = "const test = 5;";
+        // Empty array
+        let input = "[]";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let func = parser.parse_statement().unwrap();
-        assert!(matches!(func, Statement::Assign(..)));
+        let expression = parser.parse_expression(LOWEST, true).unwrap();
+        assert_eq!(expression.expression, Expression::Array(vec![]));
 
-        // Type annotation
-        let input = "var test: int = 5;";
+        // Array initialiser
+        let input = "[0; 5]";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let assign = parser.parse_assign(true).unwrap();
-        assert!(matches!(func, Statement::Assign(..)));
-        if let Statement::Assign(mutable, name, type_annotation, value, ..) = assign {
-            assert_eq!(mutable, true);
-            assert_eq!(name, "test".to_string());
-            assert_eq!(type_annotation, Some(AzulaType::Int));
-            assert_eq!(
-                value,
+        let expression = parser.parse_expression(LOWEST, true).unwrap();
+        assert_eq!(
+            expression.expression,
+            Expression::Array(vec![
+                ExpressionNode {
+                    expression: Expression::Integer(0),
+                    typed: AzulaType::Int,
+                    span: Span { start: 1, end: 2 }
+                },
+                ExpressionNode {
+                    expression: Expression::Integer(0),
+                    typed: AzulaType::Int,
+                    span: Span { start: 1, end: 2 }
+                },
                 ExpressionNode {
-                    expression: Expression::Integer(5),
+                    expression: Expression::Integer(0),
                     typed: AzulaType::Int,
-                    span: Span { start: 16, end: 17 }
+                    span: Span { start: 1, end: 2 }
+                },
+                ExpressionNode {
+                    expression: Expression::Integer(0),
+                    typed: AzulaType::Int,
+                    span: Span { start: 1, end: 2 }
+                },
+                ExpressionNode {
+                    expression: Expression::Integer(0),
+                    typed: AzulaType::Int,
+                    span: Span { start: 1, end: 2 }
                 }
-            )
-        }
+            ])
+        );
     }
 
     #[test]
-    fn test_parse_struct() {
-        // No attributes
-        let input = "struct Test { }";
+    fn test_parse_function_call() {
+        let input = "test()";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let struc = parser.parse_statement().unwrap();
-        assert!(matches!(struc, Statement::Struct { .. }));
+        let expression = parser.parse_expression(LOWEST, true).unwrap();
+        assert_eq!(
+            expression.expression,
+            Expression::FunctionCall {
+                function: Rc::new(ExpressionNode {
+                    expression: Expression::Identifier("test".to_string()),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 0, end: 4 },
+                }),
+                args: vec![],
+            }
+        );
 
-        // Single attribute
-        let input = "struct Test { test: int }";
+        let input = "test(5, \"test\")";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let struc = parser.parse_statement().unwrap();
-        assert!(matches!(struc, Statement::Struct { .. }));
-        if let Statement::Struct {
-            name,
-            attributes,
-            span,
-        } = struc
-        {
-            assert_eq!(name, "Test");
-            assert_eq!(attributes, vec![(AzulaType::Int, "test")]);
-        }
+        let expression = parser.parse_expression(LOWEST, true).unwrap();
+        println!("{:?}", parser.errors);
+        assert!(parser.errors.is_empty());
+        assert_eq!(
+            expression.expression,
+            Expression::FunctionCall {
+                function: Rc::new(ExpressionNode {
+                    expression: Expression::Identifier("test".to_string()),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 0, end: 4 },
+                }),
+                args: vec![
+                    ExpressionNode {
+                        expression: Expression::Integer(5),
+                        typed: AzulaType::Int,
+                        span: Span { start: 5, end: 6 }
+                    },
+                    ExpressionNode {
+                        expression: Expression::String("test".to_string()),
+                        typed: AzulaType::Pointer(Rc::new(AzulaType::Str)),
+                        span: Span { start: 8, end: 14 }
+                    }
+                ],
+            }
+        );
+    }
 
-        // Multiple attribute
-        let input = "struct Test { test: int, test1: &str, }";
-        let lexer: Lexer = input.into();
-        let mut parser = Parser::new(input, lexer);
+    #[test]
+    fn test_string_transform() {
+        let test = "\\\\ \\n test \\t";
+        let result = string_transform(test).unwrap();
+        assert_eq!(result, "\\ \n test \t");
 
-        let struc = parser.parse_statement().unwrap();
-        assert!(matches!(struc, Statement::Struct { .. }));
-        if let Statement::Struct {
-            name,
-            attributes,
-            span,
-        } = struc
-        {
-            assert_eq!(name, "Test");
-            assert_eq!(
-                attributes,
-                vec![
-                    (AzulaType::Int, "test"),
-                    (AzulaType::Pointer(Rc::new(AzulaType::Str)), "test1")
-                ]
-            );
-        }
+        let test = "\\d";
+        let result = string_transform(test);
+        assert!(result.is_err());
     }
 
     #[test]
-    fn test_parse_if() {
-        let input = "if x { return 5; }";
+    fn test_not() {
+        let input = "!true";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let func = parser.parse_if().unwrap();
-        assert!(matches!(func, Statement::If(..)));
-        if let Statement::If(expr, body, ..) = func {
-            assert_eq!(expr.expression, Expression::Identifier("x".to_string()));
-            assert_eq!(
-                body[0],
-                Statement::Return(
-                    Some(ExpressionNode {
-                        expression: Expression::Integer(5),
-                        typed: AzulaType::Int,
-                        span: Span { start: 14, end: 15 }
-                    }),
-                    Span { start: 7, end: 16 }
-                )
-            );
-        }
+        let expression = parser.parse_expression(LOWEST, true).unwrap();
+        assert!(parser.errors.is_empty());
+        assert_eq!(
+            expression.expression,
+            Expression::Not(Rc::new(ExpressionNode {
+                expression: Expression::Boolean(true),
+                typed: AzulaType::Bool,
+                span: Span { start: 1, end: 5 }
+            }))
+        );
+    }
 
-        let input = "if x || y { return 5; }";
+    #[test]
+    fn test_pointer() {
+        let input = "&my_var";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let func = parser.parse_if();
-        println!("{:?}", parser.errors);
-        let func = func.unwrap();
-        assert!(matches!(func, Statement::If(..)));
-        if let Statement::If(expr, body, ..) = func {
-            assert_eq!(
-                expr.expression,
-                Expression::Infix(
-                    Rc::new(ExpressionNode {
-                        expression: Expression::Identifier("x".to_string()),
-                        typed: AzulaType::Infer,
-                        span: Span { start: 3, end: 4 },
-                    }),
-                    Operator::Or,
-                    Rc::new(ExpressionNode {
-                        expression: Expression::Identifier("y".to_string()),
-                        typed: AzulaType::Infer,
-                        span: Span { start: 8, end: 9 },
-                    })
-                )
-            );
-            assert_eq!(
-                body[0],
-                Statement::Return(
-                    Some(ExpressionNode {
-                        expression: Expression::Integer(5),
-                        typed: AzulaType::Int,
-                        span: Span { start: 19, end: 20 }
-                    }),
-                    Span { start: 12, end: 21 }
-                )
-            );
-        }
+        let expression = parser.parse_expression(LOWEST, true).unwrap();
+        assert!(parser.errors.is_empty());
+        assert_eq!(
+            expression.expression,
+            Expression::Pointer(Rc::new(ExpressionNode {
+                expression: Expression::Identifier("my_var".to_string()),
+                typed: AzulaType::Infer,
+                span: Span { start: 1, end: 7 }
+            }))
+        );
     }
 
     #[test]
-    fn test_parse_type() {
-        // Basic type
-        let input = "int";
+    fn test_negate() {
+        // A prefix minus binds tighter than any infix operator
+        let input = "-x * 2";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let typ = parser.parse_type();
-        assert_eq!(typ, AzulaType::Int);
+        let expression = parser.parse_expression(LOWEST, true).unwrap();
+        assert!(parser.errors.is_empty());
+        assert_eq!(
+            expression.expression,
+            Expression::Infix(
+                Rc::new(ExpressionNode {
+                    expression: Expression::Negate(Rc::new(ExpressionNode {
+                        expression: Expression::Identifier("x".to_string()),
+                        typed: AzulaType::Infer,
+                        span: Span { start: 1, end: 2 }
+                    })),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 0, end: 2 }
+                }),
+                Operator::Mul,
+                Rc::new(ExpressionNode {
+                    expression: Expression::Integer(2),
+                    typed: AzulaType::Int,
+                    span: Span { start: 5, end: 6 }
+                })
+            )
+        );
 
-        // Pointer
-        let input = "&str";
+        let input = "7 - -2";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
+        let expression = parser.parse_expression(LOWEST, true).unwrap();
+        assert!(parser.errors.is_empty());
+        match expression.expression {
+            Expression::Infix(_, Operator::Sub, right) => {
+                assert!(matches!(right.expression, Expression::Negate(_)))
+            }
+            expression => panic!("expected a subtraction, got {:?}", expression),
+        }
+    }
 
-        let typ = parser.parse_type();
-        assert_eq!(typ, AzulaType::Pointer(Rc::new(AzulaType::Str)));
-
-        // Nested Pointers
-        let input = "&&&str";
+    #[test]
+    fn test_float() {
+        let input = "5.5";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let typ = parser.parse_type();
-        assert_eq!(
-            typ,
-            AzulaType::Pointer(Rc::new(AzulaType::Pointer(Rc::new(AzulaType::Pointer(
-                Rc::new(AzulaType::Str)
-            )))))
-        );
+        let expression = parser.parse_expression(LOWEST, true).unwrap();
+        assert!(parser.errors.is_empty());
+        assert_eq!(expression.expression, Expression::Float(5.5));
 
-        // Array
-        let input = "[int]";
+        let input = "3.14523";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let typ = parser.parse_type();
-        assert_eq!(typ, AzulaType::Array(Rc::new(AzulaType::Int), None));
+        let expression = parser.parse_expression(LOWEST, true).unwrap();
+        assert!(parser.errors.is_empty());
+        assert_eq!(expression.expression, Expression::Float(3.14523));
 
-        // Nested Array
-        let input = "[[int]]";
+        let input = "5.2 * 15.2";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let typ = parser.parse_type();
+        let expression = parser.parse_expression(LOWEST, true).unwrap();
+        assert!(parser.errors.is_empty());
         assert_eq!(
-            typ,
-            AzulaType::Array(
-                Rc::new(AzulaType::Array(Rc::new(AzulaType::Int), None)),
-                None
+            expression.expression,
+            Expression::Infix(
+                Rc::new(ExpressionNode {
+                    expression: Expression::Float(5.2),
+                    typed: AzulaType::Float,
+                    span: Span { start: 0, end: 3 }
+                }),
+                Operator::Mul,
+                Rc::new(ExpressionNode {
+                    expression: Expression::Float(15.2),
+                    typed: AzulaType::Float,
+                    span: Span { start: 6, end: 10 }
+                })
             )
         );
+    }
 
-        // Sized Array
-        let input = "[int; 20]";
+    #[test]
+    fn test_shift() {
+        // Shifts bind looser than arithmetic but tighter than comparisons
+        let input = "1 << 2 + 3 < 4 >> 1";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let typ = parser.parse_type();
-        assert_eq!(typ, AzulaType::Array(Rc::new(AzulaType::Int), Some(20)));
+        let expression = parser.parse_expression(LOWEST, true).unwrap();
+        assert!(parser.errors.is_empty());
+        match expression.expression {
+            Expression::Infix(left, Operator::Lt, right) => {
+                match &left.expression {
+                    Expression::Infix(_, Operator::Shl, shifted) => assert!(matches!(
+                        shifted.expression,
+                        Expression::Infix(_, Operator::Add, _)
+                    )),
+                    _ => panic!("expected shift left, got {:?}", left),
+                }
+                assert!(matches!(
+                    right.expression,
+                    Expression::Infix(_, Operator::Shr, _)
+                ));
+            }
+            _ => panic!("expected comparison, got {:?}", expression),
+        }
     }
 
     #[test]
-    fn test_parse_reassign() {
-        // Basic int
-        let input = "x = 5;";
+    fn test_parse_match() {
+        let input = "match x { int i => { return i; }, &str s => {} _ => {} }";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
         let stmt = parser.parse_statement().unwrap();
+        assert!(parser.errors.is_empty());
+        if let Statement::Match(expr, arms, span) = stmt {
+            assert_eq!(expr.expression, Expression::Identifier("x".to_string()));
+            assert_eq!(arms.len(), 3);
+            assert_eq!(arms[0].0, Some(AzulaType::Int));
+            assert_eq!(arms[0].1, Some("i"));
+            assert_eq!(arms[0].2.len(), 1);
+            assert_eq!(arms[1].0, Some(AzulaType::Pointer(Rc::new(AzulaType::Str))));
+            assert_eq!(arms[1].1, Some("s"));
+            assert_eq!(arms[2], (None, None, vec![], Span { start: 47, end: 48 }));
+            assert_eq!(arms[1].3, Span { start: 34, end: 40 });
+            assert_eq!(span, Span { start: 0, end: 56 });
+        } else {
+            panic!("expected match statement");
+        }
+    }
+
+    #[test]
+    fn test_parse_ignore_directive() {
+        let input = "func main() {\n// azula:ignore unused_variable\nvar x = 5;\nvar y = 6;\n}";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+        parser.parse();
+
         assert_eq!(
-            stmt,
-            Statement::Reassign(
-                ExpressionNode {
-                    expression: Expression::Identifier("x".to_string()),
-                    typed: AzulaType::Infer,
-                    span: Span { start: 0, end: 1 }
-                },
-                ExpressionNode {
-                    expression: Expression::Integer(5),
-                    typed: AzulaType::Int,
-                    span: Span { start: 4, end: 5 }
-                },
-                Span { start: 0, end: 6 },
-            )
+            parser.ignores,
+            vec![IgnoreDirective {
+                lints: vec![Lint::UnusedVariable],
+                start: 46,
+                end: 56,
+            }]
         );
     }
 
     #[test]
-    fn test_parse_while() {
-        // Basic int
-        let input = "while x { i = 1; }";
+    fn test_parse_comments_in_body() {
+        let input = "func main() {\n// line\nvar x = /* block */ 5;\n/* multi\nline */\nx;\n}";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let stmt = parser.parse_statement().unwrap();
-        assert_eq!(
-            stmt,
-            Statement::While(
-                ExpressionNode {
-                    expression: Expression::Identifier("x".to_string()),
-                    typed: AzulaType::Infer,
-                    span: Span { start: 6, end: 7 }
+        match parser.parse() {
+            Statement::Root(body) => match &body[0] {
+                Statement::Function { body, .. } => match body.as_ref() {
+                    Statement::Block(stmts) => assert_eq!(stmts.len(), 2),
+                    stmt => panic!("expected block, got {:?}", stmt),
                 },
-                vec![Statement::Reassign(
-                    ExpressionNode {
-                        expression: Expression::Identifier("i".to_string()),
-                        typed: AzulaType::Infer,
-                        span: Span { start: 10, end: 11 },
-                    },
-                    ExpressionNode {
-                        expression: Expression::Integer(1),
-                        typed: AzulaType::Int,
-                        span: Span { start: 14, end: 15 },
-                    },
-                    Span { start: 10, end: 16 }
-                )],
-                Span { start: 0, end: 18 },
-            )
-        );
+                stmt => panic!("expected function, got {:?}", stmt),
+            },
+            _ => unreachable!(),
+        }
+        assert!(parser.errors.is_empty());
     }
 
     #[test]
-    fn test_parse_array_access() {
-        // Basic int
-        let input = "x[10]";
+    fn test_parse_cast() {
+        // Casts bind tighter than arithmetic but looser than prefix operators
+        let input = "x * y as i32 + 1";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let expr = parser.parse_expression(LOWEST, true).unwrap();
-        assert_eq!(
-            expr,
-            ExpressionNode {
-                expression: Expression::ArrayAccess(
-                    Rc::new(ExpressionNode {
-                        expression: Expression::Identifier("x".to_string()),
-                        typed: AzulaType::Infer,
-                        span: Span { start: 0, end: 1 }
-                    }),
-                    Rc::new(ExpressionNode {
-                        expression: Expression::Integer(10),
-                        typed: AzulaType::Int,
-                        span: Span { start: 2, end: 4 }
-                    })
-                ),
-                typed: AzulaType::Infer,
-                span: Span { start: 0, end: 5 }
-            }
-        );
+        let expression = parser.parse_expression(LOWEST, true).unwrap();
+        assert!(parser.errors.is_empty());
+        match expression.expression {
+            Expression::Infix(left, Operator::Add, _) => match &left.expression {
+                Expression::Infix(_, Operator::Mul, cast) => {
+                    assert_eq!(
+                        cast.expression,
+                        Expression::Cast(
+                            Rc::new(ExpressionNode {
+                                expression: Expression::Identifier("y".to_string()),
+                                typed: AzulaType::Infer,
+                                span: Span { start: 4, end: 5 },
+                            }),
+                            AzulaType::SizedSignedInt(32)
+                        )
+                    );
+                    assert_eq!(cast.span, Span { start: 4, end: 12 });
+                }
+                _ => panic!("expected multiplication, got {:?}", left),
+            },
+            _ => panic!("expected addition, got {:?}", expression),
+        }
     }
 
     #[test]
-    fn test_parse_struct_access() {
-        // Basic int
-        let input = "test.test";
+    fn test_parse_deref() {
+        let input = "func set(p: *int, pp: **int) { *p = **pp * 2; }";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
+        let body = match parser.parse() {
+            Statement::Root(body) => body,
+            _ => unreachable!(),
+        };
+        assert!(parser.errors.is_empty());
 
-        let expr = parser.parse_expression(LOWEST, true).unwrap();
+        let (args, body) = match &body[0] {
+            Statement::Function { args, body, .. } => (args, body),
+            stmt => panic!("expected function, got {:?}", stmt),
+        };
+        let int = || Rc::new(AzulaType::Int);
+        assert_eq!(args[0].0, AzulaType::Pointer(int()));
         assert_eq!(
-            expr,
-            ExpressionNode {
-                expression: Expression::StructAccess(
-                    Rc::new(ExpressionNode {
-                        expression: Expression::Identifier("test".to_string()),
-                        typed: AzulaType::Infer,
-                        span: Span { start: 0, end: 4 }
-                    }),
-                    Rc::new(ExpressionNode {
-                        expression: Expression::Identifier("test".to_string()),
-                        typed: AzulaType::Infer,
-                        span: Span { start: 5, end: 9 }
-                    })
-                ),
-                typed: AzulaType::Infer,
-                span: Span { start: 0, end: 9 }
-            }
+            args[1].0,
+            AzulaType::Pointer(Rc::new(AzulaType::Pointer(int())))
         );
+        match body.as_ref() {
+            Statement::Block(stmts) => match &stmts[0] {
+                Statement::Reassign(target, value, _) => {
+                    assert!(matches!(&target.expression, Expression::Deref(_)));
+                    match &value.expression {
+                        Expression::Infix(left, Operator::Mul, _) => match &left.expression {
+                            Expression::Deref(inner) => {
+                                assert!(matches!(&inner.expression, Expression::Deref(_)))
+                            }
+                            expr => panic!("expected deref, got {:?}", expr),
+                        },
+                        expr => panic!("expected multiplication, got {:?}", expr),
+                    }
+                }
+                stmt => panic!("expected reassign, got {:?}", stmt),
+            },
+            stmt => panic!("expected block, got {:?}", stmt),
+        }
     }
 
     #[test]
-    fn test_parse_struct_init() {
-        // Basic struct init
-        let input = "Test { test: 5 }";
+    fn test_parse_new() {
+        let input = "new [int; 4]";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let expr = parser.parse_expression(LOWEST, true).unwrap();
+        let expression = parser.parse_expression(LOWEST, true).unwrap();
+        assert!(parser.errors.is_empty());
         assert_eq!(
-            expr,
-            ExpressionNode {
-                expression: Expression::StructInitialisation(
-                    Rc::new(ExpressionNode {
-                        expression: Expression::Identifier("Test".to_string()),
-                        typed: AzulaType::Infer,
-                        span: Span { start: 0, end: 4 }
-                    }),
-                    vec![(
-                        "test",
-                        ExpressionNode {
-                            expression: Expression::Integer(5),
-                            typed: AzulaType::Int,
-                            span: Span { start: 13, end: 14 },
-                        }
-                    )]
-                ),
-                typed: AzulaType::Infer,
-                span: Span { start: 0, end: 16 }
-            }
+            expression.expression,
+            Expression::New(AzulaType::Array(Rc::new(AzulaType::Int), Some(4)))
         );
+        assert_eq!(expression.span, Span { start: 0, end: 12 });
 
-        // Multiple args
-        let input = "Test { test: 5, test1: true }";
+        // Without a type after it, new is just a name
+        let input = "new + 1";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
+        let expression = parser.parse_expression(LOWEST, true).unwrap();
+        assert!(matches!(
+            expression.expression,
+            Expression::Infix(left, Operator::Add, _)
+                if left.expression == Expression::Identifier("new".to_string())
+        ));
 
-        let expr = parser.parse_expression(LOWEST, true).unwrap();
-        assert_eq!(
-            expr,
-            ExpressionNode {
-                expression: Expression::StructInitialisation(
-                    Rc::new(ExpressionNode {
-                        expression: Expression::Identifier("Test".to_string()),
-                        typed: AzulaType::Infer,
-                        span: Span { start: 0, end: 4 }
-                    }),
-                    vec![
-                        (
-                            "test",
-                            ExpressionNode {
-                                expression: Expression::Integer(5),
-                                typed: AzulaType::Int,
-                                span: Span { start: 13, end: 14 },
-                            }
-                        ),
-                        (
-                            "test1",
-                            ExpressionNode {
-                                expression: Expression::Boolean(true),
-                                typed: AzulaType::Bool,
-                                span: Span { start: 23, end: 27 },
-                            }
-                        )
-                    ]
-                ),
-                typed: AzulaType::Infer,
-                span: Span { start: 0, end: 29 }
-            }
-        );
+        let input = "new Point { x: 1 }";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+        assert!(parser.parse_expression(LOWEST, true).is_none());
+        assert!(matches!(
+            parser.errors[..],
+            [AzulaError {
+                error_type: ErrorType::NewWithLiteral,
+                start: 0,
+                end: 11,
+            }]
+        ));
+
+        // An if's body can follow it
+        let input = "new Point {";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+        let expression = parser.parse_expression(LOWEST, false).unwrap();
+        assert!(parser.errors.is_empty());
+        assert!(matches!(expression.expression, Expression::New(_)));
     }
 
     #[test]
-    fn test_parse_typed_identifier() {
-        // Basic int
-        let input = "x: int";
+    fn test_parse_sizeof() {
+        let input = "sizeof(&Point) * 2";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let (typ, name) = parser.parse_typed_identifier().unwrap();
-        assert_eq!(typ, AzulaType::Int);
-        assert_eq!(name, "x");
+        let expression = parser.parse_expression(LOWEST, true).unwrap();
+        assert!(parser.errors.is_empty());
+        match expression.expression {
+            Expression::Infix(size, Operator::Mul, _) => {
+                assert_eq!(
+                    size.expression,
+                    Expression::SizeOf(AzulaType::Pointer(Rc::new(AzulaType::Named(
+                        "Point".to_string()
+                    ))))
+                );
+                assert_eq!(size.span, Span { start: 0, end: 14 });
+            }
+            _ => panic!("expected multiplication, got {:?}", expression),
+        }
+    }
 
-        // Custom struct type
-        let input = "my_typed_identifier: CustomStruct";
+    #[test]
+    fn test_parse_ternary() {
+        let input = "a || b ? 1 : c ? 2 : 3";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let (typ, name) = parser.parse_typed_identifier().unwrap();
-        assert_eq!(typ, AzulaType::Named("CustomStruct".to_string()));
-        assert_eq!(name, "my_typed_identifier");
+        let expression = parser.parse_expression(LOWEST, true).unwrap();
+        assert!(parser.errors.is_empty());
+        assert_eq!(expression.span, Span { start: 0, end: 22 });
+        match expression.expression {
+            Expression::Ternary(cond, then, otherwise) => {
+                assert!(matches!(
+                    cond.expression,
+                    Expression::Infix(_, Operator::Or, _)
+                ));
+                assert_eq!(then.expression, Expression::Integer(1));
+                assert!(matches!(otherwise.expression, Expression::Ternary(..)));
+            }
+            _ => panic!("expected ternary, got {:?}", expression),
+        }
+    }
 
-        // Non identifier for name
-        let input = "4: CustomStruct";
+    #[test]
+    fn test_parse_tuples() {
+        let input = "func divmod(a: int, b: int): (int, int) {\nreturn (a / b, a % b);\n}\nfunc main() {\nvar (q, r) = divmod(7, 2);\n}";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        parser.parse_typed_identifier();
-        assert_eq!(parser.errors.len(), 1);
-        assert!(matches!(
-            parser.errors[0].error_type,
-            ErrorType::ExpectedToken(..)
-        ));
-        if let ErrorType::ExpectedToken(expected, ..) = &parser.errors[0].error_type {
-            assert_eq!(expected.clone(), "Identifier(\"\")".to_string());
+        let body = match parser.parse() {
+            Statement::Root(body) => body,
+            _ => unreachable!(),
+        };
+        assert!(parser.errors.is_empty());
+
+        match &body[0] {
+            Statement::Function { returns, .. } => assert_eq!(
+                returns,
+                &AzulaType::Tuple(vec![AzulaType::Int, AzulaType::Int])
+            ),
+            stmt => panic!("expected function, got {:?}", stmt),
         }
 
-        // No colon - EOF
-        let input = "x";
+        match &body[1] {
+            Statement::Function { body, .. } => match body.as_ref() {
+                Statement::Block(stmts) => match &stmts[0] {
+                    Statement::Destructure(true, names, value, span) => {
+                        assert_eq!(names, &vec!["q", "r"]);
+                        assert!(matches!(value.expression, Expression::FunctionCall { .. }));
+                        assert_eq!(
+                            span,
+                            &Span {
+                                start: 81,
+                                end: 107
+                            }
+                        );
+                    }
+                    stmt => panic!("expected destructure, got {:?}", stmt),
+                },
+                stmt => panic!("expected block, got {:?}", stmt),
+            },
+            stmt => panic!("expected function, got {:?}", stmt),
+        }
+    }
+
+    #[test]
+    fn test_parse_optional() {
+        let input = "func main() {\nvar x: ?int = nil;\nif var y = x {\n}\n}";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        parser.parse_typed_identifier();
-        assert_eq!(parser.errors.len(), 1);
-        assert!(matches!(
-            parser.errors[0].error_type,
-            ErrorType::UnexpectedEOF,
-        ));
+        let body = match parser.parse() {
+            Statement::Root(body) => body,
+            _ => unreachable!(),
+        };
+        assert!(parser.errors.is_empty());
 
-        // Pointer
-        let input = "x: &str";
+        match &body[0] {
+            Statement::Function { body, .. } => match body.as_ref() {
+                Statement::Block(stmts) => {
+                    match &stmts[0] {
+                        Statement::Assign(true, name, annotation, ..) => {
+                            assert_eq!(name, "x");
+                            assert_eq!(
+                                annotation,
+                                &Some(AzulaType::Optional(Rc::new(AzulaType::Int)))
+                            );
+                        }
+                        stmt => panic!("expected assign, got {:?}", stmt),
+                    }
+                    match &stmts[1] {
+                        Statement::IfLet(true, name, value, body, _) => {
+                            assert_eq!(*name, "y");
+                            assert_eq!(value.expression, Expression::Identifier("x".to_string()));
+                            assert!(body.is_empty());
+                        }
+                        stmt => panic!("expected if var, got {:?}", stmt),
+                    }
+                }
+                stmt => panic!("expected block, got {:?}", stmt),
+            },
+            stmt => panic!("expected function, got {:?}", stmt),
+        }
+    }
+
+    #[test]
+    fn test_parse_try() {
+        let input = "func main(): !int {\nvar x = a ? b : c;\nreturn parse(x)?;\n}";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let (typ, name) = parser.parse_typed_identifier().unwrap();
-        assert_eq!(parser.errors.len(), 0);
-        assert_eq!(typ, AzulaType::Pointer(Rc::new(AzulaType::Str)));
-        assert_eq!(name, "x");
+        let body = match parser.parse() {
+            Statement::Root(body) => body,
+            _ => unreachable!(),
+        };
+        assert!(parser.errors.is_empty());
+
+        match &body[0] {
+            Statement::Function { returns, body, .. } => {
+                assert_eq!(returns, &AzulaType::Result(Rc::new(AzulaType::Int)));
+                match body.as_ref() {
+                    Statement::Block(stmts) => {
+                        match &stmts[0] {
+                            Statement::Assign(_, _, _, value, _, _) => {
+                                assert!(matches!(value.expression, Expression::Ternary(..)))
+                            }
+                            stmt => panic!("expected assign, got {:?}", stmt),
+                        }
+                        match &stmts[1] {
+                            Statement::Return(Some(value), _) => {
+                                assert!(matches!(
+                                    &value.expression,
+                                    Expression::Try(inner, AzulaType::Infer)
+                                        if matches!(inner.expression, Expression::FunctionCall { .. })
+                                ));
+                                assert_eq!(value.span, Span { start: 46, end: 55 });
+                            }
+                            stmt => panic!("expected return, got {:?}", stmt),
+                        }
+                    }
+                    stmt => panic!("expected block, got {:?}", stmt),
+                }
+            }
+            stmt => panic!("expected function, got {:?}", stmt),
+        }
     }
 
     #[test]
-    fn test_parse_typed_identifier_list() {
-        let input = "(x: int, y: int, z: bool)";
+    fn test_parse_default_params() {
+        let input = "func greet(name: &str, greeting: &str = \"hello\", times: int = 1) {\n}";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let identifiers = parser.parse_typed_identifier_list(TokenKind::BracketOpen);
+        let body = match parser.parse() {
+            Statement::Root(body) => body,
+            _ => unreachable!(),
+        };
+        assert!(parser.errors.is_empty());
 
-        let (typ, name) = &identifiers[0];
-        assert_eq!(typ.clone(), AzulaType::Int);
-        assert_eq!(name.clone(), "x");
+        match &body[0] {
+            Statement::Function { args, defaults, .. } => {
+                assert_eq!(args.len(), 3);
+                assert_eq!(
+                    defaults
+                        .iter()
+                        .map(|default| default.expression.clone())
+                        .collect::<Vec<_>>(),
+                    vec![
+                        Expression::String("hello".to_string()),
+                        Expression::Integer(1)
+                    ]
+                );
+            }
+            stmt => panic!("expected function, got {:?}", stmt),
+        }
 
-        let (typ, name) = &identifiers[1];
-        assert_eq!(typ.clone(), AzulaType::Int);
-        assert_eq!(name.clone(), "y");
+        let input = "func greet(greeting: &str = \"hello\", name: &str) {\n}";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+        parser.parse();
 
-        let (typ, name) = &identifiers[2];
-        assert_eq!(typ.clone(), AzulaType::Bool);
-        assert_eq!(name.clone(), "z");
+        assert_eq!(parser.errors.len(), 1);
+        assert!(matches!(
+            &parser.errors[0].error_type,
+            ErrorType::MissingDefault(param) if param == "name: &str"
+        ));
     }
 
     #[test]
-    fn test_parse_expression_list() {
-        let input = "(5, \"test\", x, true)";
+    fn test_parse_variadic_params() {
+        let input = "func sum(first: int, rest: int...) {\n}";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let identifiers = parser.parse_expression_list(TokenKind::BracketOpen);
-
-        println!("{:?}", parser.errors);
-
-        let num = &identifiers[0];
-        assert_eq!(num.typed.clone(), AzulaType::Int);
-        matches!(num.expression, Expression::Integer(5));
+        let body = match parser.parse() {
+            Statement::Root(body) => body,
+            _ => unreachable!(),
+        };
+        assert!(parser.errors.is_empty());
 
-        let num = &identifiers[1];
-        assert_eq!(
-            num.typed.clone(),
-            AzulaType::Pointer(Rc::new(AzulaType::Str))
-        );
-        assert_eq!(num.expression, Expression::String("test".to_string()));
+        match &body[0] {
+            Statement::Function { args, variadic, .. } => {
+                assert!(variadic);
+                assert_eq!(
+                    args,
+                    &vec![
+                        (AzulaType::Int, "first"),
+                        (AzulaType::Slice(Rc::new(AzulaType::Int)), "rest")
+                    ]
+                );
+            }
+            stmt => panic!("expected function, got {:?}", stmt),
+        }
 
-        let num = &identifiers[2];
-        assert_eq!(num.typed.clone(), AzulaType::Infer);
-        assert_eq!(num.expression, Expression::Identifier("x".to_string()));
+        let input = "func sum(rest: int..., last: int) {\n}";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+        parser.parse();
 
-        let num = &identifiers[3];
-        assert_eq!(num.typed.clone(), AzulaType::Bool);
-        matches!(num.expression, Expression::Boolean(true));
+        assert_eq!(parser.errors.len(), 1);
+        assert!(matches!(
+            &parser.errors[0].error_type,
+            ErrorType::ParamAfterVariadic(param) if param == "last: int"
+        ));
     }
 
     #[test]
-    fn test_parse_integer_expression() {
-        let input = "123";
+    fn test_parse_named_arguments() {
+        let input = "make_rect(3, height: 4 + 1)";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let expression = parser.parse_expression(LOWEST, true).unwrap();
-        assert!(matches!(expression.expression, Expression::Integer(123)));
+        let expr = parser.parse_expression(LOWEST, true).unwrap();
+        assert!(parser.errors.is_empty());
+
+        match expr.expression {
+            Expression::FunctionCall { args, .. } => {
+                assert_eq!(args[0].expression, Expression::Integer(3));
+                assert_eq!(args[1].span, Span { start: 13, end: 26 });
+                match &args[1].expression {
+                    Expression::NamedArgument(name, value) => {
+                        assert_eq!(*name, "height");
+                        assert!(matches!(value.expression, Expression::Infix(..)));
+                    }
+                    expr => panic!("expected named argument, got {:?}", expr),
+                }
+            }
+            expr => panic!("expected call, got {:?}", expr),
+        }
     }
 
     #[test]
-    fn test_parse_boolean_expression() {
-        let input = "true";
+    fn test_parse_closure() {
+        let input = "|x: int, y: int| x * y + z";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let expression = parser.parse_expression(LOWEST, true).unwrap();
-        assert!(matches!(expression.expression, Expression::Boolean(true)));
+        let expr = parser.parse_expression(LOWEST, true).unwrap();
+        assert!(parser.errors.is_empty());
+        assert_eq!(expr.span, Span { start: 0, end: 26 });
+
+        match expr.expression {
+            Expression::Closure { args, body, .. } => {
+                assert_eq!(args, vec![(AzulaType::Int, "x"), (AzulaType::Int, "y")]);
+                assert!(matches!(
+                    body.expression,
+                    Expression::Infix(_, Operator::Add, _)
+                ));
+            }
+            expr => panic!("expected closure, got {:?}", expr),
+        }
 
-        let input = "false";
+        let input = "|| 1";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let expression = parser.parse_expression(LOWEST, true).unwrap();
-        assert!(matches!(expression.expression, Expression::Boolean(false)));
+        let expr = parser.parse_expression(LOWEST, true).unwrap();
+        assert!(parser.errors.is_empty());
+        match expr.expression {
+            Expression::Closure { args, body, .. } => {
+                assert!(args.is_empty());
+                assert_eq!(body.expression, Expression::Integer(1));
+            }
+            expr => panic!("expected closure, got {:?}", expr),
+        }
     }
 
     #[test]
-    fn test_parse_infix_expression() {
-        // Addition
-        let input = "123 + 10";
+    fn test_parse_function_type() {
+        let input = "fn(int, &str) -> fn() -> bool";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let expression = parser.parse_expression(LOWEST, true).unwrap().expression;
         assert_eq!(
-            expression,
-            Expression::Infix(
-                Rc::new(ExpressionNode {
-                    expression: Expression::Integer(123),
-                    typed: AzulaType::Int,
-                    span: Span { start: 0, end: 3 },
-                }),
-                Operator::Add,
-                Rc::new(ExpressionNode {
-                    expression: Expression::Integer(10),
-                    typed: AzulaType::Int,
-                    span: Span { start: 6, end: 8 },
-                })
+            parser.parse_type(),
+            AzulaType::Function(
+                vec![AzulaType::Int, AzulaType::Pointer(Rc::new(AzulaType::Str))],
+                Rc::new(AzulaType::Function(vec![], Rc::new(AzulaType::Bool)))
             )
         );
+        assert!(parser.errors.is_empty());
+    }
 
-        // Precedence
-        let input = "123 * 3 + 10 * 5";
+    #[test]
+    fn test_parse_generics() {
+        let input = "func max<T, U>(a: T, b: &U): T { return a; }";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let expression = parser.parse_expression(LOWEST, true).unwrap().expression;
-        assert_eq!(
-            expression,
-            Expression::Infix(
-                Rc::new(ExpressionNode {
-                    expression: Expression::Infix(
-                        Rc::new(ExpressionNode {
-                            expression: Expression::Integer(123),
-                            typed: AzulaType::Int,
-                            span: Span { start: 0, end: 3 },
-                        }),
-                        Operator::Mul,
-                        Rc::new(ExpressionNode {
-                            expression: Expression::Integer(3),
-                            typed: AzulaType::Int,
-                            span: Span { start: 6, end: 7 },
-                        })
-                    ),
-                    typed: AzulaType::Infer,
-                    span: Span { start: 0, end: 7 },
-                }),
-                Operator::Add,
-                Rc::new(ExpressionNode {
-                    expression: Expression::Infix(
-                        Rc::new(ExpressionNode {
-                            expression: Expression::Integer(10),
-                            typed: AzulaType::Int,
-                            span: Span { start: 10, end: 12 },
-                        }),
-                        Operator::Mul,
-                        Rc::new(ExpressionNode {
-                            expression: Expression::Integer(5),
-                            typed: AzulaType::Int,
-                            span: Span { start: 15, end: 16 },
-                        })
-                    ),
-                    typed: AzulaType::Infer,
-                    span: Span { start: 10, end: 16 },
-                })
-            )
-        );
+        let body = match parser.parse() {
+            Statement::Root(body) => body,
+            _ => unreachable!(),
+        };
+        assert!(parser.errors.is_empty());
 
-        // Brackets
-        let input = "(123 + 3) * 10";
+        match &body[0] {
+            Statement::Function {
+                generics,
+                args,
+                returns,
+                ..
+            } => {
+                assert_eq!(generics, &vec!["T", "U"]);
+                assert_eq!(
+                    args,
+                    &vec![
+                        (AzulaType::Named("T".to_string()), "a"),
+                        (
+                            AzulaType::Pointer(Rc::new(AzulaType::Named("U".to_string()))),
+                            "b"
+                        ),
+                    ]
+                );
+                assert_eq!(returns, &AzulaType::Named("T".to_string()));
+            }
+            stmt => panic!("expected function, got {:?}", stmt),
+        }
+    }
+
+    #[test]
+    fn test_parse_interfaces() {
+        let input = "interface Shape {
+    // The area
+    func area(self): int;
+    func scale(self, by: int);
+}
+impl Shape for Square {
+    func area(self): int { return 1; }
+}
+func total<T: Shape, U>(shape: T): int { return 0; }";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let expression = parser.parse_expression(LOWEST, true).unwrap().expression;
-        assert_eq!(
-            expression,
-            Expression::Infix(
-                Rc::new(ExpressionNode {
-                    expression: Expression::Infix(
-                        Rc::new(ExpressionNode {
-                            expression: Expression::Integer(123),
-                            typed: AzulaType::Int,
-                            span: Span { start: 1, end: 4 },
-                        }),
-                        Operator::Add,
-                        Rc::new(ExpressionNode {
-                            expression: Expression::Integer(3),
-                            typed: AzulaType::Int,
-                            span: Span { start: 7, end: 8 },
-                        })
-                    ),
-                    typed: AzulaType::Infer,
-                    span: Span { start: 1, end: 8 },
-                }),
-                Operator::Mul,
-                Rc::new(ExpressionNode {
-                    expression: Expression::Integer(10),
-                    typed: AzulaType::Int,
-                    span: Span { start: 12, end: 14 },
-                })
-            )
-        );
+        let body = match parser.parse() {
+            Statement::Root(body) => body,
+            _ => unreachable!(),
+        };
+        assert!(parser.errors.is_empty());
 
-        // Comparison
-        let input = "x || y";
+        let this = (AzulaType::Named("Self".to_string()), "self");
+        match &body[0] {
+            Statement::Interface { name, methods, .. } => {
+                assert_eq!(*name, "Shape");
+                assert_eq!(
+                    methods,
+                    &vec![
+                        ("area", vec![this.clone()], AzulaType::Int),
+                        ("scale", vec![this, (AzulaType::Int, "by")], AzulaType::Void),
+                    ]
+                );
+            }
+            stmt => panic!("expected interface, got {:?}", stmt),
+        }
+        match &body[1] {
+            Statement::Impl {
+                interface,
+                typ,
+                methods,
+                ..
+            } => {
+                assert_eq!(*interface, "Shape");
+                assert_eq!(typ, &AzulaType::Named("Square".to_string()));
+                assert!(matches!(
+                    methods[..],
+                    [Statement::Function { name: "area", .. }]
+                ));
+            }
+            stmt => panic!("expected impl, got {:?}", stmt),
+        }
+        match &body[2] {
+            Statement::Function {
+                generics, bounds, ..
+            } => {
+                assert_eq!(generics, &vec!["T", "U"]);
+                assert_eq!(bounds, &vec![("T", "Shape")]);
+            }
+            stmt => panic!("expected function, got {:?}", stmt),
+        }
+    }
+
+    #[test]
+    fn test_parse_doc_comments() {
+        let input = "/// Adds one\n///\n/// to x\nfunc f(x: int): int { return x + 1; }
+/// A point
+// not part of the docs
+struct Point { x: int }
+/// Dropped, var isn't documented
+var y = 1;
+func g() {}
+impl Show for Point {
+    /// Shows the point
+    func show(self: Point) {}
+}";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
+        let body = match parser.parse() {
+            Statement::Root(body) => body,
+            _ => unreachable!(),
+        };
+        assert!(parser.errors.is_empty());
 
-        let expression = parser.parse_expression(LOWEST, true).unwrap().expression;
+        let docs: Vec<_> = body
+            .iter()
+            .flat_map(|stmt| match stmt {
+                Statement::Impl { methods, .. } => methods.clone(),
+                stmt => vec![stmt.clone()],
+            })
+            .filter_map(|stmt| match stmt {
+                Statement::Function { doc, .. } | Statement::Struct { doc, .. } => Some(doc),
+                _ => None,
+            })
+            .collect();
         assert_eq!(
-            expression,
-            Expression::Infix(
-                Rc::new(ExpressionNode {
-                    expression: Expression::Identifier("x".to_string()),
-                    typed: AzulaType::Infer,
-                    span: Span { start: 0, end: 1 },
-                }),
-                Operator::Or,
-                Rc::new(ExpressionNode {
-                    expression: Expression::Identifier("y".to_string()),
-                    typed: AzulaType::Infer,
-                    span: Span { start: 5, end: 6 },
-                })
-            )
+            docs,
+            vec![
+                Some("Adds one\n\nto x".to_string()),
+                Some("A point".to_string()),
+                None,
+                Some("Shows the point".to_string()),
+            ]
         );
+
+        // Docs are kept when printing
+        let printed = crate::printer::print_ast(&Statement::Root(body));
+        assert!(printed.starts_with("/// Adds one\n///\n/// to x\nfunc f"));
     }
 
     #[test]
-    fn test_parse_array() {
-        let input = "[1, 2, 3]";
+    fn test_parse_tail_expression() {
+        let input = "func square(x: int): int { x * x }";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let expression = parser.parse_expression(LOWEST, true).unwrap();
-        assert_eq!(
-            expression.expression,
-            Expression::Array(vec![
-                ExpressionNode {
-                    expression: Expression::Integer(1),
-                    typed: AzulaType::Int,
-                    span: Span { start: 1, end: 2 }
-                },
-                ExpressionNode {
-                    expression: Expression::Integer(2),
-                    typed: AzulaType::Int,
-                    span: Span { start: 4, end: 5 }
-                },
-                ExpressionNode {
-                    expression: Expression::Integer(3),
-                    typed: AzulaType::Int,
-                    span: Span { start: 7, end: 8 }
-                }
-            ])
-        );
+        let func = parser.parse_function().unwrap();
+        assert!(parser.errors.is_empty());
+        match func {
+            Statement::Function { body, .. } => assert!(matches!(
+                body.as_ref(),
+                Statement::Block(stmts) if matches!(stmts[..], [Statement::ExpressionStatement(..)])
+            )),
+            stmt => panic!("expected function, got {:?}", stmt),
+        }
 
-        // Empty array
-        let input = "[]";
+        // Anywhere else the semicolon is still needed
+        let input = "func f() { g() h() }";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
+        parser.parse_function();
+        assert!(!parser.errors.is_empty());
+    }
 
-        let expression = parser.parse_expression(LOWEST, true).unwrap();
-        assert_eq!(expression.expression, Expression::Array(vec![]));
-
-        // Array initialiser
-        let input = "[0; 5]";
+    #[test]
+    fn test_parse_do_while() {
+        let input = "do { i = 1; } while x;";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let expression = parser.parse_expression(LOWEST, true).unwrap();
+        let stmt = parser.parse_statement().unwrap();
         assert_eq!(
-            expression.expression,
-            Expression::Array(vec![
-                ExpressionNode {
-                    expression: Expression::Integer(0),
-                    typed: AzulaType::Int,
-                    span: Span { start: 1, end: 2 }
-                },
-                ExpressionNode {
-                    expression: Expression::Integer(0),
-                    typed: AzulaType::Int,
-                    span: Span { start: 1, end: 2 }
-                },
-                ExpressionNode {
-                    expression: Expression::Integer(0),
-                    typed: AzulaType::Int,
-                    span: Span { start: 1, end: 2 }
-                },
+            stmt,
+            Statement::DoWhile(
                 ExpressionNode {
-                    expression: Expression::Integer(0),
-                    typed: AzulaType::Int,
-                    span: Span { start: 1, end: 2 }
+                    expression: Expression::Identifier("x".to_string()),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 20, end: 21 }
                 },
-                ExpressionNode {
-                    expression: Expression::Integer(0),
-                    typed: AzulaType::Int,
-                    span: Span { start: 1, end: 2 }
-                }
-            ])
+                vec![Statement::Reassign(
+                    ExpressionNode {
+                        expression: Expression::Identifier("i".to_string()),
+                        typed: AzulaType::Infer,
+                        span: Span { start: 5, end: 6 },
+                    },
+                    ExpressionNode {
+                        expression: Expression::Integer(1),
+                        typed: AzulaType::Int,
+                        span: Span { start: 9, end: 10 },
+                    },
+                    Span { start: 5, end: 11 }
+                )],
+                None,
+                Span { start: 0, end: 22 },
+            )
         );
+        assert!(parser.errors.is_empty());
     }
 
     #[test]
-    fn test_parse_function_call() {
-        let input = "test()";
+    fn test_parse_labeled_loop() {
+        let input = "outer: while x { break outer; continue; }";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let expression = parser.parse_expression(LOWEST, true).unwrap();
-        assert_eq!(
-            expression.expression,
-            Expression::FunctionCall {
-                function: Rc::new(ExpressionNode {
-                    expression: Expression::Identifier("test".to_string()),
-                    typed: AzulaType::Infer,
-                    span: Span { start: 0, end: 4 },
-                }),
-                args: vec![],
+        let stmt = parser.parse_statement().unwrap();
+        match stmt {
+            Statement::While(_, body, label, span) => {
+                assert_eq!(label, Some("outer"));
+                assert_eq!(span, Span { start: 7, end: 41 });
+                assert_eq!(
+                    body,
+                    vec![
+                        Statement::Break(Some("outer"), Span { start: 17, end: 29 }),
+                        Statement::Continue(None, Span { start: 30, end: 39 }),
+                    ]
+                );
             }
-        );
+            stmt => panic!("expected while, got {:?}", stmt),
+        }
+        assert!(parser.errors.is_empty());
+    }
 
-        let input = "test(5, \"test\")";
+    #[test]
+    fn test_parse_for() {
+        let input = "rows: for x in 0..n { break rows; }";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let expression = parser.parse_expression(LOWEST, true).unwrap();
-        println!("{:?}", parser.errors);
-        assert!(parser.errors.is_empty());
-        assert_eq!(
-            expression.expression,
-            Expression::FunctionCall {
-                function: Rc::new(ExpressionNode {
-                    expression: Expression::Identifier("test".to_string()),
-                    typed: AzulaType::Infer,
-                    span: Span { start: 0, end: 4 },
-                }),
-                args: vec![
-                    ExpressionNode {
-                        expression: Expression::Integer(5),
-                        typed: AzulaType::Int,
-                        span: Span { start: 5, end: 6 }
-                    },
-                    ExpressionNode {
-                        expression: Expression::String("test".to_string()),
-                        typed: AzulaType::Pointer(Rc::new(AzulaType::Str)),
-                        span: Span { start: 8, end: 14 }
-                    }
-                ],
+        let stmt = parser.parse_statement().unwrap();
+        match stmt {
+            Statement::For(index, name, iterable, body, label, span) => {
+                assert_eq!(index, None);
+                assert_eq!(name, "x");
+                assert!(matches!(iterable.expression, Expression::Range(..)));
+                assert_eq!(label, Some("rows"));
+                assert_eq!(span, Span { start: 6, end: 35 });
+                assert_eq!(
+                    body,
+                    vec![Statement::Break(Some("rows"), Span { start: 22, end: 33 })]
+                );
             }
-        );
+            stmt => panic!("expected for, got {:?}", stmt),
+        }
+        assert!(parser.errors.is_empty());
+
+        let input = "for i, x in xs {}";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+        assert!(matches!(
+            parser.parse_statement(),
+            Some(Statement::For(Some("i"), "x", ..))
+        ));
+        assert!(parser.errors.is_empty());
     }
 
     #[test]
-    fn test_string_transform() {
-        let test = "\\\\ \\n test \\t";
-        let result = string_transform(test).unwrap();
-        assert_eq!(result, "\\ \n test \t");
+    fn test_parse_range() {
+        let input = "0..n + 1";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
 
-        let test = "\\d";
-        let result = string_transform(test);
-        assert!(result.is_err());
+        let expression = parser.parse_expression(LOWEST, true).unwrap();
+        match expression.expression {
+            Expression::Range(start, end) => {
+                assert_eq!(start.expression, Expression::Integer(0));
+                assert!(matches!(
+                    end.expression,
+                    Expression::Infix(_, Operator::Add, _)
+                ));
+            }
+            expression => panic!("expected range, got {:?}", expression),
+        }
+        assert_eq!(expression.span, Span { start: 0, end: 8 });
     }
 
     #[test]
-    fn test_not() {
-        let input = "!true";
+    fn test_parse_array_repeat() {
+        let input = "[0; SIZE * 2]";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
         let expression = parser.parse_expression(LOWEST, true).unwrap();
         assert!(parser.errors.is_empty());
-        assert_eq!(
-            expression.expression,
-            Expression::Not(Rc::new(ExpressionNode {
-                expression: Expression::Boolean(true),
-                typed: AzulaType::Bool,
-                span: Span { start: 1, end: 5 }
-            }))
-        );
+        assert_eq!(expression.span, Span { start: 0, end: 13 });
+        match expression.expression {
+            Expression::ArrayRepeat(value, count) => {
+                assert_eq!(value.expression, Expression::Integer(0));
+                assert!(matches!(
+                    count.expression,
+                    Expression::Infix(_, Operator::Mul, _)
+                ));
+            }
+            expression => panic!("expected array repeat, got {:?}", expression),
+        }
     }
 
     #[test]
-    fn test_pointer() {
-        let input = "&my_var";
+    fn test_parse_raw_string() {
+        // \d would be an invalid escape in a plain string
+        let input = r#"r"^\d+\n$""#;
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
@@ -2218,53 +4863,186 @@ mod tests {
         assert!(parser.errors.is_empty());
         assert_eq!(
             expression.expression,
-            Expression::Pointer(Rc::new(ExpressionNode {
-                expression: Expression::Identifier("my_var".to_string()),
-                typed: AzulaType::Infer,
-                span: Span { start: 1, end: 7 }
-            }))
+            Expression::String(r"^\d+\n$".to_string())
+        );
+        assert_eq!(
+            expression.typed,
+            AzulaType::Pointer(Rc::new(AzulaType::Str))
         );
     }
 
     #[test]
-    fn test_float() {
-        let input = "5.5";
+    fn test_parse_multiline_string() {
+        let parse = |input| {
+            let lexer: Lexer = Lexer::new(input);
+            let mut parser = Parser::new(input, lexer);
+            let expression = parser.parse_expression(LOWEST, true).unwrap();
+            assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+            expression.expression
+        };
+
+        // The closing quotes' indentation comes off every line
+        let input = "\"\"\"
+        usage: azula <file>
+          -h\tshow this
+
+        \"\"\"";
+        assert_eq!(
+            parse(input),
+            Expression::String("usage: azula <file>\n  -h\tshow this\n".to_string())
+        );
+
+        // Or else the least indented line's
+        let input = "\"\"\"
+            first
+          second \"quoted\"\"\"\"";
+        assert_eq!(
+            parse(input),
+            Expression::String("  first\nsecond \"quoted\"".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_increment() {
+        let input = "counts[i]++;";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let expression = parser.parse_expression(LOWEST, true).unwrap();
-        assert!(parser.errors.is_empty());
-        assert_eq!(expression.expression, Expression::Float(5.5));
+        let stmt = parser.parse_statement().unwrap();
+        match stmt {
+            Statement::Reassign(target, value, span) => {
+                assert_eq!(span, Span { start: 0, end: 12 });
+                match value.expression {
+                    Expression::Infix(left, Operator::Add, one) => {
+                        assert_eq!(left.as_ref(), &target);
+                        assert_eq!(one.expression, Expression::Integer(1));
+                    }
+                    expression => panic!("expected an add, got {:?}", expression),
+                }
+            }
+            stmt => panic!("expected a reassign, got {:?}", stmt),
+        }
 
-        let input = "3.14523";
+        let input = "x--;";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let expression = parser.parse_expression(LOWEST, true).unwrap();
+        let stmt = parser.parse_statement().unwrap();
+        assert!(matches!(
+            stmt,
+            Statement::Reassign(
+                _,
+                ExpressionNode {
+                    expression: Expression::Infix(_, Operator::Sub, _),
+                    ..
+                },
+                _
+            )
+        ));
+    }
+
+    #[test]
+    fn test_parse_attributes() {
+        let input = "@deprecated(\"use h\") @inline\nfunc f() {}\n@packed\nstruct S { x: int }\n@export(\"limit\")\nconst LIMIT = 10;";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let body = match parser.parse() {
+            Statement::Root(body) => body,
+            _ => unreachable!(),
+        };
         assert!(parser.errors.is_empty());
-        assert_eq!(expression.expression, Expression::Float(3.14523));
+        match &body[0] {
+            Statement::Function { attributes, .. } => {
+                assert_eq!(attributes.len(), 2);
+                assert_eq!(attributes[0].name, "deprecated");
+                assert_eq!(
+                    attributes[0].args[0].expression,
+                    Expression::String("use h".to_string())
+                );
+                assert_eq!(attributes[0].span, Span { start: 0, end: 20 });
+                assert_eq!(
+                    attributes[1],
+                    Attribute {
+                        name: "inline",
+                        args: vec![],
+                        span: Span { start: 21, end: 28 },
+                    }
+                );
+            }
+            stmt => panic!("expected a function, got {:?}", stmt),
+        }
+        assert!(matches!(
+            &body[1],
+            Statement::Struct { annotations, .. } if annotations[0].name == "packed"
+        ));
+        assert!(matches!(
+            &body[2],
+            Statement::Assign(false, _, _, _, attributes, _) if attributes[0].name == "export"
+        ));
+    }
 
-        let input = "5.2 * 15.2";
+    #[test]
+    fn test_parse_scope() {
+        let input = "{ var x = 1; { x; } }";
         let lexer: Lexer = input.into();
         let mut parser = Parser::new(input, lexer);
 
-        let expression = parser.parse_expression(LOWEST, true).unwrap();
+        match parser.parse_statement().unwrap() {
+            Statement::Scope(body, span) => {
+                assert_eq!(span, Span { start: 0, end: 21 });
+                assert!(matches!(body[0], Statement::Assign(..)));
+                assert!(matches!(&body[1], Statement::Scope(inner, _) if inner.len() == 1));
+            }
+            stmt => panic!("expected block, got {:?}", stmt),
+        }
         assert!(parser.errors.is_empty());
-        assert_eq!(
-            expression.expression,
-            Expression::Infix(
-                Rc::new(ExpressionNode {
-                    expression: Expression::Float(5.2),
-                    typed: AzulaType::Float,
-                    span: Span { start: 0, end: 3 }
-                }),
-                Operator::Mul,
-                Rc::new(ExpressionNode {
-                    expression: Expression::Float(15.2),
-                    typed: AzulaType::Float,
-                    span: Span { start: 6, end: 10 }
-                })
-            )
-        );
+    }
+
+    #[test]
+    fn test_parse_try_before_brace_and_operator() {
+        let input = "func main(): !int {\nif check()? {\n}\nvar x = parse(s)? + 1;\nvar y = ok ? f()? : g()?;\nreturn x;\n}";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let body = match parser.parse() {
+            Statement::Root(body) => body,
+            _ => unreachable!(),
+        };
+        assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+
+        let stmts = match &body[0] {
+            Statement::Function { body, .. } => match body.as_ref() {
+                Statement::Block(stmts) => stmts.clone(),
+                stmt => panic!("expected block, got {:?}", stmt),
+            },
+            stmt => panic!("expected function, got {:?}", stmt),
+        };
+
+        match &stmts[0] {
+            Statement::If(cond, _, _) => {
+                assert!(matches!(cond.expression, Expression::Try(..)))
+            }
+            stmt => panic!("expected if, got {:?}", stmt),
+        }
+        match &stmts[1] {
+            Statement::Assign(_, _, _, value, _, _) => match &value.expression {
+                Expression::Infix(left, Operator::Add, _) => {
+                    assert!(matches!(left.expression, Expression::Try(..)))
+                }
+                expr => panic!("expected addition, got {:?}", expr),
+            },
+            stmt => panic!("expected assign, got {:?}", stmt),
+        }
+        match &stmts[2] {
+            Statement::Assign(_, _, _, value, _, _) => match &value.expression {
+                Expression::Ternary(_, then, otherwise) => {
+                    assert!(matches!(then.expression, Expression::Try(..)));
+                    assert!(matches!(otherwise.expression, Expression::Try(..)));
+                }
+                expr => panic!("expected ternary, got {:?}", expr),
+            },
+            stmt => panic!("expected assign, got {:?}", stmt),
+        }
     }
 }
@@ -0,0 +1,514 @@
+use azula_ast::prelude::*;
+use azula_type::prelude::AzulaType;
+
+/// Prints an AST back out as Azula source. The output parses back to the same
+/// tree, nested operators are always bracketed so precedence never matters
+pub fn print_ast(stmt: &Statement) -> String {
+    let mut out = String::new();
+    print_statement(stmt, 0, &mut out);
+    out
+}
+
+fn print_body(body: &[Statement], indent: usize, out: &mut String) {
+    for stmt in body {
+        print_statement(stmt, indent, out);
+    }
+}
+
+fn print_statement(stmt: &Statement, indent: usize, out: &mut String) {
+    let pad = "    ".repeat(indent);
+
+    match stmt {
+        Statement::Root(body) | Statement::Block(body) => print_body(body, indent, out),
+        Statement::Function {
+            name,
+            generics,
+            bounds,
+            args,
+            defaults,
+            variadic,
+            returns,
+            body,
+            public,
+            attributes,
+            doc,
+            ..
+        } => {
+            print_doc(doc, &pad, out);
+            print_attributes(attributes, &pad, out);
+            let fixed = args.len() - *variadic as usize;
+            let first_default = fixed - defaults.len();
+            let args: Vec<_> = args
+                .iter()
+                .enumerate()
+                .map(
+                    |(index, (typ, name))| match (typ, index.checked_sub(first_default)) {
+                        (AzulaType::Slice(inner), _) if index == fixed => {
+                            format!("{}: {}...", name, print_type(inner))
+                        }
+                        (_, Some(default)) => format!(
+                            "{}: {} = {}",
+                            name,
+                            print_type(typ),
+                            print_expression(&defaults[default])
+                        ),
+                        _ => format!("{}: {}", name, print_type(typ)),
+                    },
+                )
+                .collect();
+            out.push_str(&format!(
+                "{}{}func {}{}({})",
+                pad,
+                if *public { "pub " } else { "" },
+                name,
+                print_generics(generics, bounds),
+                args.join(", ")
+            ));
+            if *returns != AzulaType::Void {
+                out.push_str(&format!(": {}", print_type(returns)));
+            }
+            out.push_str(" {\n");
+            print_statement(body, indent + 1, out);
+            out.push_str(&format!("{}}}\n", pad));
+        }
+        Statement::Return(value, _) => match value {
+            Some(value) => out.push_str(&format!("{}return {};\n", pad, print_expression(value))),
+            None => out.push_str(&format!("{}return;\n", pad)),
+        },
+        Statement::Assign(mutable, name, annotation, value, attributes, _) => {
+            print_attributes(attributes, &pad, out);
+            out.push_str(&format!("{}{} {}", pad, keyword(*mutable), name));
+            if let Some(typ) = annotation {
+                out.push_str(&format!(": {}", print_type(typ)));
+            }
+            out.push_str(&format!(" = {};\n", print_expression(value)));
+        }
+        Statement::ExpressionStatement(expr, _) => {
+            out.push_str(&format!("{}{};\n", pad, print_expression(expr)))
+        }
+        Statement::If(cond, body, _) => {
+            out.push_str(&format!("{}if {} {{\n", pad, print_condition(cond)));
+            print_body(body, indent + 1, out);
+            out.push_str(&format!("{}}}\n", pad));
+        }
+        Statement::ExternFunction {
+            name,
+            varargs,
+            args,
+            returns,
+            ..
+        } => {
+            let args: Vec<_> = args
+                .iter()
+                .map(|(typ, name)| match name {
+                    Some(name) => format!("{}: {}", name, print_type(typ)),
+                    None => print_type(typ),
+                })
+                .collect();
+            out.push_str(&format!(
+                "{}extern {}func {}({})",
+                pad,
+                if *varargs { "varargs " } else { "" },
+                name,
+                args.join(", ")
+            ));
+            if *returns != AzulaType::Void {
+                out.push_str(&format!(": {}", print_type(returns)));
+            }
+            out.push_str(";\n");
+        }
+        Statement::Reassign(target, value, _) => out.push_str(&format!(
+            "{}{} = {};\n",
+            pad,
+            print_operand(target),
+            print_expression(value)
+        )),
+        Statement::While(cond, body, label, _) => {
+            out.push_str(&format!(
+                "{}{}while {} {{\n",
+                pad,
+                print_label(label),
+                print_condition(cond)
+            ));
+            print_body(body, indent + 1, out);
+            out.push_str(&format!("{}}}\n", pad));
+        }
+        Statement::DoWhile(cond, body, label, _) => {
+            out.push_str(&format!("{}{}do {{\n", pad, print_label(label)));
+            print_body(body, indent + 1, out);
+            out.push_str(&format!("{}}} while {};\n", pad, print_expression(cond)));
+        }
+        Statement::For(index, name, iterable, body, label, _) => {
+            let index = index.map_or(String::new(), |index| format!("{}, ", index));
+            out.push_str(&format!(
+                "{}{}for {}{} in {} {{\n",
+                pad,
+                print_label(label),
+                index,
+                name,
+                print_condition(iterable)
+            ));
+            print_body(body, indent + 1, out);
+            out.push_str(&format!("{}}}\n", pad));
+        }
+        Statement::Scope(body, _) => {
+            out.push_str(&format!("{}{{\n", pad));
+            print_body(body, indent + 1, out);
+            out.push_str(&format!("{}}}\n", pad));
+        }
+        Statement::Struct {
+            name,
+            generics,
+            attributes,
+            defaults,
+            annotations,
+            doc,
+            ..
+        } => {
+            print_doc(doc, &pad, out);
+            print_attributes(annotations, &pad, out);
+            out.push_str(&format!(
+                "{}struct {}{} {{\n",
+                pad,
+                name,
+                print_generics(generics, &[])
+            ));
+            for (typ, name) in attributes {
+                let default = match defaults.iter().find(|(attr, _)| attr == name) {
+                    Some((_, default)) => format!(" = {}", print_expression(default)),
+                    None => String::new(),
+                };
+                out.push_str(&format!(
+                    "{}    {}: {}{},\n",
+                    pad,
+                    name,
+                    print_type(typ),
+                    default
+                ));
+            }
+            out.push_str(&format!("{}}}\n", pad));
+        }
+        Statement::Match(value, arms, _) => {
+            out.push_str(&format!("{}match {} {{\n", pad, print_condition(value)));
+            for (typ, binding, body, _) in arms {
+                let pattern = match typ {
+                    Some(typ) => print_type(typ),
+                    None => "_".to_string(),
+                };
+                match binding {
+                    Some(binding) => {
+                        out.push_str(&format!("{}    {} {} => {{\n", pad, pattern, binding))
+                    }
+                    None => out.push_str(&format!("{}    {} => {{\n", pad, pattern)),
+                }
+                print_body(body, indent + 2, out);
+                out.push_str(&format!("{}    }}\n", pad));
+            }
+            out.push_str(&format!("{}}}\n", pad));
+        }
+        Statement::Destructure(mutable, names, value, _) => out.push_str(&format!(
+            "{}{} ({}) = {};\n",
+            pad,
+            keyword(*mutable),
+            names.join(", "),
+            print_expression(value)
+        )),
+        Statement::Defer(expr, _) => {
+            out.push_str(&format!("{}defer {};\n", pad, print_expression(expr)))
+        }
+        Statement::Break(label, _) => match label {
+            Some(label) => out.push_str(&format!("{}break {};\n", pad, label)),
+            None => out.push_str(&format!("{}break;\n", pad)),
+        },
+        Statement::Continue(label, _) => match label {
+            Some(label) => out.push_str(&format!("{}continue {};\n", pad, label)),
+            None => out.push_str(&format!("{}continue;\n", pad)),
+        },
+        // The imported declarations belong to their own file
+        Statement::Import(name, ..) => out.push_str(&format!("{}import {};\n", pad, name)),
+        Statement::Conditional(condition, declaration, _) => {
+            let condition: Vec<_> = condition
+                .iter()
+                .map(|(key, value)| format!("{} = \"{}\"", key, value))
+                .collect();
+            out.push_str(&format!("{}@if({})\n", pad, condition.join(", ")));
+            print_statement(declaration, indent, out);
+        }
+        Statement::IfLet(mutable, name, value, body, _) => {
+            out.push_str(&format!(
+                "{}if {} {} = {} {{\n",
+                pad,
+                keyword(*mutable),
+                name,
+                print_condition(value)
+            ));
+            print_body(body, indent + 1, out);
+            out.push_str(&format!("{}}}\n", pad));
+        }
+        Statement::Interface { name, methods, .. } => {
+            out.push_str(&format!("{}interface {} {{\n", pad, name));
+            for (method, args, returns) in methods {
+                let args: Vec<_> = args
+                    .iter()
+                    .map(|(typ, name)| format!("{}: {}", name, print_type(typ)))
+                    .collect();
+                out.push_str(&format!("{}    func {}({})", pad, method, args.join(", ")));
+                if *returns != AzulaType::Void {
+                    out.push_str(&format!(": {}", print_type(returns)));
+                }
+                out.push_str(";\n");
+            }
+            out.push_str(&format!("{}}}\n", pad));
+        }
+        Statement::Impl {
+            interface,
+            typ,
+            methods,
+            ..
+        } => {
+            out.push_str(&format!(
+                "{}impl {} for {} {{\n",
+                pad,
+                interface,
+                print_type(typ)
+            ));
+            print_body(methods, indent + 1, out);
+            out.push_str(&format!("{}}}\n", pad));
+        }
+    }
+}
+
+fn print_attributes(attributes: &[Attribute], pad: &str, out: &mut String) {
+    for attribute in attributes {
+        out.push_str(&format!("{}@{}", pad, attribute.name));
+        if !attribute.args.is_empty() {
+            let args: Vec<_> = attribute.args.iter().map(print_expression).collect();
+            out.push_str(&format!("({})", args.join(", ")));
+        }
+        out.push('\n');
+    }
+}
+
+fn print_doc(doc: &Option<String>, pad: &str, out: &mut String) {
+    for line in doc.iter().flat_map(|doc| doc.lines()) {
+        match line {
+            "" => out.push_str(&format!("{}///\n", pad)),
+            line => out.push_str(&format!("{}/// {}\n", pad, line)),
+        }
+    }
+}
+
+fn print_label(label: &Option<&str>) -> String {
+    match label {
+        Some(label) => format!("{}: ", label),
+        None => String::new(),
+    }
+}
+
+fn keyword(mutable: bool) -> &'static str {
+    if mutable {
+        "var"
+    } else {
+        "const"
+    }
+}
+
+pub fn print_type(typ: &AzulaType) -> String {
+    typ.to_string()
+}
+
+pub fn print_expression(expr: &ExpressionNode) -> String {
+    match &expr.expression {
+        Expression::Infix(left, op, right) => format!(
+            "{} {} {}",
+            print_operand(left),
+            print_operator(op),
+            print_operand(right)
+        ),
+        Expression::Integer(i) => i.to_string(),
+        Expression::Float(f) => format!("{:?}", f),
+        Expression::Identifier(name) => name.clone(),
+        Expression::Boolean(b) => b.to_string(),
+        Expression::String(s) => format!("\"{}\"", escape(s)),
+        Expression::FunctionCall { function, args } => {
+            format!("{}({})", print_operand(function), print_list(args))
+        }
+        Expression::Not(inner) => format!("!{}", print_operand(inner)),
+        Expression::Negate(inner) => format!("-{}", print_operand(inner)),
+        Expression::Pointer(inner) => format!("&{}", print_operand(inner)),
+        Expression::Deref(inner) => format!("*{}", print_operand(inner)),
+        Expression::Array(values) => format!("[{}]", print_list(values)),
+        Expression::ArrayRepeat(value, count) => {
+            format!("[{}; {}]", print_expression(value), print_expression(count))
+        }
+        Expression::ArrayAccess(array, index) => {
+            format!("{}[{}]", print_operand(array), print_expression(index))
+        }
+        Expression::StructInitialisation(name, values) => {
+            if values.is_empty() {
+                return format!("{} {{}}", print_operand(name));
+            }
+            let values: Vec<_> = values
+                .iter()
+                .map(|(name, value)| format!("{}: {}", name, print_expression(value)))
+                .collect();
+            format!("{} {{ {} }}", print_operand(name), values.join(", "))
+        }
+        // 1.x would lex as the start of a float
+        Expression::StructAccess(value, member) => match value.expression {
+            Expression::Integer(_) | Expression::Float(_) => {
+                format!("({}).{}", print_expression(value), print_operand(member))
+            }
+            _ => format!("{}.{}", print_operand(value), print_operand(member)),
+        },
+        Expression::Cast(value, typ) => format!("{} as {}", print_operand(value), print_type(typ)),
+        Expression::SizeOf(typ) => format!("sizeof({})", print_type(typ)),
+        Expression::New(typ) => format!("new {}", print_type(typ)),
+        Expression::Ternary(cond, then, otherwise) => format!(
+            "{} ? {} : {}",
+            print_operand(cond),
+            print_operand(then),
+            print_operand(otherwise)
+        ),
+        Expression::Tuple(values) => format!("({})", print_list(values)),
+        Expression::Range(start, end) => {
+            format!("{}..{}", print_operand(start), print_operand(end))
+        }
+        Expression::Try(value, _) => format!("{}?", print_operand(value)),
+        Expression::NamedArgument(name, value) => {
+            format!("{}: {}", name, print_expression(value))
+        }
+        Expression::Closure { args, body, .. } => {
+            let args: Vec<_> = args
+                .iter()
+                .map(|(typ, name)| format!("{}: {}", name, print_type(typ)))
+                .collect();
+            format!("|{}| {}", args.join(", "), print_expression(body))
+        }
+    }
+}
+
+fn print_generics(generics: &[&str], bounds: &[(&str, &str)]) -> String {
+    if generics.is_empty() {
+        return String::new();
+    }
+    let generics: Vec<_> = generics
+        .iter()
+        .map(
+            |generic| match bounds.iter().find(|(name, _)| name == generic) {
+                Some((_, bound)) => format!("{}: {}", generic, bound),
+                None => generic.to_string(),
+            },
+        )
+        .collect();
+    format!("<{}>", generics.join(", "))
+}
+
+fn print_list(values: &[ExpressionNode]) -> String {
+    let values: Vec<_> = values.iter().map(print_expression).collect();
+    values.join(", ")
+}
+
+// Anything that isn't a single term gets brackets when it's nested
+fn print_operand(expr: &ExpressionNode) -> String {
+    match expr.expression {
+        Expression::Infix(..)
+        | Expression::Not(..)
+        | Expression::Negate(..)
+        | Expression::Pointer(..)
+        | Expression::Deref(..)
+        | Expression::StructInitialisation(..)
+        | Expression::Cast(..)
+        | Expression::Ternary(..)
+        | Expression::Try(..)
+        | Expression::Range(..)
+        | Expression::Closure { .. } => format!("({})", print_expression(expr)),
+        _ => print_expression(expr),
+    }
+}
+
+// Conditions are followed by a {, which would otherwise start a struct
+// initialisation, turn a trailing ? into a ternary or end up in a closure's
+// body
+fn print_condition(expr: &ExpressionNode) -> String {
+    match expr.expression {
+        Expression::StructInitialisation(..) | Expression::Try(..) | Expression::Closure { .. } => {
+            format!("({})", print_expression(expr))
+        }
+        _ => print_expression(expr),
+    }
+}
+
+fn print_operator(op: &Operator) -> &'static str {
+    match op {
+        Operator::Add => "+",
+        Operator::Sub => "-",
+        Operator::Mul => "*",
+        Operator::Div => "/",
+        Operator::Mod => "%",
+        Operator::Power => "**",
+        Operator::Or => "||",
+        Operator::And => "&&",
+        Operator::Eq => "==",
+        Operator::Neq => "!=",
+        Operator::Lt => "<",
+        Operator::Lte => "<=",
+        Operator::Gt => ">",
+        Operator::Gte => ">=",
+        Operator::Shl => "<<",
+        Operator::Shr => ">>",
+    }
+}
+
+// The lexer has no escaped quote, so quotes go through \x
+fn escape(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\0' => out.push_str("\\0"),
+            '"' => out.push_str("\\x22"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_print_ast() {
+        let input = "extern varargs func printf(&str): int;
+struct Point {
+    x: int,
+    y: ?float,
+}
+func main(args: [&str]): !int {
+    var p = Point { x: 1 + 2 * 3, y: 1.5 };
+    if var y = p.y {
+        printf(\"%f\\n\", y as int);
+    }
+    match value {
+        int i => {
+            return (i > 0) ? i : 0;
+        }
+        _ => {
+        }
+    }
+    return parse(args[0])?;
+}
+";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+        let ast = parser.parse();
+        assert!(parser.errors.is_empty());
+
+        assert_eq!(print_ast(&ast), input.replace("1 + 2 * 3", "1 + (2 * 3)"));
+    }
+}
@@ -0,0 +1,113 @@
+use std::rc::Rc;
+
+use azula_ast::prelude::*;
+use azula_type::prelude::AzulaType;
+
+/// Moves the statements at the top level of a script into an implicit main,
+/// leaving functions, structs, interfaces, impls and imports where they are. Scripts
+/// that already have a main are left alone
+pub fn wrap_script(root: Statement) -> Statement {
+    let body = match root {
+        Statement::Root(body) => body,
+        stmt => return stmt,
+    };
+
+    if body
+        .iter()
+        .any(|stmt| matches!(stmt, Statement::Function { name: "main", .. }))
+    {
+        return Statement::Root(body);
+    }
+
+    let (mut declarations, statements): (Vec<_>, Vec<_>) = body.into_iter().partition(|stmt| {
+        matches!(
+            stmt,
+            Statement::Function { .. }
+                | Statement::ExternFunction { .. }
+                | Statement::Struct { .. }
+                | Statement::Interface { .. }
+                | Statement::Impl { .. }
+                | Statement::Import(..)
+                | Statement::Conditional(..)
+        )
+    });
+    if statements.is_empty() {
+        return Statement::Root(declarations);
+    }
+
+    let span = Span {
+        start: statements
+            .first()
+            .and_then(|stmt| stmt.span())
+            .unwrap()
+            .start,
+        end: statements.last().and_then(|stmt| stmt.span()).unwrap().end,
+    };
+    declarations.push(Statement::Function {
+        name: "main",
+        generics: vec![],
+        bounds: vec![],
+        args: vec![],
+        arg_spans: vec![],
+        defaults: vec![],
+        variadic: false,
+        returns: AzulaType::Void,
+        body: Rc::new(Statement::Block(statements)),
+        public: false,
+        attributes: vec![],
+        doc: None,
+        span,
+    });
+    Statement::Root(declarations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_wrap_script() {
+        let input = "func double(x: int): int { return x * 2; }
+var x = double(2);
+printf(\"%d\", x);";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let body = match wrap_script(parser.parse()) {
+            Statement::Root(body) => body,
+            _ => unreachable!(),
+        };
+        assert!(matches!(
+            body[0],
+            Statement::Function { name: "double", .. }
+        ));
+        match &body[1] {
+            Statement::Function {
+                name: "main",
+                body,
+                span,
+                ..
+            } => {
+                assert!(matches!(
+                    body.as_ref(),
+                    Statement::Block(stmts)
+                        if matches!(stmts[..], [Statement::Assign(..), Statement::ExpressionStatement(..)])
+                ));
+                assert_eq!(
+                    &input[span.start..span.end],
+                    "var x = double(2);\nprintf(\"%d\", x)"
+                );
+            }
+            stmt => panic!("expected main, got {:?}", stmt),
+        }
+
+        // An explicit main is kept as it is
+        let input = "func main() {}\nreturn;";
+        let lexer: Lexer = input.into();
+        let mut parser = Parser::new(input, lexer);
+
+        let root = parser.parse();
+        assert_eq!(wrap_script(root.clone()), root);
+    }
+}
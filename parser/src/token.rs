@@ -8,6 +8,10 @@ pub struct Span {
 pub enum TokenKind<'a> {
     Identifier(&'a str),
     String(&'a str),
+    // r"..." with its contents taken as they're written
+    RawString(&'a str),
+    // """...""" spanning lines, its indentation still to be taken off
+    MultilineString(&'a str),
     Char(&'a str),
     Integer(i64),
 
@@ -19,16 +23,25 @@ pub enum TokenKind<'a> {
     BraceClose,   // }
 
     Dot,       // .
+    Ellipsis,  // ...
+    DotDot,    // ..
     Comma,     // ,
     SemiColon, // ;
     Colon,     // :
+    At,        // @
+    Question,  // ?
+    Try,       // ? at the end of an expression, told apart by the parser
 
     Plus,         // +
     Minus,        // -
+    Arrow,        // ->
+    Increment,    // ++
+    Decrement,    // --
     Slash,        // /
     Asterisk,     // *
     Power,        // **
     Assign,       // =
+    FatArrow,     // =>
     Equal,        // ==
     NotEqual,     // !=
     Bar,          // |
@@ -40,21 +53,36 @@ pub enum TokenKind<'a> {
     LessEqual,    // <=
     Greater,      // >
     GreaterEqual, // >=
+    ShiftLeft,    // <<
+    ShiftRight,   // >>
     Modulo,       // %
 
-    Function, // func
-    Return,   // return
-    Var,      // var
-    Const,    // const
-    True,     // true
-    False,    // false
-    If,       // If
-    Extern,   // extern
-    VarArgs,  // varargs
-    While,    // while
-    Struct,   // struct
+    Function,  // func
+    Return,    // return
+    Var,       // var
+    Const,     // const
+    True,      // true
+    False,     // false
+    If,        // If
+    Extern,    // extern
+    VarArgs,   // varargs
+    While,     // while
+    Do,        // do
+    Break,     // break
+    Continue,  // continue
+    Struct,    // struct
+    Match,     // match
+    As,        // as
+    Defer,     // defer
+    Interface, // interface
+    Impl,      // impl
+    For,       // for
+    In,        // in
+    Import,    // import
+    Pub,       // pub
 
     Comment,
+    DocComment,
     UnknownToken,
     EOF,
 }
@@ -70,7 +98,7 @@ impl<'a> TokenKind<'a> {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Token<'a> {
     pub kind: TokenKind<'a>,
     pub span: Span,
@@ -0,0 +1,250 @@
+use std::{collections::HashMap, fmt, mem::discriminant, rc::Rc};
+
+use azula_ast::prelude::*;
+use azula_error::prelude::*;
+use azula_type::prelude::AzulaType;
+
+/// A value worked out at compile time
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub(crate) enum ConstValue {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+}
+
+impl ConstValue {
+    pub(crate) fn typ<'a>(&self) -> AzulaType<'a> {
+        match self {
+            ConstValue::Int(_) => AzulaType::Int,
+            ConstValue::Float(_) => AzulaType::Float,
+            ConstValue::Bool(_) => AzulaType::Bool,
+            ConstValue::Str(_) => AzulaType::Pointer(Rc::new(AzulaType::Str)),
+        }
+    }
+
+    /// The literal the value folds to
+    pub(crate) fn literal<'a>(&self, span: Span) -> ExpressionNode<'a> {
+        let expression = match self {
+            ConstValue::Int(value) => Expression::Integer(*value),
+            ConstValue::Float(value) => Expression::Float(*value),
+            ConstValue::Bool(value) => Expression::Boolean(*value),
+            ConstValue::Str(value) => Expression::String(value.clone()),
+        };
+
+        ExpressionNode {
+            expression,
+            typed: self.typ(),
+            span,
+        }
+    }
+}
+
+impl fmt::Display for ConstValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConstValue::Int(value) => write!(f, "{}", value),
+            ConstValue::Float(value) => write!(f, "{}", value),
+            ConstValue::Bool(value) => write!(f, "{}", value),
+            ConstValue::Str(value) => write!(f, "{:?}", value),
+        }
+    }
+}
+
+/// The top-level constants, each evaluated the first time it's needed so
+/// they can be used before they're defined
+pub(crate) struct Constants<'a> {
+    definitions: HashMap<String, ExpressionNode<'a>>,
+    // None for the ones that failed, which have already been reported
+    values: HashMap<String, Option<ConstValue>>,
+    // The constants being evaluated, innermost last, to catch cycles
+    evaluating: Vec<String>,
+}
+
+impl<'a> Constants<'a> {
+    pub(crate) fn new() -> Self {
+        Self {
+            definitions: HashMap::new(),
+            values: HashMap::new(),
+            evaluating: vec![],
+        }
+    }
+
+    pub(crate) fn define(&mut self, name: &str, value: &ExpressionNode<'a>) {
+        self.definitions
+            .entry(name.to_string())
+            .or_insert_with(|| value.clone());
+    }
+
+    /// The value of a constant, reporting why if it can't be worked out
+    pub(crate) fn value(
+        &mut self,
+        name: &str,
+        span: &Span,
+        errors: &mut Vec<AzulaError>,
+    ) -> Option<ConstValue> {
+        if let Some(value) = self.values.get(name) {
+            return value.clone();
+        }
+        let definition = match self.definitions.get(name) {
+            Some(definition) => definition.clone(),
+            None => return fail(ErrorType::NonLiteralConstant, span, errors),
+        };
+        if self.evaluating.iter().any(|evaluating| evaluating == name) {
+            return fail(ErrorType::ConstantCycle(name.to_string()), span, errors);
+        }
+
+        self.evaluating.push(name.to_string());
+        let value = self.evaluate(&definition, errors);
+        self.evaluating.pop();
+        self.values.insert(name.to_string(), value.clone());
+        value
+    }
+
+    /// Folds an expression of literals and constants, reporting the first
+    /// part that can't be
+    pub(crate) fn evaluate(
+        &mut self,
+        expr: &ExpressionNode<'a>,
+        errors: &mut Vec<AzulaError>,
+    ) -> Option<ConstValue> {
+        match &expr.expression {
+            Expression::Integer(value) => Some(ConstValue::Int(*value)),
+            Expression::Float(value) => Some(ConstValue::Float(*value)),
+            Expression::Boolean(value) => Some(ConstValue::Bool(*value)),
+            Expression::String(value) => Some(ConstValue::Str(value.clone())),
+            Expression::Identifier(name) => self.value(name, &expr.span, errors),
+            Expression::Not(inner) => match self.evaluate(inner, errors)? {
+                ConstValue::Bool(value) => Some(ConstValue::Bool(!value)),
+                value => fail(
+                    ErrorType::NonBoolCondition(format!("{:?}", value.typ()), None),
+                    &inner.span,
+                    errors,
+                ),
+            },
+            Expression::Negate(inner) => match self.evaluate(inner, errors)? {
+                ConstValue::Int(value) => match value.checked_neg() {
+                    Some(value) => Some(ConstValue::Int(value)),
+                    None => fail(ErrorType::ConstantOverflow, &expr.span, errors),
+                },
+                ConstValue::Float(value) => Some(ConstValue::Float(-value)),
+                value => fail(
+                    ErrorType::NonOperatorType(format!("{:?}", value.typ()), "-".to_string()),
+                    &inner.span,
+                    errors,
+                ),
+            },
+            Expression::Ternary(condition, then, otherwise) => {
+                match self.evaluate(condition, errors)? {
+                    ConstValue::Bool(true) => self.evaluate(then, errors),
+                    ConstValue::Bool(false) => self.evaluate(otherwise, errors),
+                    value => fail(
+                        ErrorType::NonBoolCondition(format!("{:?}", value.typ()), None),
+                        &condition.span,
+                        errors,
+                    ),
+                }
+            }
+            Expression::Infix(left, operator, right) => {
+                let left = self.evaluate(left, errors)?;
+                let right = self.evaluate(right, errors)?;
+                infix(left, operator, right, &expr.span, errors)
+            }
+            _ => fail(ErrorType::NonLiteralConstant, &expr.span, errors),
+        }
+    }
+}
+
+fn infix(
+    left: ConstValue,
+    operator: &Operator,
+    right: ConstValue,
+    span: &Span,
+    errors: &mut Vec<AzulaError>,
+) -> Option<ConstValue> {
+    use ConstValue::*;
+
+    // The one operator taking a float and an int
+    if let (Operator::Power, Float(base), Int(exponent)) = (operator, &left, &right) {
+        return match i32::try_from(*exponent) {
+            Ok(exponent) => Some(Float(base.powi(exponent))),
+            Err(_) => fail(ErrorType::ConstantOverflow, span, errors),
+        };
+    }
+
+    if discriminant(&left) != discriminant(&right) {
+        return fail(
+            ErrorType::MismatchedTypes(format!("{:?}", left.typ()), format!("{:?}", right.typ())),
+            span,
+            errors,
+        );
+    }
+
+    // None for an operator the operands can't be used with, Some(None) for
+    // one that overflows
+    let value = match (operator, &left, &right) {
+        (Operator::Eq, ..) => Some(Some(Bool(left == right))),
+        (Operator::Neq, ..) => Some(Some(Bool(left != right))),
+        (Operator::Lt, Int(_) | Float(_), _) => Some(Some(Bool(left < right))),
+        (Operator::Lte, Int(_) | Float(_), _) => Some(Some(Bool(left <= right))),
+        (Operator::Gt, Int(_) | Float(_), _) => Some(Some(Bool(left > right))),
+        (Operator::Gte, Int(_) | Float(_), _) => Some(Some(Bool(left >= right))),
+        (Operator::And, Bool(left), Bool(right)) => Some(Some(Bool(*left && *right))),
+        (Operator::Or, Bool(left), Bool(right)) => Some(Some(Bool(*left || *right))),
+        (Operator::Add, Str(left), Str(right)) => Some(Some(Str(format!("{}{}", left, right)))),
+        (Operator::Div | Operator::Mod, Int(_), Int(0)) => {
+            return fail(ErrorType::DivisionByZero, span, errors)
+        }
+        (_, Int(left), Int(right)) => integer(operator, *left, *right).map(|value| value.map(Int)),
+        (_, Float(left), Float(right)) => {
+            float(operator, *left, *right).map(|value| Some(Float(value)))
+        }
+        _ => None,
+    };
+
+    match value {
+        Some(Some(value)) => Some(value),
+        Some(None) => fail(ErrorType::ConstantOverflow, span, errors),
+        None => fail(
+            ErrorType::NonOperatorType(format!("{:?}", left.typ()), format!("{:?}", operator)),
+            span,
+            errors,
+        ),
+    }
+}
+
+fn integer(operator: &Operator, left: i64, right: i64) -> Option<Option<i64>> {
+    let shift = || u32::try_from(right).ok().filter(|shift| *shift < 64);
+    Some(match operator {
+        Operator::Add => left.checked_add(right),
+        Operator::Sub => left.checked_sub(right),
+        Operator::Mul => left.checked_mul(right),
+        Operator::Div => left.checked_div(right),
+        Operator::Mod => left.checked_rem(right),
+        // A negative exponent gives 0, the same as at runtime
+        Operator::Power if right < 0 => Some(0),
+        Operator::Power => u32::try_from(right)
+            .ok()
+            .and_then(|right| left.checked_pow(right)),
+        Operator::Shl => shift().map(|shift| left << shift),
+        Operator::Shr => shift().map(|shift| left >> shift),
+        _ => return None,
+    })
+}
+
+fn float(operator: &Operator, left: f64, right: f64) -> Option<f64> {
+    Some(match operator {
+        Operator::Add => left + right,
+        Operator::Sub => left - right,
+        Operator::Mul => left * right,
+        Operator::Div => left / right,
+        Operator::Mod => left % right,
+        Operator::Power => left.powf(right),
+        _ => return None,
+    })
+}
+
+fn fail(error: ErrorType, span: &Span, errors: &mut Vec<AzulaError>) -> Option<ConstValue> {
+    errors.push(AzulaError::new(error, span.start, span.end));
+    None
+}
@@ -0,0 +1,473 @@
+use std::{collections::HashMap, rc::Rc};
+
+use azula_ast::prelude::*;
+use azula_type::prelude::AzulaType;
+
+pub(crate) type Bindings<'a> = HashMap<&'a str, AzulaType<'a>>;
+// The generic struct and type arguments behind each struct instance
+pub(crate) type StructInstances<'a> = HashMap<String, (String, Vec<AzulaType<'a>>)>;
+
+/// Matches a param type against the type of its argument, binding any type
+/// parameters it mentions. Fails with the type parameter and its two types if
+/// it was already bound to something else
+pub(crate) fn bind<'a>(
+    param: &AzulaType<'a>,
+    arg: &AzulaType<'a>,
+    generics: &[&'a str],
+    instances: &StructInstances<'a>,
+    bindings: &mut Bindings<'a>,
+) -> Result<(), (String, AzulaType<'a>, AzulaType<'a>)> {
+    match (param, arg) {
+        (AzulaType::Named(name), _) if generics.contains(&name.as_str()) => {
+            let generic = *generics.iter().find(|generic| **generic == name).unwrap();
+            match bindings.get(generic) {
+                Some(bound) if bound != arg => Err((name.clone(), bound.clone(), arg.clone())),
+                Some(_) => Ok(()),
+                None => {
+                    bindings.insert(generic, arg.clone());
+                    Ok(())
+                }
+            }
+        }
+        (AzulaType::Pointer(param), AzulaType::Pointer(arg))
+        | (AzulaType::Array(param, _), AzulaType::Array(arg, _))
+        | (AzulaType::Optional(param), AzulaType::Optional(arg))
+        | (AzulaType::Result(param), AzulaType::Result(arg)) => {
+            bind(param, arg, generics, instances, bindings)
+        }
+        (AzulaType::Tuple(params), AzulaType::Tuple(args)) => {
+            for (param, arg) in params.iter().zip(args) {
+                bind(param, arg, generics, instances, bindings)?;
+            }
+            Ok(())
+        }
+        (AzulaType::Function(params, param_returns), AzulaType::Function(args, arg_returns)) => {
+            for (param, arg) in params.iter().zip(args) {
+                bind(param, arg, generics, instances, bindings)?;
+            }
+            bind(param_returns, arg_returns, generics, instances, bindings)
+        }
+        // Pair<T, int> against one of Pair's instances
+        (AzulaType::Generic(name, params), AzulaType::Named(instance)) => {
+            match instances.get(instance) {
+                Some((generic, args)) if generic == name => {
+                    for (param, arg) in params.iter().zip(args) {
+                        bind(param, arg, generics, instances, bindings)?;
+                    }
+                    Ok(())
+                }
+                _ => Ok(()),
+            }
+        }
+        // []T against an instance of Slice
+        (AzulaType::Slice(param), _) => bind(
+            &AzulaType::Generic("Slice".to_string(), vec![param.as_ref().clone()]),
+            arg,
+            generics,
+            instances,
+            bindings,
+        ),
+        // Anything else is left for the call to report once it's substituted
+        _ => Ok(()),
+    }
+}
+
+/// A name for an instantiation that is unique to its type arguments, e.g.
+/// max.int for max<T> with T = int
+pub(crate) fn mangle(name: &str, types: &[AzulaType]) -> String {
+    let types: Vec<_> = types.iter().map(AzulaType::mangled).collect();
+    format!("{}.{}", name, types.join("."))
+}
+
+/// Rebuilds a type from the bottom up, letting f replace each part after
+/// its own parts have been
+pub(crate) fn map_type<'a>(
+    typ: &AzulaType<'a>,
+    f: &mut dyn FnMut(AzulaType<'a>) -> AzulaType<'a>,
+) -> AzulaType<'a> {
+    let rebuilt = match typ {
+        AzulaType::Pointer(inner) => AzulaType::Pointer(Rc::new(map_type(inner, f))),
+        AzulaType::Array(inner, size) => AzulaType::Array(Rc::new(map_type(inner, f)), *size),
+        AzulaType::ConstArray(inner, size) => {
+            AzulaType::ConstArray(Rc::new(map_type(inner, f)), size.clone())
+        }
+        AzulaType::Slice(inner) => AzulaType::Slice(Rc::new(map_type(inner, f))),
+        AzulaType::Optional(inner) => AzulaType::Optional(Rc::new(map_type(inner, f))),
+        AzulaType::Result(inner) => AzulaType::Result(Rc::new(map_type(inner, f))),
+        AzulaType::Tuple(types) => {
+            AzulaType::Tuple(types.iter().map(|typ| map_type(typ, f)).collect())
+        }
+        AzulaType::Function(params, returns) => AzulaType::Function(
+            params.iter().map(|typ| map_type(typ, f)).collect(),
+            Rc::new(map_type(returns, f)),
+        ),
+        AzulaType::Generic(name, args) => AzulaType::Generic(
+            name.clone(),
+            args.iter().map(|typ| map_type(typ, f)).collect(),
+        ),
+        _ => typ.clone(),
+    };
+
+    f(rebuilt)
+}
+
+pub(crate) fn substitute_type<'a>(typ: &AzulaType<'a>, bindings: &Bindings<'a>) -> AzulaType<'a> {
+    map_type(typ, &mut |typ| match &typ {
+        AzulaType::Named(name) => bindings.get(name.as_str()).cloned().unwrap_or(typ),
+        _ => typ,
+    })
+}
+
+pub(crate) fn substitute_statement<'a>(
+    stmt: &Statement<'a>,
+    bindings: &Bindings<'a>,
+) -> Statement<'a> {
+    map_statement_types(stmt, &mut |typ| substitute_type(typ, bindings))
+}
+
+pub(crate) type TypeMap<'f, 'a> = &'f mut dyn FnMut(&AzulaType<'a>) -> AzulaType<'a>;
+
+/// Rewrites every type written in a statement, which is how type parameters
+/// are substituted and generic struct types resolved
+pub(crate) fn map_statement_types<'a>(stmt: &Statement<'a>, f: TypeMap<'_, 'a>) -> Statement<'a> {
+    match stmt {
+        Statement::Root(stmts) => Statement::Root(map_body_types(stmts, f)),
+        Statement::Block(stmts) => Statement::Block(map_body_types(stmts, f)),
+        Statement::Scope(stmts, span) => Statement::Scope(map_body_types(stmts, f), span.clone()),
+        Statement::Function {
+            name,
+            generics,
+            bounds,
+            args,
+            arg_spans,
+            defaults,
+            variadic,
+            returns,
+            body,
+            public,
+            attributes,
+            doc,
+            span,
+        } => Statement::Function {
+            name,
+            generics: generics.clone(),
+            bounds: bounds.clone(),
+            args: args.iter().map(|(typ, name)| (f(typ), *name)).collect(),
+            arg_spans: arg_spans.clone(),
+            defaults: defaults
+                .iter()
+                .map(|default| map_expression_types(default, f))
+                .collect(),
+            variadic: *variadic,
+            returns: f(returns),
+            body: Rc::new(map_statement_types(body, f)),
+            public: *public,
+            attributes: attributes.clone(),
+            doc: doc.clone(),
+            span: span.clone(),
+        },
+        Statement::ExternFunction {
+            name,
+            varargs,
+            args,
+            returns,
+            span,
+        } => Statement::ExternFunction {
+            name,
+            varargs: *varargs,
+            args: args.iter().map(|(typ, name)| (f(typ), *name)).collect(),
+            returns: f(returns),
+            span: span.clone(),
+        },
+        Statement::Struct {
+            name,
+            generics,
+            attributes,
+            defaults,
+            annotations,
+            doc,
+            span,
+        } => Statement::Struct {
+            name,
+            generics: generics.clone(),
+            annotations: annotations.clone(),
+            attributes: attributes
+                .iter()
+                .map(|(typ, name)| (f(typ), *name))
+                .collect(),
+            defaults: defaults
+                .iter()
+                .map(|(name, default)| (*name, map_expression_types(default, f)))
+                .collect(),
+            doc: doc.clone(),
+            span: span.clone(),
+        },
+        Statement::Return(value, span) => Statement::Return(
+            value.as_ref().map(|value| map_expression_types(value, f)),
+            span.clone(),
+        ),
+        Statement::Assign(mutable, name, annotation, value, attributes, span) => Statement::Assign(
+            *mutable,
+            name.clone(),
+            annotation.as_ref().map(|typ| f(typ)),
+            map_expression_types(value, f),
+            attributes.clone(),
+            span.clone(),
+        ),
+        Statement::ExpressionStatement(value, span) => {
+            Statement::ExpressionStatement(map_expression_types(value, f), span.clone())
+        }
+        Statement::If(cond, stmts, span) => Statement::If(
+            map_expression_types(cond, f),
+            map_body_types(stmts, f),
+            span.clone(),
+        ),
+        Statement::While(cond, stmts, label, span) => Statement::While(
+            map_expression_types(cond, f),
+            map_body_types(stmts, f),
+            *label,
+            span.clone(),
+        ),
+        Statement::DoWhile(cond, stmts, label, span) => Statement::DoWhile(
+            map_expression_types(cond, f),
+            map_body_types(stmts, f),
+            *label,
+            span.clone(),
+        ),
+        Statement::For(index, name, iterable, stmts, label, span) => Statement::For(
+            *index,
+            name,
+            map_expression_types(iterable, f),
+            map_body_types(stmts, f),
+            *label,
+            span.clone(),
+        ),
+        Statement::Reassign(target, value, span) => Statement::Reassign(
+            map_expression_types(target, f),
+            map_expression_types(value, f),
+            span.clone(),
+        ),
+        Statement::Match(value, arms, span) => Statement::Match(
+            map_expression_types(value, f),
+            arms.iter()
+                .map(|(typ, binding, stmts, arm)| {
+                    (
+                        typ.as_ref().map(|typ| f(typ)),
+                        *binding,
+                        map_body_types(stmts, f),
+                        arm.clone(),
+                    )
+                })
+                .collect(),
+            span.clone(),
+        ),
+        Statement::Destructure(mutable, names, value, span) => Statement::Destructure(
+            *mutable,
+            names.clone(),
+            map_expression_types(value, f),
+            span.clone(),
+        ),
+        Statement::IfLet(mutable, name, value, stmts, span) => Statement::IfLet(
+            *mutable,
+            name,
+            map_expression_types(value, f),
+            map_body_types(stmts, f),
+            span.clone(),
+        ),
+        Statement::Defer(value, span) => {
+            Statement::Defer(map_expression_types(value, f), span.clone())
+        }
+        Statement::Break(..) | Statement::Continue(..) => stmt.clone(),
+        Statement::Interface {
+            name,
+            methods,
+            span,
+        } => Statement::Interface {
+            name,
+            methods: methods
+                .iter()
+                .map(|(method, args, returns)| {
+                    (
+                        *method,
+                        args.iter().map(|(typ, name)| (f(typ), *name)).collect(),
+                        f(returns),
+                    )
+                })
+                .collect(),
+            span: span.clone(),
+        },
+        Statement::Impl {
+            interface,
+            typ,
+            methods,
+            span,
+        } => Statement::Impl {
+            interface,
+            typ: f(typ),
+            methods: map_body_types(methods, f),
+            span: span.clone(),
+        },
+        Statement::Import(name, body, span) => {
+            Statement::Import(name, map_body_types(body, f), span.clone())
+        }
+        Statement::Conditional(condition, declaration, span) => Statement::Conditional(
+            condition.clone(),
+            Rc::new(map_statement_types(declaration, f)),
+            span.clone(),
+        ),
+    }
+}
+
+fn map_body_types<'a>(body: &[Statement<'a>], f: TypeMap<'_, 'a>) -> Vec<Statement<'a>> {
+    body.iter()
+        .map(|stmt| map_statement_types(stmt, f))
+        .collect()
+}
+
+fn map_expression_types<'a>(expr: &ExpressionNode<'a>, f: TypeMap<'_, 'a>) -> ExpressionNode<'a> {
+    let mut boxed = |expr: &Rc<ExpressionNode<'a>>| Rc::new(map_expression_types(expr, f));
+
+    let expression = match &expr.expression {
+        Expression::Infix(left, op, right) => {
+            let left = boxed(left);
+            Expression::Infix(left, op.clone(), boxed(right))
+        }
+        Expression::FunctionCall { function, args } => Expression::FunctionCall {
+            function: boxed(function),
+            args: args
+                .iter()
+                .map(|arg| map_expression_types(arg, f))
+                .collect(),
+        },
+        Expression::Not(value) => Expression::Not(boxed(value)),
+        Expression::Negate(value) => Expression::Negate(boxed(value)),
+        Expression::Pointer(value) => Expression::Pointer(boxed(value)),
+        Expression::Deref(value) => Expression::Deref(boxed(value)),
+        Expression::Array(values) => Expression::Array(
+            values
+                .iter()
+                .map(|value| map_expression_types(value, f))
+                .collect(),
+        ),
+        Expression::ArrayAccess(array, index) => {
+            let array = boxed(array);
+            Expression::ArrayAccess(array, boxed(index))
+        }
+        Expression::Range(start, end) => {
+            let start = boxed(start);
+            Expression::Range(start, boxed(end))
+        }
+        Expression::ArrayRepeat(value, count) => {
+            let value = boxed(value);
+            Expression::ArrayRepeat(value, boxed(count))
+        }
+        Expression::StructInitialisation(name, values) => Expression::StructInitialisation(
+            name.clone(),
+            values
+                .iter()
+                .map(|(name, value)| (*name, map_expression_types(value, f)))
+                .collect(),
+        ),
+        Expression::StructAccess(value, member) => {
+            Expression::StructAccess(boxed(value), member.clone())
+        }
+        Expression::Cast(value, typ) => {
+            let value = boxed(value);
+            Expression::Cast(value, f(typ))
+        }
+        Expression::SizeOf(typ) => Expression::SizeOf(f(typ)),
+        Expression::New(typ) => Expression::New(f(typ)),
+        Expression::Ternary(cond, then, otherwise) => {
+            let cond = boxed(cond);
+            let then = boxed(then);
+            Expression::Ternary(cond, then, boxed(otherwise))
+        }
+        Expression::Tuple(values) => Expression::Tuple(
+            values
+                .iter()
+                .map(|value| map_expression_types(value, f))
+                .collect(),
+        ),
+        Expression::Try(value, typ) => {
+            let value = boxed(value);
+            Expression::Try(value, f(typ))
+        }
+        Expression::NamedArgument(name, value) => Expression::NamedArgument(name, boxed(value)),
+        Expression::Closure {
+            args,
+            body,
+            captures,
+        } => {
+            let body = boxed(body);
+            Expression::Closure {
+                args: args.iter().map(|(typ, name)| (f(typ), *name)).collect(),
+                body,
+                captures: captures.clone(),
+            }
+        }
+        Expression::Integer(_)
+        | Expression::Float(_)
+        | Expression::Identifier(_)
+        | Expression::Boolean(_)
+        | Expression::String(_) => expr.expression.clone(),
+    };
+
+    ExpressionNode {
+        expression,
+        typed: f(&expr.typed),
+        span: expr.span.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bind_and_mangle() {
+        let named = |name: &str| AzulaType::Named(name.to_string());
+        let mut bindings = Bindings::new();
+
+        let param = AzulaType::Tuple(vec![
+            AzulaType::Pointer(Rc::new(named("T"))),
+            AzulaType::Array(Rc::new(named("U")), None),
+        ]);
+        let arg = AzulaType::Tuple(vec![
+            AzulaType::Pointer(Rc::new(AzulaType::Str)),
+            AzulaType::Array(Rc::new(AzulaType::SizedFloat(32)), Some(3)),
+        ]);
+        let instances = StructInstances::new();
+        assert_eq!(
+            bind(&param, &arg, &["T", "U"], &instances, &mut bindings),
+            Ok(())
+        );
+        assert_eq!(
+            substitute_type(&param, &bindings),
+            AzulaType::Tuple(vec![
+                AzulaType::Pointer(Rc::new(AzulaType::Str)),
+                AzulaType::Array(Rc::new(AzulaType::SizedFloat(32)), None),
+            ])
+        );
+        assert_eq!(
+            bind(
+                &named("T"),
+                &AzulaType::Int,
+                &["T", "U"],
+                &instances,
+                &mut bindings
+            ),
+            Err(("T".to_string(), AzulaType::Str, AzulaType::Int))
+        );
+
+        assert_eq!(
+            mangle(
+                "pair",
+                &[
+                    AzulaType::Pointer(Rc::new(AzulaType::Str)),
+                    AzulaType::SizedFloat(32)
+                ]
+            ),
+            "pair.ptr_str.f32"
+        );
+    }
+}
@@ -1,8 +1,16 @@
 #[macro_use]
 extern crate maplit;
 
+mod consts;
+mod generics;
+mod query;
+mod refactor;
+mod trace;
 mod typecheck;
 
 pub mod prelude {
+    pub use crate::query::{doc_at, type_at};
+    pub use crate::refactor::{extract_function, inline_variable};
+    pub use crate::trace::trace_execution;
     pub use crate::typecheck::Typechecker;
 }
@@ -0,0 +1,262 @@
+use azula_ast::prelude::*;
+use azula_type::prelude::AzulaType;
+
+/// Looks up the type of the innermost expression covering the offset in a
+/// typechecked tree, for editors showing the type under the cursor
+pub fn type_at<'a>(ast: &Statement<'a>, offset: usize) -> Option<AzulaType<'a>> {
+    statement_expression_at(ast, offset).map(|expr| expr.typed.clone())
+}
+
+/// Looks up the docs of the function the expression at the offset names, or
+/// else of the struct it's typed as, for editors to show with the type on
+/// hover
+pub fn doc_at(ast: &Statement, offset: usize) -> Option<String> {
+    let expr = statement_expression_at(ast, offset)?;
+    let declarations = match ast {
+        Statement::Root(body) => body,
+        _ => return None,
+    };
+
+    if let Expression::Identifier(function) = &expr.expression {
+        let doc = declarations.iter().find_map(|stmt| match stmt {
+            Statement::Function { name, doc, .. } if name == function => Some(doc),
+            _ => None,
+        });
+        if let Some(doc) = doc {
+            return doc.clone();
+        }
+    }
+
+    match &expr.typed {
+        AzulaType::Named(struc) => declarations.iter().find_map(|stmt| match stmt {
+            Statement::Struct { name, doc, .. } if name == struc => doc.clone(),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+fn statement_expression_at<'a, 'b>(
+    stmt: &'b Statement<'a>,
+    offset: usize,
+) -> Option<&'b ExpressionNode<'a>> {
+    if let Some(span) = stmt.span() {
+        if !contains(&span, offset) {
+            return None;
+        }
+    }
+
+    match stmt {
+        Statement::Root(body) | Statement::Block(body) => body_expression_at(body, offset),
+        Statement::Function { defaults, body, .. } => defaults
+            .iter()
+            .find_map(|default| expression_at(default, offset))
+            .or_else(|| statement_expression_at(body, offset)),
+        Statement::Return(value, _) => value
+            .as_ref()
+            .and_then(|value| expression_at(value, offset)),
+        Statement::Assign(_, _, _, value, _, _)
+        | Statement::ExpressionStatement(value, _)
+        | Statement::Destructure(_, _, value, _)
+        | Statement::Defer(value, _) => expression_at(value, offset),
+        Statement::If(cond, body, _)
+        | Statement::While(cond, body, ..)
+        | Statement::DoWhile(cond, body, ..)
+        | Statement::For(_, _, cond, body, ..)
+        | Statement::IfLet(_, _, cond, body, _) => {
+            expression_at(cond, offset).or_else(|| body_expression_at(body, offset))
+        }
+        Statement::Reassign(target, value, _) => {
+            expression_at(target, offset).or_else(|| expression_at(value, offset))
+        }
+        Statement::Match(value, arms, _) => expression_at(value, offset).or_else(|| {
+            arms.iter()
+                .find_map(|(_, _, body, _)| body_expression_at(body, offset))
+        }),
+        Statement::Impl { methods, .. }
+        | Statement::Import(_, methods, _)
+        | Statement::Scope(methods, _) => body_expression_at(methods, offset),
+        Statement::Conditional(_, declaration, _) => statement_expression_at(declaration, offset),
+        Statement::ExternFunction { .. }
+        | Statement::Struct { .. }
+        | Statement::Interface { .. }
+        | Statement::Break(..)
+        | Statement::Continue(..) => None,
+    }
+}
+
+fn body_expression_at<'a, 'b>(
+    body: &'b [Statement<'a>],
+    offset: usize,
+) -> Option<&'b ExpressionNode<'a>> {
+    body.iter()
+        .find_map(|stmt| statement_expression_at(stmt, offset))
+}
+
+fn expression_at<'a, 'b>(
+    expr: &'b ExpressionNode<'a>,
+    offset: usize,
+) -> Option<&'b ExpressionNode<'a>> {
+    if !contains(&expr.span, offset) {
+        return None;
+    }
+
+    let inner = match &expr.expression {
+        Expression::Infix(left, _, right)
+        | Expression::ArrayAccess(left, right)
+        | Expression::ArrayRepeat(left, right)
+        | Expression::Range(left, right) => {
+            expression_at(left, offset).or_else(|| expression_at(right, offset))
+        }
+        Expression::FunctionCall { function, args } => expression_at(function, offset)
+            .or_else(|| args.iter().find_map(|arg| expression_at(arg, offset))),
+        Expression::Not(value)
+        | Expression::Negate(value)
+        | Expression::Pointer(value)
+        | Expression::Deref(value)
+        | Expression::Cast(value, _)
+        | Expression::Try(value, _)
+        | Expression::NamedArgument(_, value)
+        | Expression::Closure { body: value, .. } => expression_at(value, offset),
+        Expression::Array(values) | Expression::Tuple(values) => {
+            values.iter().find_map(|value| expression_at(value, offset))
+        }
+        Expression::StructInitialisation(_, values) => values
+            .iter()
+            .find_map(|(_, value)| expression_at(value, offset)),
+        // The member has no type of its own, the access covers it
+        Expression::StructAccess(value, _) => expression_at(value, offset),
+        Expression::Ternary(cond, then, otherwise) => expression_at(cond, offset)
+            .or_else(|| expression_at(then, offset))
+            .or_else(|| expression_at(otherwise, offset)),
+        Expression::Integer(_)
+        | Expression::Float(_)
+        | Expression::Identifier(_)
+        | Expression::Boolean(_)
+        | Expression::String(_)
+        | Expression::SizeOf(_)
+        | Expression::New(_) => None,
+    };
+
+    inner.or(Some(expr))
+}
+
+fn contains(span: &Span, offset: usize) -> bool {
+    span.start <= offset && offset < span.end
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_type_at() {
+        let node = |expression, start, end| ExpressionNode {
+            expression,
+            typed: AzulaType::Infer,
+            span: Span { start, end },
+        };
+
+        // func main() {
+        //     var xs = [1, 2];
+        //     var y = xs[0] > 1;
+        // }
+        let root = Statement::Root(vec![Statement::Function {
+            name: "main",
+            generics: vec![],
+            bounds: vec![],
+            args: vec![],
+            arg_spans: vec![],
+            defaults: vec![],
+            variadic: false,
+            returns: AzulaType::Void,
+            body: Rc::new(Statement::Block(vec![
+                Statement::Assign(
+                    true,
+                    "xs".to_string(),
+                    None,
+                    node(
+                        Expression::Array(vec![
+                            node(Expression::Integer(1), 28, 29),
+                            node(Expression::Integer(2), 31, 32),
+                        ]),
+                        27,
+                        33,
+                    ),
+                    vec![],
+                    Span { start: 18, end: 34 },
+                ),
+                Statement::Assign(
+                    true,
+                    "y".to_string(),
+                    None,
+                    node(
+                        Expression::Infix(
+                            Rc::new(node(
+                                Expression::ArrayAccess(
+                                    Rc::new(node(Expression::Identifier("xs".to_string()), 47, 49)),
+                                    Rc::new(node(Expression::Integer(0), 50, 51)),
+                                ),
+                                47,
+                                52,
+                            )),
+                            Operator::Gt,
+                            Rc::new(node(Expression::Integer(1), 55, 56)),
+                        ),
+                        47,
+                        56,
+                    ),
+                    vec![],
+                    Span { start: 39, end: 57 },
+                ),
+            ])),
+            public: false,
+            attributes: vec![],
+            doc: None,
+            span: Span { start: 0, end: 59 },
+        }]);
+
+        let mut typechecker = Typechecker::new(root);
+        let ast = typechecker.typecheck().unwrap();
+
+        assert_eq!(type_at(&ast, 28), Some(AzulaType::Int));
+        assert_eq!(
+            type_at(&ast, 30),
+            Some(AzulaType::Array(Rc::new(AzulaType::Int), Some(2)))
+        );
+        assert_eq!(
+            type_at(&ast, 48),
+            Some(AzulaType::Array(Rc::new(AzulaType::Int), Some(2)))
+        );
+        assert_eq!(type_at(&ast, 51), Some(AzulaType::Int));
+        assert_eq!(type_at(&ast, 53), Some(AzulaType::Bool));
+        assert_eq!(type_at(&ast, 40), None);
+    }
+
+    #[test]
+    fn test_doc_at() {
+        let source = "/// A point
+struct Point { x: int }
+/// Makes a point
+func origin(): Point { return Point { x: 0 }; }
+func main() {
+    var p = origin();
+    var x = p.x;
+}";
+        let lexer = azula_parser::prelude::Lexer::new(source);
+        let mut parser = azula_parser::prelude::Parser::new(source, lexer);
+        let mut typechecker = Typechecker::new(parser.parse());
+        let ast = typechecker.typecheck().unwrap();
+
+        let offset = |text: &str| source.find(text).unwrap();
+        assert_eq!(
+            doc_at(&ast, offset("origin();")),
+            Some("Makes a point".to_string())
+        );
+        assert_eq!(doc_at(&ast, offset("p.x")), Some("A point".to_string()));
+        assert_eq!(doc_at(&ast, offset("x;")), None);
+    }
+}
@@ -0,0 +1,762 @@
+use std::{collections::HashSet, rc::Rc};
+
+use azula_ast::prelude::*;
+use azula_type::prelude::AzulaType;
+
+use crate::query::type_at;
+
+/// Replaces the uses of the variable declared at the offset with the value
+/// it was declared with, and removes the declaration
+pub fn inline_variable<'a>(ast: &Statement<'a>, offset: usize) -> Result<Statement<'a>, String> {
+    let mut ast = ast.clone();
+    if inline_in_statement(&mut ast, offset)? {
+        Ok(ast)
+    } else {
+        Err("No variable is declared there".to_string())
+    }
+}
+
+/// Moves the statements between start and end into a new function called
+/// name. The variables they use from the enclosing function become its
+/// parameters, typed from the typechecked tree
+pub fn extract_function<'a>(
+    source: &'a str,
+    ast: &Statement<'a>,
+    typed: &Statement<'a>,
+    start: usize,
+    end: usize,
+    name: &'a str,
+) -> Result<Statement<'a>, String> {
+    let mut body = match ast {
+        Statement::Root(body) => body.clone(),
+        _ => return Err("Not a root node".to_string()),
+    };
+
+    let index = body
+        .iter()
+        .position(|stmt| match stmt {
+            Statement::Function { span, .. } => span.start <= start && end <= span.end,
+            _ => false,
+        })
+        .ok_or_else(|| "The selection is not inside a function".to_string())?;
+
+    let extracted = match &mut body[index] {
+        Statement::Function {
+            args,
+            body: function_body,
+            ..
+        } => {
+            // Everything declared in the function, the selection can only
+            // take parameters for these
+            let mut locals = Uses::default();
+            locals.statement(function_body, &mut HashSet::new());
+            let mut locals = locals.declarations;
+            locals.extend(args.iter().map(|(_, name)| name.to_string()));
+
+            let mut extraction = Extraction {
+                source,
+                typed,
+                start,
+                end,
+                name,
+                locals,
+                function: None,
+            };
+            extraction.statement(Rc::make_mut(function_body))?;
+            extraction.function
+        }
+        _ => unreachable!(),
+    };
+
+    match extracted {
+        Some(function) => {
+            body.insert(index + 1, function);
+            Ok(Statement::Root(body))
+        }
+        None => Err("No statements are selected".to_string()),
+    }
+}
+
+fn inline_in_statement(stmt: &mut Statement<'_>, offset: usize) -> Result<bool, String> {
+    match stmt {
+        Statement::Root(body)
+        | Statement::Block(body)
+        | Statement::If(_, body, _)
+        | Statement::While(_, body, ..)
+        | Statement::DoWhile(_, body, ..)
+        | Statement::For(_, _, _, body, ..)
+        | Statement::Scope(body, _)
+        | Statement::IfLet(_, _, _, body, _) => inline_in_body(body, offset),
+        Statement::Function { body, .. } => inline_in_statement(Rc::make_mut(body), offset),
+        Statement::Match(_, arms, _) => {
+            for (_, _, body, _) in arms {
+                if inline_in_body(body, offset)? {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        }
+        _ => Ok(false),
+    }
+}
+
+fn inline_in_body(body: &mut Vec<Statement>, offset: usize) -> Result<bool, String> {
+    for index in 0..body.len() {
+        if let Statement::Assign(_, name, _, value, _, span) = &body[index] {
+            if span.start <= offset && offset < span.end {
+                let (name, value) = (name.clone(), value.clone());
+                check_inline(&body[index + 1..], &name, &value)?;
+                body.remove(index);
+                inline_body(&mut body[index..], &name, &value)?;
+                return Ok(true);
+            }
+        }
+
+        if inline_in_statement(&mut body[index], offset)? {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+// The value is worked out where it's used instead of where it's declared,
+// which has to give the same result
+fn check_inline(body: &[Statement], name: &str, value: &ExpressionNode) -> Result<(), String> {
+    let mut after = Uses::default();
+    after.body(body, &mut HashSet::new());
+    let uses: Vec<_> = after.uses.iter().filter(|(used, _)| used == name).collect();
+
+    if has_side_effects(value) && (uses.len() != 1 || after.repeated.contains(name)) {
+        return Err(format!(
+            "The value of {} has side effects and isn't used exactly once",
+            name
+        ));
+    }
+
+    let mut reads = Uses::default();
+    reads.expression(value, &HashSet::new());
+    if let Some((_, last)) = uses.last() {
+        for (changed, at) in &after.reassigned {
+            if *at < last.start && reads.uses.iter().any(|(read, _)| read == changed) {
+                return Err(format!("{} is reassigned before {} is used", changed, name));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn has_side_effects(expr: &ExpressionNode) -> bool {
+    match &expr.expression {
+        Expression::FunctionCall { .. } | Expression::Try(..) | Expression::New(_) => true,
+        Expression::Infix(left, _, right)
+        | Expression::ArrayAccess(left, right)
+        | Expression::ArrayRepeat(left, right)
+        | Expression::Range(left, right) => has_side_effects(left) || has_side_effects(right),
+        Expression::Array(values) | Expression::Tuple(values) => {
+            values.iter().any(has_side_effects)
+        }
+        Expression::StructInitialisation(_, values) => {
+            values.iter().any(|(_, value)| has_side_effects(value))
+        }
+        Expression::Not(inner)
+        | Expression::Negate(inner)
+        | Expression::Pointer(inner)
+        | Expression::Deref(inner)
+        | Expression::Cast(inner, _)
+        | Expression::NamedArgument(_, inner)
+        | Expression::StructAccess(inner, _) => has_side_effects(inner),
+        Expression::Ternary(cond, then, otherwise) => {
+            has_side_effects(cond) || has_side_effects(then) || has_side_effects(otherwise)
+        }
+        // Making a closure doesn't run it
+        Expression::Closure { .. }
+        | Expression::Identifier(_)
+        | Expression::Integer(_)
+        | Expression::Float(_)
+        | Expression::Boolean(_)
+        | Expression::String(_)
+        | Expression::SizeOf(_) => false,
+    }
+}
+
+// Stops once the variable is shadowed, the statements after that use the
+// new one
+fn inline_body<'a>(
+    body: &mut [Statement<'a>],
+    name: &str,
+    value: &ExpressionNode<'a>,
+) -> Result<(), String> {
+    for stmt in body {
+        if !inline_statement(stmt, name, value)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn inline_statement<'a>(
+    stmt: &mut Statement<'a>,
+    name: &str,
+    value: &ExpressionNode<'a>,
+) -> Result<bool, String> {
+    match stmt {
+        Statement::Assign(_, declared, _, expr, _, _) => {
+            inline_expression(expr, name, value)?;
+            return Ok(declared != name);
+        }
+        Statement::Destructure(_, declared, expr, _) => {
+            inline_expression(expr, name, value)?;
+            return Ok(!declared.contains(&name));
+        }
+        Statement::Reassign(target, expr, _) => {
+            if assigned_variable(target) == Some(name) {
+                return Err(format!("{} is reassigned", name));
+            }
+            inline_expression(target, name, value)?;
+            inline_expression(expr, name, value)?;
+        }
+        Statement::ExpressionStatement(expr, _) | Statement::Defer(expr, _) => {
+            inline_expression(expr, name, value)?
+        }
+        Statement::Return(expr, _) => {
+            if let Some(expr) = expr {
+                inline_expression(expr, name, value)?;
+            }
+        }
+        Statement::If(cond, body, _)
+        | Statement::While(cond, body, ..)
+        | Statement::DoWhile(cond, body, ..) => {
+            inline_expression(cond, name, value)?;
+            inline_body(body, name, value)?;
+        }
+        Statement::IfLet(_, binding, expr, body, _) => {
+            inline_expression(expr, name, value)?;
+            if *binding != name {
+                inline_body(body, name, value)?;
+            }
+        }
+        Statement::For(index, binding, expr, body, ..) => {
+            inline_expression(expr, name, value)?;
+            if *binding != name && *index != Some(name) {
+                inline_body(body, name, value)?;
+            }
+        }
+        Statement::Match(expr, arms, _) => {
+            inline_expression(expr, name, value)?;
+            for (_, binding, body, _) in arms {
+                if *binding != Some(name) {
+                    inline_body(body, name, value)?;
+                }
+            }
+        }
+        Statement::Block(body) | Statement::Scope(body, _) => inline_body(body, name, value)?,
+        Statement::Break(..) | Statement::Continue(..) => {}
+        Statement::Root(_)
+        | Statement::Function { .. }
+        | Statement::ExternFunction { .. }
+        | Statement::Struct { .. }
+        | Statement::Interface { .. }
+        | Statement::Impl { .. }
+        | Statement::Import(..)
+        | Statement::Conditional(..) => {}
+    }
+
+    Ok(true)
+}
+
+fn inline_expression<'a>(
+    expr: &mut ExpressionNode<'a>,
+    name: &str,
+    value: &ExpressionNode<'a>,
+) -> Result<(), String> {
+    let inline =
+        |expr: &mut Rc<ExpressionNode<'a>>| inline_expression(Rc::make_mut(expr), name, value);
+
+    match &mut expr.expression {
+        Expression::Identifier(ident) if ident == name => *expr = value.clone(),
+        Expression::Pointer(inner) => {
+            if matches!(&inner.expression, Expression::Identifier(ident) if ident == name) {
+                return Err(format!("The address of {} is taken", name));
+            }
+            inline(inner)?;
+        }
+        Expression::Infix(left, _, right)
+        | Expression::ArrayAccess(left, right)
+        | Expression::ArrayRepeat(left, right)
+        | Expression::Range(left, right) => {
+            inline(left)?;
+            inline(right)?;
+        }
+        Expression::FunctionCall { args, .. }
+        | Expression::Array(args)
+        | Expression::Tuple(args) => {
+            for arg in args {
+                inline_expression(arg, name, value)?;
+            }
+        }
+        Expression::StructInitialisation(_, values) => {
+            for (_, field) in values {
+                inline_expression(field, name, value)?;
+            }
+        }
+        Expression::Not(inner)
+        | Expression::Negate(inner)
+        | Expression::Deref(inner)
+        | Expression::Cast(inner, _)
+        | Expression::Try(inner, _)
+        | Expression::NamedArgument(_, inner)
+        | Expression::StructAccess(inner, _) => inline(inner)?,
+        Expression::Ternary(cond, then, otherwise) => {
+            inline(cond)?;
+            inline(then)?;
+            inline(otherwise)?;
+        }
+        Expression::Closure { args, body, .. } => {
+            if !args.iter().any(|(_, arg)| *arg == name) {
+                inline(body)?;
+            }
+        }
+        Expression::Identifier(_)
+        | Expression::Integer(_)
+        | Expression::Float(_)
+        | Expression::Boolean(_)
+        | Expression::String(_)
+        | Expression::SizeOf(_)
+        | Expression::New(_) => {}
+    }
+
+    Ok(())
+}
+
+// The variable a reassignment writes to, `a` for `a.b[0] = 1`
+fn assigned_variable<'e>(target: &'e ExpressionNode) -> Option<&'e str> {
+    match &target.expression {
+        Expression::Identifier(name) => Some(name),
+        Expression::ArrayAccess(inner, _) | Expression::StructAccess(inner, _) => {
+            assigned_variable(inner)
+        }
+        _ => None,
+    }
+}
+
+struct Extraction<'a, 't> {
+    source: &'a str,
+    typed: &'t Statement<'a>,
+    start: usize,
+    end: usize,
+    name: &'a str,
+    locals: HashSet<String>,
+
+    function: Option<Statement<'a>>,
+}
+
+impl<'a, 't> Extraction<'a, 't> {
+    fn statement(&mut self, stmt: &mut Statement<'a>) -> Result<(), String> {
+        match stmt {
+            Statement::Block(body)
+            | Statement::If(_, body, _)
+            | Statement::While(_, body, ..)
+            | Statement::DoWhile(_, body, ..)
+            | Statement::For(_, _, _, body, ..)
+            | Statement::Scope(body, _)
+            | Statement::IfLet(_, _, _, body, _) => self.body(body),
+            Statement::Match(_, arms, _) => {
+                for (_, _, body, _) in arms {
+                    self.body(body)?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn body(&mut self, body: &mut Vec<Statement<'a>>) -> Result<(), String> {
+        let selected: Vec<_> = body
+            .iter()
+            .enumerate()
+            .filter(|(_, stmt)| match stmt.span() {
+                Some(span) => self.start <= span.start && span.end <= self.end,
+                None => false,
+            })
+            .map(|(index, _)| index)
+            .collect();
+
+        let (first, last) = match (selected.first(), selected.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => {
+                for stmt in body {
+                    self.statement(stmt)?;
+                }
+                return Ok(());
+            }
+        };
+
+        let statements: Vec<_> = body[first..=last].to_vec();
+
+        let mut uses = Uses::default();
+        let mut declared = HashSet::new();
+        uses.body(&statements, &mut declared);
+        if uses.returns {
+            return Err("The selection returns from the function".to_string());
+        }
+        if uses.leaves_loop {
+            return Err("The selection breaks out of a loop around it".to_string());
+        }
+        if let Some((name, _)) = uses.reassigned.first() {
+            return Err(format!("The selection reassigns {}", name));
+        }
+
+        let mut after = Uses::default();
+        after.body(&body[last + 1..], &mut HashSet::new());
+        if let Some((name, _)) = after.uses.iter().find(|(name, _)| declared.contains(name)) {
+            return Err(format!("{} is used after the selection", name));
+        }
+
+        let mut args: Vec<TypedIdentifier<'a>> = vec![];
+        let mut values = vec![];
+        for (name, span) in uses.uses {
+            if !self.locals.contains(&name) || values.contains(&name) {
+                continue;
+            }
+            let typ = type_at(self.typed, span.start)
+                .filter(|typ| *typ != AzulaType::Infer)
+                .ok_or_else(|| format!("The type of {} is not known", name))?;
+            args.push((typ, &self.source[span.start..span.end]));
+            values.push(name);
+        }
+
+        let span = Span {
+            start: body[first].span().unwrap().start,
+            end: body[last].span().unwrap().end,
+        };
+        let node = |expression, span: &Span| ExpressionNode {
+            expression,
+            typed: AzulaType::Infer,
+            span: span.clone(),
+        };
+        let call = Statement::ExpressionStatement(
+            node(
+                Expression::FunctionCall {
+                    function: Rc::new(node(Expression::Identifier(self.name.to_string()), &span)),
+                    args: values
+                        .into_iter()
+                        .map(|value| node(Expression::Identifier(value), &span))
+                        .collect(),
+                },
+                &span,
+            ),
+            span.clone(),
+        );
+        body.splice(first..=last, [call]);
+
+        self.function = Some(Statement::Function {
+            name: self.name,
+            generics: vec![],
+            bounds: vec![],
+            args,
+            arg_spans: vec![],
+            defaults: vec![],
+            variadic: false,
+            returns: AzulaType::Void,
+            body: Rc::new(Statement::Block(statements)),
+            public: false,
+            attributes: vec![],
+            doc: None,
+            span,
+        });
+
+        Ok(())
+    }
+}
+
+// The variables a piece of code reads before declaring them itself
+#[derive(Default)]
+struct Uses {
+    uses: Vec<(String, Span)>,
+    // Where each reassignment happens, which inside a loop is its start
+    reassigned: Vec<(String, usize)>,
+    // Variables read inside a loop or a closure, which can happen many times
+    repeated: HashSet<String>,
+    // Where the code being walked runs again from, inside a closure that could
+    // be anywhere
+    repeat_from: Option<usize>,
+    declarations: HashSet<String>,
+    returns: bool,
+    // Labels of the loops being walked through, and whether a break or
+    // continue is for a loop outside them
+    loops: Vec<Option<String>>,
+    leaves_loop: bool,
+}
+
+impl Uses {
+    fn body(&mut self, body: &[Statement], scope: &mut HashSet<String>) {
+        for stmt in body {
+            self.statement(stmt, scope);
+        }
+    }
+
+    fn declare(&mut self, name: &str, scope: &mut HashSet<String>) {
+        scope.insert(name.to_string());
+        self.declarations.insert(name.to_string());
+    }
+
+    fn statement(&mut self, stmt: &Statement, scope: &mut HashSet<String>) {
+        match stmt {
+            Statement::Assign(_, name, _, value, _, _) => {
+                self.expression(value, scope);
+                self.declare(name, scope);
+            }
+            Statement::Destructure(_, names, value, _) => {
+                self.expression(value, scope);
+                for name in names {
+                    self.declare(name, scope);
+                }
+            }
+            Statement::Reassign(target, value, span) => {
+                if let Some(name) = assigned_variable(target) {
+                    if !scope.contains(name) {
+                        let at = self.repeat_from.unwrap_or(span.start);
+                        self.reassigned.push((name.to_string(), at));
+                    }
+                }
+                self.expression(target, scope);
+                self.expression(value, scope);
+            }
+            Statement::ExpressionStatement(expr, _) | Statement::Defer(expr, _) => {
+                self.expression(expr, scope)
+            }
+            Statement::Return(value, _) => {
+                self.returns = true;
+                if let Some(value) = value {
+                    self.expression(value, scope);
+                }
+            }
+            Statement::If(cond, body, _) => {
+                self.expression(cond, scope);
+                self.body(body, &mut scope.clone());
+            }
+            Statement::While(cond, body, label, span)
+            | Statement::DoWhile(cond, body, label, span) => {
+                let outer = self.repeat_from;
+                self.repeat_from = outer.or(Some(span.start));
+                self.expression(cond, scope);
+                self.loops.push(label.map(str::to_string));
+                self.body(body, &mut scope.clone());
+                self.loops.pop();
+                self.repeat_from = outer;
+            }
+            Statement::For(index, name, iterable, body, label, span) => {
+                self.expression(iterable, scope);
+                let outer = self.repeat_from;
+                self.repeat_from = outer.or(Some(span.start));
+                let mut inner = scope.clone();
+                if let Some(index) = index {
+                    self.declare(index, &mut inner);
+                }
+                self.declare(name, &mut inner);
+                self.loops.push(label.map(str::to_string));
+                self.body(body, &mut inner);
+                self.loops.pop();
+                self.repeat_from = outer;
+            }
+            Statement::Break(label, _) | Statement::Continue(label, _) => {
+                let inside = match label {
+                    Some(label) => self.loops.iter().any(|l| l.as_deref() == Some(*label)),
+                    None => !self.loops.is_empty(),
+                };
+                self.leaves_loop |= !inside;
+            }
+            Statement::IfLet(_, name, value, body, _) => {
+                self.expression(value, scope);
+                let mut inner = scope.clone();
+                self.declare(name, &mut inner);
+                self.body(body, &mut inner);
+            }
+            Statement::Match(value, arms, _) => {
+                self.expression(value, scope);
+                for (_, binding, body, _) in arms {
+                    let mut inner = scope.clone();
+                    if let Some(binding) = binding {
+                        self.declare(binding, &mut inner);
+                    }
+                    self.body(body, &mut inner);
+                }
+            }
+            Statement::Function { body, .. } => self.statement(body, scope),
+            Statement::Root(body) | Statement::Block(body) | Statement::Scope(body, _) => {
+                self.body(body, &mut scope.clone())
+            }
+            Statement::ExternFunction { .. }
+            | Statement::Struct { .. }
+            | Statement::Interface { .. }
+            | Statement::Impl { .. }
+            | Statement::Import(..)
+            | Statement::Conditional(..) => {}
+        }
+    }
+
+    fn expression(&mut self, expr: &ExpressionNode, scope: &HashSet<String>) {
+        match &expr.expression {
+            Expression::Identifier(name) => {
+                if !scope.contains(name) {
+                    self.uses.push((name.clone(), expr.span.clone()));
+                    if self.repeat_from.is_some() {
+                        self.repeated.insert(name.clone());
+                    }
+                }
+            }
+            Expression::Infix(left, _, right)
+            | Expression::ArrayAccess(left, right)
+            | Expression::ArrayRepeat(left, right)
+            | Expression::Range(left, right) => {
+                self.expression(left, scope);
+                self.expression(right, scope);
+            }
+            // The function is named rather than read from a variable
+            Expression::FunctionCall { args, .. }
+            | Expression::Array(args)
+            | Expression::Tuple(args) => {
+                for arg in args {
+                    self.expression(arg, scope);
+                }
+            }
+            Expression::StructInitialisation(_, values) => {
+                for (_, value) in values {
+                    self.expression(value, scope);
+                }
+            }
+            Expression::Not(inner)
+            | Expression::Negate(inner)
+            | Expression::Pointer(inner)
+            | Expression::Deref(inner)
+            | Expression::Cast(inner, _)
+            | Expression::Try(inner, _)
+            | Expression::NamedArgument(_, inner)
+            | Expression::StructAccess(inner, _) => self.expression(inner, scope),
+            Expression::Ternary(cond, then, otherwise) => {
+                self.expression(cond, scope);
+                self.expression(then, scope);
+                self.expression(otherwise, scope);
+            }
+            Expression::Closure { args, body, .. } => {
+                let mut inner = scope.clone();
+                inner.extend(args.iter().map(|(_, name)| name.to_string()));
+                let outer = self.repeat_from.replace(0);
+                self.expression(body, &inner);
+                self.repeat_from = outer;
+            }
+            Expression::Integer(_)
+            | Expression::Float(_)
+            | Expression::Boolean(_)
+            | Expression::String(_)
+            | Expression::SizeOf(_)
+            | Expression::New(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use azula_parser::prelude::*;
+
+    use super::*;
+    use crate::prelude::*;
+
+    const SOURCE: &str = "func main() {
+    var width = 3;
+    const area = width * 4;
+    printf(\"%d\", area + 1);
+}
+";
+
+    fn parse(source: &str) -> Statement<'_> {
+        let lexer: Lexer = source.into();
+        let mut parser = Parser::new(source, lexer);
+        let ast = parser.parse();
+        assert!(parser.errors.is_empty());
+        ast
+    }
+
+    #[test]
+    fn test_inline_variable() {
+        let ast = parse(SOURCE);
+
+        let offset = SOURCE.find("const").unwrap();
+        assert_eq!(
+            print_ast(&inline_variable(&ast, offset).unwrap()),
+            "func main() {
+    var width = 3;
+    printf(\"%d\", (width * 4) + 1);
+}
+"
+        );
+
+        let source = "func main() {\n    var x = 1;\n    x = 2;\n}\n";
+        assert_eq!(
+            inline_variable(&parse(source), source.find("var").unwrap()),
+            Err("x is reassigned".to_string())
+        );
+    }
+
+    #[test]
+    fn test_inline_keeps_meaning() {
+        let source = "func main() {\n    var x = next();\n    printf(\"%d\", x + x);\n}\n";
+        assert_eq!(
+            inline_variable(&parse(source), source.find("var").unwrap()),
+            Err("The value of x has side effects and isn't used exactly once".to_string())
+        );
+
+        let source = "func main() {\n    var x = next();\n    while true {\n        printf(\"%d\", x);\n    }\n}\n";
+        assert_eq!(
+            inline_variable(&parse(source), source.find("var").unwrap()),
+            Err("The value of x has side effects and isn't used exactly once".to_string())
+        );
+
+        let source = "func main() {\n    var x = next();\n    printf(\"%d\", x);\n}\n";
+        assert_eq!(
+            print_ast(&inline_variable(&parse(source), source.find("var").unwrap()).unwrap()),
+            "func main() {\n    printf(\"%d\", next());\n}\n"
+        );
+
+        let source = "func main() {\n    var a = 1;\n    var x = a;\n    a = 2;\n    printf(\"%d\", x);\n}\n";
+        assert_eq!(
+            inline_variable(&parse(source), source.find("var x").unwrap()),
+            Err("a is reassigned before x is used".to_string())
+        );
+
+        let source = "func main() {\n    var a = 1;\n    var x = a;\n    while a < 3 {\n        printf(\"%d\", x);\n        a = a + 1;\n    }\n}\n";
+        assert_eq!(
+            inline_variable(&parse(source), source.find("var x").unwrap()),
+            Err("a is reassigned before x is used".to_string())
+        );
+    }
+
+    #[test]
+    fn test_extract_function() {
+        let ast = parse(SOURCE);
+        let typed = Typechecker::new(ast.clone()).typecheck().unwrap();
+
+        let start = SOURCE.find("const").unwrap();
+        let end = SOURCE.rfind('}').unwrap();
+        assert_eq!(
+            print_ast(&extract_function(SOURCE, &ast, &typed, start, end, "show_area").unwrap()),
+            "func main() {
+    var width = 3;
+    show_area(width);
+}
+func show_area(width: int) {
+    const area = width * 4;
+    printf(\"%d\", area + 1);
+}
+"
+        );
+
+        let end = start + "const area = width * 4;".len();
+        assert_eq!(
+            extract_function(SOURCE, &ast, &typed, start, end, "area"),
+            Err("area is used after the selection".to_string())
+        );
+    }
+}
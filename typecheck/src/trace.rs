@@ -0,0 +1,269 @@
+use std::{mem::take, rc::Rc};
+
+use azula_ast::prelude::*;
+use azula_error::prelude::SourceMap;
+use azula_type::prelude::AzulaType;
+
+/// Adds a printf to a typechecked tree before each statement in its
+/// functions, showing the line being run, and after each assignment and
+/// function entry, showing the values of the variables set. Running the
+/// program then prints a step by step trace of it. Lines from imported files
+/// are shown with the file's name
+pub fn trace_execution<'a>(ast: Statement<'a>, source: &SourceMap) -> Statement<'a> {
+    match ast {
+        Statement::Root(body) => Statement::Root(
+            body.into_iter()
+                .map(|stmt| trace_function(stmt, source))
+                .collect(),
+        ),
+        ast => ast,
+    }
+}
+
+fn trace_function<'a>(mut stmt: Statement<'a>, source: &SourceMap) -> Statement<'a> {
+    if let Statement::Function {
+        args, body, span, ..
+    } = &mut stmt
+    {
+        if let Statement::Block(stmts) = Rc::make_mut(body) {
+            let mut traced = vec![trace_line(source, span)];
+            traced.extend(
+                args.iter()
+                    .filter_map(|(typ, name)| trace_value(name, typ, span)),
+            );
+            traced.extend(trace_body(take(stmts), source));
+            *stmts = traced;
+        }
+    }
+
+    stmt
+}
+
+fn trace_body<'a>(body: Vec<Statement<'a>>, source: &SourceMap) -> Vec<Statement<'a>> {
+    let mut traced = vec![];
+    for mut stmt in body {
+        let span = match stmt.span() {
+            Some(span) => span,
+            None => {
+                if let Statement::Block(body) = &mut stmt {
+                    *body = trace_body(take(body), source);
+                }
+                traced.push(stmt);
+                continue;
+            }
+        };
+
+        let values: Vec<_> = match &mut stmt {
+            Statement::Assign(_, name, _, value, _, _) => {
+                trace_value(name.as_str(), &value.typed, &span)
+                    .into_iter()
+                    .collect()
+            }
+            Statement::Reassign(target, ..) => match &target.expression {
+                Expression::Identifier(name) => trace_value(name, &target.typed, &span)
+                    .into_iter()
+                    .collect(),
+                _ => vec![],
+            },
+            Statement::Destructure(_, names, value, _) => match &value.typed {
+                AzulaType::Tuple(types) => names
+                    .iter()
+                    .zip(types)
+                    .filter_map(|(name, typ)| trace_value(name, typ, &span))
+                    .collect(),
+                _ => vec![],
+            },
+            Statement::If(_, body, _)
+            | Statement::While(_, body, ..)
+            | Statement::DoWhile(_, body, ..)
+            | Statement::For(_, _, _, body, ..)
+            | Statement::Scope(body, _)
+            | Statement::IfLet(_, _, _, body, _) => {
+                *body = trace_body(take(body), source);
+                vec![]
+            }
+            Statement::Match(_, arms, _) => {
+                for (_, _, body, _) in arms.iter_mut() {
+                    *body = trace_body(take(body), source);
+                }
+                vec![]
+            }
+            _ => vec![],
+        };
+
+        traced.push(trace_line(source, &span));
+        traced.push(stmt);
+        traced.extend(values);
+    }
+
+    traced
+}
+
+// `[line] source` for the first line of the statement. Spans count chars
+fn trace_line<'a>(sources: &SourceMap, span: &Span) -> Statement<'a> {
+    let file = sources.file(span.start);
+    let source = file.source;
+    let start = source
+        .char_indices()
+        .nth(span.start - file.base)
+        .map_or(source.len(), |(index, _)| index);
+    let line = source[..start].matches('\n').count() + 1;
+    let text = source[start..].lines().next().unwrap_or("").trim_end();
+    let location = if file.base == 0 {
+        line.to_string()
+    } else {
+        format!("{}:{}", file.name, line)
+    };
+
+    printf(
+        format!("[{}] {}\n", location, text.replace('%', "%%")),
+        vec![],
+        span,
+    )
+}
+
+// `    name = value`, for the types printf can show
+fn trace_value<'a>(name: &str, typ: &AzulaType<'a>, span: &Span) -> Option<Statement<'a>> {
+    let node = |expression, typed| ExpressionNode {
+        expression,
+        typed,
+        span: span.clone(),
+    };
+    let string = |value: &str| {
+        node(
+            Expression::String(value.to_string()),
+            AzulaType::Pointer(Rc::new(AzulaType::Str)),
+        )
+    };
+    let variable = node(Expression::Identifier(name.to_string()), typ.clone());
+
+    let (format, value) = match typ {
+        AzulaType::Int => ("%ld", variable),
+        AzulaType::SizedSignedInt(_) | AzulaType::SizedUnsignedInt(_) => (
+            "%ld",
+            node(
+                Expression::Cast(Rc::new(variable), AzulaType::Int),
+                AzulaType::Int,
+            ),
+        ),
+        AzulaType::Float => ("%f", variable),
+        AzulaType::SizedFloat(_) => (
+            "%f",
+            node(
+                Expression::Cast(Rc::new(variable), AzulaType::Float),
+                AzulaType::Float,
+            ),
+        ),
+        AzulaType::Bool => (
+            "%s",
+            node(
+                Expression::Ternary(
+                    Rc::new(variable),
+                    Rc::new(string("true")),
+                    Rc::new(string("false")),
+                ),
+                AzulaType::Pointer(Rc::new(AzulaType::Str)),
+            ),
+        ),
+        AzulaType::Pointer(inner) if **inner == AzulaType::Str => ("%s", variable),
+        _ => return None,
+    };
+
+    Some(printf(
+        format!("    {} = {}\n", name, format),
+        vec![value],
+        span,
+    ))
+}
+
+fn printf<'a>(format: String, mut args: Vec<ExpressionNode<'a>>, span: &Span) -> Statement<'a> {
+    let node = |expression, typed| ExpressionNode {
+        expression,
+        typed,
+        span: span.clone(),
+    };
+
+    args.insert(
+        0,
+        node(
+            Expression::String(format),
+            AzulaType::Pointer(Rc::new(AzulaType::Str)),
+        ),
+    );
+    Statement::ExpressionStatement(
+        node(
+            Expression::FunctionCall {
+                function: Rc::new(node(
+                    Expression::Identifier("printf".to_string()),
+                    AzulaType::Void,
+                )),
+                args,
+            },
+            AzulaType::Void,
+        ),
+        span.clone(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Deref;
+
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_trace_execution() {
+        let source = "func main() {
+    var x = 1;
+    if x > 0 {
+        x = 5 % 2;
+    }
+}";
+        let lexer = azula_parser::prelude::Lexer::new(source);
+        let mut parser = azula_parser::prelude::Parser::new(source, lexer);
+        let ast = parser.parse();
+        let mut typechecker = Typechecker::new(ast);
+        let mut sources = SourceMap::new();
+        sources.add("main.azl".to_string(), source);
+        let ast = trace_execution(typechecker.typecheck().unwrap(), &sources);
+
+        let format = |stmt: &Statement| match stmt {
+            Statement::ExpressionStatement(call, _) => match &call.expression {
+                Expression::FunctionCall { args, .. } => match &args[0].expression {
+                    Expression::String(format) => format.clone(),
+                    expr => panic!("expected string, got {:?}", expr),
+                },
+                expr => panic!("expected call, got {:?}", expr),
+            },
+            stmt => panic!("expected printf, got {:?}", stmt),
+        };
+
+        let body = match &ast {
+            Statement::Root(stmts) => match &stmts[0] {
+                Statement::Function { body, .. } => match body.deref() {
+                    Statement::Block(body) => body.clone(),
+                    stmt => panic!("expected block, got {:?}", stmt),
+                },
+                stmt => panic!("expected function, got {:?}", stmt),
+            },
+            stmt => panic!("expected root, got {:?}", stmt),
+        };
+
+        assert_eq!(body.len(), 6);
+        assert_eq!(format(&body[0]), "[1] func main() {\n");
+        assert_eq!(format(&body[1]), "[2] var x = 1;\n");
+        assert_eq!(format(&body[3]), "    x = %ld\n");
+        assert_eq!(format(&body[4]), "[3] if x > 0 {\n");
+
+        // Nested bodies are traced too, with % escaped for printf
+        match &body[5] {
+            Statement::If(_, body, _) => {
+                assert_eq!(body.len(), 3);
+                assert_eq!(format(&body[0]), "[4] x = 5 %% 2;\n");
+                assert_eq!(format(&body[2]), "    x = %ld\n");
+            }
+            stmt => panic!("expected if, got {:?}", stmt),
+        }
+    }
+}
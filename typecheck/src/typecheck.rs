@@ -1,38 +1,122 @@
-use std::{collections::HashMap, ops::Deref, rc::Rc};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    ops::Deref,
+    rc::Rc,
+};
 
 use azula_ast::prelude::*;
 use azula_error::prelude::*;
 use azula_type::prelude::AzulaType;
 
+use crate::consts::{ConstValue, Constants};
+use crate::generics::{
+    bind, mangle, map_statement_types, map_type, substitute_statement, substitute_type, Bindings,
+    StructInstances,
+};
+
+// Function attributes the backend knows how to apply, and @test for the
+// test runner
+const ATTRIBUTES: &[&str] = &[
+    "jumptable",
+    "nojumptable",
+    "inline",
+    "noinline",
+    "cold",
+    "interrupt",
+    "test",
+];
+
+// Builtins reading or writing through a pointer, the loads taking just it
+const MEMORY_ACCESS: &[&str] = &[
+    "volatile_load",
+    "volatile_store",
+    "unaligned_load",
+    "unaligned_store",
+];
+
+// Builtins converting an integer to or from little or big endian
+const BYTE_ORDERS: &[&str] = &["to_le", "from_le", "to_be", "from_be"];
+
+// Attributes asking for opposite things
+const CONFLICTING_ATTRIBUTES: &[(&str, &str)] = &[
+    ("inline", "noinline"),
+    ("jumptable", "nojumptable"),
+    ("interrupt", "inline"),
+];
+
 pub struct Typechecker<'a> {
     ast: Statement<'a>,
 
     functions: HashMap<&'a str, FunctionDefinition<'a>>,
     globals: HashMap<String, VariableDefinition<'a>>,
+    constants: Constants<'a>,
     structs: HashMap<String, StructDefinition<'a>>,
 
+    // Locals declared and read in the function being checked, for unused_variable
+    declared_variables: Vec<(String, Span)>,
+    used_variables: HashSet<String>,
+
     pub errors: Vec<AzulaError>,
+    pub warnings: Vec<AzulaWarning>,
+    pub ignores: Vec<IgnoreDirective>,
+
+    // Returned by build_info(), set by --embed-build-info
+    pub build_info: Option<String>,
+
+    // Return type of the function being checked, for coercing return values
+    returns: AzulaType<'a>,
+    // Labels of the loops around the statement being checked, innermost last
+    loops: Vec<Option<&'a str>>,
+
+    // Generic functions by name, and the instantiations still to be checked
+    generic_functions: HashMap<&'a str, Statement<'a>>,
+    instances: Vec<Statement<'a>>,
+
+    // Generic structs by name, and the structs instantiated from them
+    generic_structs: HashMap<&'a str, Statement<'a>>,
+    struct_instances: StructInstances<'a>,
+    struct_statements: Vec<Statement<'a>>,
+
+    // Interfaces by name, and the (type, interface) pairs with an impl
+    interfaces: HashMap<&'a str, Vec<MethodSignature<'a>>>,
+    implementations: HashSet<(String, &'a str)>,
+
+    // The names declared by each imported module
+    modules: HashMap<&'a str, HashSet<String>>,
+    // The module each imported function is declared in, and whether it's pub.
+    // Private ones are declared as module::name
+    function_modules: HashMap<&'a str, (&'a str, bool)>,
+    // The module of the function being checked, None for the main file
+    current_module: Option<&'a str>,
 }
 
 struct FunctionDefinition<'a> {
     name: &'a str,
     args: Vec<(AzulaType<'a>, &'a str)>,
+    defaults: Vec<ExpressionNode<'a>>,
+    // The last arg collects the rest of the arguments into an array
+    variadic: bool,
     varargs: bool,
     returns: AzulaType<'a>,
+    generics: Vec<&'a str>,
 }
 
 struct StructDefinition<'a> {
     name: &'a str,
     attrs: Vec<(AzulaType<'a>, &'a str)>,
+    // Checked again at each literal that leaves the attribute out
+    defaults: Vec<(&'a str, ExpressionNode<'a>)>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct VariableDefinition<'a> {
     name: String,
     mutable: bool,
     typ: AzulaType<'a>,
 }
 
+#[derive(Clone)]
 pub struct Environment<'a> {
     variable_definitions: HashMap<String, VariableDefinition<'a>>,
 }
@@ -55,18 +139,109 @@ impl<'a> Typechecker<'a> {
             ast: root,
             functions: HashMap::new(),
             globals: HashMap::new(),
+            constants: Constants::new(),
             structs: HashMap::new(),
+            declared_variables: vec![],
+            used_variables: HashSet::new(),
             errors: vec![],
+            warnings: vec![],
+            ignores: vec![],
+            build_info: None,
+            returns: AzulaType::Void,
+            loops: vec![],
+            generic_functions: HashMap::new(),
+            instances: vec![],
+            generic_structs: HashMap::new(),
+            struct_instances: HashMap::new(),
+            struct_statements: vec![],
+            interfaces: HashMap::new(),
+            implementations: HashSet::new(),
+            modules: HashMap::new(),
+            function_modules: HashMap::new(),
+            current_module: None,
         }
     }
 
     pub fn typecheck(&mut self) -> Result<Statement<'a>, String> {
-        if let Statement::Root(mut x) = self.ast.clone() {
+        if let Statement::Root(x) = self.ast.clone() {
+            let mut x = self.flatten_imports(x, None, &mut HashMap::new())?;
+
+            // Constants can size array types, so they're known before any
+            // types are resolved
+            for stmt in x.iter() {
+                if let Statement::Assign(false, name, _, value, _, _) = stmt {
+                    self.constants.define(name, value);
+                }
+            }
+
+            // Interfaces are needed by name to resolve types, their
+            // signatures are collected again once they're resolved too
+            for stmt in x.iter() {
+                match stmt {
+                    Statement::Struct { name, generics, .. } if !generics.is_empty() => {
+                        self.generic_structs.insert(name, stmt.clone());
+                    }
+                    Statement::Interface { name, methods, .. } => {
+                        self.interfaces.insert(name, methods.clone());
+                    }
+                    _ => {}
+                }
+            }
+            // impl Drop for T gives T a destructor
+            let this = (AzulaType::Named("Self".to_string()), "self");
+            self.interfaces
+                .entry("Drop")
+                .or_insert_with(|| vec![("drop", vec![this], AzulaType::Void)]);
+            // start..end makes a Range<T> and []T is a Slice<T>, which
+            // programs can name themselves
+            self.generic_structs
+                .entry("Range")
+                .or_insert_with(range_struct);
+            self.generic_structs
+                .entry("Slice")
+                .or_insert_with(slice_struct);
+
+            // Generic struct types are replaced by their instances everywhere
+            // outside generic definitions, which wait until they're
+            // instantiated
+            for stmt in x.iter_mut() {
+                let generic = matches!(stmt, Statement::Function { generics, .. }
+                    | Statement::Struct { generics, .. } if !generics.is_empty());
+                if !generic {
+                    *stmt = self.resolve_statement(stmt)?;
+                }
+            }
+
+            // Impls turn into plain functions, interfaces are kept for
+            // codegen to build vtables from
+            for stmt in x.iter() {
+                if let Statement::Interface { name, methods, .. } = stmt {
+                    self.interfaces.insert(name, methods.clone());
+                }
+            }
+            let mut lowered = vec![];
+            for stmt in x {
+                match stmt {
+                    Statement::Impl { .. } => lowered.extend(self.lower_impl(stmt)?),
+                    stmt => lowered.push(stmt),
+                }
+            }
+            let mut x = lowered;
+
             for stmt in x.iter_mut() {
+                if let Statement::Function { name, generics, .. } = stmt {
+                    if !generics.is_empty() {
+                        self.generic_functions.insert(name, stmt.clone());
+                    }
+                }
+
                 match stmt {
                     Statement::Function {
                         name,
+                        generics,
                         args,
+                        defaults,
+                        variadic,
                         returns,
                         ..
                     } => {
@@ -83,7 +258,10 @@ impl<'a> Typechecker<'a> {
                                 name,
                                 varargs: true,
                                 args: args_converted.clone(),
+                                defaults: defaults.clone(),
+                                variadic: *variadic,
                                 returns: returns_converted.clone(),
+                                generics: generics.clone(),
                             },
                         );
                     }
@@ -94,8 +272,11 @@ impl<'a> Typechecker<'a> {
                         returns,
                         ..
                     } => {
-                        let args_converted: Vec<_> =
-                            args.iter().map(|typ| (typ.clone(), "xyz")).collect();
+                        // Unnamed params can't be passed by name
+                        let args_converted: Vec<_> = args
+                            .iter()
+                            .map(|(typ, name)| (typ.clone(), name.unwrap_or("")))
+                            .collect();
 
                         let returns_converted: AzulaType = returns.clone().into();
 
@@ -105,25 +286,57 @@ impl<'a> Typechecker<'a> {
                                 name,
                                 varargs: false,
                                 args: args_converted.clone(),
+                                defaults: vec![],
+                                variadic: false,
                                 returns: returns_converted.clone(),
+                                generics: vec![],
                             },
                         );
                     }
                     _ => {}
                 }
             }
+            self.ast = Statement::Root(x);
         } else {
             return Err("Not a root node".to_string());
         }
 
-        if let Statement::Root(mut x) = self.ast.clone() {
-            for stmt in x.iter_mut() {
-                *stmt = match self.typecheck_top_level_statement(stmt.clone()) {
-                    Ok(stmt) => stmt,
-                    Err(e) => return Err(e),
-                };
+        if let Statement::Root(x) = self.ast.clone() {
+            // Structs and globals are declared before any function is
+            // checked, so functions can use ones defined after them. The
+            // statements are kept in their original order
+            let mut checked = vec![];
+            for declarations in [true, false] {
+                for (index, stmt) in x.iter().enumerate() {
+                    // Generic functions and structs are only checked once
+                    // they're instantiated, and only their instances are kept
+                    if matches!(stmt, Statement::Function { generics, .. }
+                        | Statement::Struct { generics, .. } if !generics.is_empty())
+                    {
+                        continue;
+                    }
+                    if matches!(stmt, Statement::Struct { .. } | Statement::Assign(..))
+                        != declarations
+                    {
+                        continue;
+                    }
+
+                    checked.push((index, self.typecheck_top_level_statement(stmt.clone())?));
+                }
+            }
+            checked.sort_by_key(|(index, _)| *index);
+            let mut stmts: Vec<_> = checked.into_iter().map(|(_, stmt)| stmt).collect();
+
+            // Checking an instance can instantiate more
+            while !self.instances.is_empty() {
+                let instance = self.instances.remove(0);
+                stmts.push(self.typecheck_function(instance)?);
             }
-            Ok(Statement::Root(x))
+
+            // Struct instances go first so everything can use them
+            let mut root = std::mem::take(&mut self.struct_statements);
+            root.extend(stmts);
+            Ok(Statement::Root(root))
         } else {
             Err("Not a root node".to_string())
         }
@@ -135,28 +348,53 @@ impl<'a> Typechecker<'a> {
     ) -> Result<Statement<'a>, String> {
         match stmt {
             Statement::Function { .. } => self.typecheck_function(stmt),
-            Statement::ExternFunction { .. } => Ok(stmt),
+            Statement::ExternFunction { .. } | Statement::Interface { .. } => Ok(stmt),
             Statement::Assign(..) => self.typecheck_global_assign(stmt),
             Statement::Struct {
                 name,
+                generics,
                 attributes,
+                defaults,
+                annotations,
+                doc,
                 span,
             } => {
+                self.check_attributes(&annotations, &[])?;
                 self.structs.insert(
                     name.to_string(),
                     StructDefinition {
                         name,
                         attrs: attributes.clone(),
+                        defaults: defaults.clone(),
                     },
                 );
 
+                let mut typed_defaults = vec![];
+                for (attr, default) in defaults {
+                    let (typ, _) = attributes.iter().find(|(_, name)| *name == attr).unwrap();
+                    typed_defaults.push((attr, self.typecheck_default(default, typ)?));
+                }
+
                 Ok(Statement::Struct {
                     name: name,
+                    generics: generics,
                     attributes: attributes,
+                    defaults: typed_defaults,
+                    annotations,
+                    doc: doc,
                     span: span,
                 })
             }
-            _ => unreachable!(),
+            stmt => {
+                if let Some(span) = stmt.span() {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::StatementOutsideFunction(statement_kind(&stmt).to_string()),
+                        span.start,
+                        span.end,
+                    ));
+                }
+                Err("statement outside function".to_string())
+            }
         }
     }
 
@@ -180,27 +418,220 @@ impl<'a> Typechecker<'a> {
             }
             Statement::If(..) => self.typecheck_if(stmt, env),
             Statement::While(..) => self.typecheck_while(stmt, env),
+            Statement::DoWhile(..) => self.typecheck_do_while(stmt, env),
+            Statement::For(..) => self.typecheck_for(stmt, env),
+            Statement::Break(label, ref span) | Statement::Continue(label, ref span) => {
+                let keyword = match stmt {
+                    Statement::Break(..) => "break",
+                    _ => "continue",
+                };
+                let error = match label {
+                    Some(label) if !self.loops.contains(&Some(label)) => {
+                        Some(ErrorType::UnknownLoopLabel(label.to_string()))
+                    }
+                    None if self.loops.is_empty() => {
+                        Some(ErrorType::LoopControlOutsideLoop(keyword.to_string()))
+                    }
+                    _ => None,
+                };
+                if let Some(error) = error {
+                    self.errors
+                        .push(AzulaError::new(error, span.start, span.end));
+                    return Err(format!("{} outside loop", keyword));
+                }
+                Ok((stmt, AzulaType::Void))
+            }
             Statement::Reassign(..) => self.typecheck_reassign(stmt, env),
+            Statement::Match(..) => self.typecheck_match(stmt, env),
+            Statement::Destructure(..) => self.typecheck_destructure(stmt, env),
+            Statement::IfLet(..) => self.typecheck_if_let(stmt, env),
+            // Its variables are only seen by the statements inside it
+            Statement::Scope(body, span) => {
+                let stmts = self.typecheck_body(body, &mut env.clone())?;
+                Ok((Statement::Scope(stmts, span), AzulaType::Void))
+            }
+            // Top level defers are handled by typecheck_function
+            Statement::Defer(_, span) => {
+                self.errors.push(AzulaError::new(
+                    ErrorType::NestedDefer,
+                    span.start,
+                    span.end,
+                ));
+                Err("nested defer".to_string())
+            }
             _ => unreachable!("{:?}", stmt),
         }
     }
 
+    // The attributes a declaration can have are the names in `known`, none of
+    // which take arguments yet
+    fn check_attributes(
+        &mut self,
+        attributes: &[Attribute<'a>],
+        known: &[&str],
+    ) -> Result<(), String> {
+        for attribute in attributes {
+            let error = if !known.contains(&attribute.name) {
+                ErrorType::UnknownAttribute(attribute.name.to_string())
+            } else if !attribute.args.is_empty() {
+                ErrorType::UnexpectedArguments(format!("@{}", attribute.name))
+            } else {
+                continue;
+            };
+            self.errors.push(AzulaError::new(
+                error,
+                attribute.span.start,
+                attribute.span.end,
+            ));
+            return Err("invalid attribute".to_string());
+        }
+        Ok(())
+    }
+
+    // The statements of a nested block, with the locals it declares dropped
+    // at its end
+    fn typecheck_body(
+        &mut self,
+        body: Vec<Statement<'a>>,
+        env: &mut Environment<'a>,
+    ) -> Result<Vec<Statement<'a>>, String> {
+        let mut stmts = vec![];
+        for stmt in body {
+            let (stmt, _) = self.typecheck_statement(stmt, env)?;
+            let drop = self.drop_local(&stmt, env)?;
+            stmts.push(stmt);
+            stmts.extend(drop);
+        }
+        Ok(stmts)
+    }
+
+    // A local of a type implementing Drop is dropped when its block ends,
+    // by a defer after it. Copies of another variable are left to whatever
+    // else has the value, and a return of the local itself skips its defer.
+    // A closure made here or handed back by a call is dropped the same way,
+    // by closure.drop freeing its env
+    fn drop_local(
+        &mut self,
+        stmt: &Statement<'a>,
+        env: &Environment<'a>,
+    ) -> Result<Option<Statement<'a>>, String> {
+        let (name, typ, value, span) = match stmt {
+            Statement::Assign(_, name, Some(typ), value, _, span) => (name, typ, value, span),
+            _ => return Ok(None),
+        };
+        if let AzulaType::Function(..) = typ {
+            if !matches!(
+                value.expression,
+                Expression::Closure { .. } | Expression::FunctionCall { .. }
+            ) {
+                return Ok(None);
+            }
+
+            let node = |expression, typed| ExpressionNode {
+                expression,
+                typed,
+                span: span.clone(),
+            };
+            let call = node(
+                Expression::FunctionCall {
+                    function: Rc::new(node(
+                        Expression::Identifier("closure.drop".to_string()),
+                        AzulaType::Void,
+                    )),
+                    args: vec![node(Expression::Identifier(name.clone()), typ.clone())],
+                },
+                AzulaType::Void,
+            );
+            return Ok(Some(Statement::Defer(call, span.clone())));
+        }
+        if !self.implementations.contains(&(typ.mangled(), "Drop"))
+            || matches!(value.expression, Expression::Identifier(_))
+        {
+            return Ok(None);
+        }
+
+        let node = |expression| ExpressionNode {
+            expression,
+            typed: AzulaType::Infer,
+            span: span.clone(),
+        };
+        let destructor = node(Expression::Identifier(format!("{}.drop", typ.mangled())));
+        let call = node(Expression::FunctionCall {
+            function: Rc::new(destructor),
+            args: vec![node(Expression::Identifier(name.clone()))],
+        });
+        let (call, _) = self.typecheck_expression(call, env)?;
+        Ok(Some(Statement::Defer(call, span.clone())))
+    }
+
     fn typecheck_function(&mut self, stmt: Statement<'a>) -> Result<Statement<'a>, String> {
         if let Statement::Function {
             name,
+            generics,
+            bounds,
             args,
+            arg_spans,
+            defaults,
+            variadic,
             returns,
             body,
+            public,
+            attributes,
+            doc,
             span,
         } = stmt.clone()
         {
+            self.current_module = self.function_modules.get(name).map(|(module, _)| *module);
+            self.check_attributes(&attributes, ATTRIBUTES)?;
+            let attribute_names: Vec<_> = attributes.iter().map(|a| a.name).collect();
+            let attributes_contain = |name| attribute_names.contains(&name);
+            if let Some((first, second)) = CONFLICTING_ATTRIBUTES
+                .iter()
+                .find(|(first, second)| attributes_contain(*first) && attributes_contain(*second))
+            {
+                self.errors.push(AzulaError::new(
+                    ErrorType::ConflictingAttributes(first.to_string(), second.to_string()),
+                    span.start,
+                    span.end,
+                ));
+                return Err("conflicting attributes".to_string());
+            }
+            // The hardware jumps to a handler, so there's nothing to pass in
+            // and nowhere for a value to go
+            if attributes_contain("interrupt") && (!args.is_empty() || returns != AzulaType::Void) {
+                self.errors.push(AzulaError::new(
+                    ErrorType::InvalidInterruptHandler(name.to_string()),
+                    span.start,
+                    span.end,
+                ));
+                return Err("invalid interrupt handler".to_string());
+            }
+            // The runner calls each with nothing and can only tell whether it
+            // failed
+            let test_returns = returns == AzulaType::Void
+                || returns == AzulaType::Result(Rc::new(AzulaType::Void));
+            if attributes_contain("test") && (!args.is_empty() || !test_returns) {
+                self.errors.push(AzulaError::new(
+                    ErrorType::InvalidTest(name.to_string()),
+                    span.start,
+                    span.end,
+                ));
+                return Err("invalid test".to_string());
+            }
             let args_converted: Vec<_> = args
                 .iter()
                 .map(|(typ, name)| (AzulaType::from(typ.clone()), *name))
                 .collect();
 
+            let first_default = args_converted.len() - variadic as usize - defaults.len();
+            let mut typed_defaults = vec![];
+            for (default, (typ, _)) in defaults.into_iter().zip(&args_converted[first_default..]) {
+                typed_defaults.push(self.typecheck_default(default, typ)?);
+            }
+
             let mut environment = Environment::new();
-            for (typ, name) in &args_converted {
+            // A parameter named _ can't be read, so several can share the name
+            for (typ, name) in args_converted.iter().filter(|(_, name)| *name != "_") {
                 environment.add_variable(
                     name.to_string(),
                     VariableDefinition {
@@ -210,23 +641,81 @@ impl<'a> Typechecker<'a> {
                     },
                 )
             }
+            self.declared_variables.clear();
+            self.used_variables.clear();
+            self.returns = returns.clone();
+            self.loops.clear();
+
             let mut statements = vec![];
             if let Statement::Block(mut stmts) = body.deref().clone() {
+                // A function returning a value can end with it rather than a
+                // return, and it's checked like one
+                let gives_value = returns != AzulaType::Void
+                    && returns != AzulaType::Result(Rc::new(AzulaType::Void));
+                if let Some(Statement::ExpressionStatement(expr, span)) = stmts.last().cloned() {
+                    if gives_value {
+                        *stmts.last_mut().unwrap() = Statement::Return(Some(expr), span);
+                    }
+                }
                 for stmt in stmts.iter_mut() {
-                    statements.push(
-                        match self.typecheck_statement(stmt.clone(), &mut environment) {
-                            Ok((stmt, _)) => stmt,
-                            Err(e) => return Err(e),
-                        },
+                    // Deferred expressions are only allowed here, so that every
+                    // return after one is reached knows it has to run it
+                    if let Statement::Defer(expr, span) = stmt {
+                        let (expr, _) = self.typecheck_expression(expr.clone(), &environment)?;
+                        statements.push(Statement::Defer(expr, span.clone()));
+                        continue;
+                    }
+                    let stmt = match self.typecheck_statement(stmt.clone(), &mut environment) {
+                        Ok((stmt, _)) => stmt,
+                        Err(e) => return Err(e),
+                    };
+                    let drop = self.drop_local(&stmt, &environment)?;
+                    statements.push(stmt);
+                    statements.extend(drop);
+                }
+                // Falling off the end would return nothing, pointed at the
+                // closing brace
+                if gives_value && !always_returns(&statements) {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::MissingReturn(name.to_string()),
+                        span.end - 1,
+                        span.end,
+                    ));
+                    return Err("missing return".to_string());
+                }
+            }
+
+            for (name, span) in std::mem::take(&mut self.declared_variables) {
+                if !name.starts_with('_') && !self.used_variables.contains(&name) {
+                    self.warn(WarningType::UnusedVariable(name), span.start, span.end);
+                }
+            }
+            // Methods have to take self whether they use it or not. A function
+            // the compiler made has no arg spans, so it's pointed at as a whole
+            for (index, (_, arg)) in args.iter().enumerate() {
+                if !arg.starts_with('_') && *arg != "self" && !self.used_variables.contains(*arg) {
+                    let at = arg_spans.get(index).unwrap_or(&span);
+                    self.warn(
+                        WarningType::UnusedParameter(arg.to_string(), name.to_string()),
+                        at.start,
+                        at.end,
                     );
                 }
             }
 
             return Ok(Statement::Function {
                 name,
+                generics,
+                bounds,
                 args,
+                arg_spans,
+                defaults: typed_defaults,
+                variadic,
                 returns,
                 body: Rc::new(Statement::Block(statements)),
+                public,
+                attributes,
+                doc,
                 span,
             });
         }
@@ -234,1210 +723,7570 @@ impl<'a> Typechecker<'a> {
         unreachable!()
     }
 
-    fn typecheck_global_assign(&mut self, expr: Statement<'a>) -> Result<Statement<'a>, String> {
-        if let Statement::Assign(mutable, name, type_annotation, value, span) = expr {
-            if mutable {
+    // Replaces generic struct types with their instances, reporting the
+    // first problem with any of them
+    fn resolve_statement(&mut self, stmt: &Statement<'a>) -> Result<Statement<'a>, String> {
+        let errors = self.errors.len();
+        let span = stmt.span().unwrap_or(Span { start: 0, end: 0 });
+        let stmt = map_statement_types(stmt, &mut |typ| self.resolve_type(typ, &span));
+
+        if self.errors.len() > errors {
+            return Err("invalid generic type".to_string());
+        }
+        Ok(stmt)
+    }
+
+    fn resolve_type(&mut self, typ: &AzulaType<'a>, span: &Span) -> AzulaType<'a> {
+        map_type(typ, &mut |typ| match typ {
+            AzulaType::Generic(name, args) => self.instantiate_struct(name, args, span),
+            AzulaType::Slice(inner) => {
+                self.instantiate_struct("Slice".to_string(), vec![inner.deref().clone()], span)
+            }
+            AzulaType::ConstArray(inner, size) => {
+                let size = self.constants.value(&size, span, &mut self.errors);
+                match self.array_size(size, span) {
+                    Some(size) => AzulaType::Array(inner, Some(size)),
+                    None => AzulaType::Array(inner, None),
+                }
+            }
+            AzulaType::Named(name) if self.interfaces.contains_key(name.as_str()) => {
+                AzulaType::Interface(name)
+            }
+            _ => typ,
+        })
+    }
+
+    // Evaluated sizes have to be ints, a failed evaluation has already been
+    // reported
+    fn array_size(&mut self, size: Option<ConstValue>, span: &Span) -> Option<usize> {
+        match size? {
+            ConstValue::Int(size) if size >= 0 => Some(size as usize),
+            size => {
                 self.errors.push(AzulaError::new(
-                    ErrorType::NonGlobalConstant,
+                    ErrorType::NonConstantArraySize(size.to_string()),
                     span.start,
                     span.end,
                 ));
-                return Err("Non constant at top-level".to_string());
+                None
             }
+        }
+    }
 
-            // let (expr, typ) = match self.typecheck_expression(value, &Environment::new()) {
-            //     Ok((expr, value)) => (expr, value),
-            //     Err(e) => return Err(e),
-            // };
-
-            let typ = match value.expression.clone() {
-                Expression::Integer(_) => AzulaType::Int,
-                Expression::Float(_) => AzulaType::Float,
-                Expression::Boolean(_) => AzulaType::Bool,
-                Expression::String(_) => AzulaType::Pointer(Rc::new(AzulaType::Str)),
-                Expression::Array(val) => {
-                    AzulaType::Array(Rc::new(val[0].typed.clone()), Some(val.len()))
-                }
+    // Pair<int, float> is the struct Pair.int.float, with its attributes'
+    // types substituted
+    fn instantiate_struct(
+        &mut self,
+        name: String,
+        args: Vec<AzulaType<'a>>,
+        span: &Span,
+    ) -> AzulaType<'a> {
+        let (generics, attributes, defaults, annotations, struct_span) =
+            match self.generic_structs.get(name.as_str()) {
+                Some(Statement::Struct {
+                    generics,
+                    attributes,
+                    defaults,
+                    annotations,
+                    span,
+                    ..
+                }) => (
+                    generics.clone(),
+                    attributes.clone(),
+                    defaults.clone(),
+                    annotations.clone(),
+                    span.clone(),
+                ),
                 _ => {
                     self.errors.push(AzulaError::new(
-                        ErrorType::NonGlobalConstant,
+                        ErrorType::UnknownStruct(name.clone()),
                         span.start,
                         span.end,
                     ));
-                    return Err("Non constant at top-level".to_string());
+                    return AzulaType::Named(name);
                 }
             };
+        if generics.len() != args.len() {
+            self.errors.push(AzulaError::new(
+                ErrorType::WrongTypeArgumentCount(name.clone(), generics.len(), args.len()),
+                span.start,
+                span.end,
+            ));
+            return AzulaType::Named(name);
+        }
 
-            if type_annotation.is_some() {
-                let type_annotation = type_annotation.clone().unwrap();
-
-                if type_annotation != typ {
-                    self.errors.push(AzulaError::new(
-                        ErrorType::MismatchedAssignTypes(
-                            format!("{:?}", type_annotation),
-                            format!("{:?}", typ),
-                        ),
-                        span.start,
-                        value.span.end,
-                    ));
-                    return Err("mismatched types in assign".to_string());
-                }
-            }
-
-            self.globals.insert(
-                name.clone(),
-                VariableDefinition {
-                    name: name.clone(),
-                    mutable,
-                    typ,
+        let mangled = mangle(&name, &args);
+        if !self.structs.contains_key(&mangled) {
+            let instance: &'a str = Box::leak(mangled.clone().into_boxed_str());
+            self.struct_instances
+                .insert(mangled.clone(), (name, args.clone()));
+            // Registered before its attributes are resolved, so that it can
+            // refer to itself through a pointer
+            self.structs.insert(
+                mangled.clone(),
+                StructDefinition {
+                    name: instance,
+                    attrs: vec![],
+                    defaults: defaults.clone(),
                 },
             );
 
-            Ok(Statement::Assign(
-                mutable,
-                name,
-                type_annotation,
-                value,
-                span,
-            ))
-        } else {
-            unreachable!()
+            let bindings: Bindings = generics.into_iter().zip(args).collect();
+            let attrs: Vec<_> = attributes
+                .iter()
+                .map(|(typ, attr)| {
+                    let typ = substitute_type(typ, &bindings);
+                    (self.resolve_type(&typ, span), *attr)
+                })
+                .collect();
+            self.structs.get_mut(&mangled).unwrap().attrs = attrs.clone();
+            self.struct_statements.push(Statement::Struct {
+                name: instance,
+                generics: vec![],
+                attributes: attrs,
+                defaults,
+                annotations,
+                doc: None,
+                span: struct_span,
+            });
         }
+
+        AzulaType::Named(mangled)
     }
 
-    fn typecheck_assign(
+    fn bind_type_arguments(
         &mut self,
-        expr: Statement<'a>,
-        env: &mut Environment<'a>,
-    ) -> Result<(Statement<'a>, AzulaType<'a>), String> {
-        if let Statement::Assign(mutable, name, type_annotation, value, span) = expr {
-            let (expr, typ) = match self.typecheck_expression(value, env) {
-                Ok((expr, value)) => (expr, value),
-                Err(e) => return Err(e),
-            };
-
-            if type_annotation.is_some() {
-                let mut type_annotation = type_annotation.clone().unwrap();
-
-                if let AzulaType::Array(arr_typ, size) = typ.clone() {
-                    if let AzulaType::Array(inner_type, inner_size) = type_annotation.clone() {
-                        if arr_typ != inner_type {
-                            self.errors.push(AzulaError::new(
-                                ErrorType::MismatchedAssignTypes(
-                                    format!("{:?}", type_annotation),
-                                    format!("{:?}", typ),
-                                ),
-                                span.start,
-                                expr.span.end,
-                            ));
-                            return Err("mismatched types in assign".to_string());
-                        }
-
-                        if size.is_some() && inner_size.is_some() {
-                            if size.unwrap() != inner_size.unwrap() {
-                                self.errors.push(AzulaError::new(
-                                    ErrorType::MismatchedAssignTypes(
-                                        format!("{:?}", type_annotation),
-                                        format!("{:?}", typ),
-                                    ),
-                                    span.start,
-                                    expr.span.end,
-                                ));
-                                return Err("mismatched types in assign".to_string());
-                            }
-                        }
+        param: &AzulaType<'a>,
+        typ: &AzulaType<'a>,
+        generics: &[&'a str],
+        bindings: &mut Bindings<'a>,
+        span: &Span,
+    ) -> Result<(), String> {
+        if let Err((generic, first, second)) =
+            bind(param, typ, generics, &self.struct_instances, bindings)
+        {
+            self.errors.push(AzulaError::new(
+                ErrorType::ConflictingTypeArguments(
+                    generic,
+                    format!("{:?}", first),
+                    format!("{:?}", second),
+                ),
+                span.start,
+                span.end,
+            ));
+            return Err("conflicting type arguments".to_string());
+        }
 
-                        type_annotation = AzulaType::Array(arr_typ, size);
-                    }
-                }
+        Ok(())
+    }
 
-                if type_annotation != typ {
+    // The inferred type arguments in order, as long as they were all inferred
+    fn bound_type_arguments(
+        &mut self,
+        name: &str,
+        generics: &[&'a str],
+        bindings: &Bindings<'a>,
+        span: &Span,
+    ) -> Result<Vec<AzulaType<'a>>, String> {
+        let mut types = vec![];
+        for generic in generics {
+            match bindings.get(generic) {
+                Some(typ) => types.push(typ.clone()),
+                None => {
                     self.errors.push(AzulaError::new(
-                        ErrorType::MismatchedAssignTypes(
-                            format!("{:?}", type_annotation),
-                            format!("{:?}", typ),
-                        ),
+                        ErrorType::UninferredTypeParameter(generic.to_string(), name.to_string()),
                         span.start,
-                        expr.span.end,
+                        span.end,
                     ));
-                    return Err("mismatched types in assign".to_string());
+                    return Err("uninferred type parameter".to_string());
                 }
             }
+        }
 
-            env.add_variable(
-                name.clone(),
-                VariableDefinition {
-                    name: name.clone(),
-                    mutable,
-                    typ: typ,
-                },
-            );
+        Ok(types)
+    }
 
-            Ok((
-                Statement::Assign(mutable, name, type_annotation, expr, span),
-                AzulaType::Void,
-            ))
-        } else {
-            unreachable!()
+    // Checks a struct literal gives each field without a default once with a
+    // value of its type, putting the values in the order the fields are
+    // declared
+    fn struct_fields(
+        &mut self,
+        name: &str,
+        mut values: Vec<(&'a str, ExpressionNode<'a>)>,
+        span: &Span,
+    ) -> Result<Vec<(&'a str, ExpressionNode<'a>)>, String> {
+        let (attrs, defaults) = match self.structs.get(name) {
+            Some(definition) => (definition.attrs.clone(), definition.defaults.clone()),
+            None => {
+                self.errors.push(AzulaError::new(
+                    ErrorType::UnknownStruct(name.to_string()),
+                    span.start,
+                    span.end,
+                ));
+                return Err("unknown struct".to_string());
+            }
+        };
+
+        if let Some((field, value)) = values
+            .iter()
+            .find(|(field, _)| !attrs.iter().any(|(_, attr)| attr == field))
+        {
+            self.errors.push(AzulaError::new(
+                ErrorType::UnknownStructMember(field.to_string(), name.to_string()),
+                value.span.start,
+                value.span.end,
+            ));
+            return Err("unknown struct member".to_string());
+        }
+
+        let mut fields = vec![];
+        for (typ, attr) in attrs {
+            let given = values.iter().position(|(field, _)| *field == attr);
+            let default = defaults.iter().find(|(field, _)| *field == attr);
+            let value = match (given, default) {
+                (Some(index), _) => values.remove(index).1,
+                (None, Some((_, default))) => {
+                    let default = self.typecheck_default(default.clone(), &typ)?;
+                    fields.push((attr, default));
+                    continue;
+                }
+                (None, None) => {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::MissingField(name.to_string(), attr.to_string()),
+                        span.start,
+                        span.end,
+                    ));
+                    return Err("missing field".to_string());
+                }
+            };
+            let value_typ = value.typed.clone();
+            let (value, value_typ) = self.coerce(value, value_typ, &typ)?;
+            if value_typ != typ {
+                self.errors.push(AzulaError::new(
+                    ErrorType::MismatchedFieldType(
+                        attr.to_string(),
+                        format!("{:?}", typ),
+                        format!("{:?}", value_typ),
+                    ),
+                    value.span.start,
+                    value.span.end,
+                ));
+                return Err("mismatched field type".to_string());
+            }
+            fields.push((attr, value));
         }
+
+        Ok(fields)
     }
 
-    fn typecheck_reassign(
+    fn infer_struct_instance(
         &mut self,
-        expr: Statement<'a>,
-        env: &mut Environment<'a>,
-    ) -> Result<(Statement<'a>, AzulaType<'a>), String> {
-        if let Statement::Reassign(var, val, span) = expr {
-            let (val, typ) = match self.typecheck_expression(val, env) {
-                Ok((expr, value)) => (expr, value),
-                Err(e) => return Err(e),
+        name: String,
+        values: &[(&'a str, AzulaType<'a>)],
+        span: &Span,
+    ) -> Result<String, String> {
+        let (generics, attributes) = match &self.generic_structs[name.as_str()] {
+            Statement::Struct {
+                generics,
+                attributes,
+                ..
+            } => (generics.clone(), attributes.clone()),
+            _ => unreachable!(),
+        };
+
+        let mut bindings = Bindings::new();
+        for (attr, typ) in values {
+            let param = match attributes.iter().find(|(_, name)| name == attr) {
+                Some((param, _)) => param,
+                None => continue,
             };
+            self.bind_type_arguments(param, typ, &generics, &mut bindings, span)?;
+        }
+        let args = self.bound_type_arguments(&name, &generics, &bindings, span)?;
 
-            let mut mutable = true;
-            match var.expression {
-                Expression::Identifier(ref v) => match env.variable_definitions.get(v) {
-                    Some(var) => {
-                        mutable = var.mutable;
-                    }
-                    _ => {
-                        self.errors.push(AzulaError::new(
-                            ErrorType::UnknownVariable(v.clone()),
-                            var.span.start,
-                            var.span.end,
-                        ));
-                        return Err("unknown variable".to_string());
-                    }
-                },
-                Expression::ArrayAccess(..) => {}
-                Expression::StructAccess(..) => {}
-                _ => {
-                    unreachable!("{:?}", var.expression)
-                }
-            }
+        match self.instantiate_struct(name, args, span) {
+            AzulaType::Named(instance) => Ok(instance),
+            _ => unreachable!(),
+        }
+    }
 
-            if !mutable {
+    // The methods in an impl become functions named Type.method, checked
+    // against the interface with Self standing for the type
+    fn lower_impl(&mut self, stmt: Statement<'a>) -> Result<Vec<Statement<'a>>, String> {
+        let (interface, typ, methods, span) = match stmt {
+            Statement::Impl {
+                interface,
+                typ,
+                methods,
+                span,
+            } => (interface, typ, methods, span),
+            _ => unreachable!(),
+        };
+        let signatures = match self.interfaces.get(interface) {
+            Some(signatures) => signatures.clone(),
+            None => {
                 self.errors.push(AzulaError::new(
-                    ErrorType::ConstantAssign,
-                    var.span.start,
-                    var.span.end,
+                    ErrorType::UnknownInterface(interface.to_string()),
+                    span.start,
+                    span.end,
                 ));
-                return Err("constant assign".to_string());
+                return Err("unknown interface".to_string());
             }
+        };
 
-            let (variable, var_type) = match self.typecheck_expression(var, env) {
-                Ok((expr, value)) => (expr, value),
-                Err(e) => return Err(e),
+        let type_name = typ.mangled();
+        let bindings: Bindings = [("Self", typ.clone())].into_iter().collect();
+        let mut functions = vec![];
+        for method in &methods {
+            let (name, args, returns, method_span) = match method {
+                Statement::Function {
+                    name,
+                    args,
+                    returns,
+                    span,
+                    ..
+                } => (*name, args, returns, span),
+                _ => unreachable!(),
+            };
+            let (_, params, expected) = match signatures.iter().find(|(m, ..)| *m == name) {
+                Some(signature) => signature,
+                None => {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::UnknownMethod(name.to_string(), interface.to_string()),
+                        method_span.start,
+                        method_span.end,
+                    ));
+                    return Err("unknown method".to_string());
+                }
             };
 
-            if var_type != typ {
+            let matches = params.len() == args.len()
+                && params.iter().zip(args).all(|((param, _), (arg, _))| {
+                    substitute_type(param, &bindings) == substitute_type(arg, &bindings)
+                })
+                && substitute_type(expected, &bindings) == substitute_type(returns, &bindings);
+            if !matches {
                 self.errors.push(AzulaError::new(
-                    ErrorType::MismatchedAssignTypes(
-                        format!("{:?}", var_type),
+                    ErrorType::MismatchedMethodSignature(name.to_string(), interface.to_string()),
+                    method_span.start,
+                    method_span.end,
+                ));
+                return Err("mismatched method signature".to_string());
+            }
+
+            match substitute_statement(method, &bindings) {
+                Statement::Function {
+                    generics,
+                    bounds,
+                    args,
+                    arg_spans,
+                    defaults,
+                    variadic,
+                    returns,
+                    body,
+                    doc,
+                    span,
+                    ..
+                } => functions.push(Statement::Function {
+                    name: Box::leak(format!("{}.{}", type_name, name).into_boxed_str()),
+                    generics,
+                    bounds,
+                    args,
+                    arg_spans,
+                    defaults,
+                    variadic,
+                    returns,
+                    body,
+                    public: false,
+                    attributes: vec![],
+                    doc,
+                    span,
+                }),
+                _ => unreachable!(),
+            }
+        }
+
+        for (name, ..) in &signatures {
+            let implemented = methods
+                .iter()
+                .any(|method| matches!(method, Statement::Function { name: m, .. } if m == name));
+            if !implemented {
+                self.errors.push(AzulaError::new(
+                    ErrorType::MissingMethod(
+                        interface.to_string(),
                         format!("{:?}", typ),
+                        name.to_string(),
                     ),
                     span.start,
-                    val.span.end,
+                    span.end,
                 ));
-                return Err("mismatched types in assign".to_string());
+                return Err("missing method".to_string());
             }
-
-            Ok((Statement::Reassign(variable, val, span), AzulaType::Void))
-        } else {
-            unreachable!()
         }
+
+        self.implementations.insert((type_name, interface));
+        Ok(functions)
     }
 
-    fn typecheck_return(
+    // Imported declarations join the rest of the program, the names each
+    // module declares are kept to check qualified names against. Private
+    // functions are renamed to module::name, so each module can have its own.
+    // Anything else shares one namespace and can only be declared by one of
+    // them, `owners` has the module of each name so far, None for the main
+    // file
+    fn flatten_imports(
         &mut self,
-        expr: Statement<'a>,
-        env: &mut Environment<'a>,
-    ) -> Result<(Statement<'a>, AzulaType<'a>), String> {
-        if let Statement::Return(ref value, ref span) = expr {
-            if value.is_none() {
-                return Ok((expr.clone(), AzulaType::Void));
+        body: Vec<Statement<'a>>,
+        module: Option<&'a str>,
+        owners: &mut HashMap<String, Option<&'a str>>,
+    ) -> Result<Vec<Statement<'a>>, String> {
+        let mut flattened = vec![];
+        for stmt in body {
+            match stmt {
+                Statement::Import(module, mut body, _) => {
+                    let declared = body.iter().filter_map(declared_name);
+                    self.modules.entry(module).or_default().extend(declared);
+                    for stmt in &mut body {
+                        if let Statement::Function { name, public, .. } = stmt {
+                            if !*public {
+                                *name = Box::leak(private_name(module, name).into_boxed_str());
+                            }
+                            self.function_modules.insert(name, (module, *public));
+                        }
+                    }
+                    flattened.extend(self.flatten_imports(body, Some(module), owners)?);
+                }
+                stmt => {
+                    // The same extern function can be declared by each
+                    // module using it
+                    let name = match &stmt {
+                        Statement::ExternFunction { .. } => None,
+                        stmt => declared_name(stmt),
+                    };
+                    if let Some(name) = name {
+                        if let Some(owner) = owners.insert(name.clone(), module) {
+                            let span = stmt.span().unwrap();
+                            self.errors.push(AzulaError::new(
+                                ErrorType::DuplicateDeclaration(name, owner.map(str::to_string)),
+                                span.start,
+                                span.end,
+                            ));
+                            return Err("duplicate declaration".to_string());
+                        }
+                    }
+                    flattened.push(stmt);
+                }
             }
-            let value = value.as_ref().unwrap();
-            let (expr, typ) = match self.typecheck_expression(value.clone(), env) {
-                Ok((expr, value)) => (expr, value),
-                Err(e) => return Err(e),
-            };
-
-            Ok((Statement::Return(Some(expr.clone()), span.clone()), typ))
-        } else {
-            unreachable!()
         }
+
+        Ok(flattened)
     }
 
-    fn typecheck_if(
-        &mut self,
-        stmt: Statement<'a>,
-        env: &mut Environment<'a>,
-    ) -> Result<(Statement<'a>, AzulaType<'a>), String> {
-        if let Statement::If(ref expr, ref body, ref span) = stmt {
-            let (expr, typ) = match self.typecheck_expression(expr.clone(), env) {
-                Ok((expr, value)) => (expr, value),
-                Err(e) => return Err(e),
-            };
+    // sizeof and new need a type with a known layout
+    fn check_sized(&mut self, typ: &AzulaType<'a>, span: &Span) -> Result<(), String> {
+        let error = match typ {
+            AzulaType::Named(name) if !self.structs.contains_key(name) => {
+                ErrorType::UnknownStruct(name.clone())
+            }
+            AzulaType::Void | AzulaType::Infer => ErrorType::UnsizedType(format!("{:?}", typ)),
+            _ => return Ok(()),
+        };
+        self.errors
+            .push(AzulaError::new(error, span.start, span.end));
+        Err("unsized type".to_string())
+    }
 
-            if typ != AzulaType::Bool {
+    // The private function a plain name in the current module refers to,
+    // its own before any other. Failing that, one of another module's, which
+    // check_visible then reports, if no function has the plain name
+    fn resolve_private(&self, name: &str) -> Option<String> {
+        if let Some(module) = self.current_module {
+            let own = private_name(module, name);
+            if self.functions.contains_key(own.as_str()) {
+                return Some(own);
+            }
+        }
+        if self.functions.contains_key(name) {
+            return None;
+        }
+
+        self.function_modules
+            .iter()
+            .filter(|(function, (module, public))| {
+                !public && **function == private_name(module, name)
+            })
+            .map(|(function, _)| function.to_string())
+            .min()
+    }
+
+    // Functions without pub can only be used in the module declaring them
+    fn check_visible(&mut self, name: &str, span: &Span) -> Result<(), String> {
+        match self.function_modules.get(name).copied() {
+            Some((module, false)) if self.current_module != Some(module) => {
+                let name = name.strip_prefix(&format!("{}::", module)).unwrap_or(name);
                 self.errors.push(AzulaError::new(
-                    ErrorType::NonBoolCondition(format!("{:?}", typ)),
-                    expr.span.start,
-                    expr.span.end,
+                    ErrorType::PrivateFunction(name.to_string(), module.to_string()),
+                    span.start,
+                    span.end,
                 ));
-                return Err("Non boolean condition".to_string());
+                Err("private function".to_string())
             }
+            _ => Ok(()),
+        }
+    }
 
-            let mut stmts = vec![];
-            for stmt in body {
-                match self.typecheck_statement(stmt.clone(), env) {
-                    Ok((stmt, _)) => stmts.push(stmt),
-                    Err(e) => return Err(e),
-                };
+    // module.name refers to name, which has to be declared by the module.
+    // Apart from private functions, modules share one namespace, so it's the
+    // same as the plain name
+    fn qualified_name(
+        &mut self,
+        module: &ExpressionNode<'a>,
+        member: &ExpressionNode<'a>,
+        span: &Span,
+        env: &Environment<'a>,
+    ) -> Result<Option<ExpressionNode<'a>>, String> {
+        let (module, name) = match (&module.expression, &member.expression) {
+            (Expression::Identifier(module), Expression::Identifier(name))
+                if !env.variable_definitions.contains_key(module) =>
+            {
+                (module, name)
             }
+            _ => return Ok(None),
+        };
+        let declared = match self.modules.get(module.as_str()) {
+            Some(declared) => declared,
+            None => return Ok(None),
+        };
 
-            Ok((Statement::If(expr, stmts, span.clone()), AzulaType::Void))
-        } else {
-            unreachable!()
+        if !declared.contains(name) {
+            self.errors.push(AzulaError::new(
+                ErrorType::UnknownModuleMember(module.clone(), name.clone()),
+                member.span.start,
+                member.span.end,
+            ));
+            return Err("unknown module member".to_string());
         }
+
+        let private = private_name(module, name);
+        let name = match self.function_modules.get(private.as_str()) {
+            Some((_, false)) => private,
+            _ => name.clone(),
+        };
+        Ok(Some(ExpressionNode {
+            expression: Expression::Identifier(name),
+            typed: AzulaType::Infer,
+            span: span.clone(),
+        }))
     }
 
-    fn typecheck_while(
+    // value.method(args) calls Type.method(value, args) for the value's type,
+    // unless the value is a struct with a field of that name
+    fn method_call(
         &mut self,
-        stmt: Statement<'a>,
-        env: &mut Environment<'a>,
-    ) -> Result<(Statement<'a>, AzulaType<'a>), String> {
-        if let Statement::While(ref expr, ref body, ref span) = stmt {
-            let (expr, typ) = match self.typecheck_expression(expr.clone(), env) {
-                Ok((expr, value)) => (expr, value),
-                Err(e) => return Err(e),
-            };
+        function: &ExpressionNode<'a>,
+        args: &[ExpressionNode<'a>],
+        env: &Environment<'a>,
+    ) -> Result<Option<(ExpressionNode<'a>, Vec<ExpressionNode<'a>>)>, String> {
+        let (value, name, span) = match &function.expression {
+            Expression::StructAccess(value, method) => match &method.expression {
+                Expression::Identifier(name) => (value.deref().clone(), name, &method.span),
+                _ => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
 
-            if typ != AzulaType::Bool {
-                self.errors.push(AzulaError::new(
-                    ErrorType::NonBoolCondition(format!("{:?}", typ)),
-                    expr.span.start,
-                    expr.span.end,
-                ));
-                return Err("Non boolean condition".to_string());
+        let (_, typ) = self.typecheck_expression(value.clone(), env)?;
+        if let AzulaType::Named(struct_name) = &typ {
+            if let Some(definition) = self.structs.get(struct_name) {
+                if definition.attrs.iter().any(|(_, attr)| attr == name) {
+                    return Ok(None);
+                }
             }
+        }
+        let mangled = format!("{}.{}", typ.mangled(), name);
+        if !self.functions.contains_key(mangled.as_str()) {
+            return Ok(None);
+        }
 
-            let mut stmts = vec![];
-            for stmt in body {
-                match self.typecheck_statement(stmt.clone(), env) {
-                    Ok((stmt, _)) => stmts.push(stmt),
-                    Err(e) => return Err(e),
-                };
+        let mut call_args = vec![value];
+        call_args.extend(args.iter().cloned());
+        Ok(Some((
+            ExpressionNode {
+                expression: Expression::Identifier(mangled),
+                typed: AzulaType::Infer,
+                span: span.clone(),
+            },
+            call_args,
+        )))
+    }
+
+    // The method of an interface value is a closure that calls the
+    // implementation for whatever type the value holds
+    fn typecheck_interface_method(
+        &mut self,
+        value: ExpressionNode<'a>,
+        interface: &str,
+        access: Rc<ExpressionNode<'a>>,
+        span: Span,
+    ) -> Result<(ExpressionNode<'a>, AzulaType<'a>), String> {
+        let signature = match &access.expression {
+            Expression::Identifier(name) => self.interfaces[interface]
+                .iter()
+                .find(|(method, ..)| method == name)
+                .cloned(),
+            _ => None,
+        };
+        let (name, args, returns) = match signature {
+            Some(signature) => signature,
+            None => {
+                self.errors.push(AzulaError::new(
+                    ErrorType::UnknownMethod(
+                        format!("{:?}", access.expression),
+                        interface.to_string(),
+                    ),
+                    access.span.start,
+                    access.span.end,
+                ));
+                return Err("unknown method".to_string());
             }
+        };
 
-            Ok((Statement::While(expr, stmts, span.clone()), AzulaType::Void))
-        } else {
-            unreachable!()
+        // Only self is known to be the type behind the value
+        let params: Vec<_> = args.iter().skip(1).map(|(typ, _)| typ.clone()).collect();
+        let is_self = |typ: &AzulaType| *typ == AzulaType::Named("Self".to_string());
+        if params.iter().any(is_self) || is_self(&returns) {
+            self.errors.push(AzulaError::new(
+                ErrorType::NotDynamicMethod(name.to_string(), interface.to_string()),
+                access.span.start,
+                access.span.end,
+            ));
+            return Err("method uses Self".to_string());
         }
+
+        let typ = AzulaType::Function(params, Rc::new(returns));
+        Ok((
+            ExpressionNode {
+                expression: Expression::StructAccess(Rc::new(value), access),
+                typed: typ.clone(),
+                span,
+            },
+            typ,
+        ))
     }
 
-    fn typecheck_expression(
+    // A call to a generic function becomes a call to its instance for the
+    // argument types, which is queued to be checked if it's new
+    fn instantiate(
         &mut self,
-        mut expr: ExpressionNode<'a>,
+        function: Rc<ExpressionNode<'a>>,
+        args: &[ExpressionNode<'a>],
         env: &Environment<'a>,
-    ) -> Result<(ExpressionNode<'a>, AzulaType<'a>), String> {
-        match expr.expression {
-            Expression::Infix(..) => self.typecheck_infix_expression(expr, env),
-            Expression::Integer(_) => {
-                expr.typed = AzulaType::Int;
-                Ok((expr.clone(), AzulaType::Int))
-            }
-            Expression::Float(_) => {
-                expr.typed = AzulaType::Float;
-                Ok((expr.clone(), AzulaType::Float))
-            }
-            Expression::Boolean(_) => {
-                expr.typed = AzulaType::Bool;
-                Ok((expr.clone(), AzulaType::Bool))
-            }
-            Expression::String(_) => {
-                expr.typed = AzulaType::Pointer(Rc::new(AzulaType::Str));
-                Ok((expr.clone(), AzulaType::Pointer(Rc::new(AzulaType::Str))))
-            }
-            Expression::Identifier(ref name) => {
-                if name == "nil" {
-                    return Ok((expr.clone(), AzulaType::Void));
-                }
-                if let Some(variable) = env.variable_definitions.get(name) {
-                    expr.typed = variable.typ.clone().into();
+    ) -> Result<Rc<ExpressionNode<'a>>, String> {
+        let name = match &function.expression {
+            Expression::Identifier(name) => name.clone(),
+            _ => return Ok(function),
+        };
+        let (generics, params, names, variadic) = match self.functions.get(name.as_str()) {
+            Some(f) if !f.generics.is_empty() => (
+                f.generics.clone(),
+                f.args
+                    .iter()
+                    .map(|(typ, _)| typ.clone())
+                    .collect::<Vec<_>>(),
+                f.args.iter().map(|(_, name)| *name).collect::<Vec<_>>(),
+                f.variadic,
+            ),
+            _ => return Ok(function),
+        };
 
-                    Ok((expr.clone(), variable.typ.clone()))
-                } else if let Some(variable) = self.globals.get(name) {
-                    expr.typed = variable.typ.clone().into();
+        let fixed = params.len() - variadic as usize;
+        let slots = self.order_arguments(&name, args.to_vec(), &names[..fixed])?;
+        let mut bindings = Bindings::new();
+        for (index, arg) in slots.into_iter().enumerate() {
+            let arg = match arg {
+                Some(arg) => arg,
+                None => continue,
+            };
+            let param = match &params[index.min(params.len() - 1)] {
+                AzulaType::Slice(inner) if index >= fixed => inner.deref().clone(),
+                param => param.clone(),
+            };
 
-                    Ok((expr.clone(), variable.typ.clone()))
-                } else {
-                    self.errors.push(AzulaError::new(
-                        ErrorType::UnknownVariable(name.clone()),
-                        expr.span.start,
-                        expr.span.end,
-                    ));
-                    return Err("Unknown variable".to_string());
-                }
-            }
-            Expression::FunctionCall { function, args } => {
-                let return_type = match &function.expression {
-                    Expression::Identifier(i) => match self.functions.get(&i.as_str()) {
-                        Some(f) => &f.returns,
-                        None => {
-                            if i == "printf" || i == "sprintf" || i == "puts" {
-                                &AzulaType::Void
-                            } else {
-                                self.errors.push(AzulaError::new(
-                                    ErrorType::FunctionNotFound(i.to_string()),
-                                    function.span.start,
-                                    function.span.end,
-                                ));
-                                return Err("Function not found".to_string());
-                            }
-                        }
-                    },
-                    _ => todo!(),
-                }
-                .clone();
+            let span = arg.span.clone();
+            let (_, typ) = self.typecheck_expression(arg, env)?;
+            self.bind_type_arguments(&param, &typ, &generics, &mut bindings, &span)?;
+        }
+        let types = self.bound_type_arguments(&name, &generics, &bindings, &function.span)?;
 
-                let mut new_args = vec![];
-                for arg in args.clone() {
-                    let (arg, _) = match self.typecheck_expression(arg, env) {
-                        Ok((arg, typ)) => (arg, typ),
-                        Err(e) => return Err(e),
-                    };
-                    new_args.push(arg);
-                }
+        let bounds = match &self.generic_functions[name.as_str()] {
+            Statement::Function { bounds, .. } => bounds.clone(),
+            _ => unreachable!(),
+        };
+        for (generic, interface) in bounds {
+            let error_type = if !self.interfaces.contains_key(interface) {
+                ErrorType::UnknownInterface(interface.to_string())
+            } else if !self
+                .implementations
+                .contains(&(bindings[generic].mangled(), interface))
+            {
+                ErrorType::UnsatisfiedBound(
+                    format!("{:?}", bindings[generic]),
+                    interface.to_string(),
+                )
+            } else {
+                continue;
+            };
+            self.errors.push(AzulaError::new(
+                error_type,
+                function.span.start,
+                function.span.end,
+            ));
+            return Err("unsatisfied bound".to_string());
+        }
 
-                return Ok((
-                    ExpressionNode {
-                        expression: Expression::FunctionCall {
-                            function: function.clone(),
-                            args: new_args,
-                        },
-                        typed: return_type.clone(),
-                        span: expr.span,
-                    },
-                    return_type.clone(),
-                ));
+        let mangled = mangle(&name, &types);
+        if !self.functions.contains_key(mangled.as_str()) {
+            let mangled: &'a str = Box::leak(mangled.clone().into_boxed_str());
+            // Instances belong to the generic function's module
+            if let Some(owner) = self.function_modules.get(name.as_str()).copied() {
+                self.function_modules.insert(mangled, owner);
             }
-            Expression::Not(exp) => {
-                let (node, typ) = match self.typecheck_expression(exp.deref().clone(), env) {
-                    Ok((node, typ)) => (node, typ),
-                    Err(e) => return Err(e),
-                };
-
-                if typ != AzulaType::Bool {
-                    self.errors.push(AzulaError::new(
-                        ErrorType::NonBoolCondition(format!("{:?}", typ)),
-                        expr.span.start,
-                        expr.span.end,
-                    ));
-
-                    return Err("Non-bool in condition".to_string());
-                }
+            let definition = &self.functions[name.as_str()];
+            let (args, returns) = (definition.args.clone(), definition.returns.clone());
+            let (defaults, variadic, varargs) = (
+                definition.defaults.clone(),
+                definition.variadic,
+                definition.varargs,
+            );
 
-                return Ok((
-                    ExpressionNode {
-                        expression: Expression::Not(Rc::new(node)),
-                        typed: typ,
-                        span: expr.span,
-                    },
-                    AzulaType::Bool,
-                ));
-            }
-            Expression::Pointer(exp) => {
-                let (node, typ) = match self.typecheck_expression(exp.deref().clone(), env) {
-                    Ok((node, typ)) => (node, typ),
-                    Err(e) => return Err(e),
-                };
+            let span = function.span.clone();
+            let args = args
+                .iter()
+                .map(|(typ, name)| {
+                    let typ = substitute_type(typ, &bindings);
+                    (self.resolve_type(&typ, &span), *name)
+                })
+                .collect();
+            let returns = self.resolve_type(&substitute_type(&returns, &bindings), &span);
+            self.functions.insert(
+                mangled,
+                FunctionDefinition {
+                    name: mangled,
+                    args,
+                    defaults,
+                    variadic,
+                    varargs,
+                    returns,
+                    generics: vec![],
+                },
+            );
 
-                return Ok((
-                    ExpressionNode {
-                        expression: Expression::Pointer(Rc::new(node)),
-                        typed: AzulaType::Pointer(Rc::new(typ.clone())),
-                        span: expr.span,
-                    },
-                    AzulaType::Pointer(Rc::new(typ)),
-                ));
+            let generic = substitute_statement(&self.generic_functions[name.as_str()], &bindings);
+            match self.resolve_statement(&generic)? {
+                Statement::Function {
+                    args,
+                    arg_spans,
+                    defaults,
+                    variadic,
+                    returns,
+                    body,
+                    attributes,
+                    doc,
+                    span,
+                    ..
+                } => self.instances.push(Statement::Function {
+                    name: mangled,
+                    generics: vec![],
+                    bounds: vec![],
+                    args,
+                    arg_spans,
+                    defaults,
+                    variadic,
+                    returns,
+                    body,
+                    public: false,
+                    attributes,
+                    doc,
+                    span,
+                }),
+                _ => unreachable!(),
             }
-            Expression::Array(items) => {
-                let typs = items
-                    .iter()
-                    .map(|v| self.typecheck_expression(v.clone(), env))
-                    .collect::<Vec<_>>();
-
-                if typs.is_empty() {
-                    return Ok((
-                        ExpressionNode {
-                            expression: Expression::Array(vec![]),
-                            typed: AzulaType::Array(Rc::new(AzulaType::Infer), Some(0)),
-                            span: expr.span,
-                        },
-                        AzulaType::Array(Rc::new(AzulaType::Infer), Some(0)),
-                    ));
-                }
+        }
 
-                let first_typ = &typs[0].as_ref().unwrap().1;
+        Ok(Rc::new(ExpressionNode {
+            expression: Expression::Identifier(mangled),
+            typed: function.typed.clone(),
+            span: function.span.clone(),
+        }))
+    }
 
-                for val in typs.clone() {
-                    let (node, typ) = match val {
-                        Ok((node, typ)) => (node, typ),
-                        Err(_) => continue,
+    // Defaults are evaluated at each call site, so they can only refer to
+    // globals and other functions
+    // Named arguments go in the slot of the param they name, any slots they
+    // skip over are left empty for defaults
+    fn order_arguments(
+        &mut self,
+        function: &str,
+        args: Vec<ExpressionNode<'a>>,
+        names: &[&'a str],
+    ) -> Result<Vec<Option<ExpressionNode<'a>>>, String> {
+        let mut slots: Vec<Option<ExpressionNode>> = vec![];
+        let mut named = false;
+        for arg in args {
+            match arg.expression {
+                Expression::NamedArgument(name, value) => {
+                    named = true;
+                    let index = match names.iter().position(|param| *param == name) {
+                        Some(index) => index,
+                        None => {
+                            self.errors.push(AzulaError::new(
+                                ErrorType::UnknownArgument(function.to_string(), name.to_string()),
+                                arg.span.start,
+                                arg.span.end,
+                            ));
+                            return Err("unknown argument".to_string());
+                        }
                     };
-                    if typ != first_typ.clone() {
+                    if slots.len() <= index {
+                        slots.resize(index + 1, None);
+                    }
+                    if slots[index].is_some() {
                         self.errors.push(AzulaError::new(
-                            ErrorType::MismatchedTypes(
-                                format!("{:?}", typ),
-                                format!("{:?}", first_typ),
-                            ),
-                            node.span.start,
-                            node.span.end,
+                            ErrorType::DuplicateArgument(name.to_string()),
+                            arg.span.start,
+                            arg.span.end,
                         ));
+                        return Err("duplicate argument".to_string());
                     }
+                    slots[index] = Some(value.deref().clone());
                 }
-
-                Ok((
-                    ExpressionNode {
-                        expression: Expression::Array(
-                            typs.clone()
-                                .iter()
-                                .map(|s| s.as_ref().unwrap().clone())
-                                .map(|(node, _)| node)
-                                .collect(),
-                        ),
-                        typed: AzulaType::Array(Rc::new(first_typ.clone()), Some(typs.len())),
-                        span: expr.span,
-                    },
-                    AzulaType::Array(Rc::new(first_typ.clone()), Some(typs.len())),
-                ))
+                _ if named => {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::PositionalAfterNamed,
+                        arg.span.start,
+                        arg.span.end,
+                    ));
+                    return Err("positional after named".to_string());
+                }
+                _ => slots.push(Some(arg)),
             }
-            Expression::ArrayAccess(array, index) => {
-                let (array, array_typ) = self
-                    .typecheck_expression(array.deref().clone(), env)
-                    .unwrap();
+        }
 
-                let (index, typ) = self
-                    .typecheck_expression(index.deref().clone(), env)
-                    .unwrap();
+        Ok(slots)
+    }
 
-                if typ != AzulaType::Int {
+    fn typecheck_default(
+        &mut self,
+        default: ExpressionNode<'a>,
+        param: &AzulaType<'a>,
+    ) -> Result<ExpressionNode<'a>, String> {
+        let (default, typ) = self.typecheck_expression(default, &Environment::new())?;
+        let (default, typ) = self.coerce(default, typ, param)?;
+        if typ != *param {
+            self.errors.push(AzulaError::new(
+                ErrorType::MismatchedTypes(format!("{:?}", param), format!("{:?}", typ)),
+                default.span.start,
+                default.span.end,
+            ));
+            return Err("mismatched default".to_string());
+        }
+
+        Ok(default)
+    }
+
+    fn warn(&mut self, warning_type: WarningType, start: usize, end: usize) {
+        let warning = AzulaWarning::new(warning_type, start, end);
+        if !warning.is_ignored(&self.ignores) {
+            self.warnings.push(warning);
+        }
+    }
+
+    fn typecheck_global_assign(&mut self, expr: Statement<'a>) -> Result<Statement<'a>, String> {
+        if let Statement::Assign(mutable, name, type_annotation, value, attributes, span) = expr {
+            self.check_attributes(&attributes, &[])?;
+            // let (expr, typ) = match self.typecheck_expression(value, &Environment::new()) {
+            //     Ok((expr, value)) => (expr, value),
+            //     Err(e) => return Err(e),
+            // };
+
+            // Folded to a literal, a constant's value is worked out once
+            // however many others use it
+            let folded = if mutable {
+                self.constants.evaluate(&value, &mut self.errors)
+            } else {
+                self.constants.define(&name, &value);
+                self.constants.value(&name, &value.span, &mut self.errors)
+            };
+            let value = match folded {
+                Some(folded) => folded.literal(value.span),
+                None => return Err("Non constant value at top-level".to_string()),
+            };
+            let typ = value.typed.clone();
+
+            if type_annotation.is_some() {
+                let type_annotation = type_annotation.clone().unwrap();
+
+                if type_annotation != typ {
                     self.errors.push(AzulaError::new(
-                        ErrorType::NonIntIndex(format!("{:?}", typ)),
-                        array.span.start,
-                        index.span.end,
+                        ErrorType::MismatchedAssignTypes(
+                            format!("{:?}", type_annotation),
+                            format!("{:?}", typ),
+                        ),
+                        span.start,
+                        value.span.end,
                     ));
-
-                    return Err("Non int index".to_string());
+                    return Err("mismatched types in assign".to_string());
                 }
+            }
 
-                let return_typ = if array_typ.is_indexable() {
-                    match array_typ {
-                        AzulaType::Array(nested, _) => nested.deref().clone(),
-                        AzulaType::Pointer(nested) => match nested.deref().clone() {
-                            AzulaType::Str => AzulaType::SizedSignedInt(8),
-                            _ => nested.deref().clone(),
-                        },
-                        _ => unreachable!(),
-                    }
-                } else {
+            self.globals.insert(
+                name.clone(),
+                VariableDefinition {
+                    name: name.clone(),
+                    mutable,
+                    typ,
+                },
+            );
+
+            Ok(Statement::Assign(
+                mutable,
+                name,
+                type_annotation,
+                value,
+                attributes,
+                span,
+            ))
+        } else {
+            unreachable!()
+        }
+    }
+
+    fn typecheck_destructure(
+        &mut self,
+        stmt: Statement<'a>,
+        env: &mut Environment<'a>,
+    ) -> Result<(Statement<'a>, AzulaType<'a>), String> {
+        if let Statement::Destructure(mutable, names, value, span) = stmt {
+            let (value, typ) = self.typecheck_expression(value, env)?;
+
+            let types = match &typ {
+                AzulaType::Tuple(types) if types.len() == names.len() => types.clone(),
+                _ => {
                     self.errors.push(AzulaError::new(
-                        ErrorType::NonArrayInIndex(format!("{:?}", array_typ)),
-                        array.span.start,
-                        array.span.end,
+                        ErrorType::DestructureMismatch(names.len(), format!("{:?}", typ)),
+                        span.start,
+                        value.span.end,
                     ));
-                    return Err("non-array in index".to_string());
-                };
+                    return Err("cannot destructure".to_string());
+                }
+            };
 
-                return Ok((
-                    ExpressionNode {
-                        expression: Expression::ArrayAccess(Rc::new(array), Rc::new(index)),
-                        typed: return_typ.clone(),
-                        span: expr.span,
+            for (name, typ) in names.iter().zip(types) {
+                env.add_variable(
+                    name.to_string(),
+                    VariableDefinition {
+                        name: name.to_string(),
+                        mutable,
+                        typ,
                     },
-                    return_typ,
-                ));
+                );
+                self.declared_variables
+                    .push((name.to_string(), span.clone()));
             }
-            Expression::StructInitialisation(struc, attrs) => {
-                let name = match &struc.clone().expression {
-                    Expression::Identifier(s) => s.clone(),
-                    _ => unreachable!(),
-                };
 
-                let mut attrs_new = vec![];
-                for (name, attr) in attrs.iter() {
-                    let expr = match self.typecheck_expression(attr.clone(), env) {
-                        Ok((expr, _)) => expr,
-                        Err(e) => return Err(e),
-                    };
-                    attrs_new.push((*name, expr));
-                }
+            Ok((
+                Statement::Destructure(mutable, names, value, span),
+                AzulaType::Void,
+            ))
+        } else {
+            unreachable!()
+        }
+    }
 
-                return Ok((
-                    ExpressionNode {
-                        expression: Expression::StructInitialisation(struc, attrs_new),
-                        typed: AzulaType::Named(name.clone()),
-                        span: expr.span,
-                    },
-                    AzulaType::Named(name.clone()),
+    fn typecheck_assign(
+        &mut self,
+        expr: Statement<'a>,
+        env: &mut Environment<'a>,
+    ) -> Result<(Statement<'a>, AzulaType<'a>), String> {
+        if let Statement::Assign(mutable, name, type_annotation, value, attributes, span) = expr {
+            // Locals can't have any
+            self.check_attributes(&attributes, &[])?;
+            let type_annotation = match type_annotation {
+                Some(typ) => Some(self.resolve_typeof(&typ, &span, env)?),
+                None => None,
+            };
+            let (expr, typ) = match self.typecheck_expression(value, env) {
+                Ok((expr, value)) => (expr, value),
+                Err(e) => return Err(e),
+            };
+
+            let (expr, typ) = match &type_annotation {
+                Some(annotation) => match self.coerce(expr, typ, annotation) {
+                    Ok(x) => x,
+                    Err(e) => return Err(e),
+                },
+                None => (expr, typ),
+            };
+
+            if type_annotation.is_none() && !is_inferable(&typ) {
+                self.errors.push(AzulaError::new(
+                    ErrorType::CannotInferType(name.clone(), format!("{:?}", typ)),
+                    span.start,
+                    expr.span.end,
                 ));
+                return Err("cannot infer type".to_string());
             }
-            Expression::StructAccess(struc, access) => {
-                let (struc, struc_type) =
-                    match self.typecheck_expression(struc.deref().clone(), env) {
-                        Ok(x) => x,
-                        Err(e) => return Err(e),
-                    };
 
-                let struc_name = match struc_type {
-                    AzulaType::Named(s) => s,
-                    AzulaType::Pointer(nested) => match nested.deref().clone() {
-                        AzulaType::Named(s) => s,
-                        _ => {
+            if type_annotation.is_some() {
+                let mut type_annotation = type_annotation.clone().unwrap();
+
+                if let AzulaType::Array(arr_typ, size) = typ.clone() {
+                    if let AzulaType::Array(inner_type, inner_size) = type_annotation.clone() {
+                        if arr_typ != inner_type {
                             self.errors.push(AzulaError::new(
-                                ErrorType::AccessNonStruct,
-                                struc.span.start,
-                                struc.span.end,
+                                ErrorType::MismatchedAssignTypes(
+                                    format!("{:?}", type_annotation),
+                                    format!("{:?}", typ),
+                                ),
+                                span.start,
+                                expr.span.end,
                             ));
-                            return Err("accessing non-struct".to_string());
+                            return Err("mismatched types in assign".to_string());
                         }
-                    },
-                    _ => {
-                        self.errors.push(AzulaError::new(
-                            ErrorType::AccessNonStruct,
-                            struc.span.start,
-                            struc.span.end,
-                        ));
-                        return Err("accessing non-struct".to_string());
+
+                        if size.is_some() && inner_size.is_some() {
+                            if size.unwrap() != inner_size.unwrap() {
+                                self.errors.push(AzulaError::new(
+                                    ErrorType::MismatchedAssignTypes(
+                                        format!("{:?}", type_annotation),
+                                        format!("{:?}", typ),
+                                    ),
+                                    span.start,
+                                    expr.span.end,
+                                ));
+                                return Err("mismatched types in assign".to_string());
+                            }
+                        }
+
+                        type_annotation = AzulaType::Array(arr_typ, size);
                     }
-                };
+                }
 
-                let struct_type = if let Some(struct_type) = self.structs.get(&struc_name) {
-                    struct_type
-                } else {
+                if type_annotation != typ {
                     self.errors.push(AzulaError::new(
-                        ErrorType::UnknownStruct(struc_name),
-                        struc.span.start,
-                        struc.span.end,
+                        ErrorType::MismatchedAssignTypes(
+                            format!("{:?}", type_annotation),
+                            format!("{:?}", typ),
+                        ),
+                        span.start,
+                        expr.span.end,
                     ));
-                    return Err("Struct not found".to_string());
-                };
+                    return Err("mismatched types in assign".to_string());
+                }
+            }
 
-                let member_name = match &access.expression {
-                    Expression::Identifier(s) => s,
-                    _ => {
-                        self.errors.push(AzulaError::new(
-                            ErrorType::AccessNonStruct,
-                            access.span.start,
-                            access.span.end,
-                        ));
-                        return Err("accessing non-struct".to_string());
+            env.add_variable(
+                name.clone(),
+                VariableDefinition {
+                    name: name.clone(),
+                    mutable,
+                    typ: typ.clone(),
+                },
+            );
+            self.declared_variables.push((name.clone(), span.clone()));
+
+            // Record the inferred type so later passes see the same thing as an
+            // explicit annotation
+            let type_annotation = type_annotation.or(Some(typ));
+
+            Ok((
+                Statement::Assign(mutable, name, type_annotation, expr, attributes, span),
+                AzulaType::Void,
+            ))
+        } else {
+            unreachable!()
+        }
+    }
+
+    fn typecheck_reassign(
+        &mut self,
+        expr: Statement<'a>,
+        env: &mut Environment<'a>,
+    ) -> Result<(Statement<'a>, AzulaType<'a>), String> {
+        if let Statement::Reassign(var, mut val, span) = expr {
+            // x++ is parsed as x = x + 1 with the same x on both sides, and
+            // its 1 is made x's type
+            if let Expression::Infix(left, operator, one) = &val.expression {
+                if left.deref() == &var && matches!(one.expression, Expression::Integer(1)) {
+                    let (_, typ) = self.typecheck_expression(var.clone(), env)?;
+                    if typ.integer_width().is_some() || typ.is_float() {
+                        let one = ExpressionNode {
+                            expression: Expression::Cast(one.clone(), typ.clone()),
+                            typed: typ,
+                            span: one.span.clone(),
+                        };
+                        val.expression =
+                            Expression::Infix(left.clone(), operator.clone(), Rc::new(one));
                     }
-                };
+                }
+            }
 
-                let typ = match struct_type
-                    .attrs
-                    .iter()
-                    .find(|(_, name)| name.to_string() == member_name.clone())
+            let (val, typ) = match self.typecheck_expression(val, env) {
+                Ok((expr, value)) => (expr, value),
+                Err(e) => return Err(e),
+            };
+
+            let mut mutable = true;
+            match var.expression {
+                Expression::Identifier(ref v) => match env
+                    .variable_definitions
+                    .get(v)
+                    .or_else(|| self.globals.get(v))
                 {
-                    Some((typ, _)) => typ,
+                    Some(var) => {
+                        mutable = var.mutable;
+                    }
                     _ => {
                         self.errors.push(AzulaError::new(
-                            ErrorType::UnknownStructMember(member_name.clone(), struc_name),
-                            access.span.start,
-                            access.span.end,
+                            ErrorType::UnknownVariable(v.clone()),
+                            var.span.start,
+                            var.span.end,
                         ));
-                        return Err("unknown struct member".to_string());
+                        return Err("unknown variable".to_string());
                     }
-                };
+                },
+                Expression::ArrayAccess(..) => {}
+                Expression::StructAccess(..) => {}
+                // What a pointer points to can always be written
+                Expression::Deref(..) => {}
+                _ => {
+                    unreachable!("{:?}", var.expression)
+                }
+            }
 
-                return Ok((
-                    ExpressionNode {
-                        expression: Expression::StructAccess(Rc::new(struc), access),
-                        typed: typ.clone(),
-                        span: expr.span,
-                    },
-                    typ.clone(),
+            if !mutable {
+                self.errors.push(AzulaError::new(
+                    ErrorType::ConstantAssign,
+                    var.span.start,
+                    var.span.end,
                 ));
+                return Err("constant assign".to_string());
             }
-        }
-    }
 
-    fn typecheck_infix_expression(
-        &mut self,
-        mut expr: ExpressionNode<'a>,
-        env: &Environment<'a>,
-    ) -> Result<(ExpressionNode<'a>, AzulaType<'a>), String> {
-        if let Expression::Infix(ref left, ref operator, ref right) = expr.expression {
-            let (left, left_typ) = match self.typecheck_expression(left.deref().clone(), env) {
-                Ok((left, typ)) => (left, typ),
-                Err(e) => return Err(e),
+            // Writing to a plain variable doesn't count as reading it
+            let written = match &var.expression {
+                Expression::Identifier(name) if !self.used_variables.contains(name) => {
+                    Some(name.clone())
+                }
+                _ => None,
             };
 
-            let (right, right_typ) = match self.typecheck_expression(right.deref().clone(), env) {
-                Ok((right, typ)) => (right, typ),
+            let (variable, var_type) = match self.typecheck_expression(var, env) {
+                Ok((expr, value)) => (expr, value),
                 Err(e) => return Err(e),
             };
 
-            let allowed = hashmap! {
-                Operator::Add => vec![AzulaType::Int, AzulaType::Float],
-                Operator::Sub => vec![AzulaType::Int, AzulaType::Float],
-                Operator::Mul => vec![AzulaType::Int, AzulaType::Float],
-                Operator::Div => vec![AzulaType::Int, AzulaType::Float],
-                Operator::Mod => vec![AzulaType::Int, AzulaType::Float],
-                Operator::Power => vec![AzulaType::Int, AzulaType::Float],
-                Operator::Or => vec![AzulaType::Bool],
-                Operator::And => vec![AzulaType::Bool],
-                Operator::Eq => vec![AzulaType::Int, AzulaType::Float, AzulaType::Bool],
-                Operator::Neq => vec![AzulaType::Int, AzulaType::Float, AzulaType::Bool],
-                Operator::Lt => vec![AzulaType::Int, AzulaType::Float],
-                Operator::Lte => vec![AzulaType::Int, AzulaType::Float],
-                Operator::Gt => vec![AzulaType::Int, AzulaType::Float],
-                Operator::Gte => vec![AzulaType::Int, AzulaType::Float],
+            // Structs are values, so a field belongs to the variable holding
+            // the struct and only a pointer lets it be changed elsewhere
+            if let Some(name) = struct_owner(&variable) {
+                let owner = env
+                    .variable_definitions
+                    .get(name)
+                    .or_else(|| self.globals.get(name));
+                if owner.map_or(false, |owner| !owner.mutable) {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::ConstantAssign,
+                        variable.span.start,
+                        variable.span.end,
+                    ));
+                    return Err("constant assign".to_string());
+                }
+            }
+
+            if let Some(name) = written {
+                self.used_variables.remove(&name);
+            }
+
+            let (val, typ) = match self.coerce(val, typ, &var_type) {
+                Ok(x) => x,
+                Err(e) => return Err(e),
             };
 
-            let allowed = allowed.get(operator).unwrap();
-            if !allowed.contains(&left_typ) {
+            if var_type != typ {
                 self.errors.push(AzulaError::new(
-                    ErrorType::NonOperatorType(
-                        format!("{:?}", left_typ),
-                        format!("{:?}", operator),
+                    ErrorType::MismatchedAssignTypes(
+                        format!("{:?}", var_type),
+                        format!("{:?}", typ),
                     ),
-                    left.span.start,
-                    left.span.end,
+                    span.start,
+                    val.span.end,
                 ));
-                return Err("cannot use operator with type".to_string());
+                return Err("mismatched types in assign".to_string());
             }
 
-            if !allowed.contains(&right_typ) {
+            Ok((Statement::Reassign(variable, val, span), AzulaType::Void))
+        } else {
+            unreachable!()
+        }
+    }
+
+    fn typecheck_return(
+        &mut self,
+        expr: Statement<'a>,
+        env: &mut Environment<'a>,
+    ) -> Result<(Statement<'a>, AzulaType<'a>), String> {
+        if let Statement::Return(ref value, ref span) = expr {
+            // A bare return from a !void function is its success case
+            if value.is_none() && self.returns == AzulaType::Result(Rc::new(AzulaType::Void)) {
+                let value = ExpressionNode {
+                    expression: Expression::Cast(
+                        Rc::new(ExpressionNode {
+                            expression: Expression::Identifier("nil".to_string()),
+                            typed: AzulaType::Void,
+                            span: span.clone(),
+                        }),
+                        self.returns.clone(),
+                    ),
+                    typed: self.returns.clone(),
+                    span: span.clone(),
+                };
+                return Ok((
+                    Statement::Return(Some(value), span.clone()),
+                    self.returns.clone(),
+                ));
+            }
+            if value.is_none() {
+                return Ok((expr.clone(), AzulaType::Void));
+            }
+            let value = value.as_ref().unwrap();
+            let (expr, typ) = match self.typecheck_expression(value.clone(), env) {
+                Ok((expr, value)) => (expr, value),
+                Err(e) => return Err(e),
+            };
+            let returns = self.returns.clone();
+            let (expr, typ) = self.coerce(expr, typ, &returns)?;
+            if typ != returns {
                 self.errors.push(AzulaError::new(
-                    ErrorType::NonOperatorType(
-                        format!("{:?}", right_typ),
-                        format!("{:?}", operator),
+                    ErrorType::IncorrectFunctionReturn(
+                        format!("{:?}", returns),
+                        format!("{:?}", typ),
                     ),
-                    right.span.start,
-                    right.span.end,
+                    expr.span.start,
+                    expr.span.end,
                 ));
-                return Err("cannot use operator with type".to_string());
+                return Err("incorrect return type".to_string());
             }
 
-            match operator {
-                Operator::Add => {
-                    if left_typ != right_typ {
-                        self.errors.push(AzulaError::new(
-                            ErrorType::MismatchedTypes(
-                                format!("{:?}", left_typ),
-                                format!("{:?}", right_typ),
-                            ),
-                            left.span.start,
-                            right.span.end,
-                        ));
-                        return Err("mismatched types in infix".to_string());
-                    }
+            Ok((Statement::Return(Some(expr.clone()), span.clone()), typ))
+        } else {
+            unreachable!()
+        }
+    }
 
-                    expr.typed = left.clone().typed;
-                    Ok((
-                        ExpressionNode {
-                            expression: Expression::Infix(
-                                Rc::new(left),
-                                operator.clone(),
-                                Rc::new(right),
-                            ),
-                            typed: left_typ.clone().into(),
-                            span: expr.span,
+    fn typecheck_if_let(
+        &mut self,
+        stmt: Statement<'a>,
+        env: &mut Environment<'a>,
+    ) -> Result<(Statement<'a>, AzulaType<'a>), String> {
+        if let Statement::IfLet(mutable, name, expr, body, span) = stmt {
+            let (expr, typ) = self.typecheck_expression(expr, env)?;
+
+            let inner = match &typ {
+                AzulaType::Optional(inner) => inner.deref().clone(),
+                _ => {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::NonOptional(format!("{:?}", typ)),
+                        expr.span.start,
+                        expr.span.end,
+                    ));
+                    return Err("non optional in if var".to_string());
+                }
+            };
+
+            // The binding is only in scope for the body
+            let mut env = env.clone();
+            env.add_variable(
+                name.to_string(),
+                VariableDefinition {
+                    name: name.to_string(),
+                    mutable,
+                    typ: inner,
+                },
+            );
+            self.declared_variables
+                .push((name.to_string(), span.clone()));
+
+            let stmts = self.typecheck_body(body, &mut env)?;
+
+            Ok((
+                Statement::IfLet(mutable, name, expr, stmts, span),
+                AzulaType::Void,
+            ))
+        } else {
+            unreachable!()
+        }
+    }
+
+    fn typecheck_if(
+        &mut self,
+        stmt: Statement<'a>,
+        env: &mut Environment<'a>,
+    ) -> Result<(Statement<'a>, AzulaType<'a>), String> {
+        if let Statement::If(ref expr, ref body, ref span) = stmt {
+            let (expr, typ) = match self.typecheck_expression(expr.clone(), env) {
+                Ok((expr, value)) => (expr, value),
+                Err(e) => return Err(e),
+            };
+
+            if typ != AzulaType::Bool {
+                self.errors.push(AzulaError::new(
+                    ErrorType::NonBoolCondition(format!("{:?}", typ), bool_hint(&typ)),
+                    expr.span.start,
+                    expr.span.end,
+                ));
+                return Err("Non boolean condition".to_string());
+            }
+
+            // What the body declares is dropped as it ends, so it goes out of
+            // scope with it
+            let stmts = self.typecheck_body(body.clone(), &mut env.clone())?;
+
+            Ok((Statement::If(expr, stmts, span.clone()), AzulaType::Void))
+        } else {
+            unreachable!()
+        }
+    }
+
+    fn typecheck_while(
+        &mut self,
+        stmt: Statement<'a>,
+        env: &mut Environment<'a>,
+    ) -> Result<(Statement<'a>, AzulaType<'a>), String> {
+        if let Statement::While(ref expr, ref body, label, ref span) = stmt {
+            let (expr, typ) = match self.typecheck_expression(expr.clone(), env) {
+                Ok((expr, value)) => (expr, value),
+                Err(e) => return Err(e),
+            };
+
+            if typ != AzulaType::Bool {
+                self.errors.push(AzulaError::new(
+                    ErrorType::NonBoolCondition(format!("{:?}", typ), bool_hint(&typ)),
+                    expr.span.start,
+                    expr.span.end,
+                ));
+                return Err("Non boolean condition".to_string());
+            }
+
+            self.loops.push(label);
+            let stmts = self.typecheck_body(body.clone(), &mut env.clone())?;
+            self.loops.pop();
+
+            Ok((
+                Statement::While(expr, stmts, label, span.clone()),
+                AzulaType::Void,
+            ))
+        } else {
+            unreachable!()
+        }
+    }
+
+    // for x in xs binds x to each element of a sized array or slice in turn,
+    // or each value of a range of ints. for i, x binds i to its position too
+    fn typecheck_for(
+        &mut self,
+        stmt: Statement<'a>,
+        env: &mut Environment<'a>,
+    ) -> Result<(Statement<'a>, AzulaType<'a>), String> {
+        if let Statement::For(index, name, iterable, body, label, span) = stmt {
+            let (iterable, typ) = self.typecheck_expression(iterable, env)?;
+            let element = match &typ {
+                AzulaType::Array(element, Some(_)) => Some(element.deref().clone()),
+                _ => self.slice_element(&typ).or_else(|| {
+                    self.instance_argument(&typ, "Range")
+                        .filter(|typ| *typ == AzulaType::Int)
+                }),
+            };
+            let element = match element {
+                Some(element) => element,
+                None => {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::NotIterable(format!("{:?}", typ)),
+                        iterable.span.start,
+                        iterable.span.end,
+                    ));
+                    return Err("not iterable".to_string());
+                }
+            };
+
+            // Like a parameter, a loop variable named _ isn't bound. The
+            // bindings are only in scope for the body
+            let mut inner = env.clone();
+            let bindings = [(index, AzulaType::Int), (Some(name), element)];
+            for (name, typ) in bindings {
+                if let Some(name) = name.filter(|name| *name != "_") {
+                    inner.add_variable(
+                        name.to_string(),
+                        VariableDefinition {
+                            name: name.to_string(),
+                            mutable: false,
+                            typ,
                         },
-                        left_typ,
-                    ))
+                    );
+                    self.declared_variables
+                        .push((name.to_string(), span.clone()));
                 }
-                Operator::Sub => {
-                    if left_typ != right_typ {
+            }
+
+            self.loops.push(label);
+            let stmts = self.typecheck_body(body, &mut inner)?;
+            self.loops.pop();
+
+            Ok((
+                Statement::For(index, name, iterable, stmts, label, span),
+                AzulaType::Void,
+            ))
+        } else {
+            unreachable!()
+        }
+    }
+
+    fn typecheck_do_while(
+        &mut self,
+        stmt: Statement<'a>,
+        env: &mut Environment<'a>,
+    ) -> Result<(Statement<'a>, AzulaType<'a>), String> {
+        if let Statement::DoWhile(expr, body, label, span) = stmt {
+            // The body is a scope of its own, so the condition only sees
+            // what's declared around the loop
+            self.loops.push(label);
+            let stmts = self.typecheck_body(body, &mut env.clone())?;
+            self.loops.pop();
+
+            let (expr, typ) = self.typecheck_expression(expr, env)?;
+            if typ != AzulaType::Bool {
+                self.errors.push(AzulaError::new(
+                    ErrorType::NonBoolCondition(format!("{:?}", typ), bool_hint(&typ)),
+                    expr.span.start,
+                    expr.span.end,
+                ));
+                return Err("Non boolean condition".to_string());
+            }
+
+            Ok((
+                Statement::DoWhile(expr, stmts, label, span),
+                AzulaType::Void,
+            ))
+        } else {
+            unreachable!()
+        }
+    }
+
+    fn typecheck_match(
+        &mut self,
+        stmt: Statement<'a>,
+        env: &mut Environment<'a>,
+    ) -> Result<(Statement<'a>, AzulaType<'a>), String> {
+        if let Statement::Match(expr, arms, span) = stmt {
+            let (expr, typ) = match self.typecheck_expression(expr, env) {
+                Ok((expr, value)) => (expr, value),
+                Err(e) => return Err(e),
+            };
+
+            if typ != AzulaType::Any {
+                self.errors.push(AzulaError::new(
+                    ErrorType::MatchNonAny(format!("{:?}", typ)),
+                    expr.span.start,
+                    expr.span.end,
+                ));
+                return Err("match on non-any".to_string());
+            }
+
+            // Only the first arm for a type can run, and nothing after `_`
+            let mut matched = vec![];
+            let mut new_arms = vec![];
+            for (arm_typ, binding, body, arm) in arms {
+                if let Some(arm_typ) = &arm_typ {
+                    if arm_typ.any_tag().is_none() {
                         self.errors.push(AzulaError::new(
-                            ErrorType::MismatchedTypes(
-                                format!("{:?}", left_typ),
-                                format!("{:?}", right_typ),
-                            ),
-                            left.span.start,
-                            right.span.end,
+                            ErrorType::NotConvertibleToAny(format!("{:?}", arm_typ)),
+                            arm.start,
+                            arm.end,
                         ));
-                        return Err("mismatched types in infix".to_string());
+                        return Err("not convertible to any".to_string());
                     }
+                }
+                if matched.contains(&None) || matched.contains(&arm_typ) {
+                    self.warn(WarningType::UnreachableArm, arm.start, arm.end);
+                }
+                matched.push(arm_typ.clone());
 
-                    expr.typed = left.clone().typed;
-                    Ok((
-                        ExpressionNode {
-                            expression: Expression::Infix(
-                                Rc::new(left),
-                                operator.clone(),
-                                Rc::new(right),
-                            ),
-                            typed: left_typ.clone().into(),
-                            span: expr.span,
+                // The binding is only in scope for its arm
+                let mut env = env.clone();
+                if let Some(binding) = binding {
+                    env.add_variable(
+                        binding.to_string(),
+                        VariableDefinition {
+                            name: binding.to_string(),
+                            mutable: false,
+                            typ: arm_typ.clone().unwrap_or(AzulaType::Any),
                         },
-                        left_typ,
-                    ))
+                    );
                 }
-                Operator::Mul => {
-                    if left_typ != right_typ {
+
+                let stmts = self.typecheck_body(body, &mut env)?;
+
+                new_arms.push((arm_typ, binding, stmts, arm));
+            }
+
+            Ok((Statement::Match(expr, new_arms, span), AzulaType::Void))
+        } else {
+            unreachable!()
+        }
+    }
+
+    fn typecheck_expression(
+        &mut self,
+        mut expr: ExpressionNode<'a>,
+        env: &Environment<'a>,
+    ) -> Result<(ExpressionNode<'a>, AzulaType<'a>), String> {
+        match expr.expression {
+            Expression::Infix(..) => self.typecheck_infix_expression(expr, env),
+            Expression::Integer(_) => {
+                expr.typed = AzulaType::Int;
+                Ok((expr.clone(), AzulaType::Int))
+            }
+            Expression::Float(_) => {
+                expr.typed = AzulaType::Float;
+                Ok((expr.clone(), AzulaType::Float))
+            }
+            Expression::Boolean(_) => {
+                expr.typed = AzulaType::Bool;
+                Ok((expr.clone(), AzulaType::Bool))
+            }
+            Expression::String(_) => {
+                expr.typed = AzulaType::Pointer(Rc::new(AzulaType::Str));
+                Ok((expr.clone(), AzulaType::Pointer(Rc::new(AzulaType::Str))))
+            }
+            Expression::Identifier(ref name) => {
+                if name == "nil" {
+                    expr.typed = AzulaType::Void;
+                    return Ok((expr.clone(), AzulaType::Void));
+                }
+                if let Some(variable) = env.variable_definitions.get(name) {
+                    self.used_variables.insert(name.clone());
+                    expr.typed = variable.typ.clone().into();
+
+                    Ok((expr.clone(), variable.typ.clone()))
+                } else if let Some(variable) = self.globals.get(name) {
+                    expr.typed = variable.typ.clone().into();
+
+                    Ok((expr.clone(), variable.typ.clone()))
+                } else if let Some(private) = self.resolve_private(name) {
+                    expr.expression = Expression::Identifier(private);
+                    self.typecheck_expression(expr, env)
+                } else if self.functions.contains_key(name.as_str()) {
+                    self.check_visible(name, &expr.span)?;
+                    let function = &self.functions[name.as_str()];
+                    // A named function used as a value, which has to be a
+                    // concrete one
+                    if let Some(generic) = function.generics.first() {
                         self.errors.push(AzulaError::new(
-                            ErrorType::MismatchedTypes(
-                                format!("{:?}", left_typ),
-                                format!("{:?}", right_typ),
-                            ),
-                            left.span.start,
-                            right.span.end,
+                            ErrorType::UninferredTypeParameter(generic.to_string(), name.clone()),
+                            expr.span.start,
+                            expr.span.end,
                         ));
-                        return Err("mismatched types in infix".to_string());
+                        return Err("uninferred type parameter".to_string());
                     }
 
-                    expr.typed = left.clone().typed;
+                    let typ = AzulaType::Function(
+                        function.args.iter().map(|(typ, _)| typ.clone()).collect(),
+                        Rc::new(function.returns.clone()),
+                    );
+                    expr.typed = typ.clone();
+
+                    Ok((expr.clone(), typ))
+                } else if let Some(number) = signal_number(name) {
                     Ok((
                         ExpressionNode {
-                            expression: Expression::Infix(
-                                Rc::new(left),
-                                operator.clone(),
-                                Rc::new(right),
-                            ),
-                            typed: left_typ.clone().into(),
+                            expression: Expression::Integer(number),
+                            typed: AzulaType::Int,
                             span: expr.span,
                         },
-                        left_typ,
+                        AzulaType::Int,
                     ))
-                }
-                Operator::Div => {
-                    if left_typ != right_typ {
-                        self.errors.push(AzulaError::new(
-                            ErrorType::MismatchedTypes(
-                                format!("{:?}", left_typ),
-                                format!("{:?}", right_typ),
-                            ),
-                            left.span.start,
-                            right.span.end,
-                        ));
-                        return Err("mismatched types in infix".to_string());
+                } else {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::UnknownVariable(name.clone()),
+                        expr.span.start,
+                        expr.span.end,
+                    ));
+                    return Err("Unknown variable".to_string());
+                }
+            }
+            Expression::FunctionCall { function, args } => {
+                let function = match &function.expression {
+                    Expression::StructAccess(module, member) => {
+                        match self.qualified_name(module, member, &function.span, env)? {
+                            Some(name) => Rc::new(name),
+                            None => function,
+                        }
                     }
+                    _ => function,
+                };
 
-                    expr.typed = left.clone().typed;
-                    Ok((
-                        ExpressionNode {
-                            expression: Expression::Infix(
-                                Rc::new(left),
-                                operator.clone(),
-                                Rc::new(right),
+                if let Expression::Identifier(i) = &function.expression {
+                    if i == "nameof" {
+                        return self.typecheck_nameof(args, expr.span, env);
+                    }
+                    if i == "type_name" {
+                        return self.typecheck_type_name(args, expr.span, env);
+                    }
+                    if i == "error" {
+                        return self.typecheck_error(args, expr.span, env);
+                    }
+                    if i == "build_info" {
+                        return self.typecheck_build_info(args, expr.span);
+                    }
+                    if i == "free" {
+                        return self.typecheck_free(function.deref().clone(), args, expr.span, env);
+                    }
+                    if MEMORY_ACCESS.contains(&i.as_str()) {
+                        return self.typecheck_memory_access(
+                            function.deref().clone(),
+                            args,
+                            expr.span,
+                            env,
+                        );
+                    }
+                    if BYTE_ORDERS.contains(&i.as_str()) {
+                        return self.typecheck_byte_order(
+                            function.deref().clone(),
+                            args,
+                            expr.span,
+                            env,
+                        );
+                    }
+                    if i == "len" {
+                        return self.typecheck_len(function.deref().clone(), args, expr.span, env);
+                    }
+                    if i == "on_signal" {
+                        return self.typecheck_on_signal(
+                            function.deref().clone(),
+                            args,
+                            expr.span,
+                            env,
+                        );
+                    }
+                }
+
+                let (function, args) = match self.method_call(&function, &args, env)? {
+                    Some((function, args)) => (Rc::new(function), args),
+                    None => (function, args),
+                };
+
+                // A local holding a function value takes the place of any
+                // function with the same name, anything else is called
+                // through its value
+                let is_value = match &function.expression {
+                    Expression::Identifier(name) => matches!(
+                        env.variable_definitions.get(name).map(|var| &var.typ),
+                        Some(AzulaType::Function(..))
+                    ),
+                    _ => true,
+                };
+                if is_value {
+                    return self.typecheck_closure_call(
+                        function.deref().clone(),
+                        args,
+                        expr.span,
+                        env,
+                    );
+                }
+
+                let private = match &function.expression {
+                    Expression::Identifier(name) => self.resolve_private(name),
+                    _ => None,
+                };
+                let function = match private {
+                    Some(private) => Rc::new(ExpressionNode {
+                        expression: Expression::Identifier(private),
+                        ..function.deref().clone()
+                    }),
+                    None => function,
+                };
+                if let Expression::Identifier(name) = &function.expression {
+                    self.check_visible(name, &function.span)?;
+                }
+                let function = self.instantiate(function, &args, env)?;
+
+                let return_type = match &function.expression {
+                    Expression::Identifier(i) => match self.functions.get(&i.as_str()) {
+                        Some(f) => &f.returns,
+                        None => {
+                            if i == "printf" || i == "sprintf" || i == "puts" {
+                                &AzulaType::Void
+                            } else {
+                                self.errors.push(AzulaError::new(
+                                    ErrorType::FunctionNotFound(i.to_string()),
+                                    function.span.start,
+                                    function.span.end,
+                                ));
+                                return Err("Function not found".to_string());
+                            }
+                        }
+                    },
+                    _ => unreachable!(),
+                }
+                .clone();
+
+                let (params, names, defaults, variadic): (Vec<_>, Vec<_>, _, _) =
+                    match &function.expression {
+                        Expression::Identifier(i) => match self.functions.get(&i.as_str()) {
+                            Some(f) => (
+                                f.args.iter().map(|(typ, _)| typ.clone()).collect(),
+                                f.args.iter().map(|(_, name)| *name).collect(),
+                                f.defaults.clone(),
+                                f.variadic,
                             ),
-                            typed: left_typ.clone().into(),
-                            span: expr.span,
+                            None => (vec![], vec![], vec![], false),
                         },
-                        left_typ,
-                    ))
+                        _ => (vec![], vec![], vec![], false),
+                    };
+                let name = match &function.expression {
+                    Expression::Identifier(name) => name.clone(),
+                    _ => "function".to_string(),
+                };
+
+                // Arguments past the fixed params of a variadic function are
+                // collected into a slice for its last param. A generic one's
+                // params haven't been resolved to the Slice instance yet
+                let fixed = params.len() - variadic as usize;
+                let rest_type = match params.last().filter(|_| variadic) {
+                    Some(AzulaType::Slice(inner)) => Some(inner.deref().clone()),
+                    Some(param) => self.slice_element(param),
+                    None => None,
+                };
+
+                let mut slots = self.order_arguments(&name, args, &names[..fixed])?;
+                let given = slots.iter().filter(|slot| slot.is_some()).count();
+                if slots.len() < fixed {
+                    slots.resize(fixed, None);
                 }
-                Operator::Mod => {
-                    if left_typ != right_typ {
+                let first_default = fixed - defaults.len();
+
+                let mut new_args = vec![];
+                let mut rest = vec![];
+                for (index, arg) in slots.into_iter().enumerate() {
+                    // Missing arguments are filled in from the defaults
+                    let arg = match arg {
+                        Some(arg) => arg,
+                        None if index >= first_default => {
+                            let default = self.typecheck_default(
+                                defaults[index - first_default].clone(),
+                                &params[index],
+                            )?;
+                            new_args.push(default);
+                            continue;
+                        }
+                        None => {
+                            let error_type = if given < first_default {
+                                ErrorType::NotEnoughArguments(name, first_default, given)
+                            } else {
+                                ErrorType::MissingArgument(name, names[index].to_string())
+                            };
+                            self.errors.push(AzulaError::new(
+                                error_type,
+                                expr.span.start,
+                                expr.span.end,
+                            ));
+                            return Err("not enough arguments".to_string());
+                        }
+                    };
+
+                    let (arg, typ) = match self.typecheck_expression(arg, env) {
+                        Ok((arg, typ)) => (arg, typ),
+                        Err(e) => return Err(e),
+                    };
+                    let param = if index < fixed {
+                        params.get(index)
+                    } else {
+                        rest_type.as_ref()
+                    };
+                    let arg = match param {
+                        Some(param) => match self.coerce(arg, typ, param) {
+                            Ok((arg, _)) => arg,
+                            Err(e) => return Err(e),
+                        },
+                        // C varargs take floats as doubles
+                        None => self.coerce(arg, typ, &AzulaType::Float)?.0,
+                    };
+                    if index >= fixed && rest_type.is_some() {
+                        rest.push(arg);
+                    } else {
+                        new_args.push(arg);
+                    }
+                }
+
+                if let Some(typ) = rest_type {
+                    let span = match (rest.first(), rest.last()) {
+                        (Some(first), Some(last)) => Span {
+                            start: first.span.start,
+                            end: last.span.end,
+                        },
+                        _ => expr.span.clone(),
+                    };
+                    let len = ExpressionNode {
+                        expression: Expression::Integer(rest.len() as i64),
+                        typed: AzulaType::Int,
+                        span: span.clone(),
+                    };
+                    let data = ExpressionNode {
+                        expression: Expression::Array(rest),
+                        typed: AzulaType::Array(Rc::new(typ.clone()), None),
+                        span: span.clone(),
+                    };
+                    new_args.push(self.make_slice(data, len, typ, span).0);
+                }
+
+                return Ok((
+                    ExpressionNode {
+                        expression: Expression::FunctionCall {
+                            function: function.clone(),
+                            args: new_args,
+                        },
+                        typed: return_type.clone(),
+                        span: expr.span,
+                    },
+                    return_type.clone(),
+                ));
+            }
+            Expression::Not(exp) => {
+                let (node, typ) = match self.typecheck_expression(exp.deref().clone(), env) {
+                    Ok((node, typ)) => (node, typ),
+                    Err(e) => return Err(e),
+                };
+
+                if typ != AzulaType::Bool {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::NonBoolCondition(format!("{:?}", typ), None),
+                        expr.span.start,
+                        expr.span.end,
+                    ));
+
+                    return Err("Non-bool in condition".to_string());
+                }
+
+                return Ok((
+                    ExpressionNode {
+                        expression: Expression::Not(Rc::new(node)),
+                        typed: typ,
+                        span: expr.span,
+                    },
+                    AzulaType::Bool,
+                ));
+            }
+            // Unsigned integers have no negative to go to
+            Expression::Negate(exp) => {
+                let (node, typ) = self.typecheck_expression(exp.deref().clone(), env)?;
+
+                let signed = matches!(typ, AzulaType::Int | AzulaType::SizedSignedInt(_));
+                if !signed && !typ.is_float() {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::NonOperatorType(format!("{:?}", typ), "-".to_string()),
+                        node.span.start,
+                        node.span.end,
+                    ));
+
+                    return Err("cannot use operator with type".to_string());
+                }
+
+                return Ok((
+                    ExpressionNode {
+                        expression: Expression::Negate(Rc::new(node)),
+                        typed: typ.clone(),
+                        span: expr.span,
+                    },
+                    typ,
+                ));
+            }
+            Expression::Pointer(exp) => {
+                let (node, typ) = match self.typecheck_expression(exp.deref().clone(), env) {
+                    Ok((node, typ)) => (node, typ),
+                    Err(e) => return Err(e),
+                };
+
+                return Ok((
+                    ExpressionNode {
+                        expression: Expression::Pointer(Rc::new(node)),
+                        typed: AzulaType::Pointer(Rc::new(typ.clone())),
+                        span: expr.span,
+                    },
+                    AzulaType::Pointer(Rc::new(typ)),
+                ));
+            }
+            Expression::Deref(exp) => {
+                let (node, typ) = self.typecheck_expression(exp.deref().clone(), env)?;
+                let inner = match &typ {
+                    AzulaType::Pointer(inner) => inner.deref().clone(),
+                    _ => {
                         self.errors.push(AzulaError::new(
-                            ErrorType::MismatchedTypes(
-                                format!("{:?}", left_typ),
-                                format!("{:?}", right_typ),
-                            ),
-                            left.span.start,
-                            right.span.end,
+                            ErrorType::DerefNonPointer(format!("{:?}", typ)),
+                            expr.span.start,
+                            expr.span.end,
                         ));
-                        return Err("mismatched types in infix".to_string());
+                        return Err("deref of non-pointer".to_string());
                     }
+                };
 
-                    expr.typed = left.clone().typed;
-                    Ok((
+                Ok((
+                    ExpressionNode {
+                        expression: Expression::Deref(Rc::new(node)),
+                        typed: inner.clone(),
+                        span: expr.span,
+                    },
+                    inner,
+                ))
+            }
+            Expression::Array(items) => {
+                let typs = items
+                    .iter()
+                    .map(|v| self.typecheck_expression(v.clone(), env))
+                    .collect::<Vec<_>>();
+
+                if typs.is_empty() {
+                    return Ok((
                         ExpressionNode {
-                            expression: Expression::Infix(
-                                Rc::new(left),
-                                operator.clone(),
-                                Rc::new(right),
-                            ),
-                            typed: left_typ.clone().into(),
+                            expression: Expression::Array(vec![]),
+                            typed: AzulaType::Array(Rc::new(AzulaType::Infer), Some(0)),
                             span: expr.span,
                         },
-                        left_typ,
-                    ))
+                        AzulaType::Array(Rc::new(AzulaType::Infer), Some(0)),
+                    ));
                 }
-                Operator::Power => {
-                    if left_typ != right_typ {
+
+                let first_typ = &typs[0].as_ref().unwrap().1;
+
+                for val in typs.clone() {
+                    let (node, typ) = match val {
+                        Ok((node, typ)) => (node, typ),
+                        Err(_) => continue,
+                    };
+                    if typ != first_typ.clone() {
                         self.errors.push(AzulaError::new(
                             ErrorType::MismatchedTypes(
-                                format!("{:?}", left_typ),
-                                format!("{:?}", right_typ),
+                                format!("{:?}", typ),
+                                format!("{:?}", first_typ),
                             ),
-                            left.span.start,
-                            right.span.end,
+                            node.span.start,
+                            node.span.end,
                         ));
-                        return Err("mismatched types in infix".to_string());
                     }
+                }
+
+                Ok((
+                    ExpressionNode {
+                        expression: Expression::Array(
+                            typs.clone()
+                                .iter()
+                                .map(|s| s.as_ref().unwrap().clone())
+                                .map(|(node, _)| node)
+                                .collect(),
+                        ),
+                        typed: AzulaType::Array(Rc::new(first_typ.clone()), Some(typs.len())),
+                        span: expr.span,
+                    },
+                    AzulaType::Array(Rc::new(first_typ.clone()), Some(typs.len())),
+                ))
+            }
+            Expression::ArrayAccess(array, index) => {
+                let (array, array_typ) = self
+                    .typecheck_expression(array.deref().clone(), env)
+                    .unwrap();
+
+                if let Expression::Range(start, end) = &index.expression {
+                    return self.typecheck_slice(
+                        array,
+                        array_typ,
+                        start.deref().clone(),
+                        end.deref().clone(),
+                        expr.span,
+                        env,
+                    );
+                }
+                // A slice is indexed through its elements
+                let (array, array_typ) = match self.slice_element(&array_typ) {
+                    Some(element) => slice_data(array, element),
+                    None => (array, array_typ),
+                };
+
+                let (index, typ) = self
+                    .typecheck_expression(index.deref().clone(), env)
+                    .unwrap();
+
+                if typ != AzulaType::Int {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::NonIntIndex(format!("{:?}", typ)),
+                        array.span.start,
+                        index.span.end,
+                    ));
+
+                    return Err("Non int index".to_string());
+                }
+
+                let return_typ = if array_typ.is_indexable() {
+                    match array_typ {
+                        AzulaType::Array(nested, _) => nested.deref().clone(),
+                        AzulaType::Pointer(nested) => match nested.deref().clone() {
+                            AzulaType::Str => AzulaType::SizedSignedInt(8),
+                            _ => nested.deref().clone(),
+                        },
+                        _ => unreachable!(),
+                    }
+                } else {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::NonArrayInIndex(format!("{:?}", array_typ)),
+                        array.span.start,
+                        array.span.end,
+                    ));
+                    return Err("non-array in index".to_string());
+                };
+
+                return Ok((
+                    ExpressionNode {
+                        expression: Expression::ArrayAccess(Rc::new(array), Rc::new(index)),
+                        typed: return_typ.clone(),
+                        span: expr.span,
+                    },
+                    return_typ,
+                ));
+            }
+            Expression::StructInitialisation(struc, attrs) => {
+                // module.Name { ... } is the module's struct
+                let struc = match &struc.expression {
+                    Expression::StructAccess(module, member) => {
+                        match self.qualified_name(module, member, &struc.span, env)? {
+                            Some(name) => Rc::new(name),
+                            None => struc,
+                        }
+                    }
+                    _ => struc,
+                };
+                let name = match &struc.expression {
+                    Expression::Identifier(s) => s.clone(),
+                    _ => {
+                        self.errors.push(AzulaError::new(
+                            ErrorType::InvalidStructLiteral,
+                            struc.span.start,
+                            struc.span.end,
+                        ));
+                        return Err("invalid struct literal".to_string());
+                    }
+                };
+
+                let mut attrs_new = vec![];
+                let mut attr_types = vec![];
+                for (name, attr) in attrs.iter() {
+                    if attr_types.iter().any(|(seen, _)| seen == name) {
+                        self.errors.push(AzulaError::new(
+                            ErrorType::DuplicateField(name.to_string()),
+                            attr.span.start,
+                            attr.span.end,
+                        ));
+                        return Err("duplicate field".to_string());
+                    }
+                    let (expr, typ) = match self.typecheck_expression(attr.clone(), env) {
+                        Ok(x) => x,
+                        Err(e) => return Err(e),
+                    };
+                    attrs_new.push((*name, expr));
+                    attr_types.push((*name, typ));
+                }
+
+                // A generic struct's type arguments come from its values
+                let (name, struc) = if self.generic_structs.contains_key(name.as_str()) {
+                    let name = self.infer_struct_instance(name, &attr_types, &expr.span)?;
+                    let struc = Rc::new(ExpressionNode {
+                        expression: Expression::Identifier(name.clone()),
+                        ..struc.deref().clone()
+                    });
+                    (name, struc)
+                } else {
+                    (name, struc)
+                };
+                let attrs_new = self.struct_fields(&name, attrs_new, &expr.span)?;
+
+                return Ok((
+                    ExpressionNode {
+                        expression: Expression::StructInitialisation(struc, attrs_new),
+                        typed: AzulaType::Named(name.clone()),
+                        span: expr.span,
+                    },
+                    AzulaType::Named(name.clone()),
+                ));
+            }
+            Expression::StructAccess(struc, access) => {
+                if let Some(name) = self.qualified_name(&struc, &access, &expr.span, env)? {
+                    return self.typecheck_expression(name, env);
+                }
+
+                let (struc, struc_type) =
+                    match self.typecheck_expression(struc.deref().clone(), env) {
+                        Ok(x) => x,
+                        Err(e) => return Err(e),
+                    };
+
+                if let AzulaType::Interface(interface) = &struc_type {
+                    return self.typecheck_interface_method(struc, interface, access, expr.span);
+                }
+
+                let struc_name = match struc_type {
+                    AzulaType::Named(s) => s,
+                    AzulaType::Pointer(nested) => match nested.deref().clone() {
+                        AzulaType::Named(s) => s,
+                        _ => {
+                            self.errors.push(AzulaError::new(
+                                ErrorType::AccessNonStruct,
+                                struc.span.start,
+                                struc.span.end,
+                            ));
+                            return Err("accessing non-struct".to_string());
+                        }
+                    },
+                    _ => {
+                        self.errors.push(AzulaError::new(
+                            ErrorType::AccessNonStruct,
+                            struc.span.start,
+                            struc.span.end,
+                        ));
+                        return Err("accessing non-struct".to_string());
+                    }
+                };
+
+                let struct_type = if let Some(struct_type) = self.structs.get(&struc_name) {
+                    struct_type
+                } else {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::UnknownStruct(struc_name),
+                        struc.span.start,
+                        struc.span.end,
+                    ));
+                    return Err("Struct not found".to_string());
+                };
+
+                let member_name = match &access.expression {
+                    Expression::Identifier(s) => s,
+                    _ => {
+                        self.errors.push(AzulaError::new(
+                            ErrorType::AccessNonStruct,
+                            access.span.start,
+                            access.span.end,
+                        ));
+                        return Err("accessing non-struct".to_string());
+                    }
+                };
+
+                let typ = match struct_type
+                    .attrs
+                    .iter()
+                    .find(|(_, name)| name.to_string() == member_name.clone())
+                {
+                    Some((typ, _)) => typ,
+                    _ => {
+                        self.errors.push(AzulaError::new(
+                            ErrorType::UnknownStructMember(member_name.clone(), struc_name),
+                            access.span.start,
+                            access.span.end,
+                        ));
+                        return Err("unknown struct member".to_string());
+                    }
+                };
+
+                return Ok((
+                    ExpressionNode {
+                        expression: Expression::StructAccess(Rc::new(struc), access),
+                        typed: typ.clone(),
+                        span: expr.span,
+                    },
+                    typ.clone(),
+                ));
+            }
+            Expression::Cast(inner, target) => {
+                let target = self.resolve_typeof(&target, &expr.span, env)?;
+                let (inner, typ) = match self.typecheck_expression(inner.deref().clone(), env) {
+                    Ok(x) => x,
+                    Err(e) => return Err(e),
+                };
+
+                if target == AzulaType::Any || typ == target {
+                    return self.coerce(inner, typ, &target);
+                }
+
+                if !is_numeric_cast(&typ, &target) {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::InvalidCast(format!("{:?}", typ), format!("{:?}", target)),
+                        expr.span.start,
+                        expr.span.end,
+                    ));
+                    return Err("invalid cast".to_string());
+                }
+
+                Ok((
+                    ExpressionNode {
+                        expression: Expression::Cast(Rc::new(inner), target.clone()),
+                        typed: target.clone(),
+                        span: expr.span,
+                    },
+                    target,
+                ))
+            }
+            Expression::SizeOf(typ) => {
+                let typ = self.resolve_typeof(&typ, &expr.span, env)?;
+                self.check_sized(&typ, &expr.span)?;
+
+                expr.expression = Expression::SizeOf(typ);
+                expr.typed = AzulaType::Int;
+                Ok((expr, AzulaType::Int))
+            }
+            Expression::New(typ) => {
+                let typ = self.resolve_typeof(&typ, &expr.span, env)?;
+                self.check_sized(&typ, &expr.span)?;
+
+                let pointer = AzulaType::Pointer(Rc::new(typ.clone()));
+                expr.expression = Expression::New(typ);
+                expr.typed = pointer.clone();
+                Ok((expr, pointer))
+            }
+            Expression::Tuple(values) => {
+                let mut typed_values = vec![];
+                let mut types = vec![];
+                for value in values {
+                    let (value, typ) = self.typecheck_expression(value, env)?;
+                    typed_values.push(value);
+                    types.push(typ);
+                }
+
+                let typ = AzulaType::Tuple(types);
+                Ok((
+                    ExpressionNode {
+                        expression: Expression::Tuple(typed_values),
+                        typed: typ.clone(),
+                        span: expr.span,
+                    },
+                    typ,
+                ))
+            }
+            Expression::Range(start, end) => {
+                self.typecheck_range(start.deref().clone(), end.deref().clone(), expr.span, env)
+            }
+            Expression::ArrayRepeat(value, count) => {
+                let size = self.constants.evaluate(&count, &mut self.errors);
+                let size = match self.array_size(size, &count.span) {
+                    Some(size) => size,
+                    None => return Err("non constant array size".to_string()),
+                };
+
+                let array = ExpressionNode {
+                    expression: Expression::Array(vec![value.deref().clone(); size]),
+                    typed: AzulaType::Array(Rc::new(AzulaType::Infer), Some(size)),
+                    span: expr.span,
+                };
+                self.typecheck_expression(array, env)
+            }
+            Expression::Ternary(cond, then, otherwise) => {
+                let (cond, cond_typ) = self.typecheck_expression(cond.deref().clone(), env)?;
+                if cond_typ != AzulaType::Bool {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::NonBoolCondition(
+                            format!("{:?}", cond_typ),
+                            bool_hint(&cond_typ),
+                        ),
+                        cond.span.start,
+                        cond.span.end,
+                    ));
+                    return Err("non bool condition".to_string());
+                }
+
+                let (then, then_typ) = self.typecheck_expression(then.deref().clone(), env)?;
+                let (otherwise, otherwise_typ) =
+                    self.typecheck_expression(otherwise.deref().clone(), env)?;
+
+                // Numeric branches widen to a common type like infix operands
+                let (then, typ, otherwise, otherwise_typ) =
+                    self.promote_operands(then, then_typ, otherwise, otherwise_typ)?;
+                if typ != otherwise_typ {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::MismatchedTypes(
+                            format!("{:?}", typ),
+                            format!("{:?}", otherwise_typ),
+                        ),
+                        then.span.start,
+                        otherwise.span.end,
+                    ));
+                    return Err("mismatched ternary branches".to_string());
+                }
+
+                Ok((
+                    ExpressionNode {
+                        expression: Expression::Ternary(
+                            Rc::new(cond),
+                            Rc::new(then),
+                            Rc::new(otherwise),
+                        ),
+                        typed: typ.clone(),
+                        span: expr.span,
+                    },
+                    typ,
+                ))
+            }
+            // Calls to functions put them in place before checking them, the
+            // builtins and closures only take arguments by position
+            Expression::NamedArgument(name, _) => {
+                self.errors.push(AzulaError::new(
+                    ErrorType::UnexpectedNamedArgument(name.to_string()),
+                    expr.span.start,
+                    expr.span.end,
+                ));
+                Err("unexpected named argument".to_string())
+            }
+            Expression::Closure { args, body, .. } => {
+                self.typecheck_closure(args, body.deref().clone(), expr.span, env)
+            }
+            Expression::Try(value, _) => {
+                let (value, typ) = self.typecheck_expression(value.deref().clone(), env)?;
+                let inner = match &typ {
+                    AzulaType::Result(inner) => inner.deref().clone(),
+                    _ => {
+                        self.errors.push(AzulaError::new(
+                            ErrorType::TryOnNonResult(format!("{:?}", typ)),
+                            value.span.start,
+                            value.span.end,
+                        ));
+                        return Err("try on non result".to_string());
+                    }
+                };
+
+                if !matches!(self.returns, AzulaType::Result(_)) {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::TryOutsideResultFunction,
+                        expr.span.start,
+                        expr.span.end,
+                    ));
+                    return Err("try outside result function".to_string());
+                }
+
+                Ok((
+                    ExpressionNode {
+                        expression: Expression::Try(Rc::new(value), self.returns.clone()),
+                        typed: inner.clone(),
+                        span: expr.span,
+                    },
+                    inner,
+                ))
+            }
+        }
+    }
+
+    // Insert any implicit conversion needed to use a value of type `typ` where
+    // `expected` is wanted. Values that don't need converting are returned as is.
+    fn coerce(
+        &mut self,
+        expr: ExpressionNode<'a>,
+        typ: AzulaType<'a>,
+        expected: &AzulaType<'a>,
+    ) -> Result<(ExpressionNode<'a>, AzulaType<'a>), String> {
+        // nil and values of the inner type both convert to an optional
+        if let AzulaType::Optional(inner) = expected {
+            let is_nil = matches!(&expr.expression, Expression::Identifier(name) if name == "nil");
+            if is_nil || typ == *inner.deref() {
+                let span = expr.span.clone();
+                return Ok((
+                    ExpressionNode {
+                        expression: Expression::Cast(Rc::new(expr), expected.clone()),
+                        typed: expected.clone(),
+                        span,
+                    },
+                    expected.clone(),
+                ));
+            }
+        }
+
+        // The same goes for a result, which also accepts error(msg)
+        if let AzulaType::Result(inner) = expected {
+            let is_error = matches!(&expr.expression, Expression::FunctionCall { function, .. }
+                if function.expression == Expression::Identifier("error".to_string()));
+            if (is_error && typ != *expected) || typ == *inner.deref() {
+                let span = expr.span.clone();
+                return Ok((
+                    ExpressionNode {
+                        expression: Expression::Cast(Rc::new(expr), expected.clone()),
+                        typed: expected.clone(),
+                        span,
+                    },
+                    expected.clone(),
+                ));
+            }
+        }
+
+        if *expected == AzulaType::Any && typ != AzulaType::Any {
+            if typ.any_tag().is_none() {
+                self.errors.push(AzulaError::new(
+                    ErrorType::NotConvertibleToAny(format!("{:?}", typ)),
+                    expr.span.start,
+                    expr.span.end,
+                ));
+                return Err("not convertible to any".to_string());
+            }
+
+            let span = expr.span.clone();
+            return Ok((
+                ExpressionNode {
+                    expression: Expression::Cast(Rc::new(expr), AzulaType::Any),
+                    typed: AzulaType::Any,
+                    span,
+                },
+                AzulaType::Any,
+            ));
+        }
+
+        // Values of a type with an impl convert to the interface, carrying
+        // the vtable for that type
+        if let AzulaType::Interface(interface) = expected {
+            if typ != *expected {
+                if !self
+                    .implementations
+                    .contains(&(typ.mangled(), interface.as_str()))
+                {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::UnsatisfiedBound(format!("{:?}", typ), interface.clone()),
+                        expr.span.start,
+                        expr.span.end,
+                    ));
+                    return Err("unsatisfied bound".to_string());
+                }
+
+                let span = expr.span.clone();
+                return Ok((
+                    ExpressionNode {
+                        expression: Expression::Cast(Rc::new(expr), expected.clone()),
+                        typed: expected.clone(),
+                        span,
+                    },
+                    expected.clone(),
+                ));
+            }
+        }
+
+        // An array whose length is known converts to a slice of all of it
+        if let (AzulaType::Array(element, Some(size)), Some(expected_element)) =
+            (&typ, self.slice_element(expected))
+        {
+            if *element.deref() == expected_element {
+                let span = expr.span.clone();
+                let len = ExpressionNode {
+                    expression: Expression::Integer(*size as i64),
+                    typed: AzulaType::Int,
+                    span: span.clone(),
+                };
+                return Ok(self.make_slice(expr, len, expected_element, span));
+            }
+        }
+
+        // Floats convert between widths, but only a literal narrows since
+        // anything else could silently lose precision
+        if typ.is_float() && expected.is_float() && typ != *expected {
+            let narrowing = float_width(&typ) > float_width(expected);
+            if !narrowing || matches!(expr.expression, Expression::Float(_)) {
+                let span = expr.span.clone();
+                return Ok((
+                    ExpressionNode {
+                        expression: Expression::Cast(Rc::new(expr), expected.clone()),
+                        typed: expected.clone(),
+                        span,
+                    },
+                    expected.clone(),
+                ));
+            }
+        }
+
+        Ok((expr, typ))
+    }
+
+    // start..end initialises a Range<T>, with integer bounds widened to a
+    // common type
+    fn typecheck_range(
+        &mut self,
+        start: ExpressionNode<'a>,
+        end: ExpressionNode<'a>,
+        span: Span,
+        env: &Environment<'a>,
+    ) -> Result<(ExpressionNode<'a>, AzulaType<'a>), String> {
+        let (start, start_typ) = self.typecheck_expression(start, env)?;
+        let (end, end_typ) = self.typecheck_expression(end, env)?;
+        if start_typ.integer_width().is_none() || end_typ.integer_width().is_none() {
+            self.errors.push(AzulaError::new(
+                ErrorType::InvalidRange(format!("{:?}", start_typ), format!("{:?}", end_typ)),
+                span.start,
+                span.end,
+            ));
+            return Err("invalid range".to_string());
+        }
+        let (start, start_typ, end, _) = self.promote_operands(start, start_typ, end, end_typ)?;
+
+        let typ = self.instantiate_struct("Range".to_string(), vec![start_typ], &span);
+        let name = match &typ {
+            AzulaType::Named(name) => name.clone(),
+            _ => unreachable!(),
+        };
+        let struc = ExpressionNode {
+            expression: Expression::Identifier(name),
+            typed: AzulaType::Infer,
+            span: span.clone(),
+        };
+        Ok((
+            ExpressionNode {
+                expression: Expression::StructInitialisation(
+                    Rc::new(struc),
+                    vec![("start", start), ("end", end)],
+                ),
+                typed: typ.clone(),
+                span,
+            },
+            typ,
+        ))
+    }
+
+    // a[start..end] is a Slice<T> of the elements from start up to end, from
+    // an array or another slice
+    fn typecheck_slice(
+        &mut self,
+        array: ExpressionNode<'a>,
+        array_typ: AzulaType<'a>,
+        start: ExpressionNode<'a>,
+        end: ExpressionNode<'a>,
+        span: Span,
+        env: &Environment<'a>,
+    ) -> Result<(ExpressionNode<'a>, AzulaType<'a>), String> {
+        let (array, element) = match (&array_typ, self.slice_element(&array_typ)) {
+            (AzulaType::Array(element, _), _) => (array, element.deref().clone()),
+            (_, Some(element)) => (slice_data(array, element.clone()).0, element),
+            _ => {
+                self.errors.push(AzulaError::new(
+                    ErrorType::NonArrayInIndex(format!("{:?}", array_typ)),
+                    array.span.start,
+                    array.span.end,
+                ));
+                return Err("non-array in slice".to_string());
+            }
+        };
+
+        let (start, start_typ) = self.typecheck_expression(start, env)?;
+        let (end, end_typ) = self.typecheck_expression(end, env)?;
+        for (bound, typ) in [(&start, &start_typ), (&end, &end_typ)] {
+            if *typ != AzulaType::Int {
+                self.errors.push(AzulaError::new(
+                    ErrorType::NonIntIndex(format!("{:?}", typ)),
+                    bound.span.start,
+                    bound.span.end,
+                ));
+                return Err("non-int slice bound".to_string());
+            }
+        }
+
+        // The start is read again for the length
+        let data = ExpressionNode {
+            expression: Expression::Pointer(Rc::new(ExpressionNode {
+                expression: Expression::ArrayAccess(Rc::new(array), Rc::new(start.clone())),
+                typed: element.clone(),
+                span: span.clone(),
+            })),
+            typed: AzulaType::Array(Rc::new(element.clone()), None),
+            span: span.clone(),
+        };
+        let len = ExpressionNode {
+            expression: Expression::Infix(Rc::new(end), Operator::Sub, Rc::new(start)),
+            typed: AzulaType::Int,
+            span: span.clone(),
+        };
+
+        Ok(self.make_slice(data, len, element, span))
+    }
+
+    fn make_slice(
+        &mut self,
+        data: ExpressionNode<'a>,
+        len: ExpressionNode<'a>,
+        element: AzulaType<'a>,
+        span: Span,
+    ) -> (ExpressionNode<'a>, AzulaType<'a>) {
+        let typ = self.instantiate_struct("Slice".to_string(), vec![element], &span);
+        let name = match &typ {
+            AzulaType::Named(name) => name.clone(),
+            _ => unreachable!(),
+        };
+        let struc = ExpressionNode {
+            expression: Expression::Identifier(name),
+            typed: AzulaType::Infer,
+            span: span.clone(),
+        };
+        (
+            ExpressionNode {
+                expression: Expression::StructInitialisation(
+                    Rc::new(struc),
+                    vec![("data", data), ("len", len)],
+                ),
+                typed: typ.clone(),
+                span,
+            },
+            typ,
+        )
+    }
+
+    // The element type of a Slice<T> instance
+    fn slice_element(&self, typ: &AzulaType<'a>) -> Option<AzulaType<'a>> {
+        self.instance_argument(typ, "Slice")
+    }
+
+    // T for an instance of the built in generic struct, Slice<T> or Range<T>
+    fn instance_argument(&self, typ: &AzulaType<'a>, generic: &str) -> Option<AzulaType<'a>> {
+        match typ {
+            AzulaType::Named(name) => match self.struct_instances.get(name) {
+                Some((instance_of, args)) if instance_of == generic => Some(args[0].clone()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    // Operands of different numeric types are widened to a common type by
+    // wrapping the narrower one in a cast, see `promoted_type` for the rules
+    fn promote_operands(
+        &mut self,
+        left: ExpressionNode<'a>,
+        left_typ: AzulaType<'a>,
+        right: ExpressionNode<'a>,
+        right_typ: AzulaType<'a>,
+    ) -> Result<
+        (
+            ExpressionNode<'a>,
+            AzulaType<'a>,
+            ExpressionNode<'a>,
+            AzulaType<'a>,
+        ),
+        String,
+    > {
+        let numeric = |typ: &AzulaType| typ.integer_width().is_some() || typ.is_float();
+        if left_typ == right_typ || !numeric(&left_typ) || !numeric(&right_typ) {
+            return Ok((left, left_typ, right, right_typ));
+        }
+
+        // A float literal takes the width of the other side instead of
+        // widening it, so `x * 2.0` stays f32
+        let is_literal = |expr: &ExpressionNode| matches!(expr.expression, Expression::Float(_));
+        let to = |expr: ExpressionNode<'a>, typ: &AzulaType<'a>| {
+            let span = expr.span.clone();
+            ExpressionNode {
+                expression: Expression::Cast(Rc::new(expr), typ.clone()),
+                typed: typ.clone(),
+                span,
+            }
+        };
+        if left_typ.is_float() && right_typ.is_float() && is_literal(&left) != is_literal(&right) {
+            return Ok(if is_literal(&right) {
+                let right = to(right, &left_typ);
+                (left, left_typ.clone(), right, left_typ)
+            } else {
+                let left = to(left, &right_typ);
+                (left, right_typ.clone(), right, right_typ)
+            });
+        }
+
+        let typ = match promoted_type(&left_typ, &right_typ) {
+            Some(typ) => typ,
+            None => {
+                self.errors.push(AzulaError::new(
+                    ErrorType::NoNumericPromotion(
+                        format!("{:?}", left_typ),
+                        format!("{:?}", right_typ),
+                    ),
+                    left.span.start,
+                    right.span.end,
+                ));
+                return Err("no numeric promotion".to_string());
+            }
+        };
+
+        let widen = |expr: ExpressionNode<'a>, from: &AzulaType<'a>| {
+            if *from == typ {
+                return expr;
+            }
+
+            let span = expr.span.clone();
+            ExpressionNode {
+                expression: Expression::Cast(Rc::new(expr), typ.clone()),
+                typed: typ.clone(),
+                span,
+            }
+        };
+
+        Ok((
+            widen(left, &left_typ),
+            typ.clone(),
+            widen(right, &right_typ),
+            typ.clone(),
+        ))
+    }
+
+    // nameof(x) is resolved at compile time to the string "x", as long as x
+    // names a variable, global, function or struct that is in scope
+    fn typecheck_nameof(
+        &mut self,
+        args: Vec<ExpressionNode<'a>>,
+        span: Span,
+        env: &Environment<'a>,
+    ) -> Result<(ExpressionNode<'a>, AzulaType<'a>), String> {
+        let name = match args.as_slice() {
+            [ExpressionNode {
+                expression: Expression::Identifier(name),
+                ..
+            }] => name.clone(),
+            _ => {
+                self.errors.push(AzulaError::new(
+                    ErrorType::InvalidNameof,
+                    span.start,
+                    span.end,
+                ));
+                return Err("invalid nameof".to_string());
+            }
+        };
+
+        if !env.variable_definitions.contains_key(&name)
+            && !self.globals.contains_key(&name)
+            && !self.functions.contains_key(name.as_str())
+            && !self.structs.contains_key(&name)
+        {
+            self.errors.push(AzulaError::new(
+                ErrorType::UnknownVariable(name),
+                args[0].span.start,
+                args[0].span.end,
+            ));
+            return Err("Unknown variable".to_string());
+        }
+
+        let typ = AzulaType::Pointer(Rc::new(AzulaType::Str));
+        Ok((
+            ExpressionNode {
+                expression: Expression::String(name),
+                typed: typ.clone(),
+                span,
+            },
+            typ,
+        ))
+    }
+
+    // type_name(x) is resolved at compile time to the name of x's type as
+    // it's written in source, without x being evaluated
+    fn typecheck_type_name(
+        &mut self,
+        args: Vec<ExpressionNode<'a>>,
+        span: Span,
+        env: &Environment<'a>,
+    ) -> Result<(ExpressionNode<'a>, AzulaType<'a>), String> {
+        let arg = match <[_; 1]>::try_from(args) {
+            Ok([arg]) => arg,
+            Err(_) => {
+                self.errors.push(AzulaError::new(
+                    ErrorType::InvalidTypeName,
+                    span.start,
+                    span.end,
+                ));
+                return Err("invalid type_name".to_string());
+            }
+        };
+        let (_, typ) = self.typecheck_expression(arg, env)?;
+        // Instances of generic structs are named the way they're written
+        let typ = map_type(&typ, &mut |typ| match &typ {
+            AzulaType::Named(name) => match self.struct_instances.get(name) {
+                Some((name, args)) => AzulaType::Generic(name.clone(), args.clone()),
+                None => typ,
+            },
+            _ => typ,
+        });
+
+        let string = AzulaType::Pointer(Rc::new(AzulaType::Str));
+        Ok((
+            ExpressionNode {
+                expression: Expression::String(typ.to_string()),
+                typed: string.clone(),
+                span,
+            },
+            string,
+        ))
+    }
+
+    // Replaces each typeof(x) in a type with the type of the variable x
+    fn resolve_typeof(
+        &mut self,
+        typ: &AzulaType<'a>,
+        span: &Span,
+        env: &Environment<'a>,
+    ) -> Result<AzulaType<'a>, String> {
+        let mut unknown = None;
+        let typ = map_type(typ, &mut |typ| match &typ {
+            AzulaType::TypeOf(name) => {
+                if let Some(variable) = env.variable_definitions.get(name) {
+                    self.used_variables.insert(name.clone());
+                    variable.typ.clone()
+                } else if let Some(variable) = self.globals.get(name) {
+                    variable.typ.clone()
+                } else {
+                    unknown = Some(name.clone());
+                    typ
+                }
+            }
+            _ => typ,
+        });
+
+        if let Some(name) = unknown {
+            self.errors.push(AzulaError::new(
+                ErrorType::UnknownVariable(name),
+                span.start,
+                span.end,
+            ));
+            return Err("Unknown variable".to_string());
+        }
+        Ok(typ)
+    }
+
+    // The body sees the enclosing function's variables as well as the params,
+    // any it reads are copied into the closure when it's made
+    fn typecheck_closure(
+        &mut self,
+        args: Vec<TypedIdentifier<'a>>,
+        body: ExpressionNode<'a>,
+        span: Span,
+        env: &Environment<'a>,
+    ) -> Result<(ExpressionNode<'a>, AzulaType<'a>), String> {
+        let mut environment = env.clone();
+        for (typ, name) in &args {
+            environment.add_variable(
+                name.to_string(),
+                VariableDefinition {
+                    name: name.to_string(),
+                    mutable: false,
+                    typ: typ.clone(),
+                },
+            );
+        }
+
+        let (body, returns) = self.typecheck_expression(body, &environment)?;
+
+        let mut read = vec![];
+        read_variables(&body, &mut read);
+        let mut captures: Vec<(AzulaType, String)> = vec![];
+        for name in read {
+            if args.iter().any(|(_, arg)| *arg == name)
+                || captures.iter().any(|(_, captured)| *captured == name)
+            {
+                continue;
+            }
+            if let Some(var) = env.variable_definitions.get(&name) {
+                captures.push((var.typ.clone(), name));
+            }
+        }
+
+        let typ = AzulaType::Function(
+            args.iter().map(|(typ, _)| typ.clone()).collect(),
+            Rc::new(returns),
+        );
+        Ok((
+            ExpressionNode {
+                expression: Expression::Closure {
+                    args,
+                    body: Rc::new(body),
+                    captures,
+                },
+                typed: typ.clone(),
+                span,
+            },
+            typ,
+        ))
+    }
+
+    fn typecheck_closure_call(
+        &mut self,
+        function: ExpressionNode<'a>,
+        args: Vec<ExpressionNode<'a>>,
+        span: Span,
+        env: &Environment<'a>,
+    ) -> Result<(ExpressionNode<'a>, AzulaType<'a>), String> {
+        let (function, typ) = self.typecheck_expression(function, env)?;
+        let (params, returns) = match &typ {
+            AzulaType::Function(params, returns) => (params.clone(), returns.deref().clone()),
+            _ => {
+                self.errors.push(AzulaError::new(
+                    ErrorType::NotCallable(format!("{:?}", typ)),
+                    function.span.start,
+                    function.span.end,
+                ));
+                return Err("not callable".to_string());
+            }
+        };
+        let name = match &function.expression {
+            Expression::Identifier(name) => name.clone(),
+            _ => "function".to_string(),
+        };
+
+        if args.len() != params.len() {
+            self.errors.push(AzulaError::new(
+                ErrorType::WrongArgumentCount(name, params.len(), args.len()),
+                span.start,
+                span.end,
+            ));
+            return Err("wrong argument count".to_string());
+        }
+
+        let mut typed_args = vec![];
+        for (arg, param) in args.into_iter().zip(&params) {
+            // Closure params are only known by position
+            if let Expression::NamedArgument(arg_name, _) = arg.expression {
+                self.errors.push(AzulaError::new(
+                    ErrorType::UnknownArgument(name, arg_name.to_string()),
+                    arg.span.start,
+                    arg.span.end,
+                ));
+                return Err("unknown argument".to_string());
+            }
+
+            let (arg, typ) = self.typecheck_expression(arg, env)?;
+            let (arg, _) = self.coerce(arg, typ, param)?;
+            typed_args.push(arg);
+        }
+
+        Ok((
+            ExpressionNode {
+                expression: Expression::FunctionCall {
+                    function: Rc::new(function),
+                    args: typed_args,
+                },
+                typed: returns.clone(),
+                span,
+            },
+            returns,
+        ))
+    }
+
+    // error(msg) builds the failing side of a result. It is typed as !void
+    // and coerces to any other result type
+    fn typecheck_error(
+        &mut self,
+        args: Vec<ExpressionNode<'a>>,
+        span: Span,
+        env: &Environment<'a>,
+    ) -> Result<(ExpressionNode<'a>, AzulaType<'a>), String> {
+        let message = match args.as_slice() {
+            [message] => message.clone(),
+            _ => {
+                self.errors.push(AzulaError::new(
+                    ErrorType::WrongArgumentCount("error".to_string(), 1, args.len()),
+                    span.start,
+                    span.end,
+                ));
+                return Err("invalid error".to_string());
+            }
+        };
+
+        let (message, typ) = self.typecheck_expression(message, env)?;
+        let string = AzulaType::Pointer(Rc::new(AzulaType::Str));
+        if typ != string {
+            self.errors.push(AzulaError::new(
+                ErrorType::MismatchedTypes(format!("{:?}", string), format!("{:?}", typ)),
+                message.span.start,
+                message.span.end,
+            ));
+            return Err("non string error message".to_string());
+        }
+
+        let typ = AzulaType::Result(Rc::new(AzulaType::Void));
+        Ok((
+            ExpressionNode {
+                expression: Expression::FunctionCall {
+                    function: Rc::new(ExpressionNode {
+                        expression: Expression::Identifier("error".to_string()),
+                        typed: AzulaType::Infer,
+                        span: span.clone(),
+                    }),
+                    args: vec![message],
+                },
+                typed: typ.clone(),
+                span,
+            },
+            typ,
+        ))
+    }
+
+    // build_info() is replaced with the metadata string from
+    // --embed-build-info, so it ends up as a constant in the binary
+    fn typecheck_build_info(
+        &mut self,
+        args: Vec<ExpressionNode<'a>>,
+        span: Span,
+    ) -> Result<(ExpressionNode<'a>, AzulaType<'a>), String> {
+        if !args.is_empty() {
+            self.errors.push(AzulaError::new(
+                ErrorType::UnexpectedArguments("build_info".to_string()),
+                span.start,
+                span.end,
+            ));
+            return Err("unexpected arguments".to_string());
+        }
+
+        let info = match &self.build_info {
+            Some(info) => info.clone(),
+            None => {
+                self.errors.push(AzulaError::new(
+                    ErrorType::BuildInfoNotEmbedded,
+                    span.start,
+                    span.end,
+                ));
+                return Err("build info not embedded".to_string());
+            }
+        };
+
+        let typ = AzulaType::Pointer(Rc::new(AzulaType::Str));
+        Ok((
+            ExpressionNode {
+                expression: Expression::String(info),
+                typed: typ.clone(),
+                span,
+            },
+            typ,
+        ))
+    }
+
+    // free(p) hands back memory from new, p can point to anything
+    fn typecheck_free(
+        &mut self,
+        function: ExpressionNode<'a>,
+        args: Vec<ExpressionNode<'a>>,
+        span: Span,
+        env: &Environment<'a>,
+    ) -> Result<(ExpressionNode<'a>, AzulaType<'a>), String> {
+        let pointer = match args.as_slice() {
+            [pointer] => pointer.clone(),
+            _ => {
+                self.errors.push(AzulaError::new(
+                    ErrorType::WrongArgumentCount("free".to_string(), 1, args.len()),
+                    span.start,
+                    span.end,
+                ));
+                return Err("invalid free".to_string());
+            }
+        };
+
+        let (pointer, typ) = self.typecheck_expression(pointer, env)?;
+        if !matches!(typ, AzulaType::Pointer(_)) {
+            self.errors.push(AzulaError::new(
+                ErrorType::MismatchedTypes("pointer".to_string(), format!("{:?}", typ)),
+                pointer.span.start,
+                pointer.span.end,
+            ));
+            return Err("free of non-pointer".to_string());
+        }
+
+        Ok((
+            ExpressionNode {
+                expression: Expression::FunctionCall {
+                    function: Rc::new(function),
+                    args: vec![pointer],
+                },
+                typed: AzulaType::Void,
+                span,
+            },
+            AzulaType::Void,
+        ))
+    }
+
+    // volatile_load(p) reads what p points to and volatile_store(p, v) writes
+    // v there, neither of which the optimiser can remove or combine. The
+    // unaligned ones make no assumption about where p points
+    fn typecheck_memory_access(
+        &mut self,
+        function: ExpressionNode<'a>,
+        args: Vec<ExpressionNode<'a>>,
+        span: Span,
+        env: &Environment<'a>,
+    ) -> Result<(ExpressionNode<'a>, AzulaType<'a>), String> {
+        let name = match &function.expression {
+            Expression::Identifier(name) => name.clone(),
+            _ => unreachable!(),
+        };
+        let expected = if name.ends_with("load") { 1 } else { 2 };
+        if args.len() != expected {
+            self.errors.push(AzulaError::new(
+                ErrorType::WrongArgumentCount(name, expected, args.len()),
+                span.start,
+                span.end,
+            ));
+            return Err("wrong memory access argument count".to_string());
+        }
+
+        let (pointer, typ) = self.typecheck_expression(args[0].clone(), env)?;
+        let pointee = match &typ {
+            AzulaType::Pointer(inner) => inner.deref().clone(),
+            _ => {
+                self.errors.push(AzulaError::new(
+                    ErrorType::MismatchedTypes("pointer".to_string(), format!("{:?}", typ)),
+                    pointer.span.start,
+                    pointer.span.end,
+                ));
+                return Err("memory access through non-pointer".to_string());
+            }
+        };
+
+        let mut typed_args = vec![pointer];
+        let returns = if expected == 1 {
+            pointee
+        } else {
+            let (value, typ) = self.typecheck_expression(args[1].clone(), env)?;
+            let (value, typ) = self.coerce(value, typ, &pointee)?;
+            if typ != pointee {
+                self.errors.push(AzulaError::new(
+                    ErrorType::MismatchedTypes(format!("{:?}", pointee), format!("{:?}", typ)),
+                    value.span.start,
+                    value.span.end,
+                ));
+                return Err("mismatched store".to_string());
+            }
+            typed_args.push(value);
+            AzulaType::Void
+        };
+
+        Ok((
+            ExpressionNode {
+                expression: Expression::FunctionCall {
+                    function: Rc::new(function),
+                    args: typed_args,
+                },
+                typed: returns.clone(),
+                span,
+            },
+            returns,
+        ))
+    }
+
+    // to_le(x) and friends give an integer in or from a byte order, whatever
+    // the target's is
+    fn typecheck_byte_order(
+        &mut self,
+        function: ExpressionNode<'a>,
+        args: Vec<ExpressionNode<'a>>,
+        span: Span,
+        env: &Environment<'a>,
+    ) -> Result<(ExpressionNode<'a>, AzulaType<'a>), String> {
+        let value = match args.as_slice() {
+            [value] => value.clone(),
+            _ => {
+                let name = match &function.expression {
+                    Expression::Identifier(name) => name.clone(),
+                    _ => unreachable!(),
+                };
+                self.errors.push(AzulaError::new(
+                    ErrorType::WrongArgumentCount(name, 1, args.len()),
+                    span.start,
+                    span.end,
+                ));
+                return Err("invalid byte order conversion".to_string());
+            }
+        };
+
+        let (value, typ) = self.typecheck_expression(value, env)?;
+        if typ.integer_width().is_none() {
+            self.errors.push(AzulaError::new(
+                ErrorType::MismatchedTypes("integer".to_string(), format!("{:?}", typ)),
+                value.span.start,
+                value.span.end,
+            ));
+            return Err("byte order of non-integer".to_string());
+        }
+
+        Ok((
+            ExpressionNode {
+                expression: Expression::FunctionCall {
+                    function: Rc::new(function),
+                    args: vec![value],
+                },
+                typed: typ.clone(),
+                span,
+            },
+            typ,
+        ))
+    }
+
+    // len(s) is the number of bytes in a string, which is stored with it
+    fn typecheck_len(
+        &mut self,
+        function: ExpressionNode<'a>,
+        args: Vec<ExpressionNode<'a>>,
+        span: Span,
+        env: &Environment<'a>,
+    ) -> Result<(ExpressionNode<'a>, AzulaType<'a>), String> {
+        let string = match args.as_slice() {
+            [string] => string.clone(),
+            _ => {
+                self.errors.push(AzulaError::new(
+                    ErrorType::WrongArgumentCount("len".to_string(), 1, args.len()),
+                    span.start,
+                    span.end,
+                ));
+                return Err("invalid len".to_string());
+            }
+        };
+
+        let (string, typ) = self.typecheck_expression(string, env)?;
+        if typ != AzulaType::Pointer(Rc::new(AzulaType::Str)) {
+            self.errors.push(AzulaError::new(
+                ErrorType::MismatchedTypes("string".to_string(), format!("{:?}", typ)),
+                string.span.start,
+                string.span.end,
+            ));
+            return Err("len of non-string".to_string());
+        }
+
+        Ok((
+            ExpressionNode {
+                expression: Expression::FunctionCall {
+                    function: Rc::new(function),
+                    args: vec![string],
+                },
+                typed: AzulaType::Int,
+                span,
+            },
+            AzulaType::Int,
+        ))
+    }
+
+    // on_signal(SIGINT, handler) installs handler with libc's signal(). The
+    // handler is named directly since there are no function values, and must
+    // match the C handler signature
+    fn typecheck_on_signal(
+        &mut self,
+        function: ExpressionNode<'a>,
+        args: Vec<ExpressionNode<'a>>,
+        span: Span,
+        env: &Environment<'a>,
+    ) -> Result<(ExpressionNode<'a>, AzulaType<'a>), String> {
+        let (signal, handler) = match args.as_slice() {
+            [signal, handler] => (signal.clone(), handler.clone()),
+            _ => {
+                self.errors.push(AzulaError::new(
+                    ErrorType::WrongArgumentCount("on_signal".to_string(), 2, args.len()),
+                    span.start,
+                    span.end,
+                ));
+                return Err("invalid on_signal".to_string());
+            }
+        };
+
+        let (signal, typ) = self.typecheck_expression(signal, env)?;
+        if typ != AzulaType::Int {
+            self.errors.push(AzulaError::new(
+                ErrorType::MismatchedTypes(format!("{:?}", AzulaType::Int), format!("{:?}", typ)),
+                signal.span.start,
+                signal.span.end,
+            ));
+            return Err("non int signal".to_string());
+        }
+
+        let (name, valid) = match &handler.expression {
+            Expression::Identifier(name) => match self.functions.get(name.as_str()) {
+                Some(f) => (
+                    name.clone(),
+                    f.returns == AzulaType::Void
+                        && f.args.len() == 1
+                        && f.args[0].0 == AzulaType::SizedSignedInt(32),
+                ),
+                None => (name.clone(), false),
+            },
+            _ => ("expression".to_string(), false),
+        };
+        if !valid {
+            self.errors.push(AzulaError::new(
+                ErrorType::InvalidSignalHandler(name),
+                handler.span.start,
+                handler.span.end,
+            ));
+            return Err("invalid signal handler".to_string());
+        }
+
+        Ok((
+            ExpressionNode {
+                expression: Expression::FunctionCall {
+                    function: Rc::new(function),
+                    args: vec![signal, handler],
+                },
+                typed: AzulaType::Void,
+                span,
+            },
+            AzulaType::Void,
+        ))
+    }
+
+    fn typecheck_infix_expression(
+        &mut self,
+        mut expr: ExpressionNode<'a>,
+        env: &Environment<'a>,
+    ) -> Result<(ExpressionNode<'a>, AzulaType<'a>), String> {
+        if let Expression::Infix(ref left, ref operator, ref right) = expr.expression {
+            let (left, left_typ) = match self.typecheck_expression(left.deref().clone(), env) {
+                Ok((left, typ)) => (left, typ),
+                Err(e) => return Err(e),
+            };
+
+            let (right, right_typ) = match self.typecheck_expression(right.deref().clone(), env) {
+                Ok((right, typ)) => (right, typ),
+                Err(e) => return Err(e),
+            };
+
+            let (left, left_typ, right, right_typ) = match operator {
+                Operator::Shl | Operator::Shr => (left, left_typ, right, right_typ),
+                // An integer exponent is left as it is, whatever the base
+                Operator::Power if right_typ.integer_width().is_some() => {
+                    (left, left_typ, right, right_typ)
+                }
+                _ => self.promote_operands(left, left_typ, right, right_typ)?,
+            };
+
+            let integers = vec![
+                AzulaType::Int,
+                AzulaType::SizedSignedInt(8),
+                AzulaType::SizedSignedInt(16),
+                AzulaType::SizedSignedInt(32),
+                AzulaType::SizedSignedInt(64),
+                AzulaType::SizedUnsignedInt(8),
+                AzulaType::SizedUnsignedInt(16),
+                AzulaType::SizedUnsignedInt(32),
+                AzulaType::SizedUnsignedInt(64),
+            ];
+
+            let mut numbers = integers.clone();
+            numbers.extend([
+                AzulaType::Float,
+                AzulaType::SizedFloat(32),
+                AzulaType::SizedFloat(64),
+            ]);
+
+            let mut equatable = numbers.clone();
+            equatable.push(AzulaType::Bool);
+
+            let allowed = hashmap! {
+                Operator::Add => numbers.clone(),
+                Operator::Sub => numbers.clone(),
+                Operator::Mul => numbers.clone(),
+                Operator::Div => numbers.clone(),
+                Operator::Mod => numbers.clone(),
+                Operator::Power => numbers.clone(),
+                Operator::Or => vec![AzulaType::Bool],
+                Operator::And => vec![AzulaType::Bool],
+                Operator::Eq => equatable.clone(),
+                Operator::Neq => equatable,
+                Operator::Lt => numbers.clone(),
+                Operator::Lte => numbers.clone(),
+                Operator::Gt => numbers.clone(),
+                Operator::Gte => numbers,
+                Operator::Shl => integers.clone(),
+                Operator::Shr => integers,
+            };
+
+            if let Operator::And | Operator::Or = operator {
+                for (operand, typ) in [(&left, &left_typ), (&right, &right_typ)] {
+                    if *typ != AzulaType::Bool {
+                        self.errors.push(AzulaError::new(
+                            ErrorType::NonBoolCondition(format!("{:?}", typ), bool_hint(typ)),
+                            operand.span.start,
+                            operand.span.end,
+                        ));
+                        return Err("Non-bool in condition".to_string());
+                    }
+                }
+            }
+
+            let allowed = allowed.get(operator).unwrap();
+            if !allowed.contains(&left_typ) {
+                self.errors.push(AzulaError::new(
+                    ErrorType::NonOperatorType(
+                        format!("{:?}", left_typ),
+                        format!("{:?}", operator),
+                    ),
+                    left.span.start,
+                    left.span.end,
+                ));
+                return Err("cannot use operator with type".to_string());
+            }
+
+            if !allowed.contains(&right_typ) {
+                self.errors.push(AzulaError::new(
+                    ErrorType::NonOperatorType(
+                        format!("{:?}", right_typ),
+                        format!("{:?}", operator),
+                    ),
+                    right.span.start,
+                    right.span.end,
+                ));
+                return Err("cannot use operator with type".to_string());
+            }
+
+            // Integer division by zero is undefined, so a 0 written as the
+            // divisor is rejected
+            if let Operator::Div | Operator::Mod = operator {
+                let zero = matches!(right.expression, Expression::Integer(0));
+                if zero && left_typ.integer_width().is_some() {
+                    self.errors.push(AzulaError::new(
+                        ErrorType::DivisionByZero,
+                        right.span.start,
+                        right.span.end,
+                    ));
+                    return Err("division by zero".to_string());
+                }
+            }
+
+            match operator {
+                Operator::Add => {
+                    if left_typ != right_typ {
+                        self.errors.push(AzulaError::new(
+                            ErrorType::MismatchedTypes(
+                                format!("{:?}", left_typ),
+                                format!("{:?}", right_typ),
+                            ),
+                            left.span.start,
+                            right.span.end,
+                        ));
+                        return Err("mismatched types in infix".to_string());
+                    }
+
+                    expr.typed = left.clone().typed;
+                    Ok((
+                        ExpressionNode {
+                            expression: Expression::Infix(
+                                Rc::new(left),
+                                operator.clone(),
+                                Rc::new(right),
+                            ),
+                            typed: left_typ.clone().into(),
+                            span: expr.span,
+                        },
+                        left_typ,
+                    ))
+                }
+                Operator::Sub => {
+                    if left_typ != right_typ {
+                        self.errors.push(AzulaError::new(
+                            ErrorType::MismatchedTypes(
+                                format!("{:?}", left_typ),
+                                format!("{:?}", right_typ),
+                            ),
+                            left.span.start,
+                            right.span.end,
+                        ));
+                        return Err("mismatched types in infix".to_string());
+                    }
+
+                    expr.typed = left.clone().typed;
+                    Ok((
+                        ExpressionNode {
+                            expression: Expression::Infix(
+                                Rc::new(left),
+                                operator.clone(),
+                                Rc::new(right),
+                            ),
+                            typed: left_typ.clone().into(),
+                            span: expr.span,
+                        },
+                        left_typ,
+                    ))
+                }
+                Operator::Mul => {
+                    if left_typ != right_typ {
+                        self.errors.push(AzulaError::new(
+                            ErrorType::MismatchedTypes(
+                                format!("{:?}", left_typ),
+                                format!("{:?}", right_typ),
+                            ),
+                            left.span.start,
+                            right.span.end,
+                        ));
+                        return Err("mismatched types in infix".to_string());
+                    }
+
+                    expr.typed = left.clone().typed;
+                    Ok((
+                        ExpressionNode {
+                            expression: Expression::Infix(
+                                Rc::new(left),
+                                operator.clone(),
+                                Rc::new(right),
+                            ),
+                            typed: left_typ.clone().into(),
+                            span: expr.span,
+                        },
+                        left_typ,
+                    ))
+                }
+                Operator::Div => {
+                    if left_typ != right_typ {
+                        self.errors.push(AzulaError::new(
+                            ErrorType::MismatchedTypes(
+                                format!("{:?}", left_typ),
+                                format!("{:?}", right_typ),
+                            ),
+                            left.span.start,
+                            right.span.end,
+                        ));
+                        return Err("mismatched types in infix".to_string());
+                    }
+
+                    expr.typed = left.clone().typed;
+                    Ok((
+                        ExpressionNode {
+                            expression: Expression::Infix(
+                                Rc::new(left),
+                                operator.clone(),
+                                Rc::new(right),
+                            ),
+                            typed: left_typ.clone().into(),
+                            span: expr.span,
+                        },
+                        left_typ,
+                    ))
+                }
+                Operator::Mod => {
+                    if left_typ != right_typ {
+                        self.errors.push(AzulaError::new(
+                            ErrorType::MismatchedTypes(
+                                format!("{:?}", left_typ),
+                                format!("{:?}", right_typ),
+                            ),
+                            left.span.start,
+                            right.span.end,
+                        ));
+                        return Err("mismatched types in infix".to_string());
+                    }
+
+                    expr.typed = left.clone().typed;
+                    Ok((
+                        ExpressionNode {
+                            expression: Expression::Infix(
+                                Rc::new(left),
+                                operator.clone(),
+                                Rc::new(right),
+                            ),
+                            typed: left_typ.clone().into(),
+                            span: expr.span,
+                        },
+                        left_typ,
+                    ))
+                }
+                // A float can be raised to an integer power, and an integer
+                // to one of any width
+                Operator::Power => {
+                    if left_typ != right_typ && right_typ.integer_width().is_none() {
+                        self.errors.push(AzulaError::new(
+                            ErrorType::MismatchedTypes(
+                                format!("{:?}", left_typ),
+                                format!("{:?}", right_typ),
+                            ),
+                            left.span.start,
+                            right.span.end,
+                        ));
+                        return Err("mismatched types in infix".to_string());
+                    }
+
+                    expr.typed = left.clone().typed;
+                    Ok((
+                        ExpressionNode {
+                            expression: Expression::Infix(
+                                Rc::new(left),
+                                operator.clone(),
+                                Rc::new(right),
+                            ),
+                            typed: left_typ.clone().into(),
+                            span: expr.span,
+                        },
+                        left_typ,
+                    ))
+                }
+                Operator::Shl | Operator::Shr => {
+                    if left_typ != right_typ {
+                        self.errors.push(AzulaError::new(
+                            ErrorType::MismatchedTypes(
+                                format!("{:?}", left_typ),
+                                format!("{:?}", right_typ),
+                            ),
+                            left.span.start,
+                            right.span.end,
+                        ));
+                        return Err("mismatched types in infix".to_string());
+                    }
+
+                    // Constant shift amounts can be checked here, anything else
+                    // is masked to the width of the type by the backend
+                    let width = left_typ.integer_width().unwrap();
+                    if let Expression::Integer(amount) = right.expression {
+                        if amount < 0 || amount as usize >= width {
+                            self.errors.push(AzulaError::new(
+                                ErrorType::ShiftOutOfRange(amount, width),
+                                right.span.start,
+                                right.span.end,
+                            ));
+                            return Err("shift out of range".to_string());
+                        }
+                    }
+
+                    Ok((
+                        ExpressionNode {
+                            expression: Expression::Infix(
+                                Rc::new(left),
+                                operator.clone(),
+                                Rc::new(right),
+                            ),
+                            typed: left_typ.clone(),
+                            span: expr.span,
+                        },
+                        left_typ,
+                    ))
+                }
+                Operator::Or
+                | Operator::And
+                | Operator::Eq
+                | Operator::Neq
+                | Operator::Lt
+                | Operator::Lte
+                | Operator::Gt
+                | Operator::Gte => {
+                    expr.typed = AzulaType::Bool;
+                    Ok((
+                        ExpressionNode {
+                            expression: Expression::Infix(
+                                Rc::new(left),
+                                operator.clone(),
+                                Rc::new(right),
+                            ),
+                            typed: AzulaType::Bool,
+                            span: expr.span,
+                        },
+                        AzulaType::Bool,
+                    ))
+                }
+            }
+        } else {
+            unreachable!()
+        }
+    }
+}
+
+/// `as` converts between any of the integer and float types, and from bool to
+/// numbers
+// The variable a write to a struct field changes, None if the struct is
+// reached through a pointer
+fn struct_owner<'n>(node: &'n ExpressionNode) -> Option<&'n str> {
+    match &node.expression {
+        Expression::StructAccess(struc, _) if !matches!(struc.typed, AzulaType::Pointer(_)) => {
+            match &struc.expression {
+                Expression::Identifier(name) => Some(name),
+                _ => struct_owner(struc),
+            }
+        }
+        _ => None,
+    }
+}
+
+fn is_numeric_cast(from: &AzulaType, to: &AzulaType) -> bool {
+    let numeric = |typ: &AzulaType| typ.integer_width().is_some() || typ.is_float();
+
+    (numeric(from) || *from == AzulaType::Bool) && numeric(to)
+}
+
+// struct Range<T> { start: T, end: T }, built in for range expressions
+fn range_struct<'a>() -> Statement<'a> {
+    Statement::Struct {
+        name: "Range",
+        generics: vec!["T"],
+        attributes: vec![
+            (AzulaType::Named("T".to_string()), "start"),
+            (AzulaType::Named("T".to_string()), "end"),
+        ],
+        defaults: vec![],
+        annotations: vec![],
+        doc: None,
+        span: Span { start: 0, end: 0 },
+    }
+}
+
+// struct Slice<T> { data: [T], len: int }, built in for []T
+fn slice_struct<'a>() -> Statement<'a> {
+    Statement::Struct {
+        name: "Slice",
+        generics: vec!["T"],
+        attributes: vec![
+            (
+                AzulaType::Array(Rc::new(AzulaType::Named("T".to_string())), None),
+                "data",
+            ),
+            (AzulaType::Int, "len"),
+        ],
+        defaults: vec![],
+        annotations: vec![],
+        doc: None,
+        span: Span { start: 0, end: 0 },
+    }
+}
+
+fn slice_data<'a>(
+    slice: ExpressionNode<'a>,
+    element: AzulaType<'a>,
+) -> (ExpressionNode<'a>, AzulaType<'a>) {
+    let typ = AzulaType::Array(Rc::new(element), None);
+    let span = slice.span.clone();
+    let data = ExpressionNode {
+        expression: Expression::StructAccess(
+            Rc::new(slice),
+            Rc::new(ExpressionNode {
+                expression: Expression::Identifier("data".to_string()),
+                typed: AzulaType::Infer,
+                span: span.clone(),
+            }),
+        ),
+        typed: typ.clone(),
+        span,
+    };
+    (data, typ)
+}
+
+// The name a top-level declaration gives
+// What a function without pub is declared as in its module
+fn private_name(module: &str, name: &str) -> String {
+    format!("{}::{}", module, name)
+}
+
+fn declared_name(stmt: &Statement) -> Option<String> {
+    match stmt {
+        Statement::Function { name, .. }
+        | Statement::ExternFunction { name, .. }
+        | Statement::Struct { name, .. }
+        | Statement::Interface { name, .. } => Some(name.to_string()),
+        Statement::Assign(_, name, ..) => Some(name.clone()),
+        _ => None,
+    }
+}
+
+// Whether every way through the statements ends in a return
+fn always_returns(body: &[Statement]) -> bool {
+    body.iter().any(|stmt| match stmt {
+        Statement::Return(..) => true,
+        Statement::Block(body) | Statement::Scope(body, _) => always_returns(body),
+        // Without a default arm a value can match none of them and carry on
+        Statement::Match(_, arms, _) => {
+            arms.iter().any(|(typ, ..)| typ.is_none())
+                && arms.iter().all(|(_, _, body, _)| always_returns(body))
+        }
+        // Nothing but a return or a break leaves a while true
+        Statement::While(condition, body, label, _) => {
+            condition.expression == Expression::Boolean(true)
+                && !leaves_loop(body, *label, &mut vec![])
+        }
+        // The body always runs at least once
+        Statement::DoWhile(condition, body, label, _) => {
+            (always_returns(body) || condition.expression == Expression::Boolean(true))
+                && !leaves_loop(body, *label, &mut vec![])
+        }
+        _ => false,
+    })
+}
+
+// Whether a break or continue in a loop's body gets out of the loop, given the
+// labels of the loops nested in it that the statements are inside
+fn leaves_loop<'a>(
+    body: &[Statement<'a>],
+    label: Option<&str>,
+    inner: &mut Vec<Option<&'a str>>,
+) -> bool {
+    body.iter().any(|stmt| match stmt {
+        Statement::Break(None, _) => inner.is_empty(),
+        Statement::Break(Some(target), _) => !inner.contains(&Some(*target)),
+        Statement::Continue(Some(target), _) => {
+            Some(*target) != label && !inner.contains(&Some(*target))
+        }
+        Statement::Block(body)
+        | Statement::Scope(body, _)
+        | Statement::If(_, body, _)
+        | Statement::IfLet(_, _, _, body, _) => leaves_loop(body, label, inner),
+        Statement::Match(_, arms, _) => arms
+            .iter()
+            .any(|(_, _, body, _)| leaves_loop(body, label, inner)),
+        Statement::While(_, body, nested, _)
+        | Statement::DoWhile(_, body, nested, _)
+        | Statement::For(_, _, _, body, nested, _) => {
+            inner.push(*nested);
+            let leaves = leaves_loop(body, label, inner);
+            inner.pop();
+            leaves
+        }
+        _ => false,
+    })
+}
+
+// Numbers aren't truthy, but comparing them with zero is likely what was meant
+fn bool_hint(typ: &AzulaType) -> Option<String> {
+    if typ.integer_width().is_some() {
+        Some("!= 0".to_string())
+    } else if typ.is_float() {
+        Some("!= 0.0".to_string())
+    } else {
+        None
+    }
+}
+
+// How a statement that only makes sense inside a function is described
+fn statement_kind(stmt: &Statement) -> &'static str {
+    match stmt {
+        Statement::Return(..) => "A return statement",
+        Statement::If(..) | Statement::IfLet(..) => "An if statement",
+        Statement::While(..) => "A while loop",
+        Statement::DoWhile(..) => "A do while loop",
+        Statement::For(..) => "A for loop",
+        Statement::Break(..) => "A break",
+        Statement::Continue(..) => "A continue",
+        Statement::Match(..) => "A match statement",
+        Statement::Reassign(..) => "A reassignment",
+        Statement::Destructure(..) => "A destructuring assignment",
+        Statement::Defer(..) => "A defer",
+        Statement::Scope(..) => "A block",
+        _ => "An expression",
+    }
+}
+
+// float is f64
+fn float_width(typ: &AzulaType) -> usize {
+    match typ {
+        AzulaType::SizedFloat(size) => *size,
+        _ => 64,
+    }
+}
+
+/// The type two different numeric operands are widened to. Integers of the
+/// same signedness widen to the larger width and an unsigned integer widens to
+/// a strictly larger signed one, floats widen to the larger float. Mixing
+/// integers with floats, or signed with unsigned of the same width, could lose
+/// values so needs an explicit `as`
+fn promoted_type<'a>(left: &AzulaType<'a>, right: &AzulaType<'a>) -> Option<AzulaType<'a>> {
+    // int and float are the 64-bit types, so win ties against their sized
+    // spellings
+    let wider =
+        |left: &AzulaType<'a>, right: &AzulaType<'a>, width: fn(&AzulaType) -> usize| match width(
+            left,
+        )
+        .cmp(&width(right))
+        {
+            Ordering::Greater => left.clone(),
+            Ordering::Less => right.clone(),
+            Ordering::Equal if *right == AzulaType::Int || *right == AzulaType::Float => {
+                right.clone()
+            }
+            Ordering::Equal => left.clone(),
+        };
+
+    let signed = |typ: &AzulaType| matches!(typ, AzulaType::Int | AzulaType::SizedSignedInt(_));
+    match (left.integer_width(), right.integer_width()) {
+        (Some(_), Some(_)) if signed(left) == signed(right) => {
+            Some(wider(left, right, |typ| typ.integer_width().unwrap()))
+        }
+        (Some(left_width), Some(right_width)) => {
+            let (signed_typ, signed_width, unsigned_width) = if signed(left) {
+                (left, left_width, right_width)
+            } else {
+                (right, right_width, left_width)
+            };
+
+            if unsigned_width < signed_width {
+                Some(signed_typ.clone())
+            } else {
+                None
+            }
+        }
+        (None, None) if left.is_float() && right.is_float() => {
+            Some(wider(left, right, float_width))
+        }
+        _ => None,
+    }
+}
+
+/// The signals with the same number on Linux and macOS
+fn signal_number(name: &str) -> Option<i64> {
+    match name {
+        "SIGHUP" => Some(1),
+        "SIGINT" => Some(2),
+        "SIGQUIT" => Some(3),
+        "SIGKILL" => Some(9),
+        "SIGALRM" => Some(14),
+        "SIGTERM" => Some(15),
+        _ => None,
+    }
+}
+
+// Every variable an expression reads, a closure inside it reads the ones it
+// captures
+fn read_variables(expr: &ExpressionNode, read: &mut Vec<String>) {
+    match &expr.expression {
+        Expression::Identifier(name) => read.push(name.clone()),
+        Expression::Infix(left, _, right)
+        | Expression::ArrayAccess(left, right)
+        | Expression::ArrayRepeat(left, right)
+        | Expression::Range(left, right) => {
+            read_variables(left, read);
+            read_variables(right, read);
+        }
+        Expression::FunctionCall { function, args } => {
+            read_variables(function, read);
+            for arg in args {
+                read_variables(arg, read);
+            }
+        }
+        Expression::Array(values) | Expression::Tuple(values) => {
+            for value in values {
+                read_variables(value, read);
+            }
+        }
+        Expression::StructInitialisation(_, values) => {
+            for (_, value) in values {
+                read_variables(value, read);
+            }
+        }
+        // The member is a field name rather than a variable
+        Expression::StructAccess(value, _)
+        | Expression::Not(value)
+        | Expression::Negate(value)
+        | Expression::Pointer(value)
+        | Expression::Deref(value)
+        | Expression::Cast(value, _)
+        | Expression::Try(value, _)
+        | Expression::NamedArgument(_, value) => read_variables(value, read),
+        Expression::Ternary(cond, then, otherwise) => {
+            read_variables(cond, read);
+            read_variables(then, read);
+            read_variables(otherwise, read);
+        }
+        Expression::Closure { captures, .. } => {
+            read.extend(captures.iter().map(|(_, name)| name.clone()))
+        }
+        Expression::Integer(_)
+        | Expression::Float(_)
+        | Expression::Boolean(_)
+        | Expression::String(_)
+        | Expression::SizeOf(_)
+        | Expression::New(_) => {}
+    }
+}
+
+/// Whether a variable can take its type from an initialiser of this type,
+/// `nil`, void calls and empty arrays need an annotation
+fn is_inferable(typ: &AzulaType) -> bool {
+    match typ {
+        AzulaType::Void | AzulaType::Infer => false,
+        AzulaType::Array(inner, _) => is_inferable(inner),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use azula_ast::prelude::Span;
+    use std::rc::Rc;
+
+    // Parses and typechecks a program, giving the errors it reported
+    fn typecheck(input: &str) -> (Result<Statement<'_>, String>, Vec<AzulaError>) {
+        let lexer = azula_parser::prelude::Lexer::new(input);
+        let mut parser = azula_parser::prelude::Parser::new(input, lexer);
+        let ast = parser.parse();
+        assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+
+        let mut typechecker = Typechecker::new(ast);
+        let result = typechecker.typecheck();
+        (result, typechecker.errors)
+    }
+
+    // An expression for the typechecker to fill in the type of
+    fn node(expression: Expression<'_>) -> ExpressionNode<'_> {
+        ExpressionNode {
+            expression,
+            typed: AzulaType::Infer,
+            span: Span { start: 0, end: 1 },
+        }
+    }
+
+    #[test]
+    fn test_function() {
+        let root = Statement::Root(vec![Statement::Function {
+            name: "main",
+            generics: vec![],
+            bounds: vec![],
+            args: vec![(AzulaType::Int, "x")],
+            arg_spans: vec![],
+            defaults: vec![],
+            variadic: false,
+            returns: AzulaType::Bool,
+            body: Rc::new(Statement::Block(vec![Statement::Return(
+                Some(ExpressionNode {
+                    expression: Expression::Boolean(true),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 0, end: 1 },
+                }),
+                Span { start: 0, end: 1 },
+            )])),
+            public: false,
+            attributes: vec![],
+            doc: None,
+            span: Span { start: 0, end: 1 },
+        }]);
+
+        let mut typechecker = Typechecker::new(root);
+        typechecker.typecheck().unwrap();
+    }
+
+    #[test]
+    fn test_assign() {
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+
+        let mut environment = Environment::new();
+        typechecker.typecheck_assign(
+            Statement::Assign(
+                true,
+                "test".to_string(),
+                None,
+                ExpressionNode {
+                    expression: Expression::Integer(5),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 0, end: 1 },
+                },
+                vec![],
+                Span { start: 0, end: 1 },
+            ),
+            &mut environment,
+        );
+
+        let var = environment
+            .variable_definitions
+            .get(&"test".to_string())
+            .unwrap();
+        assert_eq!(var.name, "test");
+        assert_eq!(var.typ, AzulaType::Int);
+    }
+
+    #[test]
+    fn test_return() {
+        // Return value
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        typechecker.returns = AzulaType::Int;
+
+        let mut environment = Environment::new();
+        let (_, typ) = typechecker
+            .typecheck_return(
+                Statement::Return(
+                    Some(ExpressionNode {
+                        expression: Expression::Integer(5),
+                        typed: AzulaType::Infer,
+                        span: Span { start: 0, end: 1 },
+                    }),
+                    Span { start: 0, end: 1 },
+                ),
+                &mut environment,
+            )
+            .unwrap();
+        assert_eq!(typ, AzulaType::Int);
+
+        // Return none
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+
+        let mut environment = Environment::new();
+        let (_, typ) = typechecker
+            .typecheck_return(
+                Statement::Return(None, Span { start: 0, end: 1 }),
+                &mut environment,
+            )
+            .unwrap();
+        assert_eq!(typ, AzulaType::Void);
+    }
+
+    #[test]
+    fn test_integer_expression() {
+        let integer_node = ExpressionNode {
+            expression: Expression::Integer(5),
+            typed: AzulaType::Infer,
+            span: Span { start: 0, end: 0 },
+        };
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        let mut environment = Environment::new();
+        let (expr, typ) = typechecker
+            .typecheck_expression(integer_node, &environment)
+            .unwrap();
+
+        assert_eq!(typ, AzulaType::Int);
+        assert_eq!(expr.typed, AzulaType::Int);
+    }
+
+    #[test]
+    fn test_identifier_expression() {
+        // Integer
+        let identifier_node = ExpressionNode {
+            expression: Expression::Identifier("test".to_string()),
+            typed: AzulaType::Infer,
+            span: Span { start: 0, end: 0 },
+        };
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        let mut environment = Environment::new();
+        environment.add_variable(
+            "test".to_string(),
+            VariableDefinition {
+                name: "test".to_string(),
+                mutable: true,
+                typ: AzulaType::Int,
+            },
+        );
+        let (expr, typ) = typechecker
+            .typecheck_expression(identifier_node, &environment)
+            .unwrap();
+
+        assert_eq!(typ, AzulaType::Int);
+        assert_eq!(expr.typed, AzulaType::Int);
+
+        // Pointer
+        let identifier_node = ExpressionNode {
+            expression: Expression::Identifier("test".to_string()),
+            typed: AzulaType::Infer,
+            span: Span { start: 0, end: 0 },
+        };
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        let mut environment = Environment::new();
+        environment.add_variable(
+            "test".to_string(),
+            VariableDefinition {
+                typ: AzulaType::Pointer(Rc::new(AzulaType::Str)),
+                name: "test".to_string(),
+                mutable: true,
+            },
+        );
+        let (expr, typ) = typechecker
+            .typecheck_expression(identifier_node, &environment)
+            .unwrap();
+
+        assert_eq!(typ, AzulaType::Pointer(Rc::new(AzulaType::Str)));
+        assert_eq!(expr.typed, AzulaType::Pointer(Rc::new(AzulaType::Str)));
+    }
+
+    #[test]
+    fn test_infix_expression() {
+        // Int
+        let infix_node = ExpressionNode {
+            expression: Expression::Infix(
+                Rc::new(ExpressionNode {
+                    expression: Expression::Integer(5),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 0, end: 0 },
+                }),
+                Operator::Add,
+                Rc::new(ExpressionNode {
+                    expression: Expression::Integer(20),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 0, end: 0 },
+                }),
+            ),
+            typed: AzulaType::Infer,
+            span: Span { start: 0, end: 0 },
+        };
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        let mut environment = Environment::new();
+        let (expr, typ) = typechecker
+            .typecheck_expression(infix_node, &environment)
+            .unwrap();
+
+        assert_eq!(typ, AzulaType::Int);
+        assert_eq!(expr.typed, AzulaType::Int);
+
+        // Non operator type
+        let infix_node = ExpressionNode {
+            expression: Expression::Infix(
+                Rc::new(ExpressionNode {
+                    expression: Expression::Integer(5),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 0, end: 0 },
+                }),
+                Operator::Add,
+                Rc::new(ExpressionNode {
+                    expression: Expression::Identifier("test".to_string()),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 0, end: 0 },
+                }),
+            ),
+            typed: AzulaType::Infer,
+            span: Span { start: 0, end: 0 },
+        };
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        let mut environment = Environment::new();
+        environment.add_variable(
+            "test".to_string(),
+            VariableDefinition {
+                name: "test".to_string(),
+                typ: AzulaType::Bool,
+                mutable: true,
+            },
+        );
+        typechecker.typecheck_expression(infix_node, &environment);
+
+        assert_eq!(typechecker.errors.len(), 1);
+        assert!(matches!(
+            typechecker.errors[0].error_type,
+            ErrorType::NonOperatorType(..)
+        ));
+    }
+
+    #[test]
+    fn test_array_expression() {
+        // Int
+        let array = ExpressionNode {
+            expression: Expression::Array(vec![
+                ExpressionNode {
+                    expression: Expression::Integer(1),
+                    typed: AzulaType::Int,
+                    span: Span { start: 0, end: 1 },
+                },
+                ExpressionNode {
+                    expression: Expression::Integer(2),
+                    typed: AzulaType::Int,
+                    span: Span { start: 0, end: 1 },
+                },
+            ]),
+            typed: AzulaType::Infer,
+            span: Span { start: 0, end: 0 },
+        };
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        let mut environment = Environment::new();
+        let (expr, typ) = typechecker
+            .typecheck_expression(array, &environment)
+            .unwrap();
+
+        assert_eq!(typ, AzulaType::Array(Rc::new(AzulaType::Int), Some(2)));
+        assert_eq!(
+            expr.typed,
+            AzulaType::Array(Rc::new(AzulaType::Int), Some(2))
+        );
+
+        // Different types
+        let array = ExpressionNode {
+            expression: Expression::Array(vec![
+                ExpressionNode {
+                    expression: Expression::Integer(1),
+                    typed: AzulaType::Int,
+                    span: Span { start: 0, end: 1 },
+                },
+                ExpressionNode {
+                    expression: Expression::Boolean(false),
+                    typed: AzulaType::Bool,
+                    span: Span { start: 0, end: 1 },
+                },
+            ]),
+            typed: AzulaType::Infer,
+            span: Span { start: 0, end: 0 },
+        };
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        let mut environment = Environment::new();
+        let (expr, typ) = typechecker
+            .typecheck_expression(array, &environment)
+            .unwrap();
+
+        assert_eq!(typechecker.errors.len(), 1);
+        assert!(matches!(
+            typechecker.errors[0].error_type,
+            ErrorType::MismatchedTypes(..)
+        ));
+    }
+
+    #[test]
+    fn test_struct_access_expression() {
+        // Int
+        let array = ExpressionNode {
+            expression: Expression::StructAccess(
+                Rc::new(ExpressionNode {
+                    expression: Expression::Identifier("x".to_string()),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 0, end: 1 },
+                }),
+                Rc::new(ExpressionNode {
+                    expression: Expression::Identifier("test".to_string()),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 0, end: 1 },
+                }),
+            ),
+            typed: AzulaType::Infer,
+            span: Span { start: 0, end: 0 },
+        };
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        typechecker.structs.insert(
+            "Test".to_string(),
+            StructDefinition {
+                name: "Test",
+                attrs: vec![(AzulaType::Int, "test")],
+                defaults: vec![],
+            },
+        );
+        let mut environment = Environment::new();
+        environment.add_variable(
+            "x".to_string(),
+            VariableDefinition {
+                name: "x".to_string(),
+                mutable: false,
+                typ: AzulaType::Named("Test".to_string()),
+            },
+        );
+        let (expr, typ) = typechecker
+            .typecheck_expression(array, &environment)
+            .unwrap();
+
+        assert_eq!(typ, AzulaType::Int);
+        assert_eq!(expr.typed, AzulaType::Int);
+    }
+
+    #[test]
+    fn test_nameof_expression() {
+        let nameof = |arg: Expression<'static>| ExpressionNode {
+            expression: Expression::FunctionCall {
+                function: Rc::new(ExpressionNode {
+                    expression: Expression::Identifier("nameof".to_string()),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 0, end: 6 },
+                }),
+                args: vec![ExpressionNode {
+                    expression: arg,
+                    typed: AzulaType::Infer,
+                    span: Span { start: 7, end: 8 },
+                }],
+            },
+            typed: AzulaType::Infer,
+            span: Span { start: 0, end: 9 },
+        };
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        let mut environment = Environment::new();
+        environment.add_variable(
+            "x".to_string(),
+            VariableDefinition {
+                name: "x".to_string(),
+                mutable: false,
+                typ: AzulaType::Int,
+            },
+        );
+        let (expr, typ) = typechecker
+            .typecheck_expression(
+                nameof(Expression::Identifier("x".to_string())),
+                &environment,
+            )
+            .unwrap();
+
+        assert_eq!(typ, AzulaType::Pointer(Rc::new(AzulaType::Str)));
+        assert_eq!(expr.expression, Expression::String("x".to_string()));
+
+        // Unknown identifier
+        typechecker
+            .typecheck_expression(
+                nameof(Expression::Identifier("y".to_string())),
+                &environment,
+            )
+            .unwrap_err();
+        assert!(matches!(
+            typechecker.errors[0].error_type,
+            ErrorType::UnknownVariable(..)
+        ));
+
+        // Not an identifier
+        typechecker
+            .typecheck_expression(nameof(Expression::Integer(5)), &environment)
+            .unwrap_err();
+        assert!(matches!(
+            typechecker.errors[1].error_type,
+            ErrorType::InvalidNameof
+        ));
+    }
+
+    #[test]
+    fn test_shift_expression() {
+        let shift = |left: AzulaType<'static>, amount: i64| ExpressionNode {
+            expression: Expression::Infix(
+                Rc::new(ExpressionNode {
+                    expression: Expression::Identifier("x".to_string()),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 0, end: 1 },
+                }),
+                Operator::Shl,
+                Rc::new(ExpressionNode {
+                    expression: Expression::Integer(amount),
+                    typed: left,
+                    span: Span { start: 5, end: 6 },
+                }),
+            ),
+            typed: AzulaType::Infer,
+            span: Span { start: 0, end: 6 },
+        };
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        let mut environment = Environment::new();
+        environment.add_variable(
+            "x".to_string(),
+            VariableDefinition {
+                name: "x".to_string(),
+                mutable: false,
+                typ: AzulaType::Int,
+            },
+        );
+        let (expr, typ) = typechecker
+            .typecheck_expression(shift(AzulaType::Int, 3), &environment)
+            .unwrap();
+        assert_eq!(typ, AzulaType::Int);
+        assert_eq!(expr.typed, AzulaType::Int);
+
+        // Shifting by the full width is rejected
+        typechecker
+            .typecheck_expression(shift(AzulaType::Int, 64), &environment)
+            .unwrap_err();
+        assert!(matches!(
+            typechecker.errors[0].error_type,
+            ErrorType::ShiftOutOfRange(64, 64)
+        ));
+    }
+
+    #[test]
+    fn test_any_assign_and_match() {
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        let mut environment = Environment::new();
+
+        let (stmt, _) = typechecker
+            .typecheck_statement(
+                Statement::Assign(
+                    false,
+                    "x".to_string(),
+                    Some(AzulaType::Any),
+                    ExpressionNode {
+                        expression: Expression::Integer(5),
+                        typed: AzulaType::Infer,
+                        span: Span { start: 0, end: 1 },
+                    },
+                    vec![],
+                    Span { start: 0, end: 1 },
+                ),
+                &mut environment,
+            )
+            .unwrap();
+        if let Statement::Assign(_, _, _, value, _, _) = stmt {
+            assert!(matches!(
+                value.expression,
+                Expression::Cast(_, AzulaType::Any)
+            ));
+            assert_eq!(value.typed, AzulaType::Any);
+        } else {
+            panic!("expected assign statement");
+        }
+
+        let subject = |name: &str| ExpressionNode {
+            expression: Expression::Identifier(name.to_string()),
+            typed: AzulaType::Infer,
+            span: Span { start: 6, end: 7 },
+        };
+        let result = typechecker.typecheck_statement(
+            Statement::Match(
+                subject("x"),
+                vec![(
+                    Some(AzulaType::Int),
+                    Some("i"),
+                    vec![],
+                    Span { start: 10, end: 15 },
+                )],
+                Span { start: 0, end: 20 },
+            ),
+            &mut environment,
+        );
+        assert!(result.is_ok());
+        assert!(!environment.variable_definitions.contains_key("i"));
+
+        environment.add_variable(
+            "y".to_string(),
+            VariableDefinition {
+                name: "y".to_string(),
+                mutable: false,
+                typ: AzulaType::Int,
+            },
+        );
+        let result = typechecker.typecheck_statement(
+            Statement::Match(subject("y"), vec![], Span { start: 0, end: 10 }),
+            &mut environment,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_unused_variable_lint() {
+        let int = |start| ExpressionNode {
+            expression: Expression::Integer(1),
+            typed: AzulaType::Infer,
+            span: Span {
+                start,
+                end: start + 1,
+            },
+        };
+        let ident = |name: &str, start| ExpressionNode {
+            expression: Expression::Identifier(name.to_string()),
+            typed: AzulaType::Infer,
+            span: Span {
+                start,
+                end: start + 1,
+            },
+        };
+        let assign = |name: &str, start| {
+            Statement::Assign(
+                true,
+                name.to_string(),
+                None,
+                int(start + 8),
+                vec![],
+                Span {
+                    start,
+                    end: start + 10,
+                },
+            )
+        };
+
+        let root = Statement::Root(vec![Statement::Function {
+            name: "main",
+            generics: vec![],
+            bounds: vec![],
+            args: vec![],
+            arg_spans: vec![],
+            defaults: vec![],
+            variadic: false,
+            returns: AzulaType::Void,
+            body: Rc::new(Statement::Block(vec![
+                assign("x", 0),
+                assign("y", 10),
+                assign("z", 20),
+                Statement::Reassign(ident("z", 30), int(34), Span { start: 30, end: 36 }),
+                assign("w", 40),
+                Statement::ExpressionStatement(ident("w", 50), Span { start: 50, end: 51 }),
+            ])),
+            public: false,
+            attributes: vec![],
+            doc: None,
+            span: Span { start: 0, end: 60 },
+        }]);
+
+        let mut typechecker = Typechecker::new(root);
+        typechecker.ignores = vec![IgnoreDirective {
+            lints: vec![Lint::UnusedVariable],
+            start: 0,
+            end: 10,
+        }];
+        assert!(typechecker.typecheck().is_ok());
+
+        let unused: Vec<_> = typechecker
+            .warnings
+            .iter()
+            .map(|warning| match &warning.warning_type {
+                WarningType::UnusedVariable(name) => name.clone(),
+                warning => panic!("unexpected warning {:?}", warning),
+            })
+            .collect();
+        assert_eq!(unused, vec!["y".to_string(), "z".to_string()]);
+    }
+
+    #[test]
+    fn test_unused_parameter_lint() {
+        let source = "pub func f(a: int, b: int, _: int, _: int, _c: int): int {
+    return a;
+}
+// azula:ignore unused_parameter
+func g(d: int) {}
+func main() {
+    var x = f(1, 2, 3, 4, 5);
+    g(x);
+}";
+        let lexer = azula_parser::prelude::Lexer::new(source);
+        let mut parser = azula_parser::prelude::Parser::new(source, lexer);
+        let ast = parser.parse();
+        assert!(parser.errors.is_empty());
+
+        let mut typechecker = Typechecker::new(ast);
+        typechecker.ignores = parser.ignores.clone();
+        assert!(typechecker.typecheck().is_ok());
+
+        let unused: Vec<_> = typechecker
+            .warnings
+            .iter()
+            .map(|warning| match &warning.warning_type {
+                WarningType::UnusedParameter(name, function) => (name.as_str(), function.as_str()),
+                warning => panic!("unexpected warning {:?}", warning),
+            })
+            .collect();
+        assert_eq!(unused, vec![("b", "f")]);
+        assert_eq!(
+            (typechecker.warnings[0].start, typechecker.warnings[0].end),
+            (19, 20)
+        );
+    }
+
+    #[test]
+    fn test_reassign() {
+        let reassign = |name: &str, value| {
+            Statement::Reassign(
+                node(Expression::Identifier(name.to_string())),
+                node(value),
+                Span { start: 0, end: 5 },
+            )
+        };
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        typechecker
+            .typecheck_global_assign(Statement::Assign(
+                false,
+                "LIMIT".to_string(),
+                None,
+                node(Expression::Integer(10)),
+                vec![],
+                Span { start: 0, end: 5 },
+            ))
+            .unwrap();
+
+        let mut environment = Environment::new();
+        environment.add_variable(
+            "x".to_string(),
+            VariableDefinition {
+                name: "x".to_string(),
+                mutable: true,
+                typ: AzulaType::Int,
+            },
+        );
+
+        assert!(typechecker
+            .typecheck_statement(reassign("x", Expression::Integer(2)), &mut environment)
+            .is_ok());
+        assert!(typechecker
+            .typecheck_statement(reassign("x", Expression::Boolean(true)), &mut environment)
+            .is_err());
+        assert!(typechecker
+            .typecheck_statement(reassign("LIMIT", Expression::Integer(2)), &mut environment)
+            .is_err());
+        assert!(matches!(
+            typechecker.errors.last().unwrap().error_type,
+            ErrorType::ConstantAssign
+        ));
+    }
+
+    #[test]
+    fn test_global_assign() {
+        let constant = |name: &str, annotation, expression| {
+            Statement::Assign(
+                false,
+                name.to_string(),
+                annotation,
+                ExpressionNode {
+                    expression,
+                    typed: AzulaType::Infer,
+                    span: Span { start: 0, end: 1 },
+                },
+                vec![],
+                Span { start: 0, end: 5 },
+            )
+        };
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        assert!(typechecker
+            .typecheck_global_assign(constant(
+                "PI",
+                Some(AzulaType::Float),
+                Expression::Float(3.14159)
+            ))
+            .is_ok());
+        assert_eq!(typechecker.globals.get("PI").unwrap().typ, AzulaType::Float);
+
+        assert!(typechecker
+            .typecheck_global_assign(constant(
+                "DEBUG",
+                Some(AzulaType::Int),
+                Expression::Boolean(true)
+            ))
+            .is_err());
+        assert!(typechecker
+            .typecheck_global_assign(constant(
+                "TAU",
+                None,
+                Expression::Identifier("PI".to_string())
+            ))
+            .is_ok());
+        assert!(typechecker
+            .typecheck_global_assign(constant(
+                "TWO",
+                None,
+                Expression::Identifier("E".to_string())
+            ))
+            .is_err());
+        assert!(matches!(
+            typechecker.errors.last().unwrap().error_type,
+            ErrorType::NonLiteralConstant
+        ));
+    }
+
+    #[test]
+    fn test_global_var_reassign() {
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        typechecker
+            .typecheck_global_assign(Statement::Assign(
+                true,
+                "count".to_string(),
+                None,
+                node(Expression::Integer(0)),
+                vec![],
+                Span { start: 0, end: 5 },
+            ))
+            .unwrap();
+
+        let reassign = |value| {
+            Statement::Reassign(
+                node(Expression::Identifier("count".to_string())),
+                node(value),
+                Span { start: 0, end: 5 },
+            )
+        };
+        let mut environment = Environment::new();
+        assert!(typechecker
+            .typecheck_statement(reassign(Expression::Integer(1)), &mut environment)
+            .is_ok());
+        assert!(typechecker
+            .typecheck_statement(reassign(Expression::Boolean(true)), &mut environment)
+            .is_err());
+    }
+
+    #[test]
+    fn test_assign_inference() {
+        let call = |name: &str| {
+            node(Expression::FunctionCall {
+                function: Rc::new(node(Expression::Identifier(name.to_string()))),
+                args: vec![],
+            })
+        };
+        let function = |name, returns, body| Statement::Function {
+            name,
+            generics: vec![],
+            bounds: vec![],
+            args: vec![],
+            arg_spans: vec![],
+            defaults: vec![],
+            variadic: false,
+            returns,
+            body: Rc::new(Statement::Block(body)),
+            public: false,
+            attributes: vec![],
+            doc: None,
+            span: Span { start: 0, end: 1 },
+        };
+        let assign = |name: &str, value| {
+            Statement::Assign(
+                false,
+                name.to_string(),
+                None,
+                value,
+                vec![],
+                Span { start: 0, end: 1 },
+            )
+        };
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![
+            function(
+                "get",
+                AzulaType::Float,
+                vec![Statement::Return(
+                    Some(node(Expression::Float(1.0))),
+                    Span { start: 0, end: 1 },
+                )],
+            ),
+            function("main", AzulaType::Void, vec![assign("x", call("get"))]),
+        ]));
+        let root = typechecker.typecheck().unwrap();
+        let body = match root {
+            Statement::Root(stmts) => match stmts[1].clone() {
+                Statement::Function { body, .. } => body,
+                _ => panic!("expected function"),
+            },
+            _ => panic!("expected root"),
+        };
+        match body.deref() {
+            Statement::Block(stmts) => match &stmts[0] {
+                Statement::Assign(_, _, annotation, value, _, _) => {
+                    assert_eq!(annotation, &Some(AzulaType::Float));
+                    assert_eq!(value.typed, AzulaType::Float);
+                }
+                _ => panic!("expected assign"),
+            },
+            _ => panic!("expected block"),
+        }
+
+        let mut environment = Environment::new();
+        assert!(typechecker
+            .typecheck_assign(
+                assign("y", node(Expression::Identifier("nil".to_string()))),
+                &mut environment
+            )
+            .is_err());
+        assert!(matches!(
+            typechecker.errors.last().unwrap().error_type,
+            ErrorType::CannotInferType(..)
+        ));
+    }
+
+    #[test]
+    fn test_cast_expression() {
+        let cast = |expression, target| ExpressionNode {
+            expression: Expression::Cast(
+                Rc::new(ExpressionNode {
+                    expression,
+                    typed: AzulaType::Infer,
+                    span: Span { start: 0, end: 1 },
+                }),
+                target,
+            ),
+            typed: AzulaType::Infer,
+            span: Span { start: 0, end: 8 },
+        };
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        let environment = Environment::new();
+
+        let (expr, typ) = typechecker
+            .typecheck_expression(
+                cast(Expression::Integer(5), AzulaType::SizedSignedInt(32)),
+                &environment,
+            )
+            .unwrap();
+        assert_eq!(typ, AzulaType::SizedSignedInt(32));
+        assert_eq!(expr.typed, AzulaType::SizedSignedInt(32));
+
+        let (_, typ) = typechecker
+            .typecheck_expression(
+                cast(Expression::Boolean(true), AzulaType::Float),
+                &environment,
+            )
+            .unwrap();
+        assert_eq!(typ, AzulaType::Float);
+
+        // Casting to the same type leaves the expression alone
+        let (expr, _) = typechecker
+            .typecheck_expression(cast(Expression::Integer(5), AzulaType::Int), &environment)
+            .unwrap();
+        assert_eq!(expr.expression, Expression::Integer(5));
+
+        assert!(typechecker
+            .typecheck_expression(
+                cast(
+                    Expression::String("a".to_string()),
+                    AzulaType::SizedUnsignedInt(8)
+                ),
+                &environment
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_numeric_promotion() {
+        assert_eq!(
+            promoted_type(&AzulaType::SizedSignedInt(32), &AzulaType::Int),
+            Some(AzulaType::Int)
+        );
+        assert_eq!(
+            promoted_type(&AzulaType::SizedSignedInt(64), &AzulaType::Int),
+            Some(AzulaType::Int)
+        );
+        assert_eq!(
+            promoted_type(
+                &AzulaType::SizedUnsignedInt(8),
+                &AzulaType::SizedUnsignedInt(32)
+            ),
+            Some(AzulaType::SizedUnsignedInt(32))
+        );
+        assert_eq!(
+            promoted_type(
+                &AzulaType::SizedUnsignedInt(32),
+                &AzulaType::SizedSignedInt(64)
+            ),
+            Some(AzulaType::SizedSignedInt(64))
+        );
+        assert_eq!(
+            promoted_type(&AzulaType::SizedSignedInt(64), &AzulaType::SizedFloat(32)),
+            None
+        );
+        assert_eq!(
+            promoted_type(&AzulaType::SizedUnsignedInt(64), &AzulaType::Int),
+            None
+        );
+        assert_eq!(promoted_type(&AzulaType::Int, &AzulaType::Float), None);
+        assert_eq!(
+            promoted_type(&AzulaType::SizedFloat(32), &AzulaType::Float),
+            Some(AzulaType::Float)
+        );
+
+        let add = |left, right| {
+            node(Expression::Infix(
+                Rc::new(node(left)),
+                Operator::Add,
+                Rc::new(node(right)),
+            ))
+        };
+        let as_i32 = Expression::Cast(
+            Rc::new(node(Expression::Integer(5))),
+            AzulaType::SizedSignedInt(32),
+        );
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        let environment = Environment::new();
+
+        let (expr, typ) = typechecker
+            .typecheck_expression(add(as_i32, Expression::Integer(6)), &environment)
+            .unwrap();
+        assert_eq!(typ, AzulaType::Int);
+        if let Expression::Infix(left, _, _) = expr.expression {
+            assert!(matches!(
+                left.expression,
+                Expression::Cast(_, AzulaType::Int)
+            ));
+        } else {
+            panic!("expected infix");
+        }
+
+        assert!(typechecker
+            .typecheck_expression(
+                add(Expression::Integer(5), Expression::Float(1.0)),
+                &environment
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_build_info_expression() {
+        let build_info = ExpressionNode {
+            expression: Expression::FunctionCall {
+                function: Rc::new(ExpressionNode {
+                    expression: Expression::Identifier("build_info".to_string()),
+                    typed: AzulaType::Infer,
+                    span: Span { start: 0, end: 10 },
+                }),
+                args: vec![],
+            },
+            typed: AzulaType::Infer,
+            span: Span { start: 0, end: 12 },
+        };
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        let environment = Environment::new();
+
+        assert!(typechecker
+            .typecheck_expression(build_info.clone(), &environment)
+            .is_err());
+        assert!(matches!(
+            typechecker.errors[0].error_type,
+            ErrorType::BuildInfoNotEmbedded
+        ));
+
+        typechecker.build_info = Some("version=0.2.0".to_string());
+        let (expr, typ) = typechecker
+            .typecheck_expression(build_info, &environment)
+            .unwrap();
+        assert_eq!(typ, AzulaType::Pointer(Rc::new(AzulaType::Str)));
+        assert_eq!(
+            expr.expression,
+            Expression::String("version=0.2.0".to_string())
+        );
+    }
+
+    #[test]
+    fn test_ternary_expression() {
+        let ternary = |cond, then, otherwise| {
+            node(Expression::Ternary(
+                Rc::new(node(cond)),
+                Rc::new(node(then)),
+                Rc::new(node(otherwise)),
+            ))
+        };
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        let environment = Environment::new();
+
+        let (expr, typ) = typechecker
+            .typecheck_expression(
+                ternary(
+                    Expression::Boolean(true),
+                    Expression::Integer(1),
+                    Expression::Integer(2),
+                ),
+                &environment,
+            )
+            .unwrap();
+        assert_eq!(typ, AzulaType::Int);
+        assert_eq!(expr.typed, AzulaType::Int);
+
+        assert!(typechecker
+            .typecheck_expression(
+                ternary(
+                    Expression::Integer(1),
+                    Expression::Integer(1),
+                    Expression::Integer(2),
+                ),
+                &environment,
+            )
+            .is_err());
+        assert!(typechecker
+            .typecheck_expression(
+                ternary(
+                    Expression::Boolean(true),
+                    Expression::Integer(1),
+                    Expression::Boolean(false),
+                ),
+                &environment,
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_destructure() {
+        let destructure = |names, value| {
+            Statement::Destructure(false, names, node(value), Span { start: 0, end: 10 })
+        };
+        let pair = || {
+            Expression::Tuple(vec![
+                node(Expression::Integer(1)),
+                node(Expression::Boolean(true)),
+            ])
+        };
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        let mut environment = Environment::new();
+
+        typechecker
+            .typecheck_statement(destructure(vec!["a", "b"], pair()), &mut environment)
+            .unwrap();
+        assert_eq!(
+            environment.variable_definitions.get("a").unwrap().typ,
+            AzulaType::Int
+        );
+        assert_eq!(
+            environment.variable_definitions.get("b").unwrap().typ,
+            AzulaType::Bool
+        );
+
+        assert!(typechecker
+            .typecheck_statement(destructure(vec!["a"], pair()), &mut environment)
+            .is_err());
+        assert!(matches!(
+            typechecker.errors[0].error_type,
+            ErrorType::DestructureMismatch(1, _)
+        ));
+    }
+
+    #[test]
+    fn test_on_signal() {
+        let on_signal = |handler: &str| {
+            node(Expression::FunctionCall {
+                function: Rc::new(node(Expression::Identifier("on_signal".to_string()))),
+                args: vec![
+                    node(Expression::Identifier("SIGINT".to_string())),
+                    node(Expression::Identifier(handler.to_string())),
+                ],
+            })
+        };
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        typechecker.functions.insert(
+            "handle",
+            FunctionDefinition {
+                name: "handle",
+                args: vec![(AzulaType::SizedSignedInt(32), "signal")],
+                defaults: vec![],
+                variadic: false,
+                varargs: false,
+                returns: AzulaType::Void,
+                generics: vec![],
+            },
+        );
+        typechecker.functions.insert(
+            "main",
+            FunctionDefinition {
+                name: "main",
+                args: vec![],
+                defaults: vec![],
+                variadic: false,
+                varargs: false,
+                returns: AzulaType::Void,
+                generics: vec![],
+            },
+        );
+        let environment = Environment::new();
+
+        let (expr, typ) = typechecker
+            .typecheck_expression(on_signal("handle"), &environment)
+            .unwrap();
+        assert_eq!(typ, AzulaType::Void);
+        match expr.expression {
+            Expression::FunctionCall { args, .. } => {
+                assert_eq!(args[0].expression, Expression::Integer(2))
+            }
+            _ => panic!("expected call, got {:?}", expr),
+        }
+
+        assert!(typechecker
+            .typecheck_expression(on_signal("main"), &environment)
+            .is_err());
+        assert!(matches!(
+            typechecker.errors[0].error_type,
+            ErrorType::InvalidSignalHandler(_)
+        ));
+    }
+
+    #[test]
+    fn test_optional() {
+        let optional = AzulaType::Optional(Rc::new(AzulaType::Int));
+        let assign = |name: &str, value| {
+            Statement::Assign(
+                false,
+                name.to_string(),
+                Some(optional.clone()),
+                node(value),
+                vec![],
+                Span { start: 0, end: 1 },
+            )
+        };
+        let if_let = |value: &str| {
+            Statement::IfLet(
+                false,
+                "y",
+                node(Expression::Identifier(value.to_string())),
+                vec![Statement::ExpressionStatement(
+                    node(Expression::Identifier("y".to_string())),
+                    Span { start: 0, end: 1 },
+                )],
+                Span { start: 0, end: 1 },
+            )
+        };
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+        let mut environment = Environment::new();
+
+        for value in [
+            Expression::Identifier("nil".to_string()),
+            Expression::Integer(1),
+        ] {
+            match typechecker
+                .typecheck_statement(assign("x", value), &mut environment)
+                .unwrap()
+                .0
+            {
+                Statement::Assign(_, _, _, value, _, _) => {
+                    assert!(matches!(value.expression, Expression::Cast(_, _)));
+                    assert_eq!(value.typed, optional);
+                }
+                stmt => panic!("expected assign, got {:?}", stmt),
+            }
+        }
+        assert!(typechecker
+            .typecheck_statement(assign("z", Expression::Boolean(true)), &mut environment)
+            .is_err());
+
+        // The body reads the value inside, which is gone after it
+        match typechecker
+            .typecheck_statement(if_let("x"), &mut environment)
+            .unwrap()
+            .0
+        {
+            Statement::IfLet(_, _, _, body, _) => match &body[0] {
+                Statement::ExpressionStatement(value, _) => {
+                    assert_eq!(value.typed, AzulaType::Int)
+                }
+                stmt => panic!("expected expression, got {:?}", stmt),
+            },
+            stmt => panic!("expected if var, got {:?}", stmt),
+        }
+        assert!(environment.variable_definitions.get("y").is_none());
+
+        environment.add_variable(
+            "i".to_string(),
+            VariableDefinition {
+                name: "i".to_string(),
+                mutable: false,
+                typ: AzulaType::Int,
+            },
+        );
+        assert!(typechecker
+            .typecheck_statement(if_let("i"), &mut environment)
+            .is_err());
+        assert!(matches!(
+            typechecker.errors.last().unwrap().error_type,
+            ErrorType::NonOptional(_)
+        ));
+    }
+
+    #[test]
+    fn test_result() {
+        let call = |name: &str, args| {
+            node(Expression::FunctionCall {
+                function: Rc::new(node(Expression::Identifier(name.to_string()))),
+                args,
+            })
+        };
+        let ret = |value| Statement::Return(value, Span { start: 0, end: 1 });
+        let function = |name, returns, body| Statement::Function {
+            name,
+            generics: vec![],
+            bounds: vec![],
+            args: vec![],
+            arg_spans: vec![],
+            defaults: vec![],
+            variadic: false,
+            returns,
+            body: Rc::new(Statement::Block(body)),
+            public: false,
+            attributes: vec![],
+            doc: None,
+            span: Span { start: 0, end: 1 },
+        };
+        let result = |typ| AzulaType::Result(Rc::new(typ));
+
+        let parse = || {
+            function(
+                "parse",
+                result(AzulaType::Int),
+                vec![ret(Some(call(
+                    "error",
+                    vec![node(Expression::String("bad".to_string()))],
+                )))],
+            )
+        };
+        let try_parse = || {
+            Statement::ExpressionStatement(
+                node(Expression::Try(
+                    Rc::new(call("parse", vec![])),
+                    AzulaType::Infer,
+                )),
+                Span { start: 0, end: 1 },
+            )
+        };
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![
+            parse(),
+            function("main", AzulaType::Void, vec![try_parse()]),
+        ]));
+        assert!(typechecker.typecheck().is_err());
+        assert!(matches!(
+            typechecker.errors[0].error_type,
+            ErrorType::TryOutsideResultFunction
+        ));
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![
+            parse(),
+            function("run", result(AzulaType::Void), vec![try_parse(), ret(None)]),
+        ]));
+        let body = match typechecker.typecheck().unwrap() {
+            Statement::Root(stmts) => match stmts[1].clone() {
+                Statement::Function { body, .. } => body,
+                _ => panic!("expected function"),
+            },
+            _ => panic!("expected root"),
+        };
+        match body.deref() {
+            Statement::Block(stmts) => {
+                match &stmts[0] {
+                    Statement::ExpressionStatement(value, _) => {
+                        assert_eq!(value.typed, AzulaType::Int);
+                        assert!(matches!(
+                            &value.expression,
+                            Expression::Try(_, returns) if *returns == result(AzulaType::Void)
+                        ));
+                    }
+                    stmt => panic!("expected expression, got {:?}", stmt),
+                }
+                match &stmts[1] {
+                    Statement::Return(Some(value), _) => {
+                        assert!(matches!(value.expression, Expression::Cast(..)));
+                        assert_eq!(value.typed, result(AzulaType::Void));
+                    }
+                    stmt => panic!("expected return, got {:?}", stmt),
+                }
+            }
+            stmt => panic!("expected block, got {:?}", stmt),
+        }
+    }
+
+    #[test]
+    fn test_defer() {
+        let defer = || Statement::Defer(node(Expression::Integer(1)), Span { start: 0, end: 1 });
+        let function = |body| Statement::Function {
+            name: "main",
+            generics: vec![],
+            bounds: vec![],
+            args: vec![],
+            arg_spans: vec![],
+            defaults: vec![],
+            variadic: false,
+            returns: AzulaType::Void,
+            body: Rc::new(Statement::Block(body)),
+            public: false,
+            attributes: vec![],
+            doc: None,
+            span: Span { start: 0, end: 1 },
+        };
+
+        let mut typechecker = Typechecker::new(Statement::Root(vec![function(vec![defer()])]));
+        assert!(typechecker.typecheck().is_ok());
+
+        let mut typechecker =
+            Typechecker::new(Statement::Root(vec![function(vec![Statement::While(
+                node(Expression::Boolean(true)),
+                vec![defer()],
+                None,
+                Span { start: 0, end: 1 },
+            )])]));
+        assert!(typechecker.typecheck().is_err());
+        assert!(matches!(
+            typechecker.errors[0].error_type,
+            ErrorType::NestedDefer
+        ));
+    }
+
+    #[test]
+    fn test_default_params() {
+        let call = |args| {
+            Statement::ExpressionStatement(
+                node(Expression::FunctionCall {
+                    function: Rc::new(node(Expression::Identifier("add".to_string()))),
+                    args,
+                }),
+                Span { start: 0, end: 1 },
+            )
+        };
+        let root = |body| {
+            Statement::Root(vec![
+                Statement::Function {
+                    name: "add",
+                    generics: vec![],
+                    bounds: vec![],
+                    args: vec![(AzulaType::Int, "x"), (AzulaType::Int, "y")],
+                    arg_spans: vec![],
+                    defaults: vec![node(Expression::Integer(1))],
+                    variadic: false,
+                    returns: AzulaType::Void,
+                    body: Rc::new(Statement::Block(vec![])),
+                    public: false,
+                    attributes: vec![],
+                    doc: None,
+                    span: Span { start: 0, end: 1 },
+                },
+                Statement::Function {
+                    name: "main",
+                    generics: vec![],
+                    bounds: vec![],
+                    args: vec![],
+                    arg_spans: vec![],
+                    defaults: vec![],
+                    variadic: false,
+                    returns: AzulaType::Void,
+                    body: Rc::new(Statement::Block(body)),
+                    public: false,
+                    attributes: vec![],
+                    doc: None,
+                    span: Span { start: 0, end: 1 },
+                },
+            ])
+        };
+
+        let mut typechecker =
+            Typechecker::new(root(vec![call(vec![node(Expression::Integer(5))])]));
+        let body = match typechecker.typecheck().unwrap() {
+            Statement::Root(stmts) => match stmts[1].clone() {
+                Statement::Function { body, .. } => body,
+                _ => panic!("expected function"),
+            },
+            _ => panic!("expected root"),
+        };
+        match body.deref() {
+            Statement::Block(stmts) => match &stmts[0] {
+                Statement::ExpressionStatement(
+                    ExpressionNode {
+                        expression: Expression::FunctionCall { args, .. },
+                        ..
+                    },
+                    _,
+                ) => assert_eq!(
+                    args.iter()
+                        .map(|arg| arg.expression.clone())
+                        .collect::<Vec<_>>(),
+                    vec![Expression::Integer(5), Expression::Integer(1)]
+                ),
+                stmt => panic!("expected call, got {:?}", stmt),
+            },
+            _ => panic!("expected block"),
+        }
+
+        let mut typechecker = Typechecker::new(root(vec![call(vec![])]));
+        assert!(typechecker.typecheck().is_err());
+        assert!(matches!(
+            &typechecker.errors[0].error_type,
+            ErrorType::NotEnoughArguments(name, 1, 0) if name == "add"
+        ));
+    }
+
+    #[test]
+    fn test_variadic_params() {
+        let call = |args| {
+            Statement::ExpressionStatement(
+                node(Expression::FunctionCall {
+                    function: Rc::new(node(Expression::Identifier("sum".to_string()))),
+                    args,
+                }),
+                Span { start: 0, end: 1 },
+            )
+        };
+        let root = Statement::Root(vec![
+            Statement::Function {
+                name: "sum",
+                generics: vec![],
+                bounds: vec![],
+                args: vec![
+                    (AzulaType::Int, "first"),
+                    (AzulaType::Slice(Rc::new(AzulaType::Int)), "rest"),
+                ],
+                arg_spans: vec![],
+                defaults: vec![],
+                variadic: true,
+                returns: AzulaType::Void,
+                body: Rc::new(Statement::Block(vec![])),
+                public: false,
+                attributes: vec![],
+                doc: None,
+                span: Span { start: 0, end: 1 },
+            },
+            Statement::Function {
+                name: "main",
+                generics: vec![],
+                bounds: vec![],
+                args: vec![],
+                arg_spans: vec![],
+                defaults: vec![],
+                variadic: false,
+                returns: AzulaType::Void,
+                body: Rc::new(Statement::Block(vec![
+                    call(vec![
+                        node(Expression::Integer(1)),
+                        node(Expression::Integer(2)),
+                        node(Expression::Integer(3)),
+                    ]),
+                    call(vec![node(Expression::Integer(1))]),
+                ])),
+                public: false,
+                attributes: vec![],
+                doc: None,
+                span: Span { start: 0, end: 1 },
+            },
+        ]);
+
+        let mut typechecker = Typechecker::new(root);
+        let body = match typechecker.typecheck().unwrap() {
+            Statement::Root(stmts) => match stmts[1].clone() {
+                Statement::Function { body, .. } => body,
+                _ => panic!("expected function"),
+            },
+            _ => panic!("expected root"),
+        };
+        let calls: Vec<_> = match body.deref() {
+            Statement::Block(stmts) => stmts
+                .iter()
+                .map(|stmt| match stmt {
+                    Statement::ExpressionStatement(
+                        ExpressionNode {
+                            expression: Expression::FunctionCall { args, .. },
+                            ..
+                        },
+                        _,
+                    ) => args.clone(),
+                    stmt => panic!("expected call, got {:?}", stmt),
+                })
+                .collect(),
+            _ => panic!("expected block"),
+        };
+
+        let rest = |values: Vec<i64>| {
+            values
+                .into_iter()
+                .map(Expression::Integer)
+                .collect::<Vec<_>>()
+        };
+        // The rest are a slice of an array of them, with its length
+        for (args, expected) in calls.iter().zip([rest(vec![2, 3]), rest(vec![])]) {
+            assert_eq!(args.len(), 2);
+            assert_eq!(
+                typechecker.slice_element(&args[1].typed),
+                Some(AzulaType::Int)
+            );
+            match &args[1].expression {
+                Expression::StructInitialisation(_, fields) => match (&fields[0], &fields[1]) {
+                    (
+                        (
+                            "data",
+                            ExpressionNode {
+                                expression: Expression::Array(values),
+                                ..
+                            },
+                        ),
+                        (
+                            "len",
+                            ExpressionNode {
+                                expression: Expression::Integer(len),
+                                ..
+                            },
+                        ),
+                    ) => {
+                        assert_eq!(
+                            values
+                                .iter()
+                                .map(|value| value.expression.clone())
+                                .collect::<Vec<_>>(),
+                            expected
+                        );
+                        assert_eq!(*len, expected.len() as i64);
+                    }
+                    fields => panic!("expected data and len, got {:?}", fields),
+                },
+                expr => panic!("expected slice, got {:?}", expr),
+            }
+        }
+
+        // The body can loop over them and read how many there are
+        let (result, errors) = typecheck(
+            "func sum(xs: int...): int {
+    var total = 0;
+    for x in xs {
+        total = total + x;
+    }
+    return total;
+}
+func count(xs: int...): int {
+    return xs.len;
+}
+func main() {
+    var six = sum(1, 2, 3);
+    var none = sum();
+    var three = count(4, 5, 6);
+}",
+        );
+        assert!(result.is_ok(), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_named_arguments() {
+        let named = |name, value| {
+            node(Expression::NamedArgument(
+                name,
+                Rc::new(node(Expression::Integer(value))),
+            ))
+        };
+        let check = |args| {
+            let root = Statement::Root(vec![
+                Statement::Function {
+                    name: "make_rect",
+                    generics: vec![],
+                    bounds: vec![],
+                    args: vec![
+                        (AzulaType::Int, "width"),
+                        (AzulaType::Int, "height"),
+                        (AzulaType::Int, "depth"),
+                    ],
+                    arg_spans: vec![],
+                    defaults: vec![node(Expression::Integer(0)), node(Expression::Integer(1))],
+                    variadic: false,
+                    returns: AzulaType::Void,
+                    body: Rc::new(Statement::Block(vec![])),
+                    public: false,
+                    attributes: vec![],
+                    doc: None,
+                    span: Span { start: 0, end: 1 },
+                },
+                Statement::Function {
+                    name: "main",
+                    generics: vec![],
+                    bounds: vec![],
+                    args: vec![],
+                    arg_spans: vec![],
+                    defaults: vec![],
+                    variadic: false,
+                    returns: AzulaType::Void,
+                    body: Rc::new(Statement::Block(vec![Statement::ExpressionStatement(
+                        node(Expression::FunctionCall {
+                            function: Rc::new(node(Expression::Identifier(
+                                "make_rect".to_string(),
+                            ))),
+                            args,
+                        }),
+                        Span { start: 0, end: 1 },
+                    )])),
+                    public: false,
+                    attributes: vec![],
+                    doc: None,
+                    span: Span { start: 0, end: 1 },
+                },
+            ]);
+
+            let mut typechecker = Typechecker::new(root);
+            match typechecker.typecheck() {
+                Ok(Statement::Root(stmts)) => match &stmts[1] {
+                    Statement::Function { body, .. } => match body.deref() {
+                        Statement::Block(stmts) => match &stmts[0] {
+                            Statement::ExpressionStatement(
+                                ExpressionNode {
+                                    expression: Expression::FunctionCall { args, .. },
+                                    ..
+                                },
+                                _,
+                            ) => Ok(args
+                                .iter()
+                                .map(|arg| arg.expression.clone())
+                                .collect::<Vec<_>>()),
+                            stmt => panic!("expected call, got {:?}", stmt),
+                        },
+                        _ => panic!("expected block"),
+                    },
+                    _ => panic!("expected function"),
+                },
+                _ => Err(typechecker.errors.remove(0).error_type),
+            }
+        };
+
+        let ints = |values: Vec<i64>| {
+            Some(
+                values
+                    .into_iter()
+                    .map(Expression::Integer)
+                    .collect::<Vec<_>>(),
+            )
+        };
+        assert_eq!(
+            check(vec![named("depth", 5), named("width", 3)]).ok(),
+            ints(vec![3, 0, 5])
+        );
+        assert_eq!(
+            check(vec![node(Expression::Integer(3)), named("depth", 5)]).ok(),
+            ints(vec![3, 0, 5])
+        );
+        assert!(matches!(
+            check(vec![named("height", 4)]),
+            Err(ErrorType::MissingArgument(function, param)) if function == "make_rect" && param == "width"
+        ));
+        assert!(matches!(
+            check(vec![named("size", 4)]),
+            Err(ErrorType::UnknownArgument(_, name)) if name == "size"
+        ));
+        assert!(matches!(
+            check(vec![node(Expression::Integer(3)), named("width", 4)]),
+            Err(ErrorType::DuplicateArgument(name)) if name == "width"
+        ));
+        assert!(matches!(
+            check(vec![named("width", 3), node(Expression::Integer(4))]),
+            Err(ErrorType::PositionalAfterNamed)
+        ));
+
+        // Builtins don't have parameter names to give
+        for input in [
+            "func f(): !void { return error(msg: \"bad\"); }\nfunc main() {}",
+            "func main() { var n = len(s: \"azula\"); }",
+        ] {
+            let (result, errors) = typecheck(input);
+            assert!(result.is_err());
+            assert!(
+                matches!(
+                    &errors[0].error_type,
+                    ErrorType::UnexpectedNamedArgument(name) if name == "msg" || name == "s"
+                ),
+                "{:?}",
+                errors
+            );
+        }
+    }
+
+    #[test]
+    fn test_closures() {
+        let check = |source: &'static str| {
+            let (result, mut errors) = typecheck(source);
+            match result {
+                Ok(Statement::Root(stmts)) => match &stmts[0] {
+                    Statement::Function { body, .. } => match body.deref() {
+                        Statement::Block(stmts) => Ok(stmts
+                            .iter()
+                            .filter_map(|stmt| match stmt {
+                                Statement::Assign(_, _, _, value, _, _) => Some(value.clone()),
+                                _ => None,
+                            })
+                            .collect::<Vec<_>>()),
+                        _ => panic!("expected block"),
+                    },
+                    _ => panic!("expected function"),
+                },
+                _ => Err(errors.remove(0).error_type),
+            }
+        };
+
+        let values = check(
+            "func main() {
+    var z = 2;
+    var f = |x: int, y: int| x * y + z;
+    var result = f(3, 4);
+}",
+        )
+        .unwrap();
+        match &values[1].expression {
+            Expression::Closure { captures, .. } => {
+                assert_eq!(captures, &vec![(AzulaType::Int, "z".to_string())])
+            }
+            expr => panic!("expected closure, got {:?}", expr),
+        }
+        assert_eq!(
+            values[1].typed,
+            AzulaType::Function(
+                vec![AzulaType::Int, AzulaType::Int],
+                Rc::new(AzulaType::Int)
+            )
+        );
+        assert_eq!(values[2].typed, AzulaType::Int);
+
+        assert!(matches!(
+            check(
+                "func main() {
+    var f = |x: int| x;
+    var result = f(1, 2);
+}"
+            ),
+            Err(ErrorType::WrongArgumentCount(_, 1, 2))
+        ));
+
+        // A closure made or returned into a local is dropped at the end of
+        // its block, a copy of another variable isn't
+        let (result, errors) = typecheck(
+            "func adder(n: int): fn(int) -> int {
+    return |x: int| x + n;
+}
+func main() {
+    var add = |x: int| x + 1;
+    var two = adder(2);
+    var copy = add;
+}",
+        );
+        assert!(errors.is_empty(), "{:?}", errors);
+        let body = match result {
+            Ok(Statement::Root(stmts)) => match &stmts[1] {
+                Statement::Function { body, .. } => match body.deref() {
+                    Statement::Block(stmts) => stmts.clone(),
+                    _ => panic!("expected block"),
+                },
+                _ => panic!("expected function"),
+            },
+            result => panic!("expected root, got {:?}", result),
+        };
+        let dropped: Vec<_> = body
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Statement::Defer(call, _) => match &call.expression {
+                    Expression::FunctionCall { function, args } => {
+                        assert_eq!(
+                            function.expression,
+                            Expression::Identifier("closure.drop".to_string())
+                        );
+                        Some(args[0].expression.clone())
+                    }
+                    call => panic!("expected call, got {:?}", call),
+                },
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            dropped,
+            vec![
+                Expression::Identifier("add".to_string()),
+                Expression::Identifier("two".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_function_values() {
+        let check = |source: &'static str| {
+            let (result, mut errors) = typecheck(source);
+            match result {
+                Ok(Statement::Root(stmts)) => match &stmts[1] {
+                    Statement::Function { body, .. } => match body.deref() {
+                        Statement::Block(stmts) => Ok(stmts
+                            .iter()
+                            .filter_map(|stmt| match stmt {
+                                Statement::Assign(_, _, _, value, _, _) => {
+                                    Some(value.typed.clone())
+                                }
+                                _ => None,
+                            })
+                            .collect::<Vec<_>>()),
+                        _ => panic!("expected block"),
+                    },
+                    _ => panic!("expected function"),
+                },
+                _ => Err(errors.remove(0).error_type),
+            }
+        };
+
+        let double = AzulaType::Function(vec![AzulaType::Int], Rc::new(AzulaType::Int));
+        assert_eq!(
+            check(
+                "func double(x: int): int {
+    return x * 2;
+}
+func main() {
+    var f: fn(int) -> int = double;
+    var fs = [double, f];
+    var result = fs[1](3);
+}"
+            )
+            .ok(),
+            Some(vec![
+                double.clone(),
+                AzulaType::Array(Rc::new(double), Some(2)),
+                AzulaType::Int
+            ])
+        );
+
+        assert!(matches!(
+            check(
+                "func double(x: int): int {
+    return x * 2;
+}
+func main() {
+    var f: fn(bool) -> int = double;
+}"
+            ),
+            Err(ErrorType::MismatchedAssignTypes(..))
+        ));
+        assert!(matches!(
+            check(
+                "func double(x: int): int {
+    return x * 2;
+}
+func main() {
+    var x = [1, 2];
+    var result = x[0](3);
+}"
+            ),
+            Err(ErrorType::NotCallable(_))
+        ));
+    }
+
+    #[test]
+    fn test_float_widths() {
+        let check = |source: &'static str| {
+            let (result, mut errors) = typecheck(source);
+            match result {
+                Ok(Statement::Root(mut stmts)) => match stmts.remove(0) {
+                    Statement::Function { body, .. } => match body.deref() {
+                        Statement::Block(stmts) => Ok(stmts
+                            .iter()
+                            .map(|stmt| match stmt {
+                                Statement::Assign(_, _, _, value, _, _)
+                                | Statement::ExpressionStatement(value, _) => value.clone(),
+                                stmt => panic!("unexpected {:?}", stmt),
+                            })
+                            .collect::<Vec<_>>()),
+                        _ => panic!("expected block"),
+                    },
+                    _ => panic!("expected function"),
+                },
+                _ => Err(errors.remove(0).error_type),
+            }
+        };
+        fn cast_to<'a>(expr: &ExpressionNode<'a>) -> Option<(AzulaType<'a>, AzulaType<'a>)> {
+            match &expr.expression {
+                Expression::Cast(inner, typ) => Some((inner.typed.clone(), typ.clone())),
+                _ => None,
+            }
+        }
+
+        let values = check(
+            "func main() {
+    var x: f32 = 1.5;
+    var y: float = x;
+    var z = x * 2.0;
+    printf(\"%f\", x);
+}",
+        )
+        .unwrap();
+        assert_eq!(
+            cast_to(&values[0]),
+            Some((AzulaType::Float, AzulaType::SizedFloat(32)))
+        );
+        assert_eq!(
+            cast_to(&values[1]),
+            Some((AzulaType::SizedFloat(32), AzulaType::Float))
+        );
+        assert_eq!(values[2].typed, AzulaType::SizedFloat(32));
+        match &values[3].expression {
+            Expression::FunctionCall { args, .. } => assert_eq!(
+                cast_to(&args[1]),
+                Some((AzulaType::SizedFloat(32), AzulaType::Float))
+            ),
+            expr => panic!("expected call, got {:?}", expr),
+        }
+
+        assert!(matches!(
+            check(
+                "func main() {
+    var x = 1.5;
+    var y: f32 = x;
+}"
+            ),
+            Err(ErrorType::MismatchedAssignTypes(..))
+        ));
+    }
+
+    #[test]
+    fn test_non_bool_condition() {
+        let check = |source: &'static str| {
+            let (result, mut errors) = typecheck(source);
+            assert!(result.is_err());
+            let error = errors.remove(0);
+            match error.error_type {
+                ErrorType::NonBoolCondition(_, hint) => (&source[error.start..error.end], hint),
+                error_type => panic!("expected non-bool condition, got {:?}", error_type),
+            }
+        };
+
+        assert_eq!(
+            check("func main() {\n    var x = 1;\n    if x {\n    }\n}"),
+            ("x", Some("!= 0".to_string()))
+        );
+        assert_eq!(
+            check("func main() {\n    var x = 1.5;\n    while true && x {\n    }\n}"),
+            ("x", Some("!= 0.0".to_string()))
+        );
+        assert_eq!(
+            check("func main() {\n    var x = \"a\";\n    if x {\n    }\n}"),
+            ("x", None)
+        );
+    }
+
+    #[test]
+    fn test_generic_functions() {
+        let check = |source: &'static str| {
+            let (result, mut errors) = typecheck(source);
+            match result {
+                Ok(Statement::Root(stmts)) => Ok(stmts),
+                _ => Err(errors.remove(0).error_type),
+            }
+        };
+        const MAX: &str = "func max<T>(a: T, b: T): T {
+    if a > b {
+        return a;
+    }
+    return b;
+}
+";
+
+        let source = Box::leak(
+            format!(
+                "{}func main() {{
+    var x = max(1, 2);
+    var y = max(b: 1.5, a: 2.5);
+    var z = max(3, 4);
+}}",
+                MAX
+            )
+            .into_boxed_str(),
+        );
+        let stmts = check(source).unwrap();
+        let functions: Vec<_> = stmts
+            .iter()
+            .map(|stmt| match stmt {
+                Statement::Function { name, args, .. } => (*name, args.clone()),
+                stmt => panic!("expected function, got {:?}", stmt),
+            })
+            .collect();
+        assert_eq!(
+            functions,
+            vec![
+                ("main", vec![]),
+                (
+                    "max.int",
+                    vec![(AzulaType::Int, "a"), (AzulaType::Int, "b")]
+                ),
+                (
+                    "max.float",
+                    vec![(AzulaType::Float, "a"), (AzulaType::Float, "b")]
+                ),
+            ]
+        );
+        match &stmts[0] {
+            Statement::Function { body, .. } => match body.deref() {
+                Statement::Block(stmts) => match &stmts[1] {
+                    Statement::Assign(_, _, _, value, _, _) => {
+                        assert_eq!(value.typed, AzulaType::Float);
+                        match &value.expression {
+                            Expression::FunctionCall { function, .. } => assert_eq!(
+                                function.expression,
+                                Expression::Identifier("max.float".to_string())
+                            ),
+                            expr => panic!("expected call, got {:?}", expr),
+                        }
+                    }
+                    stmt => panic!("expected assign, got {:?}", stmt),
+                },
+                _ => panic!("expected block"),
+            },
+            _ => panic!("expected function"),
+        }
+
+        let source = Box::leak(
+            format!("{}func main() {{\n    var x = max(1, 2.5);\n}}", MAX).into_boxed_str(),
+        );
+        assert!(matches!(
+            check(source),
+            Err(ErrorType::ConflictingTypeArguments(..))
+        ));
+        assert!(matches!(
+            check("func make<T>(): T {\n}\nfunc main() {\n    make();\n}"),
+            Err(ErrorType::UninferredTypeParameter(..))
+        ));
+    }
+
+    #[test]
+    fn test_generic_structs() {
+        let check = |source: &'static str| {
+            let (result, mut errors) = typecheck(source);
+            match result {
+                Ok(Statement::Root(stmts)) => Ok(stmts),
+                _ => Err(errors.remove(0).error_type),
+            }
+        };
+
+        let stmts = check(
+            "struct Pair<A, B> { a: A, b: B }
+func main() {
+    var p: Pair<int, float> = Pair { a: 1, b: 2.5 };
+    var q = Pair { a: true, b: 1 };
+    var x = p.a;
+}",
+        )
+        .unwrap();
+        let structs: Vec<_> = stmts
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Statement::Struct {
+                    name, attributes, ..
+                } => Some((*name, attributes.clone())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            structs,
+            vec![
+                (
+                    "Pair.int.float",
+                    vec![(AzulaType::Int, "a"), (AzulaType::Float, "b")]
+                ),
+                (
+                    "Pair.bool.int",
+                    vec![(AzulaType::Bool, "a"), (AzulaType::Int, "b")]
+                ),
+            ]
+        );
+
+        assert!(matches!(
+            check(
+                "struct Pair<A, B> { a: A, b: B }
+func main() {
+    var p: Pair<int> = Pair { a: 1, b: 2 };
+}"
+            ),
+            Err(ErrorType::WrongTypeArgumentCount(_, 2, 1))
+        ));
+    }
+
+    #[test]
+    fn test_statement_outside_function() {
+        let check = |source: &'static str| {
+            let (result, mut errors) = typecheck(source);
+            assert!(result.is_err());
+            let error = errors.remove(0);
+            (error.error_type, &source[error.start..error.end])
+        };
+
+        let (error_type, spanned) = check("func main() {}\nreturn 1;");
+        assert!(matches!(
+            error_type,
+            ErrorType::StatementOutsideFunction(kind) if kind == "A return statement"
+        ));
+        assert_eq!(spanned, "return 1;");
+
+        let (error_type, _) = check("printf(\"hi\");");
+        assert!(matches!(
+            error_type,
+            ErrorType::StatementOutsideFunction(kind) if kind == "An expression"
+        ));
+    }
+
+    #[test]
+    fn test_interfaces() {
+        let check = |source: &'static str| {
+            let (result, mut errors) = typecheck(source);
+            match result {
+                Ok(Statement::Root(stmts)) => Ok(stmts),
+                _ => Err(errors.remove(0).error_type),
+            }
+        };
+        const SHAPE: &str = "interface Shape {
+    func area(self): int;
+}
+struct Square { side: int }
+impl Shape for Square {
+    func area(self): int {
+        return self.side * self.side;
+    }
+}
+func double<T: Shape>(shape: T): int {
+    return shape.area() * 2;
+}
+";
+        let with_shape = |main: &str| -> &'static str {
+            Box::leak(format!("{}{}", SHAPE, main).into_boxed_str())
+        };
+
+        let stmts = check(with_shape(
+            "func main() {
+    var square = Square { side: 2 };
+    var a = square.area();
+    var b = double(square);
+}",
+        ))
+        .unwrap();
+        let names: Vec<_> = stmts
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Statement::Function { name, .. } => Some(*name),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(names, vec!["Square.area", "main", "double.Square"]);
+
+        // The method call passes the value as self
+        let main = stmts
+            .iter()
+            .find(|stmt| matches!(stmt, Statement::Function { name, .. } if *name == "main"));
+        match main.unwrap() {
+            Statement::Function { body, .. } => match body.deref() {
+                Statement::Block(body) => match &body[1] {
+                    Statement::Assign(_, _, _, value, _, _) => match &value.expression {
+                        Expression::FunctionCall { function, args } => {
+                            assert_eq!(
+                                function.expression,
+                                Expression::Identifier("Square.area".to_string())
+                            );
+                            assert_eq!(args.len(), 1);
+                            assert_eq!(value.typed, AzulaType::Int);
+                        }
+                        expr => panic!("expected call, got {:?}", expr),
+                    },
+                    stmt => panic!("expected assign, got {:?}", stmt),
+                },
+                stmt => panic!("expected block, got {:?}", stmt),
+            },
+            stmt => panic!("expected function, got {:?}", stmt),
+        }
+
+        assert!(matches!(
+            check(with_shape("func main() { var x = double(1); }")),
+            Err(ErrorType::UnsatisfiedBound(_, interface)) if interface == "Shape"
+        ));
+        assert!(matches!(
+            check("interface Shape { func area(self): int; }
+        impl Shape for int {}"),
+            Err(ErrorType::MissingMethod(_, _, method)) if method == "area"
+        ));
+        assert!(matches!(
+            check(
+                "interface Shape { func area(self): int; }
+impl Shape for int { func area(self): bool { return true; } }"
+            ),
+            Err(ErrorType::MismatchedMethodSignature(..))
+        ));
+        assert!(matches!(
+            check("impl Shape for int {}"),
+            Err(ErrorType::UnknownInterface(_))
+        ));
+    }
+
+    #[test]
+    fn test_interface_values() {
+        let check = |source: &'static str| {
+            let (result, mut errors) = typecheck(source);
+            match result {
+                Ok(Statement::Root(stmts)) => Ok(stmts),
+                _ => Err(errors.remove(0).error_type),
+            }
+        };
+        let with_shape = |main: &str| -> &'static str {
+            Box::leak(
+                format!(
+                    "interface Shape {{
+    func area(self): int;
+    func bigger(self, other: Self): bool;
+}}
+struct Square {{ side: int }}
+impl Shape for Square {{
+    func area(self): int {{
+        return self.side * self.side;
+    }}
+    func bigger(self, other: Square): bool {{
+        return self.side > other.side;
+    }}
+}}
+{}",
+                    main
+                )
+                .into_boxed_str(),
+            )
+        };
+
+        let stmts = check(with_shape(
+            "func main() {
+    var shape: Shape = Square { side: 2 };
+    var a = shape.area();
+}",
+        ))
+        .unwrap();
+        let main = stmts.iter().find_map(|stmt| match stmt {
+            Statement::Function { name, body, .. } if *name == "main" => Some(body),
+            _ => None,
+        });
+        match main.unwrap().deref() {
+            Statement::Block(body) => {
+                match &body[0] {
+                    Statement::Assign(_, _, _, value, _, _) => assert!(matches!(
+                        &value.expression,
+                        Expression::Cast(_, AzulaType::Interface(name)) if name == "Shape"
+                    )),
+                    stmt => panic!("expected assign, got {:?}", stmt),
+                }
+                // The method is a closure looked up on the value at runtime
+                match &body[1] {
+                    Statement::Assign(_, _, _, value, _, _) => match &value.expression {
+                        Expression::FunctionCall { function, .. } => {
+                            assert!(matches!(function.expression, Expression::StructAccess(..)));
+                            assert_eq!(value.typed, AzulaType::Int);
+                        }
+                        expr => panic!("expected call, got {:?}", expr),
+                    },
+                    stmt => panic!("expected assign, got {:?}", stmt),
+                }
+            }
+            stmt => panic!("expected block, got {:?}", stmt),
+        }
+
+        assert!(matches!(
+            check(with_shape("func main() { var shape: Shape = 1; }")),
+            Err(ErrorType::UnsatisfiedBound(_, interface)) if interface == "Shape"
+        ));
+        assert!(matches!(
+            check(with_shape(
+                "func main() { var shape: Shape = Square { side: 1 }; var x = shape.size(); }"
+            )),
+            Err(ErrorType::UnknownMethod(..))
+        ));
+        assert!(matches!(
+            check(with_shape(
+                "func main() { var shape: Shape = Square { side: 1 }; var x = shape.bigger(shape); }"
+            )),
+            Err(ErrorType::NotDynamicMethod(method, _)) if method == "bigger"
+        ));
+    }
+
+    #[test]
+    fn test_imports() {
+        let main = "import math;\nfunc main() {\n    var x = math.square(2);\n    var y = math.cube(x);\n}";
+        let math = "pub func square(x: int): int { return x * x; }";
+        let mut sources = SourceMap::new();
+        sources.add("main.azl".to_string(), main);
+        let lexer = azula_parser::prelude::Lexer::new(main);
+        let mut parser = azula_parser::prelude::Parser::new(main, lexer);
+        let mut ast = parser.parse();
+        let (errors, _) = azula_parser::prelude::resolve_imports(
+            &mut ast,
+            "main",
+            &azula_parser::prelude::Config::for_target("x86_64-unknown-linux-gnu"),
+            &mut sources,
+            &mut |_| Ok(("math.azl".to_string(), math)),
+        );
+        assert!(errors.is_empty());
+
+        let mut typechecker = Typechecker::new(ast);
+        assert!(typechecker.typecheck().is_err());
+        assert_eq!(typechecker.errors.len(), 1);
+        assert!(matches!(
+            &typechecker.errors[0].error_type,
+            ErrorType::UnknownModuleMember(module, name) if module == "math" && name == "cube"
+        ));
+        assert_eq!(
+            &main[typechecker.errors[0].start..typechecker.errors[0].end],
+            "cube"
+        );
+    }
+
+    #[test]
+    fn test_private_functions() {
+        let main =
+            "import math;\nfunc main() {\n    var x = math.square(2);\n    var y = half(x);\n}";
+        let math = "pub func square(x: int): int { return half(x) * x * 2; }
+func half(x: int): int { return x / 2; }";
+        let mut sources = SourceMap::new();
+        sources.add("main.azl".to_string(), main);
+        let lexer = azula_parser::prelude::Lexer::new(main);
+        let mut parser = azula_parser::prelude::Parser::new(main, lexer);
+        let mut ast = parser.parse();
+        azula_parser::prelude::resolve_imports(
+            &mut ast,
+            "main",
+            &azula_parser::prelude::Config::for_target("x86_64-unknown-linux-gnu"),
+            &mut sources,
+            &mut |_| Ok(("math.azl".to_string(), math)),
+        );
+
+        // half can be called from math but not from main
+        let mut typechecker = Typechecker::new(ast);
+        assert!(typechecker.typecheck().is_err());
+        assert_eq!(typechecker.errors.len(), 1);
+        assert!(matches!(
+            &typechecker.errors[0].error_type,
+            ErrorType::PrivateFunction(function, module) if function == "half" && module == "math"
+        ));
+        assert_eq!(
+            &main[typechecker.errors[0].start..typechecker.errors[0].end],
+            "half"
+        );
+    }
+
+    #[test]
+    fn test_private_functions_per_module() {
+        let main = "import a;\nimport b;
+func helper(): int { return 3; }
+func main() { var x = a.one() + b.two() + helper(); }";
+        let mut sources = SourceMap::new();
+        sources.add("main.azl".to_string(), main);
+        let lexer = azula_parser::prelude::Lexer::new(main);
+        let mut parser = azula_parser::prelude::Parser::new(main, lexer);
+        let mut ast = parser.parse();
+        azula_parser::prelude::resolve_imports(
+            &mut ast,
+            "main",
+            &azula_parser::prelude::Config::for_target("x86_64-unknown-linux-gnu"),
+            &mut sources,
+            &mut |name| {
+                let source = match name {
+                    "a" => "func helper(): int { return 1; }\npub func one(): int { return helper(); }",
+                    _ => "func helper(): int { return 2; }\npub func two(): int { return b.helper(); }",
+                };
+                Ok((format!("{}.azl", name), source))
+            },
+        );
+
+        // Each module's private helper is its own, and none clash with main's
+        let mut typechecker = Typechecker::new(ast);
+        let result = typechecker.typecheck();
+        assert!(result.is_ok(), "{:?}", typechecker.errors);
+        let body = match result.unwrap() {
+            Statement::Root(body) => body,
+            stmt => panic!("expected root, got {:?}", stmt),
+        };
+        let calls: HashMap<_, _> = body
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Statement::Function { name, body, .. } => match body.deref() {
+                    Statement::Block(stmts) => match stmts.first() {
+                        Some(Statement::Return(Some(value), _)) => match &value.expression {
+                            Expression::FunctionCall { function, .. } => {
+                                Some((*name, function.expression.clone()))
+                            }
+                            _ => None,
+                        },
+                        _ => None,
+                    },
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            calls["one"],
+            Expression::Identifier("a::helper".to_string())
+        );
+        assert_eq!(
+            calls["two"],
+            Expression::Identifier("b::helper".to_string())
+        );
+        for name in ["a::helper", "b::helper", "helper"] {
+            assert!(
+                body.iter()
+                    .any(|stmt| matches!(stmt, Statement::Function { name: n, .. } if *n == name)),
+                "no function {}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_module_names() {
+        let check = |main: &'static str| {
+            let mut sources = SourceMap::new();
+            sources.add("main.azl".to_string(), main);
+            let lexer = azula_parser::prelude::Lexer::new(main);
+            let mut parser = azula_parser::prelude::Parser::new(main, lexer);
+            let mut ast = parser.parse();
+            let (errors, _) = azula_parser::prelude::resolve_imports(
+                &mut ast,
+                "main",
+                &azula_parser::prelude::Config::for_target("x86_64-unknown-linux-gnu"),
+                &mut sources,
+                &mut |name| {
+                    let source = match name {
+                        "a" => {
+                            "pub func helper(): int { return 1; }\nextern func puts(string): int;"
+                        }
+                        "b" => {
+                            "pub func helper(): int { return 2; }\nextern func puts(string): int;"
+                        }
+                        _ => "struct P { x: int }\nextern func puts(string): int;",
+                    };
+                    Ok((format!("{}.azl", name), source))
+                },
+            );
+            assert!(errors.is_empty(), "{:?}", errors);
+
+            let mut typechecker = Typechecker::new(ast);
+            let result = typechecker.typecheck();
+            (result, typechecker.errors)
+        };
+
+        let (result, errors) = check("import shapes;\nfunc main() { var p = shapes.P { x: 1 }; }");
+        assert!(result.is_ok(), "{:?}", errors);
+
+        // A name is only declared once across the modules, however it's
+        // reached. Externs can be declared by each module using them
+        for (main, owner) in [
+            (
+                "import a;\nimport b;\nfunc main() { var x = a.helper() + b.helper(); }",
+                Some("a"),
+            ),
+            (
+                "import a;\nfunc helper(): int { return 3; }\nfunc main() {}",
+                Some("a"),
+            ),
+            (
+                "func helper(): int { return 3; }\nimport a;\nfunc main() {}",
+                None,
+            ),
+        ] {
+            let (result, errors) = check(main);
+            assert!(result.is_err());
+            assert!(
+                matches!(
+                    &errors[0].error_type,
+                    ErrorType::DuplicateDeclaration(name, module)
+                        if name == "helper" && module.as_deref() == owner
+                ),
+                "{:?}",
+                errors
+            );
+        }
+
+        let (result, errors) = check("func main() { var p = (1 + 2) { x: 1 }; }");
+        assert!(result.is_err());
+        assert!(matches!(
+            errors[0].error_type,
+            ErrorType::InvalidStructLiteral
+        ));
+    }
+
+    #[test]
+    fn test_function_attributes() {
+        let input =
+            "@jumptable @cold\npub func f() {}\n@nojumptable @fast\nfunc g() {}\nfunc main() {}";
+        let lexer = azula_parser::prelude::Lexer::new(input);
+        let mut parser = azula_parser::prelude::Parser::new(input, lexer);
+        let ast = parser.parse();
+        assert!(parser.errors.is_empty());
+        match &ast {
+            Statement::Root(body) => assert!(matches!(
+                &body[1],
+                Statement::Function { attributes, .. }
+                    if attributes.iter().map(|a| a.name).eq(["nojumptable", "fast"])
+            )),
+            _ => unreachable!(),
+        }
+
+        let mut typechecker = Typechecker::new(ast);
+        assert!(typechecker.typecheck().is_err());
+        assert_eq!(typechecker.errors.len(), 1);
+        assert!(matches!(
+            &typechecker.errors[0].error_type,
+            ErrorType::UnknownAttribute(attribute) if attribute == "fast"
+        ));
+
+        let input = "@inline @noinline\nfunc h() {}\nfunc main() {}";
+        let (result, errors) = typecheck(input);
+        assert!(result.is_err());
+        assert!(matches!(
+            &errors[0].error_type,
+            ErrorType::ConflictingAttributes(first, second) if first == "inline" && second == "noinline"
+        ));
+
+        let input = "@interrupt\nfunc tick(): int { return 1; }\nfunc main() {}";
+        let (result, errors) = typecheck(input);
+        assert!(result.is_err());
+        assert!(matches!(
+            &errors[0].error_type,
+            ErrorType::InvalidInterruptHandler(function) if function == "tick"
+        ));
+
+        let input = "@test\nfunc adds(x: int) {}\n@test\nfunc parses(): !void { return error(\"bad\"); }\nfunc main() {}";
+        let (result, errors) = typecheck(input);
+        assert!(result.is_err());
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            &errors[0].error_type,
+            ErrorType::InvalidTest(function) if function == "adds"
+        ));
+
+        // None take arguments, and structs and globals don't have any yet
+        for (input, error) in [
+            ("@cold(1)\nfunc f() {}\nfunc main() {}", "@cold"),
+            ("@packed\nstruct S { x: int }\nfunc main() {}", "packed"),
+            ("@export\nconst LIMIT = 1;\nfunc main() {}", "export"),
+        ] {
+            let (result, errors) = typecheck(input);
+            assert!(result.is_err());
+            assert!(
+                matches!(
+                    &errors[0].error_type,
+                    ErrorType::UnexpectedArguments(name) | ErrorType::UnknownAttribute(name)
+                        if name == error
+                ),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_sizeof() {
+        let (result, _) = typecheck(
+            "struct Point { x: int, y: int }\nfunc main() { var size: int = sizeof(Point) + sizeof([i32; 4]); }",
+        );
+        assert!(result.is_ok());
+
+        let (result, errors) = typecheck("func main() { var size = sizeof(Missing); }");
+        assert!(result.is_err());
+        assert!(matches!(
+            &errors[0].error_type,
+            ErrorType::UnknownStruct(name) if name == "Missing"
+        ));
+    }
+
+    #[test]
+    fn test_deref() {
+        let (result, _) =
+            typecheck("func bump(p: *int) { *p = *p + 1; }\nfunc main() { var x = 1; bump(&x); }");
+        assert!(result.is_ok());
+
+        let (result, errors) = typecheck("func main() { var x = 1; var y = *x; }");
+        assert!(result.is_err());
+        assert!(matches!(
+            &errors[0].error_type,
+            ErrorType::DerefNonPointer(typ) if typ == "Int"
+        ));
+    }
+
+    #[test]
+    fn test_heap_allocation() {
+        let (result, _) = typecheck(
+            "struct Node { value: int }
+func main() {
+    var node: *Node = new Node;
+    node.value = 1;
+    free(node);
+}",
+        );
+        assert!(result.is_ok());
+
+        let (result, errors) = typecheck("func main() { var x = 1; free(x); }");
+        assert!(result.is_err());
+        assert!(matches!(
+            &errors[0].error_type,
+            ErrorType::MismatchedTypes(expected, got) if expected == "pointer" && got == "Int"
+        ));
+    }
+
+    #[test]
+    fn test_len() {
+        let (result, _) =
+            typecheck("func main() { var name = \"azula\"; var n: int = len(name) + 1; }");
+        assert!(result.is_ok());
+
+        let (result, errors) = typecheck("func main() { var n = len(1); }");
+        assert!(result.is_err());
+        assert!(matches!(
+            &errors[0].error_type,
+            ErrorType::MismatchedTypes(expected, _) if expected == "string"
+        ));
+    }
 
-                    expr.typed = left.clone().typed;
-                    Ok((
-                        ExpressionNode {
-                            expression: Expression::Infix(
-                                Rc::new(left),
-                                operator.clone(),
-                                Rc::new(right),
-                            ),
-                            typed: left_typ.clone().into(),
-                            span: expr.span,
-                        },
-                        left_typ,
-                    ))
-                }
-                Operator::Or
-                | Operator::And
-                | Operator::Eq
-                | Operator::Neq
-                | Operator::Lt
-                | Operator::Lte
-                | Operator::Gt
-                | Operator::Gte => {
-                    expr.typed = AzulaType::Bool;
-                    Ok((
-                        ExpressionNode {
-                            expression: Expression::Infix(
-                                Rc::new(left),
-                                operator.clone(),
-                                Rc::new(right),
-                            ),
-                            typed: AzulaType::Bool,
-                            span: expr.span,
-                        },
-                        AzulaType::Bool,
-                    ))
-                }
-            }
-        } else {
-            unreachable!()
+    #[test]
+    fn test_implicit_return() {
+        let (result, _) = typecheck("func square(x: int): int { x * x }\nfunc main() {}");
+        match result.unwrap() {
+            Statement::Root(body) => assert!(matches!(
+                &body[0],
+                Statement::Function { body, .. }
+                    if matches!(body.deref(), Statement::Block(stmts)
+                        if matches!(stmts[..], [Statement::Return(Some(_), _)]))
+            )),
+            stmt => panic!("expected root, got {:?}", stmt),
         }
+
+        let (result, errors) = typecheck("func f(): int { true }\nfunc main() {}");
+        assert!(result.is_err());
+        assert!(matches!(
+            &errors[0].error_type,
+            ErrorType::IncorrectFunctionReturn(wanted, got) if wanted == "Int" && got == "Bool"
+        ));
     }
+
+    #[test]
+    fn test_volatile() {
+        let (result, _) = typecheck(
+            "func poll(status: *u32) {
+    volatile_store(status, 1 as u32);
+    var ready: u32 = volatile_load(status);
 }
+func main() {}",
+        );
+        assert!(result.is_ok());
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use azula_ast::prelude::Span;
-    use std::rc::Rc;
+        let (result, errors) =
+            typecheck("func poll(status: *u32) { volatile_store(status, true); }\nfunc main() {}");
+        assert!(result.is_err());
+        assert!(matches!(
+            &errors[0].error_type,
+            ErrorType::MismatchedTypes(expected, got) if expected == "SizedUnsignedInt(32)" && got == "Bool"
+        ));
+    }
 
     #[test]
-    fn test_function() {
-        let root = Statement::Root(vec![Statement::Function {
-            name: "main",
-            args: vec![(AzulaType::Int, "x")],
-            returns: AzulaType::Bool,
-            body: Rc::new(Statement::Block(vec![])),
-            span: Span { start: 0, end: 1 },
-        }]);
+    fn test_missing_return() {
+        let (result, errors) = typecheck(
+            "func sign(x: int): int {\n    if x < 0 { return 0 - 1; }\n}\nfunc main() {}",
+        );
+        assert!(result.is_err());
+        assert!(matches!(
+            &errors[0].error_type,
+            ErrorType::MissingReturn(function) if function == "sign"
+        ));
+        assert_eq!((errors[0].start, errors[0].end), (56, 57));
 
-        let mut typechecker = Typechecker::new(root);
-        typechecker.typecheck().unwrap();
+        let (result, _) = typecheck(
+            "func tag(x: any): int {
+    match x {
+        int => { return 1; }
+        _ => { return 0; }
+    }
+}
+func spin(): int { while true {} }
+func main() {}",
+        );
+        assert!(result.is_ok());
     }
 
     #[test]
-    fn test_assign() {
-        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+    fn test_match_arms() {
+        let input = "func main() {
+    var x: any = 1;
+    match x {
+        int i => {}
+        int => {}
+        _ => {}
+        bool b => {}
+    }
+}";
+        let lexer = azula_parser::prelude::Lexer::new(input);
+        let mut typechecker =
+            Typechecker::new(azula_parser::prelude::Parser::new(input, lexer).parse());
+        assert!(typechecker.typecheck().is_ok(), "{:?}", typechecker.errors);
+        let unreachable: Vec<_> = typechecker
+            .warnings
+            .iter()
+            .filter(|warning| matches!(warning.warning_type, WarningType::UnreachableArm))
+            .map(|warning| &input[warning.start..warning.end])
+            .collect();
+        assert_eq!(unreachable, vec!["int", "bool b"]);
 
-        let mut environment = Environment::new();
-        typechecker.typecheck_assign(
-            Statement::Assign(
-                true,
-                "test".to_string(),
-                None,
-                ExpressionNode {
-                    expression: Expression::Integer(5),
-                    typed: AzulaType::Infer,
-                    span: Span { start: 0, end: 1 },
-                },
-                Span { start: 0, end: 1 },
-            ),
-            &mut environment,
+        // A type without a tag is pointed out in its own arm
+        let input = "func main() {\n    var x: any = 1;\n    match x {\n        int => {}\n        u8 small => {}\n    }\n}";
+        let (result, errors) = typecheck(input);
+        assert!(result.is_err());
+        assert!(matches!(
+            &errors[0].error_type,
+            ErrorType::NotConvertibleToAny(_)
+        ));
+        assert_eq!(&input[errors[0].start..errors[0].end], "u8 small");
+    }
+
+    #[test]
+    fn test_byte_order() {
+        let (result, _) = typecheck(
+            "func read(p: *u32): u32 { from_be(unaligned_load(p)) }
+func write(p: *u32, x: u32) { unaligned_store(p, to_le(x)); }
+func main() {}",
         );
+        assert!(result.is_ok());
 
-        let var = environment
-            .variable_definitions
-            .get(&"test".to_string())
-            .unwrap();
-        assert_eq!(var.name, "test");
-        assert_eq!(var.typ, AzulaType::Int);
+        let (result, errors) = typecheck("func main() { var x = to_be(1.5); }");
+        assert!(result.is_err());
+        assert!(matches!(
+            &errors[0].error_type,
+            ErrorType::MismatchedTypes(expected, got) if expected == "integer" && got == "Float"
+        ));
     }
 
     #[test]
-    fn test_return() {
-        // Return value
-        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+    fn test_do_while() {
+        let (result, _) = typecheck(
+            "func first(): int {
+    do {
+        var x = 1;
+        return x;
+    } while false;
+}
+func main() {
+    var i = 0;
+    do { i = i + 1; } while i < 10;
+}",
+        );
+        assert!(result.is_ok());
 
-        let mut environment = Environment::new();
-        let (_, typ) = typechecker
-            .typecheck_return(
-                Statement::Return(
-                    Some(ExpressionNode {
-                        expression: Expression::Integer(5),
-                        typed: AzulaType::Infer,
-                        span: Span { start: 0, end: 1 },
-                    }),
-                    Span { start: 0, end: 1 },
-                ),
-                &mut environment,
-            )
-            .unwrap();
-        assert_eq!(typ, AzulaType::Int);
+        let (result, errors) = typecheck("func main() { do {} while 1; }");
+        assert!(result.is_err());
+        assert!(matches!(
+            &errors[0].error_type,
+            ErrorType::NonBoolCondition(..)
+        ));
+    }
 
-        // Return none
-        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
+    #[test]
+    fn test_loop_control() {
+        let (result, _) = typecheck(
+            "func find(): int {
+    outer: while true {
+        while true {
+            if false { continue outer; }
+            return 1;
+        }
+    }
+}
+func main() {}",
+        );
+        assert!(result.is_ok());
 
-        let mut environment = Environment::new();
-        let (_, typ) = typechecker
-            .typecheck_return(
-                Statement::Return(None, Span { start: 0, end: 1 }),
-                &mut environment,
-            )
-            .unwrap();
-        assert_eq!(typ, AzulaType::Void);
+        // Breaking out of the outer loop means the function can end
+        let (result, errors) = typecheck(
+            "func find(): int {
+    outer: while true {
+        while true { break outer; }
+    }
+}
+func main() {}",
+        );
+        assert!(result.is_err());
+        assert!(matches!(
+            &errors[0].error_type,
+            ErrorType::MissingReturn(..)
+        ));
+
+        let (result, errors) = typecheck("func main() { while true { break missing; } }");
+        assert!(result.is_err());
+        assert!(matches!(
+            &errors[0].error_type,
+            ErrorType::UnknownLoopLabel(label) if label == "missing"
+        ));
+
+        let (result, errors) = typecheck("func main() { continue; }");
+        assert!(result.is_err());
+        assert!(matches!(
+            &errors[0].error_type,
+            ErrorType::LoopControlOutsideLoop(keyword) if keyword == "continue"
+        ));
     }
 
     #[test]
-    fn test_integer_expression() {
-        let integer_node = ExpressionNode {
-            expression: Expression::Integer(5),
-            typed: AzulaType::Infer,
-            span: Span { start: 0, end: 0 },
+    fn test_range() {
+        let (result, _) = typecheck(
+            "func width(r: Range<int>): int { r.end - r.start }
+func main() {
+    var r = 2..10;
+    width(r);
+}",
+        );
+        let body = match result.unwrap() {
+            Statement::Root(body) => body,
+            stmt => panic!("expected root, got {:?}", stmt),
         };
+        // The instance is declared once, ahead of everything using it
+        assert!(matches!(
+            &body[0],
+            Statement::Struct { attributes, .. } if attributes.len() == 2
+        ));
+        assert_eq!(
+            body.iter()
+                .filter(|stmt| matches!(stmt, Statement::Struct { .. }))
+                .count(),
+            1
+        );
 
-        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
-        let mut environment = Environment::new();
-        let (expr, typ) = typechecker
-            .typecheck_expression(integer_node, &environment)
-            .unwrap();
-
-        assert_eq!(typ, AzulaType::Int);
-        assert_eq!(expr.typed, AzulaType::Int);
+        let (result, errors) = typecheck("func main() { var r = 1..2.5; }");
+        assert!(result.is_err());
+        assert!(matches!(&errors[0].error_type, ErrorType::InvalidRange(..)));
     }
 
     #[test]
-    fn test_identifier_expression() {
-        // Integer
-        let identifier_node = ExpressionNode {
-            expression: Expression::Identifier("test".to_string()),
-            typed: AzulaType::Infer,
-            span: Span { start: 0, end: 0 },
+    fn test_slices() {
+        let (result, errors) = typecheck(
+            "func first(xs: []int): int { xs[0] }
+func main() {
+    var a = [1, 2, 3, 4];
+    var s = a[1..3];
+    first(s);
+    first(s[1..2]);
+    first(a);
+}",
+        );
+        assert!(result.is_ok(), "{:?}", errors);
+        let body = match result.unwrap() {
+            Statement::Root(body) => body,
+            stmt => panic!("expected root, got {:?}", stmt),
         };
-
-        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
-        let mut environment = Environment::new();
-        environment.add_variable(
-            "test".to_string(),
-            VariableDefinition {
-                name: "test".to_string(),
-                mutable: true,
-                typ: AzulaType::Int,
+        // Indexing goes through the slice's elements
+        let first = body.iter().find_map(|stmt| match stmt {
+            Statement::Function {
+                name: "first",
+                body,
+                ..
+            } => match body.deref() {
+                Statement::Block(stmts) => Some(stmts),
+                _ => None,
             },
+            _ => None,
+        });
+        assert!(matches!(
+            &first.unwrap()[0],
+            Statement::Return(Some(ExpressionNode {
+                expression: Expression::ArrayAccess(data, _),
+                ..
+            }), _) if matches!(data.expression, Expression::StructAccess(..))
+        ));
+
+        let (result, errors) = typecheck("func main() { var a = [1, 2]; var s = a[0..1.5]; }");
+        assert!(result.is_err());
+        assert!(matches!(&errors[0].error_type, ErrorType::NonIntIndex(..)));
+
+        let (result, errors) = typecheck("func main() { var x = 5; var s = x[0..1]; }");
+        assert!(result.is_err());
+        assert!(matches!(
+            &errors[0].error_type,
+            ErrorType::NonArrayInIndex(..)
+        ));
+    }
+
+    #[test]
+    fn test_nested_arrays() {
+        let (result, errors) = typecheck(
+            "func trace(m: [[int; 3]; 3]): int { m[0][0] + m[1][1] + m[2][2] }
+func main() {
+    var m: [[int; 3]; 3] = [[1, 0, 0], [0, 1, 0], [0, 0, 1]];
+    m[1][2] = 7;
+    m[0] = m[2];
+    trace(m);
+}",
         );
-        let (expr, typ) = typechecker
-            .typecheck_expression(identifier_node, &environment)
-            .unwrap();
+        assert!(result.is_ok(), "{:?}", errors);
 
-        assert_eq!(typ, AzulaType::Int);
-        assert_eq!(expr.typed, AzulaType::Int);
+        // Every row has to be the same length
+        let (_, errors) = typecheck("func main() { var m = [[1, 2], [3]]; }");
+        assert!(matches!(
+            &errors[0].error_type,
+            ErrorType::MismatchedTypes(..)
+        ));
+    }
 
-        // Pointer
-        let identifier_node = ExpressionNode {
-            expression: Expression::Identifier("test".to_string()),
-            typed: AzulaType::Infer,
-            span: Span { start: 0, end: 0 },
+    #[test]
+    fn test_declaration_order() {
+        let input = "func main() {
+    var even = is_even(limit);
+    var origin = Point { x: 0, y: 0 };
+}
+func is_even(n: int): bool { if n == 0 { return true; } return is_odd(n - 1); }
+func is_odd(n: int): bool { if n == 0 { return false; } return is_even(n - 1); }
+var limit = 10;
+struct Point { x: int, y: int }";
+        let (result, errors) = typecheck(input);
+        assert!(result.is_ok(), "{:?}", errors);
+
+        // Everything stays where it was written
+        let names: Vec<_> = match result.unwrap() {
+            Statement::Root(body) => body
+                .iter()
+                .map(|stmt| match stmt {
+                    Statement::Function { name, .. } | Statement::Struct { name, .. } => {
+                        name.to_string()
+                    }
+                    Statement::Assign(_, name, ..) => name.clone(),
+                    stmt => panic!("unexpected {:?}", stmt),
+                })
+                .collect(),
+            stmt => panic!("expected root, got {:?}", stmt),
         };
+        assert_eq!(names, vec!["main", "is_even", "is_odd", "limit", "Point"]);
+    }
 
-        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
-        let mut environment = Environment::new();
-        environment.add_variable(
-            "test".to_string(),
-            VariableDefinition {
-                typ: AzulaType::Pointer(Rc::new(AzulaType::Str)),
-                name: "test".to_string(),
-                mutable: true,
-            },
+    #[test]
+    fn test_const_evaluation() {
+        // Folded to literals, whatever order they're written in
+        let (result, errors) = typecheck(
+            "const AREA = WIDTH * HEIGHT;
+const WIDTH = 4;
+const HEIGHT = WIDTH - 1;
+const BIG = AREA > 10 && !false;
+const GREETING = \"hello, \" + \"world\";
+const HALF = 1.0 / 2.0;
+func main() { var grid: [int; AREA] = [0; WIDTH * HEIGHT]; }",
+        );
+        assert!(result.is_ok(), "{:?}", errors);
+        let values: Vec<_> = match result.unwrap() {
+            Statement::Root(body) => body
+                .into_iter()
+                .filter_map(|stmt| match stmt {
+                    Statement::Assign(false, name, _, value, _, _) => {
+                        Some((name, value.expression))
+                    }
+                    _ => None,
+                })
+                .collect(),
+            stmt => panic!("expected root, got {:?}", stmt),
+        };
+        assert_eq!(
+            values,
+            vec![
+                ("AREA".to_string(), Expression::Integer(12)),
+                ("WIDTH".to_string(), Expression::Integer(4)),
+                ("HEIGHT".to_string(), Expression::Integer(3)),
+                ("BIG".to_string(), Expression::Boolean(true)),
+                (
+                    "GREETING".to_string(),
+                    Expression::String("hello, world".to_string())
+                ),
+                ("HALF".to_string(), Expression::Float(0.5)),
+            ]
+        );
+
+        // Sizes in functions too
+        let (result, errors) = typecheck(
+            "const SIZE = 2 + 1;
+func main() { var xs: [int; SIZE] = [1; SIZE]; var ys: [int; 3] = xs; }",
+        );
+        assert!(result.is_ok(), "{:?}", errors);
+
+        // Each problem is reported once, where it is
+        let (result, errors) = typecheck(
+            "const A = B + 1;
+const B = 1 / 0;
+func main() {}",
+        );
+        assert!(result.is_err());
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(errors[0].error_type, ErrorType::DivisionByZero));
+
+        let (_, errors) = typecheck("const A = B; const B = A; func main() {}");
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(&errors[0].error_type, ErrorType::ConstantCycle(name) if name == "A"));
+
+        let (_, errors) = typecheck("const A = 9223372036854775807 + 1; func main() {}");
+        assert!(matches!(errors[0].error_type, ErrorType::ConstantOverflow));
+
+        let (_, errors) = typecheck("const A = 1 + \"one\"; func main() {}");
+        assert!(matches!(
+            errors[0].error_type,
+            ErrorType::MismatchedTypes(..)
+        ));
+
+        let (_, errors) = typecheck("func main() { var n = 3; var xs = [0; n]; }");
+        assert!(matches!(
+            errors[0].error_type,
+            ErrorType::NonLiteralConstant
+        ));
+
+        let (_, errors) =
+            typecheck("const NAME = \"x\"; var xs: [int; NAME] = [0; 1]; func main() {}");
+        assert!(matches!(
+            &errors[0].error_type,
+            ErrorType::NonConstantArraySize(size) if size == "\"x\""
+        ));
+    }
+
+    #[test]
+    fn test_increment() {
+        // The 1 takes the type of what's incremented
+        let (result, errors) = typecheck(
+            "func main() {
+    var small: u8 = 0 as u8;
+    small++;
+    var ratio = 0.5;
+    ratio--;
+    var counts = [0, 0];
+    counts[1]++;
+}",
+        );
+        assert!(result.is_ok(), "{:?}", errors);
+
+        let (_, errors) = typecheck("func main() { const x = 1; x++; }");
+        assert!(matches!(errors[0].error_type, ErrorType::ConstantAssign));
+
+        let (_, errors) = typecheck("func main() { var done = false; done++; }");
+        assert!(matches!(
+            errors[0].error_type,
+            ErrorType::NonOperatorType(..)
+        ));
+    }
+
+    #[test]
+    fn test_power() {
+        let (result, errors) = typecheck(
+            "const KIB = 2 ** 10;
+const EIGHTH = 0.5 ** 3;
+func main() {
+    var ints: int = 3 ** 4;
+    var small: u8 = (2 as u8) ** 7;
+    var cubed: float = 1.5 ** 3;
+    var halved: f32 = (2.0 as f32) ** 0.5;
+}",
+        );
+        assert!(result.is_ok(), "{:?}", errors);
+        let constants: Vec<_> = match result.unwrap() {
+            Statement::Root(body) => body
+                .into_iter()
+                .filter_map(|stmt| match stmt {
+                    Statement::Assign(_, _, _, value, _, _) => Some(value.expression),
+                    _ => None,
+                })
+                .collect(),
+            stmt => panic!("expected root, got {:?}", stmt),
+        };
+        assert_eq!(
+            constants,
+            vec![Expression::Integer(1024), Expression::Float(0.125)]
         );
-        let (expr, typ) = typechecker
-            .typecheck_expression(identifier_node, &environment)
-            .unwrap();
 
-        assert_eq!(typ, AzulaType::Pointer(Rc::new(AzulaType::Str)));
-        assert_eq!(expr.typed, AzulaType::Pointer(Rc::new(AzulaType::Str)));
+        // An integer can't be raised to a fractional power
+        let (_, errors) = typecheck("func main() { var x = 2 ** 0.5; }");
+        assert!(matches!(
+            errors[0].error_type,
+            ErrorType::NoNumericPromotion(..)
+        ));
+
+        let (_, errors) = typecheck("func main() { var x = true ** 2; }");
+        assert!(matches!(
+            errors[0].error_type,
+            ErrorType::NonOperatorType(..)
+        ));
     }
 
     #[test]
-    fn test_infix_expression() {
-        // Int
-        let infix_node = ExpressionNode {
-            expression: Expression::Infix(
-                Rc::new(ExpressionNode {
-                    expression: Expression::Integer(5),
-                    typed: AzulaType::Infer,
-                    span: Span { start: 0, end: 0 },
-                }),
-                Operator::Add,
-                Rc::new(ExpressionNode {
-                    expression: Expression::Integer(20),
-                    typed: AzulaType::Infer,
-                    span: Span { start: 0, end: 0 },
-                }),
-            ),
-            typed: AzulaType::Infer,
-            span: Span { start: 0, end: 0 },
-        };
+    fn test_division_by_zero() {
+        let (result, errors) = typecheck(
+            "func main() {
+    var x: float = 1.0 / 0.0;
+    var y: int = 7 / 2;
+    var z: u8 = (7 as u8) % (2 as u8);
+}",
+        );
+        assert!(result.is_ok(), "{:?}", errors);
 
-        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
-        let mut environment = Environment::new();
-        let (expr, typ) = typechecker
-            .typecheck_expression(infix_node, &environment)
-            .unwrap();
+        let (_, errors) = typecheck("func main() { var x = 7 / 0; }");
+        assert!(matches!(errors[0].error_type, ErrorType::DivisionByZero));
 
-        assert_eq!(typ, AzulaType::Int);
-        assert_eq!(expr.typed, AzulaType::Int);
+        let (_, errors) = typecheck("func main() { var x = 1; var y = x % 0; }");
+        assert!(matches!(errors[0].error_type, ErrorType::DivisionByZero));
 
-        // Non operator type
-        let infix_node = ExpressionNode {
-            expression: Expression::Infix(
-                Rc::new(ExpressionNode {
-                    expression: Expression::Integer(5),
-                    typed: AzulaType::Infer,
-                    span: Span { start: 0, end: 0 },
-                }),
-                Operator::Add,
-                Rc::new(ExpressionNode {
-                    expression: Expression::Identifier("test".to_string()),
-                    typed: AzulaType::Infer,
-                    span: Span { start: 0, end: 0 },
-                }),
-            ),
-            typed: AzulaType::Infer,
-            span: Span { start: 0, end: 0 },
-        };
+        let (_, errors) = typecheck("const X = 1 / 0;");
+        assert!(matches!(errors[0].error_type, ErrorType::DivisionByZero));
+    }
 
-        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
-        let mut environment = Environment::new();
-        environment.add_variable(
-            "test".to_string(),
-            VariableDefinition {
-                name: "test".to_string(),
-                typ: AzulaType::Bool,
-                mutable: true,
-            },
+    #[test]
+    fn test_negate() {
+        let (result, errors) = typecheck(
+            "const LOW = -7;
+func main() {
+    var b = -7 / 2;
+    var c = LOW % 2;
+    var f: float = -2.5;
+    var n: i8 = 3 as i8;
+    var m = -n;
+}",
         );
-        typechecker.typecheck_expression(infix_node, &environment);
+        assert!(result.is_ok(), "{:?}", errors);
 
-        assert_eq!(typechecker.errors.len(), 1);
+        let (_, errors) = typecheck("func main() { var x = 3 as u8; var y = -x; }");
+        assert!(
+            matches!(&errors[0].error_type, ErrorType::NonOperatorType(typ, _) if typ == "SizedUnsignedInt(8)"),
+            "{:?}",
+            errors
+        );
+
+        let (_, errors) = typecheck("func main() { var x = -true; }");
         assert!(matches!(
-            typechecker.errors[0].error_type,
+            errors[0].error_type,
             ErrorType::NonOperatorType(..)
         ));
     }
 
     #[test]
-    fn test_array_expression() {
-        // Int
-        let array = ExpressionNode {
-            expression: Expression::Array(vec![
-                ExpressionNode {
-                    expression: Expression::Integer(1),
-                    typed: AzulaType::Int,
-                    span: Span { start: 0, end: 1 },
-                },
-                ExpressionNode {
-                    expression: Expression::Integer(2),
-                    typed: AzulaType::Int,
-                    span: Span { start: 0, end: 1 },
+    fn test_struct_initialisation() {
+        // The values end up in the order the fields are declared
+        let (result, errors) = typecheck(
+            "struct Point { x: int, y: float }
+func main() {
+    var p = Point { y: 2.0, x: 1 };
+}",
+        );
+        assert!(result.is_ok(), "{:?}", errors);
+        let value = match result.unwrap() {
+            Statement::Root(body) => match &body[1] {
+                Statement::Function { body, .. } => match body.deref() {
+                    Statement::Block(stmts) => match &stmts[0] {
+                        Statement::Assign(_, _, _, value, _, _) => value.expression.clone(),
+                        stmt => panic!("expected assign, got {:?}", stmt),
+                    },
+                    stmt => panic!("expected block, got {:?}", stmt),
                 },
-            ]),
-            typed: AzulaType::Infer,
-            span: Span { start: 0, end: 0 },
+                stmt => panic!("expected function, got {:?}", stmt),
+            },
+            stmt => panic!("expected root, got {:?}", stmt),
         };
+        match value {
+            Expression::StructInitialisation(_, values) => assert_eq!(
+                values
+                    .iter()
+                    .map(|(field, value)| (*field, value.expression.clone()))
+                    .collect::<Vec<_>>(),
+                vec![("x", Expression::Integer(1)), ("y", Expression::Float(2.0))]
+            ),
+            expr => panic!("expected struct initialisation, got {:?}", expr),
+        }
 
-        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
-        let mut environment = Environment::new();
-        let (expr, typ) = typechecker
-            .typecheck_expression(array, &environment)
-            .unwrap();
+        let (_, errors) = typecheck(
+            "struct Point { x: int, y: int }
+func main() { var p = Point { x: 1 }; }",
+        );
+        assert!(matches!(
+            &errors[0].error_type,
+            ErrorType::MissingField(struc, field) if struc == "Point" && field == "y"
+        ));
 
-        assert_eq!(typ, AzulaType::Array(Rc::new(AzulaType::Int), Some(2)));
-        assert_eq!(
-            expr.typed,
-            AzulaType::Array(Rc::new(AzulaType::Int), Some(2))
+        let (_, errors) = typecheck(
+            "struct Point { x: int, y: int }
+func main() { var p = Point { x: 1, y: 2, x: 3 }; }",
         );
+        assert!(matches!(
+            errors[0].error_type,
+            ErrorType::DuplicateField(..)
+        ));
 
-        // Different types
-        let array = ExpressionNode {
-            expression: Expression::Array(vec![
-                ExpressionNode {
-                    expression: Expression::Integer(1),
-                    typed: AzulaType::Int,
-                    span: Span { start: 0, end: 1 },
-                },
-                ExpressionNode {
-                    expression: Expression::Boolean(false),
-                    typed: AzulaType::Bool,
-                    span: Span { start: 0, end: 1 },
+        let (_, errors) = typecheck(
+            "struct Point { x: int, y: int }
+func main() { var p = Point { x: 1, y: 2, z: 3 }; }",
+        );
+        assert!(matches!(
+            errors[0].error_type,
+            ErrorType::UnknownStructMember(..)
+        ));
+
+        let (_, errors) = typecheck(
+            "struct Point { x: int, y: int }
+func main() { var p = Point { x: 1, y: true }; }",
+        );
+        assert!(matches!(
+            errors[0].error_type,
+            ErrorType::MismatchedFieldType(..)
+        ));
+
+        let (_, errors) = typecheck("func main() { var p = Missing { x: 1 }; }");
+        assert!(matches!(errors[0].error_type, ErrorType::UnknownStruct(..)));
+    }
+
+    #[test]
+    fn test_struct_defaults() {
+        let (result, errors) = typecheck(
+            "struct Config { retries: int = 3, verbose: bool, scale: f32 = 1.5 }
+func main() {
+    var c = Config { verbose: true };
+}",
+        );
+        assert!(result.is_ok(), "{:?}", errors);
+        let value = match result.unwrap() {
+            Statement::Root(body) => match &body[1] {
+                Statement::Function { body, .. } => match body.deref() {
+                    Statement::Block(stmts) => match &stmts[0] {
+                        Statement::Assign(_, _, _, value, _, _) => value.expression.clone(),
+                        stmt => panic!("expected assign, got {:?}", stmt),
+                    },
+                    stmt => panic!("expected block, got {:?}", stmt),
                 },
-            ]),
-            typed: AzulaType::Infer,
-            span: Span { start: 0, end: 0 },
+                stmt => panic!("expected function, got {:?}", stmt),
+            },
+            stmt => panic!("expected root, got {:?}", stmt),
         };
+        match value {
+            Expression::StructInitialisation(_, values) => {
+                let values: Vec<_> = values
+                    .iter()
+                    .map(|(field, value)| (*field, value.typed.clone()))
+                    .collect();
+                assert_eq!(
+                    values,
+                    vec![
+                        ("retries", AzulaType::Int),
+                        ("verbose", AzulaType::Bool),
+                        ("scale", AzulaType::SizedFloat(32))
+                    ]
+                );
+            }
+            expr => panic!("expected struct initialisation, got {:?}", expr),
+        }
 
-        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
-        let mut environment = Environment::new();
-        let (expr, typ) = typechecker
-            .typecheck_expression(array, &environment)
-            .unwrap();
-
-        assert_eq!(typechecker.errors.len(), 1);
+        // A default has to be of its attribute's type
+        let (_, errors) = typecheck("struct Config { retries: int = true }");
         assert!(matches!(
-            typechecker.errors[0].error_type,
+            errors[0].error_type,
             ErrorType::MismatchedTypes(..)
         ));
     }
 
     #[test]
-    fn test_struct_access_expression() {
-        // Int
-        let array = ExpressionNode {
-            expression: Expression::StructAccess(
-                Rc::new(ExpressionNode {
-                    expression: Expression::Identifier("x".to_string()),
-                    typed: AzulaType::Infer,
-                    span: Span { start: 0, end: 1 },
-                }),
-                Rc::new(ExpressionNode {
-                    expression: Expression::Identifier("test".to_string()),
-                    typed: AzulaType::Infer,
-                    span: Span { start: 0, end: 1 },
-                }),
-            ),
-            typed: AzulaType::Infer,
-            span: Span { start: 0, end: 0 },
+    fn test_struct_field_assign() {
+        // Through a var or a pointer, a field can be written
+        let (result, errors) = typecheck(
+            "struct Point { x: int, y: int }
+func nudge(p: &Point) { p.x = p.x + 1; }
+func main() {
+    var p = Point { x: 1, y: 2 };
+    p.y = 3;
+    nudge(&p);
+}",
+        );
+        assert!(result.is_ok(), "{:?}", errors);
+
+        // A parameter is the function's own copy, which can't be changed
+        let (_, errors) = typecheck(
+            "struct Point { x: int, y: int }
+func nudge(p: Point) { p.x = 2; }",
+        );
+        assert!(matches!(errors[0].error_type, ErrorType::ConstantAssign));
+
+        let (_, errors) = typecheck(
+            "struct Point { x: int, y: int }
+func main() {
+    const p = Point { x: 1, y: 2 };
+    p.x = 2;
+}",
+        );
+        assert!(matches!(errors[0].error_type, ErrorType::ConstantAssign));
+    }
+
+    #[test]
+    fn test_drop() {
+        // The defers dropping the locals of main's body and its if or block
+        let drops = |input: &'static str| -> Vec<usize> {
+            let body = match typecheck(input).0.unwrap() {
+                Statement::Root(stmts) => match stmts.last() {
+                    Some(Statement::Function { body, .. }) => body.deref().clone(),
+                    _ => unreachable!(),
+                },
+                _ => unreachable!(),
+            };
+            let count = |stmts: &[Statement]| {
+                stmts
+                    .iter()
+                    .filter(|stmt| matches!(stmt, Statement::Defer(..)))
+                    .count()
+            };
+            match body {
+                Statement::Block(stmts) => {
+                    let nested = stmts.iter().find_map(|stmt| match stmt {
+                        Statement::If(_, body, _) | Statement::Scope(body, _) => Some(count(body)),
+                        _ => None,
+                    });
+                    vec![count(&stmts), nested.unwrap_or(0)]
+                }
+                _ => unreachable!(),
+            }
+        };
+        const BUFFER: &str = "struct Buffer { size: int }
+impl Drop for Buffer {
+    func drop(self) {}
+}
+";
+        let with_buffer = |main: &str| -> &'static str {
+            Box::leak(format!("{}{}", BUFFER, main).into_boxed_str())
         };
 
-        let mut typechecker = Typechecker::new(Statement::Root(vec![]));
-        typechecker.structs.insert(
-            "Test".to_string(),
-            StructDefinition {
-                name: "Test",
-                attrs: vec![(AzulaType::Int, "test")],
-            },
+        assert_eq!(
+            drops(with_buffer(
+                "func main() {
+    var a = Buffer { size: 1 };
+    if true {
+        var b = Buffer { size: 2 };
+    }
+}"
+            )),
+            vec![1, 1]
         );
-        let mut environment = Environment::new();
-        environment.add_variable(
-            "x".to_string(),
-            VariableDefinition {
-                name: "x".to_string(),
-                mutable: false,
-                typ: AzulaType::Named("Test".to_string()),
+        assert_eq!(
+            drops(with_buffer(
+                "func main() {
+    {
+        var b = Buffer { size: 2 };
+    }
+    var a = Buffer { size: 1 };
+}"
+            )),
+            vec![1, 1]
+        );
+        // Copies aren't dropped, and a returned local is only left out by
+        // the return giving it back
+        assert_eq!(
+            drops(with_buffer(
+                "func main(): Buffer {
+    var a = Buffer { size: 1 };
+    var b = a;
+    return a;
+}"
+            )),
+            vec![1, 0]
+        );
+
+        // A local dropped at the end of a body can't be used after it
+        for body in [
+            "if true { var b = Buffer { size: 2 }; }",
+            "while false { var b = Buffer { size: 2 }; }",
+            "do { var b = Buffer { size: 2 }; } while false;",
+        ] {
+            let input = with_buffer(Box::leak(
+                format!("func main() {{\n    {}\n    var size = b.size;\n}}", body)
+                    .into_boxed_str(),
+            ));
+            let (result, errors) = typecheck(input);
+            assert!(result.is_err(), "{}", body);
+            assert!(matches!(
+                &errors[0].error_type,
+                ErrorType::UnknownVariable(name) if name == "b"
+            ));
+        }
+    }
+
+    #[test]
+    fn test_typeof() {
+        let input = "struct Pair<A, B> { a: A, b: B }
+func main() {
+    var x = 1;
+    var copy: typeof(x) = x;
+    var size = sizeof(typeof(copy));
+    var pair = Pair { a: 1, b: true };
+    var names = [type_name(copy * 2), type_name(&pair), type_name(size > 0)];
+}";
+        let root = typecheck(input).0.unwrap();
+
+        let body = match root {
+            Statement::Root(stmts) => match stmts.last() {
+                Some(Statement::Function { body, .. }) => match body.deref().clone() {
+                    Statement::Block(body) => body,
+                    _ => unreachable!(),
+                },
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+        assert!(matches!(
+            &body[1],
+            Statement::Assign(_, _, Some(AzulaType::Int), ..)
+        ));
+        let names = match &body[4] {
+            Statement::Assign(_, _, _, value, ..) => match &value.expression {
+                Expression::Array(names) => names
+                    .iter()
+                    .map(|name| match &name.expression {
+                        Expression::String(name) => name.clone(),
+                        _ => unreachable!(),
+                    })
+                    .collect::<Vec<_>>(),
+                _ => unreachable!(),
             },
+            _ => unreachable!(),
+        };
+        assert_eq!(names, vec!["int", "&Pair<int, bool>", "bool"]);
+
+        for (input, error) in [
+            (
+                "func main() { var y: typeof(x) = 1; }",
+                ErrorType::UnknownVariable("x".to_string()),
+            ),
+            (
+                "func main() { var y = type_name(1, 2); }",
+                ErrorType::InvalidTypeName,
+            ),
+        ] {
+            let (result, errors) = typecheck(input);
+            assert!(result.is_err());
+            assert_eq!(
+                format!("{:?}", errors[0].error_type),
+                format!("{:?}", error),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_for() {
+        let input = "func main() {
+    var xs = [1, 2, 3];
+    var total = 0;
+    for x in xs { total = total + x; }
+    for i in 0..3 { total = total + i; }
+    for flag in xs[0..2] { total = total + flag; }
+    for i, x in xs { total = total + i * x; }
+}";
+        let lexer = azula_parser::prelude::Lexer::new(input);
+        let mut typechecker =
+            Typechecker::new(azula_parser::prelude::Parser::new(input, lexer).parse());
+        assert!(typechecker.typecheck().is_ok(), "{:?}", typechecker.errors);
+        assert!(typechecker.warnings.is_empty());
+
+        // Arrays without a size can't be looped over, there's no telling
+        // where they end
+        for (input, error) in [
+            "func main() { for x in 1 {} }",
+            "func f(xs: [int]) { for x in xs {} }\nfunc main() {}",
+            "struct P { a: int }\nfunc main() { var p = P { a: 1 }; for x in p {} }",
+        ]
+        .iter()
+        .zip(["Int", "Array(Int, None)", "\"P\""])
+        {
+            let (result, errors) = typecheck(input);
+            assert!(result.is_err());
+            assert!(
+                matches!(
+                    &errors[0].error_type,
+                    ErrorType::NotIterable(typ) if typ.contains(error)
+                ),
+                "{:?}",
+                errors
+            );
+        }
+
+        // The loop's variables are gone once it ends
+        for input in [
+            "func main() { for x in 0..3 {} var y = x; }",
+            "func main() { for i, x in [1, 2] {} var y = i; }",
+        ] {
+            let (result, errors) = typecheck(input);
+            assert!(result.is_err());
+            assert!(
+                matches!(&errors[0].error_type, ErrorType::UnknownVariable(_)),
+                "{:?}",
+                errors
+            );
+        }
+    }
+
+    #[test]
+    fn test_scope() {
+        let input = "func main() {
+    var total = 0;
+    {
+        var step = 2;
+        total = total + step;
+    }
+    {
+        var step = 3;
+        total = total * step;
+    }
+}";
+        let lexer = azula_parser::prelude::Lexer::new(input);
+        let mut typechecker =
+            Typechecker::new(azula_parser::prelude::Parser::new(input, lexer).parse());
+        assert!(typechecker.typecheck().is_ok(), "{:?}", typechecker.errors);
+        assert!(typechecker.warnings.is_empty());
+
+        // The variables declared inside are gone once it ends
+        let input = "func main() {
+    { var step = 2; }
+    var total = step;
+}";
+        let (result, errors) = typecheck(input);
+        assert!(result.is_err());
+        assert!(
+            matches!(
+                &errors[0].error_type,
+                ErrorType::UnknownVariable(name) if name == "step"
+            ),
+            "{:?}",
+            errors
         );
-        let (expr, typ) = typechecker
-            .typecheck_expression(array, &environment)
-            .unwrap();
 
-        assert_eq!(typ, AzulaType::Int);
-        assert_eq!(expr.typed, AzulaType::Int);
+        // Every body shadows, so the outer x is still an int after each
+        for body in [
+            "{ var x = true; }",
+            "if true { var x = true; }",
+            "while false { var x = true; }",
+            "do { var x = true; } while false;",
+            "for x in [true, false] {}",
+            "for i in 0..2 { var x = true; }",
+        ] {
+            let input = format!(
+                "func main() {{\n    var x = 1;\n    {}\n    var y = x + 1;\n}}",
+                body
+            );
+            let (result, errors) = typecheck(&input);
+            assert!(result.is_ok(), "{}: {:?}", body, errors);
+        }
     }
 }